@@ -0,0 +1,85 @@
+//! Instantiate a compiled payout template of 20 receivers, compared with
+//! building (and validating) a fresh `CapacityTransferBuilder` for every
+//! request.
+//!
+//! ```text
+//! cargo bench --bench tx_template
+//! ```
+
+use std::str::FromStr;
+
+use ckb_sdk::{
+    tx_builder::{
+        template::{SlotValue, TemplateParams, TxTemplate},
+        transfer::CapacityTransferBuilder,
+        udt::TokenRegistry,
+    },
+    Address, NetworkType,
+};
+use ckb_types::{
+    bytes::Bytes,
+    core::Capacity,
+    packed::{CellOutput, Script},
+    prelude::*,
+};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+const RECEIVERS: usize = 20;
+
+const PAYER: &str = "ckt1qzda0cr08m85hc8jlnfp3zer7xulejywt49kt2rr0vthywaa50xwsq2qf8keemy2p5uu0g0gn8cd4ju23s5269qk8rg4r";
+const RECEIVER: &str = "ckt1qzda0cr08m85hc8jlnfp3zer7xulejywt49kt2rr0vthywaa50xwsqv5dsed9par23x4g58seaw58j3ym5ml2hs8ztche";
+
+fn receiver_capacity(idx: usize) -> u64 {
+    (100 + idx as u64) * 100_000_000
+}
+
+/// What a caller does without a template: parse the addresses, build the
+/// scripts and check the capacities of every output again.
+fn build_fresh() -> CapacityTransferBuilder {
+    let payer = Address::from_str(PAYER).unwrap();
+    let _fee_payer = Script::from(&payer);
+    let mut outputs = Vec::with_capacity(RECEIVERS);
+    for idx in 0..RECEIVERS {
+        let receiver = Address::from_str(RECEIVER).unwrap();
+        let output = CellOutput::new_builder()
+            .lock(Script::from(&receiver))
+            .capacity(receiver_capacity(idx).pack())
+            .build();
+        let occupied = output.occupied_capacity(Capacity::zero()).unwrap();
+        assert!(occupied.as_u64() <= receiver_capacity(idx));
+        outputs.push((output, Bytes::default()));
+    }
+    CapacityTransferBuilder::new(outputs)
+}
+
+fn bench_tx_template(c: &mut Criterion) {
+    let registry = TokenRegistry::new();
+    let compiled = TxTemplate::payout()
+        .compile(NetworkType::Testnet, &registry)
+        .unwrap();
+    let receiver = Address::from_str(RECEIVER).unwrap();
+    let mut params = TemplateParams::new();
+    for idx in 0..RECEIVERS {
+        params.push(
+            "receivers",
+            SlotValue::Capacity {
+                receiver: receiver.clone(),
+                capacity: receiver_capacity(idx),
+            },
+        );
+    }
+    params.push(
+        "payer",
+        SlotValue::FeePayer(Address::from_str(PAYER).unwrap()),
+    );
+
+    let mut group = c.benchmark_group("payout_20_receivers");
+    group.bench_function("compiled_template", |b| {
+        b.iter(|| black_box(compiled.instantiate(&params).unwrap()))
+    });
+    group.bench_function("fresh_builder", |b| b.iter(|| black_box(build_fresh())));
+    group.finish();
+}
+
+criterion_group!(benches, bench_tx_template);
+criterion_main!(benches);
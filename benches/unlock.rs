@@ -0,0 +1,84 @@
+//! Unlock a transaction spending the cells of 200 sighash accounts, one by
+//! one by `unlock_tx` and concurrently by `unlock_tx_parallel`.
+//!
+//! ```text
+//! cargo bench --features parallel,test-util --bench unlock
+//! ```
+
+use std::collections::HashMap;
+
+use ckb_jsonrpc_types as json_types;
+use ckb_sdk::{
+    constants::{ONE_CKB, SIGHASH_TYPE_HASH},
+    test_util::{Context, TestAccount},
+    traits::{CellDepResolver, SecpCkbRawKeySigner},
+    tx_builder::{unlock_tx, unlock_tx_parallel},
+    unlock::{ScriptUnlocker, SecpSighashUnlocker},
+    ScriptId,
+};
+use ckb_types::{
+    bytes::Bytes,
+    core::{BlockView, TransactionBuilder, TransactionView},
+    packed::{CellInput, CellOutput, WitnessArgs},
+    prelude::*,
+};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+const GENESIS_JSON: &str = include_str!("../src/test-data/genesis_block.json");
+const GROUPS: usize = 200;
+
+fn build_unlockers(accounts: &[TestAccount]) -> HashMap<ScriptId, Box<dyn ScriptUnlocker>> {
+    let keys = accounts.iter().map(|account| account.secret_key).collect();
+    let signer = SecpCkbRawKeySigner::new_with_secret_keys(keys);
+    let mut unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>> = HashMap::default();
+    unlockers.insert(
+        ScriptId::new_type(SIGHASH_TYPE_HASH),
+        Box::new(SecpSighashUnlocker::from(Box::new(signer) as Box<_>)),
+    );
+    unlockers
+}
+
+fn build_sweep_tx(accounts: &[TestAccount]) -> (Context, TransactionView) {
+    let genesis_block: json_types::BlockView = serde_json::from_str(GENESIS_JSON).unwrap();
+    let genesis_block: BlockView = genesis_block.into();
+    let mut ctx = Context::new(&genesis_block, Vec::new());
+    let inputs: Vec<CellInput> = accounts
+        .iter()
+        .flat_map(|account| ctx.fund(&account.lock_script, &[100 * ONE_CKB]))
+        .map(|out_point| CellInput::new(out_point, 0))
+        .collect();
+    let output = CellOutput::new_builder()
+        .capacity((accounts.len() as u64 * 100 * ONE_CKB - ONE_CKB).pack())
+        .lock(accounts[0].lock_script.clone())
+        .build();
+    let placeholder_witness = WitnessArgs::new_builder()
+        .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+        .build();
+    let tx = TransactionBuilder::default()
+        .inputs(inputs)
+        .output(output)
+        .output_data(Bytes::new().pack())
+        .cell_dep(ctx.resolve(&accounts[0].lock_script).unwrap())
+        .set_witnesses(vec![placeholder_witness.as_bytes().pack(); accounts.len()])
+        .build();
+    (ctx, tx)
+}
+
+fn bench_unlock(c: &mut Criterion) {
+    let accounts: Vec<TestAccount> = (0..GROUPS).map(|_| TestAccount::random()).collect();
+    let (ctx, tx) = build_sweep_tx(&accounts);
+    let unlockers = build_unlockers(&accounts);
+
+    let mut group = c.benchmark_group("unlock_200_groups");
+    group.sample_size(10);
+    group.bench_function("unlock_tx", |b| {
+        b.iter(|| unlock_tx(tx.clone(), &ctx, &unlockers).unwrap())
+    });
+    group.bench_function("unlock_tx_parallel", |b| {
+        b.iter(|| unlock_tx_parallel(tx.clone(), &ctx, || build_unlockers(&accounts)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_unlock);
+criterion_main!(benches);
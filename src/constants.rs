@@ -48,6 +48,21 @@ pub const ACP_TYPE_HASH_LINA: H256 =
 pub const ACP_TYPE_HASH_AGGRON: H256 =
     h256!("0x3419a1c09eb2567f6552ee7a8ecffd64155cffe0f1796e6e61ec088d740c1356");
 
+/// pw-lock script mainnet code hash, see:
+/// <https://github.com/lay2dev/pw-lock>
+pub const PW_LOCK_TYPE_HASH_LINA: H256 =
+    h256!("0xbf43c3602455798c1a61a596e0d95278864c552fafe231c063b3fabf97a8febc");
+/// pw-lock script testnet code hash
+pub const PW_LOCK_TYPE_HASH_AGGRON: H256 =
+    h256!("0x58c5f491aba6d61678b7cf7edf4910b1f5e00ec0cde2f42e0abb4fd9aff25a63");
+
+/// The genesis block hash of the mainnet (Lina)
+pub const MAINNET_GENESIS_HASH: H256 =
+    h256!("0x92b197aa1fba0f63633922c61c92375c9c074a93e85963554f5499fe1450d0e5");
+/// The genesis block hash of the testnet (Aggron4)
+pub const TESTNET_GENESIS_HASH: H256 =
+    h256!("0x10639e0895502b5688a6be8cf69460d76541bfa4821629d86d62ba0aae3f9606");
+
 /// cheque withdraw since value
 pub const CHEQUE_CELL_SINCE: u64 = 0xA000000000000006;
 
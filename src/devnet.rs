@@ -0,0 +1,336 @@
+//! Helpers to prepare accounts and cells on a devnet, e.g. for load testing.
+//!
+//! * `generate_accounts` derives the keys from a seed, so the accounts are the
+//!   same in every run.
+//! * `FundingPlanBuilder` pays `cells_per_account` cells to every account from
+//!   one funder in batched transactions, each batch spends the change of the
+//!   previous one.
+//! * `wait_funded` polls until the funding cells are visible to the indexer.
+//! * `AlwaysSuccessUnlocker` spends the cells of an always-success lock.
+use std::thread;
+use std::time::{Duration, Instant};
+
+use ckb_hash::new_blake2b;
+use ckb_types::{
+    bytes::Bytes,
+    core::{Capacity, TransactionView},
+    packed::{Byte32, CellOutput, OutPoint, Script},
+    prelude::*,
+};
+use thiserror::Error;
+
+use crate::traits::{
+    CellCollector, CellCollectorError, CellDepResolver, CellQueryOptions, HeaderDepResolver,
+    NodeTxLimits, TransactionDependencyError, TransactionDependencyProvider,
+};
+use crate::tx_builder::{
+    batch::{BatchTransferBuilder, TransferItem},
+    CapacityBalancer, TxBuilder, TxBuilderError,
+};
+use crate::unlock::{ScriptUnlocker, UnlockError, UnlockerProvider};
+use crate::{Address, AddressPayload, NetworkType, ScriptGroup, ScriptId};
+
+/// The serialized size reserved for the inputs, witnesses, cell deps and the
+/// change output of a funding transaction.
+pub const DEFAULT_RESERVED_TX_SIZE: u64 = 2048;
+
+#[derive(Error, Debug)]
+pub enum DevnetError {
+    #[error("invalid funding plan: `{0}`")]
+    InvalidPlan(String),
+
+    #[error("funding transaction `{0}` is not fully unlocked")]
+    NotUnlocked(usize),
+
+    #[error("the accounts are not funded before timeout: `{0}` accounts left")]
+    Timeout(usize),
+
+    #[error(transparent)]
+    TxBuilder(#[from] TxBuilderError),
+
+    #[error(transparent)]
+    CellCollector(#[from] CellCollectorError),
+}
+
+/// Generate `n` secp256k1 sighash accounts of the dev chain, the key of the
+/// i-th account is derived from `seed` and `i`, the same seed always produces
+/// the same accounts.
+pub fn generate_accounts(n: usize, seed: &[u8]) -> Vec<(secp256k1::SecretKey, Address)> {
+    (0..n as u64)
+        .map(|index| {
+            let key = derive_secret_key(seed, index);
+            let pubkey = secp256k1::PublicKey::from_secret_key(&crate::SECP256K1, &key);
+            let payload = AddressPayload::from_pubkey(&pubkey);
+            (key, Address::new(NetworkType::Dev, payload, true))
+        })
+        .collect()
+}
+
+fn derive_secret_key(seed: &[u8], index: u64) -> secp256k1::SecretKey {
+    // Retry with a counter in the (negligible) case the hash is not a valid key
+    for counter in 0u32.. {
+        let mut hasher = new_blake2b();
+        hasher.update(seed);
+        hasher.update(&index.to_le_bytes());
+        hasher.update(&counter.to_le_bytes());
+        let mut hash = [0u8; 32];
+        hasher.finalize(&mut hash);
+        if let Ok(key) = secp256k1::SecretKey::from_slice(&hash) {
+            return key;
+        }
+    }
+    unreachable!("no valid secret key")
+}
+
+/// Fund `cells_per_account` cells of `capacity` shannons to every account.
+///
+/// The funding outputs are partitioned into batches so that each transaction
+/// is under `limits.max_tx_size` (and `max_outputs_per_tx` if set), every
+/// batch is built by `BatchTransferBuilder`.
+pub struct FundingPlanBuilder {
+    /// The lock script of the funder, it's the capacity provider of the
+    /// balancer given to `build_transactions`.
+    pub funder: Script,
+    pub accounts: Vec<Script>,
+    pub cells_per_account: usize,
+    /// The capacity of every funding cell
+    pub capacity: u64,
+    pub limits: NodeTxLimits,
+    pub max_outputs_per_tx: Option<usize>,
+    /// The size reserved for everything other than the funding outputs
+    pub reserved_tx_size: u64,
+}
+
+impl FundingPlanBuilder {
+    pub fn new(
+        funder: Script,
+        accounts: &[Address],
+        cells_per_account: usize,
+        capacity: u64,
+    ) -> FundingPlanBuilder {
+        FundingPlanBuilder {
+            funder,
+            accounts: accounts.iter().map(Script::from).collect(),
+            cells_per_account,
+            capacity,
+            limits: NodeTxLimits::default(),
+            max_outputs_per_tx: None,
+            reserved_tx_size: DEFAULT_RESERVED_TX_SIZE,
+        }
+    }
+
+    /// Partition the funding outputs into batches, one transaction each.
+    pub fn plan(&self) -> Result<Vec<Vec<TransferItem>>, DevnetError> {
+        let mut max_outputs = usize::MAX;
+        let mut items = Vec::with_capacity(self.accounts.len() * self.cells_per_account);
+        for lock in &self.accounts {
+            let output = CellOutput::new_builder()
+                .lock(lock.clone())
+                .capacity(self.capacity.pack())
+                .build();
+            let occupied = output
+                .occupied_capacity(Capacity::zero())
+                .expect("occupied capacity")
+                .as_u64();
+            if occupied > self.capacity {
+                return Err(DevnetError::InvalidPlan(format!(
+                    "the capacity {} is less than the occupied capacity {}",
+                    self.capacity, occupied
+                )));
+            }
+            // the output, the empty output data and their offsets
+            let output_size = output.as_slice().len() as u64 + 12;
+            let available = self
+                .limits
+                .max_tx_size
+                .saturating_sub(self.reserved_tx_size);
+            max_outputs = max_outputs.min((available / output_size) as usize);
+            for _ in 0..self.cells_per_account {
+                items.push(TransferItem::new_ckb(lock.clone(), self.capacity));
+            }
+        }
+        if let Some(max_outputs_per_tx) = self.max_outputs_per_tx {
+            max_outputs = max_outputs.min(max_outputs_per_tx);
+        }
+        if max_outputs == 0 {
+            return Err(DevnetError::InvalidPlan(format!(
+                "no funding output fits in the max transaction size {}",
+                self.limits.max_tx_size
+            )));
+        }
+        Ok(items
+            .chunks(max_outputs)
+            .map(|chunk| chunk.to_vec())
+            .collect())
+    }
+
+    /// Build and unlock the funding transactions in order, every transaction
+    /// is applied to `cell_collector` (with `tip_block_number`) before building
+    /// the next one, so the change cell of a batch funds the next batch.
+    ///
+    /// The transactions must be sent in the returned order.
+    #[allow(clippy::too_many_arguments)]
+    pub fn build_transactions(
+        &self,
+        cell_collector: &mut dyn CellCollector,
+        cell_dep_resolver: &dyn CellDepResolver,
+        header_dep_resolver: &dyn HeaderDepResolver,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
+        balancer: &CapacityBalancer,
+        unlockers: &dyn UnlockerProvider,
+        tip_block_number: u64,
+    ) -> Result<Vec<TransactionView>, DevnetError> {
+        let mut pending = PendingTxDepProvider {
+            inner: tx_dep_provider,
+            txs: Vec::new(),
+        };
+        for (idx, items) in self.plan()?.into_iter().enumerate() {
+            let builder = BatchTransferBuilder::new(self.funder.clone(), items);
+            let (tx, not_unlocked) = builder.build_unlocked(
+                cell_collector,
+                cell_dep_resolver,
+                header_dep_resolver,
+                &pending,
+                balancer,
+                unlockers,
+            )?;
+            if !not_unlocked.is_empty() {
+                return Err(DevnetError::NotUnlocked(idx));
+            }
+            cell_collector.apply_tx(tx.data(), tip_block_number)?;
+            pending.txs.push(tx);
+        }
+        Ok(pending.txs)
+    }
+}
+
+/// Resolve the outputs of the built but not yet committed transactions.
+struct PendingTxDepProvider<'a> {
+    inner: &'a dyn TransactionDependencyProvider,
+    txs: Vec<TransactionView>,
+}
+
+impl<'a> PendingTxDepProvider<'a> {
+    fn get_output(&self, out_point: &OutPoint) -> Option<(CellOutput, Bytes)> {
+        let tx = self
+            .txs
+            .iter()
+            .find(|tx| tx.hash() == out_point.tx_hash())?;
+        let index: u32 = out_point.index().unpack();
+        tx.output_with_data(index as usize)
+    }
+}
+
+impl<'a> TransactionDependencyProvider for PendingTxDepProvider<'a> {
+    fn get_transaction(
+        &self,
+        tx_hash: &Byte32,
+    ) -> Result<TransactionView, TransactionDependencyError> {
+        match self.txs.iter().find(|tx| &tx.hash() == tx_hash) {
+            Some(tx) => Ok(tx.clone()),
+            None => self.inner.get_transaction(tx_hash),
+        }
+    }
+    fn get_cell(&self, out_point: &OutPoint) -> Result<CellOutput, TransactionDependencyError> {
+        match self.get_output(out_point) {
+            Some((output, _)) => Ok(output),
+            None => self.inner.get_cell(out_point),
+        }
+    }
+    fn get_cell_data(&self, out_point: &OutPoint) -> Result<Bytes, TransactionDependencyError> {
+        match self.get_output(out_point) {
+            Some((_, data)) => Ok(data),
+            None => self.inner.get_cell_data(out_point),
+        }
+    }
+    fn get_header(
+        &self,
+        block_hash: &Byte32,
+    ) -> Result<ckb_types::core::HeaderView, TransactionDependencyError> {
+        self.inner.get_header(block_hash)
+    }
+    fn get_block_extension(
+        &self,
+        block_hash: &Byte32,
+    ) -> Result<Option<ckb_types::packed::Bytes>, TransactionDependencyError> {
+        self.inner.get_block_extension(block_hash)
+    }
+}
+
+/// Poll `cell_collector` every `interval` until every account has at least
+/// `expected_cells` live cells, or `timeout` is reached.
+///
+/// The collector is reset before every round, so don't share it with a
+/// transaction builder.
+pub fn wait_funded(
+    cell_collector: &mut dyn CellCollector,
+    accounts: &[Address],
+    expected_cells: usize,
+    timeout: Duration,
+    interval: Duration,
+) -> Result<(), DevnetError> {
+    let started_at = Instant::now();
+    let mut pending: Vec<Script> = accounts.iter().map(Script::from).collect();
+    loop {
+        cell_collector.reset();
+        let mut unfunded = Vec::new();
+        for lock in pending {
+            let mut query = CellQueryOptions::new_lock(lock.clone());
+            query.min_total_capacity = u64::MAX;
+            let (cells, _) = cell_collector.collect_live_cells(&query, false)?;
+            if cells.len() < expected_cells {
+                unfunded.push(lock);
+            }
+        }
+        if unfunded.is_empty() {
+            return Ok(());
+        }
+        if started_at.elapsed() >= timeout {
+            return Err(DevnetError::Timeout(unfunded.len()));
+        }
+        pending = unfunded;
+        thread::sleep(interval);
+    }
+}
+
+/// The unlocker of an always-success lock, the lock script accepts any
+/// witness so the transaction is left as it is.
+///
+/// Anyone can spend the cells of an always-success lock, it's only meant for
+/// devnets and tests. Register it with the `ScriptId` of the always-success
+/// binary deployed on the devnet.
+#[derive(Default, Clone)]
+pub struct AlwaysSuccessUnlocker {}
+
+impl ScriptUnlocker for AlwaysSuccessUnlocker {
+    fn match_args(&self, _args: &[u8]) -> bool {
+        true
+    }
+
+    fn is_unlocked(
+        &self,
+        _tx: &TransactionView,
+        _script_group: &ScriptGroup,
+        _tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<bool, UnlockError> {
+        Ok(true)
+    }
+
+    fn unlock(
+        &self,
+        tx: &TransactionView,
+        _script_group: &ScriptGroup,
+        _tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, UnlockError> {
+        Ok(tx.clone())
+    }
+
+    fn fill_placeholder_witness(
+        &self,
+        tx: &TransactionView,
+        _script_group: &ScriptGroup,
+        _tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, UnlockError> {
+        Ok(tx.clone())
+    }
+}
@@ -1,15 +1,21 @@
 pub mod constants;
 pub mod core;
+#[cfg(feature = "devnet")]
+pub mod devnet;
+pub mod metrics;
+pub mod mock_tx;
+pub mod payment_uri;
 pub mod pubsub;
 pub mod rpc;
 pub mod traits;
 pub mod transaction;
 pub mod tx_builder;
+pub mod tx_checker;
 pub mod types;
 pub mod unlock;
 pub mod util;
 
-#[cfg(feature = "test")]
+#[cfg(feature = "test-util")]
 pub mod test_util;
 
 #[cfg(feature = "test")]
@@ -18,9 +24,9 @@ mod tests;
 
 pub use rpc::{CkbRpcClient, IndexerRpcClient, RpcError};
 pub use types::{
-    Address, AddressPayload, AddressType, CodeHashIndex, HumanCapacity, NetworkInfo, NetworkType,
-    OldAddress, OldAddressFormat, ScriptGroup, ScriptGroupType, ScriptId, Since, SinceType,
-    TransactionWithScriptGroups,
+    Address, AddressPayload, AddressType, ChainParams, CodeHashIndex, HumanCapacity, NetworkInfo,
+    NetworkType, OldAddress, OldAddressFormat, ScriptGroup, ScriptGroupType, ScriptId, Since,
+    SinceParseError, SinceType, TransactionWithScriptGroups,
 };
 
 pub use ckb_crypto::secp::SECP256K1;
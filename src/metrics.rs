@@ -0,0 +1,292 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+/// Counter, labels: `method`, `status` (`ok` or `error`)
+pub const RPC_REQUESTS: &str = "ckb_sdk_rpc_requests";
+/// Histogram in seconds, labels: `method`
+pub const RPC_LATENCY_SECONDS: &str = "ckb_sdk_rpc_latency_seconds";
+/// Counter, labels: `cache` (`transaction`, `cell` or `header`)
+pub const CACHE_HITS: &str = "ckb_sdk_cache_hits";
+/// Counter, labels: `cache` (`transaction`, `cell` or `header`)
+pub const CACHE_MISSES: &str = "ckb_sdk_cache_misses";
+/// Histogram, the number of indexer pages fetched by one `collect_live_cells` call
+pub const INDEXER_PAGES: &str = "ckb_sdk_indexer_pages";
+
+/// A backend to report the runtime metrics into.
+///
+/// The clients and providers only report when a sink is set, so there is no
+/// cost when it is not used.
+pub trait MetricsSink: Send + Sync {
+    fn increment_counter(&self, name: &str, labels: &[(&str, &str)], value: u64);
+    fn set_gauge(&self, name: &str, labels: &[(&str, &str)], value: f64);
+    fn record_histogram(&self, name: &str, labels: &[(&str, &str)], value: f64);
+}
+
+/// The metric key used by `InMemoryMetrics`: `name{label1=value1,label2=value2}`,
+/// the labels are in the given order. Just `name` if there is no label.
+pub fn metric_key(name: &str, labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return name.to_string();
+    }
+    let labels = labels
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join(",");
+    format!("{}{{{}}}", name, labels)
+}
+
+/// The aggregate of the values recorded into a histogram.
+///
+/// The values are counted into power-of-two buckets, so the memory used does
+/// not grow with the number of recorded values.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HistogramSummary {
+    pub count: u64,
+    pub sum: f64,
+    pub min: f64,
+    pub max: f64,
+    /// `exponent => count`, a value `v` is counted in the smallest `exponent`
+    /// with `v <= 2^exponent`, the values `<= 0` are counted in `i32::MIN`.
+    pub buckets: BTreeMap<i32, u64>,
+}
+
+impl HistogramSummary {
+    /// Record a value, `NaN` is ignored.
+    pub fn record(&mut self, value: f64) {
+        if value.is_nan() {
+            return;
+        }
+        if self.count == 0 {
+            self.min = value;
+            self.max = value;
+        } else {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+        self.count += 1;
+        self.sum += value;
+        let exponent = if value <= 0.0 {
+            i32::MIN
+        } else {
+            value.log2().ceil() as i32
+        };
+        *self.buckets.entry(exponent).or_default() += 1;
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(self.sum / self.count as f64)
+        }
+    }
+
+    /// The nearest-rank percentile, `percentile` is in `[0, 100]`.
+    ///
+    /// It is approximated by the upper bound of the bucket holding the rank,
+    /// clamped to `[min, max]`.
+    pub fn percentile(&self, percentile: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = (percentile.max(0.0).min(100.0) / 100.0 * self.count as f64).ceil() as u64;
+        let rank = rank.max(1);
+        let mut seen = 0u64;
+        for (exponent, count) in &self.buckets {
+            seen += *count;
+            if seen >= rank {
+                let upper = if *exponent == i32::MIN {
+                    0.0
+                } else {
+                    2f64.powi(*exponent)
+                };
+                return Some(upper.max(self.min).min(self.max));
+            }
+        }
+        Some(self.max)
+    }
+}
+
+/// A copy of all the metrics in `InMemoryMetrics`, by `metric_key`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetricsSnapshot {
+    pub counters: HashMap<String, u64>,
+    pub gauges: HashMap<String, f64>,
+    pub histograms: HashMap<String, HistogramSummary>,
+}
+
+impl MetricsSnapshot {
+    pub fn counter(&self, name: &str, labels: &[(&str, &str)]) -> u64 {
+        self.counters
+            .get(&metric_key(name, labels))
+            .cloned()
+            .unwrap_or(0)
+    }
+
+    pub fn gauge(&self, name: &str, labels: &[(&str, &str)]) -> Option<f64> {
+        self.gauges.get(&metric_key(name, labels)).cloned()
+    }
+
+    pub fn histogram(&self, name: &str, labels: &[(&str, &str)]) -> Option<&HistogramSummary> {
+        self.histograms.get(&metric_key(name, labels))
+    }
+
+    /// See `HistogramSummary::percentile`
+    pub fn percentile(&self, name: &str, labels: &[(&str, &str)], percentile: f64) -> Option<f64> {
+        self.histogram(name, labels)?.percentile(percentile)
+    }
+
+    /// The hit ratio of a cache, `None` if the cache is never accessed.
+    pub fn cache_hit_ratio(&self, cache: &str) -> Option<f64> {
+        let hits = self.counter(CACHE_HITS, &[("cache", cache)]);
+        let misses = self.counter(CACHE_MISSES, &[("cache", cache)]);
+        if hits + misses == 0 {
+            None
+        } else {
+            Some(hits as f64 / (hits + misses) as f64)
+        }
+    }
+}
+
+/// Keep all the metrics in memory, mainly for tests and debugging.
+#[derive(Default)]
+pub struct InMemoryMetrics {
+    inner: Mutex<MetricsSnapshot>,
+}
+
+impl InMemoryMetrics {
+    pub fn new() -> InMemoryMetrics {
+        InMemoryMetrics::default()
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        self.inner.lock().clone()
+    }
+
+    pub fn reset(&self) {
+        *self.inner.lock() = MetricsSnapshot::default();
+    }
+}
+
+impl MetricsSink for InMemoryMetrics {
+    fn increment_counter(&self, name: &str, labels: &[(&str, &str)], value: u64) {
+        let mut inner = self.inner.lock();
+        let counter = inner.counters.entry(metric_key(name, labels)).or_default();
+        *counter = counter.saturating_add(value);
+    }
+
+    fn set_gauge(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.inner
+            .lock()
+            .gauges
+            .insert(metric_key(name, labels), value);
+    }
+
+    fn record_histogram(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.inner
+            .lock()
+            .histograms
+            .entry(metric_key(name, labels))
+            .or_default()
+            .record(value);
+    }
+}
+
+impl<T: MetricsSink + ?Sized> MetricsSink for Arc<T> {
+    fn increment_counter(&self, name: &str, labels: &[(&str, &str)], value: u64) {
+        self.as_ref().increment_counter(name, labels, value)
+    }
+    fn set_gauge(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.as_ref().set_gauge(name, labels, value)
+    }
+    fn record_histogram(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.as_ref().record_histogram(name, labels, value)
+    }
+}
+
+/// Report into the global recorder of the [`metrics`](https://docs.rs/metrics) crate.
+#[cfg(feature = "metrics-facade")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FacadeMetrics;
+
+#[cfg(feature = "metrics-facade")]
+impl FacadeMetrics {
+    fn labels(labels: &[(&str, &str)]) -> Vec<::metrics::Label> {
+        labels
+            .iter()
+            .map(|(key, value)| ::metrics::Label::new(key.to_string(), value.to_string()))
+            .collect()
+    }
+}
+
+#[cfg(feature = "metrics-facade")]
+impl MetricsSink for FacadeMetrics {
+    fn increment_counter(&self, name: &str, labels: &[(&str, &str)], value: u64) {
+        ::metrics::counter!(name.to_string(), Self::labels(labels)).increment(value);
+    }
+    fn set_gauge(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        ::metrics::gauge!(name.to_string(), Self::labels(labels)).set(value);
+    }
+    fn record_histogram(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        ::metrics::histogram!(name.to_string(), Self::labels(labels)).record(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_in_memory_metrics() {
+        let metrics = InMemoryMetrics::new();
+        metrics.increment_counter(CACHE_HITS, &[("cache", "cell")], 2);
+        metrics.increment_counter(CACHE_HITS, &[("cache", "cell")], 1);
+        metrics.increment_counter(CACHE_MISSES, &[("cache", "cell")], 1);
+        metrics.set_gauge("size", &[], 3.0);
+        for value in 1..=100 {
+            metrics.record_histogram(
+                RPC_LATENCY_SECONDS,
+                &[("method", "get_tip_header")],
+                value as f64,
+            );
+        }
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.counter(CACHE_HITS, &[("cache", "cell")]), 3);
+        assert_eq!(snapshot.counter(CACHE_HITS, &[("cache", "header")]), 0);
+        assert_eq!(snapshot.cache_hit_ratio("cell"), Some(0.75));
+        assert_eq!(snapshot.cache_hit_ratio("header"), None);
+        assert_eq!(snapshot.gauge("size", &[]), Some(3.0));
+        let labels = [("method", "get_tip_header")];
+        let histogram = snapshot.histogram(RPC_LATENCY_SECONDS, &labels).unwrap();
+        assert_eq!(histogram.count, 100);
+        assert_eq!(histogram.sum, 5050.0);
+        assert_eq!(histogram.min, 1.0);
+        assert_eq!(histogram.max, 100.0);
+        assert_eq!(histogram.mean(), Some(50.5));
+        // 1, 2, 3..=4, 5..=8, .., 33..=64, 65..=100
+        assert_eq!(histogram.buckets.len(), 8);
+        assert_eq!(
+            snapshot.percentile(RPC_LATENCY_SECONDS, &labels, 1.0),
+            Some(1.0)
+        );
+        assert_eq!(
+            snapshot.percentile(RPC_LATENCY_SECONDS, &labels, 50.0),
+            Some(64.0)
+        );
+        assert_eq!(
+            snapshot.percentile(RPC_LATENCY_SECONDS, &labels, 99.0),
+            Some(100.0)
+        );
+        assert_eq!(snapshot.percentile(RPC_LATENCY_SECONDS, &[], 50.0), None);
+        assert!(snapshot
+            .counters
+            .contains_key("ckb_sdk_cache_hits{cache=cell}"));
+
+        metrics.reset();
+        assert_eq!(metrics.snapshot(), MetricsSnapshot::default());
+    }
+}
@@ -0,0 +1,317 @@
+//! Export a transaction with all the cells and headers it depends on as a
+//! mock transaction, the format `ckb-debugger` reads, and load one back to
+//! build or check a transaction offline.
+use std::collections::{HashMap, HashSet};
+
+use ckb_mock_tx_types::{MockCellDep, MockInfo, MockInput, MockTransaction};
+use ckb_types::{
+    bytes::Bytes,
+    core::{DepType, HeaderView, TransactionBuilder, TransactionView},
+    packed::{self, Byte32, CellOutput, OutPoint, Transaction},
+    prelude::*,
+    H256,
+};
+use thiserror::Error;
+
+use crate::traits::{
+    CellCollector, CellCollectorError, CellQueryOptions, HeaderDepResolver, LiveCell,
+    TransactionDependencyError, TransactionDependencyProvider, PENDING_BLOCK_NUMBER,
+};
+use crate::util::expand_dep_group;
+
+pub use ckb_mock_tx_types::ReprMockTransaction;
+
+#[derive(Error, Debug)]
+pub enum MockTxError {
+    #[error("transaction dependency error: `{0}`")]
+    TxDep(#[from] TransactionDependencyError),
+
+    #[error("header dependency resolver error: `{0}`")]
+    HeaderDep(anyhow::Error),
+}
+
+/// Build the mock transaction of `tx`: every input cell, every cell dep (the
+/// cells in a dep group are added as code cell deps too) and every header dep
+/// are resolved by `tx_dep_provider`.
+///
+/// The block header of an input or a cell dep is added when
+/// `header_dep_resolver` knows it, so the scripts can load it.
+pub fn build_mock_transaction(
+    tx: &TransactionView,
+    tx_dep_provider: &dyn TransactionDependencyProvider,
+    header_dep_resolver: &dyn HeaderDepResolver,
+) -> Result<ReprMockTransaction, MockTxError> {
+    let mut header_deps: Vec<HeaderView> = Vec::new();
+    let mut extensions = Vec::new();
+    for block_hash in tx.header_deps_iter() {
+        let header = tx_dep_provider.get_header(&block_hash)?;
+        if let Some(extension) = tx_dep_provider.get_block_extension(&block_hash)? {
+            extensions.push((block_hash, extension));
+        }
+        header_deps.push(header);
+    }
+    let mut inputs = Vec::new();
+    for input in tx.inputs() {
+        let out_point = input.previous_output();
+        inputs.push(MockInput {
+            output: tx_dep_provider.get_cell(&out_point)?,
+            data: tx_dep_provider.get_cell_data(&out_point)?,
+            header: resolve_header(header_dep_resolver, &mut header_deps, &out_point)?,
+            input,
+        });
+    }
+
+    let mut cell_deps: Vec<MockCellDep> = Vec::new();
+    for cell_dep in tx.cell_deps() {
+        let mut expanded = vec![cell_dep.clone()];
+        if cell_dep.dep_type() == DepType::DepGroup.into() {
+            expanded.extend(expand_dep_group(&cell_dep, tx_dep_provider)?);
+        }
+        for cell_dep in expanded {
+            if cell_deps.iter().any(|item| item.cell_dep == cell_dep) {
+                continue;
+            }
+            let out_point = cell_dep.out_point();
+            cell_deps.push(MockCellDep {
+                output: tx_dep_provider.get_cell(&out_point)?,
+                data: tx_dep_provider.get_cell_data(&out_point)?,
+                header: resolve_header(header_dep_resolver, &mut header_deps, &out_point)?,
+                cell_dep,
+            });
+        }
+    }
+
+    let mock_tx = MockTransaction {
+        mock_info: MockInfo {
+            inputs,
+            cell_deps,
+            header_deps,
+            extensions,
+        },
+        tx: tx.data(),
+    };
+    Ok(mock_tx.into())
+}
+
+/// Load a mock transaction back, the returned provider resolves all its cells
+/// and headers, the returned collector collects its input cells.
+pub fn from_mock_transaction(
+    mock_tx: ReprMockTransaction,
+) -> (TransactionView, MockTxDependencyProvider, MockCellCollector) {
+    let mock_tx = MockTransaction::from(mock_tx);
+    let mut provider = MockTxDependencyProvider::default();
+    let mut collector = MockCellCollector::default();
+    for header in &mock_tx.mock_info.header_deps {
+        provider
+            .headers
+            .insert(header.hash().unpack(), header.clone());
+    }
+    for (block_hash, extension) in &mock_tx.mock_info.extensions {
+        provider
+            .extensions
+            .insert(block_hash.unpack(), extension.clone());
+    }
+    for cell_dep in &mock_tx.mock_info.cell_deps {
+        provider.cells.insert(
+            cell_key(&cell_dep.cell_dep.out_point()),
+            (cell_dep.output.clone(), cell_dep.data.clone()),
+        );
+    }
+    for input in &mock_tx.mock_info.inputs {
+        let out_point = input.input.previous_output();
+        provider.cells.insert(
+            cell_key(&out_point),
+            (input.output.clone(), input.data.clone()),
+        );
+        let block_number = input
+            .header
+            .as_ref()
+            .and_then(|hash| {
+                let hash: H256 = hash.unpack();
+                provider.headers.get(&hash)
+            })
+            .map(|header| header.number())
+            .unwrap_or(0);
+        collector.cells.push(LiveCell {
+            output: input.output.clone(),
+            output_data: input.data.clone(),
+            out_point,
+            block_number,
+            tx_index: 0,
+            from_cellbase: false,
+        });
+    }
+    (mock_tx.tx.into_view(), provider, collector)
+}
+
+/// A transaction dependency provider of the cells and headers in a mock
+/// transaction
+#[derive(Default, Clone)]
+pub struct MockTxDependencyProvider {
+    // (tx_hash, index) => (output, data)
+    pub cells: HashMap<(H256, u32), (CellOutput, Bytes)>,
+    pub headers: HashMap<H256, HeaderView>,
+    pub extensions: HashMap<H256, packed::Bytes>,
+}
+
+impl TransactionDependencyProvider for MockTxDependencyProvider {
+    // Only the known cells of the transaction are its outputs, it is found
+    // when they are all known.
+    fn get_transaction(
+        &self,
+        tx_hash: &Byte32,
+    ) -> Result<TransactionView, TransactionDependencyError> {
+        let tx_hash: H256 = tx_hash.unpack();
+        let mut cells: Vec<(u32, &(CellOutput, Bytes))> = self
+            .cells
+            .iter()
+            .filter(|((hash, _), _)| *hash == tx_hash)
+            .map(|((_, index), cell)| (*index, cell))
+            .collect();
+        cells.sort_by_key(|(index, _)| *index);
+        if cells.is_empty()
+            || cells.last().map(|(index, _)| *index as usize) != Some(cells.len() - 1)
+        {
+            return Err(TransactionDependencyError::NotFound(
+                "transaction not found".to_string(),
+            ));
+        }
+        let (outputs, outputs_data): (Vec<_>, Vec<_>) = cells
+            .into_iter()
+            .map(|(_, (output, data))| (output.clone(), data.pack()))
+            .unzip();
+        Ok(TransactionBuilder::default()
+            .outputs(outputs)
+            .outputs_data(outputs_data)
+            .build())
+    }
+    fn get_cell(&self, out_point: &OutPoint) -> Result<CellOutput, TransactionDependencyError> {
+        self.cells
+            .get(&cell_key(out_point))
+            .map(|(output, _)| output.clone())
+            .ok_or_else(|| TransactionDependencyError::NotFound("cell not found".to_string()))
+    }
+    fn get_cell_data(&self, out_point: &OutPoint) -> Result<Bytes, TransactionDependencyError> {
+        self.cells
+            .get(&cell_key(out_point))
+            .map(|(_, data)| data.clone())
+            .ok_or_else(|| TransactionDependencyError::NotFound("cell data not found".to_string()))
+    }
+    fn get_header(&self, block_hash: &Byte32) -> Result<HeaderView, TransactionDependencyError> {
+        let block_hash: H256 = block_hash.unpack();
+        self.headers
+            .get(&block_hash)
+            .cloned()
+            .ok_or_else(|| TransactionDependencyError::NotFound("header not found".to_string()))
+    }
+    fn get_block_extension(
+        &self,
+        block_hash: &Byte32,
+    ) -> Result<Option<packed::Bytes>, TransactionDependencyError> {
+        let block_hash: H256 = block_hash.unpack();
+        Ok(self.extensions.get(&block_hash).cloned())
+    }
+}
+
+/// A cell collector of the input cells in a mock transaction, all the cells
+/// are treated as mature.
+#[derive(Default, Clone)]
+pub struct MockCellCollector {
+    pub cells: Vec<LiveCell>,
+    /// The outputs of the applied transactions, cleared by `reset`
+    pub applied_cells: Vec<LiveCell>,
+    pub locked_cells: HashSet<(H256, u32)>,
+}
+
+impl CellCollector for MockCellCollector {
+    fn collect_live_cells(
+        &mut self,
+        query: &CellQueryOptions,
+        apply_changes: bool,
+    ) -> Result<(Vec<LiveCell>, u64), CellCollectorError> {
+        let mut total_capacity = 0;
+        let mut cells = Vec::new();
+        for cell in self.cells.iter().chain(self.applied_cells.iter()) {
+            if total_capacity >= query.min_total_capacity {
+                break;
+            }
+            if self.locked_cells.contains(&cell_key(&cell.out_point))
+                || !query.match_cell(cell, u64::MAX)
+            {
+                continue;
+            }
+            let capacity: u64 = cell.output.capacity().unpack();
+            total_capacity += capacity;
+            cells.push(cell.clone());
+        }
+        if apply_changes {
+            for cell in &cells {
+                self.locked_cells.insert(cell_key(&cell.out_point));
+            }
+        }
+        Ok((cells, total_capacity))
+    }
+
+    fn lock_cell(
+        &mut self,
+        out_point: OutPoint,
+        _tip_block_number: u64,
+    ) -> Result<(), CellCollectorError> {
+        self.locked_cells.insert(cell_key(&out_point));
+        Ok(())
+    }
+
+    fn apply_tx(
+        &mut self,
+        tx: Transaction,
+        _tip_block_number: u64,
+    ) -> Result<(), CellCollectorError> {
+        let tx_view = tx.into_view();
+        for out_point in tx_view.input_pts_iter() {
+            self.locked_cells.insert(cell_key(&out_point));
+        }
+        for (idx, (output, data)) in tx_view.outputs_with_data_iter().enumerate() {
+            self.applied_cells.push(LiveCell {
+                output,
+                output_data: data,
+                out_point: OutPoint::new(tx_view.hash(), idx as u32),
+                block_number: PENDING_BLOCK_NUMBER,
+                tx_index: 0,
+                from_cellbase: false,
+            });
+        }
+        Ok(())
+    }
+
+    fn unlock_cell(&mut self, out_point: &OutPoint) -> Result<(), CellCollectorError> {
+        self.locked_cells.remove(&cell_key(out_point));
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.applied_cells.clear();
+        self.locked_cells.clear();
+    }
+}
+
+// The hash of the block the cell is in, the header is added to `header_deps`
+fn resolve_header(
+    header_dep_resolver: &dyn HeaderDepResolver,
+    header_deps: &mut Vec<HeaderView>,
+    out_point: &OutPoint,
+) -> Result<Option<Byte32>, MockTxError> {
+    let header = header_dep_resolver
+        .resolve_by_tx(&out_point.tx_hash())
+        .map_err(MockTxError::HeaderDep)?;
+    Ok(header.map(|header| {
+        let hash = header.hash();
+        if header_deps.iter().all(|item| item.hash() != hash) {
+            header_deps.push(header);
+        }
+        hash
+    }))
+}
+
+fn cell_key(out_point: &OutPoint) -> (H256, u32) {
+    (out_point.tx_hash().unpack(), out_point.index().unpack())
+}
@@ -0,0 +1,276 @@
+//! Payment requests in the `ckb:` URI format, used by wallets to exchange
+//! payment requests as links or QR codes:
+//!
+//! ```text
+//! ckb:<address>?amount=100.5&udt_code_hash=0x..&udt_hash_type=type&udt_args=0x..&udt_amount=1000&label=..&message=..
+//! ```
+//!
+//! * `amount` is the ckb amount in the `HumanCapacity` format (at most 8
+//!   decimals).
+//! * `udt_code_hash`, `udt_hash_type`, `udt_args` and `udt_amount` are the
+//!   udt type script and the udt amount, they must be all present or all
+//!   absent.
+//! * Other keys are kept in `PaymentRequest::extras`.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use ckb_types::{bytes::Bytes, core::ScriptHashType, packed::Script, prelude::*, H256};
+use reqwest::Url;
+use thiserror::Error;
+
+use crate::types::{Address, HumanCapacity, NetworkType, ScriptHashTypeExt};
+
+pub const URI_SCHEME: &str = "ckb";
+
+const KEY_AMOUNT: &str = "amount";
+const KEY_UDT_CODE_HASH: &str = "udt_code_hash";
+const KEY_UDT_HASH_TYPE: &str = "udt_hash_type";
+const KEY_UDT_ARGS: &str = "udt_args";
+const KEY_UDT_AMOUNT: &str = "udt_amount";
+const KEY_LABEL: &str = "label";
+const KEY_MESSAGE: &str = "message";
+const RESERVED_KEYS: [&str; 7] = [
+    KEY_AMOUNT,
+    KEY_UDT_CODE_HASH,
+    KEY_UDT_HASH_TYPE,
+    KEY_UDT_ARGS,
+    KEY_UDT_AMOUNT,
+    KEY_LABEL,
+    KEY_MESSAGE,
+];
+
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum UriError {
+    #[error("invalid uri: `{0}`")]
+    InvalidUri(String),
+
+    #[error("invalid uri scheme, expected `ckb`, got: `{0}`")]
+    InvalidScheme(String),
+
+    #[error("invalid address: `{0}`")]
+    InvalidAddress(String),
+
+    #[error("address network mismatch, expected: `{0:?}`, actual: `{1:?}`")]
+    NetworkMismatch(NetworkType, NetworkType),
+
+    #[error("duplicate query key: `{0}`")]
+    DuplicateKey(String),
+
+    #[error("invalid value of `{0}`: `{1}`")]
+    InvalidValue(String, String),
+
+    #[error("missing query key: `{0}`")]
+    MissingKey(String),
+}
+
+/// The udt part of a payment request
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct UdtPayment {
+    pub type_script: Script,
+    pub amount: u128,
+}
+
+/// A payment request to `address`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PaymentRequest {
+    pub address: Address,
+    pub amount: Option<HumanCapacity>,
+    pub udt: Option<UdtPayment>,
+    pub label: Option<String>,
+    pub message: Option<String>,
+    /// The unknown query keys, a key used by the fields above is ignored by
+    /// `to_uri`.
+    pub extras: BTreeMap<String, String>,
+}
+
+impl PaymentRequest {
+    pub fn new(address: Address) -> PaymentRequest {
+        PaymentRequest {
+            address,
+            amount: None,
+            udt: None,
+            label: None,
+            message: None,
+            extras: BTreeMap::new(),
+        }
+    }
+
+    pub fn to_uri(&self) -> String {
+        let mut url = Url::parse(&format!("{}:{}", URI_SCHEME, self.address))
+            .expect("ckb uri with an address");
+        let mut pairs = Vec::new();
+        if let Some(amount) = self.amount.as_ref() {
+            pairs.push((KEY_AMOUNT, amount.to_string()));
+        }
+        if let Some(udt) = self.udt.as_ref() {
+            let code_hash: H256 = udt.type_script.code_hash().unpack();
+            let hash_type =
+                ScriptHashType::from_packed(&udt.type_script.hash_type()).expect("hash type");
+            pairs.push((KEY_UDT_CODE_HASH, format!("{:#x}", code_hash)));
+            pairs.push((KEY_UDT_HASH_TYPE, hash_type.to_name().to_string()));
+            pairs.push((
+                KEY_UDT_ARGS,
+                format!("0x{:x}", udt.type_script.args().raw_data()),
+            ));
+            pairs.push((KEY_UDT_AMOUNT, udt.amount.to_string()));
+        }
+        if let Some(label) = self.label.as_ref() {
+            pairs.push((KEY_LABEL, label.clone()));
+        }
+        if let Some(message) = self.message.as_ref() {
+            pairs.push((KEY_MESSAGE, message.clone()));
+        }
+        for (key, value) in &self.extras {
+            if !RESERVED_KEYS.contains(&key.as_str()) {
+                pairs.push((key.as_str(), value.clone()));
+            }
+        }
+        if !pairs.is_empty() {
+            url.query_pairs_mut().extend_pairs(pairs);
+        }
+        url.to_string()
+    }
+
+    /// Parse a payment request, the address can be of any network.
+    pub fn parse(uri: &str) -> Result<PaymentRequest, UriError> {
+        let url = Url::parse(uri.trim()).map_err(|err| UriError::InvalidUri(err.to_string()))?;
+        if url.scheme() != URI_SCHEME {
+            return Err(UriError::InvalidScheme(url.scheme().to_string()));
+        }
+        if url.fragment().is_some() {
+            return Err(UriError::InvalidUri("unexpected fragment".to_string()));
+        }
+        if url.has_host() {
+            return Err(UriError::InvalidUri(
+                "expected `ckb:<address>`, got `ckb://`".to_string(),
+            ));
+        }
+        let address = Address::from_str(url.path()).map_err(UriError::InvalidAddress)?;
+
+        let mut query: BTreeMap<String, String> = BTreeMap::new();
+        for (key, value) in url.query_pairs() {
+            if query.contains_key(key.as_ref()) {
+                return Err(UriError::DuplicateKey(key.into_owned()));
+            }
+            query.insert(key.into_owned(), value.into_owned());
+        }
+
+        let amount = query
+            .remove(KEY_AMOUNT)
+            .map(|value| parse_amount(&value))
+            .transpose()?;
+        let udt_keys = [
+            KEY_UDT_CODE_HASH,
+            KEY_UDT_HASH_TYPE,
+            KEY_UDT_ARGS,
+            KEY_UDT_AMOUNT,
+        ];
+        let udt = if udt_keys.iter().any(|key| query.contains_key(*key)) {
+            let mut values = Vec::with_capacity(udt_keys.len());
+            for key in udt_keys {
+                let value = query
+                    .remove(key)
+                    .ok_or_else(|| UriError::MissingKey(key.to_string()))?;
+                values.push(value);
+            }
+            Some(parse_udt(&values[0], &values[1], &values[2], &values[3])?)
+        } else {
+            None
+        };
+        let label = query.remove(KEY_LABEL);
+        let message = query.remove(KEY_MESSAGE);
+        Ok(PaymentRequest {
+            address,
+            amount,
+            udt,
+            label,
+            message,
+            extras: query,
+        })
+    }
+
+    /// Parse a payment request, the address must be of `network`.
+    pub fn parse_with_network<N: Into<NetworkType>>(
+        uri: &str,
+        network: N,
+    ) -> Result<PaymentRequest, UriError> {
+        let network = network.into();
+        let request = Self::parse(uri)?;
+        // The address only has a mainnet or testnet prefix
+        if request.address.network().to_prefix() != network.to_prefix() {
+            return Err(UriError::NetworkMismatch(
+                network,
+                request.address.network(),
+            ));
+        }
+        Ok(request)
+    }
+}
+
+impl FromStr for PaymentRequest {
+    type Err = UriError;
+
+    fn from_str(uri: &str) -> Result<Self, Self::Err> {
+        PaymentRequest::parse(uri)
+    }
+}
+
+fn parse_amount(value: &str) -> Result<HumanCapacity, UriError> {
+    let invalid = |reason: String| UriError::InvalidValue(KEY_AMOUNT.to_string(), reason);
+    // `HumanCapacity` also accepts the surrounding spaces and the `_` separators
+    let mut parts = value.splitn(2, '.');
+    let integer = parts.next().unwrap_or_default();
+    let decimal = parts.next();
+    let is_digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    if !is_digits(integer) || !decimal.map(is_digits).unwrap_or(true) {
+        return Err(invalid(value.to_string()));
+    }
+    HumanCapacity::from_str(value).map_err(|err| invalid(format!("{}: {}", value, err)))
+}
+
+fn parse_udt(
+    code_hash: &str,
+    hash_type: &str,
+    args: &str,
+    amount: &str,
+) -> Result<UdtPayment, UriError> {
+    let code_hash = parse_hex(KEY_UDT_CODE_HASH, code_hash).and_then(|bytes| {
+        H256::from_slice(&bytes)
+            .map_err(|err| UriError::InvalidValue(KEY_UDT_CODE_HASH.to_string(), err.to_string()))
+    })?;
+    let hash_type = ScriptHashType::from_name(hash_type).ok_or_else(|| {
+        UriError::InvalidValue(KEY_UDT_HASH_TYPE.to_string(), hash_type.to_string())
+    })?;
+    let args = parse_hex(KEY_UDT_ARGS, args)?;
+    if amount.is_empty() || !amount.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(UriError::InvalidValue(
+            KEY_UDT_AMOUNT.to_string(),
+            amount.to_string(),
+        ));
+    }
+    let amount = amount.parse::<u128>().map_err(|err| {
+        UriError::InvalidValue(KEY_UDT_AMOUNT.to_string(), format!("{}: {}", amount, err))
+    })?;
+    let type_script = Script::new_builder()
+        .code_hash(code_hash.pack())
+        .hash_type(hash_type.to_packed())
+        .args(Bytes::from(args).pack())
+        .build();
+    Ok(UdtPayment {
+        type_script,
+        amount,
+    })
+}
+
+fn parse_hex(key: &str, value: &str) -> Result<Vec<u8>, UriError> {
+    let invalid = || UriError::InvalidValue(key.to_string(), value.to_string());
+    let hex = value.strip_prefix("0x").ok_or_else(invalid)?;
+    if hex.len() % 2 != 0 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid()))
+        .collect()
+}
@@ -52,6 +52,14 @@ use tokio_util::codec::Framed;
 use stream_codec::StreamCodec;
 
 mod stream_codec;
+#[cfg(feature = "websocket")]
+mod ws;
+
+#[cfg(feature = "websocket")]
+pub use ws::{
+    CkbSubscriptionClient, ReconnectOptions, SubscriptionError, NEW_TIP_HEADER, NEW_TRANSACTION,
+    PROPOSED_TRANSACTION, REJECTED_TRANSACTION,
+};
 
 /// General rpc subscription client
 pub struct Client<T> {
@@ -0,0 +1,547 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+use ckb_jsonrpc_types::{
+    HeaderView as JsonHeaderView, PoolTransactionEntry, PoolTransactionReject, Status,
+};
+use ckb_types::{core::HeaderView, H256};
+use futures::{
+    sink::SinkExt,
+    stream::{self, BoxStream, StreamExt},
+};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use thiserror::Error;
+use tokio::net::TcpStream;
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+use crate::rpc::{CkbRpcClient, TxCommitStatus, WaitError};
+
+pub const NEW_TIP_HEADER: &str = "new_tip_header";
+pub const NEW_TRANSACTION: &str = "new_transaction";
+pub const PROPOSED_TRANSACTION: &str = "proposed_transaction";
+pub const REJECTED_TRANSACTION: &str = "rejected_transaction";
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+#[derive(Error, Debug)]
+pub enum SubscriptionError {
+    #[error("websocket error: `{0}`")]
+    WebSocket(#[from] tokio_tungstenite::tungstenite::Error),
+
+    #[error("subscribe topic `{0}` failed: `{1}`")]
+    Subscribe(String, String),
+
+    /// A message can not be parsed, the stream goes on
+    #[error("invalid message: `{0}`")]
+    InvalidMessage(String),
+}
+
+/// How the subscriptions are resumed when the connection is lost
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ReconnectOptions {
+    /// Give up after this many failed connections in a row
+    pub max_attempts: u32,
+    /// The interval between two connections
+    pub interval: Duration,
+}
+
+impl Default for ReconnectOptions {
+    fn default() -> Self {
+        ReconnectOptions {
+            max_attempts: 5,
+            interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Subscription client of the ckb node's websocket endpoint (`ws_listen_address`).
+///
+/// Every stream has its own connection. When the connection is lost it is
+/// reconnected and the topics are subscribed again, the notifications sent
+/// in between are missed. The stream ends after the last error when the
+/// reconnection fails `max_attempts` times in a row.
+#[derive(Debug, Clone)]
+pub struct CkbSubscriptionClient {
+    url: String,
+    reconnect: ReconnectOptions,
+}
+
+impl CkbSubscriptionClient {
+    pub fn new(url: &str) -> CkbSubscriptionClient {
+        CkbSubscriptionClient {
+            url: url.to_string(),
+            reconnect: ReconnectOptions::default(),
+        }
+    }
+
+    pub fn with_reconnect(mut self, reconnect: ReconnectOptions) -> CkbSubscriptionClient {
+        self.reconnect = reconnect;
+        self
+    }
+
+    /// The topics and the json results of the notifications of `topics`
+    pub fn subscribe_raw(
+        &self,
+        topics: &[&str],
+    ) -> BoxStream<'static, Result<(String, String), SubscriptionError>> {
+        let state = RawState {
+            url: self.url.clone(),
+            topics: topics.iter().map(|topic| topic.to_string()).collect(),
+            reconnect: self.reconnect,
+            conn: None,
+            failures: 0,
+            done: false,
+        };
+        stream::unfold(state, |mut state| async move {
+            state.next().await.map(|item| (item, state))
+        })
+        .boxed()
+    }
+
+    /// The notifications of `topic` parsed as `T`, a notification failed to
+    /// parse is returned as `SubscriptionError::InvalidMessage`.
+    pub fn subscribe_topic<T: DeserializeOwned + Send + 'static>(
+        &self,
+        topic: &str,
+    ) -> BoxStream<'static, Result<T, SubscriptionError>> {
+        self.subscribe_raw(&[topic])
+            .map(|item| {
+                item.and_then(|(_, result)| {
+                    serde_json::from_str::<T>(&result)
+                        .map_err(|err| SubscriptionError::InvalidMessage(err.to_string()))
+                })
+            })
+            .boxed()
+    }
+
+    pub fn subscribe_new_tip_header(
+        &self,
+    ) -> BoxStream<'static, Result<HeaderView, SubscriptionError>> {
+        self.subscribe_topic::<JsonHeaderView>(NEW_TIP_HEADER)
+            .map(|item| item.map(HeaderView::from))
+            .boxed()
+    }
+
+    pub fn subscribe_new_transaction(
+        &self,
+    ) -> BoxStream<'static, Result<PoolTransactionEntry, SubscriptionError>> {
+        self.subscribe_topic(NEW_TRANSACTION)
+    }
+
+    pub fn subscribe_proposed_transaction(
+        &self,
+    ) -> BoxStream<'static, Result<PoolTransactionEntry, SubscriptionError>> {
+        self.subscribe_topic(PROPOSED_TRANSACTION)
+    }
+
+    pub fn subscribe_rejected_transaction(
+        &self,
+    ) -> BoxStream<'static, Result<(PoolTransactionEntry, PoolTransactionReject), SubscriptionError>>
+    {
+        self.subscribe_topic(REJECTED_TRANSACTION)
+    }
+
+    /// Same as `CkbRpcClient::wait_for_tx` with 0 confirmations, the status
+    /// is checked by `rpc_client` on every new tip header instead of polling,
+    /// and a rejection is reported as soon as it is notified.
+    pub async fn wait_for_tx_via_subscription(
+        &self,
+        rpc_client: &CkbRpcClient,
+        tx_hash: H256,
+        timeout: Duration,
+    ) -> Result<TxCommitStatus, WaitError> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let mut events = self.subscribe_raw(&[NEW_TIP_HEADER, REJECTED_TRANSACTION]);
+        // The transaction may be committed before the subscription
+        let mut last_status = check_tx_status(rpc_client, &tx_hash).await?;
+        loop {
+            if let Status::Committed = last_status.0 {
+                return last_status.1.ok_or_else(|| {
+                    WaitError::InvalidStatus("committed without block".to_string())
+                });
+            }
+            let event = match tokio::time::timeout_at(deadline, events.next()).await {
+                Err(_) => return Err(WaitError::Timeout(last_status.0)),
+                Ok(None) => {
+                    return Err(WaitError::Subscription(
+                        "subscription stream ended".to_string(),
+                    ))
+                }
+                Ok(Some(Err(SubscriptionError::InvalidMessage(_)))) => continue,
+                Ok(Some(Err(err))) => return Err(WaitError::Subscription(err.to_string())),
+                Ok(Some(Ok(event))) => event,
+            };
+            match event {
+                (topic, result) if topic == REJECTED_TRANSACTION => {
+                    if let Some(reason) = rejected_reason(&result, &tx_hash) {
+                        return Err(WaitError::Rejected(reason));
+                    }
+                }
+                _ => last_status = check_tx_status(rpc_client, &tx_hash).await?,
+            }
+        }
+    }
+}
+
+/// The status of the transaction, and the block when it is committed
+async fn check_tx_status(
+    rpc_client: &CkbRpcClient,
+    tx_hash: &H256,
+) -> Result<(Status, Option<TxCommitStatus>), WaitError> {
+    let (rpc_client, tx_hash) = (rpc_client.clone(), tx_hash.clone());
+    // The rpc client is blocking
+    tokio::task::spawn_blocking(move || {
+        let tx_status = rpc_client.get_transaction_status(tx_hash)?.tx_status;
+        match tx_status.status {
+            Status::Rejected => Err(WaitError::Rejected(tx_status.reason.unwrap_or_default())),
+            Status::Committed => match (tx_status.block_number, tx_status.block_hash) {
+                (Some(block_number), Some(block_hash)) => {
+                    let block_number: u64 = block_number.into();
+                    let tip_number: u64 = rpc_client.get_tip_block_number()?.into();
+                    let status = TxCommitStatus {
+                        block_number,
+                        block_hash,
+                        confirmations: tip_number.saturating_sub(block_number),
+                    };
+                    Ok((Status::Committed, Some(status)))
+                }
+                _ => Err(WaitError::InvalidStatus(
+                    "committed without block number or hash".to_string(),
+                )),
+            },
+            status => Ok((status, None)),
+        }
+    })
+    .await
+    .map_err(|err| WaitError::Subscription(err.to_string()))?
+}
+
+/// The reason when the rejected transaction of a `rejected_transaction`
+/// notification is `tx_hash`
+fn rejected_reason(result: &str, tx_hash: &H256) -> Option<String> {
+    let value: Value = serde_json::from_str(result).ok()?;
+    let hash: H256 = serde_json::from_value(value[0]["transaction"]["hash"].clone()).ok()?;
+    if &hash != tx_hash {
+        return None;
+    }
+    let reject = &value[1];
+    Some(
+        reject["description"]
+            .as_str()
+            .map(|description| description.to_string())
+            .unwrap_or_else(|| reject.to_string()),
+    )
+}
+
+struct Connection {
+    ws: WsStream,
+    /// Topic by subscription id
+    topics: HashMap<String, String>,
+    /// Notifications received before the subscriptions are all confirmed
+    pending: VecDeque<String>,
+}
+
+struct RawState {
+    url: String,
+    topics: Vec<String>,
+    reconnect: ReconnectOptions,
+    conn: Option<Connection>,
+    failures: u32,
+    done: bool,
+}
+
+impl RawState {
+    async fn next(&mut self) -> Option<Result<(String, String), SubscriptionError>> {
+        loop {
+            if self.done {
+                return None;
+            }
+            let conn = match self.conn.as_mut() {
+                Some(conn) => conn,
+                None => {
+                    match connect(&self.url, &self.topics).await {
+                        Ok(conn) => {
+                            self.failures = 0;
+                            self.conn = Some(conn);
+                        }
+                        Err(err) => {
+                            self.failures += 1;
+                            if self.failures >= self.reconnect.max_attempts {
+                                self.done = true;
+                                return Some(Err(err));
+                            }
+                            log::warn!("subscription connection failed: {}", err);
+                            tokio::time::sleep(self.reconnect.interval).await;
+                        }
+                    }
+                    continue;
+                }
+            };
+            let text = match conn.pending.pop_front() {
+                Some(text) => text,
+                None => match conn.ws.next().await {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => {
+                        log::warn!("subscription connection lost, reconnecting");
+                        self.conn = None;
+                        continue;
+                    }
+                    Some(Ok(_)) => continue,
+                },
+            };
+            if let Some(item) = parse_notification(&text, &conn.topics) {
+                return Some(item);
+            }
+        }
+    }
+}
+
+async fn connect(url: &str, topics: &[String]) -> Result<Connection, SubscriptionError> {
+    let (ws, _) = tokio_tungstenite::connect_async(url).await?;
+    let mut conn = Connection {
+        ws,
+        topics: HashMap::new(),
+        pending: VecDeque::new(),
+    };
+    for (id, topic) in topics.iter().enumerate() {
+        let request = json!({
+            "id": id,
+            "jsonrpc": "2.0",
+            "method": "subscribe",
+            "params": [topic],
+        });
+        conn.ws.send(Message::Text(request.to_string())).await?;
+        loop {
+            let text = match conn.ws.next().await {
+                Some(Ok(Message::Text(text))) => text,
+                Some(Ok(_)) => continue,
+                Some(Err(err)) => return Err(err.into()),
+                None => {
+                    return Err(SubscriptionError::Subscribe(
+                        topic.clone(),
+                        "connection closed".to_string(),
+                    ))
+                }
+            };
+            match serde_json::from_str::<jsonrpc_core::response::Output>(&text) {
+                Ok(jsonrpc_core::response::Output::Success(success)) => {
+                    let subscription_id = match success.result {
+                        Value::String(subscription_id) => subscription_id,
+                        other => other.to_string(),
+                    };
+                    conn.topics.insert(subscription_id, topic.clone());
+                    break;
+                }
+                Ok(jsonrpc_core::response::Output::Failure(failure)) => {
+                    return Err(SubscriptionError::Subscribe(
+                        topic.clone(),
+                        failure.error.message,
+                    ));
+                }
+                // A notification of the topics already subscribed
+                Err(_) => conn.pending.push_back(text),
+            }
+        }
+    }
+    Ok(conn)
+}
+
+/// `None` if `text` is not a notification
+fn parse_notification(
+    text: &str,
+    topics: &HashMap<String, String>,
+) -> Option<Result<(String, String), SubscriptionError>> {
+    let invalid = || Some(Err(SubscriptionError::InvalidMessage(text.to_string())));
+    let value: Value = match serde_json::from_str(text) {
+        Ok(value) => value,
+        Err(_) => return invalid(),
+    };
+    if value.get("method").is_none() {
+        return None;
+    }
+    let params = &value["params"];
+    let subscription_id = match &params["subscription"] {
+        Value::String(subscription_id) => subscription_id.clone(),
+        other => other.to_string(),
+    };
+    match (topics.get(&subscription_id), params["result"].as_str()) {
+        (Some(topic), Some(result)) => Some(Ok((topic.clone(), result.to_string()))),
+        _ => invalid(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockRpcResult;
+    use ckb_types::{core::HeaderBuilder, h256, prelude::*};
+    use httpmock::prelude::*;
+    use tokio::net::TcpListener;
+
+    /// Accept one connection per round, confirm the `topics` subscriptions
+    /// then send the frames of the round. The connection is closed after the
+    /// frames except for the last round.
+    async fn start_ws_server(topics: usize, rounds: Vec<Vec<String>>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let count = rounds.len();
+            for (round, frames) in rounds.into_iter().enumerate() {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+                for subscription_id in 0..topics {
+                    let request = match ws.next().await {
+                        Some(Ok(Message::Text(request))) => request,
+                        other => panic!("unexpected subscribe request: {:?}", other),
+                    };
+                    let request: Value = serde_json::from_str(&request).unwrap();
+                    assert_eq!(request["method"], "subscribe");
+                    let response = json!({
+                        "jsonrpc": "2.0",
+                        "result": format!("{:#x}", subscription_id),
+                        "id": request["id"],
+                    });
+                    ws.send(Message::Text(response.to_string())).await.unwrap();
+                }
+                for frame in frames {
+                    ws.send(Message::Text(frame)).await.unwrap();
+                }
+                if round + 1 < count {
+                    ws.close(None).await.unwrap();
+                } else {
+                    // keep the last connection open until the client drops it
+                    while ws.next().await.is_some() {}
+                }
+            }
+        });
+        url
+    }
+
+    fn notification(subscription_id: &str, result: String) -> String {
+        json!({
+            "jsonrpc": "2.0",
+            "method": "subscribe",
+            "params": {"result": result, "subscription": subscription_id},
+        })
+        .to_string()
+    }
+
+    fn header_notification(number: u64) -> String {
+        let header = HeaderBuilder::default().number(number.pack()).build();
+        notification(
+            "0x0",
+            serde_json::to_string(&JsonHeaderView::from(header)).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_new_tip_header() {
+        let url = start_ws_server(
+            1,
+            vec![
+                vec![
+                    header_notification(1),
+                    notification("0x0", "not a header".to_string()),
+                    header_notification(2),
+                ],
+                vec![header_notification(3)],
+            ],
+        )
+        .await;
+        let client = CkbSubscriptionClient::new(&url).with_reconnect(ReconnectOptions {
+            max_attempts: 3,
+            interval: Duration::from_millis(10),
+        });
+
+        let items: Vec<_> = client.subscribe_new_tip_header().take(4).collect().await;
+        assert_eq!(items[0].as_ref().unwrap().number(), 1);
+        // the invalid message does not end the stream
+        assert!(matches!(
+            items[1],
+            Err(SubscriptionError::InvalidMessage(_))
+        ));
+        assert_eq!(items[2].as_ref().unwrap().number(), 2);
+        // resubscribed after the reconnection
+        assert_eq!(items[3].as_ref().unwrap().number(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_failed() {
+        // nothing listens on the port
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let client = CkbSubscriptionClient::new(&url).with_reconnect(ReconnectOptions {
+            max_attempts: 2,
+            interval: Duration::from_millis(10),
+        });
+        let items: Vec<_> = client.subscribe_new_tip_header().collect().await;
+        assert_eq!(items.len(), 1);
+        assert!(matches!(items[0], Err(SubscriptionError::WebSocket(_))));
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_rejected_tx_via_subscription() {
+        let tx_hash = h256!("0x6a3c04f1c0b1b2a4f5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b1c2d3e4f5a6b7c8");
+        let other_hash = h256!("0x1234");
+        let rejected = |hash: &H256| {
+            notification(
+                "0x1",
+                json!([
+                    {"transaction": {"hash": hash}},
+                    {"type": "Resolve", "description": "Resolve failed Dead(OutPoint(0x00))"},
+                ])
+                .to_string(),
+            )
+        };
+        let url = start_ws_server(
+            2,
+            vec![vec![
+                header_notification(1),
+                rejected(&other_hash),
+                rejected(&tx_hash),
+            ]],
+        )
+        .await;
+
+        let server = MockServer::start_async().await;
+        let tx_response = MockRpcResult::new(json!({
+            "transaction": null,
+            "cycles": null,
+            "time_added_to_pool": null,
+            "fee": null,
+            "min_replace_fee": null,
+            "tx_status": {
+                "status": "pending",
+                "block_number": null,
+                "block_hash": null,
+                "tx_index": null,
+                "reason": null,
+            },
+        }))
+        .to_json();
+        let tx_mock = server
+            .mock_async(|when, then| {
+                when.method(POST).path("/").body_contains("get_transaction");
+                then.status(200).body(&tx_response);
+            })
+            .await;
+        let rpc_client = CkbRpcClient::new(server.base_url().as_str());
+
+        let client = CkbSubscriptionClient::new(&url);
+        let err = client
+            .wait_for_tx_via_subscription(&rpc_client, tx_hash, Duration::from_secs(5))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, WaitError::Rejected(ref reason) if reason.starts_with("Resolve failed")),
+            "{}",
+            err
+        );
+        // checked once at the start and once on the new tip header
+        tx_mock.assert_hits_async(2).await;
+    }
+}
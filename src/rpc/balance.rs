@@ -0,0 +1,410 @@
+use anyhow::anyhow;
+use ckb_jsonrpc_types::{JsonBytes, Uint32};
+use ckb_types::{packed::Script, prelude::*};
+
+use super::{
+    ckb_indexer::{
+        Cell, CellsCapacity, Order, Pagination, ScriptType, SearchKey, SearchKeyFilter, SearchMode,
+    },
+    CkbRpcClient, IndexerRpcClient, RpcError,
+};
+use crate::constants::DAO_TYPE_HASH;
+use crate::traits::LiveCell;
+use crate::types::ScriptId;
+use crate::util::is_mature;
+
+/// The capacity of the live cells of a lock script, in shannons
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct Balance {
+    /// The total capacity reported by `get_cells_capacity`, it also includes
+    /// the immature cellbase cells, which are in none of the other fields.
+    pub total: u64,
+    /// The cells without type script and data, and mature
+    pub free: u64,
+    /// The cells with a type script or data, except the dao cells
+    pub occupied: u64,
+    /// The dao cells not prepared for withdrawing yet
+    pub dao_deposited: u64,
+    /// The dao cells prepared for withdrawing
+    pub dao_withdrawing: u64,
+}
+
+/// Options for `get_balance` and `get_udt_balance`
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct BalanceOptions {
+    /// The number of cells fetched by one `get_cells` request
+    pub page_size: u32,
+    /// A cellbase cell is only free when it is committed at or below this
+    /// block number, see `util::get_max_mature_number`.
+    pub max_mature_number: u64,
+}
+
+impl Default for BalanceOptions {
+    fn default() -> Self {
+        BalanceOptions {
+            page_size: 100,
+            max_mature_number: 0,
+        }
+    }
+}
+
+impl CkbRpcClient {
+    /// The balance of `lock` with the default options
+    pub fn get_balance(&self, lock: &Script) -> Result<Balance, RpcError> {
+        self.get_balance_with(lock, BalanceOptions::default())
+    }
+
+    /// The balance of `lock`, the cells are walked by pages of `opts.page_size`
+    pub fn get_balance_with(
+        &self,
+        lock: &Script,
+        opts: BalanceOptions,
+    ) -> Result<Balance, RpcError> {
+        get_balance(
+            lock,
+            opts,
+            |search_key| self.get_cells_capacity(search_key),
+            |search_key, limit, after| self.get_cells(search_key, Order::Asc, limit, after),
+        )
+    }
+
+    /// The total udt amount of the cells of `lock` and `udt_type`
+    pub fn get_udt_balance(&self, lock: &Script, udt_type: &Script) -> Result<u128, RpcError> {
+        get_udt_balance(
+            lock,
+            udt_type,
+            BalanceOptions::default(),
+            |search_key, limit, after| self.get_cells(search_key, Order::Asc, limit, after),
+        )
+    }
+}
+
+impl IndexerRpcClient {
+    /// The balance of `lock` with the default options
+    pub fn get_balance(&self, lock: &Script) -> Result<Balance, RpcError> {
+        self.get_balance_with(lock, BalanceOptions::default())
+    }
+
+    /// The balance of `lock`, the cells are walked by pages of `opts.page_size`
+    pub fn get_balance_with(
+        &self,
+        lock: &Script,
+        opts: BalanceOptions,
+    ) -> Result<Balance, RpcError> {
+        get_balance(
+            lock,
+            opts,
+            |search_key| self.get_cells_capacity(search_key),
+            |search_key, limit, after| self.get_cells(search_key, Order::Asc, limit, after),
+        )
+    }
+
+    /// The total udt amount of the cells of `lock` and `udt_type`
+    pub fn get_udt_balance(&self, lock: &Script, udt_type: &Script) -> Result<u128, RpcError> {
+        get_udt_balance(
+            lock,
+            udt_type,
+            BalanceOptions::default(),
+            |search_key, limit, after| self.get_cells(search_key, Order::Asc, limit, after),
+        )
+    }
+}
+
+/// Only the cells of exactly `lock`, the default prefix search also matches
+/// the locks with longer args.
+fn lock_search_key(lock: &Script, filter: Option<SearchKeyFilter>) -> SearchKey {
+    SearchKey {
+        script: lock.clone().into(),
+        script_type: ScriptType::Lock,
+        script_search_mode: Some(SearchMode::Exact),
+        filter,
+        with_data: Some(true),
+        group_by_transaction: None,
+    }
+}
+
+/// Call `visit` with every cell of `search_key`
+fn walk_cells<G, V>(
+    search_key: SearchKey,
+    page_size: u32,
+    get_cells: G,
+    mut visit: V,
+) -> Result<(), RpcError>
+where
+    G: Fn(SearchKey, Uint32, Option<JsonBytes>) -> Result<Pagination<Cell>, RpcError>,
+    V: FnMut(LiveCell) -> Result<(), RpcError>,
+{
+    let mut after = None;
+    loop {
+        let page = get_cells(search_key.clone(), page_size.into(), after)?;
+        let count = page.objects.len();
+        for cell in page.objects {
+            visit(cell.into())?;
+        }
+        if count < page_size as usize {
+            return Ok(());
+        }
+        after = Some(page.last_cursor);
+    }
+}
+
+fn get_balance<C, G>(
+    lock: &Script,
+    opts: BalanceOptions,
+    get_cells_capacity: C,
+    get_cells: G,
+) -> Result<Balance, RpcError>
+where
+    C: Fn(SearchKey) -> Result<Option<CellsCapacity>, RpcError>,
+    G: Fn(SearchKey, Uint32, Option<JsonBytes>) -> Result<Pagination<Cell>, RpcError>,
+{
+    let mut balance = Balance {
+        total: get_cells_capacity(lock_search_key(lock, None))?
+            .map(|capacity| capacity.capacity.value())
+            .unwrap_or_default(),
+        ..Default::default()
+    };
+    walk_cells(
+        lock_search_key(lock, None),
+        opts.page_size,
+        get_cells,
+        |cell| {
+            let capacity: u64 = cell.output.capacity().unpack();
+            let is_dao = cell
+                .output
+                .type_()
+                .to_opt()
+                .map(|script| ScriptId::from(&script) == ScriptId::new_type(DAO_TYPE_HASH.clone()))
+                .unwrap_or(false);
+            if is_dao {
+                // The data of a deposited dao cell is 8 zero bytes, a prepared
+                // one keeps the deposit block number
+                if cell.output_data.iter().all(|byte| *byte == 0) {
+                    balance.dao_deposited += capacity;
+                } else {
+                    balance.dao_withdrawing += capacity;
+                }
+            } else if cell.output.type_().to_opt().is_some() || !cell.output_data.is_empty() {
+                balance.occupied += capacity;
+            } else if is_mature(&cell, opts.max_mature_number) {
+                balance.free += capacity;
+            }
+            Ok(())
+        },
+    )?;
+    Ok(balance)
+}
+
+fn get_udt_balance<G>(
+    lock: &Script,
+    udt_type: &Script,
+    opts: BalanceOptions,
+    get_cells: G,
+) -> Result<u128, RpcError>
+where
+    G: Fn(SearchKey, Uint32, Option<JsonBytes>) -> Result<Pagination<Cell>, RpcError>,
+{
+    let filter = SearchKeyFilter {
+        script: Some(udt_type.clone().into()),
+        ..Default::default()
+    };
+    let mut total: u128 = 0;
+    walk_cells(
+        lock_search_key(lock, Some(filter)),
+        opts.page_size,
+        get_cells,
+        |cell| {
+            // The filter script is searched by prefix
+            if cell.output.type_().to_opt().as_ref() != Some(udt_type)
+                || cell.output_data.len() < 16
+            {
+                return Ok(());
+            }
+            let mut amount_bytes = [0u8; 16];
+            amount_bytes.copy_from_slice(&cell.output_data[0..16]);
+            total = total
+                .checked_add(u128::from_le_bytes(amount_bytes))
+                .ok_or_else(|| anyhow!("udt balance overflow"))?;
+            Ok(())
+        },
+    )?;
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::SIGHASH_TYPE_HASH;
+    use crate::test_util::MockRpcResult;
+    use crate::types::ScriptHashTypeExt;
+    use ckb_types::{
+        bytes::Bytes,
+        core::{Capacity, ScriptHashType},
+        h256,
+        packed::{CellOutput, OutPoint},
+        H256,
+    };
+    use httpmock::prelude::*;
+
+    const SUDT_CODE_HASH: H256 =
+        h256!("0x5e7a36a77e68eecc013dfa2fe6a23f3b6c344b04005808694ae6dd45eea4cfd5");
+
+    fn lock_script() -> Script {
+        Script::new_builder()
+            .code_hash(SIGHASH_TYPE_HASH.pack())
+            .hash_type(ScriptHashType::Type.to_packed())
+            .args(Bytes::from(vec![1u8; 20]).pack())
+            .build()
+    }
+
+    fn type_script(code_hash: &H256, args: Vec<u8>) -> Script {
+        Script::new_builder()
+            .code_hash(code_hash.pack())
+            .hash_type(ScriptHashType::Type.to_packed())
+            .args(Bytes::from(args).pack())
+            .build()
+    }
+
+    fn cell(
+        capacity_ckb: u64,
+        type_: Option<Script>,
+        data: Vec<u8>,
+        block_number: u64,
+        tx_index: u32,
+    ) -> Cell {
+        let output = CellOutput::new_builder()
+            .capacity(Capacity::shannons(capacity_ckb * 100_000_000).pack())
+            .lock(lock_script())
+            .type_(type_.pack())
+            .build();
+        Cell {
+            output: output.into(),
+            output_data: Some(JsonBytes::from_vec(data)),
+            out_point: OutPoint::default().into(),
+            block_number: block_number.into(),
+            tx_index: tx_index.into(),
+        }
+    }
+
+    fn cells_response(cells: Vec<Cell>, cursor: &str) -> String {
+        MockRpcResult::new(Pagination {
+            objects: cells,
+            last_cursor: JsonBytes::from_vec(hex::decode(cursor).unwrap()),
+        })
+        .to_json()
+    }
+
+    #[test]
+    fn test_get_balance() {
+        let server = MockServer::start();
+        let dao_type = type_script(&DAO_TYPE_HASH, Vec::new());
+        let capacity = CellsCapacity {
+            capacity: (1500 * 100_000_000u64).into(),
+            block_hash: H256::default(),
+            block_number: 200u64.into(),
+        };
+        let capacity_response = MockRpcResult::new(Some(capacity)).to_json();
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/")
+                .body_contains("get_cells_capacity")
+                .body_contains("\"exact\"");
+            then.status(200).body(&capacity_response);
+        });
+        let pages = vec![
+            (
+                ",null]",
+                vec![
+                    cell(100, None, Vec::new(), 10, 1),
+                    cell(
+                        200,
+                        Some(type_script(&SUDT_CODE_HASH, vec![2u8; 32])),
+                        vec![0u8; 16],
+                        10,
+                        1,
+                    ),
+                ],
+                "01",
+            ),
+            (
+                "\"0x01\"]",
+                vec![
+                    cell(300, Some(dao_type.clone()), vec![0u8; 8], 20, 1),
+                    cell(400, Some(dao_type), 20u64.to_le_bytes().to_vec(), 30, 1),
+                ],
+                "02",
+            ),
+            (
+                "\"0x02\"]",
+                // an immature cellbase cell
+                vec![cell(500, None, Vec::new(), 100, 0)],
+                "03",
+            ),
+        ];
+        for (after, cells, cursor) in pages {
+            let response = cells_response(cells, cursor);
+            server.mock(|when, then| {
+                when.method(POST)
+                    .path("/")
+                    .body_contains("\"get_cells\"")
+                    .body_contains(after);
+                then.status(200).body(&response);
+            });
+        }
+
+        let opts = BalanceOptions {
+            page_size: 2,
+            max_mature_number: 50,
+        };
+        let client = CkbRpcClient::new(server.base_url().as_str());
+        let expected = Balance {
+            total: 1500 * 100_000_000,
+            free: 100 * 100_000_000,
+            occupied: 200 * 100_000_000,
+            dao_deposited: 300 * 100_000_000,
+            dao_withdrawing: 400 * 100_000_000,
+        };
+        assert_eq!(
+            client.get_balance_with(&lock_script(), opts).unwrap(),
+            expected
+        );
+        let client = IndexerRpcClient::new(server.base_url().as_str());
+        assert_eq!(
+            client.get_balance_with(&lock_script(), opts).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_get_udt_balance() {
+        let server = MockServer::start();
+        let udt_type = type_script(&SUDT_CODE_HASH, vec![2u8; 32]);
+        // matched by the prefix search of the filter script
+        let other_type = type_script(&SUDT_CODE_HASH, vec![2u8; 33]);
+        // the xudt data can have extra bytes after the amount
+        let mut xudt_data = 234u128.to_le_bytes().to_vec();
+        xudt_data.extend_from_slice(&[9u8; 4]);
+        let cells = vec![
+            cell(
+                200,
+                Some(udt_type.clone()),
+                1000u128.to_le_bytes().to_vec(),
+                10,
+                1,
+            ),
+            cell(200, Some(other_type), 50u128.to_le_bytes().to_vec(), 10, 1),
+            cell(200, Some(udt_type.clone()), xudt_data, 10, 1),
+        ];
+        let response = cells_response(cells, "03");
+        server.mock(|when, then| {
+            when.method(POST).path("/").body_contains("\"get_cells\"");
+            then.status(200).body(&response);
+        });
+
+        let client = CkbRpcClient::new(server.base_url().as_str());
+        assert_eq!(
+            client.get_udt_balance(&lock_script(), &udt_type).unwrap(),
+            1234
+        );
+    }
+}
@@ -4,13 +4,17 @@ use ckb_jsonrpc_types::{
     DaoWithdrawingCalculationKind, DeploymentsInfo, EntryCompleted, EpochNumber,
     EpochNumberWithFraction, EpochView, EstimateCycles, ExtraLoggerConfig, FeeRateStatistics,
     HeaderView, JsonBytes, LocalNode, MainLoggerConfig, OutPoint, OutputsValidator,
-    PoolTxDetailInfo, RawTxPool, RemoteNode, SyncState, Timestamp, Transaction,
+    PoolTxDetailInfo, RawTxPool, RemoteNode, Status, SyncState, Timestamp, Transaction,
     TransactionAndWitnessProof, TransactionProof, TransactionWithStatusResponse, TxPoolInfo,
     Uint32, Uint64, Version,
 };
 use ckb_types::{core::Cycle, H256};
+use std::{thread, time::Instant};
 
-use super::{ckb_indexer::CellsCapacity, ResponseFormatGetter};
+use super::{
+    ckb_indexer::CellsCapacity, ResponseFormatGetter, TransactionSubmitError, TxCommitStatus,
+    WaitError, WaitOptions,
+};
 
 pub use super::ckb_indexer::{Cell, Order, Pagination, SearchKey, Tip, Tx};
 
@@ -279,6 +283,71 @@ impl CkbRpcClient {
         )
     }
 
+    /// Same as send_transaction except the error is parsed into `TransactionSubmitError`
+    pub fn send_transaction_typed(
+        &self,
+        tx: Transaction,
+        outputs_validator: Option<OutputsValidator>,
+    ) -> Result<H256, TransactionSubmitError> {
+        self.send_transaction(tx, outputs_validator)
+            .map_err(TransactionSubmitError::from)
+    }
+
+    /// The cycles the transaction consumes, run by the node without sending
+    /// it. A script error is parsed into `TransactionSubmitError` the same as
+    /// `send_transaction_typed`, e.g. `ExceededMaximumCycles`.
+    pub fn estimate_cycles_typed(&self, tx: Transaction) -> Result<Cycle, TransactionSubmitError> {
+        self.estimate_cycles(tx)
+            .map(|result| result.cycles.value())
+            .map_err(TransactionSubmitError::from)
+    }
+
+    /// Poll the transaction status until it is committed with enough confirmations.
+    ///
+    /// Returns early with the node's reason if the transaction is rejected. A freshly
+    /// broadcast transaction can be briefly unknown to the node, so the unknown status
+    /// is only reported as `WaitError::Timeout` after the timeout.
+    pub fn wait_for_tx(
+        &self,
+        tx_hash: H256,
+        opts: WaitOptions,
+    ) -> Result<TxCommitStatus, WaitError> {
+        let start = Instant::now();
+        loop {
+            let tx_status = self.get_transaction_status(tx_hash.clone())?.tx_status;
+            match tx_status.status {
+                Status::Pending | Status::Proposed | Status::Unknown => {}
+                Status::Rejected => {
+                    return Err(WaitError::Rejected(tx_status.reason.unwrap_or_default()));
+                }
+                Status::Committed => {
+                    let block_number: u64 = tx_status
+                        .block_number
+                        .ok_or_else(|| {
+                            WaitError::InvalidStatus("committed without block number".to_string())
+                        })?
+                        .into();
+                    let block_hash = tx_status.block_hash.ok_or_else(|| {
+                        WaitError::InvalidStatus("committed without block hash".to_string())
+                    })?;
+                    let tip_number: u64 = self.get_tip_block_number()?.into();
+                    let confirmations = tip_number.saturating_sub(block_number);
+                    if confirmations >= opts.confirmations {
+                        return Ok(TxCommitStatus {
+                            block_number,
+                            block_hash,
+                            confirmations,
+                        });
+                    }
+                }
+            }
+            if start.elapsed() >= opts.timeout {
+                return Err(WaitError::Timeout(tx_status.status));
+            }
+            thread::sleep(opts.poll_interval);
+        }
+    }
+
     pub fn get_packed_tip_header(&self) -> Result<JsonBytes, crate::rpc::RpcError> {
         self.post::<_, JsonBytes>("get_tip_header", (Some(Uint32::from(0u32)),))
     }
@@ -132,6 +132,8 @@ impl From<Cell> for LiveCell {
             out_point: cell.out_point.into(),
             block_number: cell.block_number.value(),
             tx_index: cell.tx_index.value(),
+            // the cellbase is always the first transaction of the block
+            from_cellbase: cell.tx_index.value() == 0,
         }
     }
 }
@@ -169,7 +171,7 @@ pub struct TxWithCells {
     pub cells: Vec<(CellType, Uint32)>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum CellType {
     Input,
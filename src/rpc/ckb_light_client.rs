@@ -167,3 +167,178 @@ crate::jsonrpc!(pub struct LightClientRpcClient {
     pub fn get_peers(&self) -> Vec<RemoteNode>;
     pub fn local_node_info(&self) -> LocalNode;
 });
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_types::prelude::*;
+    use serde::de::DeserializeOwned;
+    use serde_json::Value;
+
+    // fixtures in the shape returned by a ckb-light-client node
+    const SCRIPT_STATUS_JSON: &str = r#"{
+        "script": {
+            "code_hash": "0x9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8",
+            "hash_type": "type",
+            "args": "0x50878ce52a68feb47237c29574d82288f58b5d21"
+        },
+        "script_type": "lock",
+        "block_number": "0xa1b2c3"
+    }"#;
+
+    // The header and the transactions are recorded from a ckb node (the
+    // testnet genesis block), the light client returns them in the same json.
+    const GENESIS_JSON: &str = include_str!("../test-data/genesis_block.json");
+
+    fn genesis_json(pointer: &str) -> String {
+        let genesis: Value = serde_json::from_str(GENESIS_JSON).unwrap();
+        genesis.pointer(pointer).unwrap().to_string()
+    }
+
+    fn header_json() -> String {
+        genesis_json("/header")
+    }
+
+    // the dep group transaction
+    fn transaction_json() -> String {
+        genesis_json("/transactions/1")
+    }
+
+    // parse the fixture, the serialized value must parse back to the same value
+    fn round_trip<T: Serialize + DeserializeOwned>(json: &str) -> (T, Value) {
+        let value: T = serde_json::from_str(json).unwrap();
+        let serialized = serde_json::to_value(&value).unwrap();
+        let reparsed: T = serde_json::from_value(serialized.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&reparsed).unwrap(), serialized);
+        (value, serialized)
+    }
+
+    #[test]
+    fn test_script_status_serde() {
+        let (status, serialized) = round_trip::<ScriptStatus>(SCRIPT_STATUS_JSON);
+        assert!(matches!(status.script_type, ScriptType::Lock));
+        assert_eq!(status.block_number.value(), 0xa1b2c3);
+        assert_eq!(
+            serialized,
+            serde_json::from_str::<Value>(SCRIPT_STATUS_JSON).unwrap()
+        );
+
+        for (command, json) in [
+            (SetScriptsCommand::All, "\"all\""),
+            (SetScriptsCommand::Partial, "\"partial\""),
+            (SetScriptsCommand::Delete, "\"delete\""),
+        ] {
+            assert_eq!(serde_json::to_string(&command).unwrap(), json);
+            assert_eq!(
+                serde_json::from_str::<SetScriptsCommand>(json).unwrap(),
+                command
+            );
+        }
+    }
+
+    #[test]
+    fn test_fetch_status_serde() {
+        let json = r#"{"status": "added", "timestamp": "0x18bf1a1b0d8"}"#;
+        let (status, serialized) = round_trip::<FetchStatus<HeaderView>>(json);
+        assert_eq!(
+            status,
+            FetchStatus::Added {
+                timestamp: 0x18bf1a1b0d8u64.into()
+            }
+        );
+        assert_eq!(serialized, serde_json::from_str::<Value>(json).unwrap());
+
+        let json = r#"{"status": "fetching", "first_sent": "0x18bf1a1c0a0"}"#;
+        let (status, _) = round_trip::<FetchStatus<HeaderView>>(json);
+        assert_eq!(
+            status,
+            FetchStatus::Fetching {
+                first_sent: 0x18bf1a1c0a0u64.into()
+            }
+        );
+
+        let json = r#"{"status": "not_found"}"#;
+        let (status, serialized) = round_trip::<FetchStatus<HeaderView>>(json);
+        assert_eq!(status, FetchStatus::NotFound);
+        assert_eq!(serialized, serde_json::from_str::<Value>(json).unwrap());
+
+        let json = format!(r#"{{"status": "fetched", "data": {}}}"#, header_json());
+        let (status, serialized) = round_trip::<FetchStatus<HeaderView>>(&json);
+        match status {
+            FetchStatus::Fetched { data } => {
+                assert_eq!(data.inner.number.value(), 0);
+                let hash: H256 = ckb_types::core::HeaderView::from(data.clone())
+                    .hash()
+                    .unpack();
+                assert_eq!(hash, data.hash);
+            }
+            _ => panic!("expect fetched status"),
+        }
+        assert_eq!(serialized, serde_json::from_str::<Value>(&json).unwrap());
+
+        let json = format!(
+            r#"{{
+                "status": "fetched",
+                "data": {{
+                    "transaction": {},
+                    "cycles": "0x1a2b3c",
+                    "time_added_to_pool": null,
+                    "tx_status": {{
+                        "status": "committed",
+                        "block_number": "0x0",
+                        "block_hash": "0x10639e0895502b5688a6be8cf69460d76541bfa4821629d86d62ba0aae3f9606",
+                        "tx_index": "0x1",
+                        "reason": null
+                    }}
+                }}
+            }}"#,
+            transaction_json()
+        );
+        let (status, _) = round_trip::<FetchStatus<TransactionWithStatus>>(&json);
+        match status {
+            FetchStatus::Fetched { data } => {
+                assert_eq!(data.cycles.map(|cycles| cycles.value()), Some(0x1a2b3c));
+                let tx = data.transaction.unwrap();
+                assert_eq!(tx.inner.outputs.len(), 2);
+                let tx_hash: H256 = ckb_types::packed::Transaction::from(tx.inner)
+                    .calc_tx_hash()
+                    .unpack();
+                assert_eq!(tx_hash, tx.hash);
+            }
+            _ => panic!("expect fetched status"),
+        }
+    }
+
+    #[test]
+    fn test_tx_serde() {
+        let json = format!(
+            r#"{{
+                "transaction": {},
+                "block_number": "0x0",
+                "tx_index": "0x1",
+                "io_index": "0x0",
+                "io_type": "output"
+            }}"#,
+            transaction_json()
+        );
+        let (tx, serialized) = round_trip::<Tx>(&json);
+        assert!(matches!(tx, Tx::Ungrouped(_)));
+        assert_eq!(serialized, serde_json::from_str::<Value>(&json).unwrap());
+
+        let json = format!(
+            r#"{{
+                "transaction": {},
+                "block_number": "0x0",
+                "tx_index": "0x1",
+                "cells": [["input", "0x0"], ["output", "0x0"]]
+            }}"#,
+            transaction_json()
+        );
+        let (tx, serialized) = round_trip::<Tx>(&json);
+        match tx {
+            Tx::Grouped(tx) => assert_eq!(tx.cells.len(), 2),
+            _ => panic!("expect grouped tx"),
+        }
+        assert_eq!(serialized, serde_json::from_str::<Value>(&json).unwrap());
+    }
+}
@@ -0,0 +1,450 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ckb_jsonrpc_types::{Status, Transaction};
+use ckb_types::{core::TransactionView, prelude::*, H256};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{CkbRpcClient, RpcError, TransactionSubmitError};
+
+/// The transaction first broadcast for an intent key
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SendRecord {
+    pub tx_hash: H256,
+    /// The tip block number when the transaction was broadcast
+    pub sent_at: u64,
+}
+
+/// Persistent mapping from the caller's intent key to the sent transaction.
+///
+/// `put` and `remove` must be atomic: after a crash the store contains either
+/// the old record or the new one.
+pub trait Store {
+    fn get(&self, intent_key: &str) -> Result<Option<SendRecord>, anyhow::Error>;
+    fn put(&mut self, intent_key: &str, record: SendRecord) -> Result<(), anyhow::Error>;
+    fn remove(&mut self, intent_key: &str) -> Result<(), anyhow::Error>;
+}
+
+/// A store only kept in memory, the records are lost when the process exits.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    records: HashMap<String, SendRecord>,
+}
+
+impl Store for MemoryStore {
+    fn get(&self, intent_key: &str) -> Result<Option<SendRecord>, anyhow::Error> {
+        Ok(self.records.get(intent_key).cloned())
+    }
+    fn put(&mut self, intent_key: &str, record: SendRecord) -> Result<(), anyhow::Error> {
+        self.records.insert(intent_key.to_string(), record);
+        Ok(())
+    }
+    fn remove(&mut self, intent_key: &str) -> Result<(), anyhow::Error> {
+        self.records.remove(intent_key);
+        Ok(())
+    }
+}
+
+/// A store saved as a json file. Every update writes a temporary file then
+/// renames it over the old one, so the file is never partially written.
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    path: PathBuf,
+    records: HashMap<String, SendRecord>,
+}
+
+impl FileStore {
+    /// Open the store, the file is created on the first update if it does not exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<FileStore, anyhow::Error> {
+        let path = path.as_ref().to_path_buf();
+        let records = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(&path)?)?
+        } else {
+            HashMap::new()
+        };
+        Ok(FileStore { path, records })
+    }
+
+    fn save(&self, records: &HashMap<String, SendRecord>) -> Result<(), anyhow::Error> {
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(records)?)?;
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+impl Store for FileStore {
+    fn get(&self, intent_key: &str) -> Result<Option<SendRecord>, anyhow::Error> {
+        Ok(self.records.get(intent_key).cloned())
+    }
+    fn put(&mut self, intent_key: &str, record: SendRecord) -> Result<(), anyhow::Error> {
+        let mut records = self.records.clone();
+        records.insert(intent_key.to_string(), record);
+        self.save(&records)?;
+        self.records = records;
+        Ok(())
+    }
+    fn remove(&mut self, intent_key: &str) -> Result<(), anyhow::Error> {
+        let mut records = self.records.clone();
+        if records.remove(intent_key).is_some() {
+            self.save(&records)?;
+            self.records = records;
+        }
+        Ok(())
+    }
+}
+
+/// The node methods used by `IdempotentSender`
+pub trait SendTransactionRpc {
+    fn get_tip_block_number(&self) -> Result<u64, RpcError>;
+    /// The status of the transaction, only look at the chain if `only_committed` is true
+    fn get_transaction_status(
+        &self,
+        tx_hash: H256,
+        only_committed: bool,
+    ) -> Result<Status, RpcError>;
+    fn send_transaction(&self, tx: Transaction) -> Result<H256, TransactionSubmitError>;
+}
+
+impl SendTransactionRpc for CkbRpcClient {
+    fn get_tip_block_number(&self) -> Result<u64, RpcError> {
+        CkbRpcClient::get_tip_block_number(self).map(Into::into)
+    }
+    fn get_transaction_status(
+        &self,
+        tx_hash: H256,
+        only_committed: bool,
+    ) -> Result<Status, RpcError> {
+        let response = if only_committed {
+            self.get_only_committed_transaction_status(tx_hash)?
+        } else {
+            CkbRpcClient::get_transaction_status(self, tx_hash)?
+        };
+        Ok(response.tx_status.status)
+    }
+    fn send_transaction(&self, tx: Transaction) -> Result<H256, TransactionSubmitError> {
+        self.send_transaction_typed(tx, None)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum IdempotentSendError {
+    #[error("store error: `{0}`")]
+    Store(anyhow::Error),
+
+    #[error("build transaction error: `{0}`")]
+    Build(anyhow::Error),
+
+    #[error(transparent)]
+    Rpc(#[from] RpcError),
+
+    #[error(transparent)]
+    Submit(#[from] TransactionSubmitError),
+
+    #[error("transaction `{tx_hash:#x}` is `{status:?}`, can not rebuild it before block `{rebuild_at}`")]
+    NotSettled {
+        tx_hash: H256,
+        status: Status,
+        rebuild_at: u64,
+    },
+}
+
+/// Send a transaction at most once per intent key.
+///
+/// A retried job usually rebuilds a logically identical transaction with
+/// other cells, so the transaction hash can not be used to deduplicate sends.
+/// Instead the caller names the intent (e.g. a payout id), and the hash of the
+/// first broadcast transaction is persisted in the `Store`:
+///
+///   * committed, proposed or pending: the stored hash is returned, nothing is sent.
+///   * rejected or unknown for `rebuild_after_blocks` blocks: the transaction is
+///     checked again on chain (only committed), and a new one is built and
+///     replaces the record if it is still not committed.
+///   * rejected or unknown for less than `rebuild_after_blocks` blocks:
+///     `IdempotentSendError::NotSettled` is returned, retry later.
+pub struct IdempotentSender<S> {
+    store: S,
+    rebuild_after_blocks: u64,
+}
+
+impl<S: Store> IdempotentSender<S> {
+    pub fn new(store: S, rebuild_after_blocks: u64) -> IdempotentSender<S> {
+        IdempotentSender {
+            store,
+            rebuild_after_blocks,
+        }
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Send the transaction built by `build_fn` unless a transaction for
+    /// `intent_key` is already committed or still in flight. Returns the hash
+    /// of the transaction representing the intent.
+    pub fn send<F>(
+        &mut self,
+        intent_key: &str,
+        build_fn: F,
+        rpc: &dyn SendTransactionRpc,
+    ) -> Result<H256, IdempotentSendError>
+    where
+        F: FnOnce() -> Result<TransactionView, anyhow::Error>,
+    {
+        let prev_record = self
+            .store
+            .get(intent_key)
+            .map_err(IdempotentSendError::Store)?;
+        let tip_number = rpc.get_tip_block_number()?;
+        if let Some(record) = prev_record.as_ref() {
+            let status = rpc.get_transaction_status(record.tx_hash.clone(), false)?;
+            match status {
+                Status::Committed | Status::Proposed | Status::Pending => {
+                    return Ok(record.tx_hash.clone());
+                }
+                Status::Rejected | Status::Unknown => {}
+            }
+            let rebuild_at = record.sent_at.saturating_add(self.rebuild_after_blocks);
+            if tip_number < rebuild_at {
+                return Err(IdempotentSendError::NotSettled {
+                    tx_hash: record.tx_hash.clone(),
+                    status,
+                    rebuild_at,
+                });
+            }
+            // The pool of this node may have rejected or dropped the
+            // transaction while it is committed through another node.
+            if rpc.get_transaction_status(record.tx_hash.clone(), true)? == Status::Committed {
+                return Ok(record.tx_hash.clone());
+            }
+        }
+
+        let tx = build_fn().map_err(IdempotentSendError::Build)?;
+        let tx_hash: H256 = tx.hash().unpack();
+        // Persist before broadcasting, a crash after the broadcast must not
+        // lead to a second transaction.
+        let record = SendRecord {
+            tx_hash: tx_hash.clone(),
+            sent_at: tip_number,
+        };
+        self.store
+            .put(intent_key, record)
+            .map_err(IdempotentSendError::Store)?;
+        match rpc.send_transaction(tx.data().into()) {
+            Ok(_) | Err(TransactionSubmitError::DuplicatedTransaction) => Ok(tx_hash),
+            // The result is unknown, keep the record
+            Err(err @ TransactionSubmitError::Rpc(_)) => Err(err.into()),
+            // The node refused the transaction, it was not broadcast
+            Err(err) => {
+                match prev_record {
+                    Some(prev_record) => self.store.put(intent_key, prev_record),
+                    None => self.store.remove(intent_key),
+                }
+                .map_err(IdempotentSendError::Store)?;
+                Err(err.into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+
+    use ckb_types::{
+        core::TransactionBuilder,
+        packed::{self, CellOutput},
+    };
+
+    #[derive(Default)]
+    struct MockRpc {
+        tip: Cell<u64>,
+        // (status in the pool of the node, committed on chain)
+        statuses: RefCell<HashMap<H256, (Status, bool)>>,
+        sent: RefCell<Vec<H256>>,
+        refuse: Cell<bool>,
+    }
+
+    impl MockRpc {
+        fn set_status(&self, tx_hash: &H256, status: Status, committed: bool) {
+            self.statuses
+                .borrow_mut()
+                .insert(tx_hash.clone(), (status, committed));
+        }
+    }
+
+    impl SendTransactionRpc for MockRpc {
+        fn get_tip_block_number(&self) -> Result<u64, RpcError> {
+            Ok(self.tip.get())
+        }
+        fn get_transaction_status(
+            &self,
+            tx_hash: H256,
+            only_committed: bool,
+        ) -> Result<Status, RpcError> {
+            let (status, committed) = self
+                .statuses
+                .borrow()
+                .get(&tx_hash)
+                .cloned()
+                .unwrap_or((Status::Unknown, false));
+            if only_committed {
+                Ok(if committed {
+                    Status::Committed
+                } else {
+                    Status::Unknown
+                })
+            } else {
+                Ok(status)
+            }
+        }
+        fn send_transaction(&self, tx: Transaction) -> Result<H256, TransactionSubmitError> {
+            if self.refuse.get() {
+                return Err(TransactionSubmitError::FeeRateTooLow { min: 1000 });
+            }
+            let tx_hash: H256 = packed::Transaction::from(tx).calc_tx_hash().unpack();
+            self.sent.borrow_mut().push(tx_hash.clone());
+            self.set_status(&tx_hash, Status::Pending, false);
+            Ok(tx_hash)
+        }
+    }
+
+    // Every call selects other "cells", so the transaction hash changes
+    fn builder(nonce: &Cell<u64>) -> impl FnOnce() -> Result<TransactionView, anyhow::Error> + '_ {
+        move || {
+            nonce.set(nonce.get() + 1);
+            Ok(TransactionBuilder::default()
+                .output(
+                    CellOutput::new_builder()
+                        .capacity(nonce.get().pack())
+                        .build(),
+                )
+                .output_data(Default::default())
+                .build())
+        }
+    }
+
+    fn store_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("ckb-sdk-test-idempotent-{}.json", name));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_send_once() {
+        let rpc = MockRpc::default();
+        let nonce = Cell::new(0);
+        let mut sender = IdempotentSender::new(MemoryStore::default(), 10);
+        let tx_hash = sender.send("payout-1", builder(&nonce), &rpc).unwrap();
+        assert_eq!(rpc.sent.borrow().as_slice(), &[tx_hash.clone()]);
+
+        // pending and committed: the stored hash is returned
+        assert_eq!(
+            sender.send("payout-1", builder(&nonce), &rpc).unwrap(),
+            tx_hash
+        );
+        rpc.set_status(&tx_hash, Status::Committed, true);
+        rpc.tip.set(100);
+        assert_eq!(
+            sender.send("payout-1", builder(&nonce), &rpc).unwrap(),
+            tx_hash
+        );
+        assert_eq!(rpc.sent.borrow().len(), 1);
+        assert_eq!(nonce.get(), 1);
+
+        // another intent
+        let tx_hash2 = sender.send("payout-2", builder(&nonce), &rpc).unwrap();
+        assert_ne!(tx_hash2, tx_hash);
+        assert_eq!(rpc.sent.borrow().len(), 2);
+    }
+
+    #[test]
+    fn test_rebuild_after_blocks() {
+        let rpc = MockRpc::default();
+        let nonce = Cell::new(0);
+        let path = store_path("rebuild");
+        let mut sender = IdempotentSender::new(FileStore::open(&path).unwrap(), 10);
+        rpc.tip.set(100);
+        let tx_hash = sender.send("payout", builder(&nonce), &rpc).unwrap();
+        rpc.set_status(&tx_hash, Status::Rejected, false);
+        drop(sender);
+
+        // restart: the record is loaded from the file
+        let mut sender = IdempotentSender::new(FileStore::open(&path).unwrap(), 10);
+        rpc.tip.set(109);
+        let err = sender.send("payout", builder(&nonce), &rpc).unwrap_err();
+        assert!(matches!(
+            err,
+            IdempotentSendError::NotSettled {
+                status: Status::Rejected,
+                rebuild_at: 110,
+                ..
+            }
+        ));
+        assert_eq!(nonce.get(), 1);
+
+        rpc.tip.set(110);
+        let new_tx_hash = sender.send("payout", builder(&nonce), &rpc).unwrap();
+        assert_ne!(new_tx_hash, tx_hash);
+        assert_eq!(
+            rpc.sent.borrow().as_slice(),
+            &[tx_hash, new_tx_hash.clone()]
+        );
+        drop(sender);
+
+        let store = FileStore::open(&path).unwrap();
+        assert_eq!(
+            store.get("payout").unwrap(),
+            Some(SendRecord {
+                tx_hash: new_tx_hash,
+                sent_at: 110,
+            })
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_rejected_but_committed() {
+        let rpc = MockRpc::default();
+        let nonce = Cell::new(0);
+        let path = store_path("race");
+        let mut sender = IdempotentSender::new(FileStore::open(&path).unwrap(), 10);
+        let tx_hash = sender.send("payout", builder(&nonce), &rpc).unwrap();
+        drop(sender);
+
+        // the node dropped the transaction but it is committed through another node
+        rpc.set_status(&tx_hash, Status::Unknown, true);
+        rpc.tip.set(20);
+        let mut sender = IdempotentSender::new(FileStore::open(&path).unwrap(), 10);
+        assert_eq!(
+            sender.send("payout", builder(&nonce), &rpc).unwrap(),
+            tx_hash
+        );
+        assert_eq!(rpc.sent.borrow().len(), 1);
+        assert_eq!(nonce.get(), 1);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_refused_by_node() {
+        let rpc = MockRpc::default();
+        let nonce = Cell::new(0);
+        let mut sender = IdempotentSender::new(MemoryStore::default(), 10);
+        rpc.refuse.set(true);
+        let err = sender.send("payout", builder(&nonce), &rpc).unwrap_err();
+        assert!(matches!(
+            err,
+            IdempotentSendError::Submit(TransactionSubmitError::FeeRateTooLow { .. })
+        ));
+        // nothing was broadcast, so the record is removed and the next send
+        // does not wait
+        assert_eq!(sender.store().get("payout").unwrap(), None);
+        rpc.refuse.set(false);
+        let tx_hash = sender.send("payout", builder(&nonce), &rpc).unwrap();
+        assert_eq!(rpc.sent.borrow().as_slice(), &[tx_hash]);
+    }
+}
@@ -1,12 +1,25 @@
+mod balance;
 mod ckb;
 pub mod ckb_indexer;
 pub mod ckb_light_client;
+mod idempotent;
+mod submit_error;
+mod tx_history;
+mod wait_tx;
 
 use anyhow::anyhow;
+pub use balance::{Balance, BalanceOptions};
 pub use ckb::CkbRpcClient;
 pub use ckb_indexer::IndexerRpcClient;
 use ckb_jsonrpc_types::{JsonBytes, ResponseFormat};
 pub use ckb_light_client::LightClientRpcClient;
+pub use idempotent::{
+    FileStore, IdempotentSendError, IdempotentSender, MemoryStore, SendRecord, SendTransactionRpc,
+    Store,
+};
+pub use submit_error::TransactionSubmitError;
+pub use tx_history::{TransactionIterator, TxHistoryCursor, TxRecord, TxSummary};
+pub use wait_tx::{TxCommitStatus, WaitError, WaitOptions};
 
 use thiserror::Error;
 
@@ -37,18 +50,26 @@ macro_rules! jsonrpc {
             pub client: reqwest::blocking::Client,
             pub url: reqwest::Url,
             pub id: std::sync::atomic::AtomicU64,
+            /// Report the request counts and latencies when set
+            pub metrics: Option<std::sync::Arc<dyn $crate::metrics::MetricsSink>>,
         }
 
         impl Clone for $struct_name {
             fn clone(&self) -> Self {
-                Self::new(&self.url.to_string())
+                let mut client = Self::new(&self.url.to_string());
+                client.metrics = self.metrics.clone();
+                client
             }
         }
 
         impl $struct_name {
             pub fn new(uri: &str) -> Self {
                 let url = reqwest::Url::parse(uri).expect("ckb uri, e.g. \"http://127.0.0.1:8114\"");
-                $struct_name { url, id: 0.into(), client: reqwest::blocking::Client::new(), }
+                $struct_name { url, id: 0.into(), client: reqwest::blocking::Client::new(), metrics: None, }
+            }
+
+            pub fn set_metrics_sink(&mut self, sink: std::sync::Arc<dyn $crate::metrics::MetricsSink>) {
+                self.metrics = Some(sink);
             }
 
             pub fn post<PARAM, RET>(&self, method:&str, params: PARAM)->Result<RET, $crate::rpc::RpcError>
@@ -57,6 +78,14 @@ macro_rules! jsonrpc {
                 RET: serde::de::DeserializeOwned,
             {
                 let params = serde_json::to_value(params)?;
+                self.send_request(method, params)
+            }
+
+            fn send_request<RET>(&self, method: &str, params: serde_json::Value) -> Result<RET, $crate::rpc::RpcError>
+            where
+                RET: serde::de::DeserializeOwned,
+            {
+                let start_time = self.metrics.as_ref().map(|_| std::time::Instant::now());
                 let id = self.id.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
 
                 let mut req_json = serde_json::Map::new();
@@ -65,33 +94,8 @@ macro_rules! jsonrpc {
                 req_json.insert("method".to_owned(), serde_json::json!(method));
                 req_json.insert("params".to_owned(), params);
 
-                let resp = self.client.post(self.url.clone()).json(&req_json).send()?;
-                let output = resp.json::<jsonrpc_core::response::Output>()?;
-                match output {
-                    jsonrpc_core::response::Output::Success(success) => {
-                        serde_json::from_value(success.result).map_err(Into::into)
-                    },
-                    jsonrpc_core::response::Output::Failure(failure) => {
-                        Err(failure.error.into())
-                    }
-                }
-
-            }
-
-            $(
-                $(#[$attr])*
-                pub fn $method(&$selff $(, $arg_name: $arg_ty)*) -> Result<$return_ty, $crate::rpc::RpcError> {
-                    let method = String::from(stringify!($method));
-                    let params = $crate::serialize_parameters!($($arg_name,)*);
-                    let id = $selff.id.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-
-                    let mut req_json = serde_json::Map::new();
-                    req_json.insert("id".to_owned(), serde_json::json!(id));
-                    req_json.insert("jsonrpc".to_owned(), serde_json::json!("2.0"));
-                    req_json.insert("method".to_owned(), serde_json::json!(method));
-                    req_json.insert("params".to_owned(), params);
-
-                    let resp = $selff.client.post($selff.url.clone()).json(&req_json).send()?;
+                let result = (|| -> Result<RET, $crate::rpc::RpcError> {
+                    let resp = self.client.post(self.url.clone()).json(&req_json).send()?;
                     let output = resp.json::<jsonrpc_core::response::Output>()?;
                     match output {
                         jsonrpc_core::response::Output::Success(success) => {
@@ -101,6 +105,28 @@ macro_rules! jsonrpc {
                             Err(failure.error.into())
                         }
                     }
+                })();
+                if let (Some(sink), Some(start_time)) = (self.metrics.as_ref(), start_time) {
+                    let status = if result.is_ok() { "ok" } else { "error" };
+                    sink.increment_counter(
+                        $crate::metrics::RPC_REQUESTS,
+                        &[("method", method), ("status", status)],
+                        1,
+                    );
+                    sink.record_histogram(
+                        $crate::metrics::RPC_LATENCY_SECONDS,
+                        &[("method", method)],
+                        start_time.elapsed().as_secs_f64(),
+                    );
+                }
+                result
+            }
+
+            $(
+                $(#[$attr])*
+                pub fn $method(&$selff $(, $arg_name: $arg_ty)*) -> Result<$return_ty, $crate::rpc::RpcError> {
+                    let params = $crate::serialize_parameters!($($arg_name,)*);
+                    $selff.send_request(stringify!($method), params)
                 }
             )*
         }
@@ -0,0 +1,282 @@
+use ckb_types::{packed::OutPoint, prelude::*};
+use thiserror::Error;
+
+use super::RpcError;
+
+// Error codes of the CKB node, see `ckb/rpc/src/error.rs`
+const TRANSACTION_FAILED_TO_RESOLVE: i64 = -301;
+const TRANSACTION_FAILED_TO_VERIFY: i64 = -302;
+const POOL_REJECTED_TRANSACTION_BY_MIN_FEE_RATE: i64 = -1104;
+const POOL_REJECTED_TRANSACTION_BY_MAX_ANCESTORS_COUNT_LIMIT: i64 = -1105;
+const POOL_IS_FULL: i64 = -1106;
+const POOL_REJECTED_DUPLICATED_TRANSACTION: i64 = -1107;
+const POOL_REJECTED_MALFORMED_TRANSACTION: i64 = -1108;
+const TRANSACTION_EXPIRED: i64 = -1109;
+const POOL_REJECTED_TRANSACTION_BY_SIZE_LIMIT: i64 = -1110;
+const POOL_REJECTED_RBF: i64 = -1111;
+
+/// The error of `send_transaction`, parsed from the error code and message
+/// returned by the CKB node.
+#[derive(Error, Debug)]
+pub enum TransactionSubmitError {
+    #[error("transaction already exists in transaction pool")]
+    DuplicatedTransaction,
+
+    #[error("input or cell dep is already spent: `{0}`")]
+    DeadOutPoint(OutPoint),
+
+    #[error("input or cell dep is unknown: `{0}`")]
+    UnknownOutPoint(OutPoint),
+
+    #[error("transaction fee rate is lower than the min fee rate `{min}` shannons/KW")]
+    FeeRateTooLow { min: u64 },
+
+    #[error("transaction exceeded maximum ancestors count limit")]
+    ExceededMaximumAncestorsCount,
+
+    #[error("transaction size exceeded the limit")]
+    ExceededTransactionSizeLimit,
+
+    #[error("transaction pool is full")]
+    PoolIsFull,
+
+    #[error("transaction expired in transaction pool")]
+    TransactionExpired,
+
+    #[error("malformed transaction: `{0}`")]
+    MalformedTransaction(String),
+
+    #[error("replace-by-fee rejected: `{0}`")]
+    RbfRejected(String),
+
+    #[error("script `{script}` exceeded maximum cycles `{max_cycles}`")]
+    ExceededMaximumCycles { script: String, max_cycles: u64 },
+
+    #[error("script `{script}` verification failed: `{cause}`")]
+    ScriptVerificationFailed {
+        /// The failed script, e.g. `Inputs[0].Lock`
+        script: String,
+        exit_code: Option<i8>,
+        cause: String,
+    },
+
+    #[error("transaction rejected, code: `{code}`, message: `{message}`")]
+    Raw { code: i64, message: String },
+
+    #[error("rpc error: `{0}`")]
+    Rpc(RpcError),
+}
+
+impl TransactionSubmitError {
+    /// Parse the jsonrpc error returned by the node, unknown errors are kept
+    /// as `TransactionSubmitError::Raw`.
+    pub fn from_jsonrpc_error(err: &jsonrpc_core::Error) -> TransactionSubmitError {
+        let code = err.code.code();
+        let message = err.message.as_str();
+        let parsed = match code {
+            POOL_REJECTED_DUPLICATED_TRANSACTION => {
+                Some(TransactionSubmitError::DuplicatedTransaction)
+            }
+            TRANSACTION_FAILED_TO_RESOLVE => {
+                if let Some(out_point) = parse_out_point(message, "Dead(OutPoint(") {
+                    Some(TransactionSubmitError::DeadOutPoint(out_point))
+                } else {
+                    parse_out_point(message, "Unknown(OutPoint(")
+                        .map(TransactionSubmitError::UnknownOutPoint)
+                }
+            }
+            POOL_REJECTED_TRANSACTION_BY_MIN_FEE_RATE => {
+                parse_u64_after(message, "min fee rate is ")
+                    .map(|min| TransactionSubmitError::FeeRateTooLow { min })
+            }
+            POOL_REJECTED_TRANSACTION_BY_MAX_ANCESTORS_COUNT_LIMIT => {
+                Some(TransactionSubmitError::ExceededMaximumAncestorsCount)
+            }
+            POOL_REJECTED_TRANSACTION_BY_SIZE_LIMIT => {
+                Some(TransactionSubmitError::ExceededTransactionSizeLimit)
+            }
+            POOL_IS_FULL => Some(TransactionSubmitError::PoolIsFull),
+            TRANSACTION_EXPIRED => Some(TransactionSubmitError::TransactionExpired),
+            POOL_REJECTED_MALFORMED_TRANSACTION => Some(
+                TransactionSubmitError::MalformedTransaction(strip_kind(message).to_string()),
+            ),
+            POOL_REJECTED_RBF => Some(TransactionSubmitError::RbfRejected(
+                strip_kind(message).to_string(),
+            )),
+            TRANSACTION_FAILED_TO_VERIFY => parse_script_error(message),
+            _ => None,
+        };
+        parsed.unwrap_or_else(|| TransactionSubmitError::Raw {
+            code,
+            message: message.to_string(),
+        })
+    }
+}
+
+impl From<RpcError> for TransactionSubmitError {
+    fn from(err: RpcError) -> TransactionSubmitError {
+        match err {
+            RpcError::Rpc(err) => TransactionSubmitError::from_jsonrpc_error(&err),
+            err => TransactionSubmitError::Rpc(err),
+        }
+    }
+}
+
+// Remove the `PoolRejectedXXX: ` prefix of the message
+fn strip_kind(message: &str) -> &str {
+    message
+        .split_once(": ")
+        .map(|(_kind, rest)| rest)
+        .unwrap_or(message)
+}
+
+fn parse_u64_after(message: &str, prefix: &str) -> Option<u64> {
+    let start = message.find(prefix)? + prefix.len();
+    let digits: String = message[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.parse().ok()
+}
+
+// The out point is in debug format: `OutPoint(0x{tx_hash}{index})`
+fn parse_out_point(message: &str, prefix: &str) -> Option<OutPoint> {
+    let start = message.find(prefix)? + prefix.len();
+    let hex_str = message[start..].strip_prefix("0x")?;
+    let hex_str = hex_str.get(0..OutPoint::TOTAL_SIZE * 2)?;
+    let bytes = (0..hex_str.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(&hex_str[idx..idx + 2], 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    OutPoint::from_slice(&bytes).ok()
+}
+
+// Script error format: `TransactionScriptError { source: Inputs[0].Lock, cause: ... }`
+fn parse_script_error(message: &str) -> Option<TransactionSubmitError> {
+    let prefix = "TransactionScriptError { source: ";
+    let start = message.find(prefix)? + prefix.len();
+    let (script, rest) = message[start..].split_once(", cause: ")?;
+    let cause = rest.trim_end_matches(|c| c == ')' || c == '}').trim();
+    if let Some(max_cycles) = parse_u64_after(cause, "ExceededMaximumCycles: expect cycles <= ") {
+        return Some(TransactionSubmitError::ExceededMaximumCycles {
+            script: script.to_string(),
+            max_cycles,
+        });
+    }
+    let exit_code = cause
+        .strip_prefix("ValidationFailure: see error code ")
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|code| code.parse::<i8>().ok());
+    Some(TransactionSubmitError::ScriptVerificationFailed {
+        script: script.to_string(),
+        exit_code,
+        cause: cause.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TransactionSubmitError;
+    use ckb_types::{h256, packed::OutPoint, prelude::*};
+
+    fn parse(payload: &str) -> TransactionSubmitError {
+        let err: jsonrpc_core::Error = serde_json::from_str(payload).unwrap();
+        TransactionSubmitError::from_jsonrpc_error(&err)
+    }
+
+    #[test]
+    fn test_duplicated_transaction() {
+        let err = parse(
+            r#"{"code":-1107,"message":"PoolRejectedDuplicatedTransaction: Transaction(Byte32(0xa0ef4eb5f4ceeb08a4c8524d84c5da95dce2f608e0ca2ec8091191b0f330c6e3)) already exists in transaction_pool","data":"Duplicated(Byte32(0xa0ef4eb5f4ceeb08a4c8524d84c5da95dce2f608e0ca2ec8091191b0f330c6e3))"}"#,
+        );
+        assert!(matches!(err, TransactionSubmitError::DuplicatedTransaction));
+    }
+
+    #[test]
+    fn test_dead_and_unknown_out_point() {
+        let expected = OutPoint::new(
+            h256!("0xa563884b3686078ec7e7677a5f86449b15cf2693f3c1241766c6996f206cc541").pack(),
+            1,
+        );
+        let err = parse(
+            r#"{"code":-301,"message":"TransactionFailedToResolve: Resolve failed Dead(OutPoint(0xa563884b3686078ec7e7677a5f86449b15cf2693f3c1241766c6996f206cc54101000000))","data":"Resolve(Dead(OutPoint(0xa563884b3686078ec7e7677a5f86449b15cf2693f3c1241766c6996f206cc54101000000)))"}"#,
+        );
+        match err {
+            TransactionSubmitError::DeadOutPoint(out_point) => assert_eq!(out_point, expected),
+            err => panic!("unexpected error: {:?}", err),
+        }
+        let err = parse(
+            r#"{"code":-301,"message":"TransactionFailedToResolve: Resolve failed Unknown(OutPoint(0xa563884b3686078ec7e7677a5f86449b15cf2693f3c1241766c6996f206cc54101000000))","data":"Resolve(Unknown(OutPoint(0xa563884b3686078ec7e7677a5f86449b15cf2693f3c1241766c6996f206cc54101000000)))"}"#,
+        );
+        match err {
+            TransactionSubmitError::UnknownOutPoint(out_point) => assert_eq!(out_point, expected),
+            err => panic!("unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_fee_rate_too_low() {
+        let err = parse(
+            r#"{"code":-1104,"message":"PoolRejectedTransactionByMinFeeRate: The min fee rate is 1000 shannons/KW, requiring a transaction fee of at least 464 shannons, but the fee provided is only 0","data":"LowFeeRate(FeeRate(1000), 464, 0)"}"#,
+        );
+        assert!(matches!(
+            err,
+            TransactionSubmitError::FeeRateTooLow { min: 1000 }
+        ));
+    }
+
+    #[test]
+    fn test_exceeded_maximum_ancestors_count() {
+        let err = parse(
+            r#"{"code":-1105,"message":"PoolRejectedTransactionByMaxAncestorsCountLimit: Transaction exceeded maximum ancestors count limit; try later","data":"ExceededMaximumAncestorsCount"}"#,
+        );
+        assert!(matches!(
+            err,
+            TransactionSubmitError::ExceededMaximumAncestorsCount
+        ));
+    }
+
+    #[test]
+    fn test_script_verification_failed() {
+        let err = parse(
+            r#"{"code":-302,"message":"TransactionFailedToVerify: Verification failed Script(TransactionScriptError { source: Inputs[0].Lock, cause: ValidationFailure: see error code -31 on page https://nervosnetwork.github.io/ckb-script-error-codes/by-type-hash/9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8.html#-31 })","data":"Verification(Error { kind: Script, inner: TransactionScriptError { source: Inputs[0].Lock, cause: ValidationFailure: see error code -31 on page https://nervosnetwork.github.io/ckb-script-error-codes/by-type-hash/9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8.html#-31 } })"}"#,
+        );
+        match err {
+            TransactionSubmitError::ScriptVerificationFailed {
+                script, exit_code, ..
+            } => {
+                assert_eq!(script, "Inputs[0].Lock");
+                assert_eq!(exit_code, Some(-31));
+            }
+            err => panic!("unexpected error: {:?}", err),
+        }
+
+        let err = parse(
+            r#"{"code":-302,"message":"TransactionFailedToVerify: Verification failed Script(TransactionScriptError { source: Inputs[0].Type, cause: ExceededMaximumCycles: expect cycles <= 70000000 })","data":"Verification(Error { kind: Script, inner: TransactionScriptError { source: Inputs[0].Type, cause: ExceededMaximumCycles: expect cycles <= 70000000 } })"}"#,
+        );
+        match err {
+            TransactionSubmitError::ExceededMaximumCycles { script, max_cycles } => {
+                assert_eq!(script, "Inputs[0].Type");
+                assert_eq!(max_cycles, 70000000);
+            }
+            err => panic!("unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_raw_fallback() {
+        let err = parse(
+            r#"{"code":-1112,"message":"PoolRejectedInvalidated: Transaction is invalidated","data":"Invalidated"}"#,
+        );
+        match err {
+            TransactionSubmitError::Raw { code, message } => {
+                assert_eq!(code, -1112);
+                assert_eq!(
+                    message,
+                    "PoolRejectedInvalidated: Transaction is invalidated"
+                );
+            }
+            err => panic!("unexpected error: {:?}", err),
+        }
+    }
+}
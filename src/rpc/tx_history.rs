@@ -0,0 +1,455 @@
+use std::collections::{HashMap, VecDeque};
+
+use anyhow::anyhow;
+use ckb_jsonrpc_types::JsonBytes;
+use ckb_types::{
+    bytes::Bytes,
+    packed::{Byte32, CellOutput, Script},
+    prelude::*,
+    H256,
+};
+
+use super::{
+    ckb_indexer::{CellType, Order, SearchKey, Tx},
+    IndexerRpcClient, RpcError,
+};
+use crate::traits::{TransactionDependencyError, TransactionDependencyProvider};
+
+/// The position after the last transaction returned by a
+/// `TransactionIterator`, persist it to resume the iteration later.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct TxHistoryCursor {
+    /// The `after` cursor of the page the next record is in, `None` for the
+    /// first page
+    pub after: Option<JsonBytes>,
+    /// The number of records of that page already returned
+    pub skip: usize,
+}
+
+/// A transaction and its cells matched by the search key
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TxRecord {
+    pub tx_hash: H256,
+    pub block_number: u64,
+    pub tx_index: u32,
+    /// The cell type and the input/output index of the matched cells
+    pub cells: Vec<(CellType, u32)>,
+}
+
+/// The balance changes of a lock script made by a transaction
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TxSummary {
+    pub tx_hash: H256,
+    pub block_number: u64,
+    /// The output capacity minus the input capacity of the lock
+    pub ckb_delta: i128,
+    /// The udt amount changes by type script hash, the cells with a type
+    /// script and at least 16 bytes data are counted as udt cells.
+    pub udt_deltas: HashMap<Byte32, i128>,
+}
+
+impl TxRecord {
+    /// Fetch the transaction and its input cells by `tx_dep_provider`, the
+    /// input cells are loaded from their transactions since they are spent.
+    pub fn summarize(
+        &self,
+        lock: &Script,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TxSummary, TransactionDependencyError> {
+        let tx = tx_dep_provider.get_transaction(&self.tx_hash.pack())?;
+        let mut summary = TxSummary {
+            tx_hash: self.tx_hash.clone(),
+            block_number: self.block_number,
+            ckb_delta: 0,
+            udt_deltas: HashMap::new(),
+        };
+        for out_point in tx.input_pts_iter() {
+            // A cellbase input has no previous transaction
+            if out_point.is_null() {
+                continue;
+            }
+            let prev_tx = tx_dep_provider.get_transaction(&out_point.tx_hash())?;
+            let index: u32 = out_point.index().unpack();
+            let (output, data) = prev_tx.output_with_data(index as usize).ok_or_else(|| {
+                TransactionDependencyError::NotFound(format!("input cell {}", out_point))
+            })?;
+            add_delta(&mut summary, lock, &output, &data, -1);
+        }
+        for (output, data) in tx.outputs_with_data_iter() {
+            add_delta(&mut summary, lock, &output, &data, 1);
+        }
+        summary.udt_deltas.retain(|_, delta| *delta != 0);
+        Ok(summary)
+    }
+}
+
+fn add_delta(
+    summary: &mut TxSummary,
+    lock: &Script,
+    output: &CellOutput,
+    data: &Bytes,
+    sign: i128,
+) {
+    if &output.lock() != lock {
+        return;
+    }
+    let capacity: u64 = output.capacity().unpack();
+    summary.ckb_delta += sign * capacity as i128;
+    if let Some(type_script) = output.type_().to_opt() {
+        if data.len() >= 16 {
+            let mut amount_bytes = [0u8; 16];
+            amount_bytes.copy_from_slice(&data[0..16]);
+            // The amount is at most i128::MAX in practice
+            let amount = u128::from_le_bytes(amount_bytes) as i128;
+            *summary
+                .udt_deltas
+                .entry(type_script.calc_script_hash())
+                .or_default() += sign * amount;
+        }
+    }
+}
+
+/// Pages through `get_transactions` and groups the cell records by
+/// transaction, in the order of the records.
+///
+/// The records of a transaction are contiguous, a transaction is returned
+/// when a record of another transaction (or the end) is reached, so the
+/// records split by a page boundary are still grouped.
+pub struct TransactionIterator<'a> {
+    client: &'a IndexerRpcClient,
+    search_key: SearchKey,
+    order: Order,
+    page_size: u32,
+    page: VecDeque<TxRecord>,
+    /// The cursor the current page is fetched by
+    page_after: Option<JsonBytes>,
+    /// The number of records taken from the current page
+    page_taken: usize,
+    /// The `last_cursor` of the current page
+    next_after: Option<JsonBytes>,
+    last_page: bool,
+    /// The first record of the next transaction
+    peeked: Option<TxRecord>,
+    cursor: TxHistoryCursor,
+    /// The records to drop from the first page when resuming
+    resume_skip: usize,
+}
+
+impl<'a> TransactionIterator<'a> {
+    /// Iterate the transactions of `search_key` from the start, the records
+    /// are always fetched ungrouped.
+    pub fn new(
+        client: &'a IndexerRpcClient,
+        search_key: SearchKey,
+        order: Order,
+        page_size: u32,
+    ) -> TransactionIterator<'a> {
+        Self::resume(
+            client,
+            search_key,
+            order,
+            page_size,
+            TxHistoryCursor::default(),
+        )
+    }
+
+    /// Iterate from `cursor`, it must be returned by an iterator of the same
+    /// search key, order and page size.
+    pub fn resume(
+        client: &'a IndexerRpcClient,
+        mut search_key: SearchKey,
+        order: Order,
+        page_size: u32,
+        cursor: TxHistoryCursor,
+    ) -> TransactionIterator<'a> {
+        search_key.group_by_transaction = None;
+        TransactionIterator {
+            client,
+            search_key,
+            order,
+            page_size,
+            page: VecDeque::new(),
+            page_after: None,
+            page_taken: 0,
+            next_after: cursor.after.clone(),
+            last_page: false,
+            peeked: None,
+            resume_skip: cursor.skip,
+            cursor,
+        }
+    }
+
+    /// The position after the last returned transaction
+    pub fn cursor(&self) -> &TxHistoryCursor {
+        &self.cursor
+    }
+
+    fn next_record(&mut self) -> Result<Option<TxRecord>, RpcError> {
+        while self.page.is_empty() {
+            if self.last_page {
+                return Ok(None);
+            }
+            let after = self.next_after.take();
+            let page = self.client.get_transactions(
+                self.search_key.clone(),
+                self.order.clone(),
+                self.page_size.into(),
+                after.clone(),
+            )?;
+            self.last_page = page.objects.len() < self.page_size as usize;
+            self.page_after = after;
+            self.page_taken = 0;
+            self.next_after = Some(page.last_cursor);
+            for tx in page.objects {
+                self.page.push_back(match tx {
+                    Tx::Ungrouped(tx) => TxRecord {
+                        tx_hash: tx.tx_hash,
+                        block_number: tx.block_number.value(),
+                        tx_index: tx.tx_index.value(),
+                        cells: vec![(tx.io_type, tx.io_index.value())],
+                    },
+                    Tx::Grouped(_) => {
+                        return Err(anyhow!("unexpected grouped transaction record").into())
+                    }
+                });
+            }
+            let skip = std::mem::take(&mut self.resume_skip).min(self.page.len());
+            self.page.drain(..skip);
+            self.page_taken = skip;
+        }
+        self.page_taken += 1;
+        Ok(self.page.pop_front())
+    }
+
+    fn next_tx(&mut self) -> Result<Option<TxRecord>, RpcError> {
+        let mut tx = match self.peeked.take() {
+            Some(record) => record,
+            None => match self.next_record()? {
+                Some(record) => record,
+                None => return Ok(None),
+            },
+        };
+        loop {
+            match self.next_record()? {
+                Some(record) if record.tx_hash == tx.tx_hash => tx.cells.extend(record.cells),
+                Some(record) => {
+                    self.peeked = Some(record);
+                    break;
+                }
+                None => break,
+            }
+        }
+        // The peeked record is the last one taken from the current page
+        let peeked = usize::from(self.peeked.is_some());
+        self.cursor = TxHistoryCursor {
+            after: self.page_after.clone(),
+            skip: self.page_taken - peeked,
+        };
+        Ok(Some(tx))
+    }
+}
+
+impl<'a> Iterator for TransactionIterator<'a> {
+    type Item = Result<TxRecord, RpcError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_tx().transpose()
+    }
+}
+
+impl IndexerRpcClient {
+    /// The transactions of `search_key` grouped from the cell records, see
+    /// `TransactionIterator`.
+    pub fn transaction_iter(
+        &self,
+        search_key: SearchKey,
+        order: Order,
+        page_size: u32,
+    ) -> TransactionIterator<'_> {
+        TransactionIterator::new(self, search_key, order, page_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::SIGHASH_TYPE_HASH;
+    use crate::rpc::ckb_indexer::{Pagination, ScriptType, TxWithCell};
+    use crate::test_util::MockRpcResult;
+    use crate::traits::OffchainTransactionDependencyProvider;
+    use crate::types::ScriptHashTypeExt;
+    use ckb_types::{
+        core::{Capacity, ScriptHashType, TransactionBuilder},
+        h256,
+        packed::{CellInput, OutPoint},
+    };
+    use httpmock::prelude::*;
+
+    fn lock_script(arg: u8) -> Script {
+        Script::new_builder()
+            .code_hash(SIGHASH_TYPE_HASH.pack())
+            .hash_type(ScriptHashType::Type.to_packed())
+            .args(Bytes::from(vec![arg; 20]).pack())
+            .build()
+    }
+
+    fn search_key() -> SearchKey {
+        SearchKey {
+            script: lock_script(1).into(),
+            script_type: ScriptType::Lock,
+            script_search_mode: None,
+            filter: None,
+            with_data: None,
+            group_by_transaction: None,
+        }
+    }
+
+    fn record(tx_hash: &H256, io_type: CellType, io_index: u32) -> Tx {
+        Tx::Ungrouped(TxWithCell {
+            tx_hash: tx_hash.clone(),
+            block_number: 10u64.into(),
+            tx_index: 1u32.into(),
+            io_index: io_index.into(),
+            io_type,
+        })
+    }
+
+    #[test]
+    fn test_transaction_iterator() {
+        let tx_a = h256!("0xa");
+        let tx_b = h256!("0xb");
+        let tx_c = h256!("0xc");
+        // the records of tx_a and tx_c are split by the page boundaries
+        let pages = vec![
+            (
+                ",null]",
+                vec![
+                    record(&tx_a, CellType::Input, 0),
+                    record(&tx_a, CellType::Output, 0),
+                ],
+                "01",
+            ),
+            (
+                "\"0x01\"]",
+                vec![
+                    record(&tx_a, CellType::Output, 1),
+                    record(&tx_b, CellType::Output, 0),
+                ],
+                "02",
+            ),
+            (
+                "\"0x02\"]",
+                vec![
+                    record(&tx_c, CellType::Input, 0),
+                    record(&tx_c, CellType::Input, 1),
+                ],
+                "03",
+            ),
+            ("\"0x03\"]", vec![record(&tx_c, CellType::Output, 0)], "04"),
+        ];
+        let server = MockServer::start();
+        for (after, records, cursor) in pages {
+            let response = MockRpcResult::new(Pagination {
+                objects: records,
+                last_cursor: JsonBytes::from_vec(hex::decode(cursor).unwrap()),
+            })
+            .to_json();
+            server.mock(|when, then| {
+                when.method(POST)
+                    .path("/")
+                    .body_contains("get_transactions")
+                    .body_contains(after);
+                then.status(200).body(&response);
+            });
+        }
+        let client = IndexerRpcClient::new(server.base_url().as_str());
+
+        let mut iter = client.transaction_iter(search_key(), Order::Asc, 2);
+        let first = iter.next().unwrap().unwrap();
+        assert_eq!(first.tx_hash, tx_a);
+        assert_eq!(
+            first.cells,
+            vec![
+                (CellType::Input, 0),
+                (CellType::Output, 0),
+                (CellType::Output, 1)
+            ]
+        );
+        // tx_b is the next record of the second page
+        let cursor = iter.cursor().clone();
+        assert_eq!(
+            cursor,
+            TxHistoryCursor {
+                after: Some(JsonBytes::from_vec(vec![1])),
+                skip: 1,
+            }
+        );
+        let rest: Vec<TxRecord> = iter.map(Result::unwrap).collect();
+        assert_eq!(
+            rest.iter().map(|tx| tx.tx_hash.clone()).collect::<Vec<_>>(),
+            vec![tx_b.clone(), tx_c.clone()]
+        );
+        assert_eq!(rest[1].cells.len(), 3);
+
+        // resume from the persisted cursor
+        let resumed: Vec<TxRecord> =
+            TransactionIterator::resume(&client, search_key(), Order::Asc, 2, cursor)
+                .map(Result::unwrap)
+                .collect();
+        assert_eq!(resumed, rest);
+    }
+
+    #[test]
+    fn test_tx_record_summarize() {
+        let owner = lock_script(1);
+        let other = lock_script(2);
+        let udt_type = Script::new_builder()
+            .code_hash(h256!("0x1234").pack())
+            .hash_type(ScriptHashType::Type.to_packed())
+            .build();
+        let build_output = |lock: &Script, ckb: u64, type_: Option<Script>| {
+            CellOutput::new_builder()
+                .capacity(Capacity::shannons(ckb * 100_000_000).pack())
+                .lock(lock.clone())
+                .type_(type_.pack())
+                .build()
+        };
+        let fund_tx = TransactionBuilder::default()
+            .output(build_output(&owner, 500, None))
+            .output_data(Bytes::new().pack())
+            .output(build_output(&owner, 200, Some(udt_type.clone())))
+            .output_data(Bytes::from(1000u128.to_le_bytes().to_vec()).pack())
+            .build();
+        let spend_tx = TransactionBuilder::default()
+            .input(CellInput::new(OutPoint::new(fund_tx.hash(), 0), 0))
+            .input(CellInput::new(OutPoint::new(fund_tx.hash(), 1), 0))
+            .output(build_output(&other, 300, None))
+            .output_data(Bytes::new().pack())
+            .output(build_output(&other, 150, Some(udt_type.clone())))
+            .output_data(Bytes::from(400u128.to_le_bytes().to_vec()).pack())
+            .output(build_output(&owner, 150, Some(udt_type.clone())))
+            .output_data(Bytes::from(600u128.to_le_bytes().to_vec()).pack())
+            .output(build_output(&owner, 99, None))
+            .output_data(Bytes::new().pack())
+            .build();
+        let mut provider = OffchainTransactionDependencyProvider::new();
+        provider.apply_tx(fund_tx.data(), 0).unwrap();
+        provider.apply_tx(spend_tx.data(), 0).unwrap();
+
+        let record = TxRecord {
+            tx_hash: spend_tx.hash().unpack(),
+            block_number: 20,
+            tx_index: 1,
+            cells: vec![(CellType::Input, 0), (CellType::Input, 1)],
+        };
+        let summary = record.summarize(&owner, &provider).unwrap();
+        assert_eq!(summary.block_number, 20);
+        assert_eq!(summary.ckb_delta, -451 * 100_000_000);
+        assert_eq!(
+            summary.udt_deltas,
+            vec![(udt_type.calc_script_hash(), -400)]
+                .into_iter()
+                .collect()
+        );
+    }
+}
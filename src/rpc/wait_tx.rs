@@ -0,0 +1,177 @@
+use std::time::Duration;
+
+use ckb_jsonrpc_types::Status;
+use ckb_types::H256;
+use thiserror::Error;
+
+use super::RpcError;
+
+/// Options for `CkbRpcClient::wait_for_tx`
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct WaitOptions {
+    /// Number of blocks on top of the committing block before returning,
+    /// 0 means return as soon as the transaction is committed.
+    pub confirmations: u64,
+    /// Give up after this duration
+    pub timeout: Duration,
+    /// The interval between two polls
+    pub poll_interval: Duration,
+}
+
+impl Default for WaitOptions {
+    fn default() -> Self {
+        WaitOptions {
+            confirmations: 0,
+            timeout: Duration::from_secs(120),
+            poll_interval: Duration::from_secs(2),
+        }
+    }
+}
+
+/// The committed transaction returned by `CkbRpcClient::wait_for_tx`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TxCommitStatus {
+    pub block_number: u64,
+    pub block_hash: H256,
+    /// Number of blocks on top of the committing block when the wait finished
+    pub confirmations: u64,
+}
+
+#[derive(Error, Debug)]
+pub enum WaitError {
+    #[error("transaction rejected: `{0}`")]
+    Rejected(String),
+
+    #[error("wait transaction timeout, last status: `{0:?}`")]
+    Timeout(Status),
+
+    #[error("invalid tx status: `{0}`")]
+    InvalidStatus(String),
+
+    #[error(transparent)]
+    Rpc(#[from] RpcError),
+
+    #[error("subscription error: `{0}`")]
+    Subscription(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockRpcResult;
+    use crate::CkbRpcClient;
+    use ckb_types::h256;
+    use httpmock::prelude::*;
+    use serde_json::{json, Value};
+
+    const TX_HASH: H256 =
+        h256!("0x6a3c04f1c0b1b2a4f5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b1c2d3e4f5a6b7c8");
+
+    fn tx_response(status: &str, block: Option<(u64, &H256)>, reason: Option<&str>) -> String {
+        let (block_number, block_hash) = match block {
+            Some((number, hash)) => (json!(format!("{:#x}", number)), json!(hash)),
+            None => (Value::Null, Value::Null),
+        };
+        MockRpcResult::new(json!({
+            "transaction": null,
+            "cycles": null,
+            "time_added_to_pool": null,
+            "fee": null,
+            "min_replace_fee": null,
+            "tx_status": {
+                "status": status,
+                "block_number": block_number,
+                "block_hash": block_hash,
+                "tx_index": null,
+                "reason": reason,
+            },
+        }))
+        .to_json()
+    }
+
+    fn options(confirmations: u64) -> WaitOptions {
+        WaitOptions {
+            confirmations,
+            timeout: Duration::from_millis(300),
+            poll_interval: Duration::from_millis(50),
+        }
+    }
+
+    #[test]
+    fn test_wait_for_committed_tx() {
+        let server = MockServer::start();
+        let block_hash = h256!("0x1234");
+        server.mock(|when, then| {
+            when.method(POST).path("/").body_contains("get_transaction");
+            then.status(200)
+                .body(tx_response("committed", Some((100, &block_hash)), None));
+        });
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/")
+                .body_contains("get_tip_block_number");
+            then.status(200).body(MockRpcResult::new("0x67").to_json());
+        });
+        let client = CkbRpcClient::new(server.base_url().as_str());
+
+        let status = client.wait_for_tx(TX_HASH, options(3)).unwrap();
+        assert_eq!(
+            status,
+            TxCommitStatus {
+                block_number: 100,
+                block_hash,
+                confirmations: 3,
+            }
+        );
+        // not enough confirmations
+        let err = client.wait_for_tx(TX_HASH, options(4)).unwrap_err();
+        assert!(matches!(err, WaitError::Timeout(Status::Committed)));
+    }
+
+    #[test]
+    fn test_wait_for_rejected_tx() {
+        let server = MockServer::start();
+        let tx_mock = server.mock(|when, then| {
+            when.method(POST).path("/").body_contains("get_transaction");
+            then.status(200).body(tx_response(
+                "rejected",
+                None,
+                Some("Resolve failed Dead(OutPoint(0x00))"),
+            ));
+        });
+        let client = CkbRpcClient::new(server.base_url().as_str());
+        let err = client.wait_for_tx(TX_HASH, options(0)).unwrap_err();
+        assert!(
+            matches!(err, WaitError::Rejected(ref reason) if reason.starts_with("Resolve failed")),
+            "{}",
+            err
+        );
+        // returned without waiting for the timeout
+        tx_mock.assert_hits(1);
+    }
+
+    #[test]
+    fn test_wait_for_unknown_tx() {
+        let server = MockServer::start();
+        let tx_mock = server.mock(|when, then| {
+            when.method(POST).path("/").body_contains("get_transaction");
+            then.status(200).body(tx_response("unknown", None, None));
+        });
+        let client = CkbRpcClient::new(server.base_url().as_str());
+        let err = client.wait_for_tx(TX_HASH, options(0)).unwrap_err();
+        assert!(matches!(err, WaitError::Timeout(Status::Unknown)));
+        // unknown is retried until the timeout
+        assert!(tx_mock.hits() > 1);
+    }
+}
+
+#[cfg(test)]
+mod anyhow_tests {
+    use anyhow::anyhow;
+    #[test]
+    fn test_wait_error() {
+        let error = super::WaitError::Rejected("Resolve failed".to_string());
+        let error = anyhow!(error);
+        assert_eq!("transaction rejected: `Resolve failed`", error.to_string());
+    }
+}
@@ -1,42 +1,42 @@
-use ckb_chain_spec::consensus::ConsensusBuilder;
 use std::cmp::Ordering;
-use std::collections::{HashMap, HashSet};
-use std::convert::TryFrom;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::Arc;
 
 use ckb_jsonrpc_types::Serialize;
-use ckb_types::core::{HeaderBuilder, TransactionBuilder};
-use rand::{thread_rng, Rng};
+use ckb_types::core::TransactionBuilder;
+use rand::{rngs::StdRng, thread_rng, Rng, RngCore, SeedableRng};
 use thiserror::Error;
 
 use crate::{
     constants::{
         MULTISIG_GROUP_OUTPUT_LOC, MULTISIG_TYPE_HASH, ONE_CKB, SIGHASH_GROUP_OUTPUT_LOC,
-        SIGHASH_TYPE_HASH,
+        SIGHASH_TYPE_HASH, TYPE_ID_CODE_HASH,
     },
     traits::{
-        CellCollector, CellCollectorError, CellDepResolver, CellQueryOptions,
-        DefaultCellDepResolver, HeaderDepResolver, LiveCell, TransactionDependencyError,
-        TransactionDependencyProvider,
+        CellCollector, CellCollectorError, CellDepResolver, CellLockExpiry, CellLockTtl,
+        CellQueryOptions, DefaultCellDepResolver, HeaderDepResolver, LiveCell, SecpCkbRawKeySigner,
+        TransactionDependencyError, TransactionDependencyProvider, PENDING_BLOCK_NUMBER,
     },
-    tx_builder::tx_fee,
-    ScriptId,
+    tx_builder::{
+        latest_consensus, tx_fee, type_id::calculate_type_id, verify_mock_tx_cycles,
+        VerifyCyclesError,
+    },
+    types::{Address, AddressPayload, NetworkType, ScriptGroupType, ScriptHashTypeExt},
+    util::{blake160, expand_dep_group},
+    ScriptId, Since, SECP256K1,
 };
 use ckb_hash::blake2b_256;
-use ckb_mock_tx_types::{
-    MockCellDep, MockInfo, MockInput, MockResourceLoader, MockTransaction, Resource,
-};
-use ckb_script::{TransactionScriptsVerifier, TxVerifyEnv};
-use ckb_types::core::hardfork::{HardForks, CKB2021, CKB2023};
+use ckb_mock_tx_types::{MockCellDep, MockInfo, MockInput, MockTransaction};
+use ckb_script::ScriptError;
 use ckb_types::{
     bytes::Bytes,
     core::{
-        cell::resolve_transaction, BlockView, Capacity, Cycle, DepType, FeeRate, HeaderView,
+        BlockView, Capacity, Cycle, DepType, EpochNumberWithFraction, FeeRate, HeaderView,
         ScriptHashType, TransactionView,
     },
     packed::{Byte32, CellDep, CellInput, CellOutput, OutPoint, OutPointVec, Script, Transaction},
     prelude::*,
-    H256,
+    H160, H256,
 };
 
 /// Test utils errors
@@ -48,10 +48,92 @@ pub enum Error {
     NoEnoughFee(String),
     #[error("verify script error: {0}")]
     VerifyScript(String),
+    #[error("input not ready at the tip: {0}")]
+    Immature(String),
     #[error("other error: {0}")]
     Other(String),
 }
 
+/// The cycles consumed by a verified transaction
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct VerifyOk {
+    pub total_cycles: Cycle,
+    /// The cycles of every script group, lock script groups first
+    pub group_cycles: Vec<(ScriptGroupType, Byte32, Cycle)>,
+}
+
+/// Why `Context::verify` failed
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+#[error("verify error (group: {group:?}, exit code: {exit_code:?}): {message}")]
+pub struct VerifyError {
+    /// The failed script group, `None` when the transaction fails before
+    /// running the scripts (e.g. the fee is not enough)
+    pub group: Option<(ScriptGroupType, Byte32)>,
+    /// The exit code of the failed script, `None` when the script did not
+    /// exit by itself (e.g. exceeded the max cycles)
+    pub exit_code: Option<i8>,
+    pub message: String,
+}
+
+impl From<Error> for VerifyError {
+    fn from(err: Error) -> VerifyError {
+        VerifyError {
+            group: None,
+            exit_code: None,
+            message: err.to_string(),
+        }
+    }
+}
+
+/// A random sighash account on the testnet
+#[derive(Clone, Debug)]
+pub struct TestAccount {
+    pub secret_key: secp256k1::SecretKey,
+    /// The blake160 hash of the compressed public key
+    pub lock_arg: H160,
+    pub lock_script: Script,
+    pub address: Address,
+}
+
+impl TestAccount {
+    pub fn random() -> TestAccount {
+        let mut rng = thread_rng();
+        loop {
+            let mut buf = [0u8; 32];
+            rng.fill(&mut buf);
+            if let Ok(secret_key) = secp256k1::SecretKey::from_slice(&buf) {
+                return Self::from_secret_key(secret_key);
+            }
+        }
+    }
+
+    pub fn from_secret_key(secret_key: secp256k1::SecretKey) -> TestAccount {
+        let pubkey = secp256k1::PublicKey::from_secret_key(&SECP256K1, &secret_key);
+        let lock_arg = blake160(&pubkey.serialize());
+        let lock_script = Script::new_builder()
+            .code_hash(SIGHASH_TYPE_HASH.pack())
+            .hash_type(ScriptHashType::Type.to_packed())
+            .args(Bytes::from(lock_arg.as_bytes().to_vec()).pack())
+            .build();
+        let address = Address::new(
+            NetworkType::Testnet,
+            AddressPayload::from(lock_script.clone()),
+            true,
+        );
+        TestAccount {
+            secret_key,
+            lock_arg,
+            lock_script,
+            address,
+        }
+    }
+
+    /// A signer of the account key
+    pub fn signer(&self) -> SecpCkbRawKeySigner {
+        SecpCkbRawKeySigner::new_with_secret_keys(vec![self.secret_key])
+    }
+}
+
 /// The test context for CKB Rust SDK
 #[derive(Clone, Default)]
 pub struct Context {
@@ -65,6 +147,26 @@ pub struct Context {
     pub dep_type_hashes: Vec<Option<H256>>,
     /// For resolve dep group cell dep
     pub cell_dep_map: HashMap<ScriptId, CellDep>,
+    /// The dep groups added by `add_dep_group`, by name
+    pub dep_groups: BTreeMap<String, CellDep>,
+
+    /// The inputs from cellbase transactions
+    pub cellbase_out_points: HashSet<OutPoint>,
+    /// The chain tip, the since of the inputs and the cellbase maturity are
+    /// only checked when it is set
+    pub tip: Option<ChainTip>,
+
+    /// The random source of out points, thread rng is used when not set
+    entropy: Option<StdRng>,
+}
+
+/// The tip block of the chain the test context lives on
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ChainTip {
+    pub number: u64,
+    pub epoch: EpochNumberWithFraction,
+    /// The median time of the tip block in milliseconds
+    pub median_time: u64,
 }
 
 #[derive(Clone)]
@@ -72,6 +174,17 @@ pub struct LiveCellsContext {
     pub inputs: Vec<MockInput>,
     pub header_deps: Vec<HeaderView>,
     pub used_inputs: HashSet<usize>,
+    /// The used inputs locked by `lock_cell_with_ttl`
+    pub lock_expirations: HashMap<usize, CellLockExpiry>,
+    pub cellbase_out_points: HashSet<OutPoint>,
+    /// The max block number of the mature cellbase cells, all the inputs are
+    /// collected as cellbase cells of immature blocks when it is `None`
+    pub max_mature_number: Option<u64>,
+    /// The tip block number to expire the locks of `CellLockTtl::Blocks`
+    pub tip_block_number: u64,
+    /// The number of the inputs at the end created by `apply_tx`, they are
+    /// collected as unconfirmed cells and dropped by `reset`
+    pub pending_inputs: usize,
 }
 
 impl Context {
@@ -80,11 +193,33 @@ impl Context {
     /// contracts can only be referenced by data hash and with
     /// hash_type="data1".
     pub fn new(block: &BlockView, contracts: Vec<(&[u8], bool)>) -> Context {
+        Self::new_with_entropy(block, contracts, None)
+    }
+
+    /// Same as `new`, but the out points of the deployed contracts and the
+    /// ones returned by `Context::random_out_point` are drawn from
+    /// `seeded_entropy(seed)`, so the same seed always builds the same context.
+    pub fn new_deterministic(
+        block: &BlockView,
+        contracts: Vec<(&[u8], bool)>,
+        seed: u64,
+    ) -> Context {
+        Self::new_with_entropy(block, contracts, Some(seeded_entropy(seed)))
+    }
+
+    fn new_with_entropy(
+        block: &BlockView,
+        contracts: Vec<(&[u8], bool)>,
+        entropy: Option<StdRng>,
+    ) -> Context {
         let block_number: u64 = block.number();
         assert_eq!(block_number, 0);
         let cell_dep_resolver = DefaultCellDepResolver::from_genesis(block).expect("genesis info");
         let block_hash = block.hash();
-        let mut ctx = Context::default();
+        let mut ctx = Context {
+            entropy,
+            ..Default::default()
+        };
         for (cell_dep, (tx_idx, output_idx)) in [
             (
                 cell_dep_resolver.sighash_dep().unwrap().clone().0,
@@ -180,6 +315,31 @@ impl Context {
         None
     }
 
+    /// Adds a live cell from the cellbase transaction of the block `header`,
+    /// the header is added to the context.
+    pub fn add_cellbase_live_cell(
+        &mut self,
+        out_point: OutPoint,
+        output: CellOutput,
+        header: HeaderView,
+    ) -> Option<(CellOutput, Bytes, Option<Byte32>)> {
+        let header_hash = header.hash();
+        if !self
+            .header_deps
+            .iter()
+            .any(|item| item.hash() == header_hash)
+        {
+            self.add_header(header);
+        }
+        self.cellbase_out_points.insert(out_point.clone());
+        self.add_live_cell(
+            CellInput::new(out_point, 0),
+            output,
+            Bytes::default(),
+            Some(header_hash),
+        )
+    }
+
     /// Add a live cell with empty data
     pub fn add_simple_live_cell(
         &mut self,
@@ -201,10 +361,31 @@ impl Context {
         self.add_live_cell(input, output, Bytes::default(), None)
     }
 
+    /// Add a live cell of `lock` with empty data for every capacity
+    /// return the out-points of the cells
+    pub fn fund(&mut self, lock: &Script, capacities: &[u64]) -> Vec<OutPoint> {
+        capacities
+            .iter()
+            .map(|capacity| {
+                let out_point = self.random_out_point();
+                self.add_simple_live_cell(out_point.clone(), lock.clone(), Some(*capacity));
+                out_point
+            })
+            .collect()
+    }
+
+    /// A random out point, drawn from the seeded entropy in deterministic mode
+    pub fn random_out_point(&mut self) -> OutPoint {
+        match self.entropy.as_mut() {
+            Some(rng) => random_out_point_with(rng),
+            None => random_out_point(),
+        }
+    }
+
     /// Deploy a cell
     /// return the out-point of the cell
     pub fn deploy_cell(&mut self, data: Bytes) -> OutPoint {
-        let out_point = random_out_point();
+        let out_point = self.random_out_point();
         let cell_dep = CellDep::new_builder()
             .out_point(out_point.clone())
             .dep_type(DepType::Code.into())
@@ -250,6 +431,79 @@ impl Context {
         None
     }
 
+    /// Deploy a contract, the script id is of hash_type="type" with a type id
+    /// type script when `use_type_id` is true, otherwise of hash_type="data1".
+    /// return the out-point of the cell and the script id of the contract
+    pub fn deploy_contract(&mut self, bin: &[u8], use_type_id: bool) -> (OutPoint, ScriptId) {
+        let out_point = self.random_out_point();
+        let (output, script_id) = if use_type_id {
+            let first_input = CellInput::new(self.random_out_point(), 0);
+            let type_id = calculate_type_id(&first_input, 0);
+            let type_script = Script::new_builder()
+                .code_hash(TYPE_ID_CODE_HASH.pack())
+                .hash_type(ScriptHashType::Type.to_packed())
+                .args(Bytes::from(type_id.as_bytes().to_vec()).pack())
+                .build();
+            let script_id = ScriptId::new_type(type_script.calc_script_hash().unpack());
+            let output = CellOutput::new_builder()
+                .type_(Some(type_script).pack())
+                .build();
+            (output, script_id)
+        } else {
+            let data_hash = H256::from(blake2b_256(bin));
+            (CellOutput::default(), ScriptId::new_data1(data_hash))
+        };
+        let cell_dep = CellDep::new_builder()
+            .out_point(out_point.clone())
+            .dep_type(DepType::Code.into())
+            .build();
+        self.add_cell_dep(cell_dep, output, Bytes::from(bin.to_vec()), None);
+        (out_point, script_id)
+    }
+
+    /// Deploy a dep group cell of `member_out_points`, the members must be
+    /// deployed already. A script whose code cell is a member of the group is
+    /// resolved to the dep group cell dep by `CellDepResolver::resolve`.
+    /// return the out-point of the dep group cell
+    pub fn add_dep_group(&mut self, name: &str, member_out_points: Vec<OutPoint>) -> OutPoint {
+        for out_point in &member_out_points {
+            assert!(
+                self.get_live_cell(out_point).is_some(),
+                "dep group member not deployed: {}",
+                out_point
+            );
+        }
+        let out_points: OutPointVec = member_out_points.pack();
+        let out_point = self.deploy_cell(out_points.as_bytes());
+        let cell_dep = CellDep::new_builder()
+            .out_point(out_point.clone())
+            .dep_type(DepType::DepGroup.into())
+            .build();
+        self.dep_groups.insert(name.to_string(), cell_dep);
+        out_point
+    }
+
+    /// The cell dep of the dep group added by `add_dep_group`
+    pub fn get_dep_group(&self, name: &str) -> Option<CellDep> {
+        self.dep_groups.get(name).cloned()
+    }
+
+    /// The first dep group (by name) added by `add_dep_group` which has `out_point`
+    fn find_dep_group(&self, out_point: &OutPoint) -> Option<CellDep> {
+        self.dep_groups
+            .values()
+            .find(|cell_dep| {
+                expand_dep_group(cell_dep, self)
+                    .map(|members| {
+                        members
+                            .iter()
+                            .any(|member| &member.out_point() == out_point)
+                    })
+                    .unwrap_or(false)
+            })
+            .cloned()
+    }
+
     pub fn add_cell_dep_map(&mut self, script_id: ScriptId, cell_dep: CellDep) -> Option<CellDep> {
         self.cell_dep_map.insert(script_id, cell_dep)
     }
@@ -258,6 +512,45 @@ impl Context {
         self.header_deps.push(header);
     }
 
+    /// Set the tip of the chain, `median_time` is in milliseconds.
+    pub fn set_tip(&mut self, number: u64, epoch: EpochNumberWithFraction, median_time: u64) {
+        self.tip = Some(ChainTip {
+            number,
+            epoch,
+            median_time,
+        });
+    }
+
+    /// The max block number of the headers in the context whose cellbase
+    /// cells are mature at the tip, `None` when the tip is not set.
+    pub fn max_mature_number(&self) -> Option<u64> {
+        let tip = self.tip?;
+        let maturity = Since::relative_epoch(latest_consensus().cellbase_maturity());
+        Some(
+            self.header_deps
+                .iter()
+                .filter(|header| {
+                    maturity.is_relative_satisfied_by(
+                        header.number(),
+                        header.epoch(),
+                        header.timestamp(),
+                        tip.number,
+                        tip.epoch,
+                        tip.median_time,
+                    )
+                })
+                .map(|header| header.number())
+                .max()
+                .unwrap_or(0),
+        )
+    }
+
+    fn get_header_by_hash(&self, hash: &Byte32) -> Option<&HeaderView> {
+        self.header_deps
+            .iter()
+            .find(|header| &header.hash() == hash)
+    }
+
     pub fn get_live_cell(&self, out_point: &OutPoint) -> Option<(CellOutput, Bytes)> {
         if let Some(result) = self.get_input(out_point) {
             return Some(result);
@@ -334,6 +627,11 @@ impl Context {
             inputs: self.inputs.clone(),
             header_deps: self.header_deps.clone(),
             used_inputs: Default::default(),
+            lock_expirations: Default::default(),
+            cellbase_out_points: self.cellbase_out_points.clone(),
+            max_mature_number: self.max_mature_number(),
+            tip_block_number: 0,
+            pending_inputs: 0,
         }
     }
 
@@ -352,42 +650,127 @@ impl Context {
         Ok(())
     }
 
-    /// Run all scripts in the transaction in ckb-vm
+    /// Run all scripts in the transaction in ckb-vm, the members of the dep
+    /// group cell deps must be in the context.
     pub fn verify_scripts(&self, tx: TransactionView) -> Result<Cycle, Error> {
+        self.verify_script_groups(tx)
+            .map(|result| result.total_cycles)
+            .map_err(|err| Error::VerifyScript(err.to_string()))
+    }
+
+    /// Same as `verify_scripts`, run the script groups one by one and report
+    /// the cycles of each group, or the group failed first.
+    pub fn verify_script_groups(&self, tx: TransactionView) -> Result<VerifyOk, VerifyError> {
+        for cell_dep in tx.cell_deps_iter() {
+            let members = expand_dep_group(&cell_dep, self)
+                .map_err(|err| Error::VerifyScript(err.to_string()))?;
+            for member in members {
+                if self.get_live_cell(&member.out_point()).is_none() {
+                    return Err(Error::VerifyScript(format!(
+                        "cell dep not found: {}",
+                        member.out_point()
+                    ))
+                    .into());
+                }
+            }
+        }
         let mock_tx = self.to_mock_tx(tx.data());
-        let resource =
-            Resource::from_both(&mock_tx, &mut DummyLoader).map_err(Error::VerifyScript)?;
-        let rtx = resolve_transaction(tx, &mut HashSet::new(), &resource, &resource)
-            .map_err(|err| Error::VerifyScript(format!("Resolve transaction error: {:?}", err)))?;
-        let consensus = ConsensusBuilder::default()
-            .hardfork_switch(HardForks {
-                ckb2021: CKB2021::new_dev_default(),
-                ckb2023: CKB2023::new_dev_default(),
-            })
-            .build();
-        let tip = HeaderBuilder::default().number(0.pack()).build();
-        let tx_verify_env = TxVerifyEnv::new_submit(&tip);
-
-        let mut verifier = TransactionScriptsVerifier::new(
-            Arc::new(rtx),
-            resource,
-            Arc::new(consensus),
-            Arc::new(tx_verify_env),
-        );
-        verifier.set_debug_printer(|script_hash, message| {
-            println!("script: {:x}, debug: {}", script_hash, message);
-        });
-        verifier
-            .verify(u64::max_value())
-            .map_err(|err| Error::VerifyScript(format!("Verify script error: {:?}", err)))
+        match verify_mock_tx_cycles(&mock_tx, Arc::new(latest_consensus()), u64::max_value()) {
+            Ok(report) => Ok(VerifyOk {
+                total_cycles: report.total,
+                group_cycles: report
+                    .groups
+                    .into_iter()
+                    .map(|group| (group.group_type, group.script_hash.pack(), group.cycles))
+                    .collect(),
+            }),
+            Err(VerifyCyclesError::Script {
+                group_type,
+                script_hash,
+                error,
+            }) => {
+                let exit_code = match &error {
+                    ScriptError::ValidationFailure(_, exit_code) => Some(*exit_code),
+                    _ => None,
+                };
+                Err(VerifyError {
+                    group: Some((group_type, script_hash.pack())),
+                    exit_code,
+                    message: error.to_string(),
+                })
+            }
+            Err(err) => Err(Error::VerifyScript(err.to_string()).into()),
+        }
+    }
+
+    /// Check the since of the inputs and the cellbase maturity against the
+    /// tip, the timestamp of the block an input is committed in is used as
+    /// its median time. Nothing is checked when the tip is not set.
+    pub fn verify_tip(&self, tx: &TransactionView) -> Result<(), Error> {
+        let tip = match self.tip {
+            Some(tip) => tip,
+            None => return Ok(()),
+        };
+        let max_mature_number = self.max_mature_number().unwrap_or(0);
+        for input in tx.inputs() {
+            let out_point = input.previous_output();
+            let header = self
+                .inputs
+                .iter()
+                .find(|item| item.input.previous_output() == out_point)
+                .and_then(|item| item.header.as_ref())
+                .and_then(|hash| self.get_header_by_hash(hash));
+            if self.cellbase_out_points.contains(&out_point) {
+                let number = header.map(|header| header.number()).unwrap_or(0);
+                if number > max_mature_number {
+                    return Err(Error::Immature(format!(
+                        "cellbase cell {} of block {}",
+                        out_point, number
+                    )));
+                }
+            }
+            let since = Since::from_raw_value(input.since().unpack());
+            if since.value() == 0 {
+                continue;
+            }
+            let satisfied = match header {
+                Some(header) => since.is_relative_satisfied_by(
+                    header.number(),
+                    header.epoch(),
+                    header.timestamp(),
+                    tip.number,
+                    tip.epoch,
+                    tip.median_time,
+                ),
+                None if since.is_absolute() => {
+                    since.is_satisfied_by(tip.number, tip.epoch, tip.median_time)
+                }
+                None => {
+                    return Err(Error::Immature(format!(
+                        "the block of input {} is unknown for the relative since",
+                        out_point
+                    )))
+                }
+            };
+            if !satisfied {
+                return Err(Error::Immature(format!(
+                    "since {:#x} of input {}",
+                    since.value(),
+                    out_point
+                )));
+            }
+        }
+        Ok(())
     }
 
     /// Verify:
     ///  * the transaction fee is greater than fee rate
-    ///  * run the transaction in ckb-vm
-    pub fn verify(&self, tx: TransactionView, fee_rate: u64) -> Result<Cycle, Error> {
+    ///  * the since of the inputs and the cellbase maturity when the tip is set
+    ///  * run the transaction in ckb-vm, see `verify_script_groups`
+    pub fn verify(&self, tx: TransactionView, fee_rate: u64) -> Result<VerifyOk, VerifyError> {
         self.verify_tx_fee(&tx, fee_rate)?;
-        self.verify_scripts(tx)
+        self.verify_tip(&tx)?;
+        self.verify_script_groups(tx)
     }
 }
 
@@ -481,25 +864,23 @@ impl CellDepResolver for Context {
         let hash_type = script.hash_type();
         let script_id = ScriptId::new(
             code_hash.clone(),
-            ScriptHashType::try_from(hash_type).unwrap(),
+            ScriptHashType::from_packed(&hash_type).unwrap(),
         );
         if let Some(cell_dep) = self.cell_dep_map.get(&script_id) {
             return Some(cell_dep.clone());
         }
-        if hash_type == ScriptHashType::Type.into() {
-            for (idx, hash_opt) in self.dep_type_hashes.iter().enumerate() {
-                if hash_opt.as_ref() == Some(&code_hash) {
-                    return Some(self.cell_deps[idx].cell_dep.clone());
-                }
-            }
+        let idx = if hash_type == ScriptHashType::Type.to_packed() {
+            self.dep_type_hashes
+                .iter()
+                .position(|hash_opt| hash_opt.as_ref() == Some(&code_hash))?
         } else {
-            for (idx, hash) in self.dep_data_hashes.iter().enumerate() {
-                if *hash == code_hash {
-                    return Some(self.cell_deps[idx].cell_dep.clone());
-                }
-            }
-        }
-        None
+            self.dep_data_hashes
+                .iter()
+                .position(|hash| *hash == code_hash)?
+        };
+        let cell_dep = self.cell_deps[idx].cell_dep.clone();
+        self.find_dep_group(&cell_dep.out_point())
+            .or(Some(cell_dep))
     }
 }
 
@@ -509,14 +890,28 @@ impl CellCollector for LiveCellsContext {
         query: &CellQueryOptions,
         apply_changes: bool,
     ) -> Result<(Vec<LiveCell>, u64), CellCollectorError> {
+        let tip_block_number = self.tip_block_number;
+        let expired: Vec<usize> = self
+            .lock_expirations
+            .iter()
+            .filter(|(_idx, expiry)| expiry.is_expired(tip_block_number))
+            .map(|(idx, _expiry)| *idx)
+            .collect();
+        for idx in expired {
+            self.lock_expirations.remove(&idx);
+            self.used_inputs.remove(&idx);
+        }
         let mut total_capacity = 0;
         let mut cells = Vec::new();
         for (idx, item) in self.inputs.iter().enumerate() {
             if self.used_inputs.contains(&idx) {
                 continue;
             }
+            let pending = idx >= self.inputs.len() - self.pending_inputs;
             let mut block_number: u64 = 0;
-            if let Some(hash) = item.header.as_ref() {
+            if pending {
+                block_number = PENDING_BLOCK_NUMBER;
+            } else if let Some(hash) = item.header.as_ref() {
                 for header in &self.header_deps {
                     if *hash == header.hash() {
                         block_number = header.number();
@@ -524,14 +919,20 @@ impl CellCollector for LiveCellsContext {
                 }
             }
             let capacity: u64 = item.output.capacity().unpack();
+            let out_point = item.input.previous_output();
+            // the outputs of the applied transactions are never cellbase
+            let from_cellbase = !pending
+                && (self.max_mature_number.is_none()
+                    || self.cellbase_out_points.contains(&out_point));
             let live_cell = LiveCell {
                 output: item.output.clone(),
                 output_data: item.data.clone(),
-                out_point: item.input.previous_output().clone(),
+                out_point,
                 block_number,
-                tx_index: 0,
+                tx_index: if from_cellbase { 0 } else { 1 },
+                from_cellbase,
             };
-            if query.match_cell(&live_cell, 0) {
+            if query.match_cell(&live_cell, self.max_mature_number.unwrap_or(0)) {
                 total_capacity += capacity;
                 cells.push(live_cell);
                 if apply_changes {
@@ -547,41 +948,84 @@ impl CellCollector for LiveCellsContext {
 
     fn lock_cell(
         &mut self,
-        _out_point: OutPoint,
+        out_point: OutPoint,
         _tip_block_number: u64,
     ) -> Result<(), CellCollectorError> {
-        unimplemented!()
+        if let Some(idx) = self.input_index(&out_point) {
+            self.used_inputs.insert(idx);
+        }
+        Ok(())
+    }
+    fn lock_cell_with_ttl(
+        &mut self,
+        out_point: OutPoint,
+        ttl: CellLockTtl,
+    ) -> Result<(), CellCollectorError> {
+        if let Some(idx) = self.input_index(&out_point) {
+            self.used_inputs.insert(idx);
+            self.lock_expirations
+                .insert(idx, CellLockExpiry::new(ttl, self.tip_block_number));
+        }
+        Ok(())
+    }
+    fn unlock_cell(&mut self, out_point: &OutPoint) -> Result<(), CellCollectorError> {
+        if let Some(idx) = self.input_index(out_point) {
+            self.used_inputs.remove(&idx);
+            self.lock_expirations.remove(&idx);
+        }
+        Ok(())
     }
     fn apply_tx(
         &mut self,
-        _tx: Transaction,
+        tx: Transaction,
         _tip_block_number: u64,
     ) -> Result<(), CellCollectorError> {
-        unimplemented!()
+        let tx_view = tx.into_view();
+        for out_point in tx_view.input_pts_iter() {
+            if let Some(idx) = self.input_index(&out_point) {
+                self.used_inputs.insert(idx);
+                // the consumed cells never become live again
+                self.lock_expirations.remove(&idx);
+            }
+        }
+        for (idx, (output, data)) in tx_view.outputs_with_data_iter().enumerate() {
+            self.inputs.push(MockInput {
+                input: CellInput::new(OutPoint::new(tx_view.hash(), idx as u32), 0),
+                output,
+                data,
+                header: None,
+            });
+            self.pending_inputs += 1;
+        }
+        Ok(())
     }
     fn reset(&mut self) {
+        self.inputs
+            .truncate(self.inputs.len() - self.pending_inputs);
+        self.pending_inputs = 0;
         self.used_inputs.clear();
+        self.lock_expirations.clear();
     }
 }
 
-struct DummyLoader;
-impl MockResourceLoader for DummyLoader {
-    fn get_header(&mut self, hash: H256) -> Result<Option<HeaderView>, String> {
-        Err(format!("Can not call header getter, hash={:?}", hash))
-    }
-    fn get_live_cell(
-        &mut self,
-        out_point: OutPoint,
-    ) -> Result<Option<(CellOutput, Bytes, Option<Byte32>)>, String> {
-        Err(format!(
-            "Can not call live cell getter, out_point={:?}",
-            out_point
-        ))
+impl LiveCellsContext {
+    fn input_index(&self, out_point: &OutPoint) -> Option<usize> {
+        self.inputs
+            .iter()
+            .position(|item| &item.input.previous_output() == out_point)
     }
 }
 
+/// A random source seeded by `seed`, the same seed always produces the same values
+pub fn seeded_entropy(seed: u64) -> StdRng {
+    StdRng::seed_from_u64(seed)
+}
+
 pub fn random_out_point() -> OutPoint {
-    let mut rng = thread_rng();
+    random_out_point_with(&mut thread_rng())
+}
+
+pub fn random_out_point_with(rng: &mut dyn RngCore) -> OutPoint {
     let tx_hash = {
         let mut buf = [0u8; 32];
         rng.fill(&mut buf);
@@ -0,0 +1,445 @@
+use std::sync::Arc;
+
+use ckb_hash::blake2b_256;
+use ckb_types::{
+    bytes::Bytes,
+    core::{Capacity, FeeRate, ScriptHashType, TransactionBuilder, TransactionView},
+    packed::{self, CellInput, CellOutput, Script, WitnessArgs},
+    prelude::*,
+    H160,
+};
+use proptest::prelude::*;
+
+use crate::{
+    constants::ONE_CKB,
+    test_util::{random_out_point, Context},
+    tests::{
+        build_sighash_balancer, build_sighash_script, build_sighash_unlockers, init_context,
+        ACCOUNT0_ARG, ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG, ACCOUNT3_ARG, ALWAYS_SUCCESS_BIN,
+        FEE_RATE,
+    },
+    tx_builder::{
+        balance_tx_capacity, check_balanced_invariants, fill_placeholder_witnesses_with_policies,
+        transfer::CapacityTransferBuilder, tx_fee, unlock_tx, BalanceTxCapacityError,
+        CapacityBalancer, CapacityProvider, InvariantViolation, PlaceholderPolicy, TxBuilder,
+        TxBuilderError,
+    },
+    types::ScriptHashTypeExt,
+    util::{calc_fee, tx_size},
+};
+
+const RECEIVER_ARGS: [H160; 3] = [ACCOUNT0_ARG, ACCOUNT2_ARG, ACCOUNT3_ARG];
+
+fn build_balanced(
+    ctx: &Context,
+    receivers: &[(Script, u64)],
+    balancer: &CapacityBalancer,
+) -> Result<TransactionView, TxBuilderError> {
+    let outputs = receivers
+        .iter()
+        .map(|(lock, capacity)| {
+            let output = CellOutput::new_builder()
+                .lock(lock.clone())
+                .capacity(capacity.pack())
+                .build();
+            (output, Bytes::default())
+        })
+        .collect();
+    CapacityTransferBuilder::new(outputs).build_balanced(
+        &mut ctx.to_live_cells_context(),
+        ctx,
+        ctx,
+        ctx,
+        balancer,
+        &build_sighash_unlockers(&[ACCOUNT1_KEY]),
+    )
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn prop_balanced_invariants(
+        cells in prop::collection::vec(61u64..1000, 1..8),
+        fee_rate in 1000u64..5000,
+        receivers in prop::collection::vec((0usize..3, 61u64..300), 1..4),
+        change_lock_idx in prop::option::of(0usize..3),
+    ) {
+        let sender = build_sighash_script(ACCOUNT1_ARG);
+        let ctx = init_context(
+            Vec::new(),
+            cells
+                .iter()
+                .map(|capacity| (sender.clone(), Some(capacity * ONE_CKB)))
+                .collect(),
+        );
+        let receivers = receivers
+            .into_iter()
+            .map(|(idx, capacity)| {
+                (build_sighash_script(RECEIVER_ARGS[idx].clone()), capacity * ONE_CKB)
+            })
+            .collect::<Vec<_>>();
+        let mut balancer = build_sighash_balancer(&sender);
+        balancer.fee_rate = FeeRate::from_u64(fee_rate);
+        balancer.change_lock_script =
+            change_lock_idx.map(|idx| build_sighash_script(RECEIVER_ARGS[idx].clone()));
+
+        let tx = match build_balanced(&ctx, &receivers, &balancer) {
+            Ok(tx) => tx,
+            Err(TxBuilderError::BalanceCapacity(BalanceTxCapacityError::CapacityNotEnough(_))) => {
+                return Ok(());
+            }
+            Err(err) => return Err(TestCaseError::fail(err.to_string())),
+        };
+        if let Err(err) = check_balanced_invariants(&tx, &ctx, &balancer) {
+            return Err(TestCaseError::fail(err.to_string()));
+        }
+        let (tx, locked_groups) = unlock_tx(tx, &ctx, &build_sighash_unlockers(&[ACCOUNT1_KEY])).unwrap();
+        prop_assert!(locked_groups.is_empty());
+        prop_assert!(ctx.verify(tx, fee_rate).is_ok());
+    }
+}
+
+fn set_witnesses(tx: &TransactionView, witnesses: Vec<packed::Bytes>) -> TransactionView {
+    tx.as_advanced_builder().set_witnesses(witnesses).build()
+}
+
+fn set_output_capacity(tx: &TransactionView, idx: usize, capacity: u64) -> TransactionView {
+    let mut outputs: Vec<CellOutput> = tx.outputs().into_iter().collect();
+    outputs[idx] = outputs[idx]
+        .clone()
+        .as_builder()
+        .capacity(capacity.pack())
+        .build();
+    tx.as_advanced_builder().set_outputs(outputs).build()
+}
+
+#[test]
+fn test_balanced_invariant_violations() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let ctx = init_context(
+        Vec::new(),
+        vec![
+            (sender.clone(), Some(100 * ONE_CKB)),
+            (sender.clone(), Some(200 * ONE_CKB)),
+        ],
+    );
+    let balancer = build_sighash_balancer(&sender);
+    let tx = build_balanced(&ctx, &[(receiver, 120 * ONE_CKB)], &balancer).unwrap();
+    assert_eq!(tx.inputs().len(), 2);
+    assert_eq!(tx.outputs().len(), 2);
+    check_balanced_invariants(&tx, &ctx, &balancer).unwrap();
+
+    let duplicated = tx
+        .as_advanced_builder()
+        .input(tx.inputs().get(0).unwrap())
+        .witness(Bytes::default().pack())
+        .build();
+    assert!(matches!(
+        check_balanced_invariants(&duplicated, &ctx, &balancer),
+        Err(InvariantViolation::DuplicateInput(_))
+    ));
+
+    let capacity: u64 = tx.output(0).unwrap().capacity().unpack();
+    let underpaid = set_output_capacity(&tx, 0, capacity + 1);
+    assert!(matches!(
+        check_balanced_invariants(&underpaid, &ctx, &balancer),
+        Err(InvariantViolation::FeeMismatch { .. })
+    ));
+    let under_occupied = set_output_capacity(&tx, 0, ONE_CKB);
+    assert!(matches!(
+        check_balanced_invariants(&under_occupied, &ctx, &balancer),
+        Err(InvariantViolation::OutputCapacityNotEnough { index: 0, .. })
+    ));
+
+    let witnesses: Vec<packed::Bytes> = tx.witnesses().into_iter().collect();
+    let missing_witness = set_witnesses(&tx, witnesses[..1].to_vec());
+    assert!(matches!(
+        check_balanced_invariants(&missing_witness, &ctx, &balancer),
+        Err(InvariantViolation::WitnessCountMismatch(1, 2))
+    ));
+    let swapped = set_witnesses(&tx, vec![witnesses[1].clone(), witnesses[0].clone()]);
+    assert!(matches!(
+        check_balanced_invariants(&swapped, &ctx, &balancer),
+        Err(InvariantViolation::MisplacedWitness(0))
+    ));
+
+    let mut other_change = build_sighash_balancer(&sender);
+    other_change.change_lock_script = Some(build_sighash_script(ACCOUNT3_ARG));
+    assert!(matches!(
+        check_balanced_invariants(&tx, &ctx, &other_change),
+        Err(InvariantViolation::ChangeLockMismatch(1))
+    ));
+}
+
+fn sighash_placeholder() -> WitnessArgs {
+    WitnessArgs::new_builder()
+        .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+        .build()
+}
+
+/// The signed transaction has the same size as the balanced one, so the fee
+/// is exactly the min fee.
+fn assert_fee_estimated(ctx: &Context, balanced_tx: &TransactionView, tx: &TransactionView) {
+    assert_eq!(tx_size(balanced_tx), tx_size(tx));
+    let fee = tx_fee(tx.clone(), ctx, ctx).unwrap();
+    assert_eq!(fee, calc_fee(tx_size(tx), FeeRate::from_u64(FEE_RATE)));
+}
+
+#[test]
+fn test_placeholder_policy_uniform() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let ctx = init_context(
+        Vec::new(),
+        vec![
+            (sender.clone(), Some(100 * ONE_CKB)),
+            (sender.clone(), Some(100 * ONE_CKB)),
+            (sender.clone(), Some(100 * ONE_CKB)),
+        ],
+    );
+    // the placeholder of the policy overrides the placeholder of the lock script
+    let mut balancer =
+        CapacityBalancer::new_simple(sender.clone(), WitnessArgs::default(), FEE_RATE);
+    balancer
+        .capacity_provider
+        .set_placeholder_policy(sender, PlaceholderPolicy::Uniform(sighash_placeholder()));
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let balanced_tx = build_balanced(&ctx, &[(receiver, 250 * ONE_CKB)], &balancer).unwrap();
+    assert_eq!(balanced_tx.inputs().len(), 3);
+    assert_eq!(
+        balanced_tx.witnesses().get(0).unwrap().raw_data(),
+        sighash_placeholder().as_bytes()
+    );
+    for idx in 1..3 {
+        assert!(balanced_tx.witnesses().get(idx).unwrap().is_empty());
+    }
+
+    let (tx, locked_groups) = unlock_tx(
+        balanced_tx.clone(),
+        &ctx,
+        &build_sighash_unlockers(&[ACCOUNT1_KEY]),
+    )
+    .unwrap();
+    assert!(locked_groups.is_empty());
+    assert_fee_estimated(&ctx, &balanced_tx, &tx);
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_placeholder_policy_first_only() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let out_points = [random_out_point(), random_out_point()];
+    let mut ctx = init_context(Vec::new(), vec![(sender.clone(), Some(100 * ONE_CKB))]);
+    for out_point in &out_points {
+        ctx.add_simple_live_cell(out_point.clone(), sender.clone(), Some(100 * ONE_CKB));
+    }
+    // the witness of the second input is filled before
+    let base_tx = TransactionBuilder::default()
+        .inputs(
+            out_points
+                .iter()
+                .map(|out_point| CellInput::new(out_point.clone(), 0)),
+        )
+        .output(
+            CellOutput::new_builder()
+                .capacity((150 * ONE_CKB).pack())
+                .lock(build_sighash_script(ACCOUNT2_ARG))
+                .build(),
+        )
+        .output_data(Bytes::default().pack())
+        .witness(Bytes::default().pack())
+        .witness(sighash_placeholder().as_bytes().pack())
+        .build();
+
+    let mut provider = CapacityProvider::new_simple(vec![(sender.clone(), sighash_placeholder())]);
+    provider.set_placeholder_policy(
+        sender.clone(),
+        PlaceholderPolicy::Uniform(sighash_placeholder()),
+    );
+    let (tx, _) = fill_placeholder_witnesses_with_policies(
+        base_tx.clone(),
+        &ctx,
+        &build_sighash_unlockers(&[ACCOUNT1_KEY]),
+        &provider,
+    )
+    .unwrap();
+    assert_eq!(
+        tx.witnesses().get(1).unwrap().raw_data(),
+        sighash_placeholder().as_bytes()
+    );
+
+    provider.set_placeholder_policy(
+        sender.clone(),
+        PlaceholderPolicy::FirstOnly(sighash_placeholder()),
+    );
+    let (tx, _) = fill_placeholder_witnesses_with_policies(
+        base_tx,
+        &ctx,
+        &build_sighash_unlockers(&[ACCOUNT1_KEY]),
+        &provider,
+    )
+    .unwrap();
+    assert_eq!(
+        tx.witnesses().get(0).unwrap().raw_data(),
+        sighash_placeholder().as_bytes()
+    );
+    assert!(tx.witnesses().get(1).unwrap().is_empty());
+
+    let balancer = CapacityBalancer::new_with_provider(FEE_RATE, provider);
+    let balanced_tx = balance_tx_capacity(
+        &tx,
+        &balancer,
+        &mut ctx.to_live_cells_context(),
+        &ctx,
+        &ctx,
+        &ctx,
+    )
+    .unwrap();
+    assert_eq!(balanced_tx.inputs().len(), 2);
+    assert!(balanced_tx.witnesses().get(1).unwrap().is_empty());
+
+    let (tx, locked_groups) = unlock_tx(
+        balanced_tx.clone(),
+        &ctx,
+        &build_sighash_unlockers(&[ACCOUNT1_KEY]),
+    )
+    .unwrap();
+    assert!(locked_groups.is_empty());
+    assert_fee_estimated(&ctx, &balanced_tx, &tx);
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_placeholder_policy_per_input() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    // the always_success inputs are unlocked without witnesses
+    let always_success = Script::new_builder()
+        .code_hash(blake2b_256(ALWAYS_SUCCESS_BIN).pack())
+        .hash_type(ScriptHashType::Data1.to_packed())
+        .build();
+    let ctx = init_context(
+        vec![(ALWAYS_SUCCESS_BIN, true)],
+        vec![
+            (sender.clone(), Some(100 * ONE_CKB)),
+            (always_success.clone(), Some(100 * ONE_CKB)),
+            (always_success.clone(), Some(100 * ONE_CKB)),
+        ],
+    );
+    let mut provider = CapacityProvider::new_simple(vec![
+        (sender.clone(), sighash_placeholder()),
+        (always_success.clone(), sighash_placeholder()),
+    ]);
+    provider.set_placeholder_policy(
+        always_success.clone(),
+        PlaceholderPolicy::PerInput(Arc::new(|_: usize, _: &CellInput| -> Option<WitnessArgs> {
+            None
+        })),
+    );
+    let mut balancer = CapacityBalancer::new_with_provider(FEE_RATE, provider);
+    balancer.change_lock_script = Some(sender);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let balanced_tx = build_balanced(&ctx, &[(receiver, 250 * ONE_CKB)], &balancer).unwrap();
+    assert_eq!(balanced_tx.inputs().len(), 3);
+    assert_eq!(
+        balanced_tx.witnesses().get(0).unwrap().raw_data(),
+        sighash_placeholder().as_bytes()
+    );
+    for idx in 1..3 {
+        assert!(balanced_tx.witnesses().get(idx).unwrap().is_empty());
+    }
+
+    let (tx, locked_groups) = unlock_tx(
+        balanced_tx.clone(),
+        &ctx,
+        &build_sighash_unlockers(&[ACCOUNT1_KEY]),
+    )
+    .unwrap();
+    assert_eq!(locked_groups.len(), 1);
+    assert_eq!(locked_groups[0].script, always_success);
+    assert_fee_estimated(&ctx, &balanced_tx, &tx);
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+/// A change lock with 32 bytes args occupies 73 CKB, more than the 61 CKB of
+/// the sighash capacity provider lock.
+fn long_args_change_lock() -> Script {
+    Script::new_builder()
+        .code_hash(blake2b_256(ALWAYS_SUCCESS_BIN).pack())
+        .hash_type(ScriptHashType::Data1.to_packed())
+        .args(Bytes::from(vec![3u8; 32]).pack())
+        .build()
+}
+
+#[test]
+fn test_change_lock_occupied_capacity() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let change_lock = long_args_change_lock();
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    // 66 CKB is left, enough for a sighash change cell but not for the change lock
+    let receivers = [(receiver, 134 * ONE_CKB)];
+    let mut ctx = init_context(Vec::new(), vec![(sender.clone(), Some(200 * ONE_CKB))]);
+    let mut balancer = build_sighash_balancer(&sender);
+    balancer.change_lock_script = Some(change_lock.clone());
+    assert!(matches!(
+        build_balanced(&ctx, &receivers, &balancer),
+        Err(TxBuilderError::BalanceCapacity(
+            BalanceTxCapacityError::CapacityNotEnough(_)
+        ))
+    ));
+
+    // the small change is forced as fee
+    balancer.set_max_fee(Some(70 * ONE_CKB));
+    let tx = build_balanced(&ctx, &receivers, &balancer).unwrap();
+    assert_eq!(tx.outputs().len(), 1);
+    check_balanced_invariants(&tx, &ctx, &balancer).unwrap();
+
+    // with more capacity the change cell holds its occupied capacity
+    balancer.set_max_fee(None);
+    ctx.add_simple_live_cell(random_out_point(), sender, Some(100 * ONE_CKB));
+    let tx = build_balanced(&ctx, &receivers, &balancer).unwrap();
+    assert_eq!(tx.outputs().len(), 2);
+    let change = tx.output(1).unwrap();
+    assert_eq!(change.lock(), change_lock);
+    let capacity: u64 = change.capacity().unpack();
+    assert!(capacity >= 73 * ONE_CKB);
+    check_balanced_invariants(&tx, &ctx, &balancer).unwrap();
+    let (tx, locked_groups) =
+        unlock_tx(tx, &ctx, &build_sighash_unlockers(&[ACCOUNT1_KEY])).unwrap();
+    assert!(locked_groups.is_empty());
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_change_cell_template() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let change_lock = long_args_change_lock();
+    let change_type = build_sighash_script(ACCOUNT3_ARG);
+    let change_data = Bytes::from(vec![0u8; 16]);
+    let ctx = init_context(Vec::new(), vec![(sender.clone(), Some(300 * ONE_CKB))]);
+    let mut balancer = build_sighash_balancer(&sender);
+    balancer.set_change_cell_template(
+        change_lock.clone(),
+        Some(change_type.clone()),
+        change_data.clone(),
+    );
+    let (base_change, _) = balancer.base_change_cell().unwrap();
+    let occupied = base_change
+        .occupied_capacity(Capacity::bytes(16).unwrap())
+        .unwrap()
+        .as_u64();
+    // lock 73 CKB, type 53 CKB and data 16 CKB
+    assert_eq!(occupied, 142 * ONE_CKB);
+
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    // 150 CKB is left, not enough for the change cell in a 200 CKB transfer
+    assert!(build_balanced(&ctx, &[(receiver.clone(), 200 * ONE_CKB)], &balancer).is_err());
+    let tx = build_balanced(&ctx, &[(receiver, 100 * ONE_CKB)], &balancer).unwrap();
+    let (change, data) = tx.output_with_data(1).unwrap();
+    assert_eq!(change.lock(), change_lock);
+    assert_eq!(change.type_().to_opt(), Some(change_type));
+    assert_eq!(data, change_data);
+    let capacity: u64 = change.capacity().unpack();
+    assert!(capacity >= occupied);
+    check_balanced_invariants(&tx, &ctx, &balancer).unwrap();
+}
@@ -0,0 +1,158 @@
+use ckb_types::{bytes::Bytes, prelude::*};
+
+use crate::{
+    constants::ONE_CKB,
+    tests::{
+        add_udt_cell, build_sighash_balancer, build_sighash_script, build_sighash_unlockers,
+        build_sudt_script, init_context, ACCOUNT0_ARG, ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG,
+        ACCOUNT3_ARG, FEE_RATE, SUDT_BIN,
+    },
+    tx_builder::{
+        batch::{BatchTransferBuilder, TransferItem},
+        TransferAction, TxBuilder, TxBuilderError,
+    },
+};
+
+#[test]
+fn test_batch_transfer() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver_a = build_sighash_script(ACCOUNT2_ARG);
+    let receiver_b = build_sighash_script(ACCOUNT3_ARG);
+    let receiver_c = build_sighash_script(ACCOUNT0_ARG);
+    let udt_x = build_sudt_script(&receiver_a);
+    let udt_y = build_sudt_script(&receiver_b);
+    let mut ctx = init_context(
+        vec![(SUDT_BIN, false)],
+        vec![(sender.clone(), Some(1000 * ONE_CKB))],
+    );
+    let x_input1 = add_udt_cell(&mut ctx, &sender, &udt_x, 30);
+    let x_input2 = add_udt_cell(&mut ctx, &sender, &udt_x, 50);
+    let y_input = add_udt_cell(&mut ctx, &sender, &udt_y, 10);
+
+    let build_items = |y_amount: u128| {
+        vec![
+            TransferItem::new_ckb(receiver_a.clone(), 500 * ONE_CKB),
+            TransferItem::new_udt(
+                receiver_b.clone(),
+                udt_x.clone(),
+                60,
+                TransferAction::Create,
+            ),
+            TransferItem::new_udt(
+                receiver_c.clone(),
+                udt_y.clone(),
+                y_amount,
+                TransferAction::Create,
+            ),
+        ]
+    };
+
+    // udt y is short, nothing is locked
+    let mut cell_collector = ctx.to_live_cells_context();
+    let builder = BatchTransferBuilder::new(sender.clone(), build_items(11));
+    let err = builder
+        .build_base(&mut cell_collector, &ctx, &ctx, &ctx)
+        .unwrap_err();
+    match err {
+        TxBuilderError::InsufficientUdt {
+            type_script,
+            required,
+            available,
+        } => {
+            assert_eq!(type_script, udt_y);
+            assert_eq!(required, 11);
+            assert_eq!(available, 10);
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+
+    let builder = BatchTransferBuilder::new(sender.clone(), build_items(3));
+    let balancer = build_sighash_balancer(&sender);
+    let unlockers = build_sighash_unlockers(&[ACCOUNT1_KEY]);
+    let (tx, locked_groups) = builder
+        .build_unlocked(&mut cell_collector, &ctx, &ctx, &ctx, &balancer, &unlockers)
+        .unwrap();
+    assert!(locked_groups.is_empty());
+
+    // sender lock and sudt (the same code for both types)
+    assert_eq!(tx.cell_deps().len(), 2);
+    let inputs = tx.inputs().into_iter().collect::<Vec<_>>();
+    assert_eq!(inputs.len(), 4);
+    assert_eq!(inputs[0..3], [x_input1, x_input2, y_input]);
+    let outputs = tx.outputs().into_iter().collect::<Vec<_>>();
+    assert_eq!(outputs.len(), 6);
+    assert_eq!(outputs[0].lock(), receiver_a);
+    assert_eq!(outputs[0].type_().to_opt(), None);
+    let capacity: u64 = outputs[0].capacity().unpack();
+    assert_eq!(capacity, 500 * ONE_CKB);
+    assert_eq!(outputs[1].lock(), receiver_b);
+    assert_eq!(outputs[1].type_().to_opt(), Some(udt_x.clone()));
+    assert_eq!(outputs[2].lock(), receiver_c);
+    assert_eq!(outputs[2].type_().to_opt(), Some(udt_y.clone()));
+    // the udt change cells, then the capacity change cell
+    assert_eq!(outputs[3].type_().to_opt(), Some(udt_x));
+    assert_eq!(outputs[4].type_().to_opt(), Some(udt_y));
+    for output in &outputs[3..6] {
+        assert_eq!(output.lock(), sender);
+    }
+    let outputs_data = tx
+        .outputs_data()
+        .into_iter()
+        .map(|data| data.raw_data())
+        .collect::<Vec<_>>();
+    let expected_outputs_data = vec![
+        Bytes::default(),
+        Bytes::from(60u128.to_le_bytes().to_vec()),
+        Bytes::from(3u128.to_le_bytes().to_vec()),
+        Bytes::from(20u128.to_le_bytes().to_vec()),
+        Bytes::from(7u128.to_le_bytes().to_vec()),
+        Bytes::default(),
+    ];
+    assert_eq!(outputs_data, expected_outputs_data);
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_batch_transfer_update_receiver_not_found() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver_b = build_sighash_script(ACCOUNT3_ARG);
+    let receiver_c = build_sighash_script(ACCOUNT0_ARG);
+    let udt_x = build_sudt_script(&receiver_b);
+    let udt_y = build_sudt_script(&receiver_c);
+    let mut ctx = init_context(
+        vec![(SUDT_BIN, false)],
+        vec![(sender.clone(), Some(1000 * ONE_CKB))],
+    );
+    let x_input = add_udt_cell(&mut ctx, &sender, &udt_x, 50);
+    let y_input = add_udt_cell(&mut ctx, &sender, &udt_y, 10);
+    let receiver_input = add_udt_cell(&mut ctx, &receiver_b, &udt_x, 5);
+
+    let build_items = |y_action: TransferAction| {
+        vec![
+            TransferItem::new_udt(
+                receiver_b.clone(),
+                udt_x.clone(),
+                20,
+                TransferAction::Update,
+            ),
+            TransferItem::new_udt(receiver_c.clone(), udt_y.clone(), 3, y_action),
+        ]
+    };
+
+    // receiver c has no udt y cell, nothing is locked
+    let mut cell_collector = ctx.to_live_cells_context();
+    let builder = BatchTransferBuilder::new(sender.clone(), build_items(TransferAction::Update));
+    assert!(builder
+        .build_base(&mut cell_collector, &ctx, &ctx, &ctx)
+        .is_err());
+
+    let builder = BatchTransferBuilder::new(sender.clone(), build_items(TransferAction::Create));
+    let balancer = build_sighash_balancer(&sender);
+    let unlockers = build_sighash_unlockers(&[ACCOUNT1_KEY]);
+    let (tx, locked_groups) = builder
+        .build_unlocked(&mut cell_collector, &ctx, &ctx, &ctx, &balancer, &unlockers)
+        .unwrap();
+    assert_eq!(locked_groups.len(), 1);
+    let inputs = tx.inputs().into_iter().collect::<Vec<_>>();
+    assert_eq!(inputs[0..3], [x_input, y_input, receiver_input]);
+}
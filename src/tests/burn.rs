@@ -0,0 +1,141 @@
+use ckb_types::{core::TransactionView, packed::OutPoint};
+
+use crate::{
+    constants::ONE_CKB,
+    test_util::{random_out_point, Context},
+    tests::{
+        build_sighash_balancer, build_sighash_script, build_sighash_unlockers, init_context,
+        ACCOUNT1_ARG, ACCOUNT1_KEY, FEE_RATE,
+    },
+    traits::CellQueryOptions,
+    tx_builder::{
+        burn::{BurnBuilder, BurnInputs},
+        lint::{lint_tx, LintRule, NoOutputsBurn},
+        unlock_tx, BalanceTxCapacityError, CapacityBalancer, TxBuilder, TxBuilderError,
+    },
+};
+
+/// A context with the dust cells of account1
+fn init_dust_context(capacities: &[u64]) -> (Context, Vec<OutPoint>) {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let mut ctx = init_context(Vec::new(), Vec::new());
+    let out_points = capacities
+        .iter()
+        .map(|capacity| {
+            let out_point = random_out_point();
+            ctx.add_simple_live_cell(out_point.clone(), sender.clone(), Some(*capacity));
+            out_point
+        })
+        .collect();
+    (ctx, out_points)
+}
+
+fn build_burn(
+    ctx: &Context,
+    builder: &BurnBuilder,
+    balancer: &CapacityBalancer,
+) -> Result<TransactionView, TxBuilderError> {
+    builder.build_balanced(
+        &mut ctx.to_live_cells_context(),
+        ctx,
+        ctx,
+        ctx,
+        balancer,
+        &build_sighash_unlockers(&[ACCOUNT1_KEY]),
+    )
+}
+
+#[test]
+fn test_burn_confirmed() {
+    let (ctx, out_points) = init_dust_context(&[61 * ONE_CKB, 62 * ONE_CKB]);
+    let builder = BurnBuilder::new(BurnInputs::OutPoints(out_points.clone()), 200 * ONE_CKB);
+    let mut balancer = build_sighash_balancer(&build_sighash_script(ACCOUNT1_ARG));
+    balancer.confirm_burn(200 * ONE_CKB);
+
+    let tx = build_burn(&ctx, &builder, &balancer).unwrap();
+    assert!(tx.outputs().is_empty());
+    assert_eq!(
+        tx.input_pts_iter().collect::<Vec<_>>(),
+        out_points,
+        "no more cells are collected"
+    );
+    let (tx, locked_groups) =
+        unlock_tx(tx, &ctx, &build_sighash_unlockers(&[ACCOUNT1_KEY])).unwrap();
+    assert!(locked_groups.is_empty());
+    ctx.verify(tx.clone(), FEE_RATE).unwrap();
+
+    let mut rule = NoOutputsBurn::new();
+    let issues = lint_tx(&tx, &ctx, &[&rule as &dyn LintRule]).unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].output_index, None);
+    rule.confirm_burn(200 * ONE_CKB);
+    assert!(lint_tx(&tx, &ctx, &[&rule as &dyn LintRule])
+        .unwrap()
+        .is_empty());
+    rule.confirm_burn(100 * ONE_CKB);
+    assert_eq!(
+        lint_tx(&tx, &ctx, &[&rule as &dyn LintRule]).unwrap().len(),
+        1
+    );
+
+    // the cells are found by query
+    let query = CellQueryOptions::new_lock(build_sighash_script(ACCOUNT1_ARG));
+    let builder = BurnBuilder::new(BurnInputs::Query(query), 200 * ONE_CKB);
+    let tx = build_burn(&ctx, &builder, &balancer).unwrap();
+    assert!(tx.outputs().is_empty());
+    assert_eq!(tx.inputs().len(), 2);
+}
+
+#[test]
+fn test_burn_not_confirmed() {
+    let (ctx, out_points) = init_dust_context(&[61 * ONE_CKB]);
+    let builder = BurnBuilder::new(BurnInputs::OutPoints(out_points.clone()), 100 * ONE_CKB);
+
+    // the capacity is too small for a change cell
+    let mut balancer = build_sighash_balancer(&build_sighash_script(ACCOUNT1_ARG));
+    balancer.set_max_fee(Some(100 * ONE_CKB));
+    assert!(matches!(
+        build_burn(&ctx, &builder, &balancer),
+        Err(TxBuilderError::BalanceCapacity(
+            BalanceTxCapacityError::BurnNotConfirmed(fee)
+        )) if fee == 61 * ONE_CKB
+    ));
+    balancer.set_max_fee(None);
+    assert!(matches!(
+        build_burn(&ctx, &builder, &balancer),
+        Err(TxBuilderError::BalanceCapacity(
+            BalanceTxCapacityError::CapacityNotEnough(_)
+        ))
+    ));
+    // the confirmed amount is less than the fee
+    balancer.confirm_burn(10 * ONE_CKB);
+    assert!(matches!(
+        build_burn(&ctx, &builder, &balancer),
+        Err(TxBuilderError::BalanceCapacity(
+            BalanceTxCapacityError::ForceSmallChangeAsFeeFailed(_)
+        ))
+    ));
+
+    // more than the max burn of the builder
+    let builder = BurnBuilder::new(BurnInputs::OutPoints(out_points), 60 * ONE_CKB);
+    balancer.confirm_burn(100 * ONE_CKB);
+    assert!(matches!(
+        build_burn(&ctx, &builder, &balancer),
+        Err(TxBuilderError::InvalidParameter(_))
+    ));
+
+    // without the confirmation, the capacity goes back to the change cell
+    let (ctx, out_points) = init_dust_context(&[1000 * ONE_CKB]);
+    let builder = BurnBuilder::new(BurnInputs::OutPoints(out_points), 1000 * ONE_CKB);
+    let tx = build_burn(
+        &ctx,
+        &builder,
+        &build_sighash_balancer(&build_sighash_script(ACCOUNT1_ARG)),
+    )
+    .unwrap();
+    assert_eq!(tx.outputs().len(), 1);
+    assert_eq!(
+        tx.output(0).unwrap().lock(),
+        build_sighash_script(ACCOUNT1_ARG)
+    );
+}
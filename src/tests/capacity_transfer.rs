@@ -0,0 +1,84 @@
+use ckb_types::{bytes::Bytes, core::Capacity, packed::CellOutput, prelude::*};
+
+use crate::{
+    constants::ONE_CKB,
+    tests::{
+        build_sighash_balancer, build_sighash_script, build_sighash_unlockers, init_context,
+        ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG, FEE_RATE,
+    },
+    tx_builder::{transfer::CapacityTransferBuilder, TxBuilder, TxBuilderError},
+};
+
+#[test]
+fn test_capacity_transfer_output_data_too_large() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let ctx = init_context(Vec::new(), vec![(sender, Some(1000 * ONE_CKB))]);
+
+    // 61 CKB holds the receiver cell without data, but not the 100 bytes data
+    let data = Bytes::from(vec![1u8; 100]);
+    let output = CellOutput::new_builder()
+        .capacity((61 * ONE_CKB).pack())
+        .lock(receiver.clone())
+        .build();
+    let builder =
+        CapacityTransferBuilder::new(vec![(output.clone(), Bytes::default()), (output, data)]);
+    let mut cell_collector = ctx.to_live_cells_context();
+    let err = builder
+        .build_unlocked(
+            &mut cell_collector,
+            &ctx,
+            &ctx,
+            &ctx,
+            &build_sighash_balancer(&build_sighash_script(ACCOUNT1_ARG)),
+            &build_sighash_unlockers(&[ACCOUNT1_KEY]),
+        )
+        .unwrap_err();
+    match err {
+        TxBuilderError::CapacityTooSmallForCell { index, min, actual } => {
+            assert_eq!(index, 1);
+            assert_eq!(min, 161 * ONE_CKB);
+            assert_eq!(actual, 61 * ONE_CKB);
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn test_capacity_transfer_auto_capacity() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let ctx = init_context(Vec::new(), vec![(sender.clone(), Some(1000 * ONE_CKB))]);
+
+    let data = Bytes::from(vec![1u8; 100]);
+    let builder = CapacityTransferBuilder::new_with_auto_capacity(vec![
+        (receiver.clone(), None, data.clone()),
+        (receiver.clone(), None, Bytes::default()),
+    ]);
+    let mut cell_collector = ctx.to_live_cells_context();
+    let (tx, locked_groups) = builder
+        .build_unlocked(
+            &mut cell_collector,
+            &ctx,
+            &ctx,
+            &ctx,
+            &build_sighash_balancer(&build_sighash_script(ACCOUNT1_ARG)),
+            &build_sighash_unlockers(&[ACCOUNT1_KEY]),
+        )
+        .unwrap();
+
+    assert!(locked_groups.is_empty());
+    assert_eq!(tx.outputs().len(), 3);
+    let output = tx.output(0).unwrap();
+    assert_eq!(output.lock(), receiver);
+    let capacity: u64 = output.capacity().unpack();
+    assert_eq!(capacity, 161 * ONE_CKB);
+    assert!(!output
+        .is_lack_of_capacity(Capacity::bytes(data.len()).unwrap())
+        .unwrap());
+    assert_eq!(tx.outputs_data().get(0).unwrap().raw_data(), data);
+    let capacity: u64 = tx.output(1).unwrap().capacity().unpack();
+    assert_eq!(capacity, 61 * ONE_CKB);
+    assert_eq!(tx.output(2).unwrap().lock(), sender);
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
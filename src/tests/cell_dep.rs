@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+
+use ckb_jsonrpc_types as json_types;
+use ckb_types::{
+    bytes::Bytes,
+    core::{BlockView, DepType},
+    packed::{CellDep, CellOutput, OutPoint},
+    prelude::*,
+};
+
+use crate::{
+    constants::{
+        DAO_TYPE_HASH, MULTISIG_TYPE_HASH, ONE_CKB, SIGHASH_OUTPUT_LOC, SIGHASH_TYPE_HASH,
+    },
+    test_util::{self, random_out_point},
+    tests::{
+        build_dao_script, build_sighash_balancer, build_sighash_script, build_sighash_unlockers,
+        init_context, ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG, FEE_RATE, GENESIS_JSON,
+    },
+    traits::{CellDepResolver, DefaultCellDepResolver},
+    tx_builder::{transfer::CapacityTransferBuilder, TxBuilder},
+    types::{CellDepConfigError, NetworkType},
+    unlock::ScriptUnlocker,
+    util::expand_dep_group,
+    ScriptId,
+};
+
+fn genesis_resolver() -> DefaultCellDepResolver {
+    let genesis_block: json_types::BlockView = serde_json::from_str(GENESIS_JSON).unwrap();
+    let genesis_block: BlockView = genesis_block.into();
+    DefaultCellDepResolver::from_genesis(&genesis_block).unwrap()
+}
+
+fn code_cell_dep() -> CellDep {
+    CellDep::new_builder()
+        .out_point(random_out_point())
+        .dep_type(DepType::Code.into())
+        .build()
+}
+
+#[test]
+fn test_expand_dep_group() {
+    let ctx = init_context(Vec::new(), Vec::new());
+    let resolver = genesis_resolver();
+    let sighash_dep = resolver
+        .resolve(&build_sighash_script(ACCOUNT1_ARG))
+        .unwrap();
+    assert_eq!(sighash_dep.dep_type(), DepType::DepGroup.into());
+    // the secp256k1 data and the sighash code
+    let cell_deps = expand_dep_group(&sighash_dep, &ctx).unwrap();
+    assert_eq!(cell_deps.len(), 2);
+    for cell_dep in &cell_deps {
+        assert_eq!(cell_dep.dep_type(), DepType::Code.into());
+        assert_eq!(
+            cell_dep.out_point().tx_hash(),
+            sighash_dep.out_point().tx_hash()
+        );
+    }
+
+    let cell_dep = code_cell_dep();
+    assert_eq!(
+        expand_dep_group(&cell_dep, &ctx).unwrap(),
+        vec![cell_dep.clone()]
+    );
+    // the dep group cell is not live
+    let dep_group = cell_dep
+        .as_builder()
+        .dep_type(DepType::DepGroup.into())
+        .build();
+    assert!(expand_dep_group(&dep_group, &ctx).is_err());
+}
+
+#[test]
+fn test_resolver_extra_deps() {
+    let mut resolver = genesis_resolver();
+    let sighash_script = build_sighash_script(ACCOUNT1_ARG);
+    let sighash_id = ScriptId::new_type(SIGHASH_TYPE_HASH.clone());
+    let sighash_dep = resolver.resolve(&sighash_script).unwrap();
+    assert_eq!(
+        resolver.resolve_all(&sighash_script),
+        Some(vec![sighash_dep.clone()])
+    );
+
+    let extra_dep = code_cell_dep();
+    resolver.insert_extra(sighash_id.clone(), extra_dep.clone());
+    resolver.insert_extra(sighash_id.clone(), extra_dep.clone());
+    assert_eq!(resolver.resolve(&sighash_script), Some(sighash_dep.clone()));
+    assert_eq!(
+        resolver.resolve_all(&sighash_script),
+        Some(vec![sighash_dep, extra_dep])
+    );
+
+    assert!(resolver.remove(&sighash_id).is_some());
+    assert_eq!(resolver.resolve(&sighash_script), None);
+    assert_eq!(resolver.resolve_all(&sighash_script), None);
+}
+
+#[test]
+fn test_builder_extra_deps() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let ctx = init_context(Vec::new(), vec![(sender.clone(), Some(300 * ONE_CKB))]);
+    let mut resolver = genesis_resolver();
+    let dao_extra_dep = code_cell_dep();
+    let sighash_extra_dep = code_cell_dep();
+    resolver.insert_extra(
+        ScriptId::new_type(DAO_TYPE_HASH.clone()),
+        dao_extra_dep.clone(),
+    );
+    resolver.insert_extra(
+        ScriptId::new_type(SIGHASH_TYPE_HASH.clone()),
+        sighash_extra_dep.clone(),
+    );
+
+    let output = CellOutput::new_builder()
+        .capacity((120 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT2_ARG))
+        .type_(Some(build_dao_script()).pack())
+        .build();
+    let builder = CapacityTransferBuilder::new(vec![(output, Bytes::from(vec![0u8; 8]))]);
+    let balancer = build_sighash_balancer(&sender);
+    let unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>> = HashMap::default();
+    let tx = builder
+        .build_balanced(
+            &mut ctx.to_live_cells_context(),
+            &resolver,
+            &ctx,
+            &ctx,
+            &balancer,
+            &unlockers,
+        )
+        .unwrap();
+
+    // the type script deps from the builder, the lock deps from the balancer
+    let cell_deps: Vec<CellDep> = tx.cell_deps().into_iter().collect();
+    assert_eq!(
+        cell_deps,
+        vec![
+            resolver.resolve(&build_dao_script()).unwrap(),
+            dao_extra_dep,
+            resolver.resolve(&sender).unwrap(),
+            sighash_extra_dep,
+        ]
+    );
+}
+
+#[test]
+fn test_context_dep_group() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let mut ctx = init_context(Vec::new(), vec![(sender.clone(), Some(300 * ONE_CKB))]);
+    let genesis_block: json_types::BlockView = serde_json::from_str(GENESIS_JSON).unwrap();
+    let genesis_block: BlockView = genesis_block.into();
+    let genesis_tx_hash = genesis_block.transaction(0).unwrap().hash();
+    // resolve the sighash lock by its code cell, not the genesis dep group
+    ctx.cell_dep_map
+        .remove(&ScriptId::new_type(SIGHASH_TYPE_HASH.clone()));
+    let secp_data_out_point = OutPoint::new(genesis_tx_hash.clone(), 3);
+    let sighash_out_point = OutPoint::new(genesis_tx_hash, SIGHASH_OUTPUT_LOC.1 as u32);
+    let group_out_point =
+        ctx.add_dep_group("sighash", vec![secp_data_out_point, sighash_out_point]);
+    let group_dep = ctx.get_dep_group("sighash").unwrap();
+    assert_eq!(group_dep.out_point(), group_out_point);
+    assert_eq!(group_dep.dep_type(), DepType::DepGroup.into());
+    assert_eq!(ctx.resolve(&sender), Some(group_dep.clone()));
+
+    let output = CellOutput::new_builder()
+        .capacity((120 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT2_ARG))
+        .build();
+    let builder = CapacityTransferBuilder::new(vec![(output, Bytes::default())]);
+    let balancer = build_sighash_balancer(&sender);
+    let unlockers = build_sighash_unlockers(&[ACCOUNT1_KEY]);
+    let (tx, locked_groups) = builder
+        .build_unlocked(
+            &mut ctx.to_live_cells_context(),
+            &ctx,
+            &ctx,
+            &ctx,
+            &balancer,
+            &unlockers,
+        )
+        .unwrap();
+    assert!(locked_groups.is_empty());
+    assert_eq!(
+        tx.cell_deps().into_iter().collect::<Vec<_>>(),
+        vec![group_dep]
+    );
+    ctx.verify(tx.clone(), FEE_RATE).unwrap();
+
+    // the dep group cell is not in the context
+    let mut missing_group = ctx.clone();
+    let idx = missing_group
+        .cell_deps
+        .iter()
+        .position(|cell_dep| cell_dep.cell_dep.out_point() == group_out_point)
+        .unwrap();
+    missing_group.cell_deps.remove(idx);
+    missing_group.dep_data_hashes.remove(idx);
+    missing_group.dep_type_hashes.remove(idx);
+    assert!(matches!(
+        missing_group.verify_scripts(tx),
+        Err(test_util::Error::VerifyScript(_))
+    ));
+}
+
+#[test]
+fn test_resolver_config_roundtrip() {
+    let resolver = genesis_resolver();
+    let config = resolver.to_config();
+    assert_eq!(config.cell_deps.len(), 3);
+
+    let path = std::env::temp_dir().join(format!(
+        "ckb-sdk-cell-deps-{}.toml",
+        random_out_point().tx_hash()
+    ));
+    std::fs::write(&path, config.to_toml_string().unwrap()).unwrap();
+    let loaded = DefaultCellDepResolver::from_config_file(&path, None).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let loaded_json =
+        DefaultCellDepResolver::from_config_str(&config.to_json_string().unwrap(), None).unwrap();
+
+    let scripts = [
+        build_sighash_script(ACCOUNT1_ARG),
+        build_dao_script(),
+        build_sighash_script(ACCOUNT2_ARG)
+            .as_builder()
+            .code_hash(MULTISIG_TYPE_HASH.pack())
+            .build(),
+    ];
+    for loaded in [loaded, loaded_json] {
+        assert_eq!(loaded.to_config(), config);
+        for script in &scripts {
+            assert!(resolver.resolve(script).is_some());
+            assert_eq!(loaded.resolve(script), resolver.resolve(script));
+        }
+    }
+}
+
+#[test]
+fn test_resolver_config_errors() {
+    let mut config = genesis_resolver().to_config();
+    let mut item = config.cell_deps[0].clone();
+    item.name = "copy".to_string();
+    config.cell_deps.push(item);
+    match DefaultCellDepResolver::from_config(&config, Some(NetworkType::Testnet)) {
+        Err(CellDepConfigError::DuplicateScriptId(name, other)) => {
+            assert_eq!(name, "copy");
+            assert_eq!(other, config.cell_deps[0].name);
+        }
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(_) => panic!("duplicate script id is loaded"),
+    }
+    // a copy deployed on another network
+    config.cell_deps[3].network = Some("ckb".to_string());
+    assert!(DefaultCellDepResolver::from_config(&config, Some(NetworkType::Testnet)).is_ok());
+
+    config.cell_deps[3].hash_type = "Type".to_string();
+    assert!(matches!(
+        DefaultCellDepResolver::from_config(&config, Some(NetworkType::Testnet)),
+        Err(CellDepConfigError::UnknownHashType(name, _)) if name == "copy"
+    ));
+    assert!(matches!(
+        DefaultCellDepResolver::from_config_file("/nonexistent/cell_deps.toml", None),
+        Err(CellDepConfigError::Io(_))
+    ));
+}
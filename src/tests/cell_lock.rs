@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use ckb_types::{
+    bytes::Bytes,
+    core::TransactionBuilder,
+    packed::{CellInput, CellOutput, OutPoint},
+    prelude::*,
+    H256,
+};
+
+use crate::{
+    constants::ONE_CKB,
+    test_util::LiveCellsContext,
+    tests::{
+        build_sighash_balancer, build_sighash_script, init_context, ACCOUNT1_ARG, ACCOUNT2_ARG,
+    },
+    traits::{
+        mock::MockCellCollector, CellCollector, CellLockTtl, CellQueryOptions, LiveCell,
+        OffchainCellCollector, ValueRangeOption,
+    },
+    tx_builder::{
+        transfer::CapacityTransferBuilder, BalanceTxCapacityError, TxBuilder, TxBuilderError,
+    },
+    unlock::ScriptUnlocker,
+    ScriptId,
+};
+
+fn build_collector(cells: usize) -> LiveCellsContext {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let ctx = init_context(
+        Vec::new(),
+        (0..cells)
+            .map(|_| (sender.clone(), Some(100 * ONE_CKB)))
+            .collect(),
+    );
+    ctx.to_live_cells_context()
+}
+
+fn collect_all(collector: &mut LiveCellsContext) -> Vec<OutPoint> {
+    let mut query = CellQueryOptions::new_lock(build_sighash_script(ACCOUNT1_ARG));
+    query.min_total_capacity = u64::MAX;
+    query.data_len_range = Some(ValueRangeOption::new_exact(0));
+    let (cells, _) = collector.collect_live_cells(&query, false).unwrap();
+    cells.into_iter().map(|cell| cell.out_point).collect()
+}
+
+#[test]
+fn test_lock_cell_ttl_expires() {
+    let mut collector = build_collector(3);
+    let out_points = collect_all(&mut collector);
+    assert_eq!(out_points.len(), 3);
+
+    collector
+        .lock_cell_with_ttl(out_points[0].clone(), CellLockTtl::Blocks(2))
+        .unwrap();
+    collector
+        .lock_cell_with_ttl(
+            out_points[1].clone(),
+            CellLockTtl::Duration(Duration::from_secs(3600)),
+        )
+        .unwrap();
+    collector
+        .lock_cell_with_ttl(out_points[2].clone(), CellLockTtl::Duration(Duration::ZERO))
+        .unwrap();
+    // the zero duration lock expires at once
+    assert_eq!(collect_all(&mut collector), vec![out_points[2].clone()]);
+
+    collector.tip_block_number += 1;
+    assert_eq!(collect_all(&mut collector), vec![out_points[2].clone()]);
+    // the block ttl expires without `reset`
+    collector.tip_block_number += 1;
+    assert_eq!(
+        collect_all(&mut collector),
+        vec![out_points[0].clone(), out_points[2].clone()]
+    );
+
+    // unlock the cell explicitly
+    collector.unlock_cell(&out_points[1]).unwrap();
+    assert_eq!(collect_all(&mut collector), out_points);
+}
+
+#[test]
+fn test_apply_tx_clears_lock_ttl() {
+    let mut collector = build_collector(2);
+    let out_points = collect_all(&mut collector);
+    collector
+        .lock_cell_with_ttl(out_points[0].clone(), CellLockTtl::Blocks(1))
+        .unwrap();
+    let tx = TransactionBuilder::default()
+        .input(CellInput::new(out_points[0].clone(), 0))
+        .output(
+            CellOutput::new_builder()
+                .capacity((100 * ONE_CKB).pack())
+                .lock(build_sighash_script(ACCOUNT2_ARG))
+                .build(),
+        )
+        .output_data(Bytes::default().pack())
+        .build();
+    collector.apply_tx(tx.data(), 0).unwrap();
+    assert!(collector.lock_expirations.is_empty());
+    // the consumed cell stays dead after the ttl
+    collector.tip_block_number += 10;
+    assert_eq!(collect_all(&mut collector), vec![out_points[1].clone()]);
+}
+
+#[test]
+fn test_offchain_lock_cell_ttl() {
+    let mut collector = OffchainCellCollector::default();
+    let out_point = OutPoint::new(H256::from([1u8; 32]).pack(), 0);
+    let key = (H256::from([1u8; 32]), 0u32);
+    collector
+        .lock_cell_with_ttl(out_point.clone(), 10, CellLockTtl::Blocks(30))
+        .unwrap();
+    let query = CellQueryOptions::new_lock(build_sighash_script(ACCOUNT1_ARG));
+    // kept longer than the 13 blocks of the locks without a ttl
+    collector.collect(&query, 30);
+    assert!(collector.locked_cells.contains_key(&key));
+    collector.collect(&query, 40);
+    assert!(!collector.locked_cells.contains_key(&key));
+    assert!(collector.lock_expirations.is_empty());
+
+    collector
+        .lock_cell_with_ttl(out_point.clone(), 40, CellLockTtl::Blocks(30))
+        .unwrap();
+    collector.unlock_cell(&out_point).unwrap();
+    assert!(collector.locked_cells.is_empty());
+    assert!(collector.lock_expirations.is_empty());
+}
+
+#[test]
+fn test_lock_cell_ttl_unsupported() {
+    let output = CellOutput::new_builder()
+        .capacity((100 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT1_ARG))
+        .build();
+    let out_point = OutPoint::new(H256::from([1u8; 32]).pack(), 0);
+    let cell = LiveCell::new(output, Bytes::default(), out_point.clone(), 1, 1);
+    // the mock collector does not implement `lock_cell_with_ttl`
+    let mut collector = MockCellCollector::new(vec![cell]);
+    assert!(collector
+        .lock_cell_with_ttl(out_point, CellLockTtl::Blocks(30))
+        .is_err());
+    let query = CellQueryOptions::new_lock(build_sighash_script(ACCOUNT1_ARG));
+    let (cells, _) = collector.collect_live_cells(&query, false).unwrap();
+    assert_eq!(cells.len(), 1);
+}
+
+#[test]
+fn test_balancer_cell_lock_ttl() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let ctx = init_context(Vec::new(), vec![(sender.clone(), Some(300 * ONE_CKB))]);
+    let mut balancer = build_sighash_balancer(&sender);
+    balancer.set_cell_lock_ttl(Some(CellLockTtl::Blocks(5)));
+    let output = CellOutput::new_builder()
+        .capacity((120 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT2_ARG))
+        .build();
+    let builder = CapacityTransferBuilder::new(vec![(output, Bytes::default())]);
+    let unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>> = HashMap::new();
+
+    let mut collector = ctx.to_live_cells_context();
+    let tx = builder
+        .build_balanced(&mut collector, &ctx, &ctx, &ctx, &balancer, &unlockers)
+        .unwrap();
+    assert_eq!(tx.inputs().len(), 1);
+    assert_eq!(collector.lock_expirations.len(), 1);
+
+    // the transaction is abandoned, its input is still locked
+    assert!(matches!(
+        builder.build_balanced(&mut collector, &ctx, &ctx, &ctx, &balancer, &unlockers),
+        Err(TxBuilderError::BalanceCapacity(
+            BalanceTxCapacityError::CapacityNotEnough(_)
+        ))
+    ));
+    collector.tip_block_number += 5;
+    let new_tx = builder
+        .build_balanced(&mut collector, &ctx, &ctx, &ctx, &balancer, &unlockers)
+        .unwrap();
+    assert_eq!(new_tx.inputs(), tx.inputs());
+}
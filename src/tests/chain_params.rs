@@ -0,0 +1,150 @@
+use std::str::FromStr;
+
+use ckb_hash::blake2b_256;
+use ckb_types::{
+    bytes::Bytes,
+    core::{TransactionBuilder, TransactionView},
+    packed::{CellInput, CellOutput, WitnessArgs},
+    prelude::*,
+};
+
+use crate::{
+    constants::ONE_CKB,
+    test_util::{random_out_point, Context},
+    tests::{build_sighash_script, init_context, ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG},
+    traits::{CellDepResolver, SecpCkbRawKeySigner},
+    tx_builder::gen_script_groups,
+    unlock::{
+        generate_message, generate_message_with_params, ScriptSigner, SecpSighashScriptSigner,
+    },
+    Address, AddressPayload, ChainParams, NetworkInfo, NetworkType, ScriptGroup, SECP256K1,
+};
+
+fn fake_chain_params() -> ChainParams {
+    ChainParams {
+        blake2b_personalization: *b"fork-chain-hash!",
+        address_hrp_main: "frk".to_string(),
+        address_hrp_test: "frt".to_string(),
+    }
+}
+
+fn build_sighash_tx(ctx: &mut Context) -> (TransactionView, ScriptGroup) {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let input = CellInput::new(random_out_point(), 0);
+    let input_cell = CellOutput::new_builder()
+        .capacity((100 * ONE_CKB).pack())
+        .lock(sender)
+        .build();
+    ctx.add_live_cell(input.clone(), input_cell, Bytes::default(), None);
+    let output = CellOutput::new_builder()
+        .capacity((99 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT2_ARG))
+        .build();
+    let placeholder_witness = WitnessArgs::new_builder()
+        .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+        .build();
+    let tx = TransactionBuilder::default()
+        .cell_dep(ctx.resolve(&build_sighash_script(ACCOUNT1_ARG)).unwrap())
+        .input(input)
+        .output(output)
+        .output_data(Bytes::default().pack())
+        .witness(placeholder_witness.as_bytes().pack())
+        .build();
+    let group = gen_script_groups(&tx, ctx)
+        .unwrap()
+        .lock_groups
+        .into_values()
+        .next()
+        .unwrap();
+    (tx, group)
+}
+
+#[test]
+fn test_chain_params_hash() {
+    let default_params = ChainParams::default();
+    let fake_params = fake_chain_params();
+    let data = b"some data to hash";
+    assert_eq!(default_params.blake2b_256(data), blake2b_256(data));
+    assert_ne!(fake_params.blake2b_256(data), blake2b_256(data));
+    assert_eq!(
+        default_params.blake160(data).as_bytes(),
+        &blake2b_256(data)[0..20]
+    );
+
+    let network_info = NetworkInfo::testnet();
+    assert_eq!(network_info.chain_params, default_params);
+    let network_info = network_info.with_chain_params(fake_params.clone());
+    assert_eq!(network_info.chain_params, fake_params);
+}
+
+#[test]
+fn test_chain_params_message() {
+    let mut ctx = init_context(Vec::new(), Vec::new());
+    let (tx, group) = build_sighash_tx(&mut ctx);
+    let zero_lock = Bytes::from(vec![0u8; 65]);
+    let message = generate_message(&tx, &group, zero_lock.clone()).unwrap();
+    let default_message =
+        generate_message_with_params(&tx, &group, zero_lock.clone(), &ChainParams::default())
+            .unwrap();
+    assert_eq!(message, default_message);
+    let fake_message =
+        generate_message_with_params(&tx, &group, zero_lock, &fake_chain_params()).unwrap();
+    assert_ne!(message, fake_message);
+
+    let key = secp256k1::SecretKey::from_slice(ACCOUNT1_KEY.as_bytes()).unwrap();
+    let sign = |chain_params: Option<ChainParams>| {
+        let signer = Box::new(SecpCkbRawKeySigner::new_with_secret_keys(vec![key]));
+        let script_signer = match chain_params {
+            Some(chain_params) => {
+                SecpSighashScriptSigner::new_with_chain_params(signer, chain_params)
+            }
+            None => SecpSighashScriptSigner::new(signer),
+        };
+        script_signer.sign_tx(&tx, &group).unwrap()
+    };
+    let signed_tx = sign(None);
+    assert_eq!(signed_tx.data(), sign(Some(ChainParams::default())).data());
+    assert_ne!(
+        signed_tx.witnesses(),
+        sign(Some(fake_chain_params())).witnesses()
+    );
+    ctx.verify_scripts(signed_tx).unwrap();
+}
+
+#[test]
+fn test_chain_params_address() {
+    let default_params = ChainParams::default();
+    let fake_params = fake_chain_params();
+    let key = secp256k1::SecretKey::from_slice(ACCOUNT1_KEY.as_bytes()).unwrap();
+    let pubkey = secp256k1::PublicKey::from_secret_key(&SECP256K1, &key);
+
+    let payload = AddressPayload::from_pubkey(&pubkey);
+    assert_eq!(
+        AddressPayload::from_pubkey_with_params(&pubkey, &default_params),
+        payload
+    );
+    assert_ne!(
+        AddressPayload::from_pubkey_with_params(&pubkey, &fake_params),
+        payload
+    );
+
+    for network in [NetworkType::Mainnet, NetworkType::Testnet] {
+        let address = Address::new(network, payload.clone(), true);
+        let encoded = address.to_string();
+        assert_eq!(address.display_with_params(&default_params), encoded);
+        assert_eq!(
+            Address::from_str_with_params(&encoded, &default_params).unwrap(),
+            address
+        );
+
+        let fake_encoded = address.display_with_params(&fake_params);
+        assert_ne!(fake_encoded, encoded);
+        assert!(fake_encoded.starts_with(fake_params.hrp(network)));
+        assert_eq!(
+            Address::from_str_with_params(&fake_encoded, &fake_params).unwrap(),
+            address
+        );
+        assert!(Address::from_str(&fake_encoded).is_err());
+        assert!(Address::from_str_with_params(&encoded, &fake_params).is_err());
+    }
+}
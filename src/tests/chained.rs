@@ -0,0 +1,176 @@
+use ckb_types::{
+    bytes::Bytes,
+    core::{DepType, HeaderBuilder},
+    packed::{CellDep, CellOutput, Script},
+    prelude::*,
+};
+
+use crate::{
+    constants::ONE_CKB,
+    test_util::random_out_point,
+    tests::{build_sighash_script, ACCOUNT1_ARG, ACCOUNT2_ARG},
+    traits::{
+        dummy_impls::{DummyHeaderDepResolver, DummyTransactionDependencyProvider},
+        mock::{MockHeaderDepResolver, MockTransactionDependencyProvider},
+        CellDepResolver, ChainedCellDepResolver, ChainedHeaderDepResolver, ChainedSourcesError,
+        ChainedTransactionDependencyProvider, HeaderDepResolver, OffchainCellDepResolver,
+        TransactionDependencyError, TransactionDependencyProvider,
+    },
+    ScriptId,
+};
+
+fn build_output(capacity: u64) -> CellOutput {
+    CellOutput::new_builder()
+        .capacity((capacity * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT1_ARG))
+        .build()
+}
+
+#[test]
+fn test_chained_tx_dep_provider_fallback() {
+    let shared = random_out_point();
+    let backup_only = random_out_point();
+    let mut primary = MockTransactionDependencyProvider::new();
+    primary.add_cell(shared.clone(), build_output(100), Bytes::default());
+    let mut backup = MockTransactionDependencyProvider::new();
+    backup.add_cell(shared.clone(), build_output(200), Bytes::default());
+    backup.add_cell(
+        backup_only.clone(),
+        build_output(300),
+        Bytes::from(vec![1u8]),
+    );
+    let provider = ChainedTransactionDependencyProvider(vec![
+        Box::new(DummyTransactionDependencyProvider),
+        Box::new(primary),
+        Box::new(backup),
+    ]);
+
+    // the first source finding the cell wins, the failed source is skipped
+    assert_eq!(provider.get_cell(&shared).unwrap(), build_output(100));
+    // not found in the primary source
+    assert_eq!(provider.get_cell(&backup_only).unwrap(), build_output(300));
+    assert_eq!(
+        provider.get_cell_data(&backup_only).unwrap(),
+        Bytes::from(vec![1u8])
+    );
+
+    // not found anywhere, the error of the dummy source is reported
+    match provider.get_cell(&random_out_point()) {
+        Err(TransactionDependencyError::Other(err)) => {
+            let err = err.downcast::<ChainedSourcesError>().unwrap();
+            assert_eq!(err.errors.len(), 1);
+            assert_eq!(err.errors[0].0, 0);
+            assert!(err.to_string().contains("dummy get_cell"));
+        }
+        result => panic!("unexpected result: {:?}", result),
+    }
+    // no source has a block extension
+    match provider.get_block_extension(&random_out_point().tx_hash()) {
+        Err(TransactionDependencyError::Other(err)) => {
+            assert_eq!(
+                err.downcast::<ChainedSourcesError>().unwrap().errors.len(),
+                1
+            );
+        }
+        result => panic!("unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+fn test_chained_tx_dep_provider_not_found() {
+    let provider = ChainedTransactionDependencyProvider(vec![
+        Box::new(MockTransactionDependencyProvider::new()),
+        Box::new(MockTransactionDependencyProvider::new()),
+    ]);
+    assert!(matches!(
+        provider.get_cell(&random_out_point()),
+        Err(TransactionDependencyError::NotFound(_))
+    ));
+    assert_eq!(
+        provider
+            .get_block_extension(&random_out_point().tx_hash())
+            .unwrap(),
+        None
+    );
+
+    let provider = ChainedTransactionDependencyProvider(vec![
+        Box::new(DummyTransactionDependencyProvider),
+        Box::new(DummyTransactionDependencyProvider),
+    ]);
+    match provider.get_transaction(&random_out_point().tx_hash()) {
+        Err(TransactionDependencyError::Other(err)) => {
+            let err = err.downcast::<ChainedSourcesError>().unwrap();
+            assert_eq!(
+                err.errors.iter().map(|(idx, _)| *idx).collect::<Vec<_>>(),
+                vec![0, 1]
+            );
+        }
+        result => panic!("unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+fn test_chained_header_dep_resolver() {
+    let header5 = HeaderBuilder::default().number(5u64.pack()).build();
+    let backup_header5 = HeaderBuilder::default()
+        .number(5u64.pack())
+        .timestamp(1u64.pack())
+        .build();
+    let header6 = HeaderBuilder::default().number(6u64.pack()).build();
+    let resolver = ChainedHeaderDepResolver(vec![
+        Box::new(MockHeaderDepResolver::new(vec![header5.clone()])),
+        Box::new(DummyHeaderDepResolver),
+        Box::new(MockHeaderDepResolver::new(vec![
+            backup_header5,
+            header6.clone(),
+        ])),
+    ]);
+    assert_eq!(resolver.resolve_by_number(5).unwrap(), Some(header5));
+    assert_eq!(resolver.resolve_by_number(6).unwrap(), Some(header6));
+    let err = resolver.resolve_by_number(7).unwrap_err();
+    let err = err.downcast::<ChainedSourcesError>().unwrap();
+    assert_eq!(err.errors.len(), 1);
+    assert_eq!(err.errors[0].0, 1);
+
+    let resolver = ChainedHeaderDepResolver(vec![
+        Box::new(MockHeaderDepResolver::default()),
+        Box::new(MockHeaderDepResolver::default()),
+    ]);
+    assert_eq!(resolver.resolve_by_number(7).unwrap(), None);
+}
+
+#[test]
+fn test_chained_cell_dep_resolver() {
+    let build_resolver = |scripts: &[(ScriptId, CellDep)]| {
+        let mut resolver = OffchainCellDepResolver::default();
+        for (script_id, cell_dep) in scripts {
+            resolver
+                .items
+                .insert(script_id.clone(), (cell_dep.clone(), String::new()));
+        }
+        resolver
+    };
+    let build_cell_dep = || {
+        CellDep::new_builder()
+            .out_point(random_out_point())
+            .dep_type(DepType::Code.into())
+            .build()
+    };
+    let script1 = build_sighash_script(ACCOUNT1_ARG);
+    let script2 = Script::new_builder()
+        .code_hash([1u8; 32].pack())
+        .args(Bytes::from(ACCOUNT2_ARG.as_bytes().to_vec()).pack())
+        .build();
+    let (dep1, backup_dep1, dep2) = (build_cell_dep(), build_cell_dep(), build_cell_dep());
+    let resolver = ChainedCellDepResolver(vec![
+        Box::new(build_resolver(&[(ScriptId::from(&script1), dep1.clone())])),
+        Box::new(build_resolver(&[
+            (ScriptId::from(&script1), backup_dep1),
+            (ScriptId::from(&script2), dep2.clone()),
+        ])),
+    ]);
+    assert_eq!(resolver.resolve(&script1), Some(dep1));
+    assert_eq!(resolver.resolve(&script2), Some(dep2));
+    let script3 = Script::new_builder().code_hash([2u8; 32].pack()).build();
+    assert_eq!(resolver.resolve(&script3), None);
+}
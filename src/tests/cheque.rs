@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+
+use ckb_types::{
+    core::{TransactionBuilder, TransactionView},
+    packed::{CellInput, Script, WitnessArgs},
+    prelude::*,
+    H160, H256,
+};
+
+use crate::{
+    constants::CHEQUE_CELL_SINCE,
+    test_util::random_out_point,
+    tests::{
+        build_cheque_script, build_sighash_script, ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG,
+        ACCOUNT2_KEY, ACCOUNT3_ARG, ACCOUNT3_KEY,
+    },
+    traits::SecpCkbRawKeySigner,
+    types::ScriptGroup,
+    unlock::{ChequeAction, ChequeScriptSigner, ScriptSignError, ScriptSigner},
+};
+
+/// The cheque lock args are lock hash prefixes, so the keys are mapped by
+/// `lock_hash[0..20]` instead of the pubkey hash.
+fn build_signer(keys: &[(&H256, &Script)]) -> ChequeScriptSigner {
+    let keys: HashMap<H160, secp256k1::SecretKey> = keys
+        .iter()
+        .map(|(key, lock)| {
+            let key = secp256k1::SecretKey::from_slice(key.as_bytes()).unwrap();
+            let id = H160::from_slice(&lock.calc_script_hash().as_slice()[0..20]).unwrap();
+            (id, key)
+        })
+        .collect();
+    ChequeScriptSigner::new_auto(Box::new(SecpCkbRawKeySigner::new(keys)))
+}
+
+fn build_cheque_tx(since_list: &[u64]) -> (TransactionView, ScriptGroup) {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let cheque_script = build_cheque_script(&sender, &receiver, H256::default());
+    let tx = TransactionBuilder::default()
+        .inputs(
+            since_list
+                .iter()
+                .map(|since| CellInput::new(random_out_point(), *since)),
+        )
+        .build();
+    let mut script_group = ScriptGroup::from_lock_script(&cheque_script);
+    script_group.input_indices = (0..since_list.len()).collect();
+    (tx, script_group)
+}
+
+fn witness_lock_len(tx: &TransactionView) -> Option<usize> {
+    let witness = tx.witnesses().get(0).unwrap().raw_data();
+    WitnessArgs::from_slice(witness.as_ref())
+        .unwrap()
+        .lock()
+        .to_opt()
+        .map(|lock| lock.raw_data().len())
+}
+
+#[test]
+fn test_cheque_auto_action_receiver() {
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let signer = build_signer(&[(&ACCOUNT2_KEY, &receiver)]);
+    assert!(signer.is_auto());
+
+    let (tx, script_group) = build_cheque_tx(&[0, 0]);
+    let args = script_group.script.args().raw_data();
+    assert!(signer.match_args(args.as_ref()));
+    assert_eq!(signer.owner_id(args.as_ref()), &args[0..20]);
+    assert_eq!(
+        signer.action_for(&tx, &script_group).unwrap(),
+        ChequeAction::Claim
+    );
+    let signed_tx = signer.sign_tx(&tx, &script_group).unwrap();
+    assert_eq!(witness_lock_len(&signed_tx), Some(65));
+
+    // the receiver can not withdraw
+    let (tx, script_group) = build_cheque_tx(&[0, CHEQUE_CELL_SINCE]);
+    assert!(matches!(
+        signer.sign_tx(&tx, &script_group),
+        Err(ScriptSignError::ChequeActionNotInferred(_))
+    ));
+}
+
+#[test]
+fn test_cheque_auto_action_sender() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let signer = build_signer(&[(&ACCOUNT1_KEY, &sender)]);
+
+    let (tx, script_group) = build_cheque_tx(&[CHEQUE_CELL_SINCE]);
+    let args = script_group.script.args().raw_data();
+    assert!(signer.match_args(args.as_ref()));
+    assert_eq!(signer.owner_id(args.as_ref()), &args[20..40]);
+    assert_eq!(
+        signer.action_for(&tx, &script_group).unwrap(),
+        ChequeAction::Withdraw
+    );
+    let signed_tx = signer.sign_tx(&tx, &script_group).unwrap();
+    assert_eq!(witness_lock_len(&signed_tx), Some(65));
+
+    // the sender can not claim
+    let (tx, script_group) = build_cheque_tx(&[0]);
+    assert!(matches!(
+        signer.action_for(&tx, &script_group),
+        Err(ScriptSignError::ChequeActionNotInferred(_))
+    ));
+}
+
+#[test]
+fn test_cheque_auto_action_both_keys() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let signer = build_signer(&[(&ACCOUNT1_KEY, &sender), (&ACCOUNT2_KEY, &receiver)]);
+
+    let (tx, script_group) = build_cheque_tx(&[0]);
+    assert_eq!(
+        signer.action_for(&tx, &script_group).unwrap(),
+        ChequeAction::Claim
+    );
+    let (tx, script_group) = build_cheque_tx(&[CHEQUE_CELL_SINCE]);
+    assert_eq!(
+        signer.action_for(&tx, &script_group).unwrap(),
+        ChequeAction::Withdraw
+    );
+    let (tx, script_group) = build_cheque_tx(&[0, CHEQUE_CELL_SINCE]);
+    assert!(signer.action_for(&tx, &script_group).is_err());
+}
+
+#[test]
+fn test_cheque_auto_action_other_key() {
+    let other = build_sighash_script(ACCOUNT3_ARG);
+    let signer = build_signer(&[(&ACCOUNT3_KEY, &other)]);
+
+    let (tx, script_group) = build_cheque_tx(&[0]);
+    let args = script_group.script.args().raw_data();
+    assert!(!signer.match_args(args.as_ref()));
+    assert!(signer.owner_id(args.as_ref()).is_empty());
+    assert!(matches!(
+        signer.sign_tx(&tx, &script_group),
+        Err(ScriptSignError::ChequeActionNotInferred(_))
+    ));
+
+    // the explicit action is not inferred
+    let explicit_signer = ChequeScriptSigner::new(
+        Box::new(SecpCkbRawKeySigner::new_with_secret_keys(vec![])),
+        ChequeAction::Withdraw,
+    );
+    assert!(!explicit_signer.is_auto());
+    assert_eq!(
+        explicit_signer.action_for(&tx, &script_group).unwrap(),
+        ChequeAction::Withdraw
+    );
+}
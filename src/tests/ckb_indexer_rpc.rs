@@ -4,6 +4,7 @@ use crate::{
         CkbRpcClient,
     },
     traits::{CellQueryOptions, ValueRangeOption},
+    types::ScriptHashTypeExt,
 };
 use ckb_types::{core::ScriptHashType, h256, prelude::*, H256};
 // use serde_json;
@@ -37,7 +38,7 @@ fn test_cells_search_mode_default_partitial() {
     // default with partitial args
     let script = ckb_types::packed::Script::new_builder()
         .code_hash(CODE_HASH.pack())
-        .hash_type(ScriptHashType::Type.into())
+        .hash_type(ScriptHashType::Type.to_packed())
         .args(ARGS[0..2].pack())
         .build();
 
@@ -60,7 +61,7 @@ fn test_cells_search_mode_prefix_partitial() {
     // prefix with partitial args
     let script = ckb_types::packed::Script::new_builder()
         .code_hash(CODE_HASH.pack())
-        .hash_type(ScriptHashType::Type.into())
+        .hash_type(ScriptHashType::Type.to_packed())
         .args(ARGS[0..2].pack())
         .build();
 
@@ -82,7 +83,7 @@ fn test_cells_search_mode_exact_partitial() {
     let block_range = Some(ValueRangeOption::new(0, 1));
     let script = ckb_types::packed::Script::new_builder()
         .code_hash(CODE_HASH.pack())
-        .hash_type(ScriptHashType::Type.into())
+        .hash_type(ScriptHashType::Type.to_packed())
         .args(ARGS[0..2].pack())
         .build();
     // exact with partitial args
@@ -104,7 +105,7 @@ fn test_cells_search_mode_exact() {
     let block_range = Some(ValueRangeOption::new(0, 1));
     let script = ckb_types::packed::Script::new_builder()
         .code_hash(CODE_HASH.pack())
-        .hash_type(ScriptHashType::Type.into())
+        .hash_type(ScriptHashType::Type.to_packed())
         .args(ARGS[..].pack())
         .build();
 
@@ -127,7 +128,7 @@ fn test_get_transactions_search_mode_default() {
     let block_range = Some(ValueRangeOption::new(0, 1));
     let script = ckb_types::packed::Script::new_builder()
         .code_hash(CODE_HASH.pack())
-        .hash_type(ScriptHashType::Type.into())
+        .hash_type(ScriptHashType::Type.to_packed())
         .args(ARGS[0..2].pack())
         .build();
 
@@ -150,7 +151,7 @@ fn test_get_transactions_search_mode_prefix_partial() {
     let block_range = Some(ValueRangeOption::new(0, 1));
     let script = ckb_types::packed::Script::new_builder()
         .code_hash(CODE_HASH.pack())
-        .hash_type(ScriptHashType::Type.into())
+        .hash_type(ScriptHashType::Type.to_packed())
         .args(ARGS[0..2].pack())
         .build();
 
@@ -172,7 +173,7 @@ fn test_get_transactions_search_mode_exact_partitial() {
     let block_range = Some(ValueRangeOption::new(0, 1));
     let script = ckb_types::packed::Script::new_builder()
         .code_hash(CODE_HASH.pack())
-        .hash_type(ScriptHashType::Type.into())
+        .hash_type(ScriptHashType::Type.to_packed())
         .args(ARGS[0..2].pack())
         .build();
 
@@ -195,7 +196,7 @@ fn test_get_transactions_search_mode_exact_full() {
     // exact search
     let script = ckb_types::packed::Script::new_builder()
         .code_hash(CODE_HASH.pack())
-        .hash_type(ScriptHashType::Type.into())
+        .hash_type(ScriptHashType::Type.to_packed())
         .args(ARGS[..].pack())
         .build();
 
@@ -217,7 +218,7 @@ fn test_get_cells_capacity_search_mode_default() {
     let block_range = Some(ValueRangeOption::new(0, 1));
     let script = ckb_types::packed::Script::new_builder()
         .code_hash(CODE_HASH.pack())
-        .hash_type(ScriptHashType::Type.into())
+        .hash_type(ScriptHashType::Type.to_packed())
         .args(ARGS[0..2].pack())
         .build();
 
@@ -238,7 +239,7 @@ fn test_get_cells_capacity_search_mode_prefix_partial() {
     let block_range = Some(ValueRangeOption::new(0, 1));
     let script = ckb_types::packed::Script::new_builder()
         .code_hash(CODE_HASH.pack())
-        .hash_type(ScriptHashType::Type.into())
+        .hash_type(ScriptHashType::Type.to_packed())
         .args(ARGS[0..2].pack())
         .build();
 
@@ -259,7 +260,7 @@ fn test_get_cells_capacity_search_mode_exact_partital() {
     let block_range = Some(ValueRangeOption::new(0, 1));
     let script = ckb_types::packed::Script::new_builder()
         .code_hash(CODE_HASH.pack())
-        .hash_type(ScriptHashType::Type.into())
+        .hash_type(ScriptHashType::Type.to_packed())
         .args(ARGS[0..2].pack())
         .build();
     // exact with partitial args
@@ -280,7 +281,7 @@ fn test_get_cells_capacity_search_mode_exact() {
     let block_range = Some(ValueRangeOption::new(0, 1));
     let script = ckb_types::packed::Script::new_builder()
         .code_hash(CODE_HASH.pack())
-        .hash_type(ScriptHashType::Type.into())
+        .hash_type(ScriptHashType::Type.to_packed())
         .args(ARGS[..].pack())
         .build();
 
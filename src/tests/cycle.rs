@@ -1,25 +1,31 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use bytes::{Bytes, BytesMut};
 use ckb_hash::blake2b_256;
+use ckb_script::ScriptError;
 use ckb_types::{
     core::{ScriptHashType, TransactionView},
     packed::{self, CellOutput, Script, WitnessArgs},
-    prelude::{Builder, Entity, Pack},
+    prelude::{Builder, Entity, Pack, Unpack},
     H256,
 };
 
 use crate::test_util::Context;
+use crate::types::ScriptHashTypeExt;
 use crate::{
     constants::ONE_CKB,
-    tests::{build_sighash_script, init_context, ACCOUNT2_ARG, FEE_RATE},
+    tests::{
+        build_dao_script, build_sighash_script, init_context, ACCOUNT1_ARG, ACCOUNT2_ARG, FEE_RATE,
+    },
     traits::TransactionDependencyProvider,
     tx_builder::{
-        fill_placeholder_witnesses, transfer::CapacityTransferBuilder, unlock_tx,
-        BalanceTxCapacityError, CapacityBalancer, TxBuilder, TxBuilderError,
+        fill_placeholder_witnesses, latest_consensus, transfer::CapacityTransferBuilder, unlock_tx,
+        verify_cycles, BalanceTxCapacityError, CapacityBalancer, GroupCycles, TxBuilder,
+        TxBuilderError, VerifyCyclesError,
     },
     unlock::{ScriptUnlocker, UnlockError},
-    ScriptGroup, ScriptId,
+    ScriptGroup, ScriptGroupType, ScriptId,
 };
 
 const CYCLE_BIN: &[u8] = include_bytes!("../test-data/cycle");
@@ -69,7 +75,7 @@ fn build_script(loops: u64) -> Script {
     let cycle_data_hash = H256::from(blake2b_256(CYCLE_BIN));
     Script::new_builder()
         .code_hash(cycle_data_hash.pack())
-        .hash_type(ScriptHashType::Data.into())
+        .hash_type(ScriptHashType::Data.to_packed())
         .args(build_args(loops).pack())
         .build()
 }
@@ -363,3 +369,101 @@ fn vsize_big_and_cannot_find_more_capacity() {
         panic!("not expected result: {:?}", result);
     }
 }
+
+#[test]
+fn test_verify_cycles() {
+    let loops = 1024;
+    let sender = build_script(loops);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let ctx: &'static Context = Box::leak(Box::new(init_context(
+        vec![(CYCLE_BIN, true)],
+        vec![(sender.clone(), Some(200 * ONE_CKB))],
+    )));
+
+    let output = CellOutput::new_builder()
+        .capacity((140 * ONE_CKB).pack())
+        .lock(receiver)
+        .build();
+    let builder = CapacityTransferBuilder::new(vec![(output, Bytes::default())]);
+    let balancer = CapacityBalancer::new_simple(sender.clone(), WitnessArgs::default(), FEE_RATE);
+    let mut cell_collector = ctx.to_live_cells_context();
+    let unlockers = build_cycle_unlockers(loops);
+    let (tx, _) = builder
+        .build_balance_unlocked(&mut cell_collector, ctx, ctx, ctx, &balancer, &unlockers)
+        .unwrap();
+
+    let consensus = Arc::new(latest_consensus());
+    let report = verify_cycles(&tx, ctx, ctx, Arc::clone(&consensus), u64::MAX).unwrap();
+    assert_eq!(report.total, ctx.verify_scripts(tx.clone()).unwrap());
+    assert_eq!(
+        report.groups,
+        vec![GroupCycles {
+            script_hash: sender.calc_script_hash().unpack(),
+            group_type: ScriptGroupType::Lock,
+            cycles: report.total,
+        }]
+    );
+
+    match verify_cycles(&tx, ctx, ctx, consensus, report.total - 1) {
+        Err(VerifyCyclesError::Script {
+            group_type,
+            script_hash,
+            error: ScriptError::ExceededMaximumCycles(_),
+        }) => {
+            assert_eq!(group_type, ScriptGroupType::Lock);
+            assert_eq!(script_hash, sender.calc_script_hash().unpack());
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn test_build_balanced_verify_type_script_cycles() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let ctx = init_context(Vec::new(), vec![(sender.clone(), Some(300 * ONE_CKB))]);
+    let output = CellOutput::new_builder()
+        .capacity((120 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT2_ARG))
+        .type_(Some(build_dao_script()).pack())
+        .build();
+    let builder = CapacityTransferBuilder::new(vec![(output, Bytes::from(vec![0u8; 8]))]);
+    let placeholder_witness = WitnessArgs::new_builder()
+        .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+        .build();
+    let mut balancer = CapacityBalancer::new_simple(sender, placeholder_witness, FEE_RATE);
+    let unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>> = HashMap::default();
+
+    // the sighash lock is not signed, only the dao type script runs
+    balancer.verify_type_script_cycles = Some(u64::MAX);
+    builder
+        .build_balanced(
+            &mut ctx.to_live_cells_context(),
+            &ctx,
+            &ctx,
+            &ctx,
+            &balancer,
+            &unlockers,
+        )
+        .unwrap();
+
+    balancer.verify_type_script_cycles = Some(1);
+    let result = builder.build_balanced(
+        &mut ctx.to_live_cells_context(),
+        &ctx,
+        &ctx,
+        &ctx,
+        &balancer,
+        &unlockers,
+    );
+    match result {
+        Err(TxBuilderError::VerifyCycles(VerifyCyclesError::Script {
+            group_type,
+            script_hash,
+            error: ScriptError::ExceededMaximumCycles(_),
+        })) => {
+            assert_eq!(group_type, ScriptGroupType::Type);
+            assert_eq!(script_hash, build_dao_script().calc_script_hash().unpack());
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use ckb_types::{
+    bytes::Bytes,
+    packed::{CellInput, CellOutput, OutPoint, Script, WitnessArgs},
+    prelude::*,
+};
+
+use crate::{
+    constants::ONE_CKB,
+    devnet::{
+        generate_accounts, wait_funded, AlwaysSuccessUnlocker, DevnetError, FundingPlanBuilder,
+    },
+    tests::{
+        build_sighash_balancer, build_sighash_script, build_sighash_unlockers, init_context,
+        omni_lock_util::build_always_success_script, ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG,
+        ALWAYS_SUCCESS_BIN, FEE_RATE,
+    },
+    traits::NodeTxLimits,
+    tx_builder::{transfer::CapacityTransferBuilder, CapacityBalancer, TxBuilder},
+    unlock::ScriptUnlocker,
+    Address, ScriptId,
+};
+
+const ACCOUNTS: usize = 50;
+const CELLS_PER_ACCOUNT: usize = 2;
+
+#[test]
+fn test_generate_accounts() {
+    let accounts = generate_accounts(ACCOUNTS, b"devnet");
+    assert_eq!(accounts.len(), ACCOUNTS);
+    assert_eq!(accounts, generate_accounts(ACCOUNTS, b"devnet"));
+    assert_eq!(&accounts[..10], &generate_accounts(10, b"devnet")[..]);
+    assert_ne!(accounts[0], generate_accounts(1, b"other")[0]);
+    let addresses = accounts
+        .iter()
+        .map(|(_, address)| address.to_string())
+        .collect::<HashSet<_>>();
+    assert_eq!(addresses.len(), ACCOUNTS);
+}
+
+#[test]
+fn test_devnet_funding_flow() {
+    let funder = build_sighash_script(ACCOUNT1_ARG);
+    let mut ctx = init_context(Vec::new(), vec![(funder.clone(), Some(20_000 * ONE_CKB))]);
+    let addresses = generate_accounts(ACCOUNTS, b"devnet")
+        .into_iter()
+        .map(|(_, address)| address)
+        .collect::<Vec<Address>>();
+
+    let mut builder =
+        FundingPlanBuilder::new(funder.clone(), &addresses, CELLS_PER_ACCOUNT, 100 * ONE_CKB);
+    builder.max_outputs_per_tx = Some(30);
+    let batches = builder.plan().unwrap();
+    assert_eq!(
+        batches.iter().map(|items| items.len()).collect::<Vec<_>>(),
+        vec![30, 30, 30, 10]
+    );
+
+    let unlockers = build_sighash_unlockers(&[ACCOUNT1_KEY]);
+    let balancer = build_sighash_balancer(&funder);
+
+    let txs = builder
+        .build_transactions(
+            &mut ctx.to_live_cells_context(),
+            &ctx,
+            &ctx,
+            &ctx,
+            &balancer,
+            &unlockers,
+            0,
+        )
+        .unwrap();
+    assert_eq!(txs.len(), batches.len());
+
+    // the accounts are not funded before the transactions are committed
+    let err = wait_funded(
+        &mut ctx.to_live_cells_context(),
+        &addresses,
+        CELLS_PER_ACCOUNT,
+        Duration::from_millis(20),
+        Duration::from_millis(5),
+    )
+    .unwrap_err();
+    assert!(matches!(err, DevnetError::Timeout(ACCOUNTS)));
+
+    for (idx, tx) in txs.iter().enumerate() {
+        if idx > 0 {
+            // spends the change of the previous batch
+            let change_idx = txs[idx - 1].outputs().len() - 1;
+            let change = OutPoint::new(txs[idx - 1].hash(), change_idx as u32);
+            assert_eq!(tx.inputs().len(), 1);
+            assert_eq!(tx.inputs().get(0).unwrap().previous_output(), change);
+            assert_eq!(txs[idx - 1].output(change_idx).unwrap().lock(), funder);
+        }
+        assert!(
+            tx.data().as_reader().serialized_size_in_block() as u64 <= builder.limits.max_tx_size
+        );
+        ctx.verify(tx.clone(), FEE_RATE).unwrap();
+        for (out_idx, (output, data)) in tx.outputs_with_data_iter().enumerate() {
+            let input = CellInput::new(OutPoint::new(tx.hash(), out_idx as u32), 0);
+            ctx.add_live_cell(input, output, data, None);
+        }
+    }
+    wait_funded(
+        &mut ctx.to_live_cells_context(),
+        &addresses,
+        CELLS_PER_ACCOUNT,
+        Duration::from_secs(1),
+        Duration::from_millis(5),
+    )
+    .unwrap();
+    let receiver = Script::from(&addresses[ACCOUNTS - 1]);
+    let funded = txs
+        .iter()
+        .flat_map(|tx| tx.outputs().into_iter())
+        .filter(|output| output.lock() == receiver)
+        .count();
+    assert_eq!(funded, CELLS_PER_ACCOUNT);
+}
+
+#[test]
+fn test_funding_plan_limits() {
+    let funder = build_sighash_script(ACCOUNT1_ARG);
+    let addresses = generate_accounts(10, b"limits")
+        .into_iter()
+        .map(|(_, address)| address)
+        .collect::<Vec<Address>>();
+    let mut builder = FundingPlanBuilder::new(funder, &addresses, 3, 100 * ONE_CKB);
+    assert_eq!(builder.plan().unwrap().len(), 1);
+
+    // 109 bytes for every output
+    builder.limits = NodeTxLimits {
+        max_tx_size: builder.reserved_tx_size + 109 * 7,
+        ..Default::default()
+    };
+    let batches = builder.plan().unwrap();
+    assert_eq!(
+        batches.iter().map(|items| items.len()).collect::<Vec<_>>(),
+        vec![7, 7, 7, 7, 2]
+    );
+
+    builder.limits.max_tx_size = builder.reserved_tx_size;
+    assert!(matches!(builder.plan(), Err(DevnetError::InvalidPlan(_))));
+    builder.limits = NodeTxLimits::default();
+    builder.capacity = 60 * ONE_CKB;
+    assert!(matches!(builder.plan(), Err(DevnetError::InvalidPlan(_))));
+}
+
+#[test]
+fn test_spend_always_success_cell() {
+    let sender = build_always_success_script();
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let ctx = init_context(
+        vec![(ALWAYS_SUCCESS_BIN, false)],
+        vec![(sender.clone(), Some(200 * ONE_CKB))],
+    );
+
+    let output = CellOutput::new_builder()
+        .capacity((120 * ONE_CKB).pack())
+        .lock(receiver.clone())
+        .build();
+    let builder = CapacityTransferBuilder::new(vec![(output, Bytes::default())]);
+    let balancer = CapacityBalancer::new_simple(sender.clone(), WitnessArgs::default(), FEE_RATE);
+    let mut unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>> = HashMap::default();
+    unlockers.insert(
+        ScriptId::from(&sender),
+        Box::new(AlwaysSuccessUnlocker::default()),
+    );
+
+    let (tx, locked_groups) = builder
+        .build_unlocked(
+            &mut ctx.to_live_cells_context(),
+            &ctx,
+            &ctx,
+            &ctx,
+            &balancer,
+            &unlockers,
+        )
+        .unwrap();
+    assert!(locked_groups.is_empty());
+    assert_eq!(tx.inputs().len(), 1);
+    assert_eq!(tx.outputs().get(0).unwrap().lock(), receiver);
+    assert_eq!(tx.outputs().get(1).unwrap().lock(), sender);
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
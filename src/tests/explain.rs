@@ -0,0 +1,127 @@
+use ckb_types::{
+    bytes::Bytes,
+    core::TransactionBuilder,
+    packed::{CellInput, CellOutput, WitnessArgs},
+    prelude::*,
+    H256,
+};
+
+use crate::{
+    constants::{ONE_CKB, SIGHASH_TYPE_HASH},
+    test_util::random_out_point,
+    tests::{build_dao_script, build_sighash_script, init_context, ACCOUNT1_ARG, ACCOUNT2_ARG},
+    types::{Address, AddressPayload, NetworkType, ScriptGroupType, ScriptRegistry},
+    util::{explain_tx, tx_size, TxExplanation},
+    ScriptId,
+};
+
+#[test]
+fn test_explain_tx() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let mut ctx = init_context(Vec::new(), Vec::new());
+    let resolved = random_out_point();
+    ctx.add_simple_live_cell(resolved.clone(), sender.clone(), Some(300 * ONE_CKB));
+    let unresolved = random_out_point();
+    let dao_output = CellOutput::new_builder()
+        .capacity((102 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT2_ARG))
+        .type_(Some(build_dao_script()).pack())
+        .build();
+    let tx = TransactionBuilder::default()
+        .input(CellInput::new(resolved.clone(), 0))
+        .input(CellInput::new(unresolved.clone(), 0x2000_0000_0000_0010))
+        .output(dao_output)
+        .output_data(Bytes::from(vec![0u8; 8]).pack())
+        .witness(WitnessArgs::default().as_bytes().pack())
+        .build();
+
+    let explanation = TxExplanation::new(&tx, &ctx, NetworkType::Testnet);
+    assert_eq!(explanation.hash, tx.hash().unpack());
+    assert_eq!(explanation.size, tx_size(&tx));
+    // the fee can not be calculated without the unresolved input
+    assert!(explanation.fee.is_err());
+
+    let sender_address = Address::new(
+        NetworkType::Testnet,
+        AddressPayload::from(sender.clone()),
+        true,
+    );
+    let input = explanation.inputs[0].cell.as_ref().unwrap();
+    assert_eq!(input.capacity, 300 * ONE_CKB);
+    assert_eq!(input.lock, sender_address.to_string());
+    assert_eq!(input.type_hash, None);
+    assert_eq!(input.data_len, 0);
+    assert_eq!(explanation.inputs[1].out_point, unresolved);
+    assert_eq!(explanation.inputs[1].since, 0x2000_0000_0000_0010);
+    assert!(explanation.inputs[1].cell.is_err());
+
+    let output = &explanation.outputs[0];
+    assert_eq!(output.capacity, 102 * ONE_CKB);
+    assert_eq!(output.type_name.as_deref(), Some("dao"));
+    assert_eq!(
+        output.type_hash,
+        Some(build_dao_script().calc_script_hash().unpack())
+    );
+    assert_eq!(output.data_len, 8);
+
+    // the sender lock group, and the dao type group only in outputs
+    assert_eq!(explanation.groups.len(), 2);
+    let lock_group = &explanation.groups[0];
+    let lock_hash: H256 = sender.calc_script_hash().unpack();
+    assert_eq!(lock_group.script_hash, lock_hash);
+    assert_eq!(lock_group.group_type, ScriptGroupType::Lock);
+    assert_eq!(lock_group.witness_index, 0);
+    assert_eq!(
+        lock_group.witness_len,
+        Some(WitnessArgs::default().as_slice().len())
+    );
+    assert_eq!(explanation.groups[1].group_type, ScriptGroupType::Type);
+    assert_eq!(explanation.groups[1].witness_index, 0);
+
+    let dump = explain_tx(&tx, &ctx, NetworkType::Testnet);
+    assert_eq!(dump, explanation.to_string());
+    assert!(dump.contains(&sender_address.to_string()));
+    assert!(dump.contains("unresolved"));
+    assert!(dump.contains("type: dao"));
+}
+
+#[test]
+fn test_explain_tx_with_registry() {
+    let mut ctx = init_context(Vec::new(), Vec::new());
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let out_point = random_out_point();
+    ctx.add_simple_live_cell(out_point.clone(), sender, Some(200 * ONE_CKB));
+    let my_type_script = build_sighash_script(ACCOUNT2_ARG)
+        .as_builder()
+        .code_hash(H256::default().pack())
+        .build();
+    let output = CellOutput::new_builder()
+        .capacity((199 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT2_ARG))
+        .type_(Some(my_type_script.clone()).pack())
+        .build();
+    let tx = TransactionBuilder::default()
+        .input(CellInput::new(out_point, 0))
+        .output(output)
+        .output_data(Bytes::new().pack())
+        .build();
+
+    let explanation = TxExplanation::new(&tx, &ctx, NetworkType::Dev);
+    assert_eq!(explanation.outputs[0].type_name, None);
+    assert_eq!(explanation.fee, Ok(ONE_CKB));
+    // the lock group witness is missing
+    assert_eq!(explanation.groups[0].witness_len, None);
+
+    let mut registry = ScriptRegistry::default();
+    registry.register(
+        "my_type",
+        ScriptId::from(&my_type_script),
+        ctx.cell_dep_map
+            .get(&ScriptId::new_type(SIGHASH_TYPE_HASH.clone()))
+            .unwrap()
+            .clone(),
+    );
+    let explanation = TxExplanation::new_with_registry(&tx, &ctx, NetworkType::Dev, &registry);
+    assert_eq!(explanation.outputs[0].type_name.as_deref(), Some("my_type"));
+    assert!(explanation.to_string().contains("type: my_type"));
+}
@@ -0,0 +1,124 @@
+use ckb_types::{bytes::Bytes, core::TransactionView, packed::CellOutput, prelude::*};
+
+use crate::{
+    constants::ONE_CKB,
+    test_util::Context,
+    tests::{
+        build_sighash_balancer, build_sighash_script, build_sighash_unlockers, init_context,
+        ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG,
+    },
+    tx_builder::{transfer::CapacityTransferBuilder, TxBuilder, TxFootprint},
+    ScriptGroupType,
+};
+
+fn build_transfer_tx(ctx: &Context, outputs: usize) -> TransactionView {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let output = CellOutput::new_builder()
+        .capacity((120 * ONE_CKB).pack())
+        .lock(receiver)
+        .build();
+    let builder = CapacityTransferBuilder::new(vec![(output, Bytes::default()); outputs]);
+    let balancer = build_sighash_balancer(&sender);
+
+    let unlockers = build_sighash_unlockers(&[ACCOUNT1_KEY]);
+
+    let mut cell_collector = ctx.to_live_cells_context();
+    let (tx, locked_groups) = builder
+        .build_unlocked(&mut cell_collector, ctx, ctx, ctx, &balancer, &unlockers)
+        .unwrap();
+    assert!(locked_groups.is_empty());
+    tx
+}
+
+fn sections_sum(footprint: &TxFootprint) -> usize {
+    footprint.overhead
+        + footprint.cell_deps
+        + footprint.header_deps
+        + footprint.inputs
+        + footprint.outputs
+        + footprint.outputs_data
+        + footprint.witnesses
+}
+
+#[test]
+fn test_tx_footprint_sections() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let ctx = init_context(
+        Vec::new(),
+        vec![
+            (sender.clone(), Some(100 * ONE_CKB)),
+            (sender.clone(), Some(200 * ONE_CKB)),
+            (sender.clone(), Some(300 * ONE_CKB)),
+        ],
+    );
+    let tx = build_transfer_tx(&ctx, 1);
+
+    let mut footprint = TxFootprint::analyze(&tx, &ctx).unwrap();
+    assert_eq!(footprint.total, tx.data().as_slice().len());
+    assert_eq!(sections_sum(&footprint), footprint.total);
+    assert_eq!(footprint.inputs, 4 + 44 * tx.inputs().len());
+    assert_eq!(footprint.cell_deps, 4 + 37 * tx.cell_deps().len());
+    assert_eq!(footprint.groups.len(), 1);
+    let group = &footprint.groups[0];
+    assert_eq!(group.group_type, ScriptGroupType::Lock);
+    assert_eq!(group.script_hash, sender.calc_script_hash().unpack());
+    assert_eq!(
+        group.witness_bytes,
+        tx.witnesses()
+            .into_iter()
+            .map(|witness| witness.as_slice().len())
+            .sum::<usize>()
+    );
+    assert_eq!(group.cycles, None);
+
+    let cycles = footprint.measure_cycles(&tx, &ctx, &ctx, u64::MAX).unwrap();
+    assert!(cycles > 0);
+    assert_eq!(footprint.groups[0].cycles, Some(cycles));
+
+    let json = serde_json::to_string(&footprint).unwrap();
+    let decoded: TxFootprint = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded, footprint);
+    let table = footprint.to_string();
+    assert!(table.contains(&format!("{:#x}", group.script_hash)));
+    assert!(table.contains(&format!("total{:>19}", footprint.total)));
+}
+
+#[test]
+fn test_tx_footprint_diff() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let ctx = init_context(
+        Vec::new(),
+        vec![
+            (sender.clone(), Some(200 * ONE_CKB)),
+            (sender.clone(), Some(300 * ONE_CKB)),
+        ],
+    );
+    let before = TxFootprint::analyze(&build_transfer_tx(&ctx, 1), &ctx).unwrap();
+    let after_tx = build_transfer_tx(&ctx, 2);
+    let after = TxFootprint::analyze(&after_tx, &ctx).unwrap();
+    assert_eq!(sections_sum(&after), after_tx.data().as_slice().len());
+
+    let diff = before.diff(&after);
+    assert_eq!(diff.total, after.total as i64 - before.total as i64);
+    assert_eq!(diff.overhead, 0);
+    assert_eq!(diff.cell_deps, 0);
+    assert_eq!(diff.header_deps, 0);
+    // one more receiver output
+    assert!(diff.outputs > 0);
+    // an empty data item takes 4 bytes offset and 4 bytes length header
+    assert_eq!(diff.outputs_data, 8);
+    assert_eq!(diff.groups.len(), 1);
+    assert_eq!(diff.groups[0].cycles, None);
+    assert!(diff.to_string().contains(&format!("{:+}", diff.outputs)));
+    assert_eq!(
+        diff.total,
+        diff.overhead
+            + diff.cell_deps
+            + diff.header_deps
+            + diff.inputs
+            + diff.outputs
+            + diff.outputs_data
+            + diff.witnesses
+    );
+}
@@ -0,0 +1,477 @@
+use std::collections::HashMap;
+
+use ckb_hash::blake2b_256;
+use ckb_types::{
+    bytes::Bytes,
+    core::{DepType, ScriptHashType, TransactionBuilder, TransactionView},
+    packed::{CellDep, CellInput, CellOutput, OutPoint, Script, WitnessArgs},
+    prelude::*,
+    H256,
+};
+use rand::Rng;
+
+use crate::{
+    constants::ONE_CKB,
+    test_util::{random_out_point, Context},
+    tests::{
+        build_sighash_balancer, build_sighash_script, build_sighash_unlockers, build_sudt_script,
+        init_context, ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG, ACCOUNT2_KEY, FEE_RATE, SUDT_BIN,
+    },
+    tx_builder::{
+        fill_placeholder_witnesses,
+        hashlock::{HashlockBuilder, HashlockFunds, HashlockSpendBuilder},
+        unlock_tx, TxBuilder, TxBuilderError,
+    },
+    types::ScriptHashTypeExt,
+    unlock::{
+        hashlock::{HashlockArgs, HashlockWitness, HASHLOCK_ARGS_LEN},
+        HashlockUnlocker, ScriptUnlocker,
+    },
+    ScriptGroupType, ScriptId, Since,
+};
+
+const HASHLOCK_BIN: &[u8] = include_bytes!("../test-data/hashlock");
+
+/// Deploy the hashlock reference script (see `test-data/hashlock.md`) and
+/// register it by a type script hash, as a `DefaultCellDepResolver::insert`
+/// would do for an off-chain deployed script.
+fn deploy_hashlock(ctx: &mut Context) -> ScriptId {
+    let type_script = Script::new_builder()
+        .code_hash([0x11u8; 32].pack())
+        .hash_type(ScriptHashType::Type.to_packed())
+        .args(Bytes::from(b"hashlock".to_vec()).pack())
+        .build();
+    let code_hash = H256::from(blake2b_256(type_script.as_slice()));
+    let cell_dep = CellDep::new_builder()
+        .out_point(random_out_point())
+        .dep_type(DepType::Code.into())
+        .build();
+    let output = CellOutput::new_builder()
+        .type_(Some(type_script).pack())
+        .build();
+    ctx.add_cell_dep(
+        cell_dep.clone(),
+        output,
+        Bytes::from(HASHLOCK_BIN.to_vec()),
+        None,
+    );
+    let script_id = ScriptId::new_type(code_hash);
+    ctx.add_cell_dep_map(script_id.clone(), cell_dep);
+    script_id
+}
+
+fn build_unlockers(
+    script_id: &ScriptId,
+    hashlock_unlocker: HashlockUnlocker,
+) -> HashMap<ScriptId, Box<dyn ScriptUnlocker>> {
+    let mut unlockers = build_sighash_unlockers(&[ACCOUNT1_KEY, ACCOUNT2_KEY]);
+    unlockers.insert(script_id.clone(), Box::new(hashlock_unlocker));
+    unlockers
+}
+
+fn build_and_verify(
+    ctx: &mut Context,
+    builder: &dyn TxBuilder,
+    fee_payer: &Script,
+    unlockers: &HashMap<ScriptId, Box<dyn ScriptUnlocker>>,
+) -> TransactionView {
+    let (tx, locked_groups) = builder
+        .build_unlocked(
+            &mut ctx.to_live_cells_context(),
+            &*ctx,
+            &*ctx,
+            &*ctx,
+            &build_sighash_balancer(fee_payer),
+            unlockers,
+        )
+        .unwrap();
+    assert!(locked_groups.is_empty());
+    ctx.verify(tx.clone(), FEE_RATE).unwrap();
+    for (idx, (output, data)) in tx.outputs_with_data_iter().enumerate() {
+        let input = CellInput::new(OutPoint::new(tx.hash(), idx as u32), 0);
+        ctx.add_live_cell(input, output, data, None);
+    }
+    tx
+}
+
+fn find_output(tx: &TransactionView, lock: &Script) -> OutPoint {
+    let idx = tx
+        .outputs()
+        .into_iter()
+        .position(|output| &output.lock() == lock)
+        .unwrap();
+    OutPoint::new(tx.hash(), idx as u32)
+}
+
+fn random_preimage() -> Bytes {
+    let mut preimage = vec![0u8; 32];
+    rand::thread_rng().fill(&mut preimage[..]);
+    Bytes::from(preimage)
+}
+
+#[test]
+fn test_hashlock_args() {
+    let alice = build_sighash_script(ACCOUNT1_ARG);
+    let bob = build_sighash_script(ACCOUNT2_ARG);
+    let preimage = random_preimage();
+    let args = HashlockArgs::new(blake2b_256(&preimage), &bob, &alice, 100);
+    let args_bytes = args.to_bytes();
+    assert_eq!(args_bytes.len(), HASHLOCK_ARGS_LEN);
+    assert_eq!(HashlockArgs::parse(&args_bytes).unwrap(), args);
+    assert!(HashlockArgs::parse(&args_bytes[1..]).is_err());
+    assert!(args.is_preimage(&preimage));
+    assert!(!args.is_preimage(&preimage[1..]));
+
+    for witness in [HashlockWitness::Redeem(preimage), HashlockWitness::Refund] {
+        let lock_field = witness.to_bytes();
+        assert_eq!(lock_field.len(), witness.lock_size());
+        assert_eq!(HashlockWitness::parse(&lock_field).unwrap(), witness);
+    }
+    assert!(HashlockWitness::parse(&[1, 0]).is_err());
+    assert!(HashlockWitness::parse(&[]).is_err());
+    assert_eq!(HashlockWitness::from_witness(&[]).unwrap(), None);
+}
+
+#[test]
+fn test_hashlock_swap() {
+    // alice swaps 500 CKB for 100 udt of bob, only alice knows the preimage
+    let alice = build_sighash_script(ACCOUNT1_ARG);
+    let bob = build_sighash_script(ACCOUNT2_ARG);
+    let udt_script = build_sudt_script(&build_sighash_script(ACCOUNT2_ARG));
+    let mut ctx = init_context(
+        vec![(SUDT_BIN, false)],
+        vec![
+            (alice.clone(), Some(1000 * ONE_CKB)),
+            (bob.clone(), Some(1000 * ONE_CKB)),
+        ],
+    );
+    ctx.add_live_cell(
+        CellInput::new(random_out_point(), 0),
+        CellOutput::new_builder()
+            .capacity((200 * ONE_CKB).pack())
+            .lock(bob.clone())
+            .type_(Some(udt_script.clone()).pack())
+            .build(),
+        Bytes::from(300u128.to_le_bytes().to_vec()),
+        None,
+    );
+    let hashlock_id = deploy_hashlock(&mut ctx);
+    let preimage = random_preimage();
+    let hash = blake2b_256(&preimage);
+    // the party who knows the preimage waits longer for the refund
+    let alice_timeout = Since::absolute_block(200);
+    let bob_timeout = Since::absolute_block(100);
+    let unlockers = build_unlockers(&hashlock_id, HashlockUnlocker::default());
+
+    // leg 1: alice locks the capacity for bob
+    let builder = HashlockBuilder::new(hashlock_id.clone(), alice.clone()).lock(
+        HashlockFunds::Capacity(500 * ONE_CKB),
+        hash,
+        alice_timeout,
+        &bob,
+    );
+    let alice_lock_tx = build_and_verify(&mut ctx, &builder, &alice, &unlockers);
+    let alice_hashlock_cell = find_output(&alice_lock_tx, &builder.lock_script);
+
+    // leg 2: bob locks the udt for alice under the same hash
+    let builder = HashlockBuilder::new(hashlock_id.clone(), bob.clone()).lock(
+        HashlockFunds::Udt {
+            type_script: udt_script.clone(),
+            amount: 100,
+            capacity: 200 * ONE_CKB,
+        },
+        hash,
+        bob_timeout,
+        &alice,
+    );
+    let bob_lock_tx = build_and_verify(&mut ctx, &builder, &bob, &unlockers);
+    let bob_hashlock_cell = find_output(&bob_lock_tx, &builder.lock_script);
+    assert_eq!(
+        bob_lock_tx
+            .output_with_data(bob_hashlock_cell.index().unpack())
+            .unwrap()
+            .1
+            .as_ref(),
+        &100u128.to_le_bytes()[..]
+    );
+
+    // alice redeems the udt, the preimage is revealed
+    let builder = HashlockSpendBuilder::redeem(bob_hashlock_cell, preimage.clone(), alice.clone());
+    let alice_redeem_tx = build_and_verify(&mut ctx, &builder, &alice, &unlockers);
+    let output = alice_redeem_tx.output(0).unwrap();
+    assert_eq!(output.lock(), alice);
+    assert_eq!(output.type_().to_opt(), Some(udt_script));
+    let since: u64 = alice_redeem_tx.inputs().get(0).unwrap().since().unpack();
+    assert_eq!(since, 0);
+
+    // bob reads the preimage from the witness and redeems the capacity
+    let revealed = match HashlockWitness::from_witness(
+        &alice_redeem_tx.witnesses().get(0).unwrap().raw_data(),
+    )
+    .unwrap()
+    {
+        Some(HashlockWitness::Redeem(revealed)) => revealed,
+        other => panic!("unexpected witness: {:?}", other),
+    };
+    assert_eq!(revealed, preimage);
+    let builder = HashlockSpendBuilder::redeem(alice_hashlock_cell, revealed, bob.clone());
+    let bob_redeem_tx = build_and_verify(&mut ctx, &builder, &bob, &unlockers);
+    let output = bob_redeem_tx.output(0).unwrap();
+    assert_eq!(output.lock(), bob);
+    let capacity: u64 = output.capacity().unpack();
+    assert_eq!(capacity, 500 * ONE_CKB);
+}
+
+#[test]
+fn test_hashlock_refund() {
+    let alice = build_sighash_script(ACCOUNT1_ARG);
+    let bob = build_sighash_script(ACCOUNT2_ARG);
+    let mut ctx = init_context(
+        Vec::new(),
+        vec![
+            (alice.clone(), Some(1000 * ONE_CKB)),
+            (bob.clone(), Some(1000 * ONE_CKB)),
+        ],
+    );
+    let hashlock_id = deploy_hashlock(&mut ctx);
+    let preimage = random_preimage();
+    let timeout = Since::absolute_block(200);
+    let unlockers = build_unlockers(&hashlock_id, HashlockUnlocker::default());
+
+    let builder = HashlockBuilder::new(hashlock_id, alice.clone()).lock(
+        HashlockFunds::Capacity(500 * ONE_CKB),
+        blake2b_256(&preimage),
+        timeout,
+        &bob,
+    );
+    let lock_tx = build_and_verify(&mut ctx, &builder, &alice, &unlockers);
+    let hashlock_cell = find_output(&lock_tx, &builder.lock_script);
+
+    let build_base = |builder: &HashlockSpendBuilder| {
+        builder.build_base(&mut ctx.to_live_cells_context(), &ctx, &ctx, &ctx)
+    };
+    // only the recipient with the right preimage can redeem
+    let wrong_preimage =
+        HashlockSpendBuilder::redeem(hashlock_cell.clone(), random_preimage(), bob.clone());
+    assert!(matches!(
+        build_base(&wrong_preimage),
+        Err(TxBuilderError::InvalidParameter(_))
+    ));
+    let wrong_recipient =
+        HashlockSpendBuilder::redeem(hashlock_cell.clone(), preimage, alice.clone());
+    assert!(matches!(
+        build_base(&wrong_recipient),
+        Err(TxBuilderError::InvalidParameter(_))
+    ));
+    let wrong_refund = HashlockSpendBuilder::refund(hashlock_cell.clone(), bob);
+    assert!(matches!(
+        build_base(&wrong_refund),
+        Err(TxBuilderError::InvalidParameter(_))
+    ));
+
+    let builder = HashlockSpendBuilder::refund(hashlock_cell, alice.clone());
+    let refund_tx = build_and_verify(&mut ctx, &builder, &alice, &unlockers);
+    let since: u64 = refund_tx.inputs().get(0).unwrap().since().unpack();
+    assert_eq!(since, timeout.value());
+    assert_eq!(refund_tx.output(0).unwrap().lock(), alice);
+    assert_eq!(
+        HashlockWitness::from_witness(&refund_tx.witnesses().get(0).unwrap().raw_data()).unwrap(),
+        Some(HashlockWitness::Refund)
+    );
+}
+
+#[test]
+fn test_hashlock_unlocker() {
+    let alice = build_sighash_script(ACCOUNT1_ARG);
+    let bob = build_sighash_script(ACCOUNT2_ARG);
+    let mut ctx = init_context(Vec::new(), Vec::new());
+    let hashlock_id = deploy_hashlock(&mut ctx);
+    let preimage = random_preimage();
+    let timeout = Since::absolute_block(200);
+    let args = HashlockArgs::new(blake2b_256(&preimage), &bob, &alice, timeout);
+    let hashlock_script = args.build_script(&hashlock_id);
+    assert_eq!(
+        HashlockArgs::parse(&hashlock_script.args().raw_data()).unwrap(),
+        args
+    );
+    let bob_input = CellInput::new(random_out_point(), 0);
+    ctx.add_live_cell(
+        bob_input.clone(),
+        CellOutput::new_builder()
+            .capacity((100 * ONE_CKB).pack())
+            .lock(bob.clone())
+            .build(),
+        Bytes::new(),
+        None,
+    );
+    let hashlock_input = CellInput::new(random_out_point(), 0);
+    ctx.add_live_cell(
+        hashlock_input.clone(),
+        CellOutput::new_builder()
+            .capacity((500 * ONE_CKB).pack())
+            .lock(hashlock_script.clone())
+            .build(),
+        Bytes::new(),
+        None,
+    );
+    let build_tx = |since: u64| {
+        TransactionBuilder::default()
+            .cell_dep(ctx.cell_dep_map.get(&hashlock_id).unwrap().clone())
+            .cell_dep(ctx.cell_dep_map.get(&ScriptId::from(&bob)).unwrap().clone())
+            .input(
+                hashlock_input
+                    .clone()
+                    .as_builder()
+                    .since(since.pack())
+                    .build(),
+            )
+            .input(bob_input.clone())
+            .output(
+                CellOutput::new_builder()
+                    .capacity((599 * ONE_CKB).pack())
+                    .lock(bob.clone())
+                    .build(),
+            )
+            .output_data(Bytes::new().pack())
+            .build()
+    };
+
+    // redeem by the unlocker which knows the preimage, the placeholder has the
+    // size of the final witness
+    let unlockers = build_unlockers(&hashlock_id, HashlockUnlocker::new(vec![preimage.clone()]));
+    let (tx, _) = fill_placeholder_witnesses(build_tx(0), &ctx, &unlockers).unwrap();
+    let placeholder_len = tx.witnesses().get(0).unwrap().raw_data().len();
+    let (tx, locked_groups) = unlock_tx(tx, &ctx, &unlockers).unwrap();
+    assert!(locked_groups.is_empty());
+    let witness = tx.witnesses().get(0).unwrap().raw_data();
+    assert_eq!(witness.len(), placeholder_len);
+    assert_eq!(
+        HashlockWitness::from_witness(&witness).unwrap(),
+        Some(HashlockWitness::Redeem(preimage))
+    );
+    ctx.verify_scripts(tx).unwrap();
+
+    // without the preimage the cell can only be refunded after the timeout
+    let unlockers = build_unlockers(&hashlock_id, HashlockUnlocker::default());
+    assert!(unlock_tx(build_tx(0), &ctx, &unlockers).is_err());
+    let (tx, _) = fill_placeholder_witnesses(build_tx(timeout.value()), &ctx, &unlockers).unwrap();
+    let (tx, locked_groups) = unlock_tx(tx, &ctx, &unlockers).unwrap();
+    assert!(locked_groups.is_empty());
+    assert_eq!(
+        HashlockWitness::from_witness(&tx.witnesses().get(0).unwrap().raw_data()).unwrap(),
+        Some(HashlockWitness::Refund)
+    );
+}
+
+/// The builders and the unlocker refuse a wrong preimage or an early refund, so
+/// the hashlock witness is written directly and only the owner input is signed.
+#[test]
+fn test_hashlock_script_rejects() {
+    let alice = build_sighash_script(ACCOUNT1_ARG);
+    let bob = build_sighash_script(ACCOUNT2_ARG);
+    let mut ctx = init_context(Vec::new(), Vec::new());
+    let hashlock_id = deploy_hashlock(&mut ctx);
+    let preimage = random_preimage();
+    let timeout = Since::absolute_block(200);
+    let args = HashlockArgs::new(blake2b_256(&preimage), &bob, &alice, timeout);
+    let hashlock_script = args.build_script(&hashlock_id);
+    let hashlock_input = CellInput::new(random_out_point(), 0);
+    ctx.add_live_cell(
+        hashlock_input.clone(),
+        CellOutput::new_builder()
+            .capacity((500 * ONE_CKB).pack())
+            .lock(hashlock_script.clone())
+            .build(),
+        Bytes::new(),
+        None,
+    );
+    let mut owner_inputs = Vec::new();
+    for owner in [&alice, &bob] {
+        let input = CellInput::new(random_out_point(), 0);
+        ctx.add_live_cell(
+            input.clone(),
+            CellOutput::new_builder()
+                .capacity((100 * ONE_CKB).pack())
+                .lock(owner.clone())
+                .build(),
+            Bytes::new(),
+            None,
+        );
+        owner_inputs.push(input);
+    }
+    let (alice_input, bob_input) = (&owner_inputs[0], &owner_inputs[1]);
+
+    let unlockers = build_sighash_unlockers(&[ACCOUNT1_KEY, ACCOUNT2_KEY]);
+    let verify = |since: u64, owner_input: &CellInput, witness: HashlockWitness| {
+        let witness_args = WitnessArgs::new_builder()
+            .lock(Some(witness.to_bytes()).pack())
+            .build();
+        let tx = TransactionBuilder::default()
+            .cell_dep(ctx.cell_dep_map.get(&hashlock_id).unwrap().clone())
+            .cell_dep(
+                ctx.cell_dep_map
+                    .get(&ScriptId::from(&alice))
+                    .unwrap()
+                    .clone(),
+            )
+            .input(
+                hashlock_input
+                    .clone()
+                    .as_builder()
+                    .since(since.pack())
+                    .build(),
+            )
+            .input(owner_input.clone())
+            .output(
+                CellOutput::new_builder()
+                    .capacity((599 * ONE_CKB).pack())
+                    .lock(alice.clone())
+                    .build(),
+            )
+            .output_data(Bytes::new().pack())
+            .witness(witness_args.as_bytes().pack())
+            .build();
+        let (tx, _) = fill_placeholder_witnesses(tx, &ctx, &unlockers).unwrap();
+        let (tx, locked_groups) = unlock_tx(tx, &ctx, &unlockers).unwrap();
+        // only the hashlock group is left, its witness is already set
+        assert_eq!(locked_groups.len(), 1);
+        ctx.verify_script_groups(tx).map(|_| ()).map_err(|err| {
+            assert_eq!(
+                err.group,
+                Some((ScriptGroupType::Lock, hashlock_script.calc_script_hash()))
+            );
+            err.exit_code
+        })
+    };
+
+    verify(0, bob_input, HashlockWitness::Redeem(preimage.clone())).unwrap();
+    verify(timeout.value(), alice_input, HashlockWitness::Refund).unwrap();
+
+    // ERROR_PREIMAGE
+    assert_eq!(
+        verify(0, bob_input, HashlockWitness::Redeem(random_preimage())),
+        Err(Some(6))
+    );
+    // ERROR_SINCE, the refund before the timeout
+    assert_eq!(
+        verify(0, alice_input, HashlockWitness::Refund),
+        Err(Some(7))
+    );
+    assert_eq!(
+        verify(
+            Since::absolute_block(199).value(),
+            alice_input,
+            HashlockWitness::Refund
+        ),
+        Err(Some(7))
+    );
+    // ERROR_OWNER_INPUT, the recipient redeems and the refund lock refunds
+    assert_eq!(
+        verify(0, alice_input, HashlockWitness::Redeem(preimage)),
+        Err(Some(8))
+    );
+    assert_eq!(
+        verify(timeout.value(), bob_input, HashlockWitness::Refund),
+        Err(Some(8))
+    );
+}
@@ -0,0 +1,187 @@
+use ckb_types::{
+    bytes::Bytes,
+    core::{EpochNumberWithFraction, HeaderBuilder, HeaderView},
+    packed::{CellInput, CellOutput, OutPoint},
+    prelude::*,
+};
+
+use crate::{
+    constants::ONE_CKB,
+    test_util::{random_out_point, Context},
+    tests::{
+        build_sighash_balancer, build_sighash_script, build_sighash_unlockers, init_context,
+        ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG, FEE_RATE,
+    },
+    traits::{mock::MockCellCollector, LiveCell, PENDING_BLOCK_NUMBER},
+    tx_builder::{
+        resolve_input_header_deps, transfer::CapacityTransferBuilder, CapacityBalancer, TxBuilder,
+        TxBuilderError,
+    },
+};
+
+fn build_balancer(input_header_deps: bool) -> CapacityBalancer {
+    let mut balancer = build_sighash_balancer(&build_sighash_script(ACCOUNT1_ARG));
+    balancer.set_input_header_deps(input_header_deps);
+    balancer
+}
+
+/// Add a sender cell committed in the block of `header`, or an unconfirmed
+/// cell (the header is unknown) when `header` is `None`.
+fn add_sender_cell(ctx: &mut Context, capacity: u64, header: Option<&HeaderView>) -> OutPoint {
+    let out_point = random_out_point();
+    let output = CellOutput::new_builder()
+        .capacity((capacity * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT1_ARG))
+        .build();
+    ctx.add_live_cell(
+        CellInput::new(out_point.clone(), 0),
+        output,
+        Bytes::default(),
+        header.map(|header| header.hash()),
+    );
+    out_point
+}
+
+/// The cells committed in the headers are not cellbase cells after the tip
+/// is set.
+fn init_ctx(headers: &[&HeaderView]) -> Context {
+    let mut ctx = init_context(Vec::new(), Vec::new());
+    for header in headers {
+        ctx.add_header((*header).clone());
+    }
+    ctx.set_tip(1000, EpochNumberWithFraction::new(10, 0, 1000), 0);
+    ctx
+}
+
+fn build_transfer(capacity: u64) -> CapacityTransferBuilder {
+    let output = CellOutput::new_builder()
+        .capacity((capacity * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT2_ARG))
+        .build();
+    CapacityTransferBuilder::new(vec![(output, Bytes::default())])
+}
+
+#[test]
+fn test_input_header_deps() {
+    let header_a = HeaderBuilder::default().number(5u64.pack()).build();
+    let header_b = HeaderBuilder::default().number(6u64.pack()).build();
+    let mut ctx = init_ctx(&[&header_a, &header_b]);
+    add_sender_cell(&mut ctx, 100, Some(&header_a));
+    add_sender_cell(&mut ctx, 100, Some(&header_b));
+    add_sender_cell(&mut ctx, 100, Some(&header_a));
+
+    let mut cell_collector = ctx.to_live_cells_context();
+    let (tx, locked_groups) = build_transfer(250)
+        .build_unlocked(
+            &mut cell_collector,
+            &ctx,
+            &ctx,
+            &ctx,
+            &build_balancer(true),
+            &build_sighash_unlockers(&[ACCOUNT1_KEY]),
+        )
+        .unwrap();
+    assert!(locked_groups.is_empty());
+    // all the inputs are added by the balancer
+    assert_eq!(tx.inputs().len(), 3);
+    let header_deps: Vec<_> = tx.header_deps().into_iter().collect();
+    assert_eq!(header_deps, vec![header_a.hash(), header_b.hash()]);
+    assert_eq!(
+        resolve_input_header_deps(&tx, &ctx, &ctx).unwrap(),
+        header_deps
+    );
+    ctx.verify(tx, FEE_RATE).unwrap();
+
+    // not added by default
+    let mut cell_collector = ctx.to_live_cells_context();
+    let tx = build_transfer(250)
+        .build_balanced(
+            &mut cell_collector,
+            &ctx,
+            &ctx,
+            &ctx,
+            &build_balancer(false),
+            &build_sighash_unlockers(&[ACCOUNT1_KEY]),
+        )
+        .unwrap();
+    assert!(tx.header_deps().is_empty());
+}
+
+#[test]
+fn test_input_header_deps_unresolved() {
+    let header = HeaderBuilder::default().number(5u64.pack()).build();
+    let mut ctx = init_ctx(&[&header]);
+    add_sender_cell(&mut ctx, 100, Some(&header));
+    let unconfirmed = add_sender_cell(&mut ctx, 200, None);
+
+    let mut cell_collector = ctx.to_live_cells_context();
+    let err = build_transfer(250)
+        .build_balanced(
+            &mut cell_collector,
+            &ctx,
+            &ctx,
+            &ctx,
+            &build_balancer(true),
+            &build_sighash_unlockers(&[ACCOUNT1_KEY]),
+        )
+        .unwrap_err();
+    match err {
+        TxBuilderError::InputHeaderNotResolved(out_point) => {
+            assert_eq!(out_point, unconfirmed);
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn test_input_header_deps_skip_unconfirmed() {
+    let header = HeaderBuilder::default().number(5u64.pack()).build();
+    let mut ctx = init_ctx(&[&header]);
+    let confirmed = add_sender_cell(&mut ctx, 300, Some(&header));
+    let pending_output = CellOutput::new_builder()
+        .capacity((500 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT1_ARG))
+        .build();
+    let pending = LiveCell::new(
+        pending_output,
+        Bytes::default(),
+        random_out_point(),
+        PENDING_BLOCK_NUMBER,
+        0,
+    );
+    let confirmed_cell = LiveCell::new(
+        ctx.get_live_cell(&confirmed).unwrap().0,
+        Bytes::default(),
+        confirmed.clone(),
+        5,
+        1,
+    );
+    // the unconfirmed cell comes first, but it has no header
+    let mut cell_collector = MockCellCollector::new(vec![pending, confirmed_cell]);
+
+    let balancer = build_balancer(true);
+    assert!(balancer.allow_unconfirmed);
+    let tx = build_transfer(250)
+        .build_balanced(
+            &mut cell_collector,
+            &ctx,
+            &ctx,
+            &ctx,
+            &balancer,
+            &build_sighash_unlockers(&[ACCOUNT1_KEY]),
+        )
+        .unwrap();
+    let inputs: Vec<_> = tx
+        .inputs()
+        .into_iter()
+        .map(|input| input.previous_output())
+        .collect();
+    assert_eq!(inputs, vec![confirmed]);
+    let header_deps: Vec<_> = tx.header_deps().into_iter().collect();
+    assert_eq!(header_deps, vec![header.hash()]);
+    assert!(cell_collector
+        .queries
+        .iter()
+        .all(|query| !query.allow_unconfirmed));
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
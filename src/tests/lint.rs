@@ -0,0 +1,256 @@
+use ckb_hash::blake2b_256;
+use ckb_types::{
+    bytes::Bytes,
+    core::{ScriptHashType, TransactionBuilder, TransactionView},
+    packed::{CellInput, CellOutput, Script},
+    prelude::*,
+    H256,
+};
+
+use crate::{
+    constants::{ONE_CKB, SIGHASH_TYPE_HASH},
+    test_util::{random_out_point, Context},
+    tests::{build_sighash_script, init_context, ACCOUNT1_ARG, ACCOUNT2_ARG, SUDT_BIN},
+    tx_builder::{
+        lint::{
+            lint_tx, AlwaysSuccessLockCapacity, BurnLockOutput, LintIssue, LintRule,
+            UdtOwnerModeSatisfied, XudtArgs, XUDT_OWNER_MODE_INPUT_LOCK_NOT,
+        },
+        udt::{OwnerMode, UdtIssueBuilder, UdtTargetReceiver, UdtType},
+        TransferAction, TxBuilder,
+    },
+    types::ScriptHashTypeExt,
+    util::is_burn_lock,
+    NetworkType, ScriptId,
+};
+
+const XUDT_CODE_HASH: [u8; 32] = [5u8; 32];
+
+fn build_rule() -> UdtOwnerModeSatisfied {
+    UdtOwnerModeSatisfied::new(
+        vec![ScriptId::new_data1(H256::from(blake2b_256(SUDT_BIN)))],
+        vec![ScriptId::new_type(H256::from(XUDT_CODE_HASH))],
+    )
+}
+
+fn add_cell(
+    ctx: &mut Context,
+    lock: &Script,
+    type_script: Option<&Script>,
+    data: Bytes,
+) -> CellInput {
+    let input = CellInput::new(random_out_point(), 0);
+    let output = CellOutput::new_builder()
+        .capacity((200 * ONE_CKB).pack())
+        .lock(lock.clone())
+        .type_(type_script.cloned().pack())
+        .build();
+    ctx.add_live_cell(input.clone(), output, data, None);
+    input
+}
+
+fn build_udt_tx(input: CellInput, type_script: &Script, amounts: &[u128]) -> TransactionView {
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let mut builder = TransactionBuilder::default().input(input);
+    for amount in amounts {
+        builder = builder
+            .output(
+                CellOutput::new_builder()
+                    .capacity((150 * ONE_CKB).pack())
+                    .lock(receiver.clone())
+                    .type_(Some(type_script.clone()).pack())
+                    .build(),
+            )
+            .output_data(Bytes::from(amount.to_le_bytes().to_vec()).pack());
+    }
+    builder.build()
+}
+
+fn lint(tx: &TransactionView, ctx: &Context) -> Vec<LintIssue> {
+    let rule = build_rule();
+    lint_tx(tx, ctx, &[&rule as &dyn LintRule]).unwrap()
+}
+
+#[test]
+fn test_lint_sudt_issue() {
+    let owner = build_sighash_script(ACCOUNT1_ARG);
+    let mut ctx = init_context(
+        vec![(SUDT_BIN, false)],
+        vec![(owner.clone(), Some(100 * ONE_CKB))],
+    );
+    let builder = UdtIssueBuilder {
+        udt_type: UdtType::Sudt,
+        script_id: ScriptId::new_data1(H256::from(blake2b_256(SUDT_BIN))),
+        owner: owner.clone(),
+        owner_mode: OwnerMode::ConsumeOwnerCell,
+        receivers: vec![
+            UdtTargetReceiver::new(
+                TransferAction::Create,
+                build_sighash_script(ACCOUNT2_ARG),
+                1,
+            ),
+            UdtTargetReceiver::new(
+                TransferAction::Create,
+                build_sighash_script(ACCOUNT2_ARG),
+                2,
+            ),
+        ],
+        data_validator: None,
+    };
+    let tx = builder
+        .build_base(&mut ctx.to_live_cells_context(), &ctx, &ctx, &ctx)
+        .unwrap();
+    assert!(lint(&tx, &ctx).is_empty());
+
+    // The owner cell is dropped
+    let other_input = add_cell(
+        &mut ctx,
+        &build_sighash_script(ACCOUNT2_ARG),
+        None,
+        Bytes::new(),
+    );
+    let broken_tx = tx
+        .as_advanced_builder()
+        .set_inputs(vec![other_input])
+        .build();
+    let issues = lint(&broken_tx, &ctx);
+    assert_eq!(issues.len(), 2);
+    assert_eq!(issues[0].rule, "UdtOwnerModeSatisfied");
+    assert_eq!(issues[0].output_index, Some(0));
+    assert_eq!(issues[1].output_index, Some(1));
+}
+
+#[test]
+fn test_lint_xudt_issue() {
+    let owner = build_sighash_script(ACCOUNT1_ARG);
+    let mut ctx = init_context(Vec::new(), Vec::new());
+    let build_xudt_script = |flags: u32| {
+        let mut args = owner.calc_script_hash().as_bytes().to_vec();
+        args.extend_from_slice(&flags.to_le_bytes());
+        Script::new_builder()
+            .code_hash(XUDT_CODE_HASH.pack())
+            .hash_type(ScriptHashType::Type.to_packed())
+            .args(Bytes::from(args).pack())
+            .build()
+    };
+    let xudt_script = build_xudt_script(0);
+    let xudt_args = XudtArgs::parse(&xudt_script.args().raw_data()).unwrap();
+    assert_eq!(xudt_args.owner_hash, owner.calc_script_hash());
+    assert_eq!(xudt_args.flags, 0);
+    assert!(xudt_args.extension_data.is_empty());
+    assert!(XudtArgs::parse(&[0u8; 34]).is_err());
+
+    let owner_input = add_cell(&mut ctx, &owner, None, Bytes::new());
+    let tx = build_udt_tx(owner_input.clone(), &xudt_script, &[10]);
+    assert!(lint(&tx, &ctx).is_empty());
+
+    let other_lock = build_sighash_script(ACCOUNT2_ARG);
+    let other_input = add_cell(&mut ctx, &other_lock, None, Bytes::new());
+    let tx = build_udt_tx(other_input, &xudt_script, &[1, 10]);
+    let issues = lint(&tx, &ctx);
+    assert_eq!(
+        issues
+            .iter()
+            .map(|issue| issue.output_index)
+            .collect::<Vec<_>>(),
+        vec![Some(0), Some(1)]
+    );
+
+    // A transfer does not need the owner
+    let udt_input = add_cell(
+        &mut ctx,
+        &other_lock,
+        Some(&xudt_script),
+        Bytes::from(11u128.to_le_bytes().to_vec()),
+    );
+    let tx = build_udt_tx(udt_input, &xudt_script, &[1, 10]);
+    assert!(lint(&tx, &ctx).is_empty());
+
+    // The owner lock is not allowed by the flags
+    let xudt_script = build_xudt_script(XUDT_OWNER_MODE_INPUT_LOCK_NOT);
+    let tx = build_udt_tx(owner_input, &xudt_script, &[10]);
+    assert_eq!(lint(&tx, &ctx).len(), 1);
+}
+
+fn build_transfer_tx(input: CellInput, locks: &[(&Script, u64)]) -> TransactionView {
+    let mut builder = TransactionBuilder::default().input(input);
+    for (lock, capacity) in locks {
+        builder = builder
+            .output(
+                CellOutput::new_builder()
+                    .capacity(capacity.pack())
+                    .lock((*lock).clone())
+                    .build(),
+            )
+            .output_data(Bytes::new().pack());
+    }
+    builder.build()
+}
+
+#[test]
+fn test_lint_burn_lock() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let burn_lock = Script::new_builder()
+        .code_hash(SIGHASH_TYPE_HASH.pack())
+        .hash_type(ScriptHashType::Type.to_packed())
+        .args(Bytes::from(vec![0u8; 20]).pack())
+        .build();
+    assert!(is_burn_lock(&burn_lock));
+    assert!(is_burn_lock(&Script::default()));
+    assert!(!is_burn_lock(&sender));
+    assert!(!is_burn_lock(
+        &burn_lock
+            .clone()
+            .as_builder()
+            .args(Bytes::from(vec![0u8; 21]).pack())
+            .build()
+    ));
+
+    let mut ctx = init_context(Vec::new(), Vec::new());
+    let input = add_cell(&mut ctx, &sender, None, Bytes::new());
+    let tx = build_transfer_tx(
+        input,
+        &[(&sender, 100 * ONE_CKB), (&burn_lock, 200 * ONE_CKB)],
+    );
+    let mut rule = BurnLockOutput::new();
+    let issues = lint_tx(&tx, &ctx, &[&rule as &dyn LintRule]).unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].output_index, Some(1));
+    rule.allow_burn();
+    assert!(lint_tx(&tx, &ctx, &[&rule as &dyn LintRule])
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_lint_always_success_capacity() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let always_success = Script::new_builder()
+        .code_hash([6u8; 32].pack())
+        .hash_type(ScriptHashType::Data.to_packed())
+        .build();
+    let mut ctx = init_context(Vec::new(), Vec::new());
+    let input = add_cell(&mut ctx, &sender, None, Bytes::new());
+    let tx = build_transfer_tx(
+        input,
+        &[
+            (&always_success, 100 * ONE_CKB),
+            (&always_success, 10 * ONE_CKB),
+        ],
+    );
+    let script_ids = vec![ScriptId::from(&always_success)];
+    let rule =
+        AlwaysSuccessLockCapacity::new(NetworkType::Mainnet, script_ids.clone(), 50 * ONE_CKB);
+    let issues = lint_tx(&tx, &ctx, &[&rule as &dyn LintRule]).unwrap();
+    assert_eq!(
+        issues
+            .iter()
+            .map(|issue| issue.output_index)
+            .collect::<Vec<_>>(),
+        vec![Some(0)]
+    );
+    let rule = AlwaysSuccessLockCapacity::new(NetworkType::Dev, script_ids, 50 * ONE_CKB);
+    assert!(lint_tx(&tx, &ctx, &[&rule as &dyn LintRule])
+        .unwrap()
+        .is_empty());
+}
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+
+use ckb_types::{
+    bytes::Bytes,
+    core::{HeaderBuilder, TransactionBuilder},
+    packed::{CellInput, CellOutput},
+    prelude::*,
+};
+
+use crate::{
+    constants::ONE_CKB,
+    test_util::random_out_point,
+    tests::{
+        build_sighash_balancer, build_sighash_script, init_context, ACCOUNT1_ARG, ACCOUNT2_ARG,
+    },
+    traits::{
+        mock::{MockCellCollector, MockHeaderDepResolver, MockTransactionDependencyProvider},
+        CellCollector, CellQueryOptions, HeaderDepResolver, LiveCell,
+        TransactionDependencyProvider,
+    },
+    tx_builder::{transfer::CapacityTransferBuilder, TxBuilder},
+    unlock::ScriptUnlocker,
+    ScriptId,
+};
+
+#[test]
+fn test_mock_cell_collector() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let cells: Vec<LiveCell> = [100, 200]
+        .iter()
+        .map(|capacity| {
+            let output = CellOutput::new_builder()
+                .capacity((capacity * ONE_CKB).pack())
+                .lock(sender.clone())
+                .build();
+            LiveCell::new(output, Bytes::default(), random_out_point(), 1, 1)
+        })
+        .collect();
+    let mut collector = MockCellCollector::new(cells.clone());
+
+    let mut query = CellQueryOptions::new_lock(sender.clone());
+    query.min_total_capacity = 150 * ONE_CKB;
+    let (collected, capacity) = collector.collect_live_cells(&query, true).unwrap();
+    assert_eq!(collected.len(), 2);
+    assert_eq!(capacity, 300 * ONE_CKB);
+    // the collected cells are locked
+    let (collected, _) = collector.collect_live_cells(&query, true).unwrap();
+    assert!(collected.is_empty());
+    let other_query = CellQueryOptions::new_lock(build_sighash_script(ACCOUNT2_ARG));
+    assert!(collector
+        .collect_live_cells(&other_query, false)
+        .unwrap()
+        .0
+        .is_empty());
+
+    collector.reset();
+    collector.unlock_cell(&cells[0].out_point).unwrap();
+    assert_eq!(collector.calls.count("collect_live_cells"), 3);
+    assert_eq!(collector.calls.count("reset"), 1);
+    assert_eq!(collector.calls.total(), 5);
+    assert_eq!(collector.queries.len(), 3);
+    assert_eq!(
+        collector.queries[2].primary_script,
+        other_query.primary_script
+    );
+    collector.calls.clear();
+    assert_eq!(collector.calls.total(), 0);
+}
+
+#[test]
+fn test_mock_tx_dep_provider_and_header_resolver() {
+    let output = CellOutput::new_builder()
+        .capacity((100 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT1_ARG))
+        .build();
+    let tx = TransactionBuilder::default()
+        .input(CellInput::new(random_out_point(), 0))
+        .output(output.clone())
+        .output_data(Bytes::from(vec![1u8]).pack())
+        .build();
+    let header = HeaderBuilder::default().number(5u64.pack()).build();
+    let mut provider = MockTransactionDependencyProvider::new();
+    provider.add_transaction(tx.clone());
+    provider.add_header(header.clone());
+
+    let out_point = tx.output_pts()[0].clone();
+    assert_eq!(provider.get_cell(&out_point).unwrap(), output);
+    assert_eq!(
+        provider.get_cell_data(&out_point).unwrap(),
+        Bytes::from(vec![1u8])
+    );
+    assert_eq!(provider.get_transaction(&tx.hash()).unwrap(), tx);
+    assert_eq!(provider.get_header(&header.hash()).unwrap(), header);
+    assert!(provider.get_cell(&random_out_point()).is_err());
+    assert_eq!(provider.calls.count("get_cell"), 2);
+    assert_eq!(provider.calls.count("get_block_extension"), 0);
+    assert_eq!(provider.calls.total(), 5);
+
+    let mut resolver = MockHeaderDepResolver::new(vec![header.clone()]);
+    assert_eq!(resolver.resolve_by_number(5).unwrap(), Some(header.clone()));
+    assert_eq!(resolver.resolve_by_number(6).unwrap(), None);
+    assert_eq!(resolver.resolve_by_tx(&tx.hash()).unwrap(), None);
+    resolver.commit_tx(tx.hash(), 5).unwrap();
+    assert!(resolver.commit_tx(tx.hash(), 6).is_err());
+    assert_eq!(resolver.resolve_by_tx(&tx.hash()).unwrap(), Some(header));
+    assert_eq!(resolver.calls.count("resolve_by_number"), 2);
+    assert_eq!(resolver.calls.count("resolve_by_tx"), 2);
+}
+
+#[test]
+fn test_build_balanced_with_mocks() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    // only resolve the cell deps by the context
+    let ctx = init_context(Vec::new(), Vec::new());
+    let mut provider = MockTransactionDependencyProvider::new();
+    let mut cells = Vec::new();
+    for capacity in [100, 200] {
+        let output = CellOutput::new_builder()
+            .capacity((capacity * ONE_CKB).pack())
+            .lock(sender.clone())
+            .build();
+        let out_point = random_out_point();
+        provider.add_cell(out_point.clone(), output.clone(), Bytes::default());
+        cells.push(LiveCell::new(output, Bytes::default(), out_point, 1, 1));
+    }
+    let mut collector = MockCellCollector::new(cells);
+    let header_dep_resolver = MockHeaderDepResolver::default();
+
+    let balancer = build_sighash_balancer(&sender);
+    let output = CellOutput::new_builder()
+        .capacity((150 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT2_ARG))
+        .build();
+    let builder = CapacityTransferBuilder::new(vec![(output, Bytes::default())]);
+    let unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>> = HashMap::new();
+    let tx = builder
+        .build_balanced(
+            &mut collector,
+            &ctx,
+            &header_dep_resolver,
+            &provider,
+            &balancer,
+            &unlockers,
+        )
+        .unwrap();
+    assert_eq!(tx.inputs().len(), 2);
+    assert!(collector.calls.count("collect_live_cells") >= 1);
+    assert!(provider.calls.count("get_cell") >= 2);
+    assert_eq!(header_dep_resolver.calls.total(), 0);
+}
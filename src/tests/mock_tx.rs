@@ -0,0 +1,217 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use ckb_chain_spec::consensus::ConsensusBuilder;
+use ckb_mock_tx_types::{MockResourceLoader, MockTransaction, Resource};
+use ckb_script::{TransactionScriptsVerifier, TxVerifyEnv};
+use ckb_types::{
+    bytes::Bytes,
+    core::{
+        cell::resolve_transaction,
+        hardfork::{HardForks, CKB2021, CKB2023},
+        HeaderBuilder, HeaderView, TransactionView,
+    },
+    packed::{Byte32, CellInput, CellOutput, OutPoint},
+    prelude::*,
+    H256,
+};
+
+use crate::{
+    constants::ONE_CKB,
+    mock_tx::{build_mock_transaction, from_mock_transaction, MockTxError, ReprMockTransaction},
+    test_util::{random_out_point, Context},
+    tests::{
+        build_sighash_balancer, build_sighash_script, build_sighash_unlockers, init_context,
+        ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG, FEE_RATE,
+    },
+    traits::{CellCollector, CellQueryOptions, TransactionDependencyProvider},
+    tx_builder::{transfer::CapacityTransferBuilder, TxBuilder},
+    util::tx_fee,
+    ScriptGroupType,
+};
+
+struct NoLoader;
+impl MockResourceLoader for NoLoader {
+    fn get_header(&mut self, hash: H256) -> Result<Option<HeaderView>, String> {
+        Err(format!("header not in the mock transaction: {:?}", hash))
+    }
+    fn get_live_cell(
+        &mut self,
+        out_point: OutPoint,
+    ) -> Result<Option<(CellOutput, Bytes, Option<Byte32>)>, String> {
+        Err(format!("cell not in the mock transaction: {:?}", out_point))
+    }
+}
+
+/// A transfer from account1 to account2, signed when `sign` is true
+fn build_transfer(sign: bool) -> (Context, TransactionView) {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let ctx = init_context(
+        Vec::new(),
+        vec![
+            (sender.clone(), Some(100 * ONE_CKB)),
+            (sender.clone(), Some(200 * ONE_CKB)),
+        ],
+    );
+    let output = CellOutput::new_builder()
+        .capacity((120 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT2_ARG))
+        .build();
+    let builder = CapacityTransferBuilder::new(vec![(output, Bytes::default())]);
+    let balancer = build_sighash_balancer(&sender);
+
+    let unlockers = if sign {
+        build_sighash_unlockers(&[ACCOUNT1_KEY])
+    } else {
+        HashMap::default()
+    };
+    let (tx, _) = builder
+        .build_unlocked(
+            &mut ctx.to_live_cells_context(),
+            &ctx,
+            &ctx,
+            &ctx,
+            &balancer,
+            &unlockers,
+        )
+        .unwrap();
+    (ctx, tx)
+}
+
+/// Run the scripts of the mock transaction like ckb-debugger does
+fn verify_mock_tx(mock_tx: ReprMockTransaction) -> Result<u64, String> {
+    let mock_tx = MockTransaction::from(mock_tx);
+    let resource = Resource::from_both(&mock_tx, &mut NoLoader)?;
+    let rtx = resolve_transaction(
+        mock_tx.tx.clone().into_view(),
+        &mut HashSet::new(),
+        &resource,
+        &resource,
+    )
+    .map_err(|err| format!("resolve transaction error: {:?}", err))?;
+    let consensus = ConsensusBuilder::default()
+        .hardfork_switch(HardForks {
+            ckb2021: CKB2021::new_dev_default(),
+            ckb2023: CKB2023::new_dev_default(),
+        })
+        .build();
+    let tip = HeaderBuilder::default().number(0.pack()).build();
+    let verifier = TransactionScriptsVerifier::new(
+        Arc::new(rtx),
+        resource,
+        Arc::new(consensus),
+        Arc::new(TxVerifyEnv::new_submit(&tip)),
+    );
+    verifier
+        .verify(u64::MAX)
+        .map_err(|err| format!("verify script error: {:?}", err))
+}
+
+#[test]
+fn test_mock_tx_roundtrip() {
+    let (ctx, tx) = build_transfer(true);
+    let mock_tx = build_mock_transaction(&tx, &ctx, &ctx).unwrap();
+
+    let json = serde_json::to_string_pretty(&mock_tx).unwrap();
+    let loaded: ReprMockTransaction = serde_json::from_str(&json).unwrap();
+    assert_eq!(serde_json::to_string_pretty(&loaded).unwrap(), json);
+
+    let mock_info = MockTransaction::from(loaded.clone()).mock_info;
+    assert_eq!(mock_info.inputs.len(), tx.inputs().len());
+    // the sighash dep group, the secp256k1 data and the sighash code
+    assert_eq!(tx.cell_deps().len(), 1);
+    assert_eq!(mock_info.cell_deps.len(), 3);
+
+    let (loaded_tx, provider, mut collector) = from_mock_transaction(loaded);
+    assert_eq!(loaded_tx.hash(), tx.hash());
+    for cell_dep in &mock_info.cell_deps {
+        let out_point = cell_dep.cell_dep.out_point();
+        assert_eq!(
+            provider.get_cell(&out_point).unwrap(),
+            ctx.get_cell(&out_point).unwrap()
+        );
+        assert_eq!(
+            provider.get_cell_data(&out_point).unwrap(),
+            ctx.get_cell_data(&out_point).unwrap()
+        );
+        // the genesis header
+        let block_hash = cell_dep.header.clone().unwrap();
+        assert_eq!(provider.get_header(&block_hash).unwrap().number(), 0);
+    }
+    assert_eq!(
+        tx_fee(&loaded_tx, &provider).unwrap(),
+        tx_fee(&tx, &ctx).unwrap()
+    );
+
+    let query = CellQueryOptions::new_lock(build_sighash_script(ACCOUNT1_ARG));
+    let (cells, capacity) = collector.collect_live_cells(&query, true).unwrap();
+    assert_eq!(
+        cells
+            .iter()
+            .map(|cell| cell.out_point.clone())
+            .collect::<Vec<_>>(),
+        tx.input_pts_iter().collect::<Vec<_>>()
+    );
+    assert_eq!(capacity, 300 * ONE_CKB);
+    assert!(collector
+        .collect_live_cells(&query, true)
+        .unwrap()
+        .0
+        .is_empty());
+    collector.reset();
+    assert_eq!(
+        collector.collect_live_cells(&query, false).unwrap().1,
+        capacity
+    );
+}
+
+#[test]
+fn test_mock_tx_verify() {
+    let (ctx, tx) = build_transfer(true);
+    let mock_tx = build_mock_transaction(&tx, &ctx, &ctx).unwrap();
+    assert_eq!(
+        verify_mock_tx(mock_tx).unwrap(),
+        ctx.verify_scripts(tx).unwrap()
+    );
+
+    // the script failure is reproduced by the mock transaction
+    let (ctx, tx) = build_transfer(false);
+    let mock_tx = build_mock_transaction(&tx, &ctx, &ctx).unwrap();
+    assert!(verify_mock_tx(mock_tx).is_err());
+    assert!(ctx.verify_scripts(tx).is_err());
+}
+
+#[test]
+fn test_context_verify_groups() {
+    let (ctx, tx) = build_transfer(true);
+    let sender_hash = tx.output(1).unwrap().lock().calc_script_hash();
+    let result = ctx.verify(tx.clone(), FEE_RATE).unwrap();
+    assert_eq!(result.total_cycles, ctx.verify_scripts(tx).unwrap());
+    assert_eq!(
+        result.group_cycles,
+        vec![(
+            ScriptGroupType::Lock,
+            sender_hash.clone(),
+            result.total_cycles
+        )]
+    );
+
+    // the zero signature can not be recovered by the sighash lock
+    let (ctx, tx) = build_transfer(false);
+    let err = ctx.verify(tx, FEE_RATE).unwrap_err();
+    assert_eq!(err.group, Some((ScriptGroupType::Lock, sender_hash)));
+    assert!(err.exit_code.is_some());
+}
+
+#[test]
+fn test_mock_tx_unresolved_input() {
+    let (ctx, tx) = build_transfer(true);
+    let tx = tx
+        .as_advanced_builder()
+        .input(CellInput::new(random_out_point(), 0))
+        .build();
+    assert!(matches!(
+        build_mock_transaction(&tx, &ctx, &ctx),
+        Err(MockTxError::TxDep(_))
+    ));
+}
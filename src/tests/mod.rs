@@ -1,13 +1,23 @@
-use std::{collections::HashMap, u64};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    u64,
+};
 
 use ckb_dao_utils::pack_dao_data;
 use ckb_hash::blake2b_256;
 use ckb_jsonrpc_types as json_types;
 use ckb_types::{
     bytes::Bytes,
-    core::{BlockView, Capacity, EpochNumberWithFraction, HeaderBuilder, ScriptHashType},
+    core::{
+        BlockView, Capacity, EpochNumberWithFraction, FeeRate, HeaderBuilder, HeaderView,
+        ScriptHashType, TransactionView,
+    },
     h160, h256,
-    packed::{CellInput, CellOutput, Script, ScriptOpt, WitnessArgs},
+    packed::{CellInput, CellOutput, OutPoint, Script, ScriptOpt, WitnessArgs},
     prelude::*,
     H160, H256,
 };
@@ -15,26 +25,39 @@ use ckb_types::{
 use crate::constants::{
     CHEQUE_CELL_SINCE, DAO_TYPE_HASH, MULTISIG_TYPE_HASH, ONE_CKB, SIGHASH_TYPE_HASH,
 };
-use crate::traits::SecpCkbRawKeySigner;
+use crate::traits::{
+    CellCollector, CellDepResolver, FeeRateProvider, HeaderDepResolver, SecpCkbRawKeySigner,
+    TransactionDependencyProvider,
+};
 use crate::tx_builder::{
-    acp::{AcpTransferBuilder, AcpTransferReceiver},
-    cheque::{ChequeClaimBuilder, ChequeWithdrawBuilder},
+    acp::{AcpCreateBuilder, AcpCreateReceiver, AcpTransferBuilder, AcpTransferReceiver},
+    balance_tx_capacity,
+    cheque::{
+        ChequeBulkWithdrawBuilder, ChequeClaimBuilder, ChequeWithdrawBuilder, ChequeWithdrawSummary,
+    },
     dao::{
         DaoDepositBuilder, DaoDepositReceiver, DaoPrepareBuilder, DaoWithdrawBuilder,
         DaoWithdrawItem, DaoWithdrawReceiver,
     },
-    transfer::CapacityTransferBuilder,
-    udt::{UdtIssueBuilder, UdtTargetReceiver, UdtTransferBuilder, UdtType},
-    unlock_tx, CapacityBalancer, TransferAction, TxBuilder,
+    fill_placeholder_witnesses, gen_script_groups,
+    transfer::{set_lock_args_since, CapacitySweepBuilder, CapacityTransferBuilder},
+    tx_fee,
+    udt::{
+        validate_xudt_data, OwnerMode, UdtIssueBuilder, UdtTargetReceiver, UdtTransferBuilder,
+        UdtType,
+    },
+    unlock_tx, BalanceTxCapacityError, CapacityBalancer, TransferAction, TxBuilder, TxBuilderError,
 };
+use crate::types::ScriptHashTypeExt;
 use crate::unlock::{
-    AcpUnlocker, ChequeAction, ChequeUnlocker, MultisigConfig, ScriptUnlocker,
-    SecpMultisigUnlocker, SecpSighashUnlocker,
+    multisig_args_since, update_witness_field, AcpUnlocker, ChequeAction, ChequeUnlocker,
+    MultisigConfig, ScriptSignError, ScriptUnlocker, SecpMultisigUnlocker, SecpSighashUnlocker,
+    UnlockError, WitnessField,
 };
 use crate::util::{calculate_dao_maximum_withdraw4, minimal_unlock_point};
 use crate::{ScriptId, Since, SinceType};
 
-use crate::test_util::{random_out_point, Context};
+use crate::test_util::{random_out_point, Context, TestAccount};
 
 // ckt1qyq86vaa6e8tsruv5ngcd5tp7lcvcewxy7cquuksvj
 const ACCOUNT0_KEY: H256 =
@@ -66,15 +89,37 @@ const ALWAYS_SUCCESS_BIN: &[u8] = include_bytes!("../test-data/always_success");
 fn build_sighash_script(args: H160) -> Script {
     Script::new_builder()
         .code_hash(SIGHASH_TYPE_HASH.pack())
-        .hash_type(ScriptHashType::Type.into())
+        .hash_type(ScriptHashType::Type.to_packed())
         .args(Bytes::from(args.0.to_vec()).pack())
         .build()
 }
 
+// The sudt type script issued by `owner`
+fn build_sudt_script(owner: &Script) -> Script {
+    Script::new_builder()
+        .code_hash(H256::from(blake2b_256(SUDT_BIN)).pack())
+        .hash_type(ScriptHashType::Data1.to_packed())
+        .args(owner.calc_script_hash().as_bytes().pack())
+        .build()
+}
+
+// Add a live udt cell of 200 CKB holding `amount`
+fn add_udt_cell(ctx: &mut Context, lock: &Script, type_script: &Script, amount: u128) -> CellInput {
+    let input = CellInput::new(random_out_point(), 0);
+    let output = CellOutput::new_builder()
+        .capacity((200 * ONE_CKB).pack())
+        .lock(lock.clone())
+        .type_(Some(type_script.clone()).pack())
+        .build();
+    let data = Bytes::from(amount.to_le_bytes().to_vec());
+    ctx.add_live_cell(input.clone(), output, data, None);
+    input
+}
+
 fn build_multisig_script(cfg: &MultisigConfig) -> Script {
     Script::new_builder()
         .code_hash(MULTISIG_TYPE_HASH.pack())
-        .hash_type(ScriptHashType::Type.into())
+        .hash_type(ScriptHashType::Type.to_packed())
         .args(Bytes::from(cfg.hash160().0.to_vec()).pack())
         .build()
 }
@@ -82,7 +127,7 @@ fn build_multisig_script(cfg: &MultisigConfig) -> Script {
 fn build_dao_script() -> Script {
     Script::new_builder()
         .code_hash(DAO_TYPE_HASH.pack())
-        .hash_type(ScriptHashType::Type.into())
+        .hash_type(ScriptHashType::Type.to_packed())
         .build()
 }
 
@@ -94,7 +139,7 @@ fn build_cheque_script(sender: &Script, receiver: &Script, cheque_data_hash: H25
     script_args[20..40].copy_from_slice(&sender_script_hash.as_slice()[0..20]);
     Script::new_builder()
         .code_hash(cheque_data_hash.pack())
-        .hash_type(ScriptHashType::Data1.into())
+        .hash_type(ScriptHashType::Data1.to_packed())
         .args(Bytes::from(script_args).pack())
         .build()
 }
@@ -114,6 +159,69 @@ fn build_multisig_unlockers(
     unlockers
 }
 
+fn build_sighash_unlockers(keys: &[H256]) -> HashMap<ScriptId, Box<dyn ScriptUnlocker>> {
+    let keys = keys
+        .iter()
+        .map(|key| secp256k1::SecretKey::from_slice(key.as_bytes()).unwrap())
+        .collect();
+    let signer = SecpCkbRawKeySigner::new_with_secret_keys(keys);
+    let sighash_unlocker = SecpSighashUnlocker::from(Box::new(signer) as Box<_>);
+    let sighash_script_id = ScriptId::new_type(SIGHASH_TYPE_HASH.clone());
+    let mut unlockers = HashMap::default();
+    unlockers.insert(
+        sighash_script_id,
+        Box::new(sighash_unlocker) as Box<dyn ScriptUnlocker>,
+    );
+    unlockers
+}
+
+/// A balancer paying the fee of `FEE_RATE` by the cells of the sighash lock
+/// script `sender`.
+fn build_sighash_balancer(sender: &Script) -> CapacityBalancer {
+    let placeholder_witness = WitnessArgs::new_builder()
+        .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+        .build();
+    CapacityBalancer::new_simple(sender.clone(), placeholder_witness, FEE_RATE)
+}
+
+/// An unsigned transfer of 120 CKB from `sender` to account2
+fn build_unsigned(sender: Script, placeholder_witness: WitnessArgs) -> (Context, TransactionView) {
+    build_unsigned_with(Vec::new(), sender, placeholder_witness)
+}
+
+/// Same as `build_unsigned`, `contracts` are deployed in the context
+fn build_unsigned_with(
+    contracts: Vec<(&[u8], bool)>,
+    sender: Script,
+    placeholder_witness: WitnessArgs,
+) -> (Context, TransactionView) {
+    let ctx = init_context(
+        contracts,
+        vec![
+            (sender.clone(), Some(100 * ONE_CKB)),
+            (sender.clone(), Some(200 * ONE_CKB)),
+        ],
+    );
+    let output = CellOutput::new_builder()
+        .capacity((120 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT2_ARG))
+        .build();
+    let builder = CapacityTransferBuilder::new(vec![(output, Bytes::default())]);
+    let balancer = CapacityBalancer::new_simple(sender, placeholder_witness, FEE_RATE);
+    let unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>> = HashMap::default();
+    let tx = builder
+        .build_balanced(
+            &mut ctx.to_live_cells_context(),
+            &ctx,
+            &ctx,
+            &ctx,
+            &balancer,
+            &unlockers,
+        )
+        .unwrap();
+    (ctx, tx)
+}
+
 fn init_context(contracts: Vec<(&[u8], bool)>, live_cells: Vec<(Script, Option<u64>)>) -> Context {
     // ckb-cli --url https://testnet.ckb.dev rpc get_block_by_number --number 0 --output-format json --raw-data > genensis_block.json
     let genesis_block: json_types::BlockView = serde_json::from_str(GENESIS_JSON).unwrap();
@@ -125,6 +233,261 @@ fn init_context(contracts: Vec<(&[u8], bool)>, live_cells: Vec<(Script, Option<u
     ctx
 }
 
+fn init_deterministic_context(
+    seed: u64,
+    contracts: Vec<(&[u8], bool)>,
+    live_cells: Vec<(Script, Option<u64>)>,
+) -> Context {
+    let genesis_block: json_types::BlockView = serde_json::from_str(GENESIS_JSON).unwrap();
+    let genesis_block: BlockView = genesis_block.into();
+    let mut ctx = Context::new_deterministic(&genesis_block, contracts, seed);
+    for (lock, capacity_opt) in live_cells {
+        let out_point = ctx.random_out_point();
+        ctx.add_simple_live_cell(out_point, lock, capacity_opt);
+    }
+    ctx
+}
+
+#[derive(Clone)]
+struct SizeLimitedFeeRate(u64);
+
+impl FeeRateProvider for SizeLimitedFeeRate {
+    fn fee_rate(&self) -> Result<FeeRate, anyhow::Error> {
+        Ok(FeeRate::from_u64(FEE_RATE))
+    }
+    fn max_tx_size(&self) -> Option<u64> {
+        Some(self.0)
+    }
+}
+
+#[test]
+fn test_balance_tx_size_limit() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let ctx = init_context(
+        Vec::new(),
+        vec![
+            (sender.clone(), Some(100 * ONE_CKB)),
+            (sender.clone(), Some(200 * ONE_CKB)),
+        ],
+    );
+    let output = CellOutput::new_builder()
+        .capacity((120 * ONE_CKB).pack())
+        .lock(receiver)
+        .build();
+    let builder = CapacityTransferBuilder::new(vec![(output, Bytes::default())]);
+    let placeholder_witness = WitnessArgs::new_builder()
+        .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+        .build();
+    let mut balancer = CapacityBalancer::new_simple(sender, placeholder_witness, FEE_RATE);
+    let base_tx = builder
+        .build_base(&mut ctx.to_live_cells_context(), &ctx, &ctx, &ctx)
+        .unwrap();
+    let balance = |balancer: &CapacityBalancer| {
+        let mut cell_collector = ctx.to_live_cells_context();
+        balance_tx_capacity(&base_tx, balancer, &mut cell_collector, &ctx, &ctx, &ctx)
+    };
+
+    let tx = balance(&balancer).unwrap();
+    let tx_size = tx.data().as_reader().serialized_size_in_block() as u64;
+    balancer.set_fee_rate_provider(Some(Box::new(SizeLimitedFeeRate(tx_size))));
+    assert_eq!(balance(&balancer).unwrap().hash(), tx.hash());
+    balancer.set_fee_rate_provider(Some(Box::new(SizeLimitedFeeRate(tx_size - 1))));
+    let err = balance(&balancer).unwrap_err();
+    assert!(
+        matches!(err, BalanceTxCapacityError::TxSizeLimitExceeded(size, limit) if size == tx_size && limit == tx_size - 1),
+        "{}",
+        err
+    );
+}
+
+#[derive(Clone)]
+struct CountedFeeRate(Arc<AtomicUsize>);
+
+impl FeeRateProvider for CountedFeeRate {
+    fn fee_rate(&self) -> Result<FeeRate, anyhow::Error> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        Ok(FeeRate::from_u64(FEE_RATE))
+    }
+}
+
+#[test]
+fn test_balance_read_fee_rate_once() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let ctx = init_context(
+        Vec::new(),
+        vec![
+            (sender.clone(), Some(100 * ONE_CKB)),
+            (sender.clone(), Some(200 * ONE_CKB)),
+            (sender.clone(), Some(1000 * ONE_CKB)),
+        ],
+    );
+    let output = CellOutput::new_builder()
+        .capacity((120 * ONE_CKB).pack())
+        .lock(receiver)
+        .build();
+    let builder = CapacityTransferBuilder::new(vec![(output, Bytes::default())]);
+    let placeholder_witness = WitnessArgs::new_builder()
+        .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+        .build();
+    let mut balancer = CapacityBalancer::new_simple(sender, placeholder_witness, FEE_RATE);
+    let reads = Arc::new(AtomicUsize::new(0));
+    balancer.set_fee_rate_provider(Some(Box::new(CountedFeeRate(reads.clone()))));
+
+    let mut cell_collector = ctx.to_live_cells_context();
+    let unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>> = HashMap::default();
+    let tx = builder
+        .build_balanced(&mut cell_collector, &ctx, &ctx, &ctx, &balancer, &unlockers)
+        .unwrap();
+    assert_eq!(reads.load(Ordering::SeqCst), 1);
+
+    // the change output can not pay the fee, more inputs are collected
+    let change_idx = tx.outputs().len() - 1;
+    let change_capacity: u64 = tx.outputs().get(change_idx).unwrap().capacity().unpack();
+    let fee = tx_fee(tx.clone(), &ctx, &ctx).unwrap();
+    reads.store(0, Ordering::SeqCst);
+    let (rebalanced_tx, _) = balancer
+        .rebalance_tx_capacity(
+            &tx,
+            &mut cell_collector,
+            &ctx,
+            &ctx,
+            &ctx,
+            fee + change_capacity,
+            Some(change_idx),
+        )
+        .unwrap();
+    assert!(rebalanced_tx.inputs().len() > tx.inputs().len());
+    assert_eq!(reads.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_balance_large_data_fee() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let ctx = init_context(
+        Vec::new(),
+        vec![
+            (sender.clone(), Some(200_000 * ONE_CKB)),
+            (sender.clone(), Some(200_000 * ONE_CKB)),
+        ],
+    );
+    let data = Bytes::from(vec![0x42u8; 300 * 1024]);
+    let output = CellOutput::new_builder()
+        .lock(receiver)
+        .build_exact_capacity(Capacity::bytes(data.len()).unwrap())
+        .unwrap();
+    let builder = CapacityTransferBuilder::new(vec![(output, data)]);
+    let placeholder_witness = WitnessArgs::new_builder()
+        .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+        .build();
+    let balancer = CapacityBalancer::new_simple(sender.clone(), placeholder_witness, FEE_RATE);
+    let account1_key = secp256k1::SecretKey::from_slice(ACCOUNT1_KEY.as_bytes()).unwrap();
+    let signer = SecpCkbRawKeySigner::new_with_secret_keys(vec![account1_key]);
+    let script_unlocker = SecpSighashUnlocker::from(Box::new(signer) as Box<_>);
+    let mut unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>> = HashMap::default();
+    unlockers.insert(
+        ScriptId::new_type(SIGHASH_TYPE_HASH.clone()),
+        Box::new(script_unlocker),
+    );
+
+    let (tx, locked_groups) = builder
+        .build_unlocked(
+            &mut ctx.to_live_cells_context(),
+            &ctx,
+            &ctx,
+            &ctx,
+            &balancer,
+            &unlockers,
+        )
+        .unwrap();
+    assert!(locked_groups.is_empty());
+    assert_eq!(tx.inputs().len(), 2);
+    assert_eq!(tx.outputs().len(), 2);
+    assert_eq!(tx.output(1).unwrap().lock(), sender);
+
+    let input_capacity: u64 = tx
+        .input_pts_iter()
+        .map(|out_point| ctx.get_input(&out_point).unwrap().0.capacity().unpack())
+        .sum();
+    let fee = input_capacity - tx.outputs_capacity().unwrap().as_u64();
+    let tx_size = tx.data().as_reader().serialized_size_in_block() as u64;
+    // the fee rate of the signed transaction is within 1 shannon/KB of the target
+    assert!(fee >= FeeRate::from_u64(FEE_RATE).fee(tx_size).as_u64());
+    assert!(fee * 1000 <= (FEE_RATE + 1) * tx_size);
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_balance_trailing_witness() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let ctx = init_context(
+        Vec::new(),
+        vec![
+            (sender.clone(), Some(200 * ONE_CKB)),
+            (sender.clone(), Some(300 * ONE_CKB)),
+        ],
+    );
+    let output = CellOutput::new_builder()
+        .capacity((400 * ONE_CKB).pack())
+        .lock(receiver)
+        .build();
+    let builder = CapacityTransferBuilder::new(vec![(output, Bytes::default())]);
+    let placeholder_witness = WitnessArgs::new_builder()
+        .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+        .build();
+    let mut balancer = CapacityBalancer::new_simple(sender, placeholder_witness, FEE_RATE);
+    let commitment = Bytes::from(blake2b_256(b"commitment").to_vec());
+    balancer.reserve_trailing_witness("commitment", commitment.clone());
+
+    let account1_key = secp256k1::SecretKey::from_slice(ACCOUNT1_KEY.as_bytes()).unwrap();
+    let signer = SecpCkbRawKeySigner::new_with_secret_keys(vec![account1_key]);
+    let script_unlocker = SecpSighashUnlocker::from(Box::new(signer) as Box<_>);
+    let mut unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>> = HashMap::default();
+    unlockers.insert(
+        ScriptId::new_type(SIGHASH_TYPE_HASH.clone()),
+        Box::new(script_unlocker),
+    );
+
+    let balanced_tx = builder
+        .build_balanced(
+            &mut ctx.to_live_cells_context(),
+            &ctx,
+            &ctx,
+            &ctx,
+            &balancer,
+            &unlockers,
+        )
+        .unwrap();
+    assert_eq!(balanced_tx.inputs().len(), 2);
+    assert_eq!(balanced_tx.witnesses().len(), 3);
+    assert_eq!(
+        balancer.trailing_witness_index(&balanced_tx, "commitment"),
+        Some(2)
+    );
+    assert_eq!(balancer.trailing_witness_index(&balanced_tx, "other"), None);
+
+    // balancing again does not duplicate the trailing witness
+    let rebalanced_tx = balance_tx_capacity(
+        &balanced_tx,
+        &balancer,
+        &mut ctx.to_live_cells_context(),
+        &ctx,
+        &ctx,
+        &ctx,
+    )
+    .unwrap();
+    assert_eq!(rebalanced_tx.hash(), balanced_tx.hash());
+
+    let (tx, locked_groups) = unlock_tx(balanced_tx, &ctx, &unlockers).unwrap();
+    assert!(locked_groups.is_empty());
+    assert_eq!(tx.witnesses().get(2).unwrap().raw_data(), commitment);
+    assert_eq!(balancer.trailing_witness_index(&tx, "commitment"), Some(2));
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
 #[test]
 fn test_transfer_from_sighash() {
     let sender = build_sighash_script(ACCOUNT1_ARG);
@@ -182,6 +545,73 @@ fn test_transfer_from_sighash() {
     ctx.verify(tx, FEE_RATE).unwrap();
 }
 
+#[test]
+fn test_transfer_with_test_fixtures() {
+    let sender = TestAccount::random();
+    let receiver = TestAccount::random();
+    assert_ne!(sender.lock_arg, receiver.lock_arg);
+    assert_eq!(Script::from(&sender.address), sender.lock_script);
+    let mut ctx = init_context(Vec::new(), Vec::new());
+    let funded = ctx.fund(&sender.lock_script, &[100 * ONE_CKB, 200 * ONE_CKB]);
+    assert_eq!(funded.len(), 2);
+    let (type_out_point, type_script_id) = ctx.deploy_contract(ALWAYS_SUCCESS_BIN, true);
+    let (data_out_point, data_script_id) = ctx.deploy_contract(SUDT_BIN, false);
+    assert_eq!(
+        data_script_id,
+        ScriptId::new_data1(H256::from(blake2b_256(SUDT_BIN)))
+    );
+    assert_eq!(type_script_id.hash_type, ScriptHashType::Type);
+    let type_script = Script::new_builder()
+        .code_hash(type_script_id.code_hash.pack())
+        .hash_type(ScriptHashType::Type.to_packed())
+        .build();
+    assert_eq!(
+        ctx.resolve(&type_script).unwrap().out_point(),
+        type_out_point
+    );
+    let data_script = Script::new_builder()
+        .code_hash(data_script_id.code_hash.pack())
+        .hash_type(ScriptHashType::Data1.to_packed())
+        .build();
+    assert_eq!(
+        ctx.resolve(&data_script).unwrap().out_point(),
+        data_out_point
+    );
+
+    let output = CellOutput::new_builder()
+        .capacity((150 * ONE_CKB).pack())
+        .lock(receiver.lock_script.clone())
+        .type_(Some(type_script).pack())
+        .build();
+    let builder = CapacityTransferBuilder::new(vec![(output.clone(), Bytes::default())]);
+    let placeholder_witness = WitnessArgs::new_builder()
+        .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+        .build();
+    let balancer =
+        CapacityBalancer::new_simple(sender.lock_script.clone(), placeholder_witness, FEE_RATE);
+    let mut unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>> = HashMap::default();
+    unlockers.insert(
+        ScriptId::new_type(SIGHASH_TYPE_HASH.clone()),
+        Box::new(SecpSighashUnlocker::from(
+            Box::new(sender.signer()) as Box<_>
+        )),
+    );
+    let (tx, locked_groups) = builder
+        .build_unlocked(
+            &mut ctx.to_live_cells_context(),
+            &ctx,
+            &ctx,
+            &ctx,
+            &balancer,
+            &unlockers,
+        )
+        .unwrap();
+    assert!(locked_groups.is_empty());
+    assert_eq!(tx.inputs().len(), 2);
+    assert_eq!(tx.output(0).unwrap(), output);
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
 #[test]
 fn test_transfer_capacity_overflow() {
     let sender = build_sighash_script(ACCOUNT1_ARG);
@@ -207,6 +637,175 @@ fn test_transfer_capacity_overflow() {
     assert!(res.unwrap_err().to_string().contains("capacity not enough"));
 }
 
+// Rewrite the args of the first output with the hash of the first input
+struct FirstInputArgsBuilder {
+    inner: CapacityTransferBuilder,
+}
+
+impl TxBuilder for FirstInputArgsBuilder {
+    fn build_base(
+        &self,
+        cell_collector: &mut dyn CellCollector,
+        cell_dep_resolver: &dyn CellDepResolver,
+        header_dep_resolver: &dyn HeaderDepResolver,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, TxBuilderError> {
+        self.inner.build_base(
+            cell_collector,
+            cell_dep_resolver,
+            header_dep_resolver,
+            tx_dep_provider,
+        )
+    }
+
+    fn adjust_after_balance(
+        &self,
+        tx: TransactionView,
+        _tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, TxBuilderError> {
+        let first_input = tx.inputs().get(0).unwrap();
+        let mut outputs: Vec<_> = tx.outputs().into_iter().collect();
+        let lock = outputs[0]
+            .lock()
+            .as_builder()
+            .args(Bytes::from(blake2b_256(first_input.as_slice()).to_vec()).pack())
+            .build();
+        outputs[0] = outputs[0].clone().as_builder().lock(lock).build();
+        Ok(tx.as_advanced_builder().set_outputs(outputs).build())
+    }
+}
+
+#[test]
+fn test_adjust_after_balance() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let ctx = init_context(
+        Vec::new(),
+        vec![
+            (sender.clone(), Some(100 * ONE_CKB)),
+            (sender.clone(), Some(200 * ONE_CKB)),
+        ],
+    );
+
+    let output = CellOutput::new_builder()
+        .capacity((120 * ONE_CKB).pack())
+        .lock(receiver.clone())
+        .build();
+    let builder = FirstInputArgsBuilder {
+        inner: CapacityTransferBuilder::new(vec![(output, Bytes::default())]),
+    };
+    let placeholder_witness = WitnessArgs::new_builder()
+        .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+        .build();
+    let balancer = CapacityBalancer::new_simple(sender.clone(), placeholder_witness, FEE_RATE);
+
+    let account1_key = secp256k1::SecretKey::from_slice(ACCOUNT1_KEY.as_bytes()).unwrap();
+    let signer = SecpCkbRawKeySigner::new_with_secret_keys(vec![account1_key]);
+    let script_unlocker = SecpSighashUnlocker::from(Box::new(signer) as Box<_>);
+    let mut unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>> = HashMap::default();
+    unlockers.insert(
+        ScriptId::new_type(SIGHASH_TYPE_HASH.clone()),
+        Box::new(script_unlocker),
+    );
+
+    let mut cell_collector = ctx.to_live_cells_context();
+    let (tx, locked_groups) = builder
+        .build_unlocked(&mut cell_collector, &ctx, &ctx, &ctx, &balancer, &unlockers)
+        .unwrap();
+    assert!(locked_groups.is_empty());
+    assert_eq!(tx.inputs().len(), 2);
+    assert_eq!(tx.outputs().len(), 2);
+    // the args grows from 20 bytes to 32 bytes, the change is rebalanced
+    let first_input = tx.inputs().get(0).unwrap();
+    let output = tx.output(0).unwrap();
+    assert_eq!(output.lock().code_hash(), receiver.code_hash());
+    assert_eq!(
+        output.lock().args().raw_data().as_ref(),
+        &blake2b_256(first_input.as_slice())[..]
+    );
+    assert_eq!(tx.output(1).unwrap().lock(), sender);
+    let fee = FeeRate::from_u64(FEE_RATE)
+        .fee(tx.data().as_reader().serialized_size_in_block() as u64)
+        .as_u64();
+    let outputs_capacity: u64 = tx
+        .outputs()
+        .into_iter()
+        .map(|output| Unpack::<u64>::unpack(&output.capacity()))
+        .sum();
+    assert_eq!(300 * ONE_CKB - outputs_capacity, fee);
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_sweep_capacity() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let mut ctx = init_context(
+        Vec::new(),
+        vec![
+            (sender.clone(), Some(100 * ONE_CKB)),
+            (sender.clone(), Some(200 * ONE_CKB)),
+            (sender.clone(), Some(300 * ONE_CKB)),
+            (receiver.clone(), Some(400 * ONE_CKB)),
+        ],
+    );
+    // cells with type script are not swept by default
+    let dao_output = CellOutput::new_builder()
+        .capacity((500 * ONE_CKB).pack())
+        .lock(sender.clone())
+        .type_(Some(build_dao_script()).pack())
+        .build();
+    ctx.add_live_cell(
+        CellInput::new(random_out_point(), 0),
+        dao_output,
+        Bytes::from(vec![0u8; 8]),
+        None,
+    );
+
+    let builder = CapacitySweepBuilder::new(vec![sender.clone()], receiver.clone());
+    let placeholder_witness = WitnessArgs::new_builder()
+        .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+        .build();
+    let balancer = CapacityBalancer::new_simple(sender.clone(), placeholder_witness, FEE_RATE);
+
+    let account1_key = secp256k1::SecretKey::from_slice(ACCOUNT1_KEY.as_bytes()).unwrap();
+    let signer = SecpCkbRawKeySigner::new_with_secret_keys(vec![account1_key]);
+    let script_unlocker = SecpSighashUnlocker::from(Box::new(signer) as Box<_>);
+    let mut unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>> = HashMap::default();
+    unlockers.insert(
+        ScriptId::new_type(SIGHASH_TYPE_HASH.clone()),
+        Box::new(script_unlocker),
+    );
+
+    let mut cell_collector = ctx.to_live_cells_context();
+    let (tx, locked_groups) = builder
+        .build_unlocked(&mut cell_collector, &ctx, &ctx, &ctx, &balancer, &unlockers)
+        .unwrap();
+
+    assert!(locked_groups.is_empty());
+    assert_eq!(tx.cell_deps().len(), 1);
+    assert_eq!(tx.inputs().len(), 3);
+    for out_point in tx.input_pts_iter() {
+        let (input, data) = ctx.get_input(&out_point).unwrap();
+        assert_eq!(input.lock(), sender);
+        assert!(input.type_().is_none());
+        assert!(data.is_empty());
+    }
+    assert_eq!(tx.outputs().len(), 1);
+    let output = tx.output(0).unwrap();
+    assert_eq!(output.lock(), receiver);
+    let fee = FeeRate::from_u64(FEE_RATE)
+        .fee(tx.data().as_reader().serialized_size_in_block() as u64)
+        .as_u64();
+    let capacity: u64 = output.capacity().unpack();
+    assert_eq!(capacity, 600 * ONE_CKB - fee);
+    ctx.verify(tx, FEE_RATE).unwrap();
+
+    // nothing left to sweep
+    let res = builder.build_base(&mut cell_collector, &ctx, &ctx, &ctx);
+    assert!(res.is_err());
+}
+
 #[test]
 fn test_transfer_from_multisig() {
     let lock_args = vec![
@@ -274,12 +873,84 @@ fn test_transfer_from_multisig() {
     ctx.verify(tx, FEE_RATE).unwrap();
 }
 
+#[test]
+fn test_transfer_from_multisig_with_since() {
+    let lock_args = vec![
+        ACCOUNT0_ARG.clone(),
+        ACCOUNT1_ARG.clone(),
+        ACCOUNT2_ARG.clone(),
+    ];
+    let cfg = MultisigConfig::new_with(lock_args, 0, 2).unwrap();
+    let since = Since::absolute_epoch(EpochNumberWithFraction::new(2, 1, 3));
+    let sender = cfg.to_script_with_since(since);
+    assert_eq!(sender.args().raw_data().len(), 28);
+    assert_eq!(multisig_args_since(&sender.args().raw_data()), Some(since));
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let ctx = init_context(
+        Vec::new(),
+        vec![
+            (sender.clone(), Some(100 * ONE_CKB)),
+            (sender.clone(), Some(200 * ONE_CKB)),
+            (receiver.clone(), Some(300 * ONE_CKB)),
+        ],
+    );
+
+    let output = CellOutput::new_builder()
+        .capacity((150 * ONE_CKB).pack())
+        .lock(receiver)
+        .build();
+    let builder = CapacityTransferBuilder::new(vec![(output, Bytes::default())]);
+    let balancer =
+        CapacityBalancer::new_simple(sender.clone(), cfg.placeholder_witness(), FEE_RATE);
+    let account0_key = secp256k1::SecretKey::from_slice(ACCOUNT0_KEY.as_bytes()).unwrap();
+    let account2_key = secp256k1::SecretKey::from_slice(ACCOUNT2_KEY.as_bytes()).unwrap();
+    let unlockers = build_multisig_unlockers(account0_key, cfg.clone());
+    let tx = builder
+        .build_balanced(
+            &mut ctx.to_live_cells_context(),
+            &ctx,
+            &ctx,
+            &ctx,
+            &balancer,
+            &unlockers,
+        )
+        .unwrap();
+    assert_eq!(tx.inputs().len(), 2);
+
+    // the since is not stamped on the inputs by the simple balancer
+    match unlock_tx(tx.clone(), &ctx, &unlockers) {
+        Err(UnlockError::ScriptSigner(ScriptSignError::MultisigSinceMismatch(
+            idx,
+            0,
+            expected,
+        ))) => {
+            assert_eq!(idx, 0);
+            assert_eq!(expected, since.value());
+        }
+        other => panic!("unexpected result: {:?}", other.err()),
+    }
+
+    let mut tx = set_lock_args_since(&tx, &ctx).unwrap();
+    for input in tx.inputs() {
+        let input_since: u64 = input.since().unpack();
+        assert_eq!(input_since, since.value());
+    }
+    for key in [account0_key, account2_key] {
+        let unlockers = build_multisig_unlockers(key, cfg.clone());
+        let (new_tx, locked_groups) = unlock_tx(tx, &ctx, &unlockers).unwrap();
+        assert!(locked_groups.is_empty());
+        tx = new_tx;
+    }
+    assert_eq!(tx.output(1).unwrap().lock(), sender);
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
 #[test]
 fn test_transfer_from_acp() {
     let data_hash = H256::from(blake2b_256(ACP_BIN));
     let sender = Script::new_builder()
         .code_hash(data_hash.pack())
-        .hash_type(ScriptHashType::Data1.into())
+        .hash_type(ScriptHashType::Data1.to_packed())
         .args(Bytes::from(ACCOUNT1_ARG.0.to_vec()).pack())
         .build();
     let receiver = build_sighash_script(ACCOUNT2_ARG);
@@ -341,7 +1012,7 @@ fn test_transfer_to_acp() {
     let sender = build_sighash_script(ACCOUNT1_ARG);
     let receiver = Script::new_builder()
         .code_hash(data_hash.pack())
-        .hash_type(ScriptHashType::Data1.into())
+        .hash_type(ScriptHashType::Data1.to_packed())
         .args(Bytes::from(ACCOUNT2_ARG.0.to_vec()).pack())
         .build();
     let ctx = init_context(
@@ -418,6 +1089,278 @@ fn test_transfer_to_acp() {
     ctx.verify(tx, FEE_RATE).unwrap();
 }
 
+#[test]
+fn test_transfer_udt_to_acp() {
+    let acp_data_hash = H256::from(blake2b_256(ACP_BIN));
+    let sudt_data_hash = H256::from(blake2b_256(SUDT_BIN));
+    let owner = build_sighash_script(H160::default());
+    let type_script = Script::new_builder()
+        .code_hash(sudt_data_hash.pack())
+        .hash_type(ScriptHashType::Data1.to_packed())
+        .args(owner.calc_script_hash().as_bytes().pack())
+        .build();
+    let udt_acp_lock = Script::new_builder()
+        .code_hash(acp_data_hash.pack())
+        .hash_type(ScriptHashType::Data1.to_packed())
+        .args(Bytes::from(ACCOUNT2_ARG.0.to_vec()).pack())
+        .build();
+    let ckb_acp_lock = Script::new_builder()
+        .code_hash(acp_data_hash.pack())
+        .hash_type(ScriptHashType::Data1.to_packed())
+        .args(Bytes::from(ACCOUNT3_ARG.0.to_vec()).pack())
+        .build();
+    let mut ctx = init_context(
+        vec![(ACP_BIN, true), (SUDT_BIN, false)],
+        vec![(ckb_acp_lock.clone(), Some(99 * ONE_CKB))],
+    );
+    let udt_acp_output = CellOutput::new_builder()
+        .capacity((200 * ONE_CKB).pack())
+        .lock(udt_acp_lock.clone())
+        .type_(Some(type_script.clone()).pack())
+        .build();
+    let mut udt_acp_data = 100u128.to_le_bytes().to_vec();
+    udt_acp_data.extend_from_slice(&[0xab; 4]);
+    ctx.add_live_cell(
+        CellInput::new(random_out_point(), 0),
+        udt_acp_output.clone(),
+        Bytes::from(udt_acp_data),
+        None,
+    );
+
+    let builder = AcpTransferBuilder::new(vec![AcpTransferReceiver::new_with_udt_amount(
+        udt_acp_lock,
+        type_script.clone(),
+        ONE_CKB,
+        300,
+    )]);
+    let tx = builder
+        .build_base(&mut ctx.to_live_cells_context(), &ctx, &ctx, &ctx)
+        .unwrap();
+    assert_eq!(tx.cell_deps().len(), 2);
+    assert!(tx
+        .cell_deps()
+        .into_iter()
+        .any(|dep| Some(dep) == ctx.resolve(&type_script)));
+    let output = tx.output(0).unwrap();
+    assert_eq!(output.type_(), udt_acp_output.type_());
+    let capacity: u64 = output.capacity().unpack();
+    assert_eq!(capacity, 201 * ONE_CKB);
+    let mut expected_data = 400u128.to_le_bytes().to_vec();
+    expected_data.extend_from_slice(&[0xab; 4]);
+    assert_eq!(
+        tx.outputs_data().get(0).unwrap().raw_data(),
+        Bytes::from(expected_data)
+    );
+
+    // the acp cell holds no udt
+    let mut receiver = AcpTransferReceiver::new(ckb_acp_lock, ONE_CKB);
+    receiver.udt_amount = Some(300);
+    let builder = AcpTransferBuilder::new(vec![receiver]);
+    let err = builder
+        .build_base(&mut ctx.to_live_cells_context(), &ctx, &ctx, &ctx)
+        .unwrap_err();
+    assert!(matches!(err, TxBuilderError::InvalidParameter(_)));
+}
+
+#[test]
+fn test_transfer_to_acp_select_cell() {
+    let acp_data_hash = H256::from(blake2b_256(ACP_BIN));
+    let type_script_a = build_sudt_script(&build_sighash_script(ACCOUNT0_ARG));
+    let type_script_b = build_sudt_script(&build_sighash_script(ACCOUNT1_ARG));
+    let type_script_c = build_sudt_script(&build_sighash_script(ACCOUNT3_ARG));
+    let acp_lock = Script::new_builder()
+        .code_hash(acp_data_hash.pack())
+        .hash_type(ScriptHashType::Data1.to_packed())
+        .args(Bytes::from(ACCOUNT2_ARG.0.to_vec()).pack())
+        .build();
+    let mut ctx = init_context(vec![(ACP_BIN, true), (SUDT_BIN, false)], Vec::new());
+    let mut add_acp_cell = |type_script: Option<Script>, capacity: u64, data: Bytes| {
+        let out_point = random_out_point();
+        let output = CellOutput::new_builder()
+            .capacity(capacity.pack())
+            .lock(acp_lock.clone())
+            .type_(type_script.pack())
+            .build();
+        ctx.add_live_cell(CellInput::new(out_point.clone(), 0), output, data, None);
+        out_point
+    };
+    let udt_data = Bytes::from(100u128.to_le_bytes().to_vec());
+    let udt_a_out_point =
+        add_acp_cell(Some(type_script_a.clone()), 142 * ONE_CKB, udt_data.clone());
+    let udt_b_out_point =
+        add_acp_cell(Some(type_script_b.clone()), 143 * ONE_CKB, udt_data.clone());
+    let ckb_out_point = add_acp_cell(None, 61 * ONE_CKB, Bytes::default());
+
+    let build = |receiver: AcpTransferReceiver| {
+        let builder = AcpTransferBuilder::new(vec![receiver]);
+        builder.build_base(&mut ctx.to_live_cells_context(), &ctx, &ctx, &ctx)
+    };
+    let capacity_of = |tx: &TransactionView| -> u64 { tx.output(0).unwrap().capacity().unpack() };
+
+    // no type script: the ckb only acp cell
+    let tx = build(AcpTransferReceiver::new(acp_lock.clone(), ONE_CKB)).unwrap();
+    assert_eq!(tx.inputs().get(0).unwrap().previous_output(), ckb_out_point);
+    assert!(tx.output(0).unwrap().type_().is_none());
+    assert_eq!(capacity_of(&tx), 62 * ONE_CKB);
+    assert_eq!(tx.cell_deps().len(), 1);
+
+    // by type script
+    let tx = build(AcpTransferReceiver::new_with_udt_amount(
+        acp_lock.clone(),
+        type_script_a.clone(),
+        ONE_CKB,
+        20,
+    ))
+    .unwrap();
+    assert_eq!(
+        tx.inputs().get(0).unwrap().previous_output(),
+        udt_a_out_point
+    );
+    assert_eq!(tx.output(0).unwrap().type_().to_opt(), Some(type_script_a));
+    assert_eq!(capacity_of(&tx), 143 * ONE_CKB);
+    assert_eq!(
+        tx.outputs_data().get(0).unwrap().raw_data(),
+        Bytes::from(120u128.to_le_bytes().to_vec())
+    );
+
+    // by out point
+    let mut receiver = AcpTransferReceiver::new(acp_lock.clone(), ONE_CKB);
+    receiver.out_point = Some(udt_b_out_point.clone());
+    let tx = build(receiver).unwrap();
+    assert_eq!(
+        tx.inputs().get(0).unwrap().previous_output(),
+        udt_b_out_point
+    );
+    assert_eq!(tx.output(0).unwrap().type_().to_opt(), Some(type_script_b));
+    assert_eq!(capacity_of(&tx), 144 * ONE_CKB);
+    assert_eq!(tx.outputs_data().get(0).unwrap().raw_data(), udt_data);
+
+    // the out point is not locked by the receiver lock
+    let mut receiver = AcpTransferReceiver::new(build_sighash_script(ACCOUNT2_ARG), ONE_CKB);
+    receiver.out_point = Some(udt_b_out_point);
+    assert!(matches!(
+        build(receiver).unwrap_err(),
+        TxBuilderError::InvalidParameter(_)
+    ));
+
+    // no acp cell with this lock/type combination
+    let err = build(AcpTransferReceiver::new_with_udt_amount(
+        acp_lock,
+        type_script_c,
+        ONE_CKB,
+        20,
+    ))
+    .unwrap_err();
+    assert!(matches!(err, TxBuilderError::Other(_)));
+}
+
+#[test]
+fn test_create_acp() {
+    let acp_data_hash = H256::from(blake2b_256(ACP_BIN));
+    let sudt_data_hash = H256::from(blake2b_256(SUDT_BIN));
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let owner = build_sighash_script(H160::default());
+    let type_script = Script::new_builder()
+        .code_hash(sudt_data_hash.pack())
+        .hash_type(ScriptHashType::Data1.to_packed())
+        .args(owner.calc_script_hash().as_bytes().pack())
+        .build();
+    let acp_lock = Script::new_builder()
+        .code_hash(acp_data_hash.pack())
+        .hash_type(ScriptHashType::Data1.to_packed())
+        .args(Bytes::from(ACCOUNT1_ARG.0.to_vec()).pack())
+        .build();
+    let ctx = init_context(
+        vec![(ACP_BIN, true), (SUDT_BIN, false)],
+        vec![
+            (sender.clone(), Some(100 * ONE_CKB)),
+            (sender.clone(), Some(200 * ONE_CKB)),
+        ],
+    );
+
+    let mut ckb_receiver = AcpCreateReceiver::new(acp_lock.clone());
+    ckb_receiver.capacity = Some(100 * ONE_CKB);
+    let udt_receiver = AcpCreateReceiver::new_with_udt(acp_lock.clone(), type_script.clone(), 0);
+    let builder = AcpCreateBuilder::new(vec![ckb_receiver, udt_receiver]);
+    let placeholder_witness = WitnessArgs::new_builder()
+        .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+        .build();
+    let balancer =
+        CapacityBalancer::new_simple(sender.clone(), placeholder_witness.clone(), FEE_RATE);
+
+    let account1_key = secp256k1::SecretKey::from_slice(ACCOUNT1_KEY.as_bytes()).unwrap();
+    let signer = SecpCkbRawKeySigner::new_with_secret_keys(vec![account1_key]);
+    let script_unlocker = SecpSighashUnlocker::from(Box::new(signer) as Box<_>);
+    let mut unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>> = HashMap::default();
+    unlockers.insert(
+        ScriptId::new_type(SIGHASH_TYPE_HASH.clone()),
+        Box::new(script_unlocker),
+    );
+
+    let mut cell_collector = ctx.to_live_cells_context();
+    let (tx, locked_groups) = builder
+        .build_unlocked(&mut cell_collector, &ctx, &ctx, &ctx, &balancer, &unlockers)
+        .unwrap();
+
+    assert!(locked_groups.is_empty());
+    assert_eq!(tx.cell_deps().len(), 3);
+    let outputs = tx.outputs().into_iter().collect::<Vec<_>>();
+    assert_eq!(outputs.len(), 3);
+    assert_eq!(
+        outputs[0],
+        CellOutput::new_builder()
+            .capacity((100 * ONE_CKB).pack())
+            .lock(acp_lock.clone())
+            .build()
+    );
+    let udt_output = CellOutput::new_builder()
+        .lock(acp_lock.clone())
+        .type_(Some(type_script.clone()).pack())
+        .build();
+    let occupied_capacity = udt_output
+        .occupied_capacity(Capacity::bytes(16).unwrap())
+        .unwrap();
+    assert_eq!(
+        outputs[1],
+        udt_output
+            .as_builder()
+            .capacity(occupied_capacity.pack())
+            .build()
+    );
+    assert_eq!(outputs[2].lock(), sender);
+    let outputs_data = tx
+        .outputs_data()
+        .into_iter()
+        .map(|d| d.raw_data())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        outputs_data,
+        vec![
+            Bytes::default(),
+            Bytes::from(0u128.to_le_bytes().to_vec()),
+            Bytes::default(),
+        ]
+    );
+    ctx.verify(tx, FEE_RATE).unwrap();
+
+    // explicit capacity below the occupied capacity
+    let mut receiver = AcpCreateReceiver::new_with_udt(acp_lock.clone(), type_script, 0);
+    receiver.capacity = Some(61 * ONE_CKB);
+    assert!(AcpCreateBuilder::new(vec![receiver])
+        .build_base(&mut ctx.to_live_cells_context(), &ctx, &ctx, &ctx)
+        .is_err());
+
+    // udt amount without udt type script
+    let mut receiver = AcpCreateReceiver::new(acp_lock);
+    receiver.init_udt_amount = 100;
+    assert!(matches!(
+        AcpCreateBuilder::new(vec![receiver])
+            .build_base(&mut ctx.to_live_cells_context(), &ctx, &ctx, &ctx)
+            .unwrap_err(),
+        TxBuilderError::InvalidParameter(_)
+    ));
+}
+
 #[test]
 fn test_cheque_claim() {
     let sudt_data_hash = H256::from(blake2b_256(SUDT_BIN));
@@ -427,7 +1370,7 @@ fn test_cheque_claim() {
     let cheque_script = build_cheque_script(&sender, &receiver, cheque_data_hash.clone());
     let type_script = Script::new_builder()
         .code_hash(sudt_data_hash.pack())
-        .hash_type(ScriptHashType::Data1.into())
+        .hash_type(ScriptHashType::Data1.to_packed())
         .args(Bytes::from(vec![9u8; 32]).pack())
         .build();
     let mut ctx = init_context(
@@ -539,15 +1482,226 @@ fn test_cheque_claim() {
 }
 
 #[test]
-fn test_cheque_withdraw() {
+fn test_cheque_withdraw() {
+    let sudt_data_hash = H256::from(blake2b_256(SUDT_BIN));
+    let cheque_data_hash = H256::from(blake2b_256(CHEQUE_BIN));
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let cheque_script = build_cheque_script(&sender, &receiver, cheque_data_hash.clone());
+    let type_script = Script::new_builder()
+        .code_hash(sudt_data_hash.pack())
+        .hash_type(ScriptHashType::Data1.to_packed())
+        .args(Bytes::from(vec![9u8; 32]).pack())
+        .build();
+    let mut ctx = init_context(
+        vec![(CHEQUE_BIN, true), (SUDT_BIN, false)],
+        vec![
+            (sender.clone(), Some(100 * ONE_CKB)),
+            (sender.clone(), Some(200 * ONE_CKB)),
+        ],
+    );
+
+    let cheque_out_point = random_out_point();
+    let cheque_input = CellInput::new(cheque_out_point.clone(), CHEQUE_CELL_SINCE);
+    let cheque_output = CellOutput::new_builder()
+        .capacity((220 * ONE_CKB).pack())
+        .lock(cheque_script)
+        .type_(Some(type_script).pack())
+        .build();
+    let cheque_data = Bytes::from(500u128.to_le_bytes().to_vec());
+    ctx.add_live_cell(cheque_input, cheque_output.clone(), cheque_data, None);
+
+    let builder = ChequeWithdrawBuilder::new(vec![cheque_out_point], sender.clone(), None);
+    let placeholder_witness = WitnessArgs::new_builder()
+        .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+        .build();
+    let balancer =
+        CapacityBalancer::new_simple(sender.clone(), placeholder_witness.clone(), FEE_RATE);
+
+    let account1_key = secp256k1::SecretKey::from_slice(ACCOUNT1_KEY.as_bytes()).unwrap();
+    let signer = SecpCkbRawKeySigner::new_with_secret_keys(vec![account1_key]);
+    let sighash_unlocker = SecpSighashUnlocker::from(Box::new(signer.clone()) as Box<_>);
+    let cheque_unlocker =
+        ChequeUnlocker::from((Box::new(signer) as Box<_>, ChequeAction::Withdraw));
+    let mut unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>> = HashMap::default();
+    unlockers.insert(
+        ScriptId::new_type(SIGHASH_TYPE_HASH),
+        Box::new(sighash_unlocker),
+    );
+    unlockers.insert(
+        ScriptId::new_data1(cheque_data_hash),
+        Box::new(cheque_unlocker),
+    );
+
+    let mut cell_collector = ctx.to_live_cells_context();
+    let (tx, locked_groups) = builder
+        .build_unlocked(&mut cell_collector, &ctx, &ctx, &ctx, &balancer, &unlockers)
+        .unwrap();
+
+    assert!(locked_groups.is_empty());
+    assert_eq!(tx.header_deps().len(), 0);
+    assert_eq!(tx.cell_deps().len(), 3);
+    assert_eq!(tx.inputs().len(), 2);
+    let input_cells = vec![
+        cheque_output.clone(),
+        CellOutput::new_builder()
+            .capacity((100 * ONE_CKB).pack())
+            .lock(sender.clone())
+            .build(),
+    ];
+    for (idx, out_point) in tx.input_pts_iter().enumerate() {
+        assert_eq!(ctx.get_input(&out_point).unwrap().0, input_cells[idx]);
+    }
+    assert_eq!(tx.outputs().len(), 2);
+    let sender_output = cheque_output.as_builder().lock(sender.clone()).build();
+    assert_eq!(tx.output(0).unwrap(), sender_output);
+    assert_eq!(tx.output(1).unwrap().lock(), sender);
+    let expected_outputs_data = vec![
+        Bytes::from(500u128.to_le_bytes().to_vec()),
+        Bytes::default(),
+    ];
+    let outputs_data = tx
+        .outputs_data()
+        .into_iter()
+        .map(|d| d.raw_data())
+        .collect::<Vec<_>>();
+    assert_eq!(outputs_data, expected_outputs_data);
+    let witnesses_len = tx
+        .witnesses()
+        .into_iter()
+        .map(|w| w.raw_data().len())
+        .collect::<Vec<_>>();
+    assert_eq!(witnesses_len, vec![0, placeholder_witness.as_slice().len()]);
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_cheque_withdraw_since() {
+    let cheque_data_hash = H256::from(blake2b_256(CHEQUE_BIN));
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let cheque_script = build_cheque_script(&sender, &receiver, cheque_data_hash);
+    let account1_key = secp256k1::SecretKey::from_slice(ACCOUNT1_KEY.as_bytes()).unwrap();
+    let signer = SecpCkbRawKeySigner::new_with_secret_keys(vec![account1_key]);
+    let relative_epoch = |number, index, length| {
+        let epoch = EpochNumberWithFraction::new(number, index, length);
+        Since::new(SinceType::EpochNumberWithFraction, epoch.full_value(), true)
+    };
+
+    for since in [
+        Since::new_absolute_epoch(2),
+        Since::new(SinceType::BlockNumber, 100, true),
+        Since::from_raw_value(relative_epoch(2, 0, 1).value() | 0x0100_0000_0000_0000),
+        relative_epoch(0, 0, 1),
+    ] {
+        let result =
+            ChequeUnlocker::new_with_withdraw_since(Box::new(signer.clone()) as Box<_>, since);
+        assert!(matches!(
+            result,
+            Err(ScriptSignError::InvalidChequeWithdrawSince(_))
+        ));
+    }
+
+    // a cheque script with 2 epochs lock period
+    let withdraw_since = relative_epoch(2, 0, 1);
+    let mut ctx = init_context(vec![(CHEQUE_BIN, true)], Vec::new());
+    let cheque_out_point = random_out_point();
+    let cheque_output = CellOutput::new_builder()
+        .capacity((220 * ONE_CKB).pack())
+        .lock(cheque_script)
+        .build();
+    ctx.add_live_cell(
+        CellInput::new(cheque_out_point.clone(), 0),
+        cheque_output,
+        Bytes::default(),
+        None,
+    );
+    let sender_out_point = random_out_point();
+    let sender_output = CellOutput::new_builder()
+        .capacity((100 * ONE_CKB).pack())
+        .lock(sender)
+        .build();
+    ctx.add_live_cell(
+        CellInput::new(sender_out_point.clone(), 0),
+        sender_output,
+        Bytes::default(),
+        None,
+    );
+    let tx = TransactionView::new_advanced_builder()
+        .input(CellInput::new(cheque_out_point, withdraw_since.value()))
+        .input(CellInput::new(sender_out_point, 0))
+        .witness(Bytes::default().pack())
+        .witness(
+            WitnessArgs::new_builder()
+                .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+                .build()
+                .as_bytes()
+                .pack(),
+        )
+        .build();
+    let cheque_group = gen_script_groups(&tx, &ctx)
+        .unwrap()
+        .lock_groups
+        .into_values()
+        .find(|group| group.input_indices == vec![0])
+        .unwrap();
+
+    let unlocker =
+        ChequeUnlocker::new_with_withdraw_since(Box::new(signer.clone()) as Box<_>, withdraw_since)
+            .unwrap();
+    assert!(unlocker.is_unlocked(&tx, &cheque_group, &ctx).unwrap());
+
+    // the default unlocker requires the 6 epochs lock period
+    let unlocker = ChequeUnlocker::from((Box::new(signer) as Box<_>, ChequeAction::Withdraw));
+    let err = unlocker
+        .is_unlocked(&tx, &cheque_group, &ctx)
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.contains(&format!("{:#x}", CHEQUE_CELL_SINCE)),
+        "{}",
+        err
+    );
+    assert!(
+        err.contains(&format!("{:#x}", withdraw_since.value())),
+        "{}",
+        err
+    );
+}
+
+fn add_cheque_cell(
+    ctx: &mut Context,
+    cheque_script: Script,
+    type_script: Script,
+    header: &HeaderView,
+    amount: u128,
+) -> OutPoint {
+    let out_point = random_out_point();
+    let output = CellOutput::new_builder()
+        .capacity((220 * ONE_CKB).pack())
+        .lock(cheque_script)
+        .type_(Some(type_script).pack())
+        .build();
+    ctx.add_live_cell(
+        CellInput::new(out_point.clone(), 0),
+        output,
+        Bytes::from(amount.to_le_bytes().to_vec()),
+        Some(header.hash()),
+    );
+    out_point
+}
+
+#[test]
+fn test_cheque_bulk_withdraw() {
     let sudt_data_hash = H256::from(blake2b_256(SUDT_BIN));
     let cheque_data_hash = H256::from(blake2b_256(CHEQUE_BIN));
     let sender = build_sighash_script(ACCOUNT1_ARG);
-    let receiver = build_sighash_script(ACCOUNT2_ARG);
-    let cheque_script = build_cheque_script(&sender, &receiver, cheque_data_hash.clone());
+    let receiver_a = build_sighash_script(ACCOUNT2_ARG);
+    let receiver_b = build_sighash_script(ACCOUNT0_ARG);
+    let other_sender = build_sighash_script(ACCOUNT3_ARG);
     let type_script = Script::new_builder()
         .code_hash(sudt_data_hash.pack())
-        .hash_type(ScriptHashType::Data1.into())
+        .hash_type(ScriptHashType::Data1.to_packed())
         .args(Bytes::from(vec![9u8; 32]).pack())
         .build();
     let mut ctx = init_context(
@@ -557,24 +1711,99 @@ fn test_cheque_withdraw() {
             (sender.clone(), Some(200 * ONE_CKB)),
         ],
     );
+    let old_header = HeaderBuilder::default()
+        .epoch(
+            EpochNumberWithFraction::new(10, 500, 1000)
+                .full_value()
+                .pack(),
+        )
+        .number(10_500.pack())
+        .build();
+    let young_header = HeaderBuilder::default()
+        .epoch(
+            EpochNumberWithFraction::new(15, 0, 1000)
+                .full_value()
+                .pack(),
+        )
+        .number(15_000.pack())
+        .build();
+    ctx.add_header(old_header.clone());
+    ctx.add_header(young_header.clone());
+
+    let cheque_a = build_cheque_script(&sender, &receiver_a, cheque_data_hash.clone());
+    let cheque_b = build_cheque_script(&sender, &receiver_b, cheque_data_hash.clone());
+    let mut reclaimable = Vec::new();
+    for _ in 0..3 {
+        reclaimable.push(add_cheque_cell(
+            &mut ctx,
+            cheque_a.clone(),
+            type_script.clone(),
+            &old_header,
+            100,
+        ));
+    }
+    reclaimable.push(add_cheque_cell(
+        &mut ctx,
+        cheque_b.clone(),
+        type_script.clone(),
+        &old_header,
+        50,
+    ));
+    let too_young = add_cheque_cell(&mut ctx, cheque_b, type_script.clone(), &young_header, 70);
+    // cheque issued by someone else
+    add_cheque_cell(
+        &mut ctx,
+        build_cheque_script(&other_sender, &receiver_a, cheque_data_hash.clone()),
+        type_script.clone(),
+        &old_header,
+        30,
+    );
 
-    let cheque_out_point = random_out_point();
-    let cheque_input = CellInput::new(cheque_out_point.clone(), CHEQUE_CELL_SINCE);
-    let cheque_output = CellOutput::new_builder()
-        .capacity((220 * ONE_CKB).pack())
-        .lock(cheque_script)
-        .type_(Some(type_script).pack())
+    let mut builder = ChequeBulkWithdrawBuilder::new(
+        ScriptId::new_data1(cheque_data_hash.clone()),
+        sender.clone(),
+    );
+    builder.max_inputs_per_tx = 3;
+    let tip_header = HeaderBuilder::default()
+        .epoch(
+            EpochNumberWithFraction::new(16, 500, 1000)
+                .full_value()
+                .pack(),
+        )
+        .number(16_500.pack())
         .build();
-    let cheque_data = Bytes::from(500u128.to_le_bytes().to_vec());
-    ctx.add_live_cell(cheque_input, cheque_output.clone(), cheque_data, None);
+    let mut cell_collector = ctx.to_live_cells_context();
+    let result = builder
+        .build_base_txs(&mut cell_collector, &ctx, &ctx, &tip_header)
+        .unwrap();
+    assert_eq!(result.not_ready, vec![too_young]);
+    assert_eq!(
+        result.reclaimed,
+        vec![ChequeWithdrawSummary {
+            type_script: type_script.clone(),
+            cells: 4,
+            capacity: 4 * 220 * ONE_CKB,
+            amount: 350,
+        }]
+    );
+    assert_eq!(result.transactions.len(), 2);
+    let withdrawn = result
+        .transactions
+        .iter()
+        .flat_map(|tx| tx.input_pts_iter())
+        .collect::<Vec<_>>();
+    assert_eq!(withdrawn, reclaimable);
+    // only the withdrawn cheque cells are locked
+    let rebuilt = builder
+        .build_base_txs(&mut cell_collector, &ctx, &ctx, &tip_header)
+        .unwrap();
+    assert!(rebuilt.transactions.is_empty());
+    assert_eq!(rebuilt.not_ready, result.not_ready);
 
-    let builder = ChequeWithdrawBuilder::new(vec![cheque_out_point], sender.clone(), None);
     let placeholder_witness = WitnessArgs::new_builder()
         .lock(Some(Bytes::from(vec![0u8; 65])).pack())
         .build();
-    let balancer =
-        CapacityBalancer::new_simple(sender.clone(), placeholder_witness.clone(), FEE_RATE);
-
+    let balancer = CapacityBalancer::new_simple(sender.clone(), placeholder_witness, FEE_RATE);
     let account1_key = secp256k1::SecretKey::from_slice(ACCOUNT1_KEY.as_bytes()).unwrap();
     let signer = SecpCkbRawKeySigner::new_with_secret_keys(vec![account1_key]);
     let sighash_unlocker = SecpSighashUnlocker::from(Box::new(signer.clone()) as Box<_>);
@@ -589,47 +1818,23 @@ fn test_cheque_withdraw() {
         ScriptId::new_data1(cheque_data_hash),
         Box::new(cheque_unlocker),
     );
-
-    let mut cell_collector = ctx.to_live_cells_context();
-    let (tx, locked_groups) = builder
-        .build_unlocked(&mut cell_collector, &ctx, &ctx, &ctx, &balancer, &unlockers)
-        .unwrap();
-
-    assert!(locked_groups.is_empty());
-    assert_eq!(tx.header_deps().len(), 0);
-    assert_eq!(tx.cell_deps().len(), 3);
-    assert_eq!(tx.inputs().len(), 2);
-    let input_cells = vec![
-        cheque_output.clone(),
-        CellOutput::new_builder()
-            .capacity((100 * ONE_CKB).pack())
-            .lock(sender.clone())
-            .build(),
-    ];
-    for (idx, out_point) in tx.input_pts_iter().enumerate() {
-        assert_eq!(ctx.get_input(&out_point).unwrap().0, input_cells[idx]);
+    for tx in result.transactions {
+        for input in tx.inputs() {
+            let since: u64 = input.since().unpack();
+            assert_eq!(since, CHEQUE_CELL_SINCE);
+        }
+        let mut amount_bytes = [0u8; 16];
+        amount_bytes.copy_from_slice(&tx.outputs_data().get(0).unwrap().raw_data());
+        let expected_amount = if tx.inputs().len() == 3 { 300 } else { 50 };
+        assert_eq!(u128::from_le_bytes(amount_bytes), expected_amount);
+        let (tx, _) = fill_placeholder_witnesses(tx, &ctx, &unlockers).unwrap();
+        let tx =
+            balance_tx_capacity(&tx, &balancer, &mut cell_collector, &ctx, &ctx, &ctx).unwrap();
+        let (tx, locked_groups) = unlock_tx(tx, &ctx, &unlockers).unwrap();
+        assert!(locked_groups.is_empty());
+        assert_eq!(tx.output(0).unwrap().lock(), sender);
+        ctx.verify(tx, FEE_RATE).unwrap();
     }
-    assert_eq!(tx.outputs().len(), 2);
-    let sender_output = cheque_output.as_builder().lock(sender.clone()).build();
-    assert_eq!(tx.output(0).unwrap(), sender_output);
-    assert_eq!(tx.output(1).unwrap().lock(), sender);
-    let expected_outputs_data = vec![
-        Bytes::from(500u128.to_le_bytes().to_vec()),
-        Bytes::default(),
-    ];
-    let outputs_data = tx
-        .outputs_data()
-        .into_iter()
-        .map(|d| d.raw_data())
-        .collect::<Vec<_>>();
-    assert_eq!(outputs_data, expected_outputs_data);
-    let witnesses_len = tx
-        .witnesses()
-        .into_iter()
-        .map(|w| w.raw_data().len())
-        .collect::<Vec<_>>();
-    assert_eq!(witnesses_len, vec![0, placeholder_witness.as_slice().len()]);
-    ctx.verify(tx, FEE_RATE).unwrap();
 }
 
 #[test]
@@ -944,7 +2149,9 @@ fn test_udt_issue() {
         udt_type: UdtType::Sudt,
         script_id: sudt_script_id,
         owner: owner.clone(),
+        owner_mode: OwnerMode::ConsumeOwnerCell,
         receivers: vec![udt_receiver],
+        data_validator: None,
     };
     let placeholder_witness = WitnessArgs::new_builder()
         .lock(Some(Bytes::from(vec![0u8; 65])).pack())
@@ -976,7 +2183,7 @@ fn test_udt_issue() {
     assert_eq!(tx.outputs().len(), 2);
     let type_script = Script::new_builder()
         .code_hash(sudt_data_hash.pack())
-        .hash_type(ScriptHashType::Data1.into())
+        .hash_type(ScriptHashType::Data1.to_packed())
         .args(owner.calc_script_hash().as_bytes().pack())
         .build();
     let output = CellOutput::new_builder()
@@ -1020,7 +2227,7 @@ fn test_udt_transfer() {
     let owner = build_sighash_script(H160::default());
     let type_script = Script::new_builder()
         .code_hash(sudt_data_hash.pack())
-        .hash_type(ScriptHashType::Data1.into())
+        .hash_type(ScriptHashType::Data1.to_packed())
         .args(owner.calc_script_hash().as_bytes().pack())
         .build();
     let mut ctx = init_context(
@@ -1042,7 +2249,7 @@ fn test_udt_transfer() {
 
     let receiver_acp_lock = Script::new_builder()
         .code_hash(acp_data_hash.pack())
-        .hash_type(ScriptHashType::Data1.into())
+        .hash_type(ScriptHashType::Data1.to_packed())
         .args(Bytes::from(ACCOUNT2_ARG.0.to_vec()).pack())
         .build();
     let receiver_input = CellInput::new(random_out_point(), 0);
@@ -1059,6 +2266,7 @@ fn test_udt_transfer() {
         type_script,
         sender: sender.clone(),
         receivers: vec![udt_receiver],
+        data_validator: None,
     };
     let placeholder_witness = WitnessArgs::new_builder()
         .lock(Some(Bytes::from(vec![0u8; 65])).pack())
@@ -1111,9 +2319,551 @@ fn test_udt_transfer() {
     ctx.verify(tx, FEE_RATE).unwrap();
 }
 
+#[test]
+fn test_udt_transfer_deterministic_cell_deps() {
+    let acp_data_hash = H256::from(blake2b_256(ACP_BIN));
+    let sudt_data_hash = H256::from(blake2b_256(SUDT_BIN));
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let owner = build_sighash_script(H160::default());
+    let type_script = Script::new_builder()
+        .code_hash(sudt_data_hash.pack())
+        .hash_type(ScriptHashType::Data1.to_packed())
+        .args(owner.calc_script_hash().as_bytes().pack())
+        .build();
+    let mut ctx = init_context(
+        vec![(ACP_BIN, true), (SUDT_BIN, false)],
+        vec![(sender.clone(), Some(200 * ONE_CKB))],
+    );
+    let sender_output = CellOutput::new_builder()
+        .capacity((200 * ONE_CKB).pack())
+        .lock(sender.clone())
+        .type_(Some(type_script.clone()).pack())
+        .build();
+    let sender_data = Bytes::from(500u128.to_le_bytes().to_vec());
+    ctx.add_live_cell(
+        CellInput::new(random_out_point(), 0),
+        sender_output,
+        sender_data,
+        None,
+    );
+    let receiver_acp_lock = Script::new_builder()
+        .code_hash(acp_data_hash.pack())
+        .hash_type(ScriptHashType::Data1.to_packed())
+        .args(Bytes::from(ACCOUNT2_ARG.0.to_vec()).pack())
+        .build();
+    let receiver_output = CellOutput::new_builder()
+        .capacity((200 * ONE_CKB).pack())
+        .lock(receiver_acp_lock.clone())
+        .type_(Some(type_script.clone()).pack())
+        .build();
+    let receiver_data = Bytes::from(100u128.to_le_bytes().to_vec());
+    ctx.add_live_cell(
+        CellInput::new(random_out_point(), 0),
+        receiver_output,
+        receiver_data,
+        None,
+    );
+
+    let builder = UdtTransferBuilder {
+        type_script: type_script.clone(),
+        sender: sender.clone(),
+        receivers: vec![UdtTargetReceiver::new(
+            TransferAction::Update,
+            receiver_acp_lock.clone(),
+            300,
+        )],
+        data_validator: None,
+    };
+    let placeholder_witness = WitnessArgs::new_builder()
+        .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+        .build();
+    let balancer = CapacityBalancer::new_simple(sender.clone(), placeholder_witness, FEE_RATE);
+    let build_tx = || {
+        let mut cell_collector = ctx.to_live_cells_context();
+        let base_tx = builder
+            .build_base(&mut cell_collector, &ctx, &ctx, &ctx)
+            .unwrap();
+        balance_tx_capacity(&base_tx, &balancer, &mut cell_collector, &ctx, &ctx, &ctx).unwrap()
+    };
+
+    let tx1 = build_tx();
+    let tx2 = build_tx();
+    assert_eq!(tx1.hash(), tx2.hash());
+    // cell deps are in the order they are resolved
+    let expected_cell_deps = vec![
+        ctx.resolve(&sender).unwrap(),
+        ctx.resolve(&type_script).unwrap(),
+        ctx.resolve(&receiver_acp_lock).unwrap(),
+    ];
+    assert_eq!(
+        tx1.cell_deps().into_iter().collect::<Vec<_>>(),
+        expected_cell_deps
+    );
+}
+
+#[test]
+fn test_deterministic_context() {
+    fn build_tx(seed: u64) -> TransactionView {
+        let sudt_data_hash = H256::from(blake2b_256(SUDT_BIN));
+        let sender = build_sighash_script(ACCOUNT1_ARG);
+        let receiver = build_sighash_script(ACCOUNT2_ARG);
+        let owner = build_sighash_script(H160::default());
+        let type_script = Script::new_builder()
+            .code_hash(sudt_data_hash.pack())
+            .hash_type(ScriptHashType::Data1.to_packed())
+            .args(owner.calc_script_hash().as_bytes().pack())
+            .build();
+        // deployed contracts, live cells and the udt cell all use random out points
+        let mut ctx = init_deterministic_context(
+            seed,
+            vec![(ACP_BIN, true), (SUDT_BIN, false)],
+            vec![(sender.clone(), Some(200 * ONE_CKB))],
+        );
+        let sender_output = CellOutput::new_builder()
+            .capacity((200 * ONE_CKB).pack())
+            .lock(sender.clone())
+            .type_(Some(type_script.clone()).pack())
+            .build();
+        let sender_input = CellInput::new(ctx.random_out_point(), 0);
+        ctx.add_live_cell(
+            sender_input,
+            sender_output,
+            Bytes::from(500u128.to_le_bytes().to_vec()),
+            None,
+        );
+
+        let builder = UdtTransferBuilder {
+            type_script,
+            sender: sender.clone(),
+            receivers: vec![UdtTargetReceiver::new(
+                TransferAction::Create,
+                receiver,
+                100,
+            )],
+            data_validator: None,
+        };
+        let placeholder_witness = WitnessArgs::new_builder()
+            .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+            .build();
+        let balancer = CapacityBalancer::new_simple(sender, placeholder_witness, FEE_RATE);
+        let account1_key = secp256k1::SecretKey::from_slice(ACCOUNT1_KEY.as_bytes()).unwrap();
+        let signer = SecpCkbRawKeySigner::new_with_secret_keys(vec![account1_key]);
+        let script_unlocker = SecpSighashUnlocker::from(Box::new(signer) as Box<_>);
+        let mut unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>> = HashMap::default();
+        unlockers.insert(
+            ScriptId::new_type(SIGHASH_TYPE_HASH.clone()),
+            Box::new(script_unlocker),
+        );
+
+        let mut cell_collector = ctx.to_live_cells_context();
+        let (tx, locked_groups) = builder
+            .build_unlocked(&mut cell_collector, &ctx, &ctx, &ctx, &balancer, &unlockers)
+            .unwrap();
+        assert!(locked_groups.is_empty());
+        ctx.verify(tx.clone(), FEE_RATE).unwrap();
+        tx
+    }
+
+    let tx = build_tx(42);
+    assert_eq!(tx.data().as_slice(), build_tx(42).data().as_slice());
+    assert_ne!(tx.hash(), build_tx(43).hash());
+}
+
+#[test]
+fn test_udt_transfer_amount_overflow() {
+    let acp_data_hash = H256::from(blake2b_256(ACP_BIN));
+    let sudt_data_hash = H256::from(blake2b_256(SUDT_BIN));
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let owner = build_sighash_script(H160::default());
+    let type_script = Script::new_builder()
+        .code_hash(sudt_data_hash.pack())
+        .hash_type(ScriptHashType::Data1.to_packed())
+        .args(owner.calc_script_hash().as_bytes().pack())
+        .build();
+    let mut ctx = init_context(vec![(ACP_BIN, true), (SUDT_BIN, false)], Vec::new());
+    let sender_output = CellOutput::new_builder()
+        .capacity((200 * ONE_CKB).pack())
+        .lock(sender.clone())
+        .type_(Some(type_script.clone()).pack())
+        .build();
+    ctx.add_live_cell(
+        CellInput::new(random_out_point(), 0),
+        sender_output,
+        Bytes::from(u128::MAX.to_le_bytes().to_vec()),
+        None,
+    );
+    let receiver_acp_lock = Script::new_builder()
+        .code_hash(acp_data_hash.pack())
+        .hash_type(ScriptHashType::Data1.to_packed())
+        .args(Bytes::from(ACCOUNT2_ARG.0.to_vec()).pack())
+        .build();
+    let receiver_output = CellOutput::new_builder()
+        .capacity((200 * ONE_CKB).pack())
+        .lock(receiver_acp_lock.clone())
+        .type_(Some(type_script.clone()).pack())
+        .build();
+    ctx.add_live_cell(
+        CellInput::new(random_out_point(), 0),
+        receiver_output,
+        Bytes::from((u128::MAX - 10).to_le_bytes().to_vec()),
+        None,
+    );
+    let build = |receivers: Vec<UdtTargetReceiver>| {
+        let builder = UdtTransferBuilder {
+            type_script: type_script.clone(),
+            sender: sender.clone(),
+            receivers,
+            data_validator: None,
+        };
+        let mut cell_collector = ctx.to_live_cells_context();
+        builder.build_base(&mut cell_collector, &ctx, &ctx, &ctx)
+    };
+
+    // the receiver cell reaches exactly u128::MAX
+    let tx = build(vec![UdtTargetReceiver::new(
+        TransferAction::Update,
+        receiver_acp_lock.clone(),
+        10,
+    )])
+    .unwrap();
+    assert_eq!(
+        tx.outputs_data().get(1).unwrap().raw_data(),
+        Bytes::from(u128::MAX.to_le_bytes().to_vec())
+    );
+    let err = build(vec![UdtTargetReceiver::new(
+        TransferAction::Update,
+        receiver_acp_lock,
+        11,
+    )])
+    .err()
+    .unwrap();
+    assert!(matches!(
+        err,
+        TxBuilderError::AmountOverflow(amount, 11) if amount == u128::MAX - 10
+    ));
+
+    // the receivers total overflows
+    build(vec![
+        UdtTargetReceiver::new(TransferAction::Create, receiver.clone(), u128::MAX - 1),
+        UdtTargetReceiver::new(TransferAction::Create, receiver.clone(), 1),
+    ])
+    .unwrap();
+    let err = build(vec![
+        UdtTargetReceiver::new(TransferAction::Create, receiver.clone(), u128::MAX),
+        UdtTargetReceiver::new(TransferAction::Create, receiver, 1),
+    ])
+    .err()
+    .unwrap();
+    assert!(matches!(err, TxBuilderError::AmountOverflow(u128::MAX, 1)));
+}
+
+#[test]
+fn test_udt_transfer_data_validator() {
+    let sudt_data_hash = H256::from(blake2b_256(SUDT_BIN));
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let owner = build_sighash_script(H160::default());
+    let type_script = Script::new_builder()
+        .code_hash(sudt_data_hash.pack())
+        .hash_type(ScriptHashType::Data1.to_packed())
+        .args(owner.calc_script_hash().as_bytes().pack())
+        .build();
+    let build = |sender_data: Vec<u8>, extra_data: Option<Bytes>| {
+        let mut ctx = init_context(vec![(SUDT_BIN, false)], Vec::new());
+        let sender_output = CellOutput::new_builder()
+            .capacity((300 * ONE_CKB).pack())
+            .lock(sender.clone())
+            .type_(Some(type_script.clone()).pack())
+            .build();
+        let mut data = 500u128.to_le_bytes().to_vec();
+        data.extend(sender_data);
+        ctx.add_live_cell(
+            CellInput::new(random_out_point(), 0),
+            sender_output,
+            Bytes::from(data),
+            None,
+        );
+        let mut udt_receiver =
+            UdtTargetReceiver::new(TransferAction::Create, receiver.clone(), 100);
+        udt_receiver.extra_data = extra_data;
+        let builder = UdtTransferBuilder {
+            type_script: type_script.clone(),
+            sender: sender.clone(),
+            receivers: vec![udt_receiver],
+            data_validator: Some(Box::new(validate_xudt_data)),
+        };
+        let mut cell_collector = ctx.to_live_cells_context();
+        builder.build_base(&mut cell_collector, &ctx, &ctx, &ctx)
+    };
+    // lock: 0x, data: []
+    let xudt_data = vec![
+        20, 0, 0, 0, 12, 0, 0, 0, 16, 0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0,
+    ];
+
+    build(Vec::new(), None).unwrap();
+    let tx = build(xudt_data.clone(), Some(Bytes::from(xudt_data.clone()))).unwrap();
+    assert_eq!(
+        tx.outputs_data().get(0).unwrap().raw_data()[16..],
+        xudt_data[..]
+    );
+    // the extension data of the sender cell is broken
+    let err = build(vec![1, 2, 3], None).err().unwrap();
+    assert!(
+        matches!(err, TxBuilderError::InvalidOutputData(0, _)),
+        "{}",
+        err
+    );
+    let err = build(Vec::new(), Some(Bytes::from(xudt_data[0..19].to_vec())))
+        .err()
+        .unwrap();
+    assert!(
+        matches!(err, TxBuilderError::InvalidOutputData(1, _)),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_udt_self_transfer() {
+    let sudt_data_hash = H256::from(blake2b_256(SUDT_BIN));
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let owner = build_sighash_script(H160::default());
+    let type_script = Script::new_builder()
+        .code_hash(sudt_data_hash.pack())
+        .hash_type(ScriptHashType::Data1.to_packed())
+        .args(owner.calc_script_hash().as_bytes().pack())
+        .build();
+    let mut ctx = init_context(
+        vec![(SUDT_BIN, false)],
+        vec![(sender.clone(), Some(200 * ONE_CKB))],
+    );
+    let sender_output = CellOutput::new_builder()
+        .capacity((200 * ONE_CKB).pack())
+        .lock(sender.clone())
+        .type_(Some(type_script.clone()).pack())
+        .build();
+    let sender_input = CellInput::new(random_out_point(), 0);
+    ctx.add_live_cell(
+        sender_input.clone(),
+        sender_output.clone(),
+        Bytes::from(500u128.to_le_bytes().to_vec()),
+        None,
+    );
+
+    let builder = UdtTransferBuilder {
+        type_script: type_script.clone(),
+        sender: sender.clone(),
+        receivers: vec![UdtTargetReceiver::new(
+            TransferAction::Update,
+            sender.clone(),
+            100,
+        )],
+        data_validator: None,
+    };
+    let placeholder_witness = WitnessArgs::new_builder()
+        .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+        .build();
+    let balancer = CapacityBalancer::new_simple(sender.clone(), placeholder_witness, FEE_RATE);
+    let account1_key = secp256k1::SecretKey::from_slice(ACCOUNT1_KEY.as_bytes()).unwrap();
+    let signer = SecpCkbRawKeySigner::new_with_secret_keys(vec![account1_key]);
+    let script_unlocker = SecpSighashUnlocker::from(Box::new(signer) as Box<_>);
+    let mut unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>> = HashMap::default();
+    unlockers.insert(
+        ScriptId::new_type(SIGHASH_TYPE_HASH.clone()),
+        Box::new(script_unlocker),
+    );
+
+    // the only udt cell is the sender cell, the amount is merged into it
+    let mut cell_collector = ctx.to_live_cells_context();
+    let base_tx = builder
+        .build_base(&mut cell_collector, &ctx, &ctx, &ctx)
+        .unwrap();
+    assert_eq!(base_tx.inputs().len(), 1);
+    assert_eq!(base_tx.outputs().len(), 1);
+    assert_eq!(
+        base_tx.outputs_data().get(0).unwrap().raw_data(),
+        Bytes::from(500u128.to_le_bytes().to_vec())
+    );
+    let mut cell_collector = ctx.to_live_cells_context();
+    let (tx, locked_groups) = builder
+        .build_unlocked(&mut cell_collector, &ctx, &ctx, &ctx, &balancer, &unlockers)
+        .unwrap();
+    assert!(locked_groups.is_empty());
+    let udt_inputs = tx
+        .inputs()
+        .into_iter()
+        .filter(|input| input.previous_output() == sender_input.previous_output())
+        .count();
+    assert_eq!(udt_inputs, 1);
+    ctx.verify(tx, FEE_RATE).unwrap();
+
+    // another udt cell of the sender is picked when exists
+    let other_input = CellInput::new(random_out_point(), 0);
+    ctx.add_live_cell(
+        other_input.clone(),
+        sender_output,
+        Bytes::from(50u128.to_le_bytes().to_vec()),
+        None,
+    );
+    let mut cell_collector = ctx.to_live_cells_context();
+    let (tx, _) = builder
+        .build_unlocked(&mut cell_collector, &ctx, &ctx, &ctx, &balancer, &unlockers)
+        .unwrap();
+    let inputs = tx.inputs().into_iter().collect::<Vec<_>>();
+    assert_eq!(inputs[0..2], vec![sender_input, other_input]);
+    let outputs_data = tx
+        .outputs_data()
+        .into_iter()
+        .map(|d| d.raw_data())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        outputs_data[0..2],
+        vec![
+            Bytes::from(400u128.to_le_bytes().to_vec()),
+            Bytes::from(150u128.to_le_bytes().to_vec()),
+        ]
+    );
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_xudt_transfer_witness_shared_by_lock_and_type() {
+    // always_success stands in for the xUDT script, only the witness layout matters here
+    let xudt_data_hash = H256::from(blake2b_256(ALWAYS_SUCCESS_BIN));
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let owner = build_sighash_script(H160::default());
+    let type_script = UdtType::new_xudt(Bytes::from(vec![0u8; 4])).build_script(
+        &ScriptId::new_data1(xudt_data_hash),
+        &owner.calc_script_hash(),
+    );
+    let extension_data = Bytes::from(vec![0x5au8; 36]);
+
+    // input_type placed before and after the lock placeholder
+    for input_type_first in [true, false] {
+        let mut ctx = init_context(
+            vec![(ALWAYS_SUCCESS_BIN, false)],
+            vec![
+                (sender.clone(), Some(100 * ONE_CKB)),
+                (sender.clone(), Some(200 * ONE_CKB)),
+            ],
+        );
+        let sender_output = CellOutput::new_builder()
+            .capacity((200 * ONE_CKB).pack())
+            .lock(sender.clone())
+            .type_(Some(type_script.clone()).pack())
+            .build();
+        let sender_data = Bytes::from(500u128.to_le_bytes().to_vec());
+        ctx.add_live_cell(
+            CellInput::new(random_out_point(), 0),
+            sender_output,
+            sender_data,
+            None,
+        );
+
+        let udt_receiver = UdtTargetReceiver::new(TransferAction::Create, receiver.clone(), 300);
+        let builder = UdtTransferBuilder {
+            type_script: type_script.clone(),
+            sender: sender.clone(),
+            receivers: vec![udt_receiver],
+            data_validator: None,
+        };
+        let placeholder_witness = WitnessArgs::new_builder()
+            .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+            .build();
+        let balancer = CapacityBalancer::new_simple(sender.clone(), placeholder_witness, FEE_RATE);
+
+        let account1_key = secp256k1::SecretKey::from_slice(ACCOUNT1_KEY.as_bytes()).unwrap();
+        let signer = SecpCkbRawKeySigner::new_with_secret_keys(vec![account1_key]);
+        let script_unlocker = SecpSighashUnlocker::from(Box::new(signer) as Box<_>);
+        let mut unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>> = HashMap::default();
+        unlockers.insert(
+            ScriptId::new_type(SIGHASH_TYPE_HASH.clone()),
+            Box::new(script_unlocker),
+        );
+
+        let mut cell_collector = ctx.to_live_cells_context();
+        let mut tx = builder
+            .build_base(&mut cell_collector, &ctx, &ctx, &ctx)
+            .unwrap();
+        if input_type_first {
+            tx = update_witness_field(&tx, 0, WitnessField::InputType, extension_data.clone())
+                .unwrap();
+        }
+        let (mut tx, _) = fill_placeholder_witnesses(tx, &ctx, &unlockers).unwrap();
+        if !input_type_first {
+            tx = update_witness_field(&tx, 0, WitnessField::InputType, extension_data.clone())
+                .unwrap();
+        }
+        let tx =
+            balance_tx_capacity(&tx, &balancer, &mut cell_collector, &ctx, &ctx, &ctx).unwrap();
+        let (tx, locked_groups) = unlock_tx(tx, &ctx, &unlockers).unwrap();
+        assert!(locked_groups.is_empty());
+
+        let witness = WitnessArgs::from_slice(&tx.witnesses().get(0).unwrap().raw_data()).unwrap();
+        assert_eq!(
+            witness.input_type().to_opt().unwrap().raw_data(),
+            extension_data
+        );
+        let signature = witness.lock().to_opt().unwrap().raw_data();
+        assert_eq!(signature.len(), 65);
+        assert_ne!(signature, Bytes::from(vec![0u8; 65]));
+        ctx.verify(tx, FEE_RATE).unwrap();
+    }
+}
+
+pub mod balancer;
+pub mod batch;
+pub mod burn;
+pub mod capacity_transfer;
+pub mod cell_dep;
+pub mod cell_lock;
+pub mod chain_params;
+pub mod chained;
+pub mod cheque;
 pub mod ckb_indexer_rpc;
 pub mod ckb_rpc;
 pub mod cycle;
+#[cfg(feature = "devnet")]
+pub mod devnet;
+pub mod explain;
+pub mod footprint;
+pub mod hashlock;
+pub mod input_header_deps;
+pub mod lint;
+pub mod mock_traits;
+pub mod mock_tx;
+pub mod name_cell;
 pub mod omni_lock;
+#[cfg(feature = "rsa")]
+pub mod omni_lock_rsa;
 pub mod omni_lock_util;
+pub mod omni_lock_witness;
+#[cfg(feature = "parallel")]
+pub mod parallel;
+pub mod payment_uri;
+pub mod pw_lock;
+#[cfg(feature = "rce")]
+pub mod rce;
+#[cfg(feature = "secp256r1")]
+pub mod secp256r1;
+pub mod sighash_message;
+pub mod sighash_signer;
+pub mod signing_package;
+pub mod singleton;
+pub mod sponsor;
+pub mod static_witness;
+pub mod summary;
+pub mod template;
+pub mod tip;
 pub mod transaction;
+pub mod tx_checker;
+pub mod tx_context;
+pub mod type_id;
+pub mod udt_multisig;
+pub mod udt_owner;
+pub mod udt_plan;
+pub mod udt_smart;
+pub mod udt_supply;
+pub mod unconfirmed;
@@ -0,0 +1,161 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{
+    constants::ONE_CKB,
+    test_util::{random_out_point, LiveCellsContext},
+    tests::{build_sighash_script, init_context, ACCOUNT1_ARG, ACCOUNT2_ARG},
+    traits::{
+        BeneficiaryResolver, CellCollector, CellCollectorError, CellQueryOptions, LiveCell,
+        NameArgsDerivation, NameCellBeneficiaryResolver, ResolveError,
+    },
+    ScriptId,
+};
+use ckb_types::{
+    bytes::Bytes,
+    h256,
+    packed::{CellInput, CellOutput, OutPoint, Script, Transaction},
+    prelude::*,
+};
+use parking_lot::Mutex;
+
+const NAME_CODE_HASH: ckb_types::H256 =
+    h256!("0x00000000000000000000000000000000000000000000000000545950455f4944");
+
+// A cell collector sharing its live cells with the test, so the test can
+// change the name cells after the resolver is created.
+#[derive(Clone)]
+struct SharedCollector(Arc<Mutex<LiveCellsContext>>);
+
+impl CellCollector for SharedCollector {
+    fn collect_live_cells(
+        &mut self,
+        query: &CellQueryOptions,
+        apply_changes: bool,
+    ) -> Result<(Vec<LiveCell>, u64), CellCollectorError> {
+        self.0.lock().collect_live_cells(query, apply_changes)
+    }
+    fn lock_cell(
+        &mut self,
+        out_point: OutPoint,
+        tip_block_number: u64,
+    ) -> Result<(), CellCollectorError> {
+        self.0.lock().lock_cell(out_point, tip_block_number)
+    }
+    fn apply_tx(
+        &mut self,
+        tx: Transaction,
+        tip_block_number: u64,
+    ) -> Result<(), CellCollectorError> {
+        self.0.lock().apply_tx(tx, tip_block_number)
+    }
+    fn reset(&mut self) {
+        self.0.lock().reset()
+    }
+}
+
+fn add_name_cell(
+    cells: &Mutex<LiveCellsContext>,
+    resolver: &NameCellBeneficiaryResolver,
+    name: &str,
+    data: Bytes,
+) -> OutPoint {
+    let out_point = random_out_point();
+    let output = CellOutput::new_builder()
+        .capacity((200 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT1_ARG))
+        .type_(Some(resolver.name_type_script(name)).pack())
+        .build();
+    cells.lock().inputs.push(ckb_mock_tx_types::MockInput {
+        input: CellInput::new(out_point.clone(), 0),
+        output,
+        data,
+        header: None,
+    });
+    out_point
+}
+
+fn remove_cell(cells: &Mutex<LiveCellsContext>, out_point: &OutPoint) {
+    cells
+        .lock()
+        .inputs
+        .retain(|input| &input.input.previous_output() != out_point);
+}
+
+fn build_resolver() -> (Arc<Mutex<LiveCellsContext>>, NameCellBeneficiaryResolver) {
+    let ctx = init_context(Vec::new(), Vec::new());
+    let cells = Arc::new(Mutex::new(ctx.to_live_cells_context()));
+    let resolver = NameCellBeneficiaryResolver::new(
+        Box::new(SharedCollector(Arc::clone(&cells))),
+        ScriptId::new_type(NAME_CODE_HASH),
+        NameArgsDerivation::Blake2b256,
+    );
+    (cells, resolver)
+}
+
+#[test]
+fn test_resolve_name_cell() {
+    let (cells, resolver) = build_resolver();
+    let alice = build_sighash_script(ACCOUNT2_ARG);
+    add_name_cell(&cells, &resolver, "alice.ckb", alice.as_bytes());
+    add_name_cell(&cells, &resolver, "bob.ckb", Bytes::from(vec![1u8; 10]));
+
+    assert_eq!(resolver.resolve("alice.ckb").unwrap(), alice);
+    resolver.verify("alice.ckb", &alice).unwrap();
+    assert!(matches!(
+        resolver.resolve("carol.ckb"),
+        Err(ResolveError::NotFound(name)) if name == "carol.ckb"
+    ));
+    assert!(matches!(
+        resolver.resolve("bob.ckb"),
+        Err(ResolveError::InvalidNameCell(name, _)) if name == "bob.ckb"
+    ));
+}
+
+#[test]
+fn test_resolve_name_cell_cache_and_strict() {
+    let (cells, mut resolver) = build_resolver();
+    let alice = build_sighash_script(ACCOUNT2_ARG);
+    let new_owner: Script = build_sighash_script(ACCOUNT1_ARG);
+    let name_cell = add_name_cell(&cells, &resolver, "alice.ckb", alice.as_bytes());
+    assert_eq!(resolver.resolve("alice.ckb").unwrap(), alice);
+
+    // the name is transferred, the cached lock is still returned
+    remove_cell(&cells, &name_cell);
+    let name_cell = add_name_cell(&cells, &resolver, "alice.ckb", new_owner.as_bytes());
+    assert_eq!(resolver.resolve("alice.ckb").unwrap(), alice);
+    // but paying the stale owner is caught before broadcast
+    assert!(matches!(
+        resolver.verify("alice.ckb", &alice),
+        Err(ResolveError::Stale(_))
+    ));
+    resolver.set_ttl(Duration::ZERO);
+    assert_eq!(resolver.resolve("alice.ckb").unwrap(), new_owner);
+
+    // the name cell is re-created with the same lock
+    remove_cell(&cells, &name_cell);
+    let name_cell = add_name_cell(&cells, &resolver, "alice.ckb", new_owner.as_bytes());
+    resolver.set_strict(false);
+    resolver.verify("alice.ckb", &new_owner).unwrap();
+    remove_cell(&cells, &name_cell);
+    let name_cell = add_name_cell(&cells, &resolver, "alice.ckb", new_owner.as_bytes());
+    resolver.set_strict(true);
+    assert!(matches!(
+        resolver.verify("alice.ckb", &new_owner),
+        Err(ResolveError::Stale(_))
+    ));
+    // verify does not record the re-pointed name cell
+    assert!(matches!(
+        resolver.verify("alice.ckb", &new_owner),
+        Err(ResolveError::Stale(_))
+    ));
+    // until the name is resolved again
+    assert_eq!(resolver.resolve("alice.ckb").unwrap(), new_owner);
+    resolver.verify("alice.ckb", &new_owner).unwrap();
+
+    remove_cell(&cells, &name_cell);
+    assert!(matches!(
+        resolver.verify("alice.ckb", &new_owner),
+        Err(ResolveError::NotFound(_))
+    ));
+}
@@ -4,35 +4,37 @@ use crate::{
     constants::{ONE_CKB, SIGHASH_TYPE_HASH},
     test_util::random_out_point,
     tests::{
-        build_sighash_script, init_context, omni_lock_util::generate_rc, ACCOUNT0_ARG,
-        ACCOUNT0_KEY, ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG, ACCOUNT2_KEY, ACCOUNT3_ARG,
-        ACCOUNT3_KEY, ALWAYS_SUCCESS_BIN, FEE_RATE, SUDT_BIN,
+        build_sighash_script, init_context,
+        omni_lock_util::{generate_rc, generate_rc_with_whitelist},
+        ACCOUNT0_ARG, ACCOUNT0_KEY, ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG, ACCOUNT2_KEY,
+        ACCOUNT3_ARG, ACCOUNT3_KEY, ALWAYS_SUCCESS_BIN, FEE_RATE, SUDT_BIN,
     },
-    traits::{CellDepResolver, SecpCkbRawKeySigner},
+    traits::{CellDepResolver, SecpCkbRawKeySigner, SignerError},
     tx_builder::{
         acp::{AcpTransferBuilder, AcpTransferReceiver},
         balance_tx_capacity, fill_placeholder_witnesses,
         omni_lock::OmniLockTransferBuilder,
         udt::{UdtTargetReceiver, UdtTransferBuilder},
-        CapacityProvider, TransferAction,
+        CapacityProvider, TrailingWitnesses, TransferAction,
     },
     types::xudt_rce_mol::SmtProofEntryVec,
     unlock::{
         omni_lock::{AdminConfig, Identity},
-        IdentityFlag, InfoCellData, MultisigConfig, OmniLockAcpConfig, OmniLockConfig,
-        OmniLockScriptSigner, OmniLockUnlocker, OmniUnlockMode, ScriptUnlocker,
-        SecpSighashUnlocker,
+        AuthWitnessProvider, ExecDlConfig, IdentityFlag, InfoCellData, MultisigConfig,
+        OmniLockAcpConfig, OmniLockConfig, OmniLockScriptSigner, OmniLockUnlocker, OmniUnlockMode,
+        ScriptUnlocker, SecpSighashUnlocker,
     },
     util::{blake160, keccak160},
     ScriptId, Since,
 };
 
 use crate::tx_builder::{unlock_tx, CapacityBalancer, TxBuilder};
+use crate::types::{omni_lock::OmniLockWitnessLock, ScriptHashTypeExt};
 use ckb_crypto::secp::{Pubkey, SECP256K1};
 use ckb_hash::blake2b_256;
 use ckb_types::{
     bytes::Bytes,
-    core::{FeeRate, ScriptHashType},
+    core::{FeeRate, ScriptHashType, TransactionView},
     packed::{Byte32, CellInput, CellOutput, Script, WitnessArgs},
     prelude::*,
     H160, H256,
@@ -40,12 +42,15 @@ use ckb_types::{
 use rand::Rng;
 
 const OMNILOCK_BIN: &[u8] = include_bytes!("../test-data/omni_lock");
+/// The max cycles of a simple omni-lock transfer, to catch an unexpected
+/// growth of the witness or the signing message
+const OMNILOCK_CYCLES_BUDGET: u64 = 10_000_000;
 
 fn build_omnilock_script(cfg: &OmniLockConfig) -> Script {
     let omnilock_data_hash = H256::from(blake2b_256(OMNILOCK_BIN));
     Script::new_builder()
         .code_hash(omnilock_data_hash.pack())
-        .hash_type(ScriptHashType::Data1.into())
+        .hash_type(ScriptHashType::Data1.to_packed())
         .args(cfg.build_args().pack())
         .build()
 }
@@ -117,15 +122,11 @@ fn test_omnilock_simple_hash(cfg: OmniLockConfig) {
 
     let mut cell_collector = ctx.to_live_cells_context();
     let account2_key = secp256k1::SecretKey::from_slice(ACCOUNT0_KEY.as_bytes()).unwrap();
-    let unlockers = build_omnilock_unlockers(account2_key, cfg.clone(), unlock_mode);
-    let mut tx = builder
-        .build_balanced(&mut cell_collector, &ctx, &ctx, &ctx, &balancer, &unlockers)
-        .unwrap();
-
     let unlockers = build_omnilock_unlockers(account2_key, cfg, unlock_mode);
-    let (new_tx, new_locked_groups) = unlock_tx(tx.clone(), &ctx, &unlockers).unwrap();
-    assert!(new_locked_groups.is_empty());
-    tx = new_tx;
+    let (tx, locked_groups) = builder
+        .build_unlocked(&mut cell_collector, &ctx, &ctx, &ctx, &balancer, &unlockers)
+        .unwrap();
+    assert!(locked_groups.is_empty());
 
     assert_eq!(tx.header_deps().len(), 0);
     assert_eq!(tx.cell_deps().len(), 1);
@@ -144,7 +145,14 @@ fn test_omnilock_simple_hash(cfg: OmniLockConfig) {
     assert_eq!(witnesses.len(), 2);
     assert_eq!(witnesses[0].len(), placeholder_witness.as_slice().len());
     assert_eq!(witnesses[1].len(), 0);
-    ctx.verify(tx, FEE_RATE).unwrap();
+    let result = ctx.verify(tx, FEE_RATE).unwrap();
+    assert_eq!(result.group_cycles.len(), 1);
+    assert_eq!(result.group_cycles[0].1, sender.calc_script_hash());
+    assert!(
+        result.total_cycles <= OMNILOCK_CYCLES_BUDGET,
+        "cycles: {}",
+        result.total_cycles
+    );
 }
 
 #[test]
@@ -206,7 +214,7 @@ fn test_omnilock_simple_hash_rc_input(mut cfg: OmniLockConfig) {
 
     let (proof_vec, rc_type_id, rce_cells) = generate_rc(
         &mut ctx,
-        admin_config.get_auth().to_smt_key().into(),
+        admin_config.get_auth(),
         admin_config.rce_in_input(),
         ACCOUNT3_ARG,
     );
@@ -381,7 +389,305 @@ fn test_omnilock_transfer_from_ethereum_wl_admin() {
     test_omnilock_simple_hash_rc(cfg, OmniUnlockMode::Admin);
 }
 
-fn test_omnilock_simple_hash_rc(mut cfg: OmniLockConfig, unlock_mode: OmniUnlockMode) {
+#[test]
+fn test_omnilock_admin_whitelist_sizes() {
+    let sender_key = secp256k1::SecretKey::from_slice(ACCOUNT0_KEY.as_bytes()).unwrap();
+    let pubkey = secp256k1::PublicKey::from_secret_key(&SECP256K1, &sender_key);
+    let mut cfg = OmniLockConfig::new_pubkey_hash(blake160(&pubkey.serialize()));
+    let account3_key = secp256k1::SecretKey::from_slice(ACCOUNT3_KEY.as_bytes()).unwrap();
+    let pubkey = secp256k1::PublicKey::from_secret_key(&SECP256K1, &account3_key);
+    let id = Identity::new_pubkey_hash(blake160(&pubkey.serialize()));
+    cfg.set_admin_config(AdminConfig::new(
+        H256::default(),
+        SmtProofEntryVec::default(),
+        id,
+        None,
+        false,
+    ));
+
+    for whitelist_size in [1u32, 100, 10_000] {
+        let other_identities: Vec<Identity> = (1..whitelist_size)
+            .map(|idx| {
+                let hash = blake2b_256(idx.to_le_bytes());
+                Identity::new_pubkey_hash(H160::from_slice(&hash[0..20]).unwrap())
+            })
+            .collect();
+        // the placeholder witness must have the same length as the signed one
+        test_omnilock_simple_hash_rc_whitelist(
+            cfg.clone(),
+            OmniUnlockMode::Admin,
+            &other_identities,
+        );
+    }
+}
+
+#[test]
+fn test_omnilock_admin_proofs_for_args() {
+    let unlock_mode = OmniUnlockMode::Admin;
+    let account3_key = secp256k1::SecretKey::from_slice(ACCOUNT3_KEY.as_bytes()).unwrap();
+    let pubkey = secp256k1::PublicKey::from_secret_key(&SECP256K1, &account3_key);
+    let admin_id = Identity::new_pubkey_hash(blake160(&pubkey.serialize()));
+
+    let mut ctx = init_context(vec![(OMNILOCK_BIN, true)], vec![]);
+    let (proof_vec, rc_type_id, rce_cells) = generate_rc(&mut ctx, &admin_id, false, ACCOUNT3_ARG);
+    // the global proofs can not unlock any script group
+    let mut admin_config = AdminConfig::new(
+        H256::from_slice(rc_type_id.as_ref()).unwrap(),
+        SmtProofEntryVec::default(),
+        admin_id,
+        None,
+        false,
+    );
+
+    // two users' cells under the same administrator
+    let mut senders = Vec::new();
+    for key in [ACCOUNT0_KEY, ACCOUNT1_KEY] {
+        let key = secp256k1::SecretKey::from_slice(key.as_bytes()).unwrap();
+        let pubkey = secp256k1::PublicKey::from_secret_key(&SECP256K1, &key);
+        let mut cfg = OmniLockConfig::new_pubkey_hash(blake160(&pubkey.serialize()));
+        cfg.set_admin_config(admin_config.clone());
+        senders.push(build_omnilock_script(&cfg));
+    }
+    for sender in &senders {
+        admin_config.set_proofs_for_args(sender.args().raw_data(), proof_vec.clone());
+    }
+    assert_eq!(
+        admin_config.proofs_for_args(&senders[1].args().raw_data()),
+        &proof_vec
+    );
+    assert_eq!(
+        admin_config.proofs_for_args(&[0u8; 54]),
+        &SmtProofEntryVec::default()
+    );
+    let mut cfg = OmniLockConfig::new_pubkey_hash(H160::default());
+    cfg.set_admin_config(admin_config);
+
+    ctx.add_simple_live_cell(random_out_point(), senders[0].clone(), Some(200 * ONE_CKB));
+    let other_input = random_out_point();
+    ctx.add_simple_live_cell(other_input.clone(), senders[1].clone(), Some(100 * ONE_CKB));
+
+    let output = CellOutput::new_builder()
+        .capacity((150 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT2_ARG))
+        .build();
+    let builder = OmniLockTransferBuilder::new(
+        vec![(output.clone(), Bytes::default())],
+        cfg.clone(),
+        Some(rce_cells),
+    );
+    let placeholder_lock = cfg
+        .placeholder_witness_lock_for_args(unlock_mode, &senders[0].args().raw_data())
+        .unwrap();
+    assert_ne!(
+        placeholder_lock,
+        cfg.placeholder_witness_lock(unlock_mode).unwrap()
+    );
+    let placeholder_witness = WitnessArgs::new_builder()
+        .lock(Some(placeholder_lock).pack())
+        .build();
+    let balancer = CapacityBalancer::new_simple(senders[0].clone(), placeholder_witness, FEE_RATE);
+
+    let mut cell_collector = ctx.to_live_cells_context();
+    let unlockers = build_omnilock_unlockers(account3_key, cfg, unlock_mode);
+    let base_tx = builder
+        .build_base(&mut cell_collector, &ctx, &ctx, &ctx)
+        .unwrap();
+    let base_tx = base_tx
+        .as_advanced_builder()
+        .input(CellInput::new(other_input, 0))
+        .witness(Bytes::default().pack())
+        .build();
+    let (tx_filled_witnesses, _) = fill_placeholder_witnesses(base_tx, &ctx, &unlockers).unwrap();
+    let tx = balance_tx_capacity(
+        &tx_filled_witnesses,
+        &balancer,
+        &mut cell_collector,
+        &ctx,
+        &ctx,
+        &ctx,
+    )
+    .unwrap();
+    let (tx, locked_groups) = unlock_tx(tx, &ctx, &unlockers).unwrap();
+    assert!(locked_groups.is_empty());
+    assert_eq!(tx.inputs().len(), 2);
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+// Sign a transfer from `cfg` with `keys` one by one, the signed witness must
+// have the same size as the placeholder witness used for balancing.
+fn check_placeholder_witness_size(
+    mut cfg: OmniLockConfig,
+    unlock_mode: OmniUnlockMode,
+    keys: &[H256],
+) {
+    let mut ctx = init_context(vec![(OMNILOCK_BIN, true)], vec![]);
+    let rce_cells = if unlock_mode == OmniUnlockMode::Admin {
+        let mut admin_config = cfg.get_admin_config().unwrap().clone();
+        let (proof_vec, rc_type_id, rce_cells) =
+            generate_rc(&mut ctx, admin_config.get_auth(), false, ACCOUNT0_ARG);
+        admin_config.set_proofs(proof_vec);
+        admin_config.set_rc_type_id(H256::from_slice(rc_type_id.as_ref()).unwrap());
+        cfg.set_admin_config(admin_config);
+        Some(rce_cells)
+    } else {
+        None
+    };
+    let sender = build_omnilock_script(&cfg);
+    for capacity in [100 * ONE_CKB, 200 * ONE_CKB] {
+        ctx.add_simple_live_cell(random_out_point(), sender.clone(), Some(capacity));
+    }
+
+    let output = CellOutput::new_builder()
+        .capacity((120 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT2_ARG))
+        .build();
+    let builder =
+        OmniLockTransferBuilder::new(vec![(output, Bytes::default())], cfg.clone(), rce_cells);
+    let placeholder_witness = cfg.placeholder_witness(unlock_mode).unwrap();
+    let balancer =
+        CapacityBalancer::new_simple(sender.clone(), placeholder_witness.clone(), FEE_RATE);
+
+    let is_ethereum = match unlock_mode {
+        OmniUnlockMode::Admin => cfg.get_admin_config().unwrap().get_auth().flag(),
+        OmniUnlockMode::Normal => cfg.id().flag(),
+    } == IdentityFlag::Ethereum;
+    let build_unlockers = |key: &H256| {
+        let key = secp256k1::SecretKey::from_slice(key.as_bytes()).unwrap();
+        let signer = if is_ethereum {
+            SecpCkbRawKeySigner::new_with_ethereum_secret_keys(vec![key])
+        } else {
+            SecpCkbRawKeySigner::new_with_secret_keys(vec![key])
+        };
+        let omnilock_script_signer =
+            OmniLockScriptSigner::new(Box::new(signer) as Box<_>, cfg.clone(), unlock_mode);
+        let omnilock_unlocker = OmniLockUnlocker::new(omnilock_script_signer, cfg.clone());
+        let mut unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>> = HashMap::default();
+        unlockers.insert(ScriptId::from(&sender), Box::new(omnilock_unlocker));
+        unlockers
+    };
+
+    let mut cell_collector = ctx.to_live_cells_context();
+    let base_tx = builder
+        .build_base(&mut cell_collector, &ctx, &ctx, &ctx)
+        .unwrap();
+    let (tx_filled_witnesses, _) =
+        fill_placeholder_witnesses(base_tx, &ctx, &build_unlockers(&keys[0])).unwrap();
+    let mut tx = balance_tx_capacity(
+        &tx_filled_witnesses,
+        &balancer,
+        &mut cell_collector,
+        &ctx,
+        &ctx,
+        &ctx,
+    )
+    .unwrap();
+    for key in keys {
+        let (new_tx, locked_groups) = unlock_tx(tx, &ctx, &build_unlockers(key)).unwrap();
+        assert!(locked_groups.is_empty());
+        tx = new_tx;
+    }
+    let signed_witness = tx.witnesses().get(0).unwrap().raw_data();
+    assert_eq!(signed_witness.len(), placeholder_witness.as_slice().len());
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_omnilock_placeholder_witness_sizes() {
+    let pubkey_hash = |key: &H256| {
+        let key = secp256k1::SecretKey::from_slice(key.as_bytes()).unwrap();
+        blake160(&secp256k1::PublicKey::from_secret_key(&SECP256K1, &key).serialize())
+    };
+    let user_multisig = MultisigConfig::new_with(
+        vec![
+            ACCOUNT0_ARG.clone(),
+            ACCOUNT1_ARG.clone(),
+            ACCOUNT2_ARG.clone(),
+        ],
+        0,
+        2,
+    )
+    .unwrap();
+    let admin_multisig = MultisigConfig::new_with(
+        vec![
+            ACCOUNT3_ARG.clone(),
+            ACCOUNT1_ARG.clone(),
+            ACCOUNT2_ARG.clone(),
+        ],
+        0,
+        2,
+    )
+    .unwrap();
+    let admin_pubkey_hash = Identity::new_pubkey_hash(pubkey_hash(&ACCOUNT3_KEY));
+    let with_admin = |mut cfg: OmniLockConfig, auth: Identity, multisig: Option<MultisigConfig>| {
+        cfg.set_admin_config(AdminConfig::new(
+            H256::default(),
+            SmtProofEntryVec::default(),
+            auth,
+            multisig,
+            false,
+        ));
+        cfg
+    };
+
+    // normal mode with the acp flag
+    let mut cfg = OmniLockConfig::new_pubkey_hash(pubkey_hash(&ACCOUNT0_KEY));
+    cfg.set_acp_config(OmniLockAcpConfig::new(0, 0));
+    check_placeholder_witness_size(cfg, OmniUnlockMode::Normal, &[ACCOUNT0_KEY]);
+    let mut cfg = OmniLockConfig::new_multisig(user_multisig.clone());
+    cfg.set_acp_config(OmniLockAcpConfig::new(0, 0));
+    check_placeholder_witness_size(cfg, OmniUnlockMode::Normal, &[ACCOUNT0_KEY, ACCOUNT2_KEY]);
+
+    // the admin auth differs from the identity in the args
+    let cfg = with_admin(
+        OmniLockConfig::new_pubkey_hash(pubkey_hash(&ACCOUNT0_KEY)),
+        Identity::new_multisig(admin_multisig.clone()),
+        Some(admin_multisig.clone()),
+    );
+    check_placeholder_witness_size(cfg, OmniUnlockMode::Admin, &[ACCOUNT3_KEY, ACCOUNT2_KEY]);
+    let cfg = with_admin(
+        OmniLockConfig::new_multisig(user_multisig.clone()),
+        admin_pubkey_hash.clone(),
+        None,
+    );
+    check_placeholder_witness_size(cfg, OmniUnlockMode::Admin, &[ACCOUNT3_KEY]);
+    let account3_key = secp256k1::SecretKey::from_slice(ACCOUNT3_KEY.as_bytes()).unwrap();
+    let account3_pubkey = secp256k1::PublicKey::from_secret_key(&SECP256K1, &account3_key);
+    let cfg = with_admin(
+        OmniLockConfig::new_multisig(user_multisig),
+        Identity::new_ethereum(keccak160(Pubkey::from(account3_pubkey).as_ref())),
+        None,
+    );
+    check_placeholder_witness_size(cfg, OmniUnlockMode::Admin, &[ACCOUNT3_KEY]);
+
+    // the acp flag does not change the witness size
+    let mut cfg = with_admin(
+        OmniLockConfig::new_pubkey_hash(pubkey_hash(&ACCOUNT0_KEY)),
+        admin_pubkey_hash,
+        None,
+    );
+    let placeholder_witness = cfg.placeholder_witness(OmniUnlockMode::Admin).unwrap();
+    cfg.set_acp_config(OmniLockAcpConfig::new(0, 0));
+    assert_eq!(
+        cfg.placeholder_witness(OmniUnlockMode::Admin).unwrap(),
+        placeholder_witness
+    );
+    check_placeholder_witness_size(cfg, OmniUnlockMode::Admin, &[ACCOUNT3_KEY]);
+
+    // other identity flags are errors instead of panics
+    let cfg = OmniLockConfig::new(IdentityFlag::Bitcoin, H160::default());
+    assert!(cfg.placeholder_witness(OmniUnlockMode::Normal).is_err());
+}
+
+fn test_omnilock_simple_hash_rc(cfg: OmniLockConfig, unlock_mode: OmniUnlockMode) {
+    test_omnilock_simple_hash_rc_whitelist(cfg, unlock_mode, &[]);
+}
+
+// The admin is in a white list with `other_identities`
+fn test_omnilock_simple_hash_rc_whitelist(
+    mut cfg: OmniLockConfig,
+    unlock_mode: OmniUnlockMode,
+    other_identities: &[Identity],
+) {
     let receiver = build_sighash_script(ACCOUNT2_ARG);
 
     let mut ctx = init_context(vec![(OMNILOCK_BIN, true)], vec![]);
@@ -392,9 +698,10 @@ fn test_omnilock_simple_hash_rc(mut cfg: OmniLockConfig, unlock_mode: OmniUnlock
                 OmniUnlockMode::Admin => ACCOUNT3_ARG,
                 OmniUnlockMode::Normal => ACCOUNT0_ARG,
             };
-            let (proof_vec, rc_type_id, rce_cells) = generate_rc(
+            let (proof_vec, rc_type_id, rce_cells) = generate_rc_with_whitelist(
                 &mut ctx,
-                admin_config.get_auth().to_smt_key().into(),
+                admin_config.get_auth(),
+                other_identities,
                 false,
                 rc_args,
             );
@@ -421,6 +728,14 @@ fn test_omnilock_simple_hash_rc(mut cfg: OmniLockConfig, unlock_mode: OmniUnlock
         rce_cells,
     );
     let placeholder_witness = cfg.placeholder_witness(unlock_mode).unwrap();
+    if unlock_mode == OmniUnlockMode::Admin {
+        // the zero lock is sized by the proof sizes
+        let placeholder_lock = placeholder_witness.lock().to_opt().unwrap();
+        assert_eq!(
+            cfg.zero_lock(unlock_mode).unwrap().len(),
+            placeholder_lock.raw_data().len()
+        );
+    }
     let balancer =
         CapacityBalancer::new_simple(sender.clone(), placeholder_witness.clone(), FEE_RATE);
 
@@ -501,12 +816,8 @@ fn test_omnilock_simple_hash_rc2(mut cfg: OmniLockConfig) {
     let mut ctx = init_context(vec![(OMNILOCK_BIN, true)], vec![]);
     let alternative_auth =
         build_alternative_auth(ACCOUNT1_KEY.as_bytes(), IdentityFlag::PubkeyHash);
-    let (proof_vec, rc_type_id, rce_cells) = generate_rc(
-        &mut ctx,
-        alternative_auth.to_smt_key().into(),
-        false,
-        ACCOUNT1_ARG,
-    );
+    let (proof_vec, rc_type_id, rce_cells) =
+        generate_rc(&mut ctx, &alternative_auth, false, ACCOUNT1_ARG);
     let admin_config = AdminConfig::new(
         H256::from_slice(rc_type_id.as_ref()).unwrap(),
         proof_vec,
@@ -676,8 +987,7 @@ fn test_omnilock_transfer_from_multisig_wl_commnon(unlock_mode: OmniUnlockMode)
     let multi_cfg = MultisigConfig::new_with(lock_args, 0, 2).unwrap();
     let admin_id = Identity::new_multisig(multi_cfg.clone());
     let mut ctx = init_context(vec![(OMNILOCK_BIN, true)], vec![]);
-    let (proof_vec, rc_type_id, rce_cells) =
-        generate_rc(&mut ctx, admin_id.to_smt_key().into(), false, ACCOUNT0_ARG);
+    let (proof_vec, rc_type_id, rce_cells) = generate_rc(&mut ctx, &admin_id, false, ACCOUNT0_ARG);
     cfg.set_admin_config(AdminConfig::new(
         H256::from_slice(rc_type_id.as_ref()).unwrap(),
         proof_vec,
@@ -764,6 +1074,143 @@ fn test_omnilock_transfer_from_multisig_wl_commnon(unlock_mode: OmniUnlockMode)
     ctx.verify(tx, FEE_RATE).unwrap();
 }
 
+/// The auth of an always success auth script, any signature is accepted
+struct AlwaysSuccessAuth {
+    pubkey_hash: H160,
+    sig_len: usize,
+}
+
+impl AuthWitnessProvider for AlwaysSuccessAuth {
+    fn match_pubkey_hash(&self, pubkey_hash: &H160) -> bool {
+        pubkey_hash == &self.pubkey_hash
+    }
+
+    fn sign(
+        &self,
+        _pubkey_hash: &H160,
+        message: &[u8],
+        _tx: &TransactionView,
+    ) -> Result<Bytes, SignerError> {
+        let mut signature = message.to_vec();
+        signature.resize(self.sig_len, 0);
+        Ok(Bytes::from(signature))
+    }
+}
+
+#[test]
+fn test_omnilock_transfer_from_exec_auth() {
+    let unlock_mode = OmniUnlockMode::Normal;
+    let auth_code_hash = H256::from(blake2b_256(ALWAYS_SUCCESS_BIN));
+    let pubkey_hash = H160::from_slice(&[7u8; 20]).unwrap();
+    let mut cfg = OmniLockConfig::new_exec_auth(
+        auth_code_hash,
+        ScriptHashType::Data1,
+        0,
+        0,
+        pubkey_hash.clone(),
+    );
+    let mut exec_dl_config = cfg.get_exec_dl_config().unwrap().clone();
+    assert_eq!(exec_dl_config.preimage().len(), ExecDlConfig::PREIMAGE_LEN);
+    assert_eq!(
+        cfg.id().auth_content(),
+        &blake160(exec_dl_config.preimage())
+    );
+    // a signature of another size than the default 65 bytes
+    exec_dl_config.set_sig_len(40);
+    cfg.set_exec_dl_config(exec_dl_config.clone());
+
+    let sender = build_omnilock_script(&cfg);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let ctx = init_context(
+        vec![(OMNILOCK_BIN, true), (ALWAYS_SUCCESS_BIN, false)],
+        vec![
+            (sender.clone(), Some(100 * ONE_CKB)),
+            (sender.clone(), Some(200 * ONE_CKB)),
+        ],
+    );
+
+    let output = CellOutput::new_builder()
+        .capacity((120 * ONE_CKB).pack())
+        .lock(receiver)
+        .build();
+    let builder =
+        OmniLockTransferBuilder::new(vec![(output.clone(), Bytes::default())], cfg.clone(), None);
+    let placeholder_witness = cfg.placeholder_witness(unlock_mode).unwrap();
+    let balancer =
+        CapacityBalancer::new_simple(sender.clone(), placeholder_witness.clone(), FEE_RATE);
+
+    let signer = SecpCkbRawKeySigner::new_with_secret_keys(vec![]);
+    let auth = AlwaysSuccessAuth {
+        pubkey_hash,
+        sig_len: 40,
+    };
+    let omnilock_script_signer =
+        OmniLockScriptSigner::new(Box::new(signer) as Box<_>, cfg.clone(), unlock_mode)
+            .with_auth_witness_provider(Box::new(auth));
+    let omnilock_unlocker = OmniLockUnlocker::new(omnilock_script_signer, cfg);
+    let mut unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>> = HashMap::default();
+    unlockers.insert(ScriptId::from(&sender), Box::new(omnilock_unlocker));
+
+    let mut cell_collector = ctx.to_live_cells_context();
+    let (tx, locked_groups) = builder
+        .build_unlocked(&mut cell_collector, &ctx, &ctx, &ctx, &balancer, &unlockers)
+        .unwrap();
+    assert!(locked_groups.is_empty());
+
+    // the omni-lock and the auth script
+    assert_eq!(tx.cell_deps().len(), 2);
+    assert_eq!(tx.inputs().len(), 2);
+    assert_eq!(tx.output(0).unwrap(), output);
+    let witness = tx.witnesses().get(0).unwrap().raw_data();
+    assert_eq!(witness.len(), placeholder_witness.as_slice().len());
+    let witness_lock = WitnessArgs::from_slice(&witness)
+        .unwrap()
+        .lock()
+        .to_opt()
+        .unwrap()
+        .raw_data();
+    let witness_lock = OmniLockWitnessLock::from_slice(&witness_lock).unwrap();
+    assert_eq!(
+        &witness_lock.preimage().to_opt().unwrap().raw_data(),
+        exec_dl_config.preimage()
+    );
+    let signature = witness_lock.signature().to_opt().unwrap().raw_data();
+    assert_eq!(signature.len(), 40);
+    assert_ne!(signature, Bytes::from(vec![0u8; 40]));
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_omnilock_dl_auth_placeholder_witness() {
+    let auth_code_hash = H256::from(blake2b_256(ALWAYS_SUCCESS_BIN));
+    let pubkey_hash = H160::from_slice(&[7u8; 20]).unwrap();
+    let cfg = OmniLockConfig::new_dl_auth(auth_code_hash, ScriptHashType::Type, 1, 8, pubkey_hash);
+    let args = cfg.build_args();
+    assert_eq!(args[0], IdentityFlag::Dl as u8);
+    let exec_dl_config = cfg.get_exec_dl_config().unwrap();
+    assert_eq!(&args[1..21], blake160(exec_dl_config.preimage()).as_bytes());
+    assert_eq!(exec_dl_config.place(), 1);
+
+    let witness_lock = cfg
+        .placeholder_witness_lock(OmniUnlockMode::Normal)
+        .unwrap();
+    let witness_lock = OmniLockWitnessLock::from_slice(&witness_lock).unwrap();
+    assert_eq!(
+        witness_lock.signature().to_opt().unwrap().raw_data().len(),
+        65
+    );
+    assert_eq!(
+        &witness_lock.preimage().to_opt().unwrap().raw_data(),
+        exec_dl_config.preimage()
+    );
+
+    // the preimage is not in the args
+    let mut parsed = OmniLockConfig::from_args(&args).unwrap();
+    assert!(parsed.placeholder_witness(OmniUnlockMode::Normal).is_err());
+    parsed.set_exec_dl_config(exec_dl_config.clone());
+    assert_eq!(parsed, cfg);
+}
+
 #[test]
 fn test_omnilock_transfer_from_ownerlock() {
     let unlock_mode = OmniUnlockMode::Normal;
@@ -799,7 +1246,16 @@ fn test_omnilock_transfer_from_ownerlock() {
             (sender1.clone(), placeholder_witness1.clone()),
         ]),
         change_lock_script: None,
+        change_cell_template: None,
         force_small_change_as_fee: Some(ONE_CKB),
+        fee_rate_provider: None,
+        trailing_witnesses: TrailingWitnesses::default(),
+        confirmed_burn: None,
+        check_transaction: false,
+        verify_type_script_cycles: None,
+        cell_lock_ttl: None,
+        allow_unconfirmed: true,
+        input_header_deps: false,
     };
 
     let mut cell_collector = ctx.to_live_cells_context();
@@ -842,7 +1298,8 @@ fn test_omnilock_transfer_from_ownerlock() {
         .map(|w| w.raw_data())
         .collect::<Vec<_>>();
     assert_eq!(witnesses.len(), 2);
-    assert_eq!(witnesses[0].len(), placeholder_witness0.as_slice().len());
+    // the owner lock input unlocks the omni-lock, no witness is needed
+    assert!(witnesses[0].is_empty());
     assert_eq!(witnesses[1].len(), placeholder_witness1.as_slice().len());
     ctx.verify(tx, FEE_RATE).unwrap();
 }
@@ -864,8 +1321,7 @@ fn test_omnilock_transfer_from_ownerlock_wl_admin() {
 
     let owner_hash = H160::from_slice(&owner_sender.calc_script_hash().as_slice()[0..20]).unwrap();
     let owner_id = Identity::new(IdentityFlag::OwnerLock, owner_hash);
-    let (proof_vec, rc_type_id, rce_cells) =
-        generate_rc(&mut ctx, owner_id.to_smt_key().into(), false, ACCOUNT0_ARG);
+    let (proof_vec, rc_type_id, rce_cells) = generate_rc(&mut ctx, &owner_id, false, ACCOUNT0_ARG);
     cfg.set_admin_config(AdminConfig::new(
         H256::from_slice(rc_type_id.as_ref()).unwrap(),
         proof_vec,
@@ -899,7 +1355,16 @@ fn test_omnilock_transfer_from_ownerlock_wl_admin() {
             (owner_sender.clone(), placeholder_witness1.clone()),
         ]),
         change_lock_script: None,
+        change_cell_template: None,
         force_small_change_as_fee: Some(ONE_CKB),
+        fee_rate_provider: None,
+        trailing_witnesses: TrailingWitnesses::default(),
+        confirmed_burn: None,
+        check_transaction: false,
+        verify_type_script_cycles: None,
+        cell_lock_ttl: None,
+        allow_unconfirmed: true,
+        input_header_deps: false,
     };
 
     let mut cell_collector = ctx.to_live_cells_context();
@@ -1140,7 +1605,7 @@ fn test_omnilock_udt_transfer() {
     let owner = build_sighash_script(H160::default());
     let type_script = Script::new_builder()
         .code_hash(sudt_data_hash.pack())
-        .hash_type(ScriptHashType::Data1.into())
+        .hash_type(ScriptHashType::Data1.to_packed())
         .args(owner.calc_script_hash().as_bytes().pack())
         .build();
     let mut ctx = init_context(
@@ -1175,6 +1640,7 @@ fn test_omnilock_udt_transfer() {
         type_script,
         sender: sender.clone(),
         receivers: vec![udt_receiver],
+        data_validator: None,
     };
     let placeholder_witness = WitnessArgs::new_builder()
         .lock(Some(Bytes::from(vec![0u8; 65])).pack())
@@ -1248,7 +1714,7 @@ fn test_omnilock_simple_hash_timelock(mut cfg: OmniLockConfig) {
     let epoch_number = 200;
     let since = Since::new_absolute_epoch(epoch_number);
 
-    cfg.set_time_lock_config(since.value());
+    cfg.set_time_lock_config(since);
 
     let sender = build_omnilock_script(&cfg);
     let receiver = build_sighash_script(ACCOUNT2_ARG);
@@ -1318,7 +1784,7 @@ fn build_sudt_script(omnilock_hash: Byte32) -> Script {
     let sudt_data_hash = H256::from(blake2b_256(SUDT_BIN));
     Script::new_builder()
         .code_hash(sudt_data_hash.pack())
-        .hash_type(ScriptHashType::Data1.into())
+        .hash_type(ScriptHashType::Data1.to_packed())
         .args(omnilock_hash.as_bytes().pack())
         .build()
 }
@@ -1330,7 +1796,7 @@ fn build_info_cell_type_script() -> (Script, H256) {
     rng.fill(&mut args[..]);
     let script = Script::new_builder()
         .code_hash(data_hash.pack())
-        .hash_type(ScriptHashType::Data.into())
+        .hash_type(ScriptHashType::Data.to_packed())
         .args(Bytes::from(args).pack())
         .build();
     let script_hash = script.calc_script_hash();
@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+
+use ckb_hash::blake2b_256;
+use ckb_types::{
+    bytes::Bytes,
+    core::ScriptHashType,
+    packed::{CellOutput, Script, WitnessArgs},
+    prelude::*,
+    H160, H256,
+};
+use rsa::{
+    pkcs1::DecodeRsaPrivateKey,
+    pkcs1v15::{Signature, VerifyingKey},
+    pkcs8::DecodePublicKey,
+    signature::Verifier,
+    traits::PublicKeyParts,
+    BigUint, RsaPrivateKey, RsaPublicKey,
+};
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+
+use crate::{
+    constants::ONE_CKB,
+    tests::{build_sighash_script, init_context, ACCOUNT2_ARG, ALWAYS_SUCCESS_BIN, FEE_RATE},
+    traits::SecpCkbRawKeySigner,
+    tx_builder::{omni_lock::OmniLockTransferBuilder, CapacityBalancer, TxBuilder},
+    types::{omni_lock::OmniLockWitnessLock, ScriptHashTypeExt},
+    unlock::{
+        AuthWitnessProvider, IdentityFlag, OmniLockConfig, OmniLockScriptSigner, OmniLockUnlocker,
+        OmniUnlockMode, RsaAlgorithm, RsaPubkey, RsaSigner, ScriptUnlocker,
+    },
+    util::blake160,
+    ScriptId,
+};
+
+const OMNILOCK_BIN: &[u8] = include_bytes!("../test-data/omni_lock");
+// Generated by OpenSSL (python `cryptography`), the signatures and the pubkey
+// hashes below are of the message `0x00..0x1f`.
+const RSA_KEY_DER: &[u8] = include_bytes!("../test-data/rsa_1024_key.der");
+const RSA_PUBKEY_DER: &[u8] = include_bytes!("../test-data/rsa_1024_pubkey.der");
+const PKCS1V15_PUBKEY_HASH: &str = "8fe8edf9f600eec2bff1328c644349dabe6d8dec";
+const PKCS1V15_SIGNATURE: &str = "97a941c3fd6f8e389c0f3146b16c447cba226ab99a20bee0d299299ff0d8ff5d2f8bc85cac49bdb374bc78afa9b4469a4f407b11b5f0187a8e229d923c0638cce1ecb54522ec5019a015be5834759928913d43cfcf281bf5f2b0699a036f352039ce283019ceff6abdadd1a20c0df280bc0acf5adadfc25bbcbc27b90cd2aceb";
+const ISO9796_2_PUBKEY_HASH: &str = "f77e657f44a4aa00eb38f8ed8cba8697c9ec06f0";
+const ISO9796_2_SIGNATURE: &str = "9b71ba7f25a3c6270f58d40fe47af90ca8f66ffa1b87efdccfbc2cd703a8db27243a3e6728d63a3033bd6526fa56dfa2b4076426bf917110a93847c23d5be18dd0fcfe12fde20c2209ad7b4c0b14749f4ff6bf6bb76c054a5cae69ee00bc06ee843dfe9a033c1bccb5307af7c404fe5b2e053ef509ea9bb79270f9dd12e355a7";
+
+fn auth_script_id(auth_bin: &[u8]) -> ScriptId {
+    ScriptId::new_data1(H256::from(blake2b_256(auth_bin)))
+}
+
+fn build_omnilock_script(cfg: &OmniLockConfig) -> Script {
+    Script::new_builder()
+        .code_hash(blake2b_256(OMNILOCK_BIN).pack())
+        .hash_type(ScriptHashType::Data1.to_packed())
+        .args(cfg.build_args().pack())
+        .build()
+}
+
+fn rsa_signer(algorithm: RsaAlgorithm) -> RsaSigner {
+    RsaSigner::new(
+        RsaPrivateKey::from_pkcs1_der(RSA_KEY_DER).unwrap(),
+        algorithm,
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_rsa_signature_fixtures() {
+    let message: Vec<u8> = (0u8..32).collect();
+    for (algorithm, pubkey_hash, signature) in [
+        (
+            RsaAlgorithm::Pkcs1v15,
+            PKCS1V15_PUBKEY_HASH,
+            PKCS1V15_SIGNATURE,
+        ),
+        (
+            RsaAlgorithm::Iso9796_2,
+            ISO9796_2_PUBKEY_HASH,
+            ISO9796_2_SIGNATURE,
+        ),
+    ] {
+        let pubkey = RsaPubkey::from_der(RSA_PUBKEY_DER, algorithm).unwrap();
+        assert_eq!(pubkey.key_size(), 1024);
+        assert_eq!(hex::encode(pubkey.pubkey_hash().as_bytes()), pubkey_hash);
+
+        let signer = rsa_signer(algorithm);
+        assert_eq!(signer.pubkey(), &pubkey);
+        assert_eq!(
+            hex::encode(signer.sign_message(&message).unwrap()),
+            signature
+        );
+
+        let tx = ckb_types::core::TransactionBuilder::default().build();
+        let rsa_info = signer.sign(&pubkey.pubkey_hash(), &message, &tx).unwrap();
+        assert_eq!(rsa_info.len(), pubkey.rsa_info_len());
+        assert_eq!(rsa_info.len(), 8 + 128 + 128);
+        assert_eq!(&rsa_info[0..136], pubkey.pubkey_info().as_ref());
+        assert_eq!(hex::encode(&rsa_info[136..]), signature);
+        assert!(signer.sign(&H160::default(), &message, &tx).is_err());
+    }
+}
+
+/// Check the `RsaInfo` fields against the DER key and the standard RSA
+/// verification, instead of the encoder itself.
+#[test]
+fn test_rsa_info_layout() {
+    let key = RsaPublicKey::from_public_key_der(RSA_PUBKEY_DER).unwrap();
+    let message: Vec<u8> = (0u8..32).collect();
+    let tx = ckb_types::core::TransactionBuilder::default().build();
+    for (algorithm, algorithm_id, md_type) in [
+        (RsaAlgorithm::Pkcs1v15, 1u8, 6u8),
+        (RsaAlgorithm::Iso9796_2, 2u8, 4u8),
+    ] {
+        let signer = rsa_signer(algorithm);
+        let rsa_info = signer
+            .sign(&signer.pubkey().pubkey_hash(), &message, &tx)
+            .unwrap();
+        // algorithm id, key size 1024, PKCS#1 v1.5 padding and the mbedtls md type
+        assert_eq!(&rsa_info[0..4], &[algorithm_id, 1, 0, md_type]);
+        // E and N are little endian
+        assert_eq!(&rsa_info[4..8], &[0x01, 0x00, 0x01, 0x00]);
+        assert_eq!(&BigUint::from_bytes_le(&rsa_info[8..136]), key.n());
+        assert_eq!(blake160(&rsa_info[0..136]), signer.pubkey().pubkey_hash());
+
+        // the signature is big endian
+        let signature = &rsa_info[136..];
+        match algorithm {
+            RsaAlgorithm::Pkcs1v15 => {
+                VerifyingKey::<Sha256>::new(key.clone())
+                    .verify(&message, &Signature::try_from(signature).unwrap())
+                    .unwrap();
+            }
+            RsaAlgorithm::Iso9796_2 => {
+                let block = BigUint::from_bytes_be(signature)
+                    .modpow(key.e(), key.n())
+                    .to_bytes_be();
+                assert_eq!(block.len(), 127);
+                assert_eq!(block[0], 0x4b);
+                assert_eq!(block[126], 0xbc);
+                assert_eq!(&block[106..126], Sha1::digest(&message).as_slice());
+                assert_eq!(&block[74..106], message.as_slice());
+                assert_eq!(block[73], 0xba);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_rsa_config_args() {
+    let cfg = OmniLockConfig::new_rsa(
+        Bytes::from(RSA_PUBKEY_DER.to_vec()),
+        RsaAlgorithm::Pkcs1v15,
+        IdentityFlag::Dl,
+        &auth_script_id(ALWAYS_SUCCESS_BIN),
+    )
+    .unwrap();
+    let exec_dl_config = cfg.get_exec_dl_config().unwrap();
+    assert_eq!(
+        hex::encode(exec_dl_config.pubkey_hash().as_bytes()),
+        PKCS1V15_PUBKEY_HASH
+    );
+    assert_eq!(
+        exec_dl_config.script_id(),
+        auth_script_id(ALWAYS_SUCCESS_BIN)
+    );
+    assert_eq!(exec_dl_config.place(), 0);
+    assert_eq!(exec_dl_config.sig_len(), 264);
+
+    let parsed = OmniLockConfig::from_args(&cfg.build_args()).unwrap();
+    assert_eq!(parsed.id(), cfg.id());
+    assert_eq!(parsed.build_args(), cfg.build_args());
+    let json = serde_json::to_string(&cfg).unwrap();
+    assert_eq!(serde_json::from_str::<OmniLockConfig>(&json).unwrap(), cfg);
+
+    // the RSA identity is only verified by exec or dl
+    assert!(OmniLockConfig::new_rsa(
+        Bytes::from(RSA_PUBKEY_DER.to_vec()),
+        RsaAlgorithm::Pkcs1v15,
+        IdentityFlag::PubkeyHash,
+        &auth_script_id(ALWAYS_SUCCESS_BIN),
+    )
+    .is_err());
+    assert!(OmniLockConfig::new_rsa(
+        Bytes::from(vec![0u8; 32]),
+        RsaAlgorithm::Pkcs1v15,
+        IdentityFlag::Dl,
+        &auth_script_id(ALWAYS_SUCCESS_BIN),
+    )
+    .is_err());
+}
+
+#[test]
+fn test_rsa_placeholder_size() {
+    let e = BigUint::from(65537u32);
+    for bits in [1024usize, 2048, 4096] {
+        let n = (BigUint::from(1u32) << (bits - 1)) + BigUint::from(1u32);
+        let key = RsaPublicKey::new(n, e.clone()).unwrap();
+        let pubkey = RsaPubkey::new(&key, RsaAlgorithm::Pkcs1v15).unwrap();
+        assert_eq!(pubkey.key_size(), bits);
+        assert_eq!(pubkey.rsa_info_len(), 8 + bits / 4);
+    }
+    let n = (BigUint::from(1u32) << 1535) + BigUint::from(1u32);
+    let key = RsaPublicKey::new(n, e).unwrap();
+    assert!(RsaPubkey::new(&key, RsaAlgorithm::Pkcs1v15).is_err());
+}
+
+#[test]
+fn test_omnilock_transfer_from_rsa() {
+    // always_success stands for the ckb-auth script, omni-lock execs it
+    transfer_from_rsa(ALWAYS_SUCCESS_BIN);
+}
+
+/// Verify the signature by the real ckb-auth script, the binary is not
+/// vendored, build it by `make ckb-auth` in `src/test-data` first.
+#[test]
+#[ignore]
+fn test_omnilock_transfer_from_rsa_with_ckb_auth() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/test-data/ckb_auth");
+    let auth_bin = std::fs::read(path).expect("build ckb_auth by `make ckb-auth` first");
+    transfer_from_rsa(&auth_bin);
+}
+
+fn transfer_from_rsa(auth_bin: &[u8]) {
+    let unlock_mode = OmniUnlockMode::Normal;
+    let cfg = OmniLockConfig::new_rsa(
+        Bytes::from(RSA_PUBKEY_DER.to_vec()),
+        RsaAlgorithm::Pkcs1v15,
+        IdentityFlag::Exec,
+        &auth_script_id(auth_bin),
+    )
+    .unwrap();
+    let sender = build_omnilock_script(&cfg);
+    let ctx = init_context(
+        vec![(OMNILOCK_BIN, true), (auth_bin, false)],
+        vec![(sender.clone(), Some(300 * ONE_CKB))],
+    );
+
+    let output = CellOutput::new_builder()
+        .capacity((120 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT2_ARG))
+        .build();
+    let builder = OmniLockTransferBuilder::new(vec![(output, Bytes::default())], cfg.clone(), None);
+    let placeholder_witness = cfg.placeholder_witness(unlock_mode).unwrap();
+    let balancer =
+        CapacityBalancer::new_simple(sender.clone(), placeholder_witness.clone(), FEE_RATE);
+
+    let rsa_signer = rsa_signer(RsaAlgorithm::Pkcs1v15);
+    let pubkey_info = rsa_signer.pubkey().pubkey_info();
+    let signer = SecpCkbRawKeySigner::new_with_secret_keys(vec![]);
+    let omnilock_script_signer =
+        OmniLockScriptSigner::new(Box::new(signer) as Box<_>, cfg.clone(), unlock_mode)
+            .with_auth_witness_provider(Box::new(rsa_signer));
+    let omnilock_unlocker = OmniLockUnlocker::new(omnilock_script_signer, cfg.clone());
+    let mut unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>> = HashMap::default();
+    unlockers.insert(ScriptId::from(&sender), Box::new(omnilock_unlocker));
+
+    let (tx, locked_groups) = builder
+        .build_unlocked(
+            &mut ctx.to_live_cells_context(),
+            &ctx,
+            &ctx,
+            &ctx,
+            &balancer,
+            &unlockers,
+        )
+        .unwrap();
+    assert!(locked_groups.is_empty());
+
+    let witness = tx.witnesses().get(0).unwrap().raw_data();
+    assert_eq!(witness.len(), placeholder_witness.as_slice().len());
+    let witness_lock = WitnessArgs::from_slice(&witness)
+        .unwrap()
+        .lock()
+        .to_opt()
+        .unwrap()
+        .raw_data();
+    let witness_lock = OmniLockWitnessLock::from_slice(&witness_lock).unwrap();
+    assert_eq!(
+        &witness_lock.preimage().to_opt().unwrap().raw_data(),
+        cfg.get_exec_dl_config().unwrap().preimage()
+    );
+    let rsa_info = witness_lock.signature().to_opt().unwrap().raw_data();
+    assert_eq!(rsa_info.len(), 264);
+    assert_eq!(&rsa_info[0..136], pubkey_info.as_ref());
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
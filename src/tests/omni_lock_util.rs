@@ -3,33 +3,38 @@ use ckb_types::core::{DepType, ScriptHashType};
 
 use crate::constants::ONE_CKB;
 use crate::test_util::{random_out_point, Context};
-use crate::types::xudt_rce_mol::{RCCellVecBuilder, RCDataBuilder, RCDataUnion, SmtProofEntryVec};
-use crate::unlock::rc_data::ListType;
-use crate::unlock::rc_data::{Mask, RcRuleVecBuilder};
+use crate::types::xudt_rce_mol::SmtProofEntryVec;
+use crate::types::ScriptHashTypeExt;
+use crate::unlock::omni_lock::{
+    rce::{build_rc_cell_vec_data, generate_admin_proofs, RcScheme},
+    Identity,
+};
 
 use ckb_types::{packed::*, prelude::*, H160};
-use sparse_merkle_tree::H256 as SmtH256;
 
 use super::{build_sighash_script, ALWAYS_SUCCESS_BIN};
 
 pub fn generate_rc(
     ctx: &mut Context,
-    smt_key: SmtH256,
+    identity: &Identity,
     in_input_cell: bool,
     args: H160,
 ) -> (SmtProofEntryVec, Bytes, Vec<OutPoint>) {
-    let mut builder = RcRuleVecBuilder::new();
-    builder
-        .build_single_proof_and_rule(&[smt_key], Mask::Input, ListType::White, false, true)
-        .unwrap();
-    builder
-        .build_single_proof_and_rule(&[smt_key], Mask::Output, ListType::White, false, false)
-        .unwrap();
-    let proof_vec = builder.build_proofs();
+    generate_rc_with_whitelist(ctx, identity, &[], in_input_cell, args)
+}
 
-    let rc_rules = builder.rc_rules();
+// Same as `generate_rc`, but the input white list also contains `other_identities`
+pub fn generate_rc_with_whitelist(
+    ctx: &mut Context,
+    identity: &Identity,
+    other_identities: &[Identity],
+    in_input_cell: bool,
+    args: H160,
+) -> (SmtProofEntryVec, Bytes, Vec<OutPoint>) {
+    let (_, proof_vec, rc_rules) =
+        generate_admin_proofs(other_identities, identity, RcScheme::OnlyInputOnWhiteList).unwrap();
     let mut rce_cells = vec![];
-    let rc_type_id = generate_rce_cell(ctx, rc_rules, &mut rce_cells, in_input_cell, args);
+    let rc_type_id = generate_rce_cell(ctx, &rc_rules, &mut rce_cells, in_input_cell, args);
 
     (proof_vec, rc_type_id.as_bytes(), rce_cells)
 }
@@ -38,7 +43,7 @@ pub fn build_always_success_script() -> Script {
     let data_hash = CellOutput::calc_data_hash(ALWAYS_SUCCESS_BIN);
     Script::new_builder()
         .code_hash(data_hash)
-        .hash_type(ScriptHashType::Data.into())
+        .hash_type(ScriptHashType::Data.to_packed())
         .build()
 }
 //
@@ -76,7 +81,7 @@ fn build_script(
             Script::new_builder()
                 .args(args.pack())
                 .code_hash(hash.pack())
-                .hash_type(ScriptHashType::Type.into())
+                .hash_type(ScriptHashType::Type.to_packed())
                 .build()
         }
     };
@@ -117,44 +122,27 @@ fn build_script(
     Script::new_builder()
         .args(args.pack())
         .code_hash(code_hash.pack())
-        .hash_type(hash_type.into())
+        .hash_type(hash_type.to_packed())
         .build()
 }
 
 // first generate N RCE cells with each contained one RCRule
 // then collect all these RCE cell hash and create the final RCE cell.
-pub fn generate_rce_cell(
+fn generate_rce_cell(
     ctx: &mut Context,
     rc_rules: &Vec<Bytes>,
     rce_cells: &mut Vec<OutPoint>,
     in_input_cell: bool,
     args: H160,
 ) -> Byte32 {
-    let mut cell_vec_builder = RCCellVecBuilder::default();
-
-    for rc_rule in rc_rules {
-        let rce_script = build_script(ctx, true, in_input_cell, rc_rule, args.clone(), rce_cells);
-
-        let hash = rce_script.code_hash();
-
-        cell_vec_builder =
-            cell_vec_builder.push(Byte32::from_slice(hash.as_slice()).expect("Byte32::from_slice"));
-    }
-
-    let cell_vec = cell_vec_builder.build();
-
-    let rce_cell_content = RCDataBuilder::default()
-        .set(RCDataUnion::RCCellVec(cell_vec))
-        .build();
-
-    let bin = rce_cell_content.as_slice();
-    let rce_script = build_script(
-        ctx,
-        true,
-        in_input_cell,
-        &Bytes::copy_from_slice(bin),
-        args,
-        rce_cells,
-    );
+    let rc_rule_type_hashes = rc_rules
+        .iter()
+        .map(|rc_rule| {
+            build_script(ctx, true, in_input_cell, rc_rule, args.clone(), rce_cells).code_hash()
+        })
+        .collect::<Vec<_>>();
+
+    let rce_cell_content = build_rc_cell_vec_data(&rc_rule_type_hashes);
+    let rce_script = build_script(ctx, true, in_input_cell, &rce_cell_content, args, rce_cells);
     rce_script.code_hash()
 }
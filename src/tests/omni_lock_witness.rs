@@ -0,0 +1,87 @@
+use ckb_types::{
+    bytes::Bytes,
+    packed::{Byte, BytesOpt},
+    prelude::*,
+};
+
+use crate::types::{
+    omni_lock::{Auth, Identity, IdentityOpt, OmniLockWitnessLock},
+    xudt_rce_mol::{SmtProof, SmtProofEntry, SmtProofEntryVec},
+};
+
+fn bytes_opt(len: usize) -> BytesOpt {
+    if len == 0 {
+        BytesOpt::default()
+    } else {
+        Some(Bytes::from(vec![1u8; len])).pack()
+    }
+}
+
+fn build_proofs(entries: usize, proof_len: usize) -> SmtProofEntryVec {
+    let mut builder = SmtProofEntryVec::new_builder();
+    for _ in 0..entries {
+        let proof = SmtProof::new_builder()
+            .extend((0..proof_len).map(|idx| Byte::new(idx as u8)))
+            .build();
+        builder = builder.push(
+            SmtProofEntry::new_builder()
+                .mask(Byte::new(3))
+                .proof(proof)
+                .build(),
+        );
+    }
+    builder.build()
+}
+
+#[test]
+fn test_witness_lock_size_with() {
+    for signature_len in [0, 1, 65, 264] {
+        // `None` is no omni identity
+        for proofs in [
+            None,
+            Some((0, 0)),
+            Some((1, 0)),
+            Some((1, 32)),
+            Some((3, 100)),
+        ] {
+            for preimage_len in [0, 20, 41] {
+                let (identity, proofs_len) = match proofs {
+                    Some((entries, proof_len)) => {
+                        let proofs = build_proofs(entries, proof_len);
+                        let proofs_len = proofs.as_slice().len();
+                        let identity = Identity::new_builder()
+                            .identity(Auth::from_slice(&[2u8; 21]).unwrap())
+                            .proofs(proofs)
+                            .build();
+                        (Some(identity), proofs_len)
+                    }
+                    None => (None, 0),
+                };
+                let witness_lock = OmniLockWitnessLock::new_builder()
+                    .signature(bytes_opt(signature_len))
+                    .omni_identity(IdentityOpt::new_builder().set(identity).build())
+                    .preimage(bytes_opt(preimage_len))
+                    .build();
+                assert_eq!(
+                    OmniLockWitnessLock::size_with(signature_len, proofs_len, preimage_len),
+                    witness_lock.as_slice().len(),
+                    "signature: {}, proofs: {:?}, preimage: {}",
+                    signature_len,
+                    proofs,
+                    preimage_len
+                );
+
+                let parsed = OmniLockWitnessLock::parse(witness_lock.as_slice()).unwrap();
+                assert_eq!(parsed.as_slice(), witness_lock.as_slice());
+                assert_eq!(
+                    parsed
+                        .signature()
+                        .to_opt()
+                        .map(|data| data.raw_data().len()),
+                    Some(signature_len).filter(|len| *len > 0)
+                );
+            }
+        }
+    }
+    assert!(OmniLockWitnessLock::parse(&[0u8; 3]).is_err());
+}
@@ -0,0 +1,82 @@
+use ckb_types::{
+    bytes::Bytes,
+    core::{TransactionBuilder, TransactionView},
+    packed::{CellInput, CellOutput, WitnessArgs},
+    prelude::*,
+    H256,
+};
+
+use crate::{
+    constants::ONE_CKB,
+    test_util::{Context, TestAccount},
+    tests::{build_sighash_script, build_sighash_unlockers, init_context, ACCOUNT2_ARG, FEE_RATE},
+    traits::CellDepResolver,
+    tx_builder::{unlock_tx, unlock_tx_parallel},
+};
+
+/// Every account spends 2 cells of 100 ckb to account2
+fn build_sweep_tx(accounts: &[TestAccount]) -> (Context, TransactionView) {
+    let mut ctx = init_context(Vec::new(), Vec::new());
+    let mut inputs = Vec::new();
+    for account in accounts {
+        for out_point in ctx.fund(&account.lock_script, &[100 * ONE_CKB, 100 * ONE_CKB]) {
+            inputs.push(CellInput::new(out_point, 0));
+        }
+    }
+    let output = CellOutput::new_builder()
+        .capacity((inputs.len() as u64 * 100 * ONE_CKB - ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT2_ARG))
+        .build();
+    let placeholder_witness = WitnessArgs::new_builder()
+        .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+        .build();
+    let witnesses = inputs
+        .iter()
+        .enumerate()
+        .map(|(idx, _)| {
+            if idx % 2 == 0 {
+                placeholder_witness.as_bytes().pack()
+            } else {
+                Bytes::new().pack()
+            }
+        })
+        .collect::<Vec<_>>();
+    let tx = TransactionBuilder::default()
+        .inputs(inputs)
+        .output(output)
+        .output_data(Bytes::new().pack())
+        .cell_dep(ctx.resolve(&accounts[0].lock_script).unwrap())
+        .set_witnesses(witnesses)
+        .build();
+    (ctx, tx)
+}
+
+#[test]
+fn test_unlock_tx_parallel() {
+    let accounts: Vec<TestAccount> = (0..16).map(|_| TestAccount::random()).collect();
+    let (ctx, tx) = build_sweep_tx(&accounts);
+
+    let keys: Vec<H256> = accounts
+        .iter()
+        .map(|account| H256(account.secret_key.secret_bytes()))
+        .collect();
+
+    let (serial_tx, not_unlocked) =
+        unlock_tx(tx.clone(), &ctx, &build_sighash_unlockers(&keys)).unwrap();
+    assert!(not_unlocked.is_empty());
+    let (parallel_tx, not_unlocked) =
+        unlock_tx_parallel(tx.clone(), &ctx, || build_sighash_unlockers(&keys)).unwrap();
+    assert!(not_unlocked.is_empty());
+    // the secp256k1 signatures are deterministic
+    assert_eq!(parallel_tx.data(), serial_tx.data());
+    ctx.verify(parallel_tx, FEE_RATE).unwrap();
+
+    // the groups without a signing key are not unlocked
+    let (partial_tx, not_unlocked) =
+        unlock_tx_parallel(tx, &ctx, || build_sighash_unlockers(&keys[..10])).unwrap();
+    assert_eq!(not_unlocked.len(), 6);
+    for (group, account) in not_unlocked.iter().zip(&accounts[10..]) {
+        assert_eq!(group.script, account.lock_script);
+    }
+    assert!(ctx.verify(partial_tx, FEE_RATE).is_err());
+}
@@ -0,0 +1,234 @@
+use std::str::FromStr;
+
+use ckb_types::{bytes::Bytes, core::ScriptHashType, h256, packed::Script, prelude::*};
+use proptest::prelude::*;
+
+use crate::{
+    payment_uri::{PaymentRequest, UdtPayment, UriError},
+    tests::{build_sighash_script, ACCOUNT1_ARG, ACCOUNT2_ARG},
+    types::{Address, AddressPayload, HumanCapacity, NetworkType, ScriptHashTypeExt},
+};
+
+fn address(network: NetworkType) -> Address {
+    Address::new(
+        network,
+        AddressPayload::from(build_sighash_script(ACCOUNT1_ARG)),
+        true,
+    )
+}
+
+fn udt_script() -> Script {
+    Script::new_builder()
+        .code_hash(
+            h256!("0x5e7a36a77e68eecc013dfa2fe6a23f3b6c344b04005808694ae6dd45eea4cfd5").pack(),
+        )
+        .hash_type(ScriptHashType::Type.to_packed())
+        .args(Bytes::from(ACCOUNT2_ARG.as_bytes().to_vec()).pack())
+        .build()
+}
+
+fn full_request() -> PaymentRequest {
+    let mut request = PaymentRequest::new(address(NetworkType::Testnet));
+    request.amount = Some(HumanCapacity::from_str("100.5").unwrap());
+    request.udt = Some(UdtPayment {
+        type_script: udt_script(),
+        amount: u128::MAX,
+    });
+    request.label = Some("Coffee & cake".to_string());
+    request.message = Some("order #42, 100% = ok?".to_string());
+    request
+        .extras
+        .insert("x-memo".to_string(), "hello world".to_string());
+    request
+}
+
+#[test]
+fn test_payment_uri_roundtrip() {
+    let request = PaymentRequest::new(address(NetworkType::Mainnet));
+    let uri = request.to_uri();
+    assert_eq!(uri, format!("ckb:{}", request.address));
+    assert_eq!(PaymentRequest::parse(&uri).unwrap(), request);
+
+    let request = full_request();
+    let uri = request.to_uri();
+    assert!(uri.starts_with(&format!(
+        "ckb:{}?amount=100.5&udt_code_hash=0x",
+        request.address
+    )));
+    assert_eq!(PaymentRequest::parse(&uri).unwrap(), request);
+    assert_eq!(PaymentRequest::from_str(&uri).unwrap(), request);
+
+    // a reserved key in the extras is not written
+    let mut with_reserved = request.clone();
+    with_reserved
+        .extras
+        .insert("amount".to_string(), "1".to_string());
+    assert_eq!(with_reserved.to_uri(), uri);
+}
+
+#[test]
+fn test_payment_uri_extras() {
+    let addr = address(NetworkType::Testnet);
+    let uri = format!(
+        "ckb:{}?amount=1&foo=bar&empty=&label=%E4%BD%A0%E5%A5%BD",
+        addr
+    );
+    let request = PaymentRequest::parse(&uri).unwrap();
+    assert_eq!(request.amount, Some(HumanCapacity::from(100_000_000)));
+    assert_eq!(request.label.as_deref(), Some("你好"));
+    assert_eq!(request.extras.len(), 2);
+    assert_eq!(request.extras["foo"], "bar");
+    assert_eq!(request.extras["empty"], "");
+}
+
+#[test]
+fn test_payment_uri_network() {
+    let uri = PaymentRequest::new(address(NetworkType::Testnet)).to_uri();
+    assert!(PaymentRequest::parse_with_network(&uri, NetworkType::Testnet).is_ok());
+    // the dev chain uses the testnet prefix
+    assert!(PaymentRequest::parse_with_network(&uri, NetworkType::Dev).is_ok());
+    assert_eq!(
+        PaymentRequest::parse_with_network(&uri, NetworkType::Mainnet),
+        Err(UriError::NetworkMismatch(
+            NetworkType::Mainnet,
+            NetworkType::Testnet
+        ))
+    );
+}
+
+#[test]
+fn test_payment_uri_errors() {
+    let addr = address(NetworkType::Testnet);
+    let udt_query = full_request()
+        .to_uri()
+        .split('?')
+        .nth(1)
+        .unwrap()
+        .to_string();
+    let cases = vec![
+        (
+            format!("bitcoin:{}", addr),
+            UriError::InvalidScheme("bitcoin".to_string()),
+        ),
+        (
+            format!("ckb:{}#amount=1", addr),
+            UriError::InvalidUri("unexpected fragment".to_string()),
+        ),
+        (
+            format!("ckb:{}?amount=1&amount=2", addr),
+            UriError::DuplicateKey("amount".to_string()),
+        ),
+        (
+            format!("ckb:{}?udt_amount=1", addr),
+            UriError::MissingKey("udt_code_hash".to_string()),
+        ),
+        (
+            format!(
+                "ckb:{}?{}",
+                addr,
+                udt_query.replace("udt_hash_type", "hash_type")
+            ),
+            UriError::MissingKey("udt_hash_type".to_string()),
+        ),
+        (
+            format!("ckb:{}?{}", addr, udt_query.replace("=type", "=Type")),
+            UriError::InvalidValue("udt_hash_type".to_string(), "Type".to_string()),
+        ),
+        (
+            format!(
+                "ckb:{}?{}",
+                addr,
+                udt_query.replace("udt_args=0x", "udt_args=0")
+            ),
+            UriError::InvalidValue("udt_args".to_string(), format!("0{:x}", ACCOUNT2_ARG)),
+        ),
+    ];
+    for (uri, expected) in cases {
+        assert_eq!(PaymentRequest::parse(&uri), Err(expected), "uri: {}", uri);
+    }
+
+    for amount in [
+        "",
+        "-1",
+        "+1",
+        " 1",
+        "1.",
+        ".5",
+        "1.2.3",
+        "1.123456789",
+        "1e8",
+        "184467440738",
+    ] {
+        let uri = format!("ckb:{}?amount={}", addr, amount.replace(' ', "%20"));
+        assert!(
+            matches!(
+                PaymentRequest::parse(&uri),
+                Err(UriError::InvalidValue(key, _)) if key == "amount"
+            ),
+            "amount: {:?}",
+            amount
+        );
+    }
+    for udt_amount in ["", "-1", "0x10", "340282366920938463463374607431768211456"] {
+        let uri = format!(
+            "ckb:{}?{}",
+            addr,
+            udt_query.replace(
+                &format!("udt_amount={}", u128::MAX),
+                &format!("udt_amount={}", udt_amount)
+            )
+        );
+        assert!(
+            matches!(
+                PaymentRequest::parse(&uri),
+                Err(UriError::InvalidValue(key, _)) if key == "udt_amount"
+            ),
+            "udt amount: {:?}",
+            udt_amount
+        );
+    }
+    for uri in [
+        "ckb:",
+        "ckb:ckt1qyq",
+        "ckb:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq",
+        "ckb://ckt1qyqrdsefa43s6m882pcj53m4gdnj4k440axqswmu83",
+        "ckt1qyqrdsefa43s6m882pcj53m4gdnj4k440axqswmu83",
+    ] {
+        assert!(PaymentRequest::parse(uri).is_err(), "uri: {}", uri);
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn test_payment_uri_parse_never_panics(input in ".*") {
+        let _ = PaymentRequest::parse(&input);
+        let _ = PaymentRequest::parse(&format!("ckb:{}", input));
+    }
+
+    #[test]
+    fn test_payment_uri_query_never_panics(query in "[a-z_=&%0-9A-Fx.#?+-]{0,64}") {
+        let uri = format!("ckb:{}?{}", address(NetworkType::Testnet), query);
+        let _ = PaymentRequest::parse(&uri);
+    }
+
+    #[test]
+    fn test_payment_uri_roundtrip_any(
+        shannons in any::<u64>(),
+        udt_amount in any::<u128>(),
+        args in proptest::collection::vec(any::<u8>(), 0..40),
+        label in ".*",
+        extra in ".*",
+    ) {
+        let mut request = PaymentRequest::new(address(NetworkType::Mainnet));
+        request.amount = Some(HumanCapacity::from(shannons));
+        request.udt = Some(UdtPayment {
+            type_script: udt_script().as_builder().args(Bytes::from(args).pack()).build(),
+            amount: udt_amount,
+        });
+        request.label = Some(label);
+        request.extras.insert("extra".to_string(), extra);
+        prop_assert_eq!(PaymentRequest::parse(&request.to_uri()), Ok(request));
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+
+use ckb_crypto::secp::Pubkey;
+use ckb_hash::blake2b_256;
+use ckb_types::{
+    bytes::Bytes,
+    core::{DepType, ScriptHashType, TransactionBuilder},
+    packed::{CellDep, CellInput, CellOutput, Script, WitnessArgs},
+    prelude::*,
+    H160, H256,
+};
+
+use crate::{
+    constants::{ONE_CKB, PW_LOCK_TYPE_HASH_AGGRON, PW_LOCK_TYPE_HASH_LINA},
+    test_util::{random_out_point, Context},
+    tests::{
+        build_sighash_balancer, build_sighash_script, init_context, ACCOUNT0_KEY, ACCOUNT1_KEY,
+        ACCOUNT2_ARG, ALWAYS_SUCCESS_BIN, FEE_RATE,
+    },
+    traits::SecpCkbRawKeySigner,
+    tx_builder::{transfer::CapacityTransferBuilder, TxBuilder},
+    types::{ScriptHashTypeExt, ScriptRegistry},
+    unlock::{
+        generate_keccak256_message, verify_signature_against_message, PwLockUnlocker,
+        ScriptUnlocker, SignAlgorithm,
+    },
+    util::{convert_keccak256_hash, keccak160},
+    NetworkType, ScriptGroup, ScriptId, SECP256K1,
+};
+
+fn eth_address(key: &H256) -> H160 {
+    let secret_key = secp256k1::SecretKey::from_slice(key.as_bytes()).unwrap();
+    let pubkey = secp256k1::PublicKey::from_secret_key(&SECP256K1, &secret_key);
+    keccak160(Pubkey::from(pubkey).as_ref())
+}
+
+fn build_unlocker(key: &H256) -> PwLockUnlocker {
+    let secret_key = secp256k1::SecretKey::from_slice(key.as_bytes()).unwrap();
+    let signer = SecpCkbRawKeySigner::new_with_ethereum_secret_keys(vec![secret_key]);
+    PwLockUnlocker::from(Box::new(signer) as Box<_>)
+}
+
+/// Deploy `always_success` as the pw-lock code cell, the signature is checked
+/// off-chain.
+fn deploy_pw_lock(ctx: &mut Context) -> ScriptId {
+    let type_script = Script::new_builder()
+        .code_hash([0x33u8; 32].pack())
+        .hash_type(ScriptHashType::Type.to_packed())
+        .args(Bytes::from(b"pw-lock".to_vec()).pack())
+        .build();
+    let code_hash = H256::from(blake2b_256(type_script.as_slice()));
+    let cell_dep = CellDep::new_builder()
+        .out_point(random_out_point())
+        .dep_type(DepType::Code.into())
+        .build();
+    let output = CellOutput::new_builder()
+        .type_(Some(type_script).pack())
+        .build();
+    ctx.add_cell_dep(
+        cell_dep.clone(),
+        output,
+        Bytes::from(ALWAYS_SUCCESS_BIN.to_vec()),
+        None,
+    );
+    let script_id = ScriptId::new_type(code_hash);
+    ctx.add_cell_dep_map(script_id.clone(), cell_dep);
+    script_id
+}
+
+fn build_pw_lock_script(script_id: &ScriptId, args: &[u8]) -> Script {
+    Script::new_builder()
+        .code_hash(script_id.code_hash.pack())
+        .hash_type(script_id.hash_type.to_packed())
+        .args(Bytes::from(args.to_vec()).pack())
+        .build()
+}
+
+#[test]
+fn test_pw_lock_registry() {
+    for (network, code_hash) in [
+        (NetworkType::Mainnet, PW_LOCK_TYPE_HASH_LINA),
+        (NetworkType::Testnet, PW_LOCK_TYPE_HASH_AGGRON),
+    ] {
+        let registry = ScriptRegistry::from_network(network).unwrap();
+        let script_id = ScriptId::new_type(code_hash);
+        assert_eq!(
+            registry.get(ScriptRegistry::PW_LOCK).unwrap().script_id,
+            script_id
+        );
+        assert_eq!(
+            ScriptRegistry::well_known_name(&script_id),
+            Some(ScriptRegistry::PW_LOCK)
+        );
+    }
+}
+
+#[test]
+fn test_pw_lock_transfer() {
+    let mut ctx = init_context(Vec::new(), Vec::new());
+    let script_id = deploy_pw_lock(&mut ctx);
+    transfer(ctx, script_id);
+}
+
+/// Verify the signature by the pw-lock script deployed on the mainnet, the
+/// binary is not vendored, fetch it by `make pw-lock` in `src/test-data` first.
+#[test]
+#[ignore]
+fn test_pw_lock_transfer_with_pw_lock_bin() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/test-data/pw_lock");
+    let pw_lock_bin = std::fs::read(path).expect("fetch pw_lock by `make pw-lock` first");
+    // deployed in a dep group with the secp256k1 data cell
+    let ctx = init_context(vec![(pw_lock_bin.as_slice(), true)], Vec::new());
+    let script_id = ScriptId::new_data1(H256::from(blake2b_256(&pw_lock_bin)));
+    transfer(ctx, script_id);
+}
+
+fn transfer(mut ctx: Context, script_id: ScriptId) {
+    let address = eth_address(&ACCOUNT0_KEY);
+    let sender = build_pw_lock_script(&script_id, address.as_bytes());
+    ctx.add_simple_live_cell(random_out_point(), sender.clone(), Some(100 * ONE_CKB));
+    ctx.add_simple_live_cell(random_out_point(), sender.clone(), Some(200 * ONE_CKB));
+
+    let unlocker = build_unlocker(&ACCOUNT0_KEY);
+    assert!(unlocker.match_args(address.as_bytes()));
+    // with the acp minimums
+    assert!(unlocker.match_args(&[address.as_bytes(), &[1u8, 2][..]].concat()));
+    assert!(!unlocker.match_args(eth_address(&ACCOUNT1_KEY).as_bytes()));
+    let mut unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>> = HashMap::default();
+    unlockers.insert(script_id, Box::new(unlocker));
+
+    let output = CellOutput::new_builder()
+        .capacity((120 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT2_ARG))
+        .build();
+    let builder = CapacityTransferBuilder::new(vec![(output, Bytes::default())]);
+    let balancer = build_sighash_balancer(&sender);
+    let (tx, locked_groups) = builder
+        .build_unlocked(
+            &mut ctx.to_live_cells_context(),
+            &ctx,
+            &ctx,
+            &ctx,
+            &balancer,
+            &unlockers,
+        )
+        .unwrap();
+    assert!(locked_groups.is_empty());
+    assert_eq!(tx.inputs().len(), 2);
+    ctx.verify(tx.clone(), FEE_RATE).unwrap();
+
+    let mut script_group = ScriptGroup::from_lock_script(&sender);
+    script_group.input_indices.extend([0, 1]);
+    let signature = WitnessArgs::from_slice(&tx.witnesses().get(0).unwrap().raw_data())
+        .unwrap()
+        .lock()
+        .to_opt()
+        .unwrap()
+        .raw_data();
+    let message =
+        generate_keccak256_message(&tx, &script_group, Bytes::from(vec![0u8; 65])).unwrap();
+    verify_signature_against_message(
+        &convert_keccak256_hash(&message),
+        &signature,
+        SignAlgorithm::EthPersonalSign,
+        &address,
+    )
+    .unwrap();
+    // the blake2b sighash message is not what the pw-lock signs
+    let blake2b_message =
+        crate::unlock::generate_message(&tx, &script_group, Bytes::from(vec![0u8; 65])).unwrap();
+    assert_ne!(message, blake2b_message);
+}
+
+#[test]
+fn test_pw_lock_acp_deposit() {
+    let mut ctx = init_context(Vec::new(), Vec::new());
+    let script_id = deploy_pw_lock(&mut ctx);
+    // minimum ckb: 10^0 shannons
+    let args = [eth_address(&ACCOUNT0_KEY).as_bytes(), &[0u8][..]].concat();
+    let receiver = build_pw_lock_script(&script_id, &args);
+    let out_point = random_out_point();
+    ctx.add_simple_live_cell(out_point.clone(), receiver.clone(), Some(100 * ONE_CKB));
+
+    // the depositor does not have the key of the receiver
+    let unlocker = build_unlocker(&ACCOUNT1_KEY);
+    let mut script_group = ScriptGroup::from_lock_script(&receiver);
+    script_group.input_indices.push(0);
+    let build_tx = |capacity: u64| {
+        TransactionBuilder::default()
+            .input(CellInput::new(out_point.clone(), 0))
+            .output(
+                CellOutput::new_builder()
+                    .capacity(capacity.pack())
+                    .lock(receiver.clone())
+                    .build(),
+            )
+            .output_data(Bytes::default().pack())
+            .witness(Bytes::default().pack())
+            .build()
+    };
+
+    let tx = build_tx(150 * ONE_CKB);
+    assert!(unlocker.is_unlocked(&tx, &script_group, &ctx).unwrap());
+    let tx = unlocker.unlock(&tx, &script_group, &ctx).unwrap();
+    assert!(tx.witnesses().get(0).unwrap().raw_data().is_empty());
+
+    // taking ckb out of the cell needs the signature
+    let tx = build_tx(99 * ONE_CKB);
+    assert!(!unlocker.is_unlocked(&tx, &script_group, &ctx).unwrap());
+    assert!(unlocker.unlock(&tx, &script_group, &ctx).is_err());
+}
@@ -0,0 +1,145 @@
+use ckb_types::{
+    bytes::Bytes,
+    core::ScriptHashType,
+    packed::{CellInput, CellOutput, Script},
+    prelude::*,
+};
+use sparse_merkle_tree::{CompiledMerkleProof, H256 as SmtH256};
+
+use crate::{
+    constants::{ONE_CKB, TYPE_ID_CODE_HASH},
+    test_util::random_out_point,
+    tests::{
+        build_sighash_balancer, build_sighash_script, build_sighash_unlockers, init_context,
+        ACCOUNT1_ARG, ACCOUNT1_KEY, FEE_RATE,
+    },
+    tx_builder::{
+        udt::xudt::rce::{
+            build_rc_rule_data, parse_rc_rule_data, RceCellCreateBuilder, RceCellUpdateBuilder,
+        },
+        TxBuilder,
+    },
+    types::ScriptHashTypeExt,
+    unlock::rc_data::{
+        CKBBlake2bHasher, ListType, RcRuleDataBuilder, EMERGENCY_HALT_MODE_MASK, SMT_EXISTING,
+        SMT_NOT_EXISTING, WHITE_BLACK_LIST_MASK,
+    },
+};
+
+fn build_key(idx: u32) -> SmtH256 {
+    SmtH256::from(ckb_hash::blake2b_256(idx.to_le_bytes()))
+}
+
+#[test]
+fn test_rce_cell_create() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let ctx = init_context(Vec::new(), vec![(sender.clone(), Some(500 * ONE_CKB))]);
+    let mut rule_builder = RcRuleDataBuilder::new(ListType::White, false);
+    rule_builder.update_hashes(&[build_key(0), build_key(1)]);
+    let flags = WHITE_BLACK_LIST_MASK | EMERGENCY_HALT_MODE_MASK;
+    let builder = RceCellCreateBuilder::new(sender.clone(), rule_builder.root(), flags);
+    let (tx, locked_groups) = builder
+        .build_unlocked(
+            &mut ctx.to_live_cells_context(),
+            &ctx,
+            &ctx,
+            &ctx,
+            &build_sighash_balancer(&sender),
+            &build_sighash_unlockers(&[ACCOUNT1_KEY]),
+        )
+        .unwrap();
+    assert!(locked_groups.is_empty());
+    let output = tx.output(0).unwrap();
+    let type_script = output.type_().to_opt().unwrap();
+    assert!(ScriptId::from(&type_script).is_type_id());
+    assert_ne!(type_script.args().raw_data().as_ref(), &[0u8; 32][..]);
+    let data = tx.outputs_data().get(0).unwrap().raw_data();
+    assert_eq!(
+        parse_rc_rule_data(&data).unwrap(),
+        (rule_builder.root(), flags)
+    );
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_rce_cell_update() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let mut ctx = init_context(Vec::new(), vec![(sender.clone(), Some(500 * ONE_CKB))]);
+    let existing_keys = vec![build_key(0), build_key(1)];
+    let mut rule_builder = RcRuleDataBuilder::new(ListType::White, false);
+    rule_builder.update_hashes(&existing_keys);
+
+    let type_id_script = Script::new_builder()
+        .code_hash(TYPE_ID_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.to_packed())
+        .args(Bytes::from(vec![7u8; 32]).pack())
+        .build();
+    let rc_input = CellInput::new(random_out_point(), 0);
+    ctx.add_live_cell(
+        rc_input.clone(),
+        CellOutput::new_builder()
+            .capacity((200 * ONE_CKB).pack())
+            .lock(sender.clone())
+            .type_(Some(type_id_script.clone()).pack())
+            .build(),
+        build_rc_rule_data(&rule_builder.root(), WHITE_BLACK_LIST_MASK),
+        None,
+    );
+
+    // the existing keys do not match the root
+    let builder = RceCellUpdateBuilder::new(
+        type_id_script.clone(),
+        existing_keys[..1].to_vec(),
+        vec![build_key(2)],
+        Vec::new(),
+    );
+    assert!(builder
+        .build_base(&mut ctx.to_live_cells_context(), &ctx, &ctx, &ctx)
+        .is_err());
+
+    let builder = RceCellUpdateBuilder::new(
+        type_id_script.clone(),
+        existing_keys,
+        vec![build_key(2)],
+        vec![build_key(0)],
+    );
+    let (tx, locked_groups) = builder
+        .build_unlocked(
+            &mut ctx.to_live_cells_context(),
+            &ctx,
+            &ctx,
+            &ctx,
+            &build_sighash_balancer(&sender),
+            &build_sighash_unlockers(&[ACCOUNT1_KEY]),
+        )
+        .unwrap();
+    assert!(locked_groups.is_empty());
+    assert_eq!(tx.inputs().get(0).unwrap(), rc_input);
+    let output = tx.output(0).unwrap();
+    assert_eq!(output.type_().to_opt(), Some(type_id_script));
+    let (new_root, flags) =
+        parse_rc_rule_data(&tx.outputs_data().get(0).unwrap().raw_data()).unwrap();
+    assert_eq!(new_root, builder.new_root());
+    assert_eq!(flags, WHITE_BLACK_LIST_MASK);
+
+    let mut expected_builder = RcRuleDataBuilder::new(ListType::White, false);
+    expected_builder.update_hashes(&[build_key(1), build_key(2)]);
+    assert_eq!(new_root, expected_builder.root());
+
+    // inclusion and exclusion proofs against the new root
+    let keys = [build_key(0), build_key(1), build_key(2), build_key(3)];
+    assert_eq!(builder.value_of(&keys[0]), *SMT_NOT_EXISTING);
+    assert_eq!(builder.value_of(&keys[2]), *SMT_EXISTING);
+    let proof = CompiledMerkleProof(builder.proof(&keys).unwrap());
+    let leaves = keys
+        .iter()
+        .map(|key| (*key, builder.value_of(key)))
+        .collect::<Vec<_>>();
+    assert!(proof
+        .verify::<CKBBlake2bHasher>(&new_root, leaves.clone())
+        .unwrap());
+    assert!(!proof
+        .verify::<CKBBlake2bHasher>(&rule_builder.root(), leaves)
+        .unwrap());
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
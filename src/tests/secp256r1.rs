@@ -0,0 +1,27 @@
+use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+
+use crate::traits::{MessageSigner, Secp256r1Signer};
+
+#[test]
+fn test_secp256r1_signer() {
+    let signer = Secp256r1Signer::from_secret_key(&[0x42u8; 32]).unwrap();
+    assert_eq!(
+        hex::encode(signer.public_key()),
+        "3ad3861a95621392516bb593ef05583ed2e5866f5cb6260a3017237fd89b90afd0961c7e37075a6791a39c61f56295b02b6d26567b615e60aa41ee1c8e83388d"
+    );
+    assert_eq!(
+        signer.pubkey_hash().as_bytes(),
+        &ckb_hash::blake2b_256(signer.raw_public_key())[0..20]
+    );
+
+    let message: Vec<u8> = (0u8..32).collect();
+    let signature = signer.sign_message(&message).unwrap();
+    let signature = Signature::from_der(&signature).unwrap();
+    let mut sec1_pubkey = vec![0x04u8];
+    sec1_pubkey.extend_from_slice(&signer.raw_public_key());
+    let verifying_key = VerifyingKey::from_sec1_bytes(&sec1_pubkey).unwrap();
+    verifying_key.verify(&message, &signature).unwrap();
+    assert!(verifying_key.verify(&[0u8; 32], &signature).is_err());
+
+    assert!(Secp256r1Signer::from_secret_key(&[0u8; 32]).is_err());
+}
@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+
+use ckb_hash::blake2b_256;
+use ckb_types::{
+    bytes::Bytes,
+    core::ScriptHashType,
+    packed::{Script, WitnessArgs},
+    prelude::*,
+    H160, H256,
+};
+
+use crate::{
+    constants::{MULTISIG_TYPE_HASH, SIGHASH_TYPE_HASH},
+    tests::{
+        build_multisig_script, build_sighash_script, build_unsigned, build_unsigned_with,
+        ACCOUNT0_ARG, ACCOUNT0_KEY, ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG, ACCOUNT2_KEY,
+        FEE_RATE,
+    },
+    traits::{SecpCkbRawKeySigner, Signer},
+    tx_builder::unlock_tx,
+    types::ScriptHashTypeExt,
+    unlock::{
+        attach_signature, compute_sighash_messages, verify_signature_against_message,
+        MultisigConfig, OmniLockConfig, OmniUnlockMode, ScriptSignError, ScriptUnlocker,
+        SecpSighashUnlocker, SignAlgorithm, SigningDescriptor, UnlockError, WitnessLayout,
+    },
+    util::{blake160, serialize_signature},
+    ScriptId, SECP256K1,
+};
+
+const OMNILOCK_BIN: &[u8] = include_bytes!("../test-data/omni_lock");
+
+/// Sign the message outside of the SDK, like a hardware wallet does
+fn external_sign(key: &H256, message: &H256) -> Bytes {
+    let key = secp256k1::SecretKey::from_slice(key.as_bytes()).unwrap();
+    let message = secp256k1::Message::from_digest_slice(message.as_bytes()).unwrap();
+    let signature = SECP256K1.sign_ecdsa_recoverable(&message, &key);
+    Bytes::from(serialize_signature(&signature).to_vec())
+}
+
+#[test]
+fn test_sighash_message() {
+    let placeholder_witness = WitnessArgs::new_builder()
+        .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+        .build();
+    let (ctx, tx) = build_unsigned(build_sighash_script(ACCOUNT1_ARG), placeholder_witness);
+    let mut unlock_configs = HashMap::new();
+    unlock_configs.insert(
+        ScriptId::new_type(SIGHASH_TYPE_HASH.clone()),
+        SigningDescriptor::Sighash,
+    );
+
+    let messages = compute_sighash_messages(&tx, &ctx, &unlock_configs).unwrap();
+    assert_eq!(messages.len(), 1);
+    let group = &messages[0];
+    assert_eq!(group.script_group.input_indices, vec![0, 1]);
+    assert_eq!(group.algorithm, SignAlgorithm::Secp256k1Blake160);
+
+    let signature = external_sign(&ACCOUNT1_KEY, &group.message);
+    verify_signature_against_message(&group.message, &signature, group.algorithm, &ACCOUNT1_ARG)
+        .unwrap();
+    // signed by another key
+    assert!(matches!(
+        verify_signature_against_message(
+            &group.message,
+            &signature,
+            group.algorithm,
+            &ACCOUNT0_ARG
+        ),
+        Err(ScriptSignError::InvalidSignature(_))
+    ));
+
+    let signed_tx = attach_signature(
+        &tx,
+        &group.script_group,
+        signature,
+        WitnessLayout::SighashLock,
+    )
+    .unwrap();
+    // the same as signed by the sighash unlocker
+    let key = secp256k1::SecretKey::from_slice(ACCOUNT1_KEY.as_bytes()).unwrap();
+    let unlocker = SecpSighashUnlocker::from(Box::new(SecpCkbRawKeySigner::new_with_secret_keys(
+        vec![key],
+    )) as Box<_>);
+    let mut unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>> = HashMap::default();
+    unlockers.insert(
+        ScriptId::new_type(SIGHASH_TYPE_HASH.clone()),
+        Box::new(unlocker),
+    );
+    let (unlocked_tx, _) = unlock_tx(tx, &ctx, &unlockers).unwrap();
+    assert_eq!(signed_tx.witnesses(), unlocked_tx.witnesses());
+    ctx.verify(signed_tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_multisig_message() {
+    let cfg = MultisigConfig::new_with(
+        vec![
+            ACCOUNT0_ARG.clone(),
+            ACCOUNT1_ARG.clone(),
+            ACCOUNT2_ARG.clone(),
+        ],
+        0,
+        2,
+    )
+    .unwrap();
+    let (ctx, mut tx) = build_unsigned(build_multisig_script(&cfg), cfg.placeholder_witness());
+    let mut unlock_configs = HashMap::new();
+    unlock_configs.insert(
+        ScriptId::new_type(MULTISIG_TYPE_HASH.clone()),
+        SigningDescriptor::Multisig {
+            config: cfg.clone(),
+        },
+    );
+
+    let messages = compute_sighash_messages(&tx, &ctx, &unlock_configs).unwrap();
+    assert_eq!(messages.len(), 1);
+    let group = &messages[0];
+    for (key, arg) in [
+        (&ACCOUNT0_KEY, &ACCOUNT0_ARG),
+        (&ACCOUNT1_KEY, &ACCOUNT1_ARG),
+    ] {
+        let signature = external_sign(key, &group.message);
+        verify_signature_against_message(&group.message, &signature, group.algorithm, arg).unwrap();
+        let layout = WitnessLayout::MultisigSlot {
+            config: cfg.clone(),
+            pubkey_hash: arg.clone(),
+        };
+        tx = attach_signature(&tx, &group.script_group, signature, layout).unwrap();
+        // the message does not change with the signatures
+        let messages = compute_sighash_messages(&tx, &ctx, &unlock_configs).unwrap();
+        assert_eq!(messages[0].message, group.message);
+    }
+    // no empty slot for the third signature
+    let signature = external_sign(&ACCOUNT2_KEY, &group.message);
+    let layout = WitnessLayout::MultisigSlot {
+        config: cfg.clone(),
+        pubkey_hash: ACCOUNT2_ARG.clone(),
+    };
+    assert!(matches!(
+        attach_signature(&tx, &group.script_group, signature, layout),
+        Err(UnlockError::ScriptSigner(
+            ScriptSignError::TooManySignatures
+        ))
+    ));
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_attach_signature_keeps_witness_fields() {
+    let input_type = Bytes::from(vec![0x42u8; 10]);
+    let placeholder_witness = WitnessArgs::new_builder()
+        .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+        .input_type(Some(input_type.clone()).pack())
+        .build();
+    let (ctx, tx) = build_unsigned(build_sighash_script(ACCOUNT1_ARG), placeholder_witness);
+    let mut unlock_configs = HashMap::new();
+    unlock_configs.insert(
+        ScriptId::new_type(SIGHASH_TYPE_HASH.clone()),
+        SigningDescriptor::Sighash,
+    );
+    let group = compute_sighash_messages(&tx, &ctx, &unlock_configs)
+        .unwrap()
+        .remove(0);
+
+    let key = secp256k1::SecretKey::from_slice(ACCOUNT1_KEY.as_bytes()).unwrap();
+    let signer = SecpCkbRawKeySigner::new_with_secret_keys(vec![key]);
+    let signature = signer
+        .sign(ACCOUNT1_ARG.as_bytes(), group.message.as_bytes(), true, &tx)
+        .unwrap();
+    // not a recoverable signature
+    assert!(matches!(
+        attach_signature(
+            &tx,
+            &group.script_group,
+            signature.slice(0..64),
+            WitnessLayout::SighashLock
+        ),
+        Err(UnlockError::ScriptSigner(
+            ScriptSignError::InvalidSignature(_)
+        ))
+    ));
+
+    let signed_tx = attach_signature(
+        &tx,
+        &group.script_group,
+        signature.clone(),
+        WitnessLayout::SighashLock,
+    )
+    .unwrap();
+    let witness =
+        WitnessArgs::from_slice(&signed_tx.witnesses().get(0).unwrap().raw_data()).unwrap();
+    assert_eq!(witness.lock().to_opt().unwrap().raw_data(), signature);
+    assert_eq!(
+        witness.input_type().to_opt().unwrap().raw_data(),
+        input_type
+    );
+    // the witness of the other input is untouched
+    assert_eq!(signed_tx.witnesses().get(1), tx.witnesses().get(1));
+    ctx.verify(signed_tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_attach_multisig_signature_of_wrong_key() {
+    let cfg =
+        MultisigConfig::new_with(vec![ACCOUNT0_ARG.clone(), ACCOUNT1_ARG.clone()], 0, 2).unwrap();
+    let (ctx, tx) = build_unsigned(build_multisig_script(&cfg), cfg.placeholder_witness());
+    let mut unlock_configs = HashMap::new();
+    unlock_configs.insert(
+        ScriptId::new_type(MULTISIG_TYPE_HASH.clone()),
+        SigningDescriptor::Multisig {
+            config: cfg.clone(),
+        },
+    );
+    let group = compute_sighash_messages(&tx, &ctx, &unlock_configs)
+        .unwrap()
+        .remove(0);
+    let layout = |pubkey_hash: &H160| WitnessLayout::MultisigSlot {
+        config: cfg.clone(),
+        pubkey_hash: pubkey_hash.clone(),
+    };
+
+    let signature0 = external_sign(&ACCOUNT0_KEY, &group.message);
+    assert!(matches!(
+        attach_signature(
+            &tx,
+            &group.script_group,
+            signature0.clone(),
+            layout(&ACCOUNT1_ARG)
+        ),
+        Err(UnlockError::ScriptSigner(
+            ScriptSignError::InvalidSignature(_)
+        ))
+    ));
+    let tx = attach_signature(
+        &tx,
+        &group.script_group,
+        signature0.clone(),
+        layout(&ACCOUNT0_ARG),
+    )
+    .unwrap();
+    // attached twice, the signature takes the same slot
+    let tx = attach_signature(&tx, &group.script_group, signature0, layout(&ACCOUNT0_ARG)).unwrap();
+    let signature1 = external_sign(&ACCOUNT1_KEY, &group.message);
+    let tx = attach_signature(&tx, &group.script_group, signature1, layout(&ACCOUNT1_ARG)).unwrap();
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_attach_omni_lock_signature() {
+    let key = secp256k1::SecretKey::from_slice(ACCOUNT0_KEY.as_bytes()).unwrap();
+    let pubkey = secp256k1::PublicKey::from_secret_key(&SECP256K1, &key);
+    let cfg = OmniLockConfig::new_pubkey_hash(blake160(&pubkey.serialize()));
+    let unlock_mode = OmniUnlockMode::Normal;
+    let sender = Script::new_builder()
+        .code_hash(H256::from(blake2b_256(OMNILOCK_BIN)).pack())
+        .hash_type(ScriptHashType::Data1.to_packed())
+        .args(cfg.build_args().pack())
+        .build();
+    let (ctx, tx) = build_unsigned_with(
+        vec![(OMNILOCK_BIN, true)],
+        sender.clone(),
+        cfg.placeholder_witness(unlock_mode).unwrap(),
+    );
+    let mut unlock_configs = HashMap::new();
+    unlock_configs.insert(
+        ScriptId::from(&sender),
+        SigningDescriptor::OmniLock {
+            config: cfg.clone(),
+            unlock_mode,
+        },
+    );
+    let group = compute_sighash_messages(&tx, &ctx, &unlock_configs)
+        .unwrap()
+        .remove(0);
+    assert_eq!(group.algorithm, SignAlgorithm::Secp256k1Blake160);
+
+    let signer = SecpCkbRawKeySigner::new_with_secret_keys(vec![key]);
+    let signature = signer
+        .sign(
+            cfg.id().auth_content().as_bytes(),
+            group.message.as_bytes(),
+            true,
+            &tx,
+        )
+        .unwrap();
+    let layout = WitnessLayout::OmniLock {
+        config: cfg.clone(),
+        unlock_mode,
+    };
+    let signed_tx = attach_signature(&tx, &group.script_group, signature, layout).unwrap();
+    assert_eq!(
+        signed_tx.witnesses().get(0).unwrap().raw_data().len(),
+        tx.witnesses().get(0).unwrap().raw_data().len()
+    );
+    ctx.verify(signed_tx, FEE_RATE).unwrap();
+}
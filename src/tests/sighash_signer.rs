@@ -0,0 +1,520 @@
+use ckb_hash::new_blake2b;
+use ckb_types::{
+    bytes::Bytes,
+    core::{TransactionBuilder, TransactionView},
+    packed::{self, CellInput, CellOutput, WitnessArgs},
+    prelude::*,
+    H256,
+};
+
+use crate::{
+    constants::{ONE_CKB, SIGHASH_TYPE_HASH},
+    test_util::{random_out_point, Context},
+    tests::{
+        build_sighash_balancer, build_sighash_script, build_sighash_unlockers, init_context,
+        ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG, ACCOUNT2_KEY, FEE_RATE,
+    },
+    traits::{CellDepResolver, SecpCkbRawKeySigner, TransactionDependencyProvider},
+    transaction::signer::{sighash::Secp256k1Blake160SighashAllSigner, TransactionSigner},
+    tx_builder::{
+        balance_tx_capacity, fill_placeholder_witnesses, gen_script_groups,
+        transfer::CapacityTransferBuilder, unlock_tx, unlock_tx_checked, unlock_tx_detailed,
+        TxBuilder, UnlockGroupStatus,
+    },
+    unlock::{
+        build_unlockers, fill_witness_lock, generate_message, reset_witness_lock, RegistryError,
+        ScriptUnlocker, ScriptUnlockerManager, SecpSighashUnlocker, UnlockError,
+    },
+    NetworkInfo, ScriptGroup, ScriptId,
+};
+
+/// The message hashing of the sighash-all lock script, written out step by
+/// step as an independent reference for `generate_message`.
+fn reference_message(tx: &TransactionView, input_indices: &[usize]) -> [u8; 32] {
+    let witnesses: Vec<Bytes> = tx.witnesses().into_iter().map(|w| w.raw_data()).collect();
+    let first_witness = WitnessArgs::from_slice(&witnesses[input_indices[0]]).unwrap();
+    let first_witness = first_witness
+        .as_builder()
+        .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+        .build()
+        .as_bytes();
+
+    let mut blake2b = new_blake2b();
+    blake2b.update(tx.hash().as_slice());
+    blake2b.update(&(first_witness.len() as u64).to_le_bytes());
+    blake2b.update(&first_witness);
+    for idx in &input_indices[1..] {
+        if let Some(witness) = witnesses.get(*idx) {
+            blake2b.update(&(witness.len() as u64).to_le_bytes());
+            blake2b.update(witness);
+        }
+    }
+    for witness in witnesses.iter().skip(tx.inputs().len()) {
+        blake2b.update(&(witness.len() as u64).to_le_bytes());
+        blake2b.update(witness);
+    }
+    let mut message = [0u8; 32];
+    blake2b.finalize(&mut message);
+    message
+}
+
+fn add_input(ctx: &mut Context, lock: &packed::Script, capacity: u64) -> CellInput {
+    let input = CellInput::new(random_out_point(), 0);
+    let output = CellOutput::new_builder()
+        .capacity(capacity.pack())
+        .lock(lock.clone())
+        .build();
+    ctx.add_live_cell(input.clone(), output, Bytes::default(), None);
+    input
+}
+
+/// Build a transaction with a sighash group of `group_size` inputs which is not
+/// the first input and is interleaved with another sighash group, plus
+/// `extra_witnesses` witnesses not covered by any input.
+fn build_tx(ctx: &mut Context, group_size: usize, extra_witnesses: usize) -> TransactionView {
+    let lock1 = build_sighash_script(ACCOUNT1_ARG);
+    let lock2 = build_sighash_script(ACCOUNT2_ARG);
+    let placeholder_witness = WitnessArgs::new_builder()
+        .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+        .build();
+
+    let mut inputs = vec![add_input(ctx, &lock2, 100 * ONE_CKB)];
+    let mut witnesses = vec![placeholder_witness.as_bytes().pack()];
+    for idx in 0..group_size {
+        inputs.push(add_input(ctx, &lock1, 100 * ONE_CKB));
+        let witness = if idx == 0 {
+            placeholder_witness
+                .clone()
+                .as_builder()
+                .input_type(Some(Bytes::from(vec![1u8; 3])).pack())
+                .build()
+                .as_bytes()
+        } else if idx % 2 == 0 {
+            Bytes::from(vec![idx as u8; idx])
+        } else {
+            Bytes::default()
+        };
+        witnesses.push(witness.pack());
+        if idx == 0 {
+            inputs.push(add_input(ctx, &lock2, 100 * ONE_CKB));
+            witnesses.push(Bytes::default().pack());
+        }
+    }
+    for idx in 0..extra_witnesses {
+        witnesses.push(Bytes::from(vec![0xee; idx * 7 + 1]).pack());
+    }
+    let capacity = (100 * (group_size as u64 + 2) - 1) * ONE_CKB;
+    let output = CellOutput::new_builder()
+        .capacity(capacity.pack())
+        .lock(lock2)
+        .build();
+    TransactionBuilder::default()
+        .cell_dep(ctx.resolve(&build_sighash_script(ACCOUNT1_ARG)).unwrap())
+        .set_inputs(inputs)
+        .output(output)
+        .output_data(Bytes::default().pack())
+        .set_witnesses(witnesses)
+        .build()
+}
+
+fn sighash_group(tx: &TransactionView, ctx: &Context, arg: &[u8]) -> ScriptGroup {
+    gen_script_groups(tx, ctx)
+        .unwrap()
+        .lock_groups
+        .into_values()
+        .find(|group| group.script.args().raw_data().as_ref() == arg)
+        .unwrap()
+}
+
+#[test]
+fn test_sighash_signer_message_matrix() {
+    let unlockers = build_sighash_unlockers(&[ACCOUNT1_KEY, ACCOUNT2_KEY]);
+
+    for group_size in [1, 2, 5] {
+        for extra_witnesses in [0, 1, 3] {
+            let mut ctx = init_context(Vec::new(), Vec::new());
+            let tx = build_tx(&mut ctx, group_size, extra_witnesses);
+            assert_eq!(tx.witnesses().len(), tx.inputs().len() + extra_witnesses);
+            let group = sighash_group(&tx, &ctx, ACCOUNT1_ARG.as_bytes());
+            assert_eq!(group.input_indices.len(), group_size);
+            assert_ne!(group.input_indices[0], 0);
+
+            let message = generate_message(&tx, &group, Bytes::from(vec![0u8; 65])).unwrap();
+            assert_eq!(
+                message.as_ref(),
+                &reference_message(&tx, &group.input_indices)[..],
+                "group_size: {}, extra_witnesses: {}",
+                group_size,
+                extra_witnesses
+            );
+
+            let (tx, locked_groups) = unlock_tx(tx, &ctx, &unlockers).unwrap();
+            assert!(locked_groups.is_empty());
+            // the extra witnesses are kept as is
+            let witnesses = tx.witnesses();
+            for idx in 0..extra_witnesses {
+                assert_eq!(
+                    witnesses.get(tx.inputs().len() + idx).unwrap().raw_data(),
+                    Bytes::from(vec![0xee; idx * 7 + 1])
+                );
+            }
+            ctx.verify_scripts(tx).unwrap_or_else(|err| {
+                panic!(
+                    "group_size: {}, extra_witnesses: {}, error: {}",
+                    group_size, extra_witnesses, err
+                )
+            });
+        }
+    }
+}
+
+fn build_sighash_unlocker(key: &H256) -> Box<dyn ScriptUnlocker> {
+    let key = secp256k1::SecretKey::from_slice(key.as_bytes()).unwrap();
+    let signer = SecpCkbRawKeySigner::new_with_secret_keys(vec![key]);
+    Box::new(SecpSighashUnlocker::from(Box::new(signer) as Box<_>))
+}
+
+#[test]
+fn test_build_unlockers_duplicate() {
+    let sighash_id = ScriptId::new_type(SIGHASH_TYPE_HASH.clone());
+    let unlockers = build_unlockers(vec![(
+        sighash_id.clone(),
+        build_sighash_unlocker(&ACCOUNT1_KEY),
+    )])
+    .unwrap();
+    assert!(unlockers.contains_key(&sighash_id));
+
+    let err = build_unlockers(vec![
+        (sighash_id.clone(), build_sighash_unlocker(&ACCOUNT1_KEY)),
+        (sighash_id.clone(), build_sighash_unlocker(&ACCOUNT2_KEY)),
+    ])
+    .unwrap_err();
+    assert_eq!(err, RegistryError::Duplicate(sighash_id.clone()));
+
+    let mut signer = TransactionSigner::new(&NetworkInfo::testnet());
+    assert_eq!(
+        signer
+            .try_register(
+                sighash_id.clone(),
+                Box::new(Secp256k1Blake160SighashAllSigner {})
+            )
+            .unwrap_err(),
+        RegistryError::Duplicate(sighash_id.clone())
+    );
+    assert!(signer
+        .register(sighash_id, Box::new(Secp256k1Blake160SighashAllSigner {}))
+        .is_some());
+    let other_id = ScriptId::new_data1(H256::default());
+    assert!(signer
+        .try_register(
+            other_id.clone(),
+            Box::new(Secp256k1Blake160SighashAllSigner {})
+        )
+        .is_ok());
+    assert!(signer
+        .register(other_id, Box::new(Secp256k1Blake160SighashAllSigner {}))
+        .is_some());
+}
+
+#[test]
+fn test_unlock_tx_args_mismatch() {
+    let sighash_id = ScriptId::new_type(SIGHASH_TYPE_HASH.clone());
+    // only has the key of account1, so it does not accept the args of account2
+    let unlockers = build_unlockers(vec![(
+        sighash_id.clone(),
+        build_sighash_unlocker(&ACCOUNT1_KEY),
+    )])
+    .unwrap();
+    let mut ctx = init_context(Vec::new(), Vec::new());
+    let tx = build_tx(&mut ctx, 1, 0);
+
+    let (_, locked_groups) = unlock_tx(tx.clone(), &ctx, &unlockers).unwrap();
+    assert_eq!(locked_groups.len(), 1);
+    assert_eq!(
+        locked_groups[0].script.args().raw_data().as_ref(),
+        ACCOUNT2_ARG.as_bytes()
+    );
+
+    match unlock_tx_checked(tx.clone(), &ctx, &unlockers) {
+        Err(UnlockError::UnlockerArgsMismatch { script_id }) => {
+            assert_eq!(script_id, sighash_id)
+        }
+        other => panic!("unexpected result: {:?}", other.map(|(_, groups)| groups)),
+    }
+
+    let unlockers = build_unlockers(vec![(sighash_id, {
+        let key1 = secp256k1::SecretKey::from_slice(ACCOUNT1_KEY.as_bytes()).unwrap();
+        let key2 = secp256k1::SecretKey::from_slice(ACCOUNT2_KEY.as_bytes()).unwrap();
+        let signer = SecpCkbRawKeySigner::new_with_secret_keys(vec![key1, key2]);
+        Box::new(SecpSighashUnlocker::from(Box::new(signer) as Box<_>)) as Box<_>
+    })])
+    .unwrap();
+    let (tx, locked_groups) = unlock_tx_checked(tx, &ctx, &unlockers).unwrap();
+    assert!(locked_groups.is_empty());
+    ctx.verify_scripts(tx).unwrap();
+}
+
+#[test]
+fn test_unlocker_manager_fallback() {
+    let sighash_id = ScriptId::new_type(SIGHASH_TYPE_HASH.clone());
+    let mut ctx = init_context(Vec::new(), Vec::new());
+    let tx = build_tx(&mut ctx, 1, 0);
+
+    // the fallback of another code hash is never used
+    let mut manager = ScriptUnlockerManager::new();
+    manager
+        .register_code_hash(H256::default(), build_sighash_unlocker(&ACCOUNT2_KEY))
+        .unwrap();
+    let (_, locked_groups) = unlock_tx(tx.clone(), &ctx, &manager).unwrap();
+    assert_eq!(locked_groups.len(), 2);
+
+    // account1 by the exact script id, account2 by the fallback of the code hash
+    let mut manager = ScriptUnlockerManager::new();
+    manager
+        .register(sighash_id.clone(), build_sighash_unlocker(&ACCOUNT1_KEY))
+        .unwrap();
+    manager
+        .register_code_hash(
+            SIGHASH_TYPE_HASH.clone(),
+            build_sighash_unlocker(&ACCOUNT2_KEY),
+        )
+        .unwrap();
+    assert_eq!(
+        manager
+            .register(sighash_id, build_sighash_unlocker(&ACCOUNT2_KEY))
+            .unwrap_err(),
+        RegistryError::Duplicate(ScriptId::new_type(SIGHASH_TYPE_HASH.clone()))
+    );
+    assert_eq!(
+        manager
+            .register_code_hash(
+                SIGHASH_TYPE_HASH.clone(),
+                build_sighash_unlocker(&ACCOUNT1_KEY)
+            )
+            .unwrap_err(),
+        RegistryError::DuplicateCodeHash(SIGHASH_TYPE_HASH.clone())
+    );
+
+    let (tx, _) = fill_placeholder_witnesses(tx, &ctx, &manager).unwrap();
+    let (tx, locked_groups) = unlock_tx_checked(tx, &ctx, &manager).unwrap();
+    assert!(locked_groups.is_empty());
+    ctx.verify_scripts(tx).unwrap();
+}
+
+fn witness_args(tx: &TransactionView, idx: usize) -> WitnessArgs {
+    WitnessArgs::from_slice(&tx.witnesses().get(idx).unwrap().raw_data()).unwrap()
+}
+
+fn set_witness(tx: &TransactionView, idx: usize, witness: &WitnessArgs) -> TransactionView {
+    let mut witnesses: Vec<packed::Bytes> = tx.witnesses().into_iter().collect();
+    witnesses[idx] = witness.as_bytes().pack();
+    tx.as_advanced_builder().set_witnesses(witnesses).build()
+}
+
+#[test]
+fn test_fill_witness_lock_keeps_type_fields() {
+    let mut ctx = init_context(Vec::new(), Vec::new());
+    let tx = build_tx(&mut ctx, 1, 0);
+    let group = sighash_group(&tx, &ctx, ACCOUNT1_ARG.as_bytes());
+    let witness_idx = group.input_indices[0];
+    let typed_witness = WitnessArgs::new_builder()
+        .input_type(Some(Bytes::from(vec![1u8; 3])).pack())
+        .output_type(Some(Bytes::from(vec![2u8; 5])).pack())
+        .build();
+    let tx = set_witness(&tx, witness_idx, &typed_witness);
+    let placeholder = Bytes::from(vec![0u8; 65]);
+
+    let filled_tx = fill_witness_lock(&tx, &group, placeholder.clone()).unwrap();
+    let witness = witness_args(&filled_tx, witness_idx);
+    assert_eq!(witness.lock().to_opt().unwrap().raw_data(), placeholder);
+    assert_eq!(witness.input_type(), typed_witness.input_type());
+    assert_eq!(witness.output_type(), typed_witness.output_type());
+    // filling the same placeholder again is a no-op
+    assert_eq!(
+        fill_witness_lock(&filled_tx, &group, placeholder.clone())
+            .unwrap()
+            .hash(),
+        filled_tx.hash()
+    );
+
+    let signed_witness = typed_witness
+        .clone()
+        .as_builder()
+        .lock(Some(Bytes::from(vec![3u8; 65])).pack())
+        .build();
+    let signed_tx = set_witness(&tx, witness_idx, &signed_witness);
+    assert!(matches!(
+        fill_witness_lock(&signed_tx, &group, placeholder),
+        Err(UnlockError::WitnessLockOccupied(idx)) if idx == witness_idx
+    ));
+
+    let reset_tx = reset_witness_lock(signed_tx, witness_idx).unwrap();
+    assert_eq!(
+        reset_tx.witnesses().get(witness_idx).unwrap().raw_data(),
+        typed_witness.as_bytes()
+    );
+}
+
+#[test]
+fn test_input_type_survives_pipeline() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let ctx = init_context(Vec::new(), vec![(sender.clone(), Some(300 * ONE_CKB))]);
+    let output = CellOutput::new_builder()
+        .capacity((120 * ONE_CKB).pack())
+        .lock(receiver)
+        .build();
+    let builder = CapacityTransferBuilder::new(vec![(output, Bytes::default())]);
+    let balancer = build_sighash_balancer(&sender);
+    let unlockers = build_unlockers(vec![(
+        ScriptId::new_type(SIGHASH_TYPE_HASH.clone()),
+        build_sighash_unlocker(&ACCOUNT1_KEY),
+    )])
+    .unwrap();
+
+    let mut cell_collector = ctx.to_live_cells_context();
+    let base_tx = builder
+        .build_base(&mut cell_collector, &ctx, &ctx, &ctx)
+        .unwrap();
+    // the witness of the first input is prepared before the inputs are collected
+    let input_type = Bytes::from(vec![7u8; 8]);
+    let base_tx = base_tx
+        .as_advanced_builder()
+        .witness(
+            WitnessArgs::new_builder()
+                .input_type(Some(input_type.clone()).pack())
+                .build()
+                .as_bytes()
+                .pack(),
+        )
+        .build();
+    let (tx, _) = fill_placeholder_witnesses(base_tx, &ctx, &unlockers).unwrap();
+    let tx = balance_tx_capacity(&tx, &balancer, &mut cell_collector, &ctx, &ctx, &ctx).unwrap();
+    let witness = witness_args(&tx, 0);
+    assert_eq!(witness.lock().to_opt().unwrap().raw_data().len(), 65);
+    assert_eq!(
+        witness.input_type().to_opt().unwrap().raw_data(),
+        input_type
+    );
+
+    let (tx, locked_groups) = unlock_tx(tx, &ctx, &unlockers).unwrap();
+    assert!(locked_groups.is_empty());
+    let witness = witness_args(&tx, 0);
+    assert_eq!(
+        witness.input_type().to_opt().unwrap().raw_data(),
+        input_type
+    );
+    assert!(witness.output_type().is_none());
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+/// Unlock by the inner unlocker, except the groups with `failed_args`
+struct FailingUnlocker {
+    inner: Box<dyn ScriptUnlocker>,
+    failed_args: Bytes,
+}
+
+impl ScriptUnlocker for FailingUnlocker {
+    fn match_args(&self, _args: &[u8]) -> bool {
+        true
+    }
+
+    fn unlock(
+        &self,
+        tx: &TransactionView,
+        script_group: &ScriptGroup,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, UnlockError> {
+        if script_group.script.args().raw_data() == self.failed_args {
+            return Err(UnlockError::SignContextTypeIncorrect);
+        }
+        self.inner.unlock(tx, script_group, tx_dep_provider)
+    }
+
+    fn fill_placeholder_witness(
+        &self,
+        tx: &TransactionView,
+        script_group: &ScriptGroup,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, UnlockError> {
+        self.inner
+            .fill_placeholder_witness(tx, script_group, tx_dep_provider)
+    }
+}
+
+#[test]
+fn test_unlock_tx_detailed() {
+    let sighash_id = ScriptId::new_type(SIGHASH_TYPE_HASH.clone());
+    let mut ctx = init_context(Vec::new(), Vec::new());
+    let tx = build_tx(&mut ctx, 2, 0);
+    let group1 = sighash_group(&tx, &ctx, ACCOUNT1_ARG.as_bytes());
+    let group2 = sighash_group(&tx, &ctx, ACCOUNT2_ARG.as_bytes());
+
+    // the unlocker only has the key of account1
+    let unlockers = build_unlockers(vec![(
+        sighash_id.clone(),
+        build_sighash_unlocker(&ACCOUNT1_KEY),
+    )])
+    .unwrap();
+    let (_, results) = unlock_tx_detailed(tx.clone(), &ctx, &unlockers).unwrap();
+    assert_eq!(results.len(), 2);
+    for result in &results {
+        if result.script_group == group1 {
+            assert!(matches!(result.status, UnlockGroupStatus::Unlocked));
+        } else {
+            assert_eq!(result.script_group, group2);
+            match &result.status {
+                UnlockGroupStatus::NoUnlockerMatched { script_id, args } => {
+                    assert_eq!(script_id, &sighash_id);
+                    assert_eq!(args.as_ref(), ACCOUNT2_ARG.as_bytes());
+                }
+                other => panic!("unexpected status: {:?}", other),
+            }
+        }
+    }
+
+    // the unlocker fails on account2, the unlock of account1 is still applied
+    let failing_unlocker = FailingUnlocker {
+        inner: {
+            let key1 = secp256k1::SecretKey::from_slice(ACCOUNT1_KEY.as_bytes()).unwrap();
+            let key2 = secp256k1::SecretKey::from_slice(ACCOUNT2_KEY.as_bytes()).unwrap();
+            let signer = SecpCkbRawKeySigner::new_with_secret_keys(vec![key1, key2]);
+            Box::new(SecpSighashUnlocker::from(Box::new(signer) as Box<_>))
+        },
+        failed_args: Bytes::from(ACCOUNT2_ARG.as_bytes().to_vec()),
+    };
+    let unlockers = build_unlockers(vec![(
+        sighash_id.clone(),
+        Box::new(failing_unlocker) as Box<_>,
+    )])
+    .unwrap();
+    assert!(unlock_tx(tx.clone(), &ctx, &unlockers).is_err());
+    let (partial_tx, results) = unlock_tx_detailed(tx.clone(), &ctx, &unlockers).unwrap();
+    let failed = results
+        .iter()
+        .filter(|result| !result.is_unlocked())
+        .collect::<Vec<_>>();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].script_group, group2);
+    assert!(matches!(
+        failed[0].status,
+        UnlockGroupStatus::Failed(UnlockError::SignContextTypeIncorrect)
+    ));
+    let witness_idx = group1.input_indices[0];
+    assert_ne!(
+        partial_tx.witnesses().get(witness_idx),
+        tx.witnesses().get(witness_idx)
+    );
+    for idx in &group2.input_indices {
+        assert_eq!(partial_tx.witnesses().get(*idx), tx.witnesses().get(*idx));
+    }
+
+    // retry the failed group only
+    let unlockers =
+        build_unlockers(vec![(sighash_id, build_sighash_unlocker(&ACCOUNT2_KEY))]).unwrap();
+    let (tx, results) = unlock_tx_detailed(partial_tx, &ctx, &unlockers).unwrap();
+    assert!(results
+        .iter()
+        .all(|result| result.is_unlocked() || result.script_group == group1));
+    ctx.verify_scripts(tx).unwrap();
+}
@@ -0,0 +1,135 @@
+use ckb_types::{bytes::Bytes, packed::WitnessArgs, prelude::*, H256};
+
+use crate::{
+    constants::{MULTISIG_TYPE_HASH, SIGHASH_TYPE_HASH},
+    test_util::Context,
+    tests::{
+        build_multisig_script, build_sighash_script, build_unsigned, ACCOUNT0_ARG, ACCOUNT0_KEY,
+        ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG, ACCOUNT2_KEY, FEE_RATE,
+    },
+    traits::SecpCkbRawKeySigner,
+    unlock::{
+        MultisigConfig, SignatureStatus, SigningDescriptor, SigningPackage, SigningPackageError,
+        SIGNING_PACKAGE_VERSION,
+    },
+    ScriptId,
+};
+
+fn signer(key: &H256) -> Box<SecpCkbRawKeySigner> {
+    let key = secp256k1::SecretKey::from_slice(key.as_bytes()).unwrap();
+    Box::new(SecpCkbRawKeySigner::new_with_secret_keys(vec![key]))
+}
+
+fn sighash_package() -> (Context, SigningPackage) {
+    let placeholder_witness = WitnessArgs::new_builder()
+        .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+        .build();
+    let (ctx, tx) = build_unsigned(build_sighash_script(ACCOUNT1_ARG), placeholder_witness);
+    let descriptors = vec![(
+        ScriptId::new_type(SIGHASH_TYPE_HASH.clone()),
+        SigningDescriptor::Sighash,
+    )];
+    let package = SigningPackage::new(&tx, &ctx, descriptors).unwrap();
+    (ctx, package)
+}
+
+#[test]
+fn test_signing_package_sighash() {
+    let (ctx, mut package) = sighash_package();
+    assert_eq!(package.version, SIGNING_PACKAGE_VERSION);
+    assert_eq!(package.inputs.len(), 2);
+    assert_eq!(package.groups.len(), 1);
+    assert_eq!(package.groups[0].input_indices, vec![0, 1]);
+    assert_eq!(package.groups[0].status, SignatureStatus::Unsigned);
+
+    // a key of no input
+    assert_eq!(package.sign_with(signer(&ACCOUNT2_KEY)).unwrap(), 0);
+    assert!(matches!(
+        package.finalize(),
+        Err(SigningPackageError::Unsigned(groups)) if groups.len() == 1
+    ));
+
+    let json = serde_json::to_string(&package).unwrap();
+    let mut package: SigningPackage = serde_json::from_str(&json).unwrap();
+    assert_eq!(package.sign_with(signer(&ACCOUNT1_KEY)).unwrap(), 1);
+    assert_eq!(package.groups[0].status, SignatureStatus::Signed);
+    // a signed group is not signed again
+    assert_eq!(package.sign_with(signer(&ACCOUNT1_KEY)).unwrap(), 0);
+    let tx = package.finalize().unwrap();
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_signing_package_multisig_merge() {
+    let cfg = MultisigConfig::new_with(
+        vec![
+            ACCOUNT0_ARG.clone(),
+            ACCOUNT1_ARG.clone(),
+            ACCOUNT2_ARG.clone(),
+        ],
+        0,
+        2,
+    )
+    .unwrap();
+    let (ctx, tx) = build_unsigned(build_multisig_script(&cfg), cfg.placeholder_witness());
+    let descriptors = vec![(
+        ScriptId::new_type(MULTISIG_TYPE_HASH.clone()),
+        SigningDescriptor::Multisig {
+            config: cfg.clone(),
+        },
+    )];
+    let package = SigningPackage::new(&tx, &ctx, descriptors).unwrap();
+    let json = serde_json::to_string_pretty(&package).unwrap();
+
+    // two signers sign their own copies in parallel
+    let mut package0: SigningPackage = serde_json::from_str(&json).unwrap();
+    let mut package2: SigningPackage = serde_json::from_str(&json).unwrap();
+    assert_eq!(package0.sign_with(signer(&ACCOUNT0_KEY)).unwrap(), 1);
+    assert_eq!(package2.sign_with(signer(&ACCOUNT2_KEY)).unwrap(), 1);
+    let partial = SignatureStatus::Partial {
+        signatures: 1,
+        threshold: 2,
+    };
+    assert_eq!(package0.groups[0].status, partial);
+    assert_eq!(package2.groups[0].status, partial);
+    match package0.finalize() {
+        Err(SigningPackageError::Unsigned(groups)) => {
+            assert_eq!(groups, vec![package0.groups[0].lock_hash.clone()]);
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    // merging a package into itself changes nothing
+    let copy = package0.clone();
+    package0.merge(&copy).unwrap();
+    assert_eq!(package0, copy);
+
+    package0.merge(&package2).unwrap();
+    assert_eq!(package0.groups[0].status, SignatureStatus::Signed);
+    let tx = package0.finalize().unwrap();
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_signing_package_merge_errors() {
+    let (_, mut package) = sighash_package();
+    let (_, other) = sighash_package();
+    assert!(matches!(
+        package.merge(&other),
+        Err(SigningPackageError::TransactionMismatch(_, _))
+    ));
+
+    let mut other = package.clone();
+    other.version = SIGNING_PACKAGE_VERSION + 1;
+    assert!(matches!(
+        package.merge(&other),
+        Err(SigningPackageError::UnsupportedVersion(version)) if version == SIGNING_PACKAGE_VERSION + 1
+    ));
+
+    let mut other = package.clone();
+    other.inputs.pop();
+    assert!(matches!(
+        other.finalize(),
+        Err(SigningPackageError::InvalidPackage(_))
+    ));
+}
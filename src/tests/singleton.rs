@@ -0,0 +1,301 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use ckb_jsonrpc_types::{Status, Transaction};
+use ckb_types::{
+    bytes::Bytes,
+    core::{ScriptHashType, TransactionView},
+    packed::{self, CellOutput, OutPoint, Script},
+    prelude::*,
+    H256,
+};
+
+use crate::{
+    constants::{ONE_CKB, TYPE_ID_CODE_HASH},
+    rpc::{SendTransactionRpc, TransactionSubmitError},
+    test_util::{random_out_point, Context},
+    tests::{build_sighash_script, init_context, ACCOUNT1_ARG},
+    traits::{CellCollector, CellCollectorError, CellQueryOptions, LiveCell},
+    tx_builder::{
+        singleton::{RetryBackoff, SingletonCellUpdater, SingletonError, SingletonHandle},
+        TxBuilder,
+    },
+    types::ScriptHashTypeExt,
+    RpcError,
+};
+
+/// The live cells on the chain, shared by the mock collector and rpc
+#[derive(Default)]
+struct MockChain {
+    cells: Vec<LiveCell>,
+    block_number: u64,
+}
+
+impl MockChain {
+    /// Commit the transaction if all the inputs are live
+    fn commit(&mut self, tx: &TransactionView) -> Result<(), OutPoint> {
+        for out_point in tx.input_pts_iter() {
+            if !self.cells.iter().any(|cell| cell.out_point == out_point) {
+                return Err(out_point);
+            }
+        }
+        self.cells
+            .retain(|cell| !tx.input_pts_iter().any(|op| op == cell.out_point));
+        self.block_number += 1;
+        for (idx, (output, data)) in tx.outputs_with_data_iter().enumerate() {
+            self.cells.push(LiveCell::new(
+                output,
+                data,
+                OutPoint::new(tx.hash(), idx as u32),
+                self.block_number,
+                1,
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+struct MockCollector {
+    chain: Rc<RefCell<MockChain>>,
+}
+
+impl CellCollector for MockCollector {
+    fn collect_live_cells(
+        &mut self,
+        query: &CellQueryOptions,
+        _apply_changes: bool,
+    ) -> Result<(Vec<LiveCell>, u64), CellCollectorError> {
+        let cells = self
+            .chain
+            .borrow()
+            .cells
+            .iter()
+            .filter(|cell| query.match_cell(cell, 0))
+            .cloned()
+            .collect::<Vec<_>>();
+        let total = cells
+            .iter()
+            .map(|cell| Unpack::<u64>::unpack(&cell.output.capacity()))
+            .sum();
+        Ok((cells, total))
+    }
+    fn lock_cell(&mut self, _: OutPoint, _: u64) -> Result<(), CellCollectorError> {
+        Ok(())
+    }
+    fn apply_tx(&mut self, _: packed::Transaction, _: u64) -> Result<(), CellCollectorError> {
+        Ok(())
+    }
+    fn reset(&mut self) {}
+}
+
+/// Before handling a send, the rpc commits the next scripted competitor
+/// update. With `lose_response` set, our transaction is committed but the
+/// response is lost.
+struct MockRpc {
+    chain: Rc<RefCell<MockChain>>,
+    competitors: RefCell<Vec<Bytes>>,
+    lose_response: Cell<bool>,
+    sent: RefCell<Vec<H256>>,
+}
+
+impl MockRpc {
+    fn new(chain: Rc<RefCell<MockChain>>, competitors: Vec<Bytes>) -> MockRpc {
+        MockRpc {
+            chain,
+            competitors: RefCell::new(competitors),
+            lose_response: Cell::new(false),
+            sent: RefCell::new(Vec::new()),
+        }
+    }
+}
+
+impl SendTransactionRpc for MockRpc {
+    fn get_tip_block_number(&self) -> Result<u64, RpcError> {
+        Ok(self.chain.borrow().block_number)
+    }
+    fn get_transaction_status(&self, _: H256, _: bool) -> Result<Status, RpcError> {
+        Ok(Status::Unknown)
+    }
+    fn send_transaction(&self, tx: Transaction) -> Result<H256, TransactionSubmitError> {
+        let tx = packed::Transaction::from(tx).into_view();
+        if !self.competitors.borrow().is_empty() {
+            let data = self.competitors.borrow_mut().remove(0);
+            let mut chain = self.chain.borrow_mut();
+            let cell = chain.cells[0].clone();
+            let competitor_tx = TransactionView::new_advanced_builder()
+                .input(packed::CellInput::new(cell.out_point, 0))
+                .output(cell.output)
+                .output_data(data.pack())
+                .build();
+            chain.commit(&competitor_tx).unwrap();
+        }
+        self.chain
+            .borrow_mut()
+            .commit(&tx)
+            .map_err(TransactionSubmitError::DeadOutPoint)?;
+        let tx_hash: H256 = tx.hash().unpack();
+        self.sent.borrow_mut().push(tx_hash.clone());
+        if self.lose_response.get() {
+            return Err(TransactionSubmitError::Rpc(RpcError::Other(
+                anyhow::anyhow!("timeout"),
+            )));
+        }
+        Ok(tx_hash)
+    }
+}
+
+fn init_singleton(counter: u64) -> (Context, SingletonCellUpdater, Rc<RefCell<MockChain>>) {
+    let ctx = init_context(Vec::new(), Vec::new());
+    let type_script = Script::new_builder()
+        .code_hash(TYPE_ID_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.to_packed())
+        .args(Bytes::from(vec![7u8; 32]).pack())
+        .build();
+    let output = CellOutput::new_builder()
+        .capacity((200 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT1_ARG))
+        .type_(Some(type_script.clone()).pack())
+        .build();
+    let chain = MockChain {
+        cells: vec![LiveCell::new(
+            output,
+            Bytes::from(counter.to_le_bytes().to_vec()),
+            random_out_point(),
+            0,
+            0,
+        )],
+        block_number: 0,
+    };
+    let mut updater = SingletonCellUpdater::new(type_script);
+    updater.set_backoff(RetryBackoff::none());
+    (ctx, updater, Rc::new(RefCell::new(chain)))
+}
+
+fn read_counter(data: &Bytes) -> u64 {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(&data[0..8]);
+    u64::from_le_bytes(buf)
+}
+
+// Increase the counter in the config cell (compare-and-swap on the fetched version)
+fn increase<'a>(
+    updater: &'a SingletonCellUpdater,
+    ctx: &'a Context,
+    seen: &'a RefCell<Vec<u64>>,
+) -> impl FnMut(&SingletonHandle) -> Result<TransactionView, anyhow::Error> + 'a {
+    move |handle| {
+        let counter = read_counter(&handle.data);
+        seen.borrow_mut().push(counter);
+        let new_data = Bytes::from((counter + 1).to_le_bytes().to_vec());
+        let tx = updater.build_update(handle, new_data).build_base(
+            &mut ctx.to_live_cells_context(),
+            ctx,
+            ctx,
+            ctx,
+        )?;
+        Ok(tx)
+    }
+}
+
+#[test]
+fn test_singleton_update_race() {
+    let (ctx, updater, chain) = init_singleton(0);
+    let mut collector = MockCollector {
+        chain: chain.clone(),
+    };
+    let handle = updater.fetch(&mut collector).unwrap();
+    assert_eq!(read_counter(&handle.data), 0);
+    assert_eq!(handle.version_hint, 0);
+
+    // Another service updates the counter to 10 before our first submission
+    let rpc = MockRpc::new(
+        chain.clone(),
+        vec![Bytes::from(10u64.to_le_bytes().to_vec())],
+    );
+    let seen = RefCell::new(Vec::new());
+    let tx_hash = updater
+        .submit_with_retry(&mut collector, &rpc, increase(&updater, &ctx, &seen), 3)
+        .unwrap();
+    assert_eq!(seen.into_inner(), vec![0, 10]);
+    assert_eq!(rpc.sent.borrow().as_slice(), &[tx_hash.clone()]);
+
+    let handle = updater.fetch(&mut collector).unwrap();
+    assert_eq!(read_counter(&handle.data), 11);
+    assert_eq!(handle.out_point, OutPoint::new(tx_hash.pack(), 0));
+    assert_eq!(handle.version_hint, 2);
+}
+
+#[test]
+fn test_singleton_own_update_landed() {
+    let (ctx, updater, chain) = init_singleton(5);
+    let mut collector = MockCollector {
+        chain: chain.clone(),
+    };
+    let rpc = MockRpc::new(chain, Vec::new());
+    rpc.lose_response.set(true);
+    let seen = RefCell::new(Vec::new());
+    let tx_hash = updater
+        .submit_with_retry(&mut collector, &rpc, increase(&updater, &ctx, &seen), 3)
+        .unwrap();
+    // Not rebuilt on top of our own update
+    assert_eq!(seen.into_inner(), vec![5]);
+    assert_eq!(rpc.sent.borrow().as_slice(), &[tx_hash]);
+    let handle = updater.fetch(&mut collector).unwrap();
+    assert_eq!(read_counter(&handle.data), 6);
+}
+
+#[test]
+fn test_singleton_retry_backoff() {
+    let backoff = RetryBackoff {
+        initial_delay: Duration::from_millis(100),
+        max_delay: Duration::from_millis(500),
+    };
+    let delays: Vec<_> = (0..5).map(|attempt| backoff.delay(attempt)).collect();
+    assert_eq!(
+        delays,
+        [100, 200, 400, 500, 500]
+            .map(Duration::from_millis)
+            .to_vec()
+    );
+    assert_eq!(backoff.delay(usize::MAX), backoff.max_delay);
+    assert_eq!(RetryBackoff::none().delay(3), Duration::ZERO);
+}
+
+#[test]
+fn test_singleton_exceed_max_attempts() {
+    let (ctx, mut updater, chain) = init_singleton(0);
+    updater.set_backoff(RetryBackoff {
+        initial_delay: Duration::from_millis(20),
+        max_delay: Duration::from_millis(30),
+    });
+    let mut collector = MockCollector {
+        chain: chain.clone(),
+    };
+    let competitors = (1..=3u64)
+        .map(|n| Bytes::from((n * 100).to_le_bytes().to_vec()))
+        .collect();
+    let rpc = MockRpc::new(chain, competitors);
+    let seen = RefCell::new(Vec::new());
+    let start = Instant::now();
+    let err = updater
+        .submit_with_retry(&mut collector, &rpc, increase(&updater, &ctx, &seen), 3)
+        .unwrap_err();
+    // waits 20ms and 30ms between the 3 attempts
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    assert!(matches!(err, SingletonError::ExceedMaxAttempts(3)));
+    assert_eq!(seen.into_inner(), vec![0, 100, 200]);
+    assert!(rpc.sent.borrow().is_empty());
+
+    // A transaction not spending the fetched version is refused
+    let err = updater
+        .submit_with_retry(
+            &mut collector,
+            &rpc,
+            |_| Ok(TransactionView::new_advanced_builder().build()),
+            3,
+        )
+        .unwrap_err();
+    assert!(matches!(err, SingletonError::NotSpendingSingleton(_)));
+}
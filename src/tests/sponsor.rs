@@ -0,0 +1,217 @@
+use ckb_hash::blake2b_256;
+use ckb_types::{
+    bytes::Bytes,
+    core::{ScriptHashType, TransactionBuilder, TransactionView},
+    packed::{CellInput, CellOutput, Script, WitnessArgs},
+    prelude::*,
+};
+
+use crate::{
+    constants::ONE_CKB,
+    test_util::{random_out_point, Context},
+    tests::{
+        build_sighash_script, build_sighash_unlockers, init_context, ACCOUNT0_ARG, ACCOUNT0_KEY,
+        ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG, ALWAYS_SUCCESS_BIN, FEE_RATE,
+    },
+    traits::CellDepResolver,
+    tx_builder::{sponsor_tx, unlock_tx, FeeSponsor, SponsorError},
+    types::ScriptHashTypeExt,
+};
+
+fn build_sponsor(max_fee: u64) -> FeeSponsor {
+    let placeholder_witness = WitnessArgs::new_builder()
+        .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+        .build();
+    FeeSponsor::new(
+        build_sighash_script(ACCOUNT1_ARG),
+        placeholder_witness,
+        max_fee,
+    )
+}
+
+/// Transfer all the capacity of `user_lock` cell to account 2, the fee is
+/// not paid.
+fn build_user_tx(ctx: &mut Context, user_lock: &Script, witness: Bytes) -> TransactionView {
+    let out_point = random_out_point();
+    ctx.add_simple_live_cell(out_point.clone(), user_lock.clone(), Some(100 * ONE_CKB));
+    TransactionBuilder::default()
+        .cell_dep(ctx.resolve(user_lock).unwrap())
+        .input(CellInput::new(out_point, 0))
+        .output(
+            CellOutput::new_builder()
+                .capacity((100 * ONE_CKB).pack())
+                .lock(build_sighash_script(ACCOUNT2_ARG))
+                .build(),
+        )
+        .output_data(Bytes::default().pack())
+        .witness(witness.pack())
+        .build()
+}
+
+fn assert_user_part_kept(user_tx: &TransactionView, tx: &TransactionView) {
+    let inputs = tx.inputs();
+    for (idx, input) in user_tx.inputs().into_iter().enumerate() {
+        assert_eq!(inputs.get(idx).unwrap().as_slice(), input.as_slice());
+    }
+    let outputs = tx.outputs();
+    for (idx, output) in user_tx.outputs().into_iter().enumerate() {
+        assert_eq!(outputs.get(idx).unwrap().as_slice(), output.as_slice());
+    }
+    let outputs_data = tx.outputs_data();
+    for (idx, data) in user_tx.outputs_data().into_iter().enumerate() {
+        assert_eq!(outputs_data.get(idx).unwrap().as_slice(), data.as_slice());
+    }
+    let cell_deps = tx.cell_deps();
+    for (idx, cell_dep) in user_tx.cell_deps().into_iter().enumerate() {
+        assert_eq!(cell_deps.get(idx).unwrap().as_slice(), cell_dep.as_slice());
+    }
+    assert_eq!(
+        tx.header_deps().as_slice(),
+        user_tx.header_deps().as_slice()
+    );
+    let witnesses = tx.witnesses();
+    for (idx, witness) in user_tx.witnesses().into_iter().enumerate() {
+        assert_eq!(witnesses.get(idx).unwrap().as_slice(), witness.as_slice());
+    }
+}
+
+#[test]
+fn test_sponsor_signed_tx() {
+    let sponsor = build_sponsor(ONE_CKB);
+    let mut ctx = init_context(
+        vec![(ALWAYS_SUCCESS_BIN, true)],
+        vec![(sponsor.lock.clone(), Some(200 * ONE_CKB))],
+    );
+    // always_success stands for a lock whose signature does not cover the
+    // appended parts of the transaction
+    let user_lock = Script::new_builder()
+        .code_hash(blake2b_256(ALWAYS_SUCCESS_BIN).pack())
+        .hash_type(ScriptHashType::Data1.to_packed())
+        .args(Bytes::from(vec![1u8; 20]).pack())
+        .build();
+    let user_witness = WitnessArgs::new_builder()
+        .lock(Some(Bytes::from(vec![7u8; 65])).pack())
+        .build();
+    let user_tx = build_user_tx(&mut ctx, &user_lock, user_witness.as_bytes());
+
+    let tx = sponsor_tx(
+        &user_tx,
+        &sponsor,
+        FEE_RATE,
+        &mut ctx.to_live_cells_context(),
+        &ctx,
+        &ctx,
+        &ctx,
+    )
+    .unwrap();
+    assert_user_part_kept(&user_tx, &tx);
+    assert_eq!(tx.inputs().len(), 2);
+    assert_eq!(tx.outputs().len(), 2);
+    assert_eq!(tx.output(1).unwrap().lock(), sponsor.lock);
+    assert_eq!(
+        tx.witnesses().get(1).unwrap().raw_data(),
+        sponsor.placeholder_witness.as_bytes()
+    );
+
+    // the sponsor unlocks its own inputs
+    let (tx, locked_groups) =
+        unlock_tx(tx, &ctx, &build_sighash_unlockers(&[ACCOUNT1_KEY])).unwrap();
+    assert_eq!(locked_groups.len(), 1);
+    assert_eq!(locked_groups[0].script, user_lock);
+    assert_user_part_kept(&user_tx, &tx);
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_sponsor_then_sign() {
+    let sponsor = build_sponsor(ONE_CKB);
+    let mut ctx = init_context(
+        Vec::new(),
+        vec![(sponsor.lock.clone(), Some(200 * ONE_CKB))],
+    );
+    // sighash all signs the whole transaction, the user signs after sponsoring
+    let user_lock = build_sighash_script(ACCOUNT0_ARG);
+    let user_placeholder = build_sponsor(0).placeholder_witness;
+    let user_tx = build_user_tx(&mut ctx, &user_lock, user_placeholder.as_bytes());
+    let tx = sponsor_tx(
+        &user_tx,
+        &sponsor,
+        FEE_RATE,
+        &mut ctx.to_live_cells_context(),
+        &ctx,
+        &ctx,
+        &ctx,
+    )
+    .unwrap();
+    // only the sighash cell dep, the sponsor does not add it again
+    assert_eq!(tx.cell_deps().len(), 1);
+
+    let (tx, locked_groups) =
+        unlock_tx(tx, &ctx, &build_sighash_unlockers(&[ACCOUNT0_KEY])).unwrap();
+    assert_eq!(locked_groups.len(), 1);
+    assert_eq!(locked_groups[0].script, sponsor.lock);
+    let user_signed_tx = tx.clone();
+    let (tx, locked_groups) =
+        unlock_tx(tx, &ctx, &build_sighash_unlockers(&[ACCOUNT1_KEY])).unwrap();
+    assert_eq!(locked_groups.len(), 1);
+    assert_eq!(locked_groups[0].script, user_lock);
+    assert_eq!(
+        tx.witnesses().get(0).unwrap().as_slice(),
+        user_signed_tx.witnesses().get(0).unwrap().as_slice()
+    );
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_sponsor_errors() {
+    let sponsor = build_sponsor(ONE_CKB);
+    let mut ctx = init_context(
+        Vec::new(),
+        vec![(sponsor.lock.clone(), Some(200 * ONE_CKB))],
+    );
+    let sponsor_tx_of = |ctx: &Context, tx: &TransactionView, sponsor: &FeeSponsor| {
+        sponsor_tx(
+            tx,
+            sponsor,
+            FEE_RATE,
+            &mut ctx.to_live_cells_context(),
+            ctx,
+            ctx,
+            ctx,
+        )
+    };
+
+    let sponsor_input_tx = build_user_tx(&mut ctx, &sponsor.lock, Bytes::default());
+    assert!(matches!(
+        sponsor_tx_of(&ctx, &sponsor_input_tx, &sponsor),
+        Err(SponsorError::SponsorLockInUse(0))
+    ));
+
+    let user_lock = build_sighash_script(ACCOUNT0_ARG);
+    let user_tx = build_user_tx(&mut ctx, &user_lock, Bytes::default());
+    let extra_witness_tx = user_tx
+        .as_advanced_builder()
+        .witness(Bytes::default().pack())
+        .build();
+    assert!(matches!(
+        sponsor_tx_of(&ctx, &extra_witness_tx, &sponsor),
+        Err(SponsorError::ExtraWitnesses(1))
+    ));
+
+    // the user outputs need 50 CKB more than the user inputs
+    let overspent_tx = user_tx
+        .as_advanced_builder()
+        .output(
+            CellOutput::new_builder()
+                .capacity((50 * ONE_CKB).pack())
+                .lock(build_sighash_script(ACCOUNT2_ARG))
+                .build(),
+        )
+        .output_data(Bytes::default().pack())
+        .build();
+    assert!(matches!(
+        sponsor_tx_of(&ctx, &overspent_tx, &sponsor),
+        Err(SponsorError::MaxFeeExceeded(_, _))
+    ));
+    assert!(sponsor_tx_of(&ctx, &overspent_tx, &build_sponsor(51 * ONE_CKB)).is_ok());
+}
@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use ckb_hash::blake2b_256;
+use ckb_types::{
+    bytes::Bytes,
+    core::ScriptHashType,
+    packed::{CellOutput, Script, WitnessArgs},
+    prelude::*,
+    H256,
+};
+
+use crate::{
+    constants::ONE_CKB,
+    tests::{build_sighash_script, init_context, ACCOUNT2_ARG, ALWAYS_SUCCESS_BIN, FEE_RATE},
+    tx_builder::{transfer::CapacityTransferBuilder, unlock_tx, CapacityBalancer, TxBuilder},
+    types::ScriptHashTypeExt,
+    unlock::{ScriptUnlocker, StaticWitnessUnlocker, UnlockError},
+    ScriptGroup, ScriptId,
+};
+
+const LOCK_SIZE: usize = 40;
+
+fn build_lock_script(script_id: &ScriptId, args: &[u8]) -> Script {
+    Script::new_builder()
+        .code_hash(script_id.code_hash.pack())
+        .hash_type(script_id.hash_type.to_packed())
+        .args(Bytes::from(args.to_vec()).pack())
+        .build()
+}
+
+fn placeholder_witness() -> WitnessArgs {
+    WitnessArgs::new_builder()
+        .lock(Some(Bytes::from(vec![0u8; LOCK_SIZE])).pack())
+        .build()
+}
+
+#[test]
+fn test_static_witness_build_balanced() {
+    // always_success stands for the lock script being developed
+    let script_id = ScriptId::new_data1(H256::from(blake2b_256(ALWAYS_SUCCESS_BIN)));
+    let sender = build_lock_script(&script_id, b"my-lock");
+    let ctx = init_context(
+        vec![(ALWAYS_SUCCESS_BIN, true)],
+        vec![
+            (sender.clone(), Some(100 * ONE_CKB)),
+            (sender.clone(), Some(200 * ONE_CKB)),
+        ],
+    );
+
+    let unlocker = StaticWitnessUnlocker::new(script_id.clone(), placeholder_witness())
+        .with_witness_builder(|tx, script_group| {
+            // e.g. sign the hash of the transaction
+            let mut lock = tx.hash().raw_data().to_vec();
+            lock.extend_from_slice(&(script_group.input_indices.len() as u64).to_le_bytes());
+            Ok(WitnessArgs::new_builder()
+                .lock(Some(Bytes::from(lock)).pack())
+                .build())
+        });
+    assert!(unlocker.match_args(b"any args"));
+    let mut unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>> = HashMap::default();
+    unlockers.insert(script_id.clone(), Box::new(unlocker));
+
+    let output = CellOutput::new_builder()
+        .capacity((250 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT2_ARG))
+        .build();
+    let builder = CapacityTransferBuilder::new(vec![(output, Bytes::default())]);
+    let balancer = CapacityBalancer::new_simple(sender.clone(), placeholder_witness(), FEE_RATE);
+    let balanced_tx = builder
+        .build_balanced(
+            &mut ctx.to_live_cells_context(),
+            &ctx,
+            &ctx,
+            &ctx,
+            &balancer,
+            &unlockers,
+        )
+        .unwrap();
+    assert_eq!(balanced_tx.inputs().len(), 2);
+    assert_eq!(
+        balanced_tx.witnesses().get(0).unwrap().raw_data(),
+        placeholder_witness().as_bytes()
+    );
+
+    let (tx, locked_groups) = unlock_tx(balanced_tx.clone(), &ctx, &unlockers).unwrap();
+    assert!(locked_groups.is_empty());
+    let witness_args = WitnessArgs::from_slice(&tx.witnesses().get(0).unwrap().raw_data()).unwrap();
+    let lock = witness_args.lock().to_opt().unwrap().raw_data();
+    // the witness keeps the placeholder size, so the fee is estimated correctly
+    assert_eq!(lock.len(), LOCK_SIZE);
+    assert_eq!(&lock[0..32], balanced_tx.hash().as_slice());
+    assert_eq!(&lock[32..], &2u64.to_le_bytes()[..]);
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_static_witness_placeholder() {
+    let script_id = ScriptId::new_data1(H256::from(blake2b_256(ALWAYS_SUCCESS_BIN)));
+    let sender = build_lock_script(&script_id, &[]);
+    let ctx = init_context(
+        vec![(ALWAYS_SUCCESS_BIN, true)],
+        vec![(sender.clone(), Some(100 * ONE_CKB))],
+    );
+    let unlocker = StaticWitnessUnlocker::new(script_id, placeholder_witness());
+    let mut script_group = ScriptGroup::from_lock_script(&sender);
+    script_group.input_indices.push(0);
+
+    // the other fields of the current witness are kept
+    let input_type = Bytes::from(vec![7u8; 3]);
+    let tx = ckb_types::core::TransactionBuilder::default()
+        .witness(
+            WitnessArgs::new_builder()
+                .input_type(Some(input_type.clone()).pack())
+                .build()
+                .as_bytes()
+                .pack(),
+        )
+        .build();
+    let tx = unlocker
+        .fill_placeholder_witness(&tx, &script_group, &ctx)
+        .unwrap();
+    let witness_args = WitnessArgs::from_slice(&tx.witnesses().get(0).unwrap().raw_data()).unwrap();
+    assert_eq!(
+        witness_args.input_type().to_opt().unwrap().raw_data(),
+        input_type
+    );
+    assert_eq!(
+        witness_args.lock().to_opt().unwrap().raw_data().len(),
+        LOCK_SIZE
+    );
+
+    // without a witness builder the placeholder witness is the final witness
+    let tx = unlocker.unlock(&tx, &script_group, &ctx).unwrap();
+    let witness_args = WitnessArgs::from_slice(&tx.witnesses().get(0).unwrap().raw_data()).unwrap();
+    assert_eq!(witness_args.lock(), placeholder_witness().lock());
+    assert_eq!(
+        witness_args.input_type().to_opt().unwrap().raw_data(),
+        input_type
+    );
+
+    // the script group of another script id is rejected
+    let other = Script::new_builder()
+        .code_hash([1u8; 32].pack())
+        .hash_type(ScriptHashType::Type.to_packed())
+        .build();
+    let mut other_group = ScriptGroup::from_lock_script(&other);
+    other_group.input_indices.push(0);
+    assert!(matches!(
+        unlocker.unlock(&tx, &other_group, &ctx),
+        Err(UnlockError::UnlockerArgsMismatch { .. })
+    ));
+}
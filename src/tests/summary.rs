@@ -0,0 +1,221 @@
+use ckb_hash::blake2b_256;
+use ckb_types::{
+    bytes::Bytes,
+    core::{TransactionBuilder, TransactionView},
+    packed::{Byte32, CellInput, CellOutput, Script},
+    prelude::*,
+    H256,
+};
+
+use crate::{
+    constants::ONE_CKB,
+    test_util::{random_out_point, Context},
+    tests::{
+        build_sighash_script, build_sudt_script, init_context, ACCOUNT0_ARG, ACCOUNT1_ARG,
+        ACCOUNT2_ARG, ACCOUNT3_ARG,
+    },
+    tx_builder::{compact_summary, CompactSummary},
+};
+
+fn build_cell(lock: &Script, type_script: Option<&Script>, capacity: u64) -> CellOutput {
+    CellOutput::new_builder()
+        .capacity(capacity.pack())
+        .lock(lock.clone())
+        .type_(type_script.cloned().pack())
+        .build()
+}
+
+fn add_input(ctx: &mut Context, output: CellOutput, data: Bytes) -> CellInput {
+    let input = CellInput::new(random_out_point(), 0);
+    ctx.add_live_cell(input.clone(), output, data, None);
+    input
+}
+
+fn build_tx(inputs: Vec<CellInput>, outputs: Vec<(CellOutput, Bytes)>) -> TransactionView {
+    let (outputs, outputs_data): (Vec<_>, Vec<_>) = outputs.into_iter().unzip();
+    TransactionBuilder::default()
+        .set_inputs(inputs)
+        .set_outputs(outputs)
+        .set_outputs_data(outputs_data.into_iter().map(|data| data.pack()).collect())
+        .build()
+}
+
+fn amount_data(amount: u128) -> Bytes {
+    Bytes::from(amount.to_le_bytes().to_vec())
+}
+
+#[test]
+fn test_compact_summary_self_transfer() {
+    let owner = build_sighash_script(ACCOUNT1_ARG);
+    let mut ctx = init_context(Vec::new(), Vec::new());
+    let input = add_input(
+        &mut ctx,
+        build_cell(&owner, None, 300 * ONE_CKB),
+        Bytes::default(),
+    );
+    let tx = build_tx(
+        vec![input],
+        vec![(
+            build_cell(&owner, None, 300 * ONE_CKB - 1000),
+            Bytes::default(),
+        )],
+    );
+
+    let summary = compact_summary(&tx, &ctx, &[owner]).unwrap();
+    assert_eq!(
+        summary,
+        CompactSummary {
+            external_out: 0,
+            change_back: 300 * ONE_CKB - 1000,
+            fee: 1000,
+            external_udt: Vec::new(),
+        }
+    );
+
+    let mut expected_bytes = Vec::new();
+    expected_bytes.extend_from_slice(&0u64.to_le_bytes());
+    expected_bytes.extend_from_slice(&(300 * ONE_CKB - 1000).to_le_bytes());
+    expected_bytes.extend_from_slice(&1000u64.to_le_bytes());
+    expected_bytes.extend_from_slice(&0u32.to_le_bytes());
+    assert_eq!(summary.to_bytes(), Bytes::from(expected_bytes.clone()));
+    assert_eq!(
+        summary.commitment(),
+        H256::from(blake2b_256(&expected_bytes))
+    );
+
+    // not owned by anyone: every output is external
+    let summary = compact_summary(&tx, &ctx, &[]).unwrap();
+    assert_eq!(summary.external_out, 300 * ONE_CKB - 1000);
+    assert_eq!(summary.change_back, 0);
+}
+
+#[test]
+fn test_compact_summary_multi_change() {
+    let owner = build_sighash_script(ACCOUNT1_ARG);
+    let owner2 = build_sighash_script(ACCOUNT3_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    // same code hash and args prefix as `owner`, but not the same lock
+    let lookalike = owner
+        .clone()
+        .as_builder()
+        .args(Bytes::from([ACCOUNT1_ARG.as_bytes(), &[0u8][..]].concat()).pack())
+        .build();
+    let mut ctx = init_context(Vec::new(), Vec::new());
+    let inputs = vec![
+        add_input(
+            &mut ctx,
+            build_cell(&owner, None, 300 * ONE_CKB),
+            Bytes::default(),
+        ),
+        add_input(
+            &mut ctx,
+            build_cell(&owner2, None, 200 * ONE_CKB),
+            Bytes::default(),
+        ),
+    ];
+    let tx = build_tx(
+        inputs,
+        vec![
+            (build_cell(&receiver, None, 100 * ONE_CKB), Bytes::default()),
+            (build_cell(&owner, None, 150 * ONE_CKB), Bytes::default()),
+            (build_cell(&lookalike, None, 61 * ONE_CKB), Bytes::default()),
+            (build_cell(&owner2, None, 188 * ONE_CKB), Bytes::default()),
+        ],
+    );
+
+    let owned_locks = vec![owner, owner2];
+    let summary = compact_summary(&tx, &ctx, &owned_locks).unwrap();
+    assert_eq!(summary.external_out, (100 + 61) * ONE_CKB);
+    assert_eq!(summary.change_back, (150 + 188) * ONE_CKB);
+    assert_eq!(summary.fee, ONE_CKB);
+    assert!(summary.external_udt.is_empty());
+
+    // the summary does not depend on the order of the owned locks
+    let reversed: Vec<_> = owned_locks.iter().rev().cloned().collect();
+    let summary2 = compact_summary(&tx, &ctx, &reversed).unwrap();
+    assert_eq!(summary2.commitment(), summary.commitment());
+
+    // outputs spend more than inputs
+    let tx = build_tx(
+        Vec::new(),
+        vec![(build_cell(&receiver, None, 100 * ONE_CKB), Bytes::default())],
+    );
+    assert!(compact_summary(&tx, &ctx, &owned_locks).is_err());
+}
+
+#[test]
+fn test_compact_summary_udt() {
+    let owner = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let udt_a = build_sudt_script(&build_sighash_script(ACCOUNT0_ARG));
+    let udt_b = build_sudt_script(&build_sighash_script(ACCOUNT3_ARG));
+    let mut ctx = init_context(Vec::new(), Vec::new());
+    let inputs = vec![
+        add_input(
+            &mut ctx,
+            build_cell(&owner, Some(&udt_a), 200 * ONE_CKB),
+            amount_data(500),
+        ),
+        add_input(
+            &mut ctx,
+            build_cell(&owner, Some(&udt_b), 200 * ONE_CKB),
+            amount_data(50),
+        ),
+        add_input(
+            &mut ctx,
+            build_cell(&owner, None, 300 * ONE_CKB),
+            Bytes::default(),
+        ),
+    ];
+    let mut xudt_data = amount_data(20).to_vec();
+    xudt_data.extend_from_slice(&[1, 2, 3]);
+    let tx = build_tx(
+        inputs,
+        vec![
+            (
+                build_cell(&receiver, Some(&udt_a), 142 * ONE_CKB),
+                amount_data(300),
+            ),
+            (
+                build_cell(&owner, Some(&udt_a), 200 * ONE_CKB),
+                amount_data(200),
+            ),
+            (
+                build_cell(&receiver, Some(&udt_b), 142 * ONE_CKB),
+                amount_data(30),
+            ),
+            (
+                build_cell(&receiver, Some(&udt_b), 142 * ONE_CKB),
+                Bytes::from(xudt_data),
+            ),
+            // data too short to be a udt cell
+            (
+                build_cell(&receiver, Some(&udt_a), 73 * ONE_CKB),
+                Bytes::from(vec![0u8; 8]),
+            ),
+            (build_cell(&owner, None, 0), Bytes::default()),
+        ],
+    );
+
+    let summary = compact_summary(&tx, &ctx, &[owner]).unwrap();
+    assert_eq!(summary.external_out, (142 * 3 + 73) * ONE_CKB);
+    assert_eq!(summary.change_back, 200 * ONE_CKB);
+    assert_eq!(summary.fee, (700 - 142 * 3 - 73 - 200) * ONE_CKB);
+    let mut expected_udt: Vec<(Byte32, u128)> = vec![
+        (udt_a.calc_script_hash(), 300),
+        (udt_b.calc_script_hash(), 50),
+    ];
+    expected_udt.sort_by_key(|(type_hash, _)| type_hash.as_slice().to_vec());
+    assert_eq!(summary.external_udt, expected_udt);
+
+    let bytes = summary.to_bytes();
+    assert_eq!(bytes.len(), 28 + 2 * 48);
+    assert_eq!(&bytes[24..28], &2u32.to_le_bytes());
+    assert_eq!(&bytes[28..60], expected_udt[0].0.as_slice());
+    assert_eq!(&bytes[60..76], &expected_udt[0].1.to_le_bytes());
+
+    // firmware recomputes the summary and compares the commitment
+    let mut tampered = summary.clone();
+    tampered.external_udt[0].1 -= 1;
+    assert_ne!(tampered.commitment(), summary.commitment());
+}
@@ -0,0 +1,262 @@
+use ckb_types::{
+    bytes::Bytes,
+    packed::{CellInput, CellOutput, Script},
+    prelude::*,
+};
+
+use crate::{
+    constants::ONE_CKB,
+    test_util::random_out_point,
+    tests::{
+        build_sighash_balancer, build_sighash_script, build_sighash_unlockers, build_sudt_script,
+        init_context, ACCOUNT0_ARG, ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG, ACCOUNT3_ARG,
+        FEE_RATE, SUDT_BIN,
+    },
+    tx_builder::{
+        template::{
+            SlotValue, TemplateError, TemplateParams, TemplateSlot, TemplateTxBuilder, TxTemplate,
+        },
+        udt::{TokenEntry, TokenRegistry},
+        TxBuilder,
+    },
+    Address, NetworkType,
+};
+
+fn build_address(lock: &Script) -> Address {
+    Address::new(NetworkType::Testnet, lock.clone().into(), true)
+}
+
+fn build_registry() -> TokenRegistry {
+    let mut registry = TokenRegistry::new();
+    registry
+        .register(TokenEntry::new(
+            "TKN",
+            build_sudt_script(&build_sighash_script(ACCOUNT0_ARG)),
+            8,
+        ))
+        .unwrap();
+    registry
+}
+
+#[test]
+fn test_template_compile() {
+    let registry = build_registry();
+    let fixed_address = build_address(&build_sighash_script(ACCOUNT3_ARG)).to_string();
+    let template = TxTemplate {
+        name: "fee_and_payout".to_string(),
+        slots: vec![
+            TemplateSlot::FixedOutput {
+                name: "service_fee".to_string(),
+                address: fixed_address.clone(),
+                capacity: 61 * ONE_CKB,
+                data: Default::default(),
+            },
+            TemplateSlot::CapacityReceiver {
+                name: "receivers".to_string(),
+            },
+            TemplateSlot::FeePayer {
+                name: "payer".to_string(),
+            },
+        ],
+    };
+    let json = serde_json::to_string(&template).unwrap();
+    assert!(json.contains(r#""type":"fixed_output""#));
+    assert_eq!(serde_json::from_str::<TxTemplate>(&json).unwrap(), template);
+    assert!(template.compile(NetworkType::Testnet, &registry).is_ok());
+    assert_eq!(
+        template
+            .compile(NetworkType::Mainnet, &registry)
+            .unwrap_err(),
+        TemplateError::NetworkMismatch {
+            expected: NetworkType::Mainnet,
+            actual: NetworkType::Testnet,
+        }
+    );
+
+    let mut low_capacity = template.clone();
+    low_capacity.slots[0] = TemplateSlot::FixedOutput {
+        name: "service_fee".to_string(),
+        address: fixed_address,
+        capacity: 60 * ONE_CKB,
+        data: Default::default(),
+    };
+    assert!(matches!(
+        low_capacity.compile(NetworkType::Testnet, &registry),
+        Err(TemplateError::InsufficientCapacity { min, .. }) if min == 61 * ONE_CKB
+    ));
+
+    let mut no_payer = template.clone();
+    no_payer.slots.pop();
+    assert_eq!(
+        no_payer
+            .compile(NetworkType::Testnet, &registry)
+            .unwrap_err(),
+        TemplateError::FeePayerCount(0)
+    );
+
+    let mut duplicated = template;
+    duplicated.slots.push(TemplateSlot::UdtReceiver {
+        name: "receivers".to_string(),
+        symbol: "TKN".to_string(),
+    });
+    assert_eq!(
+        duplicated
+            .compile(NetworkType::Testnet, &registry)
+            .unwrap_err(),
+        TemplateError::DuplicatedSlot("receivers".to_string())
+    );
+
+    assert_eq!(
+        TxTemplate::airdrop("UNKNOWN")
+            .compile(NetworkType::Testnet, &registry)
+            .unwrap_err(),
+        TemplateError::SymbolNotFound("UNKNOWN".to_string())
+    );
+}
+
+#[test]
+fn test_template_payout() {
+    let payer = build_sighash_script(ACCOUNT1_ARG);
+    let receiver_a = build_sighash_script(ACCOUNT2_ARG);
+    let receiver_b = build_sighash_script(ACCOUNT3_ARG);
+    let ctx = init_context(Vec::new(), vec![(payer.clone(), Some(1000 * ONE_CKB))]);
+    let compiled = TxTemplate::payout()
+        .compile(NetworkType::Testnet, &build_registry())
+        .unwrap();
+
+    let mut params = TemplateParams::new();
+    params.push(
+        "receivers",
+        SlotValue::Capacity {
+            receiver: build_address(&receiver_a),
+            capacity: 100 * ONE_CKB,
+        },
+    );
+    // missing fee payer
+    assert!(matches!(
+        compiled.instantiate(&params),
+        Err(TemplateError::InvalidParam(slot, _)) if slot == "payer"
+    ));
+    params
+        .push(
+            "receivers",
+            SlotValue::Capacity {
+                receiver: build_address(&receiver_b),
+                capacity: 200 * ONE_CKB,
+            },
+        )
+        .push("payer", SlotValue::FeePayer(build_address(&payer)));
+    let instance = compiled.instantiate(&params).unwrap();
+    assert_eq!(instance.fee_payer, payer);
+    assert!(matches!(instance.builder, TemplateTxBuilder::Capacity(_)));
+
+    let (tx, locked_groups) = instance
+        .build_unlocked(
+            &mut ctx.to_live_cells_context(),
+            &ctx,
+            &ctx,
+            &ctx,
+            &build_sighash_balancer(&instance.fee_payer),
+            &build_sighash_unlockers(&[ACCOUNT1_KEY]),
+        )
+        .unwrap();
+    assert!(locked_groups.is_empty());
+    let outputs = tx.outputs().into_iter().collect::<Vec<_>>();
+    assert_eq!(outputs.len(), 3);
+    assert_eq!(outputs[0].lock(), receiver_a);
+    assert_eq!(outputs[1].lock(), receiver_b);
+    assert_eq!(outputs[2].lock(), payer);
+    ctx.verify(tx, FEE_RATE).unwrap();
+
+    // not enough capacity for a receiver cell
+    let mut params = TemplateParams::new();
+    params
+        .push(
+            "receivers",
+            SlotValue::Capacity {
+                receiver: build_address(&receiver_a),
+                capacity: 60 * ONE_CKB,
+            },
+        )
+        .push("payer", SlotValue::FeePayer(build_address(&payer)));
+    assert!(matches!(
+        compiled.instantiate(&params),
+        Err(TemplateError::InsufficientCapacity { .. })
+    ));
+}
+
+#[test]
+fn test_template_airdrop() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver_a = build_sighash_script(ACCOUNT2_ARG);
+    let receiver_b = build_sighash_script(ACCOUNT3_ARG);
+    let sudt_script = build_sudt_script(&build_sighash_script(ACCOUNT0_ARG));
+    let mut ctx = init_context(
+        vec![(SUDT_BIN, false)],
+        vec![(sender.clone(), Some(1000 * ONE_CKB))],
+    );
+    ctx.add_live_cell(
+        CellInput::new(random_out_point(), 0),
+        CellOutput::new_builder()
+            .capacity((200 * ONE_CKB).pack())
+            .lock(sender.clone())
+            .type_(Some(sudt_script.clone()).pack())
+            .build(),
+        Bytes::from(100u128.to_le_bytes().to_vec()),
+        None,
+    );
+    let compiled = TxTemplate::airdrop("TKN")
+        .compile(NetworkType::Testnet, &build_registry())
+        .unwrap();
+    assert_eq!(compiled.fee_payer_slot(), "sender");
+
+    let mut params = TemplateParams::new();
+    for (receiver, amount) in [(&receiver_a, 10u128), (&receiver_b, 20)] {
+        params.push(
+            "receivers",
+            SlotValue::Udt {
+                receiver: build_address(receiver),
+                amount,
+            },
+        );
+    }
+    params.push("sender", SlotValue::FeePayer(build_address(&sender)));
+    let instance = compiled.instantiate(&params).unwrap();
+    assert!(matches!(instance.builder, TemplateTxBuilder::Batch(_)));
+
+    let (tx, locked_groups) = instance
+        .build_unlocked(
+            &mut ctx.to_live_cells_context(),
+            &ctx,
+            &ctx,
+            &ctx,
+            &build_sighash_balancer(&instance.fee_payer),
+            &build_sighash_unlockers(&[ACCOUNT1_KEY]),
+        )
+        .unwrap();
+    assert!(locked_groups.is_empty());
+    let outputs_data = tx
+        .outputs_data()
+        .into_iter()
+        .map(|data| data.raw_data())
+        .collect::<Vec<_>>();
+    // two receivers, the udt change and the capacity change
+    assert_eq!(outputs_data.len(), 4);
+    for (data, amount) in outputs_data.iter().zip([10u128, 20, 70]) {
+        assert_eq!(data.as_ref(), &amount.to_le_bytes()[..]);
+    }
+    ctx.verify(tx, FEE_RATE).unwrap();
+
+    // wrong value type
+    let mut params = TemplateParams::new();
+    params
+        .push(
+            "receivers",
+            SlotValue::Capacity {
+                receiver: build_address(&receiver_a),
+                capacity: 100 * ONE_CKB,
+            },
+        )
+        .push("sender", SlotValue::FeePayer(build_address(&sender)));
+    assert!(compiled.instantiate(&params).is_err());
+}
@@ -0,0 +1,195 @@
+use ckb_hash::blake2b_256;
+use ckb_types::{
+    bytes::Bytes,
+    core::{
+        EpochNumberWithFraction, HeaderBuilder, HeaderView, ScriptHashType, TransactionBuilder,
+    },
+    packed::{CellInput, CellOutput, OutPoint, Script},
+    prelude::*,
+    H256,
+};
+
+use crate::{
+    constants::{CHEQUE_CELL_SINCE, ONE_CKB},
+    test_util::{random_out_point, Context, Error},
+    tests::{
+        build_cheque_script, build_sighash_balancer, build_sighash_script, build_sighash_unlockers,
+        init_context, ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG, CHEQUE_BIN, FEE_RATE, SUDT_BIN,
+    },
+    traits::{CellCollector, CellQueryOptions, LiveCell, MaturityOption, SecpCkbRawKeySigner},
+    tx_builder::{cheque::ChequeWithdrawBuilder, TxBuilder},
+    types::ScriptHashTypeExt,
+    unlock::{ChequeAction, ChequeUnlocker},
+    ScriptId,
+};
+
+fn build_header(number: u64, epoch: EpochNumberWithFraction) -> HeaderView {
+    HeaderBuilder::default()
+        .number(number.pack())
+        .epoch(epoch.full_value().pack())
+        .timestamp((number * 8_000).pack())
+        .build()
+}
+
+#[test]
+fn test_cheque_withdraw_at_tip() {
+    let sudt_data_hash = H256::from(blake2b_256(SUDT_BIN));
+    let cheque_data_hash = H256::from(blake2b_256(CHEQUE_BIN));
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let cheque_script = build_cheque_script(&sender, &receiver, cheque_data_hash.clone());
+    let type_script = Script::new_builder()
+        .code_hash(sudt_data_hash.pack())
+        .hash_type(ScriptHashType::Data1.to_packed())
+        .args(Bytes::from(vec![9u8; 32]).pack())
+        .build();
+    let mut ctx = init_context(
+        vec![(CHEQUE_BIN, true), (SUDT_BIN, false)],
+        vec![(sender.clone(), Some(100 * ONE_CKB))],
+    );
+
+    // the cheque cell is committed at epoch 10
+    let header = build_header(10_000, EpochNumberWithFraction::new(10, 0, 1000));
+    ctx.add_header(header.clone());
+    let cheque_out_point = random_out_point();
+    let cheque_output = CellOutput::new_builder()
+        .capacity((220 * ONE_CKB).pack())
+        .lock(cheque_script)
+        .type_(Some(type_script).pack())
+        .build();
+    ctx.add_live_cell(
+        CellInput::new(cheque_out_point.clone(), CHEQUE_CELL_SINCE),
+        cheque_output,
+        Bytes::from(500u128.to_le_bytes().to_vec()),
+        Some(header.hash()),
+    );
+
+    let builder = ChequeWithdrawBuilder::new(vec![cheque_out_point], sender.clone(), None);
+    let balancer = build_sighash_balancer(&sender);
+    let account1_key = secp256k1::SecretKey::from_slice(ACCOUNT1_KEY.as_bytes()).unwrap();
+    let signer = SecpCkbRawKeySigner::new_with_secret_keys(vec![account1_key]);
+    let cheque_unlocker =
+        ChequeUnlocker::from((Box::new(signer) as Box<_>, ChequeAction::Withdraw));
+    let mut unlockers = build_sighash_unlockers(&[ACCOUNT1_KEY]);
+    unlockers.insert(
+        ScriptId::new_data1(cheque_data_hash),
+        Box::new(cheque_unlocker),
+    );
+    let (tx, locked_groups) = builder
+        .build_unlocked(
+            &mut ctx.to_live_cells_context(),
+            &ctx,
+            &ctx,
+            &ctx,
+            &balancer,
+            &unlockers,
+        )
+        .unwrap();
+    assert!(locked_groups.is_empty());
+
+    // the since is only checked when the tip is set
+    ctx.verify(tx.clone(), FEE_RATE).unwrap();
+
+    ctx.set_tip(
+        15_999,
+        EpochNumberWithFraction::new(15, 999, 1000),
+        15_999 * 8_000,
+    );
+    assert!(matches!(ctx.verify_tip(&tx), Err(Error::Immature(_))));
+    assert!(ctx
+        .verify(tx.clone(), FEE_RATE)
+        .unwrap_err()
+        .group
+        .is_none());
+
+    ctx.set_tip(
+        16_000,
+        EpochNumberWithFraction::new(16, 0, 1000),
+        16_000 * 8_000,
+    );
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_cellbase_maturity() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let mut ctx = init_context(Vec::new(), vec![(sender.clone(), Some(100 * ONE_CKB))]);
+    let cellbase_out_point = random_out_point();
+    let cellbase_output = CellOutput::new_builder()
+        .capacity((1000 * ONE_CKB).pack())
+        .lock(sender.clone())
+        .build();
+    ctx.add_cellbase_live_cell(
+        cellbase_out_point.clone(),
+        cellbase_output,
+        build_header(1000, EpochNumberWithFraction::new(1, 0, 1000)),
+    );
+    let collect = |ctx: &Context| {
+        let mut query = CellQueryOptions::new_lock(sender.clone());
+        query.min_total_capacity = u64::MAX;
+        let (cells, _) = ctx
+            .to_live_cells_context()
+            .collect_live_cells(&query, false)
+            .unwrap();
+        cells
+            .into_iter()
+            .any(|cell| cell.out_point == cellbase_out_point)
+    };
+    let tx = TransactionBuilder::default()
+        .input(CellInput::new(cellbase_out_point.clone(), 0))
+        .build();
+
+    // the cellbase cell matures 4 epochs later
+    ctx.set_tip(4_999, EpochNumberWithFraction::new(4, 999, 1000), 0);
+    assert_eq!(ctx.max_mature_number(), Some(0));
+    assert!(!collect(&ctx));
+    assert!(matches!(ctx.verify_tip(&tx), Err(Error::Immature(_))));
+
+    ctx.set_tip(5_000, EpochNumberWithFraction::new(5, 0, 1000), 0);
+    assert_eq!(ctx.max_mature_number(), Some(1000));
+    assert!(collect(&ctx));
+    ctx.verify_tip(&tx).unwrap();
+}
+
+#[test]
+fn test_live_cell_maturity() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let mut ctx = init_context(Vec::new(), vec![(sender.clone(), Some(100 * ONE_CKB))]);
+    let cellbase_out_point = random_out_point();
+    let cellbase_output = CellOutput::new_builder()
+        .capacity((1000 * ONE_CKB).pack())
+        .lock(sender.clone())
+        .build();
+    ctx.add_cellbase_live_cell(
+        cellbase_out_point.clone(),
+        cellbase_output.clone(),
+        build_header(1000, EpochNumberWithFraction::new(1, 0, 1000)),
+    );
+    ctx.set_tip(4_999, EpochNumberWithFraction::new(4, 999, 1000), 0);
+
+    let mut query = CellQueryOptions::new_lock(sender);
+    query.min_total_capacity = u64::MAX;
+    query.maturity = MaturityOption::Both;
+    let (cells, _) = ctx
+        .to_live_cells_context()
+        .collect_live_cells(&query, false)
+        .unwrap();
+    assert_eq!(cells.len(), 2);
+    for cell in cells {
+        let is_cellbase = cell.out_point == cellbase_out_point;
+        assert_eq!(cell.from_cellbase, is_cellbase);
+        assert_eq!(cell.is_mature(0), !is_cellbase);
+        assert!(cell.is_mature(1000));
+    }
+
+    // not from a cellbase transaction by default, even with tx_index 0
+    let cell = LiveCell::new(
+        cellbase_output,
+        Bytes::default(),
+        OutPoint::default(),
+        1000,
+        0,
+    );
+    assert!(!cell.from_cellbase);
+    assert!(cell.is_mature(0));
+}
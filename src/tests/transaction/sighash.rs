@@ -1,7 +1,12 @@
-use ckb_types::{packed::CellOutput, prelude::*};
+use ckb_types::{
+    bytes::Bytes,
+    core::{Capacity, ScriptHashType},
+    packed::{CellOutput, Script},
+    prelude::*,
+};
 
 use crate::{
-    constants::ONE_CKB,
+    constants::{ONE_CKB, SIGHASH_TYPE_HASH},
     tests::{
         build_sighash_script, init_context, ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG, FEE_RATE,
     },
@@ -11,6 +16,8 @@ use crate::{
         signer::{SignContexts, TransactionSigner},
         TransactionBuilderConfiguration,
     },
+    tx_builder::TxBuilderError,
+    types::ScriptHashTypeExt,
     NetworkInfo,
 };
 
@@ -76,3 +83,37 @@ fn test_transfer_from_sighash() {
 
     ctx.verify(tx, FEE_RATE).unwrap();
 }
+
+#[test]
+fn test_transfer_to_burn_lock() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let burn_lock = Script::new_builder()
+        .code_hash(SIGHASH_TYPE_HASH.pack())
+        .hash_type(ScriptHashType::Type.to_packed())
+        .args(Bytes::from(vec![0u8; 20]).pack())
+        .build();
+    let ctx = init_context(Vec::new(), vec![(sender.clone(), Some(300 * ONE_CKB))]);
+    let network_info = NetworkInfo::testnet();
+    let build = |allow_burn: bool| {
+        let configuration =
+            TransactionBuilderConfiguration::new_with_network(network_info.clone()).unwrap();
+        let iterator = InputIterator::new_with_cell_collector(
+            vec![sender.clone()],
+            Box::new(ctx.to_live_cells_context()) as Box<_>,
+        );
+        let mut builder = SimpleTransactionBuilder::new(configuration, iterator);
+        builder.add_output(burn_lock.clone(), Capacity::shannons(120 * ONE_CKB));
+        if allow_burn {
+            builder.allow_burn();
+        }
+        builder.build(&Default::default())
+    };
+
+    assert!(matches!(
+        build(false),
+        Err(TxBuilderError::InvalidParameter(_))
+    ));
+    let tx_with_groups = build(true).expect("build failed");
+    let tx = tx_with_groups.get_tx_view();
+    assert_eq!(tx.outputs().get(0).unwrap().lock(), burn_lock);
+}
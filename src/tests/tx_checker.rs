@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+
+use ckb_chain_spec::consensus::ConsensusBuilder;
+use ckb_types::{
+    bytes::Bytes,
+    core::{TransactionBuilder, TransactionView},
+    packed::{CellInput, CellOutput, WitnessArgs},
+    prelude::*,
+};
+
+use crate::{
+    constants::{ONE_CKB, SIGHASH_TYPE_HASH},
+    test_util::{random_out_point, Context},
+    tests::{
+        build_sighash_balancer, build_sighash_script, init_context, ACCOUNT1_ARG, ACCOUNT2_ARG,
+    },
+    tx_builder::{transfer::CapacityTransferBuilder, TxBuilder, TxBuilderError},
+    tx_checker::{
+        check_transaction, check_transaction_strict, check_transaction_with_registry, Severity,
+        TxCheckIssue,
+    },
+    types::ScriptRegistry,
+    unlock::ScriptUnlocker,
+    ScriptId, Since,
+};
+
+/// A transaction spending two cells of account1, it passes all the checks
+fn init_tx() -> (Context, TransactionView) {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let mut ctx = init_context(Vec::new(), Vec::new());
+    let inputs: Vec<CellInput> = (0..2)
+        .map(|_| {
+            let out_point = random_out_point();
+            ctx.add_simple_live_cell(out_point.clone(), sender.clone(), Some(100 * ONE_CKB));
+            CellInput::new(out_point, 0)
+        })
+        .collect();
+    let sighash_dep = ctx
+        .cell_dep_map
+        .get(&ScriptId::new_type(SIGHASH_TYPE_HASH.clone()))
+        .unwrap()
+        .clone();
+    let tx = TransactionBuilder::default()
+        .cell_dep(sighash_dep)
+        .inputs(inputs)
+        .output(
+            CellOutput::new_builder()
+                .capacity((199 * ONE_CKB).pack())
+                .lock(build_sighash_script(ACCOUNT2_ARG))
+                .build(),
+        )
+        .output_data(Bytes::new().pack())
+        .witness(WitnessArgs::default().as_bytes().pack())
+        .build();
+    (ctx, tx)
+}
+
+fn check(ctx: &Context, tx: &TransactionView) -> Vec<TxCheckIssue> {
+    check_transaction(tx, ctx, None)
+}
+
+#[test]
+fn test_check_valid_tx() {
+    let (ctx, tx) = init_tx();
+    assert_eq!(check(&ctx, &tx), Vec::new());
+    assert_eq!(check_transaction_strict(&tx, &ctx, None), Ok(Vec::new()));
+}
+
+#[test]
+fn test_check_empty_inputs() {
+    let (ctx, tx) = init_tx();
+    let tx = tx.as_advanced_builder().set_inputs(Vec::new()).build();
+    let issues = check(&ctx, &tx);
+    assert_eq!(issues, vec![TxCheckIssue::EmptyInputs]);
+    assert_eq!(issues[0].index(), None);
+}
+
+#[test]
+fn test_check_outputs_data_mismatch() {
+    let (ctx, tx) = init_tx();
+    let tx = tx
+        .as_advanced_builder()
+        .set_outputs_data(Vec::new())
+        .build();
+    assert_eq!(
+        check(&ctx, &tx),
+        vec![TxCheckIssue::OutputsDataMismatch {
+            outputs: 1,
+            outputs_data: 0
+        }]
+    );
+}
+
+#[test]
+fn test_check_output_capacity_not_enough() {
+    let (ctx, tx) = init_tx();
+    let output = tx
+        .output(0)
+        .unwrap()
+        .as_builder()
+        .capacity((60 * ONE_CKB).pack())
+        .build();
+    let tx = tx
+        .as_advanced_builder()
+        .output(output)
+        .output_data(Bytes::from(vec![0u8; 10]).pack())
+        .build();
+    let issues = check(&ctx, &tx);
+    assert_eq!(
+        issues,
+        vec![TxCheckIssue::OutputCapacityNotEnough {
+            index: 1,
+            capacity: 60 * ONE_CKB,
+            occupied: 71 * ONE_CKB,
+        }]
+    );
+    assert_eq!(issues[0].index(), Some(1));
+}
+
+#[test]
+fn test_check_duplicate_input() {
+    let (ctx, tx) = init_tx();
+    let tx = tx
+        .as_advanced_builder()
+        .input(tx.inputs().get(0).unwrap())
+        .build();
+    assert_eq!(check(&ctx, &tx), vec![TxCheckIssue::DuplicateInput(2)]);
+}
+
+#[test]
+fn test_check_duplicate_cell_dep() {
+    let (ctx, tx) = init_tx();
+    let tx = tx
+        .as_advanced_builder()
+        .cell_dep(tx.cell_deps().get(0).unwrap())
+        .build();
+    assert_eq!(check(&ctx, &tx), vec![TxCheckIssue::DuplicateCellDep(1)]);
+}
+
+#[test]
+fn test_check_tx_size_exceeded() {
+    let (ctx, tx) = init_tx();
+    let size = tx.data().as_reader().serialized_size_in_block() as u64;
+    let consensus = ConsensusBuilder::default()
+        .max_block_bytes(size - 1)
+        .build();
+    let issues = check_transaction(&tx, &ctx, Some(&consensus));
+    assert_eq!(
+        issues,
+        vec![TxCheckIssue::TxSizeExceeded {
+            size,
+            limit: size - 1
+        }]
+    );
+
+    let tx = tx
+        .as_advanced_builder()
+        .witness(Bytes::from(vec![0u8; 600_000]).pack())
+        .build();
+    assert!(matches!(
+        check(&ctx, &tx).as_slice(),
+        [TxCheckIssue::TxSizeExceeded { .. }]
+    ));
+}
+
+#[test]
+fn test_check_unsatisfiable_since() {
+    let (ctx, tx) = init_tx();
+    // invalid flags and a malformed epoch fraction
+    let invalid_since = [0x0100_0000_0000_0000u64, 0x2000_0100_0100_0001];
+    let inputs: Vec<CellInput> = tx
+        .inputs()
+        .into_iter()
+        .zip(invalid_since.iter())
+        .map(|(input, since)| input.as_builder().since(since.pack()).build())
+        .collect();
+    let tx = tx.as_advanced_builder().set_inputs(inputs).build();
+    assert_eq!(
+        check(&ctx, &tx),
+        vec![
+            TxCheckIssue::UnsatisfiableSince {
+                index: 0,
+                since: invalid_since[0]
+            },
+            TxCheckIssue::UnsatisfiableSince {
+                index: 1,
+                since: invalid_since[1]
+            },
+        ]
+    );
+    assert!(Since::from_raw(invalid_since[1]).is_err());
+}
+
+#[test]
+fn test_check_missing_group_witness() {
+    let (mut ctx, tx) = init_tx();
+    let out_point = random_out_point();
+    ctx.add_simple_live_cell(
+        out_point.clone(),
+        build_sighash_script(ACCOUNT2_ARG),
+        Some(100 * ONE_CKB),
+    );
+    // the witness of the first input of the account2 group is missing
+    let tx = tx
+        .as_advanced_builder()
+        .input(CellInput::new(out_point, 0))
+        .build();
+    assert_eq!(check(&ctx, &tx), vec![TxCheckIssue::MissingGroupWitness(2)]);
+
+    let tx = tx.as_advanced_builder().set_witnesses(Vec::new()).build();
+    assert_eq!(
+        check(&ctx, &tx),
+        vec![
+            TxCheckIssue::MissingGroupWitness(0),
+            TxCheckIssue::MissingGroupWitness(2)
+        ]
+    );
+}
+
+#[test]
+fn test_check_unresolved_input() {
+    let (ctx, tx) = init_tx();
+    let tx = tx
+        .as_advanced_builder()
+        .input(CellInput::new(random_out_point(), 0))
+        .build();
+    let issues = check(&ctx, &tx);
+    assert!(matches!(
+        issues.as_slice(),
+        [TxCheckIssue::UnresolvedInput { index: 2, .. }]
+    ));
+    assert_eq!(issues[0].severity(), Severity::Warning);
+    // only a warning
+    assert_eq!(check_transaction_strict(&tx, &ctx, None), Ok(issues));
+}
+
+#[test]
+fn test_check_missing_cell_dep() {
+    let (ctx, tx) = init_tx();
+    // the test context is built from the testnet genesis block
+    let registry = ScriptRegistry::testnet();
+    assert_eq!(
+        check_transaction_with_registry(&tx, &ctx, None, &registry),
+        Vec::new()
+    );
+
+    let tx = tx.as_advanced_builder().set_cell_deps(Vec::new()).build();
+    let issues = check_transaction_with_registry(&tx, &ctx, None, &registry);
+    assert_eq!(
+        issues,
+        vec![TxCheckIssue::MissingCellDep(
+            ScriptRegistry::SIGHASH.to_string()
+        )]
+    );
+    assert_eq!(issues[0].severity(), Severity::Warning);
+    assert_eq!(check(&ctx, &tx), Vec::new());
+}
+
+#[test]
+fn test_check_build_balanced() {
+    let (ctx, _) = init_tx();
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let mut balancer = build_sighash_balancer(&sender);
+    balancer.check_transaction = true;
+    let unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>> = HashMap::default();
+    let build = |capacity: u64| {
+        let output = CellOutput::new_builder()
+            .capacity(capacity.pack())
+            .lock(build_sighash_script(ACCOUNT2_ARG))
+            .build();
+        CapacityTransferBuilder::new(vec![(output, Bytes::new())]).build_balanced(
+            &mut ctx.to_live_cells_context(),
+            &ctx,
+            &ctx,
+            &ctx,
+            &balancer,
+            &unlockers,
+        )
+    };
+    build(120 * ONE_CKB).unwrap();
+    // the output is too small to hold itself
+    match build(ONE_CKB) {
+        Err(TxBuilderError::TxCheck(err)) => {
+            assert!(matches!(
+                err.0.as_slice(),
+                [TxCheckIssue::OutputCapacityNotEnough { index: 0, .. }]
+            ));
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
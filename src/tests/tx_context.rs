@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+use ckb_types::{
+    bytes::Bytes,
+    core::{HeaderView, TransactionView},
+    packed::{Byte32, CellInput, CellOutput, OutPoint},
+    prelude::*,
+};
+use parking_lot::Mutex;
+
+use crate::{
+    constants::ONE_CKB,
+    test_util::{random_out_point, Context},
+    tests::{
+        build_sighash_balancer, build_sighash_script, build_sighash_unlockers, init_context,
+        ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG, FEE_RATE,
+    },
+    traits::{TransactionDependencyError, TransactionDependencyProvider},
+    tx_builder::{gen_script_groups, transfer::CapacityTransferBuilder, TxBuilder, TxContext},
+};
+
+/// Counts the cells loaded from the context
+struct CountingProvider<'a> {
+    ctx: &'a Context,
+    cell_loads: Mutex<HashMap<OutPoint, usize>>,
+}
+
+impl<'a> TransactionDependencyProvider for CountingProvider<'a> {
+    fn get_transaction(
+        &self,
+        tx_hash: &Byte32,
+    ) -> Result<TransactionView, TransactionDependencyError> {
+        self.ctx.get_transaction(tx_hash)
+    }
+
+    fn get_cell(&self, out_point: &OutPoint) -> Result<CellOutput, TransactionDependencyError> {
+        *self.cell_loads.lock().entry(out_point.clone()).or_default() += 1;
+        self.ctx.get_cell(out_point)
+    }
+
+    fn get_cell_data(&self, out_point: &OutPoint) -> Result<Bytes, TransactionDependencyError> {
+        self.ctx.get_cell_data(out_point)
+    }
+
+    fn get_header(&self, block_hash: &Byte32) -> Result<HeaderView, TransactionDependencyError> {
+        self.ctx.get_header(block_hash)
+    }
+
+    fn get_block_extension(
+        &self,
+        block_hash: &Byte32,
+    ) -> Result<Option<ckb_types::packed::Bytes>, TransactionDependencyError> {
+        self.ctx.get_block_extension(block_hash)
+    }
+}
+
+#[test]
+fn test_build_loads_cells_once() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let ctx = init_context(
+        Vec::new(),
+        vec![
+            (sender.clone(), Some(100 * ONE_CKB)),
+            (sender.clone(), Some(200 * ONE_CKB)),
+            (sender.clone(), Some(300 * ONE_CKB)),
+        ],
+    );
+
+    let output = CellOutput::new_builder()
+        .capacity((450 * ONE_CKB).pack())
+        .lock(receiver)
+        .build();
+    let builder = CapacityTransferBuilder::new(vec![(output, Bytes::default())]);
+    let balancer = build_sighash_balancer(&sender);
+    let unlockers = build_sighash_unlockers(&[ACCOUNT1_KEY]);
+
+    let provider = CountingProvider {
+        ctx: &ctx,
+        cell_loads: Mutex::new(HashMap::new()),
+    };
+    let mut cell_collector = ctx.to_live_cells_context();
+    let (tx, locked_groups) = builder
+        .build_unlocked(
+            &mut cell_collector,
+            &ctx,
+            &ctx,
+            &provider,
+            &balancer,
+            &unlockers,
+        )
+        .unwrap();
+    assert!(locked_groups.is_empty());
+    assert_eq!(tx.inputs().len(), 3);
+
+    let cell_loads = provider.cell_loads.lock();
+    for out_point in tx.input_pts_iter() {
+        assert_eq!(cell_loads.get(&out_point), Some(&1));
+    }
+    assert!(cell_loads.values().all(|count| *count == 1));
+    drop(cell_loads);
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_tx_context_script_groups() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let mut ctx = init_context(Vec::new(), Vec::new());
+    let mut out_points = Vec::new();
+    for (lock, capacity) in [(&sender, 100), (&receiver, 200), (&sender, 300)] {
+        let out_point = random_out_point();
+        ctx.add_simple_live_cell(out_point.clone(), lock.clone(), Some(capacity * ONE_CKB));
+        out_points.push(out_point);
+    }
+    let build_tx = |count: usize| {
+        TransactionView::new_advanced_builder()
+            .inputs(
+                out_points[..count]
+                    .iter()
+                    .map(|out_point| CellInput::new(out_point.clone(), 0)),
+            )
+            .build()
+    };
+
+    let mut tx_context = TxContext::new(&ctx);
+    for tx in [build_tx(1), build_tx(3), build_tx(2)] {
+        let groups = tx_context.script_groups(&tx).unwrap();
+        let expected = gen_script_groups(&tx, &ctx).unwrap();
+        assert_eq!(groups.lock_groups, expected.lock_groups);
+        assert_eq!(groups.type_groups, expected.type_groups);
+    }
+}
@@ -0,0 +1,140 @@
+use ckb_hash::new_blake2b;
+use ckb_types::{
+    bytes::Bytes,
+    core::{Capacity, ScriptHashType},
+    packed::{CellInput, CellOutput, Script},
+    prelude::*,
+    H256,
+};
+
+use crate::{
+    constants::{ONE_CKB, TYPE_ID_CODE_HASH},
+    test_util::random_out_point,
+    tests::{
+        build_sighash_balancer, build_sighash_script, build_sighash_unlockers, init_context,
+        ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG, FEE_RATE,
+    },
+    tx_builder::{
+        type_id::{calculate_type_id, TypeIdCreateBuilder, TypeIdUpdateBuilder},
+        TxBuilder,
+    },
+    types::ScriptHashTypeExt,
+};
+
+#[test]
+fn test_type_id_create() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let owner = build_sighash_script(ACCOUNT2_ARG);
+    let ctx = init_context(
+        Vec::new(),
+        vec![
+            (sender.clone(), Some(100 * ONE_CKB)),
+            (sender.clone(), Some(200 * ONE_CKB)),
+        ],
+    );
+    let data = Bytes::from(vec![0x42u8; 100]);
+    let builder = TypeIdCreateBuilder::new(owner.clone(), data.clone());
+
+    let mut cell_collector = ctx.to_live_cells_context();
+    let (tx, locked_groups) = builder
+        .build_unlocked(
+            &mut cell_collector,
+            &ctx,
+            &ctx,
+            &ctx,
+            &build_sighash_balancer(&sender),
+            &build_sighash_unlockers(&[ACCOUNT1_KEY]),
+        )
+        .unwrap();
+
+    assert!(locked_groups.is_empty());
+    assert_eq!(tx.cell_deps().len(), 1);
+    assert_eq!(tx.outputs().len(), 2);
+    let output = tx.output(0).unwrap();
+    assert_eq!(output.lock(), owner);
+    assert_eq!(tx.outputs_data().get(0).unwrap().raw_data(), data);
+    let occupied_capacity = output
+        .occupied_capacity(Capacity::bytes(data.len()).unwrap())
+        .unwrap()
+        .as_u64();
+    let capacity: u64 = output.capacity().unpack();
+    assert_eq!(capacity, occupied_capacity);
+
+    // blake2b(first input | output index)
+    let mut blake2b = new_blake2b();
+    blake2b.update(tx.inputs().get(0).unwrap().as_slice());
+    blake2b.update(&0u64.to_le_bytes());
+    let mut type_id = [0u8; 32];
+    blake2b.finalize(&mut type_id);
+    let type_script = output.type_().to_opt().unwrap();
+    assert_eq!(type_script.code_hash(), TYPE_ID_CODE_HASH.pack());
+    assert_eq!(type_script.hash_type(), ScriptHashType::Type.to_packed());
+    assert_eq!(type_script.args().raw_data().as_ref(), &type_id[..]);
+    assert_eq!(
+        calculate_type_id(&tx.inputs().get(0).unwrap(), 0),
+        H256::from(type_id)
+    );
+
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_type_id_update() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let new_owner = build_sighash_script(ACCOUNT2_ARG);
+    let mut ctx = init_context(Vec::new(), vec![(sender.clone(), Some(500 * ONE_CKB))]);
+    let type_id_script = Script::new_builder()
+        .code_hash(TYPE_ID_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.to_packed())
+        .args(Bytes::from(vec![3u8; 32]).pack())
+        .build();
+    let type_id_output = CellOutput::new_builder()
+        .capacity((150 * ONE_CKB).pack())
+        .lock(sender.clone())
+        .type_(Some(type_id_script.clone()).pack())
+        .build();
+    let type_id_input = CellInput::new(random_out_point(), 0);
+    ctx.add_live_cell(
+        type_id_input.clone(),
+        type_id_output,
+        Bytes::from(vec![1u8; 10]),
+        None,
+    );
+
+    let new_data = Bytes::from(vec![2u8; 200]);
+    let mut builder = TypeIdUpdateBuilder::new(type_id_script.clone(), new_data.clone());
+    builder.new_lock = Some(new_owner.clone());
+    let mut cell_collector = ctx.to_live_cells_context();
+    let (tx, locked_groups) = builder
+        .build_unlocked(
+            &mut cell_collector,
+            &ctx,
+            &ctx,
+            &ctx,
+            &build_sighash_balancer(&sender),
+            &build_sighash_unlockers(&[ACCOUNT1_KEY]),
+        )
+        .unwrap();
+
+    assert!(locked_groups.is_empty());
+    assert_eq!(tx.cell_deps().len(), 1);
+    assert_eq!(tx.inputs().get(0).unwrap(), type_id_input);
+    let output = tx.output(0).unwrap();
+    assert_eq!(output.lock(), new_owner);
+    assert_eq!(output.type_().to_opt(), Some(type_id_script.clone()));
+    assert_eq!(tx.outputs_data().get(0).unwrap().raw_data(), new_data);
+    // 8 (capacity) + 53 (lock) + 65 (type) + 200 (data)
+    let capacity: u64 = output.capacity().unpack();
+    assert_eq!(capacity, 326 * ONE_CKB);
+    ctx.verify(tx, FEE_RATE).unwrap();
+
+    // the type id cell is spent
+    let err = builder.build_base(&mut cell_collector, &ctx, &ctx, &ctx);
+    assert!(err.is_err());
+
+    // not a type id script
+    let builder = TypeIdUpdateBuilder::new(sender, new_data);
+    assert!(builder
+        .build_base(&mut ctx.to_live_cells_context(), &ctx, &ctx, &ctx)
+        .is_err());
+}
@@ -0,0 +1,162 @@
+use ckb_hash::blake2b_256;
+use ckb_types::{
+    bytes::Bytes,
+    core::{Capacity, ScriptHashType},
+    packed::{CellOutput, Script},
+    prelude::*,
+    H256,
+};
+
+use crate::{
+    constants::ONE_CKB,
+    traits::CellDepResolver,
+    tx_builder::{
+        udt::{OwnerMode, UdtIssueBuilder, UdtTargetReceiver, UdtType},
+        unlock_tx, CapacityBalancer, TransferAction, TxBuilder,
+    },
+    types::ScriptHashTypeExt,
+    unlock::MultisigConfig,
+    ScriptId,
+};
+
+use super::{
+    build_multisig_script, build_multisig_unlockers, build_sighash_script, init_context,
+    ACCOUNT0_ARG, ACCOUNT0_KEY, ACCOUNT1_ARG, ACCOUNT2_ARG, ACCOUNT2_KEY, ACCOUNT3_ARG,
+    ALWAYS_SUCCESS_BIN, FEE_RATE,
+};
+
+#[test]
+fn test_xudt_issue_with_multisig_owner() {
+    // always_success stands in for the xUDT script, the owner lock is the
+    // real multisig script
+    let xudt_data_hash = H256::from(blake2b_256(ALWAYS_SUCCESS_BIN));
+    let cfg = MultisigConfig::new_with(
+        vec![
+            ACCOUNT0_ARG.clone(),
+            ACCOUNT1_ARG.clone(),
+            ACCOUNT2_ARG.clone(),
+        ],
+        0,
+        2,
+    )
+    .unwrap();
+    let owner = build_multisig_script(&cfg);
+    let receiver = build_sighash_script(ACCOUNT3_ARG);
+    let ctx = init_context(
+        vec![(ALWAYS_SUCCESS_BIN, false)],
+        vec![
+            (owner.clone(), Some(100 * ONE_CKB)),
+            (owner.clone(), Some(200 * ONE_CKB)),
+        ],
+    );
+
+    let xudt_args = Bytes::from(vec![0u8; 4]);
+    let builder = UdtIssueBuilder {
+        udt_type: UdtType::new_xudt(xudt_args.clone()),
+        script_id: ScriptId::new_data1(xudt_data_hash.clone()),
+        owner: owner.clone(),
+        owner_mode: OwnerMode::ConsumeOwnerCell,
+        receivers: vec![UdtTargetReceiver::new(
+            TransferAction::Create,
+            receiver.clone(),
+            1000,
+        )],
+        data_validator: None,
+    };
+    let placeholder_witness = cfg.placeholder_witness();
+    let balancer =
+        CapacityBalancer::new_simple(owner.clone(), placeholder_witness.clone(), FEE_RATE);
+
+    let account0_key = secp256k1::SecretKey::from_slice(ACCOUNT0_KEY.as_bytes()).unwrap();
+    let account2_key = secp256k1::SecretKey::from_slice(ACCOUNT2_KEY.as_bytes()).unwrap();
+    let mut cell_collector = ctx.to_live_cells_context();
+    let tx = builder
+        .build_balanced(
+            &mut cell_collector,
+            &ctx,
+            &ctx,
+            &ctx,
+            &balancer,
+            &build_multisig_unlockers(account0_key, cfg.clone()),
+        )
+        .unwrap();
+
+    let owner_input = tx.inputs().get(UdtIssueBuilder::OWNER_INPUT_INDEX).unwrap();
+    let (owner_cell, owner_data) = ctx.get_input(&owner_input.previous_output()).unwrap();
+    assert_eq!(owner_cell.lock(), owner);
+    assert!(owner_cell.type_().is_none());
+    assert!(owner_data.is_empty());
+    let multisig_dep = ctx.resolve(&owner).unwrap();
+    assert!(tx.cell_deps().into_iter().any(|dep| dep == multisig_dep));
+    // the owner group witness is sized by the multisig placeholder, not the
+    // 65 bytes sighash one
+    let owner_witness = tx
+        .witnesses()
+        .get(UdtIssueBuilder::OWNER_INPUT_INDEX)
+        .unwrap()
+        .raw_data();
+    assert_eq!(owner_witness.len(), placeholder_witness.as_slice().len());
+    for out_point in tx.input_pts_iter() {
+        assert_eq!(ctx.get_input(&out_point).unwrap().0.lock(), owner);
+    }
+
+    // every party signs a copy of the transaction it received from the last one
+    let (partial_tx, locked_groups) = unlock_tx(
+        tx,
+        &ctx,
+        &build_multisig_unlockers(account0_key, cfg.clone()),
+    )
+    .unwrap();
+    assert!(locked_groups.is_empty());
+    assert!(ctx.verify(partial_tx.clone(), FEE_RATE).is_err());
+    let (tx, locked_groups) = unlock_tx(
+        partial_tx.clone(),
+        &ctx,
+        &build_multisig_unlockers(account2_key, cfg.clone()),
+    )
+    .unwrap();
+    assert!(locked_groups.is_empty());
+    let owner_witness = tx
+        .witnesses()
+        .get(UdtIssueBuilder::OWNER_INPUT_INDEX)
+        .unwrap()
+        .raw_data();
+    assert_eq!(owner_witness.len(), placeholder_witness.as_slice().len());
+    assert_ne!(
+        owner_witness,
+        partial_tx
+            .witnesses()
+            .get(UdtIssueBuilder::OWNER_INPUT_INDEX)
+            .unwrap()
+            .raw_data()
+    );
+
+    let mut type_args = owner.calc_script_hash().as_bytes().to_vec();
+    type_args.extend_from_slice(&xudt_args);
+    let type_script = Script::new_builder()
+        .code_hash(xudt_data_hash.pack())
+        .hash_type(ScriptHashType::Data1.to_packed())
+        .args(Bytes::from(type_args).pack())
+        .build();
+    let output = CellOutput::new_builder()
+        .lock(receiver)
+        .type_(Some(type_script).pack())
+        .build();
+    let occupied_capacity = output
+        .occupied_capacity(Capacity::bytes(16).unwrap())
+        .unwrap()
+        .as_u64();
+    assert_eq!(
+        tx.output(0).unwrap(),
+        output
+            .as_builder()
+            .capacity(occupied_capacity.pack())
+            .build()
+    );
+    assert_eq!(
+        tx.outputs_data().get(0).unwrap().raw_data(),
+        Bytes::from(1000u128.to_le_bytes().to_vec())
+    );
+    assert_eq!(tx.output(1).unwrap().lock(), owner);
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
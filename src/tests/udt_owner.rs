@@ -0,0 +1,104 @@
+use ckb_hash::blake2b_256;
+use ckb_types::{bytes::Bytes, core::DepType, packed::CellDep, prelude::*, H256};
+
+use crate::{
+    constants::ONE_CKB,
+    test_util::Context,
+    traits::CellDepResolver,
+    tx_builder::{
+        lint::XUDT_OWNER_MODE_INPUT_LOCK_NOT,
+        udt::{OwnerMode, UdtIssueBuilder, UdtTargetReceiver, UdtType},
+        TransferAction, TxBuilder, TxBuilderError,
+    },
+    ScriptId,
+};
+
+use super::{
+    build_sighash_balancer, build_sighash_script, build_sighash_unlockers, init_context,
+    ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG, ACCOUNT2_KEY, ACCOUNT3_ARG, ALWAYS_SUCCESS_BIN,
+    FEE_RATE,
+};
+
+fn build_issue(ctx: &Context, owner_mode: OwnerMode, flags: u32) -> Result<(), TxBuilderError> {
+    // always_success stands in for the xUDT script
+    let xudt_script_id = ScriptId::new_data1(H256::from(blake2b_256(ALWAYS_SUCCESS_BIN)));
+    let owner = build_sighash_script(ACCOUNT1_ARG);
+    let payer = build_sighash_script(ACCOUNT2_ARG);
+    let receiver = build_sighash_script(ACCOUNT3_ARG);
+    let builder = UdtIssueBuilder {
+        udt_type: UdtType::new_xudt(Bytes::from(flags.to_le_bytes().to_vec())),
+        script_id: xudt_script_id,
+        owner: owner.clone(),
+        owner_mode: owner_mode.clone(),
+        receivers: vec![UdtTargetReceiver::new(
+            TransferAction::Create,
+            receiver,
+            1000,
+        )],
+        data_validator: None,
+    };
+    let balancer = build_sighash_balancer(&payer);
+
+    let mut cell_collector = ctx.to_live_cells_context();
+    let (tx, locked_groups) = builder.build_unlocked(
+        &mut cell_collector,
+        ctx,
+        ctx,
+        ctx,
+        &balancer,
+        &build_sighash_unlockers(&[ACCOUNT1_KEY, ACCOUNT2_KEY]),
+    )?;
+    assert!(locked_groups.is_empty());
+
+    let owner_dep = ctx.resolve(&owner).unwrap();
+    assert!(tx.cell_deps().into_iter().any(|dep| dep == owner_dep));
+    let input_locks: Vec<_> = tx
+        .input_pts_iter()
+        .map(|out_point| ctx.get_input(&out_point).unwrap().0.lock())
+        .collect();
+    match owner_mode {
+        OwnerMode::ConsumeOwnerCell => {
+            assert_eq!(input_locks[UdtIssueBuilder::OWNER_INPUT_INDEX], owner);
+        }
+        OwnerMode::OwnerCellDep { out_point } => {
+            assert!(input_locks.iter().all(|lock| *lock == payer));
+            let owner_cell_dep = CellDep::new_builder()
+                .out_point(out_point)
+                .dep_type(DepType::Code.into())
+                .build();
+            assert!(tx.cell_deps().into_iter().any(|dep| dep == owner_cell_dep));
+        }
+    }
+    ctx.verify(tx, FEE_RATE).unwrap();
+    Ok(())
+}
+
+#[test]
+fn test_xudt_issue_owner_modes() {
+    let owner = build_sighash_script(ACCOUNT1_ARG);
+    let payer = build_sighash_script(ACCOUNT2_ARG);
+    let mut ctx = init_context(vec![(ALWAYS_SUCCESS_BIN, false)], Vec::new());
+    let owner_out_points = ctx.fund(&owner, &[100 * ONE_CKB]);
+    let payer_out_points = ctx.fund(&payer, &[300 * ONE_CKB]);
+
+    build_issue(&ctx, OwnerMode::ConsumeOwnerCell, 0).unwrap();
+    build_issue(
+        &ctx,
+        OwnerMode::OwnerCellDep {
+            out_point: owner_out_points[0].clone(),
+        },
+        XUDT_OWNER_MODE_INPUT_LOCK_NOT,
+    )
+    .unwrap();
+
+    // the cell dep must be a cell of the owner lock
+    let err = build_issue(
+        &ctx,
+        OwnerMode::OwnerCellDep {
+            out_point: payer_out_points[0].clone(),
+        },
+        XUDT_OWNER_MODE_INPUT_LOCK_NOT,
+    )
+    .unwrap_err();
+    assert!(matches!(err, TxBuilderError::InvalidParameter(_)));
+}
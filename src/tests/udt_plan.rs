@@ -0,0 +1,161 @@
+use ckb_hash::blake2b_256;
+use ckb_types::{
+    bytes::Bytes,
+    core::ScriptHashType,
+    packed::{CellInput, CellOutput, OutPoint, Script},
+    prelude::*,
+    H160, H256,
+};
+
+use crate::{
+    constants::ONE_CKB,
+    test_util::{random_out_point, Context},
+    tests::{
+        build_sighash_script, init_context, ACCOUNT1_ARG, ACCOUNT2_ARG, ACCOUNT3_ARG, SUDT_BIN,
+    },
+    tx_builder::{
+        udt::{PlannedAction, UdtTargetReceiver, UdtTransferBuilder, UdtTransferPlan},
+        TransferAction, TxBuilder,
+    },
+    types::ScriptHashTypeExt,
+};
+
+fn build_udt_context() -> (Context, UdtTransferBuilder, CellInput, CellInput) {
+    let sudt_data_hash = H256::from(blake2b_256(SUDT_BIN));
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let owner = build_sighash_script(H160::default());
+    let type_script = Script::new_builder()
+        .code_hash(sudt_data_hash.pack())
+        .hash_type(ScriptHashType::Data1.to_packed())
+        .args(owner.calc_script_hash().as_bytes().pack())
+        .build();
+    let mut ctx = init_context(
+        vec![(SUDT_BIN, false)],
+        vec![(sender.clone(), Some(100 * ONE_CKB))],
+    );
+
+    let sender_input = CellInput::new(random_out_point(), 0);
+    let sender_output = CellOutput::new_builder()
+        .capacity((200 * ONE_CKB).pack())
+        .lock(sender.clone())
+        .type_(Some(type_script.clone()).pack())
+        .build();
+    let sender_data = Bytes::from(500u128.to_le_bytes().to_vec());
+    ctx.add_live_cell(sender_input.clone(), sender_output, sender_data, None);
+
+    let receiver_lock = build_sighash_script(ACCOUNT2_ARG);
+    let receiver_input = CellInput::new(random_out_point(), 0);
+    let receiver_output = CellOutput::new_builder()
+        .capacity((200 * ONE_CKB).pack())
+        .lock(receiver_lock.clone())
+        .type_(Some(type_script.clone()).pack())
+        .build();
+    let receiver_data = Bytes::from(100u128.to_le_bytes().to_vec());
+    ctx.add_live_cell(receiver_input.clone(), receiver_output, receiver_data, None);
+
+    let builder = UdtTransferBuilder {
+        type_script,
+        sender,
+        receivers: vec![
+            UdtTargetReceiver::new(TransferAction::Update, receiver_lock, 300),
+            UdtTargetReceiver::new(
+                TransferAction::Create,
+                build_sighash_script(ACCOUNT3_ARG),
+                50,
+            ),
+        ],
+        data_validator: None,
+    };
+    (ctx, builder, sender_input, receiver_input)
+}
+
+#[test]
+fn test_udt_plan_matches_build() {
+    let (ctx, builder, sender_input, receiver_input) = build_udt_context();
+
+    let mut cell_collector = ctx.to_live_cells_context();
+    let plan = builder.plan(&mut cell_collector).unwrap();
+    assert_eq!(u128::from(plan.change_amount), 150);
+    let input_out_points = plan
+        .input_cells()
+        .into_iter()
+        .map(|cell| OutPoint::from(cell.out_point.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        input_out_points,
+        vec![
+            sender_input.previous_output(),
+            receiver_input.previous_output()
+        ]
+    );
+    assert!(matches!(
+        plan.receivers[0].action,
+        PlannedAction::Update { .. }
+    ));
+    assert!(matches!(
+        plan.receivers[1].action,
+        PlannedAction::Create { .. }
+    ));
+
+    let tx = builder.build_from_plan(&plan, &ctx).unwrap();
+    let base_tx = builder
+        .build_base(&mut ctx.to_live_cells_context(), &ctx, &ctx, &ctx)
+        .unwrap();
+    assert_eq!(tx.data(), base_tx.data());
+    assert_eq!(
+        tx.inputs().into_iter().collect::<Vec<_>>(),
+        vec![sender_input, receiver_input]
+    );
+    let outputs_data = tx
+        .outputs_data()
+        .into_iter()
+        .map(|data| data.raw_data())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        outputs_data,
+        vec![
+            Bytes::from(150u128.to_le_bytes().to_vec()),
+            Bytes::from(400u128.to_le_bytes().to_vec()),
+            Bytes::from(50u128.to_le_bytes().to_vec()),
+        ]
+    );
+    for (idx, cell) in plan.input_cells().into_iter().enumerate() {
+        assert_eq!(
+            tx.output(idx).unwrap(),
+            CellOutput::from(cell.output.clone())
+        );
+    }
+
+    // The plan can be stored and built later
+    let json = serde_json::to_string(&plan).unwrap();
+    let loaded: UdtTransferPlan = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded, plan);
+    assert_eq!(
+        builder.build_from_plan(&loaded, &ctx).unwrap().data(),
+        tx.data()
+    );
+}
+
+#[test]
+fn test_udt_plan_revalidate() {
+    let (ctx, builder, _, _) = build_udt_context();
+
+    let mut cell_collector = ctx.to_live_cells_context();
+    let plan = builder.plan(&mut cell_collector).unwrap();
+    plan.revalidate(&mut ctx.to_live_cells_context()).unwrap();
+    // The cells are locked by the collector used to plan
+    assert!(plan.revalidate(&mut cell_collector).is_err());
+
+    // A changed cell is detected
+    let mut stale_plan = plan.clone();
+    stale_plan.sender_cell.output_data =
+        ckb_jsonrpc_types::JsonBytes::from_vec(600u128.to_le_bytes().to_vec());
+    assert!(stale_plan
+        .revalidate(&mut ctx.to_live_cells_context())
+        .is_err());
+
+    // An unbalanced plan is rejected
+    let mut bad_plan = plan;
+    bad_plan.change_amount = 151u128.into();
+    assert!(builder.build_from_plan(&bad_plan, &ctx).is_err());
+}
@@ -0,0 +1,144 @@
+use ckb_hash::blake2b_256;
+use ckb_types::{bytes::Bytes, core::ScriptHashType, packed::Script, prelude::*, H160, H256};
+
+use crate::{
+    constants::ONE_CKB,
+    tests::{
+        add_udt_cell, build_sighash_balancer, build_sighash_script, build_sighash_unlockers,
+        build_sudt_script, init_context, ACCOUNT0_ARG, ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG,
+        ACCOUNT3_ARG, ACP_BIN, CHEQUE_BIN, FEE_RATE, SUDT_BIN,
+    },
+    traits::SecpCkbRawKeySigner,
+    tx_builder::{
+        udt::{UdtReceiverDecision, UdtSmartTransferBuilder},
+        TxBuilder,
+    },
+    types::ScriptHashTypeExt,
+    unlock::AcpUnlocker,
+    ScriptId,
+};
+
+#[test]
+fn test_udt_smart_transfer() {
+    let acp_data_hash = H256::from(blake2b_256(ACP_BIN));
+    let cheque_data_hash = H256::from(blake2b_256(CHEQUE_BIN));
+    let omni_lock_code_hash = H256::from([9u8; 32]);
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let owner = build_sighash_script(H160::default());
+    let type_script = build_sudt_script(&owner);
+    let build_acp_lock = |arg: H160| {
+        Script::new_builder()
+            .code_hash(acp_data_hash.pack())
+            .hash_type(ScriptHashType::Data1.to_packed())
+            .args(Bytes::from(arg.0.to_vec()).pack())
+            .build()
+    };
+    // <1 byte identity flag> <20 bytes auth> <1 byte omni-lock flags (ACP)> <2 bytes acp config>
+    let mut omni_args = vec![0u8; 21];
+    omni_args.extend_from_slice(&[0b10, 1, 1]);
+    let omni_acp_lock = Script::new_builder()
+        .code_hash(omni_lock_code_hash.pack())
+        .hash_type(ScriptHashType::Type.to_packed())
+        .args(Bytes::from(omni_args).pack())
+        .build();
+
+    let mut ctx = init_context(
+        vec![(ACP_BIN, true), (SUDT_BIN, false), (CHEQUE_BIN, true)],
+        vec![(sender.clone(), Some(600 * ONE_CKB))],
+    );
+    let sender_input = add_udt_cell(&mut ctx, &sender, &type_script, 1000);
+    // acp lock with a udt cell
+    let acp_lock_a = build_acp_lock(ACCOUNT2_ARG);
+    let acp_input = add_udt_cell(&mut ctx, &acp_lock_a, &type_script, 1);
+    // acp lock without a udt cell
+    let acp_lock_b = build_acp_lock(ACCOUNT3_ARG);
+    // sighash lock, its udt cell can not be updated without its signature
+    let plain_lock = build_sighash_script(ACCOUNT0_ARG);
+    add_udt_cell(&mut ctx, &plain_lock, &type_script, 1);
+
+    let mut builder = UdtSmartTransferBuilder::new(
+        type_script,
+        sender.clone(),
+        vec![
+            (acp_lock_a.clone(), 100),
+            (acp_lock_b.clone(), 200),
+            (omni_acp_lock.clone(), 300),
+            (plain_lock.clone(), 50),
+        ],
+        ScriptId::new_data1(cheque_data_hash),
+    );
+    builder
+        .acp_script_ids
+        .push(ScriptId::new_data1(acp_data_hash.clone()));
+    assert!(!builder.is_acp_lock(&omni_acp_lock));
+    builder
+        .omni_lock_script_ids
+        .push(ScriptId::new_type(omni_lock_code_hash));
+    assert!(builder.is_acp_lock(&omni_acp_lock));
+    assert!(!builder.is_acp_lock(&plain_lock));
+
+    let (base_tx, decisions) = builder
+        .build_base_with_decisions(&mut ctx.to_live_cells_context(), &ctx)
+        .unwrap();
+    let cheque_lock = builder.cheque_lock_script(&plain_lock);
+    assert_eq!(
+        decisions,
+        vec![
+            UdtReceiverDecision::Update(acp_input.previous_output()),
+            // 8 + 53 (lock) + 65 (type) + 16 (data)
+            UdtReceiverDecision::CreateAcp {
+                capacity: 142 * ONE_CKB
+            },
+            // 8 + 57 (lock) + 65 (type) + 16 (data)
+            UdtReceiverDecision::CreateAcp {
+                capacity: 146 * ONE_CKB
+            },
+            // 8 + 73 (lock) + 65 (type) + 16 (data)
+            UdtReceiverDecision::Cheque {
+                lock_script: cheque_lock.clone(),
+                capacity: 162 * ONE_CKB
+            },
+        ]
+    );
+    assert_eq!(
+        base_tx.inputs().into_iter().collect::<Vec<_>>(),
+        vec![sender_input, acp_input]
+    );
+    let output_locks = base_tx
+        .outputs()
+        .into_iter()
+        .map(|output| output.lock())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        output_locks,
+        vec![sender, acp_lock_a, acp_lock_b, omni_acp_lock, cheque_lock]
+    );
+    let outputs_data = base_tx
+        .outputs_data()
+        .into_iter()
+        .map(|data| data.raw_data())
+        .collect::<Vec<_>>();
+    let expected_outputs_data = [350u128, 101, 200, 300, 50]
+        .iter()
+        .map(|amount| Bytes::from(amount.to_le_bytes().to_vec()))
+        .collect::<Vec<_>>();
+    assert_eq!(outputs_data, expected_outputs_data);
+
+    let balancer = build_sighash_balancer(&build_sighash_script(ACCOUNT1_ARG));
+    let acp_unlocker = AcpUnlocker::from(Box::<SecpCkbRawKeySigner>::default() as Box<_>);
+    let mut unlockers = build_sighash_unlockers(&[ACCOUNT1_KEY]);
+    unlockers.insert(ScriptId::new_data1(acp_data_hash), Box::new(acp_unlocker));
+    let (tx, locked_groups) = builder
+        .build_unlocked(
+            &mut ctx.to_live_cells_context(),
+            &ctx,
+            &ctx,
+            &ctx,
+            &balancer,
+            &unlockers,
+        )
+        .unwrap();
+    assert!(locked_groups.is_empty());
+    assert_eq!(tx.outputs().len(), 6);
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
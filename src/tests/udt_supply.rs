@@ -0,0 +1,92 @@
+use ckb_hash::blake2b_256;
+use ckb_types::{bytes::Bytes, prelude::*, H256};
+
+use crate::{
+    constants::ONE_CKB,
+    tx_builder::{
+        lint::XudtArgs,
+        udt::{OwnerMode, UdtIssueBuilder, UdtTargetReceiver, UdtType, XUDT_FLAG_SUPPLY_LIMIT},
+        TransferAction, TxBuilder, TxBuilderError,
+    },
+    ScriptId,
+};
+
+use super::{
+    build_sighash_balancer, build_sighash_script, build_sighash_unlockers, init_context,
+    ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG, ACCOUNT3_ARG, ALWAYS_SUCCESS_BIN, FEE_RATE,
+};
+
+fn build_issue_builder(amounts: &[u128], supply_limit: u128) -> UdtIssueBuilder {
+    // always_success stands in for the xUDT script
+    let xudt_script_id = ScriptId::new_data1(H256::from(blake2b_256(ALWAYS_SUCCESS_BIN)));
+    let receivers = [ACCOUNT2_ARG, ACCOUNT3_ARG]
+        .iter()
+        .zip(amounts)
+        .map(|(arg, amount)| {
+            UdtTargetReceiver::new(
+                TransferAction::Create,
+                build_sighash_script(arg.clone()),
+                *amount,
+            )
+        })
+        .collect();
+    UdtIssueBuilder {
+        udt_type: UdtType::Xudt {
+            args: Bytes::new(),
+            supply_limit: Some(supply_limit),
+        },
+        script_id: xudt_script_id,
+        owner: build_sighash_script(ACCOUNT1_ARG),
+        owner_mode: OwnerMode::ConsumeOwnerCell,
+        receivers,
+        data_validator: None,
+    }
+}
+
+#[test]
+fn test_xudt_issue_supply_limit() {
+    let owner = build_sighash_script(ACCOUNT1_ARG);
+    let ctx = init_context(
+        vec![(ALWAYS_SUCCESS_BIN, false)],
+        vec![
+            (owner.clone(), Some(100 * ONE_CKB)),
+            (owner.clone(), Some(500 * ONE_CKB)),
+        ],
+    );
+    let balancer = build_sighash_balancer(&owner);
+    let unlockers = build_sighash_unlockers(&[ACCOUNT1_KEY]);
+
+    // the exact supply is issued
+    let builder = build_issue_builder(&[600, 400], 1000);
+    let type_script = builder.type_script();
+    let xudt_args = XudtArgs::parse(&type_script.args().raw_data()).unwrap();
+    assert_eq!(xudt_args.owner_hash, owner.calc_script_hash());
+    assert_eq!(xudt_args.flags, XUDT_FLAG_SUPPLY_LIMIT);
+    assert_eq!(
+        xudt_args.extension_data,
+        Bytes::from(1000u128.to_le_bytes().to_vec())
+    );
+    let mut cell_collector = ctx.to_live_cells_context();
+    let (tx, locked_groups) = builder
+        .build_unlocked(&mut cell_collector, &ctx, &ctx, &ctx, &balancer, &unlockers)
+        .unwrap();
+    assert!(locked_groups.is_empty());
+    for idx in 0..2 {
+        assert_eq!(
+            tx.output(idx).unwrap().type_().to_opt(),
+            Some(type_script.clone())
+        );
+    }
+    ctx.verify(tx, FEE_RATE).unwrap();
+
+    // one unit over the supply limit
+    let builder = build_issue_builder(&[600, 401], 1000);
+    let mut cell_collector = ctx.to_live_cells_context();
+    let err = builder
+        .build_unlocked(&mut cell_collector, &ctx, &ctx, &ctx, &balancer, &unlockers)
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        TxBuilderError::SupplyLimitExceeded(1000, 1001)
+    ));
+}
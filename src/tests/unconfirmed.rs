@@ -0,0 +1,202 @@
+use ckb_hash::blake2b_256;
+
+use ckb_types::{
+    bytes::Bytes,
+    core::{ScriptHashType, TransactionBuilder, TransactionView},
+    packed::{CellInput, CellOutput, OutPoint, Script},
+    prelude::*,
+    H256,
+};
+
+use crate::{
+    constants::ONE_CKB,
+    test_util::random_out_point,
+    tests::{
+        build_sighash_balancer, build_sighash_script, build_sighash_unlockers, init_context,
+        ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG, FEE_RATE, SUDT_BIN,
+    },
+    traits::{CellCollector, CellQueryOptions, OffchainCellCollector},
+    tx_builder::{
+        transfer::CapacityTransferBuilder,
+        udt::{UdtTargetReceiver, UdtTransferBuilder},
+        TransferAction, TxBuilder,
+    },
+    types::ScriptHashTypeExt,
+};
+
+#[test]
+fn test_chain_transfers_offline() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let mut ctx = init_context(Vec::new(), Vec::new());
+    let initial_out_point = random_out_point();
+    ctx.add_simple_live_cell(
+        initial_out_point.clone(),
+        sender.clone(),
+        Some(1000 * ONE_CKB),
+    );
+    let balancer = build_sighash_balancer(&sender);
+    let unlockers = build_sighash_unlockers(&[ACCOUNT1_KEY]);
+    let mut collector = ctx.to_live_cells_context();
+
+    let mut txs = Vec::new();
+    for _ in 0..3 {
+        let output = CellOutput::new_builder()
+            .capacity((100 * ONE_CKB).pack())
+            .lock(build_sighash_script(ACCOUNT2_ARG))
+            .build();
+        let builder = CapacityTransferBuilder::new(vec![(output, Bytes::default())]);
+        let (tx, locked_groups) = builder
+            .build_unlocked(&mut collector, &ctx, &ctx, &ctx, &balancer, &unlockers)
+            .unwrap();
+        assert!(locked_groups.is_empty());
+        // spend the change of the previous transaction
+        let expected_input = match txs.last() {
+            Some(prev_tx) => OutPoint::new(prev_tx.hash(), 1),
+            None => initial_out_point.clone(),
+        };
+        assert_eq!(
+            tx.input_pts_iter().collect::<Vec<_>>(),
+            vec![expected_input]
+        );
+        assert_eq!(tx.outputs().get(1).unwrap().lock(), sender);
+
+        collector.apply_tx(tx.data(), 0).unwrap();
+        // the outputs become live after the transaction is committed
+        for (idx, (output, data)) in tx.outputs_with_data_iter().enumerate() {
+            let input = CellInput::new(OutPoint::new(tx.hash(), idx as u32), 0);
+            ctx.add_live_cell(input, output, data, None);
+        }
+        txs.push(tx);
+    }
+    for tx in txs.iter() {
+        ctx.verify(tx.clone(), FEE_RATE).unwrap();
+    }
+
+    let mut query = CellQueryOptions::new_lock(sender);
+    query.min_total_capacity = u64::MAX;
+    assert!(collector
+        .collect_live_cells(&query, false)
+        .unwrap()
+        .0
+        .is_empty());
+    query.allow_unconfirmed = true;
+    let (cells, _) = collector.collect_live_cells(&query, false).unwrap();
+    assert_eq!(
+        cells
+            .iter()
+            .map(|cell| cell.out_point.clone())
+            .collect::<Vec<_>>(),
+        vec![OutPoint::new(txs[2].hash(), 1)]
+    );
+    assert!(cells[0].is_unconfirmed());
+
+    // drop the unconfirmed cells
+    collector.reset();
+    let (cells, _) = collector.collect_live_cells(&query, false).unwrap();
+    assert_eq!(
+        cells
+            .iter()
+            .map(|cell| cell.out_point.clone())
+            .collect::<Vec<_>>(),
+        vec![initial_out_point]
+    );
+    assert!(!cells[0].is_unconfirmed());
+}
+
+#[test]
+fn test_offchain_unconfirmed_cells() {
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let tx = TransactionBuilder::default()
+        .input(CellInput::new(
+            OutPoint::new(H256::from([1u8; 32]).pack(), 0),
+            0,
+        ))
+        .output(
+            CellOutput::new_builder()
+                .capacity((100 * ONE_CKB).pack())
+                .lock(receiver.clone())
+                .build(),
+        )
+        .output_data(Bytes::default().pack())
+        .build();
+    let mut collector = OffchainCellCollector::default();
+    collector.apply_tx(tx.data(), 0).unwrap();
+
+    let mut query = CellQueryOptions::new_lock(receiver);
+    assert!(collector.collect(&query, 0).cells.is_empty());
+    query.allow_unconfirmed = true;
+    let cells = collector.collect(&query, 0).cells;
+    assert_eq!(cells.len(), 1);
+    assert_eq!(cells[0].0.out_point, OutPoint::new(tx.hash(), 0));
+    assert!(cells[0].0.is_unconfirmed());
+
+    collector.reset();
+    assert!(collector.collect(&query, 0).cells.is_empty());
+}
+
+#[test]
+fn test_chain_udt_transfer_offline() {
+    let sudt_data_hash = H256::from(blake2b_256(SUDT_BIN));
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let type_script = Script::new_builder()
+        .code_hash(sudt_data_hash.pack())
+        .hash_type(ScriptHashType::Data1.to_packed())
+        .args(Bytes::from(vec![9u8; 32]).pack())
+        .build();
+    let mut ctx = init_context(
+        vec![(SUDT_BIN, false)],
+        vec![(sender.clone(), Some(1000 * ONE_CKB))],
+    );
+    let sender_output = CellOutput::new_builder()
+        .capacity((200 * ONE_CKB).pack())
+        .lock(sender.clone())
+        .type_(Some(type_script.clone()).pack())
+        .build();
+    ctx.add_live_cell(
+        CellInput::new(random_out_point(), 0),
+        sender_output,
+        Bytes::from(500u128.to_le_bytes().to_vec()),
+        None,
+    );
+    let balancer = build_sighash_balancer(&sender);
+    let unlockers = build_sighash_unlockers(&[ACCOUNT1_KEY]);
+    let mut collector = ctx.to_live_cells_context();
+
+    let mut txs: Vec<TransactionView> = Vec::new();
+    for amount in [300u128, 100] {
+        let builder = UdtTransferBuilder {
+            type_script: type_script.clone(),
+            sender: sender.clone(),
+            receivers: vec![UdtTargetReceiver::new(
+                TransferAction::Create,
+                receiver.clone(),
+                amount,
+            )],
+            data_validator: None,
+        };
+        let (tx, locked_groups) = builder
+            .build_unlocked(&mut collector, &ctx, &ctx, &ctx, &balancer, &unlockers)
+            .unwrap();
+        assert!(locked_groups.is_empty());
+        // the second transfer spends the pending udt cell of the first one
+        if let Some(prev_tx) = txs.last() {
+            assert!(tx
+                .input_pts_iter()
+                .any(|out_point| out_point == OutPoint::new(prev_tx.hash(), 0)));
+        }
+        collector.apply_tx(tx.data(), 0).unwrap();
+        for (idx, (output, data)) in tx.outputs_with_data_iter().enumerate() {
+            let input = CellInput::new(OutPoint::new(tx.hash(), idx as u32), 0);
+            ctx.add_live_cell(input, output, data, None);
+        }
+        txs.push(tx);
+    }
+    assert_eq!(
+        txs[1].outputs_data().get(0).unwrap().raw_data(),
+        Bytes::from(100u128.to_le_bytes().to_vec())
+    );
+    for tx in txs {
+        ctx.verify(tx, FEE_RATE).unwrap();
+    }
+}
@@ -0,0 +1,155 @@
+//! Consult several sources in order, e.g. a local cache before the RPC, or a
+//! primary node before a backup node.
+//!
+//! For every method the sources are tried in order and the first found value
+//! is returned:
+//!   * a not-found result (`None` or `TransactionDependencyError::NotFound`)
+//!     falls through to the next source
+//!   * an error (e.g. a transport error) also falls through, it is recorded
+//!     and returned in a `ChainedSourcesError` when no source finds the value
+//!   * the result is not-found only when every source returns not-found
+
+use ckb_types::{
+    bytes::Bytes,
+    core::{HeaderView, TransactionView},
+    packed::{self, Byte32, CellDep, CellOutput, OutPoint, Script},
+};
+use thiserror::Error;
+
+use crate::traits::{
+    CellDepResolver, HeaderDepResolver, TransactionDependencyError, TransactionDependencyProvider,
+};
+
+/// The errors of the sources when no source finds the value, with the index
+/// of the source.
+#[derive(Error, Debug)]
+#[error("all the sources failed: `{}`", join_errors(.errors))]
+pub struct ChainedSourcesError {
+    pub errors: Vec<(usize, anyhow::Error)>,
+}
+
+fn join_errors(errors: &[(usize, anyhow::Error)]) -> String {
+    errors
+        .iter()
+        .map(|(idx, err)| format!("#{}: {}", idx, err))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// A transaction dependency provider trying the providers in order
+pub struct ChainedTransactionDependencyProvider(pub Vec<Box<dyn TransactionDependencyProvider>>);
+
+impl ChainedTransactionDependencyProvider {
+    fn get<T, F>(&self, what: &str, f: F) -> Result<T, TransactionDependencyError>
+    where
+        F: Fn(&dyn TransactionDependencyProvider) -> Result<T, TransactionDependencyError>,
+    {
+        let mut errors = Vec::new();
+        for (idx, provider) in self.0.iter().enumerate() {
+            match f(provider.as_ref()) {
+                Ok(value) => return Ok(value),
+                Err(TransactionDependencyError::NotFound(_)) => {}
+                Err(TransactionDependencyError::Other(err)) => errors.push((idx, err)),
+            }
+        }
+        if errors.is_empty() {
+            Err(TransactionDependencyError::NotFound(what.to_string()))
+        } else {
+            Err(TransactionDependencyError::Other(
+                ChainedSourcesError { errors }.into(),
+            ))
+        }
+    }
+
+    fn get_optional<T, F>(&self, f: F) -> Result<Option<T>, TransactionDependencyError>
+    where
+        F: Fn(&dyn TransactionDependencyProvider) -> Result<Option<T>, TransactionDependencyError>,
+    {
+        let mut errors = Vec::new();
+        for (idx, provider) in self.0.iter().enumerate() {
+            match f(provider.as_ref()) {
+                Ok(Some(value)) => return Ok(Some(value)),
+                Ok(None) | Err(TransactionDependencyError::NotFound(_)) => {}
+                Err(TransactionDependencyError::Other(err)) => errors.push((idx, err)),
+            }
+        }
+        if errors.is_empty() {
+            Ok(None)
+        } else {
+            Err(TransactionDependencyError::Other(
+                ChainedSourcesError { errors }.into(),
+            ))
+        }
+    }
+}
+
+impl TransactionDependencyProvider for ChainedTransactionDependencyProvider {
+    fn get_transaction(
+        &self,
+        tx_hash: &Byte32,
+    ) -> Result<TransactionView, TransactionDependencyError> {
+        self.get("transaction", |provider| provider.get_transaction(tx_hash))
+    }
+    fn get_cell(&self, out_point: &OutPoint) -> Result<CellOutput, TransactionDependencyError> {
+        self.get("cell", |provider| provider.get_cell(out_point))
+    }
+    fn get_cell_data(&self, out_point: &OutPoint) -> Result<Bytes, TransactionDependencyError> {
+        self.get("cell data", |provider| provider.get_cell_data(out_point))
+    }
+    fn get_header(&self, block_hash: &Byte32) -> Result<HeaderView, TransactionDependencyError> {
+        self.get("header", |provider| provider.get_header(block_hash))
+    }
+    fn get_block_extension(
+        &self,
+        block_hash: &Byte32,
+    ) -> Result<Option<packed::Bytes>, TransactionDependencyError> {
+        self.get_optional(|provider| provider.get_block_extension(block_hash))
+    }
+}
+
+/// A cell dep resolver trying the resolvers in order
+pub struct ChainedCellDepResolver(pub Vec<Box<dyn CellDepResolver>>);
+
+impl CellDepResolver for ChainedCellDepResolver {
+    fn resolve(&self, script: &Script) -> Option<CellDep> {
+        self.0.iter().find_map(|resolver| resolver.resolve(script))
+    }
+    fn resolve_all(&self, script: &Script) -> Option<Vec<CellDep>> {
+        self.0
+            .iter()
+            .find_map(|resolver| resolver.resolve_all(script))
+    }
+}
+
+/// A header dep resolver trying the resolvers in order
+pub struct ChainedHeaderDepResolver(pub Vec<Box<dyn HeaderDepResolver>>);
+
+impl ChainedHeaderDepResolver {
+    fn resolve<F>(&self, f: F) -> Result<Option<HeaderView>, anyhow::Error>
+    where
+        F: Fn(&dyn HeaderDepResolver) -> Result<Option<HeaderView>, anyhow::Error>,
+    {
+        let mut errors = Vec::new();
+        for (idx, resolver) in self.0.iter().enumerate() {
+            match f(resolver.as_ref()) {
+                Ok(Some(header)) => return Ok(Some(header)),
+                Ok(None) => {}
+                Err(err) => errors.push((idx, err)),
+            }
+        }
+        if errors.is_empty() {
+            Ok(None)
+        } else {
+            Err(ChainedSourcesError { errors }.into())
+        }
+    }
+}
+
+impl HeaderDepResolver for ChainedHeaderDepResolver {
+    fn resolve_by_tx(&self, tx_hash: &Byte32) -> Result<Option<HeaderView>, anyhow::Error> {
+        self.resolve(|resolver| resolver.resolve_by_tx(tx_hash))
+    }
+    fn resolve_by_number(&self, number: u64) -> Result<Option<HeaderView>, anyhow::Error> {
+        self.resolve(|resolver| resolver.resolve_by_number(number))
+    }
+}
@@ -1,7 +1,8 @@
 use std::collections::HashMap;
+use std::path::Path;
 use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use anyhow::anyhow;
 use ckb_crypto::secp::Pubkey;
@@ -13,7 +14,7 @@ use ckb_hash::blake2b_256;
 use ckb_jsonrpc_types::{self as json_types, Either};
 use ckb_types::{
     bytes::Bytes,
-    core::{BlockView, DepType, HeaderView, TransactionView},
+    core::{BlockView, DepType, FeeRate, HeaderView, TransactionView},
     packed::{Byte32, CellDep, CellOutput, OutPoint, Script, Transaction, TransactionReader},
     prelude::*,
     H160,
@@ -23,14 +24,18 @@ use super::{
     offchain_impls::CollectResult, OffchainCellCollector, OffchainCellDepResolver,
     OffchainTransactionDependencyProvider,
 };
+use crate::metrics::{MetricsSink, CACHE_HITS, CACHE_MISSES, INDEXER_PAGES};
 use crate::rpc::ckb_indexer::{Order, SearchKey, Tip};
 use crate::rpc::{CkbRpcClient, IndexerRpcClient};
 use crate::traits::{
-    CellCollector, CellCollectorError, CellDepResolver, CellQueryOptions, HeaderDepResolver,
-    LiveCell, QueryOrder, Signer, SignerError, TransactionDependencyError,
-    TransactionDependencyProvider,
+    CellCollector, CellCollectorError, CellDepResolver, CellLockTtl, CellQueryOptions,
+    FeeRateProvider, HeaderDepResolver, LiveCell, QueryOrder, Signer, SignerError,
+    TransactionDependencyError, TransactionDependencyProvider,
+};
+use crate::types::{
+    CellDepConfig, CellDepConfigError, CellDepConfigItem, ChainParams, NetworkType, ScriptId,
+    ScriptRegistry,
 };
-use crate::types::ScriptId;
 use crate::util::{get_max_mature_number, serialize_signature, zeroize_privkey};
 use crate::SECP256K1;
 use crate::{
@@ -60,6 +65,8 @@ pub enum ParseGenesisInfoError {
 #[derive(Clone)]
 pub struct DefaultCellDepResolver {
     offchain: OffchainCellDepResolver,
+    // The cell deps after the first one of a script
+    extra_deps: HashMap<ScriptId, Vec<CellDep>>,
 }
 impl DefaultCellDepResolver {
     pub fn from_genesis(
@@ -166,7 +173,70 @@ impl DefaultCellDepResolver {
             (dao_dep, "Nervos DAO".to_string()),
         );
         let offchain = OffchainCellDepResolver { items };
-        Ok(DefaultCellDepResolver { offchain })
+        Ok(DefaultCellDepResolver {
+            offchain,
+            extra_deps: HashMap::default(),
+        })
+    }
+    /// A resolver of all the scripts in `registry`
+    pub fn from_registry(registry: &ScriptRegistry) -> DefaultCellDepResolver {
+        let items = registry
+            .iter()
+            .map(|script| {
+                (
+                    script.script_id.clone(),
+                    (script.cell_dep.clone(), script.name.clone()),
+                )
+            })
+            .collect();
+        let offchain = OffchainCellDepResolver { items };
+        DefaultCellDepResolver {
+            offchain,
+            extra_deps: HashMap::default(),
+        }
+    }
+    /// A resolver of the items in a TOML or JSON config used on `network`,
+    /// see `CellDepConfig` for the format.
+    pub fn from_config_str(
+        content: &str,
+        network: Option<NetworkType>,
+    ) -> Result<DefaultCellDepResolver, CellDepConfigError> {
+        Self::from_config(&CellDepConfig::from_config_str(content)?, network)
+    }
+    pub fn from_config_file<P: AsRef<Path>>(
+        path: P,
+        network: Option<NetworkType>,
+    ) -> Result<DefaultCellDepResolver, CellDepConfigError> {
+        Self::from_config(&CellDepConfig::from_config_file(path)?, network)
+    }
+    pub fn from_config(
+        config: &CellDepConfig,
+        network: Option<NetworkType>,
+    ) -> Result<DefaultCellDepResolver, CellDepConfigError> {
+        let items = config
+            .resolve_items(network)?
+            .into_iter()
+            .map(|(name, script_id, cell_dep)| (script_id, (cell_dep, name)))
+            .collect();
+        let offchain = OffchainCellDepResolver { items };
+        Ok(DefaultCellDepResolver {
+            offchain,
+            extra_deps: HashMap::default(),
+        })
+    }
+    /// Dump the scripts to a config sorted by name, the extra cell deps are
+    /// not included.
+    pub fn to_config(&self) -> CellDepConfig {
+        let mut cell_deps: Vec<_> = self
+            .offchain
+            .items
+            .iter()
+            .map(|(script_id, (cell_dep, name))| {
+                CellDepConfigItem::new(name.clone(), script_id, cell_dep)
+            })
+            .collect();
+        cell_deps.sort_by(|a, b| a.name.cmp(&b.name));
+        CellDepConfig { cell_deps }
     }
     pub fn insert(
         &mut self,
@@ -176,7 +246,17 @@ impl DefaultCellDepResolver {
     ) -> Option<(CellDep, String)> {
         self.offchain.items.insert(script_id, (cell_dep, name))
     }
+    /// Add a cell dep after the one inserted by `insert`, e.g. the code cell
+    /// of a script loaded by its dep group.
+    pub fn insert_extra(&mut self, script_id: ScriptId, cell_dep: CellDep) {
+        let cell_deps = self.extra_deps.entry(script_id).or_default();
+        if !cell_deps.contains(&cell_dep) {
+            cell_deps.push(cell_dep);
+        }
+    }
+    /// Remove the script with its extra cell deps
     pub fn remove(&mut self, script_id: &ScriptId) -> Option<(CellDep, String)> {
+        self.extra_deps.remove(script_id);
         self.offchain.items.remove(script_id)
     }
     pub fn contains(&self, script_id: &ScriptId) -> bool {
@@ -200,6 +280,13 @@ impl CellDepResolver for DefaultCellDepResolver {
     fn resolve(&self, script: &Script) -> Option<CellDep> {
         self.offchain.resolve(script)
     }
+    fn resolve_all(&self, script: &Script) -> Option<Vec<CellDep>> {
+        let mut cell_deps = vec![self.offchain.resolve(script)?];
+        if let Some(extra_deps) = self.extra_deps.get(&ScriptId::from(script)) {
+            cell_deps.extend(extra_deps.iter().cloned());
+        }
+        Some(cell_deps)
+    }
 }
 
 /// A header_dep resolver use ckb jsonrpc client as backend
@@ -238,6 +325,252 @@ impl HeaderDepResolver for DefaultHeaderDepResolver {
     }
 }
 
+/// The value picked from the `get_fee_rate_statistics` result.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FeeRateStatisticsValue {
+    Mean,
+    Median,
+}
+
+/// A fee rate provider use `get_fee_rate_statistics` rpc as backend.
+///
+/// The fee rate is clamped between `floor` and `ceiling`, and cached for `ttl`.
+/// If the node does not have enough data (returns `null`), `default_fee_rate`
+/// is used.
+#[derive(Clone)]
+pub struct DefaultFeeRateProvider {
+    ckb_client: CkbRpcClient,
+    target: Option<u64>,
+    value: FeeRateStatisticsValue,
+    floor: u64,
+    ceiling: u64,
+    default_fee_rate: u64,
+    ttl: Duration,
+    // (fee_rate, updated_at)
+    cache: Arc<Mutex<Option<(u64, Instant)>>>,
+}
+
+impl DefaultFeeRateProvider {
+    /// Create a fee rate provider with the median fee rate of the node's
+    /// default target window, clamped in `[1000, 1_000_000]` and cached for 30 seconds.
+    pub fn new(ckb_client: &str) -> DefaultFeeRateProvider {
+        DefaultFeeRateProvider {
+            ckb_client: CkbRpcClient::new(ckb_client),
+            target: None,
+            value: FeeRateStatisticsValue::Median,
+            floor: 1000,
+            ceiling: 1_000_000,
+            default_fee_rate: 1000,
+            ttl: Duration::from_secs(30),
+            cache: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Set the target window (number of blocks) of the statistics, `None` means the node's default.
+    pub fn set_target(&mut self, target: Option<u64>) {
+        self.target = target;
+        self.clear_cache();
+    }
+
+    /// Use mean or median of the statistics.
+    pub fn set_value(&mut self, value: FeeRateStatisticsValue) {
+        self.value = value;
+        self.clear_cache();
+    }
+
+    /// Set the lower and upper bound of the returned fee rate, the bounds
+    /// are unchanged when `floor` exceeds `ceiling`.
+    pub fn set_bounds(&mut self, floor: u64, ceiling: u64) -> Result<(), anyhow::Error> {
+        if floor > ceiling {
+            return Err(anyhow!(
+                "fee rate floor {} exceeds the ceiling {}",
+                floor,
+                ceiling
+            ));
+        }
+        self.floor = floor;
+        self.ceiling = ceiling;
+        self.clear_cache();
+        Ok(())
+    }
+
+    /// Set the fee rate used when the node returns no statistics.
+    pub fn set_default_fee_rate(&mut self, fee_rate: u64) {
+        self.default_fee_rate = fee_rate;
+        self.clear_cache();
+    }
+
+    /// Set how long a fetched fee rate is reused.
+    pub fn set_ttl(&mut self, ttl: Duration) {
+        self.ttl = ttl;
+    }
+
+    fn clear_cache(&self) {
+        *self.cache.lock() = None;
+    }
+}
+
+impl FeeRateProvider for DefaultFeeRateProvider {
+    fn fee_rate(&self) -> Result<FeeRate, anyhow::Error> {
+        let mut cache = self.cache.lock();
+        if let Some((fee_rate, updated_at)) = *cache {
+            if updated_at.elapsed() < self.ttl {
+                return Ok(FeeRate::from_u64(fee_rate));
+            }
+        }
+        let statistics = self
+            .ckb_client
+            .get_fee_rate_statistics(self.target.map(Into::into))
+            .map_err(|e| anyhow!(e))?;
+        let fee_rate = statistics
+            .map(|statistics| match self.value {
+                FeeRateStatisticsValue::Mean => statistics.mean.value(),
+                FeeRateStatisticsValue::Median => statistics.median.value(),
+            })
+            .unwrap_or(self.default_fee_rate)
+            .clamp(self.floor, self.ceiling);
+        *cache = Some((fee_rate, Instant::now()));
+        Ok(FeeRate::from_u64(fee_rate))
+    }
+}
+
+/// How fast the transaction is expected to be committed, used to pick the
+/// fee rate statistics window.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum FeePriority {
+    /// Median fee rate of the last 101 blocks
+    Low,
+    /// Median fee rate of the node's default window (21 blocks)
+    Medium,
+    /// Mean fee rate of the last 5 blocks
+    High,
+}
+
+impl FeePriority {
+    fn estimator(self, ckb_client: &str) -> DefaultFeeRateProvider {
+        let mut estimator = DefaultFeeRateProvider::new(ckb_client);
+        match self {
+            FeePriority::Low => estimator.set_target(Some(101)),
+            FeePriority::Medium => {}
+            FeePriority::High => {
+                estimator.set_target(Some(5));
+                estimator.set_value(FeeRateStatisticsValue::Mean);
+            }
+        }
+        estimator
+    }
+}
+
+/// The transaction limits of a node, from the `tx_pool_info` rpc.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct NodeTxLimits {
+    /// shannons/KW
+    pub min_fee_rate: u64,
+    /// Max serialized size (in block) of a transaction
+    pub max_tx_size: u64,
+}
+
+impl Default for NodeTxLimits {
+    /// The default values of a CKB node
+    fn default() -> Self {
+        NodeTxLimits {
+            min_fee_rate: 1000,
+            max_tx_size: 512_000,
+        }
+    }
+}
+
+/// A fee rate provider honoring the node's limits: the fee rate is the larger
+/// one of the estimated fee rate (by `FeePriority`) and the node's
+/// `min_fee_rate`, and the transaction size is limited to the node's
+/// `tx_size_limit`.
+///
+/// When the node is unavailable the default `NodeTxLimits` and fee rate are
+/// used, the reason is kept in `last_warning`.
+#[derive(Clone)]
+pub struct NodeFeeRateProvider {
+    ckb_client: CkbRpcClient,
+    estimator: DefaultFeeRateProvider,
+    max_age: Duration,
+    // (limits, fetched_at)
+    limits: Arc<Mutex<Option<(NodeTxLimits, Instant)>>>,
+    warning: Arc<Mutex<Option<String>>>,
+}
+
+impl NodeFeeRateProvider {
+    /// The node limits are refreshed every 60 seconds.
+    pub fn new(ckb_client: &str, priority: FeePriority) -> NodeFeeRateProvider {
+        NodeFeeRateProvider {
+            ckb_client: CkbRpcClient::new(ckb_client),
+            estimator: priority.estimator(ckb_client),
+            max_age: Duration::from_secs(60),
+            limits: Arc::new(Mutex::new(None)),
+            warning: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Set how long the fetched node limits are reused.
+    pub fn set_max_age(&mut self, max_age: Duration) {
+        self.max_age = max_age;
+    }
+
+    /// The cached node limits, fetch them when missing or older than `max_age`.
+    pub fn limits(&self) -> NodeTxLimits {
+        let mut limits = self.limits.lock();
+        if let Some((value, fetched_at)) = *limits {
+            if fetched_at.elapsed() < self.max_age {
+                return value;
+            }
+        }
+        let value = match self.ckb_client.tx_pool_info() {
+            Ok(info) => NodeTxLimits {
+                min_fee_rate: info.min_fee_rate.value(),
+                max_tx_size: info.tx_size_limit.value(),
+            },
+            Err(err) => {
+                self.warn(format!(
+                    "get tx_pool_info failed, use the default limits: {}",
+                    err
+                ));
+                NodeTxLimits::default()
+            }
+        };
+        *limits = Some((value, Instant::now()));
+        value
+    }
+
+    /// The reason of the last fallback to the default values
+    pub fn last_warning(&self) -> Option<String> {
+        self.warning.lock().clone()
+    }
+
+    fn warn(&self, message: String) {
+        log::warn!("{}", message);
+        *self.warning.lock() = Some(message);
+    }
+}
+
+impl FeeRateProvider for NodeFeeRateProvider {
+    fn fee_rate(&self) -> Result<FeeRate, anyhow::Error> {
+        let min_fee_rate = self.limits().min_fee_rate;
+        let estimated = match self.estimator.fee_rate() {
+            Ok(fee_rate) => fee_rate.as_u64(),
+            Err(err) => {
+                self.warn(format!(
+                    "estimate fee rate failed, use the node's minimal fee rate: {}",
+                    err
+                ));
+                min_fee_rate
+            }
+        };
+        Ok(FeeRate::from_u64(estimated.max(min_fee_rate)))
+    }
+
+    fn max_tx_size(&self) -> Option<u64> {
+        Some(self.limits().max_tx_size)
+    }
+}
+
 /// A cell collector use ckb-indexer as backend
 #[derive(Clone)]
 pub struct DefaultCellCollector {
@@ -245,6 +578,7 @@ pub struct DefaultCellCollector {
     ckb_client: CkbRpcClient,
     offchain: OffchainCellCollector,
     acceptable_indexer_leftbehind: u64,
+    metrics: Option<Arc<dyn MetricsSink>>,
 }
 
 impl DefaultCellCollector {
@@ -256,9 +590,17 @@ impl DefaultCellCollector {
             ckb_client,
             offchain: OffchainCellCollector::default(),
             acceptable_indexer_leftbehind: 1,
+            metrics: None,
         }
     }
 
+    /// Report the indexer pages fetched by every query and the rpc requests into `sink`
+    pub fn set_metrics_sink(&mut self, sink: Arc<dyn MetricsSink>) {
+        self.indexer_client.set_metrics_sink(Arc::clone(&sink));
+        self.ckb_client.set_metrics_sink(Arc::clone(&sink));
+        self.metrics = Some(sink);
+    }
+
     /// THe acceptable ckb-indexer leftbehind block number (default = 1)
     pub fn acceptable_indexer_leftbehind(&self) -> u64 {
         self.acceptable_indexer_leftbehind
@@ -340,7 +682,9 @@ impl CellCollector for DefaultCellCollector {
             const MAX_LIMIT: u32 = 4096;
             let mut limit: u32 = query.limit.unwrap_or(16);
             let mut last_cursor: Option<json_types::JsonBytes> = None;
+            let mut pages: u64 = 0;
             while total_capacity < query.min_total_capacity {
+                pages += 1;
                 let page = self
                     .indexer_client
                     .get_cells(search_key.clone(), order.clone(), limit.into(), last_cursor)
@@ -375,6 +719,9 @@ impl CellCollector for DefaultCellCollector {
                     limit *= 2;
                 }
             }
+            if let Some(sink) = self.metrics.as_ref() {
+                sink.record_histogram(INDEXER_PAGES, &[], pages as f64);
+            }
             cells = ret_cells.into_values().collect();
         }
         if apply_changes {
@@ -393,6 +740,21 @@ impl CellCollector for DefaultCellCollector {
     ) -> Result<(), CellCollectorError> {
         self.offchain.lock_cell(out_point, tip_block_number)
     }
+    fn lock_cell_with_ttl(
+        &mut self,
+        out_point: OutPoint,
+        ttl: CellLockTtl,
+    ) -> Result<(), CellCollectorError> {
+        let tip_num = self
+            .ckb_client
+            .get_tip_block_number()
+            .map_err(|err| CellCollectorError::Internal(anyhow!(err)))?
+            .value();
+        self.offchain.lock_cell_with_ttl(out_point, tip_num, ttl)
+    }
+    fn unlock_cell(&mut self, out_point: &OutPoint) -> Result<(), CellCollectorError> {
+        self.offchain.unlock_cell(out_point)
+    }
     fn apply_tx(
         &mut self,
         tx: Transaction,
@@ -411,6 +773,16 @@ struct DefaultTxDepProviderInner {
     cell_cache: LruCache<OutPoint, (CellOutput, Bytes)>,
     header_cache: LruCache<Byte32, HeaderView>,
     offchain_cache: OffchainTransactionDependencyProvider,
+    metrics: Option<Arc<dyn MetricsSink>>,
+}
+
+impl DefaultTxDepProviderInner {
+    fn record_cache_access(&self, cache: &str, hit: bool) {
+        if let Some(sink) = self.metrics.as_ref() {
+            let name = if hit { CACHE_HITS } else { CACHE_MISSES };
+            sink.increment_counter(name, &[("cache", cache)], 1);
+        }
+    }
 }
 
 /// A transaction dependency provider use ckb rpc client as backend, and with LRU cache supported
@@ -438,12 +810,22 @@ impl DefaultTransactionDependencyProvider {
             cell_cache: LruCache::new(cache_capacity),
             header_cache: LruCache::new(cache_capacity),
             offchain_cache: OffchainTransactionDependencyProvider::new(),
+            metrics: None,
         };
         DefaultTransactionDependencyProvider {
             inner: Arc::new(Mutex::new(inner)),
         }
     }
 
+    /// Report the cache hits/misses and the rpc requests into `sink`.
+    ///
+    /// The sink is shared by all the clones of this provider.
+    pub fn set_metrics_sink(&mut self, sink: Arc<dyn MetricsSink>) {
+        let mut inner = self.inner.lock();
+        inner.rpc_client.set_metrics_sink(Arc::clone(&sink));
+        inner.metrics = Some(sink);
+    }
+
     pub fn apply_tx(
         &mut self,
         tx: Transaction,
@@ -459,9 +841,11 @@ impl DefaultTransactionDependencyProvider {
         out_point: &OutPoint,
     ) -> Result<(CellOutput, Bytes), TransactionDependencyError> {
         let mut inner = self.inner.lock();
-        if let Some(pair) = inner.cell_cache.get(out_point) {
-            return Ok(pair.clone());
+        if let Some(pair) = inner.cell_cache.get(out_point).cloned() {
+            inner.record_cache_access("cell", true);
+            return Ok(pair);
         }
+        inner.record_cache_access("cell", false);
 
         let cell_with_status = inner
             .rpc_client
@@ -489,13 +873,15 @@ impl TransactionDependencyProvider for DefaultTransactionDependencyProvider {
         tx_hash: &Byte32,
     ) -> Result<TransactionView, TransactionDependencyError> {
         let mut inner = self.inner.lock();
-        if let Some(tx) = inner.tx_cache.get(tx_hash) {
-            return Ok(tx.clone());
+        if let Some(tx) = inner.tx_cache.get(tx_hash).cloned() {
+            inner.record_cache_access("transaction", true);
+            return Ok(tx);
         }
-        let ret = inner.offchain_cache.get_transaction(tx_hash);
-        if ret.is_ok() {
-            return ret;
+        if let Ok(tx) = inner.offchain_cache.get_transaction(tx_hash) {
+            inner.record_cache_access("transaction", true);
+            return Ok(tx);
         }
+        inner.record_cache_access("transaction", false);
         let tx_with_status = inner
             .rpc_client
             .get_transaction(tx_hash.unpack())
@@ -519,9 +905,9 @@ impl TransactionDependencyProvider for DefaultTransactionDependencyProvider {
     fn get_cell(&self, out_point: &OutPoint) -> Result<CellOutput, TransactionDependencyError> {
         {
             let inner = self.inner.lock();
-            let ret = inner.offchain_cache.get_cell(out_point);
-            if ret.is_ok() {
-                return ret;
+            if let Ok(output) = inner.offchain_cache.get_cell(out_point) {
+                inner.record_cache_access("cell", true);
+                return Ok(output);
             }
         }
         self.get_cell_with_data(out_point).map(|(output, _)| output)
@@ -529,9 +915,9 @@ impl TransactionDependencyProvider for DefaultTransactionDependencyProvider {
     fn get_cell_data(&self, out_point: &OutPoint) -> Result<Bytes, TransactionDependencyError> {
         {
             let inner = self.inner.lock();
-            let ret = inner.offchain_cache.get_cell_data(out_point);
-            if ret.is_ok() {
-                return ret;
+            if let Ok(output_data) = inner.offchain_cache.get_cell_data(out_point) {
+                inner.record_cache_access("cell", true);
+                return Ok(output_data);
             }
         }
         self.get_cell_with_data(out_point)
@@ -539,9 +925,11 @@ impl TransactionDependencyProvider for DefaultTransactionDependencyProvider {
     }
     fn get_header(&self, block_hash: &Byte32) -> Result<HeaderView, TransactionDependencyError> {
         let mut inner = self.inner.lock();
-        if let Some(header) = inner.header_cache.get(block_hash) {
-            return Ok(header.clone());
+        if let Some(header) = inner.header_cache.get(block_hash).cloned() {
+            inner.record_cache_access("header", true);
+            return Ok(header);
         }
+        inner.record_cache_access("header", false);
         let header = inner
             .rpc_client
             .get_header(block_hash.unpack())
@@ -592,6 +980,17 @@ impl SecpCkbRawKeySigner {
             .expect("Generate hash(H160) from pubkey failed");
         self.keys.insert(hash160, key);
     }
+    /// Add a secret key, the id is `blake160(pubkey)` hashed with the chain's
+    /// blake2b personalization
+    pub fn add_secret_key_with_params(
+        &mut self,
+        key: secp256k1::SecretKey,
+        chain_params: &ChainParams,
+    ) {
+        let pubkey = secp256k1::PublicKey::from_secret_key(&SECP256K1, &key);
+        let hash160 = chain_params.blake160(&pubkey.serialize()[..]);
+        self.keys.insert(hash160, key);
+    }
 
     /// Create SecpkRawKeySigner from secret keys for ethereum algorithm.
     pub fn new_with_ethereum_secret_keys(keys: Vec<secp256k1::SecretKey>) -> SecpCkbRawKeySigner {
@@ -651,6 +1050,212 @@ impl Drop for SecpCkbRawKeySigner {
     }
 }
 #[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockRpcResult;
+    use ckb_jsonrpc_types::FeeRateStatistics;
+    use httpmock::prelude::*;
+
+    #[test]
+    fn test_default_fee_rate_provider() {
+        let server = MockServer::start();
+        let mut statistics_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/")
+                .body_contains("get_fee_rate_statistics");
+            then.status(200).body(
+                MockRpcResult::new(Some(FeeRateStatistics {
+                    mean: 3000.into(),
+                    median: 500.into(),
+                }))
+                .to_json(),
+            );
+        });
+        let mut provider = DefaultFeeRateProvider::new(server.base_url().as_str());
+        // median is lower than the floor
+        assert_eq!(provider.fee_rate().unwrap().as_u64(), 1000);
+        provider.set_value(FeeRateStatisticsValue::Mean);
+        assert_eq!(provider.fee_rate().unwrap().as_u64(), 3000);
+        // hit the cache
+        assert_eq!(provider.fee_rate().unwrap().as_u64(), 3000);
+        statistics_mock.assert_hits(2);
+        provider.set_bounds(1000, 2000).unwrap();
+        assert_eq!(provider.fee_rate().unwrap().as_u64(), 2000);
+        // the inverted bounds are rejected and the cached fee rate is kept
+        let err = provider.set_bounds(2000, 1000).unwrap_err();
+        assert!(err.to_string().contains("exceeds the ceiling"));
+        assert_eq!(provider.fee_rate().unwrap().as_u64(), 2000);
+        statistics_mock.assert_hits(3);
+        statistics_mock.delete();
+
+        // not enough data in the node
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/")
+                .body_contains("get_fee_rate_statistics");
+            then.status(200)
+                .body(MockRpcResult::new(None::<FeeRateStatistics>).to_json());
+        });
+        provider.set_default_fee_rate(1500);
+        assert_eq!(provider.fee_rate().unwrap().as_u64(), 1500);
+    }
+
+    fn tx_pool_info(min_fee_rate: u64) -> String {
+        MockRpcResult::new(serde_json::json!({
+            "tip_hash": "0x0000000000000000000000000000000000000000000000000000000000000000",
+            "tip_number": "0x400",
+            "pending": "0x1",
+            "proposed": "0x0",
+            "orphan": "0x0",
+            "total_tx_size": "0x112",
+            "total_tx_cycles": "0x219e0b",
+            "min_fee_rate": format!("{:#x}", min_fee_rate),
+            "min_rbf_rate": "0x5dc",
+            "last_txs_updated_at": "0x0",
+            "tx_size_limit": "0x7d000",
+            "max_tx_pool_size": "0xaba9500",
+            "verify_queue_size": "0x0",
+        }))
+        .to_json()
+    }
+
+    #[test]
+    fn test_node_fee_rate_provider() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/")
+                .body_contains("get_fee_rate_statistics");
+            then.status(200).body(
+                MockRpcResult::new(Some(FeeRateStatistics {
+                    mean: 4000.into(),
+                    median: 3000.into(),
+                }))
+                .to_json(),
+            );
+        });
+        let mut pool_info_mock = server.mock(|when, then| {
+            when.method(POST).path("/").body_contains("tx_pool_info");
+            then.status(200).body(tx_pool_info(5000));
+        });
+        let mut provider =
+            NodeFeeRateProvider::new(server.base_url().as_str(), FeePriority::Medium);
+        // the node minimum is higher than the estimated
+        assert_eq!(provider.fee_rate().unwrap().as_u64(), 5000);
+        assert_eq!(provider.max_tx_size(), Some(512_000));
+        // the limits are cached
+        pool_info_mock.assert_hits(1);
+        pool_info_mock.delete();
+
+        server.mock(|when, then| {
+            when.method(POST).path("/").body_contains("tx_pool_info");
+            then.status(200).body(tx_pool_info(1000));
+        });
+        assert_eq!(provider.fee_rate().unwrap().as_u64(), 5000);
+        provider.set_max_age(Duration::ZERO);
+        assert_eq!(provider.fee_rate().unwrap().as_u64(), 3000);
+        let provider = NodeFeeRateProvider::new(server.base_url().as_str(), FeePriority::High);
+        assert_eq!(provider.fee_rate().unwrap().as_u64(), 4000);
+        assert!(provider.last_warning().is_none());
+
+        let balancer = crate::tx_builder::CapacityBalancer::new_from_node(
+            server.base_url().as_str(),
+            Script::default(),
+            ckb_types::packed::WitnessArgs::default(),
+            FeePriority::Low,
+        );
+        assert_eq!(balancer.current_fee_rate().unwrap().as_u64(), 3000);
+    }
+
+    #[test]
+    fn test_node_fee_rate_provider_unavailable() {
+        // no rpc is mocked
+        let server = MockServer::start();
+        let provider = NodeFeeRateProvider::new(server.base_url().as_str(), FeePriority::Low);
+        assert_eq!(provider.limits(), NodeTxLimits::default());
+        assert_eq!(provider.fee_rate().unwrap().as_u64(), 1000);
+        assert!(provider.last_warning().is_some());
+    }
+
+    #[test]
+    fn test_tx_dep_provider_metrics() {
+        let server = MockServer::start();
+        let live_cell_mock = server.mock(|when, then| {
+            when.method(POST).path("/").body_contains("get_live_cell");
+            then.status(200).body(
+                MockRpcResult::new(serde_json::json!({
+                    "cell": {
+                        "output": {
+                            "capacity": "0x174876e800",
+                            "lock": {
+                                "code_hash": "0x9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8",
+                                "hash_type": "type",
+                                "args": "0x"
+                            },
+                            "type": null
+                        },
+                        "data": {
+                            "content": "0x01",
+                            "hash": "0x0000000000000000000000000000000000000000000000000000000000000000"
+                        }
+                    },
+                    "status": "live"
+                }))
+                .to_json(),
+            );
+        });
+        let metrics = Arc::new(crate::metrics::InMemoryMetrics::new());
+        let mut provider = DefaultTransactionDependencyProvider::new(server.base_url().as_str(), 8);
+        provider.set_metrics_sink(metrics.clone());
+
+        let out_point = OutPoint::new(Byte32::zero(), 0);
+        let output = provider.get_cell(&out_point).unwrap();
+        assert_eq!(output, provider.get_cell(&out_point).unwrap());
+        assert_eq!(
+            provider.get_cell_data(&out_point).unwrap(),
+            Bytes::from(vec![1])
+        );
+        live_cell_mock.assert_hits(1);
+        // no get_header mocked
+        assert!(provider.get_header(&Byte32::zero()).is_err());
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.counter(CACHE_HITS, &[("cache", "cell")]), 2);
+        assert_eq!(snapshot.counter(CACHE_MISSES, &[("cache", "cell")]), 1);
+        assert_eq!(snapshot.counter(CACHE_MISSES, &[("cache", "header")]), 1);
+        assert_eq!(snapshot.cache_hit_ratio("transaction"), None);
+        let rpc_requests = crate::metrics::RPC_REQUESTS;
+        assert_eq!(
+            snapshot.counter(
+                rpc_requests,
+                &[("method", "get_live_cell"), ("status", "ok")]
+            ),
+            1
+        );
+        assert_eq!(
+            snapshot.counter(
+                rpc_requests,
+                &[("method", "get_header"), ("status", "error")]
+            ),
+            1
+        );
+        assert!(snapshot
+            .percentile(
+                crate::metrics::RPC_LATENCY_SECONDS,
+                &[("method", "get_live_cell")],
+                99.0
+            )
+            .is_some());
+
+        // a hit in the offchain cache
+        let tx = Transaction::default();
+        let tx_hash = tx.calc_tx_hash();
+        provider.apply_tx(tx, 0).unwrap();
+        assert_eq!(provider.get_transaction(&tx_hash).unwrap().hash(), tx_hash);
+        assert_eq!(metrics.snapshot().cache_hit_ratio("transaction"), Some(1.0));
+    }
+}
+#[cfg(test)]
 mod anyhow_tests {
     use anyhow::anyhow;
     #[test]
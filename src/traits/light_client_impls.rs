@@ -17,8 +17,8 @@ use crate::rpc::{
     LightClientRpcClient,
 };
 use crate::traits::{
-    CellCollector, CellCollectorError, CellQueryOptions, HeaderDepResolver, LiveCell, QueryOrder,
-    TransactionDependencyError, TransactionDependencyProvider,
+    CellCollector, CellCollectorError, CellLockTtl, CellQueryOptions, HeaderDepResolver, LiveCell,
+    QueryOrder, TransactionDependencyError, TransactionDependencyProvider,
 };
 
 pub struct LightClientHeaderDepResolver {
@@ -338,6 +338,23 @@ impl CellCollector for LightClientCellCollector {
     ) -> Result<(), CellCollectorError> {
         self.offchain.lock_cell(out_point, tip_number)
     }
+    fn lock_cell_with_ttl(
+        &mut self,
+        out_point: OutPoint,
+        ttl: CellLockTtl,
+    ) -> Result<(), CellCollectorError> {
+        let tip_num = self
+            .light_client
+            .get_tip_header()
+            .map_err(|err| CellCollectorError::Internal(anyhow!(err)))?
+            .inner
+            .number
+            .value();
+        self.offchain.lock_cell_with_ttl(out_point, tip_num, ttl)
+    }
+    fn unlock_cell(&mut self, out_point: &OutPoint) -> Result<(), CellCollectorError> {
+        self.offchain.unlock_cell(out_point)
+    }
     fn apply_tx(&mut self, tx: Transaction, tip_number: u64) -> Result<(), CellCollectorError> {
         self.offchain.apply_tx(tx, tip_number)
     }
@@ -0,0 +1,272 @@
+//! Configurable implementations of the traits with canned data, to unit test
+//! the code using the traits without a node or the whole test `Context`.
+//!
+//! Every mock counts the calls of each trait method by a `CallCounter`, so
+//! the tests can assert how many requests the code under test makes.
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::anyhow;
+use ckb_types::{
+    bytes::Bytes,
+    core::{HeaderView, TransactionView},
+    packed::{self, Byte32, CellOutput, OutPoint, Transaction},
+    prelude::*,
+};
+use parking_lot::Mutex;
+
+use crate::traits::{
+    CellCollector, CellCollectorError, CellQueryOptions, HeaderDepResolver, LiveCell,
+    TransactionDependencyError, TransactionDependencyProvider, PENDING_BLOCK_NUMBER,
+};
+
+/// The number of the calls of each method, cloned mocks count separately.
+#[derive(Default, Debug)]
+pub struct CallCounter {
+    counts: Mutex<HashMap<&'static str, usize>>,
+}
+
+impl Clone for CallCounter {
+    fn clone(&self) -> CallCounter {
+        CallCounter {
+            counts: Mutex::new(self.counts.lock().clone()),
+        }
+    }
+}
+
+impl CallCounter {
+    pub fn record(&self, method: &'static str) {
+        *self.counts.lock().entry(method).or_default() += 1;
+    }
+
+    /// The number of the calls of the trait method named `method`
+    pub fn count(&self, method: &str) -> usize {
+        self.counts.lock().get(method).cloned().unwrap_or_default()
+    }
+
+    /// The number of the calls of all the methods
+    pub fn total(&self) -> usize {
+        self.counts.lock().values().sum()
+    }
+
+    pub fn clear(&self) {
+        self.counts.lock().clear();
+    }
+}
+
+/// A cell collector of the pre-loaded cells, the queries are matched by
+/// `CellQueryOptions::match_cell`.
+#[derive(Default, Clone)]
+pub struct MockCellCollector {
+    pub cells: Vec<LiveCell>,
+    /// The outputs of the applied transactions, dropped by `reset`
+    pub applied_cells: Vec<LiveCell>,
+    pub locked_cells: HashSet<OutPoint>,
+    /// The cellbase cells of the blocks after it are immature
+    pub max_mature_number: u64,
+    /// The queries of all the `collect_live_cells` calls
+    pub queries: Vec<CellQueryOptions>,
+    pub calls: CallCounter,
+}
+
+impl MockCellCollector {
+    pub fn new(cells: Vec<LiveCell>) -> MockCellCollector {
+        MockCellCollector {
+            cells,
+            ..Default::default()
+        }
+    }
+}
+
+impl CellCollector for MockCellCollector {
+    fn collect_live_cells(
+        &mut self,
+        query: &CellQueryOptions,
+        apply_changes: bool,
+    ) -> Result<(Vec<LiveCell>, u64), CellCollectorError> {
+        self.calls.record("collect_live_cells");
+        self.queries.push(query.clone());
+        let mut total_capacity = 0;
+        let mut cells = Vec::new();
+        for cell in self.cells.iter().chain(self.applied_cells.iter()) {
+            if total_capacity >= query.min_total_capacity {
+                break;
+            }
+            if self.locked_cells.contains(&cell.out_point)
+                || !query.match_cell(cell, self.max_mature_number)
+            {
+                continue;
+            }
+            let capacity: u64 = cell.output.capacity().unpack();
+            total_capacity += capacity;
+            cells.push(cell.clone());
+        }
+        if apply_changes {
+            for cell in &cells {
+                self.locked_cells.insert(cell.out_point.clone());
+            }
+        }
+        Ok((cells, total_capacity))
+    }
+
+    fn lock_cell(
+        &mut self,
+        out_point: OutPoint,
+        _tip_block_number: u64,
+    ) -> Result<(), CellCollectorError> {
+        self.calls.record("lock_cell");
+        self.locked_cells.insert(out_point);
+        Ok(())
+    }
+
+    fn unlock_cell(&mut self, out_point: &OutPoint) -> Result<(), CellCollectorError> {
+        self.calls.record("unlock_cell");
+        self.locked_cells.remove(out_point);
+        Ok(())
+    }
+
+    fn apply_tx(
+        &mut self,
+        tx: Transaction,
+        _tip_block_number: u64,
+    ) -> Result<(), CellCollectorError> {
+        self.calls.record("apply_tx");
+        let tx_view = tx.into_view();
+        for out_point in tx_view.input_pts_iter() {
+            self.locked_cells.insert(out_point);
+        }
+        for (idx, (output, data)) in tx_view.outputs_with_data_iter().enumerate() {
+            self.applied_cells.push(LiveCell::new(
+                output,
+                data,
+                OutPoint::new(tx_view.hash(), idx as u32),
+                PENDING_BLOCK_NUMBER,
+                0,
+            ));
+        }
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.calls.record("reset");
+        self.applied_cells.clear();
+        self.locked_cells.clear();
+    }
+}
+
+/// A transaction dependency provider of the pre-loaded cells, transactions
+/// and headers.
+#[derive(Default, Clone)]
+pub struct MockTransactionDependencyProvider {
+    pub cells: HashMap<OutPoint, (CellOutput, Bytes)>,
+    pub transactions: HashMap<Byte32, TransactionView>,
+    pub headers: HashMap<Byte32, HeaderView>,
+    pub extensions: HashMap<Byte32, packed::Bytes>,
+    pub calls: CallCounter,
+}
+
+impl MockTransactionDependencyProvider {
+    pub fn new() -> MockTransactionDependencyProvider {
+        MockTransactionDependencyProvider::default()
+    }
+
+    pub fn add_cell(&mut self, out_point: OutPoint, output: CellOutput, data: Bytes) {
+        self.cells.insert(out_point, (output, data));
+    }
+
+    /// Add the transaction and all its outputs
+    pub fn add_transaction(&mut self, tx: TransactionView) {
+        for (idx, (output, data)) in tx.outputs_with_data_iter().enumerate() {
+            self.add_cell(OutPoint::new(tx.hash(), idx as u32), output, data);
+        }
+        self.transactions.insert(tx.hash(), tx);
+    }
+
+    pub fn add_header(&mut self, header: HeaderView) {
+        self.headers.insert(header.hash(), header);
+    }
+}
+
+impl TransactionDependencyProvider for MockTransactionDependencyProvider {
+    fn get_transaction(
+        &self,
+        tx_hash: &Byte32,
+    ) -> Result<TransactionView, TransactionDependencyError> {
+        self.calls.record("get_transaction");
+        self.transactions
+            .get(tx_hash)
+            .cloned()
+            .ok_or_else(|| TransactionDependencyError::NotFound("transaction".to_string()))
+    }
+    fn get_cell(&self, out_point: &OutPoint) -> Result<CellOutput, TransactionDependencyError> {
+        self.calls.record("get_cell");
+        self.cells
+            .get(out_point)
+            .map(|(output, _)| output.clone())
+            .ok_or_else(|| TransactionDependencyError::NotFound("cell".to_string()))
+    }
+    fn get_cell_data(&self, out_point: &OutPoint) -> Result<Bytes, TransactionDependencyError> {
+        self.calls.record("get_cell_data");
+        self.cells
+            .get(out_point)
+            .map(|(_, data)| data.clone())
+            .ok_or_else(|| TransactionDependencyError::NotFound("cell data".to_string()))
+    }
+    fn get_header(&self, block_hash: &Byte32) -> Result<HeaderView, TransactionDependencyError> {
+        self.calls.record("get_header");
+        self.headers
+            .get(block_hash)
+            .cloned()
+            .ok_or_else(|| TransactionDependencyError::NotFound("header".to_string()))
+    }
+    fn get_block_extension(
+        &self,
+        block_hash: &Byte32,
+    ) -> Result<Option<packed::Bytes>, TransactionDependencyError> {
+        self.calls.record("get_block_extension");
+        Ok(self.extensions.get(block_hash).cloned())
+    }
+}
+
+/// A header dep resolver of the pre-loaded headers
+#[derive(Default, Clone)]
+pub struct MockHeaderDepResolver {
+    pub by_number: HashMap<u64, HeaderView>,
+    /// The block header of the committed transactions
+    pub by_tx_hash: HashMap<Byte32, HeaderView>,
+    pub calls: CallCounter,
+}
+
+impl MockHeaderDepResolver {
+    pub fn new(headers: Vec<HeaderView>) -> MockHeaderDepResolver {
+        MockHeaderDepResolver {
+            by_number: headers
+                .into_iter()
+                .map(|header| (header.number(), header))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Set the block of the transaction, the header must be added first
+    pub fn commit_tx(&mut self, tx_hash: Byte32, number: u64) -> Result<(), anyhow::Error> {
+        let header = self
+            .by_number
+            .get(&number)
+            .cloned()
+            .ok_or_else(|| anyhow!("header not found: {}", number))?;
+        self.by_tx_hash.insert(tx_hash, header);
+        Ok(())
+    }
+}
+
+impl HeaderDepResolver for MockHeaderDepResolver {
+    fn resolve_by_tx(&self, tx_hash: &Byte32) -> Result<Option<HeaderView>, anyhow::Error> {
+        self.calls.record("resolve_by_tx");
+        Ok(self.by_tx_hash.get(tx_hash).cloned())
+    }
+    fn resolve_by_number(&self, number: u64) -> Result<Option<HeaderView>, anyhow::Error> {
+        self.calls.record("resolve_by_number");
+        Ok(self.by_number.get(&number).cloned())
+    }
+}
@@ -1,23 +1,39 @@
 //! The traits defined here is intent to describe the requirements of current
 //!  library code and only implemented the trait in upper level code.
 
+pub mod chained_impls;
 pub mod default_impls;
 pub mod dummy_impls;
 pub mod light_client_impls;
+#[cfg(feature = "test-util")]
+pub mod mock;
+pub mod name_cell_impls;
 pub mod offchain_impls;
+#[cfg(feature = "secp256r1")]
+pub mod secp256r1_impls;
 
+pub use chained_impls::{
+    ChainedCellDepResolver, ChainedHeaderDepResolver, ChainedSourcesError,
+    ChainedTransactionDependencyProvider,
+};
 pub use default_impls::{
-    DefaultCellCollector, DefaultCellDepResolver, DefaultHeaderDepResolver,
-    DefaultTransactionDependencyProvider, SecpCkbRawKeySigner,
+    DefaultCellCollector, DefaultCellDepResolver, DefaultFeeRateProvider, DefaultHeaderDepResolver,
+    DefaultTransactionDependencyProvider, FeePriority, FeeRateStatisticsValue, NodeFeeRateProvider,
+    NodeTxLimits, SecpCkbRawKeySigner,
 };
 pub use light_client_impls::{
     LightClientCellCollector, LightClientHeaderDepResolver,
     LightClientTransactionDependencyProvider,
 };
+pub use name_cell_impls::{NameArgsDerivation, NameCellBeneficiaryResolver};
 pub use offchain_impls::{
     OffchainCellCollector, OffchainCellDepResolver, OffchainHeaderDepResolver,
     OffchainTransactionDependencyProvider,
 };
+#[cfg(feature = "secp256r1")]
+pub use secp256r1_impls::Secp256r1Signer;
+
+use std::time::{Duration, Instant};
 
 use dyn_clone::DynClone;
 use thiserror::Error;
@@ -29,13 +45,13 @@ use ckb_types::{
     core::{
         cell::{CellMetaBuilder, CellProvider, CellStatus, HeaderChecker},
         error::OutPointError,
-        HeaderView, TransactionView,
+        FeeRate, HeaderView, TransactionView,
     },
     packed::{Byte32, CellDep, CellOutput, OutPoint, Script, Transaction},
     prelude::*,
 };
 
-use crate::{rpc::ckb_indexer::SearchMode, util::is_mature};
+use crate::rpc::ckb_indexer::SearchMode;
 
 /// Signer errors
 #[derive(Error, Debug)]
@@ -76,6 +92,17 @@ pub trait Signer {
     ) -> Result<Bytes, SignerError>;
 }
 
+/// A single key signer which signs the message as is, for the algorithms
+/// can not be looked up by an id like `Signer`, e.g. secp256r1 passkeys.
+pub trait MessageSigner {
+    /// The public key in the encoding the lock script expects
+    fn public_key(&self) -> Bytes;
+
+    /// Sign the message, the signature is in the native encoding of the
+    /// algorithm (DER for secp256r1).
+    fn sign_message(&self, message: &[u8]) -> Result<Bytes, SignerError>;
+}
+
 /// Transaction dependency provider errors
 #[derive(Error, Debug)]
 pub enum TransactionDependencyError {
@@ -178,13 +205,92 @@ pub enum CellCollectorError {
     Other(anyhow::Error),
 }
 
+/// How long a cell locked by `CellCollector::lock_cell_with_ttl` stays locked
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CellLockTtl {
+    /// The lock expires after the duration
+    Duration(Duration),
+    /// The lock expires when the tip block number is the given number of
+    /// blocks higher than the tip block number when it is locked
+    Blocks(u64),
+}
+
+/// When a cell lock expires
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CellLockExpiry {
+    Instant(Instant),
+    BlockNumber(u64),
+}
+
+impl CellLockExpiry {
+    pub fn new(ttl: CellLockTtl, tip_block_number: u64) -> CellLockExpiry {
+        match ttl {
+            CellLockTtl::Duration(duration) => CellLockExpiry::Instant(Instant::now() + duration),
+            CellLockTtl::Blocks(blocks) => {
+                CellLockExpiry::BlockNumber(tip_block_number.saturating_add(blocks))
+            }
+        }
+    }
+
+    pub fn is_expired(&self, tip_block_number: u64) -> bool {
+        match self {
+            CellLockExpiry::Instant(instant) => Instant::now() >= *instant,
+            CellLockExpiry::BlockNumber(number) => tip_block_number >= *number,
+        }
+    }
+}
+
+/// The block number of the cells created by the transactions applied by
+/// `CellCollector::apply_tx`, those cells are not committed yet.
+pub const PENDING_BLOCK_NUMBER: u64 = u64::MAX;
+
 #[derive(Debug, Clone)]
 pub struct LiveCell {
     pub output: CellOutput,
     pub output_data: Bytes,
     pub out_point: OutPoint,
     pub block_number: u64,
+    /// The index of the transaction in the block
     pub tx_index: u32,
+    /// Whether the cell is an output of a cellbase transaction, only the
+    /// cellbase cells need to wait for the cellbase maturity.
+    pub from_cellbase: bool,
+}
+
+impl LiveCell {
+    /// Create a live cell not from a cellbase transaction.
+    pub fn new(
+        output: CellOutput,
+        output_data: Bytes,
+        out_point: OutPoint,
+        block_number: u64,
+        tx_index: u32,
+    ) -> LiveCell {
+        LiveCell {
+            output,
+            output_data,
+            out_point,
+            block_number,
+            tx_index,
+            from_cellbase: false,
+        }
+    }
+
+    /// Whether the cell can be spent when the cellbase cells of the blocks
+    /// up to `max_mature_number` are mature, see
+    /// `util::get_max_mature_number`.
+    pub fn is_mature(&self, max_mature_number: u64) -> bool {
+        !self.from_cellbase
+            // Live cells in genesis are all mature
+            || self.block_number == 0
+            || self.block_number <= max_mature_number
+    }
+
+    /// Whether the cell is created by a transaction applied by
+    /// `CellCollector::apply_tx` and not committed yet.
+    pub fn is_unconfirmed(&self) -> bool {
+        self.block_number == PENDING_BLOCK_NUMBER
+    }
 }
 
 /// The value range option: `start <= value < end`
@@ -257,6 +363,12 @@ pub struct CellQueryOptions {
     /// collect only one cell at most.
     pub min_total_capacity: u64,
     pub script_search_mode: Option<SearchMode>,
+    /// Cells with these out points are never collected
+    pub excluded_out_points: Vec<OutPoint>,
+    /// Also collect the cells created by the transactions applied by
+    /// `CellCollector::apply_tx`, so the transactions can be chained before
+    /// they are committed. The default value is false.
+    pub allow_unconfirmed: bool,
 }
 impl CellQueryOptions {
     pub fn new(primary_script: Script, primary_type: PrimaryScriptType) -> CellQueryOptions {
@@ -274,6 +386,8 @@ impl CellQueryOptions {
             maturity: MaturityOption::Mature,
             min_total_capacity: 1,
             script_search_mode: None,
+            excluded_out_points: Vec::new(),
+            allow_unconfirmed: false,
         }
     }
     pub fn new_lock(primary_script: Script) -> CellQueryOptions {
@@ -283,6 +397,12 @@ impl CellQueryOptions {
         CellQueryOptions::new(primary_script, PrimaryScriptType::Type)
     }
     pub fn match_cell(&self, cell: &LiveCell, max_mature_number: u64) -> bool {
+        if self.excluded_out_points.contains(&cell.out_point) {
+            return false;
+        }
+        if cell.is_unconfirmed() && !self.allow_unconfirmed {
+            return false;
+        }
         fn extract_raw_data(script: &Script) -> Vec<u8> {
             [
                 script.code_hash().as_slice(),
@@ -291,6 +411,22 @@ impl CellQueryOptions {
             ]
             .concat()
         }
+        let match_primary_script = |script: &Script| match self.script_search_mode {
+            Some(SearchMode::Prefix) => {
+                extract_raw_data(script).starts_with(&extract_raw_data(&self.primary_script))
+            }
+            Some(SearchMode::Partial) => {
+                let args = script.args().raw_data();
+                let query_args = self.primary_script.args().raw_data();
+                script.code_hash() == self.primary_script.code_hash()
+                    && script.hash_type() == self.primary_script.hash_type()
+                    && (query_args.is_empty()
+                        || args
+                            .windows(query_args.len())
+                            .any(|window| window == query_args.as_ref()))
+            }
+            Some(SearchMode::Exact) | None => script == &self.primary_script,
+        };
         let filter_prefix = self.secondary_script.as_ref().map(|script| {
             if script != &Script::default() {
                 extract_raw_data(script)
@@ -301,7 +437,7 @@ impl CellQueryOptions {
         match self.primary_type {
             PrimaryScriptType::Lock => {
                 // check primary script
-                if cell.output.lock() != self.primary_script {
+                if !match_primary_script(&cell.output.lock()) {
                     return false;
                 }
 
@@ -326,7 +462,12 @@ impl CellQueryOptions {
             }
             PrimaryScriptType::Type => {
                 // check primary script
-                if cell.output.type_().to_opt().as_ref() != Some(&self.primary_script) {
+                if !cell
+                    .output
+                    .type_()
+                    .to_opt()
+                    .map_or(false, |script| match_primary_script(&script))
+                {
                     return false;
                 }
 
@@ -376,7 +517,7 @@ impl CellQueryOptions {
                 return false;
             }
         }
-        let cell_is_mature = is_mature(cell, max_mature_number);
+        let cell_is_mature = cell.is_mature(max_mature_number);
         match self.maturity {
             MaturityOption::Mature => cell_is_mature,
             MaturityOption::Immature => !cell_is_mature,
@@ -399,6 +540,27 @@ pub trait CellCollector: DynClone {
         out_point: OutPoint,
         tip_block_number: u64,
     ) -> Result<(), CellCollectorError>;
+
+    /// Mark this cell as dead cell until `ttl` expires, the expired locks are
+    /// purged when collecting live cells. A cell consumed by `apply_tx` stays
+    /// dead. Not supported by default.
+    fn lock_cell_with_ttl(
+        &mut self,
+        _out_point: OutPoint,
+        _ttl: CellLockTtl,
+    ) -> Result<(), CellCollectorError> {
+        Err(CellCollectorError::Other(anyhow::anyhow!(
+            "lock_cell_with_ttl is not supported by the cell collector"
+        )))
+    }
+
+    /// Mark a cell locked by `lock_cell` or `lock_cell_with_ttl` as live
+    /// cell again, without a full `reset`.
+    fn unlock_cell(&mut self, _out_point: &OutPoint) -> Result<(), CellCollectorError> {
+        Err(CellCollectorError::Other(anyhow::anyhow!(
+            "unlock_cell is not supported by the cell collector"
+        )))
+    }
     /// Mark all inputs as dead cells and outputs as live cells in the transaction.
     fn apply_tx(
         &mut self,
@@ -415,6 +577,12 @@ pub trait CellDepResolver {
     ///
     /// When a new script is added, transaction builders use CellDepResolver to find the corresponding cell deps and add them to the transaction.
     fn resolve(&self, script: &Script) -> Option<CellDep>;
+
+    /// Resolve all the cell deps of a script, e.g. a dep group and an extra
+    /// code cell. The first one is the cell dep of `resolve`.
+    fn resolve_all(&self, script: &Script) -> Option<Vec<CellDep>> {
+        self.resolve(script).map(|cell_dep| vec![cell_dep])
+    }
 }
 pub trait HeaderDepResolver {
     /// Resolve header dep by trancation hash
@@ -424,10 +592,66 @@ pub trait HeaderDepResolver {
     fn resolve_by_number(&self, number: u64) -> Result<Option<HeaderView>, anyhow::Error>;
 }
 
+/// Provide the fee rate used when balancing a transaction.
+pub trait FeeRateProvider: DynClone {
+    /// The fee rate (shannons/KW) to use for the transaction being built now.
+    fn fee_rate(&self) -> Result<FeeRate, anyhow::Error>;
+
+    /// The max serialized size (in block) of the balanced transaction, `None` means no limit.
+    fn max_tx_size(&self) -> Option<u64> {
+        None
+    }
+}
+dyn_clone::clone_trait_object!(FeeRateProvider);
+
+impl std::fmt::Debug for dyn FeeRateProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("FeeRateProvider")
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ResolveError {
+    #[error("name not found: `{0}`")]
+    NotFound(String),
+
+    #[error("invalid name cell of `{0}`, reason: `{1}`")]
+    InvalidNameCell(String, String),
+
+    #[error("the name cell of `{0}` changed after it was resolved")]
+    Stale(String),
+
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+/// Resolve a human readable name (e.g. `alice.ckb`) to the lock script it points to.
+pub trait BeneficiaryResolver {
+    /// Resolve the name, the result may come from a cache.
+    fn resolve(&self, name: &str) -> Result<Script, ResolveError>;
+
+    /// Check again against the chain (never the cache) that `name` still
+    /// points to `lock`, call it right before broadcasting a transaction
+    /// paying to a resolved name.
+    fn verify(&self, name: &str, lock: &Script) -> Result<(), ResolveError>;
+}
+
 // test cases make sure new added exception won't breadk `anyhow!(e_variable)` usage,
 #[cfg(test)]
 mod anyhow_tests {
     use anyhow::anyhow;
+    #[test]
+    fn test_resolve_error() {
+        use super::ResolveError;
+        let error = anyhow!(ResolveError::NotFound("alice.ckb".to_string()));
+        assert_eq!("name not found: `alice.ckb`", error.to_string());
+        let error = anyhow!(ResolveError::Stale("alice.ckb".to_string()));
+        assert_eq!(
+            "the name cell of `alice.ckb` changed after it was resolved",
+            error.to_string()
+        );
+    }
+
     #[test]
     fn test_signer_error() {
         use super::SignerError;
@@ -0,0 +1,147 @@
+//! Resolve names registered in on-chain name cells.
+//!
+//! A name cell is a live cell whose type script is `<name script id> + <args
+//! derived from the name>` (usually a type-id cell), and whose data is the
+//! molecule serialized lock script the name points to.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use ckb_hash::blake2b_256;
+use ckb_types::{
+    bytes::Bytes,
+    packed::{OutPoint, Script},
+    prelude::*,
+};
+use parking_lot::Mutex;
+
+use super::{BeneficiaryResolver, CellCollector, CellQueryOptions, MaturityOption, ResolveError};
+use crate::rpc::ckb_indexer::SearchMode;
+use crate::types::{ScriptHashTypeExt, ScriptId};
+
+/// How the type script args of a name cell are derived from the name
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum NameArgsDerivation {
+    /// The utf-8 bytes of the name
+    Raw,
+    /// blake2b_256(name)
+    Blake2b256,
+    /// blake2b_256(name)[0..20]
+    Blake160,
+}
+
+impl NameArgsDerivation {
+    pub fn derive(&self, name: &str) -> Bytes {
+        match self {
+            NameArgsDerivation::Raw => Bytes::from(name.as_bytes().to_vec()),
+            NameArgsDerivation::Blake2b256 => Bytes::from(blake2b_256(name).to_vec()),
+            NameArgsDerivation::Blake160 => Bytes::from(blake2b_256(name)[0..20].to_vec()),
+        }
+    }
+}
+
+/// A `BeneficiaryResolver` backed by on-chain name cells
+pub struct NameCellBeneficiaryResolver {
+    cell_collector: Mutex<Box<dyn CellCollector>>,
+    name_script_id: ScriptId,
+    derivation: NameArgsDerivation,
+    ttl: Duration,
+    strict: bool,
+    // name => (lock script, name cell out point, resolved at)
+    cache: Mutex<HashMap<String, (Script, OutPoint, Instant)>>,
+}
+
+impl NameCellBeneficiaryResolver {
+    /// The default cache ttl is 60 seconds and strict mode is off.
+    pub fn new(
+        cell_collector: Box<dyn CellCollector>,
+        name_script_id: ScriptId,
+        derivation: NameArgsDerivation,
+    ) -> NameCellBeneficiaryResolver {
+        NameCellBeneficiaryResolver {
+            cell_collector: Mutex::new(cell_collector),
+            name_script_id,
+            derivation,
+            ttl: Duration::from_secs(60),
+            strict: false,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Set how long a resolved name is cached, `Duration::ZERO` disables the cache.
+    pub fn set_ttl(&mut self, ttl: Duration) {
+        self.ttl = ttl;
+    }
+
+    /// In strict mode `verify` also fails when the name cell the name was
+    /// last resolved from is no longer live, even if the new name cell points
+    /// to the same lock script. A name never resolved fails as well.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// The type script of the name cell
+    pub fn name_type_script(&self, name: &str) -> Script {
+        Script::new_builder()
+            .code_hash(self.name_script_id.code_hash.pack())
+            .hash_type(self.name_script_id.hash_type.to_packed())
+            .args(self.derivation.derive(name).pack())
+            .build()
+    }
+
+    pub fn clear_cache(&self) {
+        self.cache.lock().clear();
+    }
+
+    /// Query the current name cell, the cache is not touched.
+    fn lookup(&self, name: &str) -> Result<(Script, OutPoint), ResolveError> {
+        let mut query = CellQueryOptions::new_type(self.name_type_script(name));
+        query.script_search_mode = Some(SearchMode::Exact);
+        query.maturity = MaturityOption::Both;
+        query.with_data = Some(true);
+        let (cells, _) = self
+            .cell_collector
+            .lock()
+            .collect_live_cells(&query, false)
+            .map_err(|err| ResolveError::Other(err.into()))?;
+        let cell = cells
+            .into_iter()
+            .next()
+            .ok_or_else(|| ResolveError::NotFound(name.to_string()))?;
+        let lock = Script::from_slice(&cell.output_data)
+            .map_err(|err| ResolveError::InvalidNameCell(name.to_string(), err.to_string()))?;
+        Ok((lock, cell.out_point))
+    }
+}
+
+impl BeneficiaryResolver for NameCellBeneficiaryResolver {
+    fn resolve(&self, name: &str) -> Result<Script, ResolveError> {
+        if let Some((lock, _, resolved_at)) = self.cache.lock().get(name) {
+            if resolved_at.elapsed() < self.ttl {
+                return Ok(lock.clone());
+            }
+        }
+        let (lock, out_point) = self.lookup(name)?;
+        self.cache
+            .lock()
+            .insert(name.to_string(), (lock.clone(), out_point, Instant::now()));
+        Ok(lock)
+    }
+
+    fn verify(&self, name: &str, lock: &Script) -> Result<(), ResolveError> {
+        // the out point recorded when the name was resolved
+        let cached_out_point = self
+            .cache
+            .lock()
+            .get(name)
+            .map(|(_, out_point, _)| out_point.clone());
+        let (current_lock, current_out_point) = self.lookup(name)?;
+        if &current_lock != lock {
+            return Err(ResolveError::Stale(name.to_string()));
+        }
+        if self.strict && cached_out_point.as_ref() != Some(&current_out_point) {
+            return Err(ResolveError::Stale(name.to_string()));
+        }
+        Ok(())
+    }
+}
@@ -11,8 +11,9 @@ use ckb_types::{
 };
 
 use crate::traits::{
-    CellCollectorError, CellDepResolver, CellQueryOptions, HeaderDepResolver, LiveCell,
-    TransactionDependencyError, TransactionDependencyProvider,
+    CellCollectorError, CellDepResolver, CellLockExpiry, CellLockTtl, CellQueryOptions,
+    HeaderDepResolver, LiveCell, TransactionDependencyError, TransactionDependencyProvider,
+    PENDING_BLOCK_NUMBER,
 };
 use crate::types::ScriptId;
 use anyhow::anyhow;
@@ -53,6 +54,8 @@ const KEEP_BLOCK_PERIOD: u64 = 13;
 pub struct OffchainCellCollector {
     // (block_hash, index) => tip_block_number
     pub locked_cells: HashMap<(H256, u32), u64>,
+    // (block_hash, index) => expiry, the locked cells locked with a ttl
+    pub lock_expirations: HashMap<(H256, u32), CellLockExpiry>,
     // (live_cell, tip_block_number)
     pub live_cells: Vec<(LiveCell, u64)>,
     pub max_mature_number: u64,
@@ -74,15 +77,32 @@ impl OffchainCellCollector {
                     || (current_tip_block_number - block_num) <= KEEP_BLOCK_PERIOD
             })
             .collect();
+        let lock_expirations = &self.lock_expirations;
         self.locked_cells = self
             .locked_cells
             .clone()
             .into_iter()
-            .filter(|(_k, block_num)| {
-                *block_num >= current_tip_block_number
+            .filter(|(k, block_num)| {
+                // the locks with a ttl are kept until they expire
+                lock_expirations.contains_key(k)
+                    || *block_num >= current_tip_block_number
                     || (current_tip_block_number - block_num) <= KEEP_BLOCK_PERIOD
             })
             .collect();
+        self.purge_expired_locks(current_tip_block_number);
+    }
+
+    fn purge_expired_locks(&mut self, current_tip_block_number: u64) {
+        let expired: Vec<_> = self
+            .lock_expirations
+            .iter()
+            .filter(|(_k, expiry)| expiry.is_expired(current_tip_block_number))
+            .map(|(k, _expiry)| k.clone())
+            .collect();
+        for k in expired {
+            self.lock_expirations.remove(&k);
+            self.locked_cells.remove(&k);
+        }
     }
 
     pub(crate) fn collect(
@@ -92,6 +112,7 @@ impl OffchainCellCollector {
     ) -> CollectResult {
         self.truncate(tip_block_number);
         let mut total_capacity = 0;
+        let locked_cells = &self.locked_cells;
         let (cells, rest_cells): (Vec<_>, Vec<_>) =
             self.live_cells
                 .clone()
@@ -99,6 +120,10 @@ impl OffchainCellCollector {
                 .partition(|(cell, _tip_num)| {
                     if total_capacity < query.min_total_capacity
                         && query.match_cell(cell, self.max_mature_number)
+                        && !locked_cells.contains_key(&(
+                            cell.out_point.tx_hash().unpack(),
+                            cell.out_point.index().unpack(),
+                        ))
                     {
                         let capacity: u64 = cell.output.capacity().unpack();
                         total_capacity += capacity;
@@ -125,6 +150,26 @@ impl OffchainCellCollector {
         );
         Ok(())
     }
+
+    pub(crate) fn lock_cell_with_ttl(
+        &mut self,
+        out_point: OutPoint,
+        tip_blocknumber: u64,
+        ttl: CellLockTtl,
+    ) -> Result<(), CellCollectorError> {
+        self.lock_expirations.insert(
+            (out_point.tx_hash().unpack(), out_point.index().unpack()),
+            CellLockExpiry::new(ttl, tip_blocknumber),
+        );
+        self.lock_cell(out_point, tip_blocknumber)
+    }
+
+    pub(crate) fn unlock_cell(&mut self, out_point: &OutPoint) -> Result<(), CellCollectorError> {
+        let k = (out_point.tx_hash().unpack(), out_point.index().unpack());
+        self.lock_expirations.remove(&k);
+        self.locked_cells.remove(&k);
+        Ok(())
+    }
     pub(crate) fn apply_tx(
         &mut self,
         tx: Transaction,
@@ -133,6 +178,9 @@ impl OffchainCellCollector {
         let tx_view = tx.into_view();
         let tx_hash = tx_view.hash();
         for out_point in tx_view.input_pts_iter() {
+            // the consumed cells never become live again
+            self.lock_expirations
+                .remove(&(out_point.tx_hash().unpack(), out_point.index().unpack()));
             self.lock_cell(out_point, tip_blocknumber)?;
         }
         for (output_index, (output, data)) in tx_view.outputs_with_data_iter().enumerate() {
@@ -141,8 +189,9 @@ impl OffchainCellCollector {
                 output: output.clone(),
                 output_data: data.clone(),
                 out_point,
-                block_number: 0,
+                block_number: PENDING_BLOCK_NUMBER,
                 tx_index: 0,
+                from_cellbase: false,
             };
             self.live_cells.push((info, tip_blocknumber));
         }
@@ -151,6 +200,7 @@ impl OffchainCellCollector {
 
     pub(crate) fn reset(&mut self) {
         self.locked_cells.clear();
+        self.lock_expirations.clear();
         self.live_cells.clear();
     }
 }
@@ -0,0 +1,48 @@
+use ckb_hash::blake2b_256;
+use ckb_types::{bytes::Bytes, H160};
+use p256::ecdsa::{signature::Signer as _, Signature, SigningKey};
+
+use super::{MessageSigner, SignerError};
+
+/// A secp256r1 (P-256) raw key signer, the signature is ECDSA over the
+/// SHA-256 digest of the message as a WebAuthn authenticator does.
+#[derive(Clone)]
+pub struct Secp256r1Signer {
+    key: SigningKey,
+}
+
+impl Secp256r1Signer {
+    pub fn new(key: SigningKey) -> Secp256r1Signer {
+        Secp256r1Signer { key }
+    }
+
+    pub fn from_secret_key(secret_key: &[u8]) -> Result<Secp256r1Signer, SignerError> {
+        let key = SigningKey::from_slice(secret_key)
+            .map_err(|err| SignerError::Other(anyhow::anyhow!("invalid secp256r1 key: {}", err)))?;
+        Ok(Secp256r1Signer { key })
+    }
+
+    /// The uncompressed public key without the `0x04` prefix: `x || y`
+    pub fn raw_public_key(&self) -> [u8; 64] {
+        let point = self.key.verifying_key().to_encoded_point(false);
+        let mut pubkey = [0u8; 64];
+        pubkey.copy_from_slice(&point.as_bytes()[1..]);
+        pubkey
+    }
+
+    /// `blake160(x || y)`
+    pub fn pubkey_hash(&self) -> H160 {
+        H160::from_slice(&blake2b_256(self.raw_public_key())[0..20]).expect("20 bytes")
+    }
+}
+
+impl MessageSigner for Secp256r1Signer {
+    fn public_key(&self) -> Bytes {
+        Bytes::from(self.raw_public_key().to_vec())
+    }
+
+    fn sign_message(&self, message: &[u8]) -> Result<Bytes, SignerError> {
+        let signature: Signature = self.key.sign(message);
+        Ok(Bytes::from(signature.to_der().as_bytes().to_vec()))
+    }
+}
@@ -1,6 +1,10 @@
 use ckb_types::core::FeeRate;
 
-use crate::{core::TransactionBuilder, tx_builder::bytes_per_cycle};
+use crate::{
+    core::TransactionBuilder,
+    tx_builder::bytes_per_cycle,
+    util::{calc_fee, tx_size},
+};
 
 pub struct FeeCalculator {
     fee_rate: u64,
@@ -11,8 +15,7 @@ impl FeeCalculator {
         Self { fee_rate }
     }
     pub fn fee(&self, weight: u64) -> u64 {
-        let fee_rate = FeeRate::from_u64(self.fee_rate);
-        fee_rate.fee(weight).as_u64()
+        calc_fee(weight, FeeRate::from_u64(self.fee_rate))
     }
 
     pub fn fee_with_cycle(&self, tx_size: u64, cycles: u64) -> u64 {
@@ -21,12 +24,6 @@ impl FeeCalculator {
     }
 
     pub fn fee_with_tx_builder(&self, tx_builder: &TransactionBuilder) -> u64 {
-        let tx_size = tx_builder
-            .clone()
-            .build()
-            .data()
-            .as_reader()
-            .serialized_size_in_block();
-        self.fee(tx_size as u64)
+        self.fee(tx_size(&tx_builder.clone().build()))
     }
 }
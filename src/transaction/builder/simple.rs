@@ -1,11 +1,14 @@
 use crate::{
     core::TransactionBuilder,
+    traits::{BeneficiaryResolver, ResolveError},
     transaction::{
         handler::HandlerContexts, input::InputIterator, TransactionBuilderConfiguration,
     },
     tx_builder::TxBuilderError,
+    util::is_burn_lock,
     TransactionWithScriptGroups,
 };
+use anyhow::anyhow;
 use ckb_types::{
     core::Capacity,
     packed::{self, CellOutput, Script},
@@ -24,6 +27,8 @@ pub struct SimpleTransactionBuilder {
     input_iter: InputIterator,
     /// The inner transaction builder
     tx: TransactionBuilder,
+    /// Accept outputs to a burn lock, see `is_burn_lock`
+    allow_burn: bool,
 }
 
 impl SimpleTransactionBuilder {
@@ -37,9 +42,15 @@ impl SimpleTransactionBuilder {
             configuration,
             input_iter,
             tx: TransactionBuilder::default(),
+            allow_burn: false,
         }
     }
 
+    /// Accept outputs to a burn lock, by default `build` refuses them since the capacity is lost forever.
+    pub fn allow_burn(&mut self) {
+        self.allow_burn = true;
+    }
+
     /// Update the change lock script.
     pub fn set_change_lock(&mut self, lock_script: Script) {
         self.change_lock = lock_script;
@@ -59,6 +70,20 @@ impl SimpleTransactionBuilder {
             .build();
         self.add_output_and_data(output, packed::Bytes::default());
     }
+
+    /// Add an output cell paying to the lock script `name` resolves to, returns the resolved lock script.
+    ///
+    /// Call `BeneficiaryResolver::verify` with the returned lock script right before broadcasting the transaction.
+    pub fn add_output_to_name(
+        &mut self,
+        resolver: &dyn BeneficiaryResolver,
+        name: &str,
+        capacity: Capacity,
+    ) -> Result<Script, ResolveError> {
+        let lock_script = resolver.resolve(name)?;
+        self.add_output(lock_script.clone(), capacity);
+        Ok(lock_script)
+    }
 }
 
 impl CkbTransactionBuilder for SimpleTransactionBuilder {
@@ -71,8 +96,22 @@ impl CkbTransactionBuilder for SimpleTransactionBuilder {
             configuration,
             input_iter,
             tx,
+            allow_burn,
         } = self;
 
+        if !allow_burn {
+            if let Some(idx) = tx
+                .outputs
+                .iter()
+                .position(|output| is_burn_lock(&output.lock()))
+            {
+                return Err(TxBuilderError::InvalidParameter(anyhow!(
+                    "output #{} is sent to a burn lock, call allow_burn if it is intended",
+                    idx
+                )));
+            }
+        }
+
         let change_builder = DefaultChangeBuilder {
             configuration: &configuration,
             change_lock,
@@ -6,6 +6,7 @@ use crate::{
         handler::HandlerContexts, input::InputIterator, TransactionBuilderConfiguration,
     },
     tx_builder::{BalanceTxCapacityError, TxBuilderError},
+    types::ScriptHashTypeExt,
     NetworkInfo, NetworkType, TransactionWithScriptGroups,
 };
 use anyhow::anyhow;
@@ -234,7 +235,7 @@ fn build_sudt_type_script(
 
     Script::new_builder()
         .code_hash(code_hash.pack())
-        .hash_type(ScriptHashType::Type.into())
+        .hash_type(ScriptHashType::Type.to_packed())
         .args(sudt_owner_lock_script.calc_script_hash().as_bytes().pack())
         .build()
 }
@@ -3,7 +3,7 @@ use std::collections::HashMap;
 
 use crate::{
     constants,
-    unlock::{MultisigConfig, UnlockError},
+    unlock::{MultisigConfig, RegistryError, UnlockError},
     NetworkInfo, ScriptGroup, ScriptId, TransactionWithScriptGroups,
 };
 
@@ -96,6 +96,28 @@ impl TransactionSigner {
         Self { unlockers }
     }
 
+    /// Register the signer of `script_id`, return the previous one if any.
+    pub fn register(
+        &mut self,
+        script_id: ScriptId,
+        signer: Box<dyn CKBScriptSigner>,
+    ) -> Option<Box<dyn CKBScriptSigner>> {
+        self.unlockers.insert(script_id, signer)
+    }
+
+    /// Register the signer of `script_id`, error if it's already registered.
+    pub fn try_register(
+        &mut self,
+        script_id: ScriptId,
+        signer: Box<dyn CKBScriptSigner>,
+    ) -> Result<(), RegistryError> {
+        if self.unlockers.contains_key(&script_id) {
+            return Err(RegistryError::Duplicate(script_id));
+        }
+        self.unlockers.insert(script_id, signer);
+        Ok(())
+    }
+
     pub fn sign_transaction(
         &self,
         transaction: &mut TransactionWithScriptGroups,
@@ -1,33 +1,104 @@
-use std::collections::HashSet;
-
 use anyhow::anyhow;
 use ckb_types::{
-    core::{TransactionBuilder, TransactionView},
-    packed::{CellInput, Script},
+    bytes::Bytes,
+    core::{Capacity, TransactionBuilder, TransactionView},
+    packed::{CellInput, CellOutput, OutPoint, Script},
     prelude::*,
 };
 
-use super::{TxBuilder, TxBuilderError};
+use super::{extend_unique, udt::checked_add_amount, TxBuilder, TxBuilderError};
 use crate::traits::{
-    CellCollector, CellDepResolver, CellQueryOptions, HeaderDepResolver,
-    TransactionDependencyProvider,
+    CellCollector, CellDepResolver, CellQueryOptions, HeaderDepResolver, LiveCell,
+    TransactionDependencyProvider, ValueRangeOption,
 };
 
 #[derive(Clone, Debug)]
 pub struct AcpTransferReceiver {
     pub lock_script: Script,
+    /// The type script of the acp cell to transfer to, `None` means the acp
+    /// cell must have no type script. Used to pick the right cell when the
+    /// lock script owns several acp cells.
+    pub type_script: Option<Script>,
+    /// Use this acp cell directly instead of searching it by `lock_script`
+    /// and `type_script`, the cell is fetched by `TransactionDependencyProvider`.
+    pub out_point: Option<OutPoint>,
     pub capacity: u64,
+    /// Also add this amount to the udt amount (the first 16 bytes of the
+    /// data) of the acp cell, the acp cell must have a type script. The udt
+    /// must be provided by other inputs of the transaction.
+    pub udt_amount: Option<u128>,
 }
 impl AcpTransferReceiver {
     pub fn new(lock_script: Script, capacity: u64) -> AcpTransferReceiver {
         AcpTransferReceiver {
             lock_script,
+            type_script: None,
+            out_point: None,
+            capacity,
+            udt_amount: None,
+        }
+    }
+
+    pub fn new_with_udt_amount(
+        lock_script: Script,
+        type_script: Script,
+        capacity: u64,
+        udt_amount: u128,
+    ) -> AcpTransferReceiver {
+        AcpTransferReceiver {
+            lock_script,
+            type_script: Some(type_script),
+            out_point: None,
             capacity,
+            udt_amount: Some(udt_amount),
         }
     }
+
+    fn find_cell(
+        &self,
+        cell_collector: &mut dyn CellCollector,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<LiveCell, TxBuilderError> {
+        if let Some(out_point) = self.out_point.as_ref() {
+            let output = tx_dep_provider.get_cell(out_point)?;
+            if output.lock() != self.lock_script {
+                return Err(TxBuilderError::InvalidParameter(anyhow!(
+                    "the lock script of acp cell {} is not {:?}",
+                    out_point,
+                    self.lock_script
+                )));
+            }
+            let output_data = tx_dep_provider.get_cell_data(out_point)?;
+            return Ok(LiveCell {
+                output,
+                output_data,
+                out_point: out_point.clone(),
+                block_number: 0,
+                tx_index: 0,
+                from_cellbase: false,
+            });
+        }
+        let mut query = CellQueryOptions::new_lock(self.lock_script.clone());
+        if let Some(type_script) = self.type_script.as_ref() {
+            query.secondary_script = Some(type_script.clone());
+        } else {
+            query.secondary_script_len_range = Some(ValueRangeOption::new_exact(0));
+        }
+        // the acp cell may be updated by a chained transaction
+        query.allow_unconfirmed = true;
+        let (mut cells, _) = cell_collector.collect_live_cells(&query, true)?;
+        if cells.is_empty() {
+            return Err(TxBuilderError::Other(anyhow!(
+                "can not found acp cell by lock script: {:?}, type script: {:?}",
+                self.lock_script,
+                self.type_script
+            )));
+        }
+        Ok(cells.remove(0))
+    }
 }
-/// Transfer capacity to already exists acp cell, the type script and cell data
-/// will be copied.
+/// Transfer capacity (and optionally udt) to already exists acp cell, the type
+/// script and cell data (except the udt amount) will be copied.
 pub struct AcpTransferBuilder {
     pub receivers: Vec<AcpTransferReceiver>,
 }
@@ -43,24 +114,16 @@ impl TxBuilder for AcpTransferBuilder {
         cell_collector: &mut dyn CellCollector,
         cell_dep_resolver: &dyn CellDepResolver,
         _header_dep_resolver: &dyn HeaderDepResolver,
-        _tx_dep_provider: &dyn TransactionDependencyProvider,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
     ) -> Result<TransactionView, TxBuilderError> {
-        #[allow(clippy::mutable_key_type)]
-        let mut cell_deps = HashSet::new();
+        let mut cell_deps = Vec::new();
         let mut inputs = Vec::new();
         let mut outputs = Vec::new();
         let mut outputs_data = Vec::new();
         for receiver in &self.receivers {
-            let query = CellQueryOptions::new_lock(receiver.lock_script.clone());
-            let (cells, input_capacity) = cell_collector.collect_live_cells(&query, true)?;
-            if cells.is_empty() {
-                return Err(TxBuilderError::Other(anyhow!(
-                    "can not found cell by lock script: {:?}",
-                    receiver.lock_script
-                )));
-            }
-            let input_cell = &cells[0];
+            let input_cell = receiver.find_cell(cell_collector, tx_dep_provider)?;
             let input = CellInput::new(input_cell.out_point.clone(), 0);
+            let input_capacity: u64 = input_cell.output.capacity().unpack();
             let output_capacity = input_capacity + receiver.capacity;
             let output = input_cell
                 .output
@@ -68,19 +131,43 @@ impl TxBuilder for AcpTransferBuilder {
                 .as_builder()
                 .capacity(output_capacity.pack())
                 .build();
-            let output_data = input_cell.output_data.clone();
+            let output_data = match receiver.udt_amount {
+                Some(udt_amount) => {
+                    if input_cell.output.type_().is_none() {
+                        return Err(TxBuilderError::InvalidParameter(anyhow!(
+                            "acp cell has no type script, can not add udt amount: {}",
+                            input_cell.out_point
+                        )));
+                    }
+                    let data = &input_cell.output_data;
+                    if data.len() < 16 {
+                        return Err(TxBuilderError::InvalidParameter(anyhow!(
+                            "acp cell data too short to hold udt amount, expected at least 16 bytes, got {}: {}",
+                            data.len(),
+                            input_cell.out_point
+                        )));
+                    }
+                    let mut amount_bytes = [0u8; 16];
+                    amount_bytes.copy_from_slice(&data[0..16]);
+                    let amount = checked_add_amount(u128::from_le_bytes(amount_bytes), udt_amount)?;
+                    let mut new_data = amount.to_le_bytes().to_vec();
+                    new_data.extend_from_slice(&data[16..]);
+                    Bytes::from(new_data)
+                }
+                None => input_cell.output_data.clone(),
+            };
 
-            let lock_cell_dep = cell_dep_resolver
-                .resolve(&receiver.lock_script)
+            let lock_cell_deps = cell_dep_resolver
+                .resolve_all(&receiver.lock_script)
                 .ok_or_else(|| {
                     TxBuilderError::ResolveCellDepFailed(receiver.lock_script.clone())
                 })?;
-            cell_deps.insert(lock_cell_dep);
+            extend_unique(&mut cell_deps, lock_cell_deps);
             if let Some(type_script) = input_cell.output.type_().to_opt() {
-                let cell_dep = cell_dep_resolver
-                    .resolve(&type_script)
+                let type_cell_deps = cell_dep_resolver
+                    .resolve_all(&type_script)
                     .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(type_script.clone()))?;
-                cell_deps.insert(cell_dep);
+                extend_unique(&mut cell_deps, type_cell_deps);
             }
 
             inputs.push(input);
@@ -88,10 +175,125 @@ impl TxBuilder for AcpTransferBuilder {
             outputs_data.push(output_data.pack());
         }
         Ok(TransactionBuilder::default()
-            .set_cell_deps(cell_deps.into_iter().collect())
+            .set_cell_deps(cell_deps)
             .set_inputs(inputs)
             .set_outputs(outputs)
             .set_outputs_data(outputs_data)
             .build())
     }
 }
+
+/// The acp cell to create
+#[derive(Clone, Debug)]
+pub struct AcpCreateReceiver {
+    pub acp_lock: Script,
+    /// The capacity of the cell, `None` means the occupied capacity
+    pub capacity: Option<u64>,
+    /// The udt type script of the cell, `None` means a ckb only acp cell
+    pub udt_type: Option<Script>,
+    /// The udt amount written to the cell data, only used when `udt_type` is set
+    pub init_udt_amount: u128,
+}
+impl AcpCreateReceiver {
+    pub fn new(acp_lock: Script) -> AcpCreateReceiver {
+        AcpCreateReceiver {
+            acp_lock,
+            capacity: None,
+            udt_type: None,
+            init_udt_amount: 0,
+        }
+    }
+
+    pub fn new_with_udt(
+        acp_lock: Script,
+        udt_type: Script,
+        init_udt_amount: u128,
+    ) -> AcpCreateReceiver {
+        AcpCreateReceiver {
+            acp_lock,
+            capacity: None,
+            udt_type: Some(udt_type),
+            init_udt_amount,
+        }
+    }
+}
+
+/// Create new acp cells, the capacity (and udt) are provided by the balancer
+/// or other inputs of the transaction.
+#[derive(Clone, Debug)]
+pub struct AcpCreateBuilder {
+    pub receivers: Vec<AcpCreateReceiver>,
+}
+impl AcpCreateBuilder {
+    pub fn new(receivers: Vec<AcpCreateReceiver>) -> AcpCreateBuilder {
+        AcpCreateBuilder { receivers }
+    }
+}
+
+impl TxBuilder for AcpCreateBuilder {
+    fn build_base(
+        &self,
+        _cell_collector: &mut dyn CellCollector,
+        cell_dep_resolver: &dyn CellDepResolver,
+        _header_dep_resolver: &dyn HeaderDepResolver,
+        _tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, TxBuilderError> {
+        if self.receivers.is_empty() {
+            return Err(TxBuilderError::InvalidParameter(anyhow!(
+                "empty acp receivers"
+            )));
+        }
+        let mut cell_deps = Vec::new();
+        let mut outputs = Vec::new();
+        let mut outputs_data = Vec::new();
+        for receiver in &self.receivers {
+            let lock_cell_deps = cell_dep_resolver
+                .resolve_all(&receiver.acp_lock)
+                .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(receiver.acp_lock.clone()))?;
+            extend_unique(&mut cell_deps, lock_cell_deps);
+
+            let output_data = match receiver.udt_type.as_ref() {
+                Some(udt_type) => {
+                    let type_cell_deps = cell_dep_resolver
+                        .resolve_all(udt_type)
+                        .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(udt_type.clone()))?;
+                    extend_unique(&mut cell_deps, type_cell_deps);
+                    Bytes::from(receiver.init_udt_amount.to_le_bytes().to_vec())
+                }
+                None if receiver.init_udt_amount > 0 => {
+                    return Err(TxBuilderError::InvalidParameter(anyhow!(
+                        "init udt amount is set but udt type script is missing, lock={:?}",
+                        receiver.acp_lock
+                    )));
+                }
+                None => Bytes::new(),
+            };
+            let base_output = CellOutput::new_builder()
+                .lock(receiver.acp_lock.clone())
+                .type_(receiver.udt_type.clone().pack())
+                .build();
+            let occupied_capacity = base_output
+                .occupied_capacity(Capacity::bytes(output_data.len()).unwrap())
+                .unwrap()
+                .as_u64();
+            let capacity = match receiver.capacity {
+                Some(capacity) if capacity < occupied_capacity => {
+                    return Err(TxBuilderError::Other(anyhow!(
+                        "Not enough capacity to hold an acp cell, min: {}, actual: {}",
+                        occupied_capacity,
+                        capacity,
+                    )));
+                }
+                Some(capacity) => capacity,
+                None => occupied_capacity,
+            };
+            outputs.push(base_output.as_builder().capacity(capacity.pack()).build());
+            outputs_data.push(output_data.pack());
+        }
+        Ok(TransactionBuilder::default()
+            .set_cell_deps(cell_deps)
+            .set_outputs(outputs)
+            .set_outputs_data(outputs_data)
+            .build())
+    }
+}
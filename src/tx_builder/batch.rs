@@ -0,0 +1,288 @@
+use anyhow::anyhow;
+use ckb_types::{
+    bytes::Bytes,
+    core::{Capacity, TransactionBuilder, TransactionView},
+    packed::{CellInput, CellOutput, OutPoint, Script},
+    prelude::*,
+};
+
+use super::udt::{checked_add_amount, set_udt_amount, ReceiverBuildOutput, UdtTargetReceiver};
+use super::{extend_unique, TransferAction, TxBuilder, TxBuilderError};
+use crate::traits::{
+    CellCollector, CellDepResolver, CellQueryOptions, HeaderDepResolver, LiveCell,
+    TransactionDependencyProvider, ValueRangeOption,
+};
+
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub enum Asset {
+    /// The capacity in shannons
+    Ckb(u64),
+    Udt {
+        type_script: Script,
+        amount: u128,
+        action: TransferAction,
+    },
+}
+
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub struct TransferItem {
+    pub receiver_lock: Script,
+    pub asset: Asset,
+}
+
+impl TransferItem {
+    pub fn new_ckb(receiver_lock: Script, capacity: u64) -> TransferItem {
+        TransferItem {
+            receiver_lock,
+            asset: Asset::Ckb(capacity),
+        }
+    }
+
+    pub fn new_udt(
+        receiver_lock: Script,
+        type_script: Script,
+        amount: u128,
+        action: TransferAction,
+    ) -> TransferItem {
+        TransferItem {
+            receiver_lock,
+            asset: Asset::Udt {
+                type_script,
+                amount,
+                action,
+            },
+        }
+    }
+}
+
+/// Pay CKB and several kinds of udt from one sender in one transaction.
+///
+/// The outputs follow the order of `items`, then one udt change cell for
+/// every udt type script (in the order the type script first appears in
+/// `items`). The CKB is balanced by the `CapacityBalancer`.
+///
+/// The cells are only locked in the cell collector after all the items are
+/// built, no cell is locked when the build fails. If the sender does not have
+/// enough udt of any type script, `TxBuilderError::InsufficientUdt` tells the
+/// type script.
+pub struct BatchTransferBuilder {
+    /// The sender's lock script, owns the udt cells
+    pub sender: Script,
+    pub items: Vec<TransferItem>,
+}
+
+impl BatchTransferBuilder {
+    pub fn new(sender: Script, items: Vec<TransferItem>) -> BatchTransferBuilder {
+        BatchTransferBuilder { sender, items }
+    }
+
+    /// The udt type scripts (in the order they first appear) and the total amounts
+    fn udt_totals(&self) -> Result<Vec<(Script, u128)>, TxBuilderError> {
+        let mut totals: Vec<(Script, u128)> = Vec::new();
+        for item in &self.items {
+            if let Asset::Udt {
+                type_script,
+                amount,
+                ..
+            } = &item.asset
+            {
+                match totals.iter_mut().find(|(script, _)| script == type_script) {
+                    Some((_, total)) => *total = checked_add_amount(*total, *amount)?,
+                    None => totals.push((type_script.clone(), *amount)),
+                }
+            }
+        }
+        Ok(totals)
+    }
+
+    fn sender_udt_query(&self, type_script: &Script) -> CellQueryOptions {
+        let mut query = CellQueryOptions::new_lock(self.sender.clone());
+        query.secondary_script = Some(type_script.clone());
+        query.data_len_range = Some(ValueRangeOption::new_min(16));
+        query.min_total_capacity = u64::MAX;
+        query
+    }
+
+    /// Select the sender udt cells of every type script without locking them,
+    /// fail if any type script is short.
+    fn select_sender_cells(
+        &self,
+        totals: &[(Script, u128)],
+        cell_collector: &mut dyn CellCollector,
+    ) -> Result<Vec<(Vec<LiveCell>, u128)>, TxBuilderError> {
+        let mut selected = Vec::with_capacity(totals.len());
+        for (type_script, required) in totals {
+            let query = self.sender_udt_query(type_script);
+            let (cells, _) = cell_collector.collect_live_cells(&query, false)?;
+            let mut cells_amount: u128 = 0;
+            let mut cells_selected = Vec::new();
+            for cell in cells {
+                if cells_amount >= *required && !cells_selected.is_empty() {
+                    break;
+                }
+                let mut amount_bytes = [0u8; 16];
+                amount_bytes.copy_from_slice(&cell.output_data.as_ref()[0..16]);
+                cells_amount = checked_add_amount(cells_amount, u128::from_le_bytes(amount_bytes))?;
+                cells_selected.push(cell);
+            }
+            if cells_selected.is_empty() || cells_amount < *required {
+                return Err(TxBuilderError::InsufficientUdt {
+                    type_script: type_script.clone(),
+                    required: *required,
+                    available: cells_amount,
+                });
+            }
+            selected.push((cells_selected, cells_amount));
+        }
+        Ok(selected)
+    }
+}
+
+impl TxBuilder for BatchTransferBuilder {
+    fn build_base(
+        &self,
+        cell_collector: &mut dyn CellCollector,
+        cell_dep_resolver: &dyn CellDepResolver,
+        _header_dep_resolver: &dyn HeaderDepResolver,
+        _tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, TxBuilderError> {
+        if self.items.is_empty() {
+            return Err(TxBuilderError::InvalidParameter(anyhow!(
+                "empty transfer items"
+            )));
+        }
+        let totals = self.udt_totals()?;
+        let selected = self.select_sender_cells(&totals, cell_collector)?;
+        let mut used_out_points: Vec<OutPoint> = selected
+            .iter()
+            .flat_map(|(cells, _)| cells.iter().map(|cell| cell.out_point.clone()))
+            .collect();
+
+        let mut cell_deps = Vec::new();
+        let sender_cell_deps = cell_dep_resolver
+            .resolve_all(&self.sender)
+            .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(self.sender.clone()))?;
+        extend_unique(&mut cell_deps, sender_cell_deps);
+        for (type_script, _) in &totals {
+            let udt_cell_deps = cell_dep_resolver
+                .resolve_all(type_script)
+                .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(type_script.clone()))?;
+            extend_unique(&mut cell_deps, udt_cell_deps);
+        }
+
+        let mut inputs = used_out_points
+            .iter()
+            .map(|out_point| CellInput::new(out_point.clone(), 0))
+            .collect::<Vec<_>>();
+        let mut outputs = Vec::new();
+        let mut outputs_data = Vec::new();
+        // The queries and the out points of the `Update` receiver cells
+        let mut receiver_cells = Vec::new();
+        for item in &self.items {
+            match &item.asset {
+                Asset::Ckb(capacity) => {
+                    let output = CellOutput::new_builder()
+                        .lock(item.receiver_lock.clone())
+                        .capacity(capacity.pack())
+                        .build();
+                    let occupied_capacity =
+                        output.occupied_capacity(Capacity::zero()).unwrap().as_u64();
+                    if *capacity < occupied_capacity {
+                        return Err(TxBuilderError::InvalidParameter(anyhow!(
+                            "Not enough capacity to hold a receiver cell, min: {}, actual: {}",
+                            occupied_capacity,
+                            capacity
+                        )));
+                    }
+                    outputs.push(output);
+                    outputs_data.push(Bytes::default().pack());
+                }
+                Asset::Udt {
+                    type_script,
+                    amount,
+                    action,
+                } => {
+                    let receiver =
+                        UdtTargetReceiver::new(action.clone(), item.receiver_lock.clone(), *amount);
+                    let ReceiverBuildOutput {
+                        input,
+                        output,
+                        output_data,
+                    } = receiver.build_excluding_with(
+                        type_script,
+                        cell_collector,
+                        cell_dep_resolver,
+                        &used_out_points,
+                        false,
+                    )?;
+                    if let Some((input, input_lock_cell_deps)) = input {
+                        used_out_points.push(input.previous_output());
+                        receiver_cells
+                            .push((receiver.update_query(type_script), input.previous_output()));
+                        inputs.push(input);
+                        extend_unique(&mut cell_deps, input_lock_cell_deps);
+                    }
+                    outputs.push(output);
+                    outputs_data.push(output_data.pack());
+                }
+            }
+        }
+
+        // One change cell for every udt type script, it reuses the first
+        // selected sender cell (capacity and extra data)
+        for ((_, required), (cells, cells_amount)) in totals.iter().zip(selected.iter()) {
+            let change_cell = &cells[0];
+            outputs.push(change_cell.output.clone());
+            outputs_data
+                .push(set_udt_amount(&change_cell.output_data, cells_amount - required).pack());
+        }
+
+        // All the items are built, now lock the selected cells
+        for ((type_script, _), (cells, _)) in totals.iter().zip(selected.iter()) {
+            let out_points = cells
+                .iter()
+                .map(|cell| cell.out_point.clone())
+                .collect::<Vec<_>>();
+            lock_cells(
+                cell_collector,
+                self.sender_udt_query(type_script),
+                &out_points,
+            )?;
+        }
+        for (query, out_point) in receiver_cells {
+            lock_cells(cell_collector, query, &[out_point])?;
+        }
+
+        Ok(TransactionBuilder::default()
+            .set_cell_deps(cell_deps)
+            .set_inputs(inputs)
+            .set_outputs(outputs)
+            .set_outputs_data(outputs_data)
+            .build())
+    }
+}
+
+/// Lock exactly the cells `out_points` found by `query` in the cell collector
+fn lock_cells(
+    cell_collector: &mut dyn CellCollector,
+    mut query: CellQueryOptions,
+    out_points: &[OutPoint],
+) -> Result<(), TxBuilderError> {
+    query.min_total_capacity = u64::MAX;
+    query.excluded_out_points = cell_collector
+        .collect_live_cells(&query, false)?
+        .0
+        .into_iter()
+        .map(|cell| cell.out_point)
+        .filter(|out_point| !out_points.contains(out_point))
+        .collect();
+    let (locked_cells, _) = cell_collector.collect_live_cells(&query, true)?;
+    if locked_cells.len() != out_points.len() {
+        return Err(TxBuilderError::Other(anyhow!(
+            "udt cells changed while building, lock: {:?}, type script: {:?}",
+            query.primary_script,
+            query.secondary_script
+        )));
+    }
+    Ok(())
+}
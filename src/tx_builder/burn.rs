@@ -0,0 +1,102 @@
+use anyhow::anyhow;
+use ckb_types::{
+    core::{TransactionBuilder, TransactionView},
+    packed::{CellInput, OutPoint},
+    prelude::*,
+};
+
+use super::{extend_unique, TxBuilder, TxBuilderError};
+use crate::traits::{
+    CellCollector, CellDepResolver, CellQueryOptions, HeaderDepResolver,
+    TransactionDependencyProvider,
+};
+
+/// The cells to burn
+#[derive(Debug, Clone)]
+pub enum BurnInputs {
+    OutPoints(Vec<OutPoint>),
+    /// All the live cells matched by the query
+    Query(CellQueryOptions),
+}
+
+/// Build a transaction without outputs, the capacity of the inputs is all
+/// burned as fee, e.g. to clean up dust cells or to consume a protocol cell
+/// for its side effect.
+///
+/// The balancer must confirm the burn by `CapacityBalancer::confirm_burn`,
+/// otherwise it creates a change cell or rejects the transaction:
+///
+/// ```ignore
+/// let builder = BurnBuilder::new(BurnInputs::OutPoints(dust_cells), max_burn);
+/// balancer.confirm_burn(max_burn);
+/// let tx = builder.build_balanced(&mut cell_collector, &cell_dep_resolver, &header_dep_resolver, &tx_dep_provider, &balancer, &unlockers)?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct BurnBuilder {
+    pub inputs: BurnInputs,
+    /// The max total capacity of the inputs
+    pub max_burn: u64,
+}
+
+impl BurnBuilder {
+    pub fn new(inputs: BurnInputs, max_burn: u64) -> BurnBuilder {
+        BurnBuilder { inputs, max_burn }
+    }
+}
+
+impl TxBuilder for BurnBuilder {
+    fn build_base(
+        &self,
+        cell_collector: &mut dyn CellCollector,
+        cell_dep_resolver: &dyn CellDepResolver,
+        _header_dep_resolver: &dyn HeaderDepResolver,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, TxBuilderError> {
+        let out_points = match &self.inputs {
+            BurnInputs::OutPoints(out_points) => out_points.clone(),
+            BurnInputs::Query(query) => {
+                let (cells, _) = cell_collector.collect_live_cells(query, true)?;
+                cells.into_iter().map(|cell| cell.out_point).collect()
+            }
+        };
+        if out_points.is_empty() {
+            return Err(TxBuilderError::InvalidParameter(anyhow!(
+                "no cells to burn"
+            )));
+        }
+
+        let mut cell_deps = Vec::new();
+        let mut inputs = Vec::new();
+        let mut total_capacity = 0u64;
+        for out_point in out_points {
+            let cell = tx_dep_provider.get_cell(&out_point)?;
+            let capacity: u64 = cell.capacity().unpack();
+            total_capacity = total_capacity.saturating_add(capacity);
+            for script in Some(cell.lock()).into_iter().chain(cell.type_().to_opt()) {
+                let script_cell_deps = cell_dep_resolver
+                    .resolve_all(&script)
+                    .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(script.clone()))?;
+                extend_unique(&mut cell_deps, script_cell_deps);
+            }
+            let input = CellInput::new(out_point, 0);
+            if inputs.contains(&input) {
+                return Err(TxBuilderError::InvalidParameter(anyhow!(
+                    "duplicated cell to burn: {}",
+                    input.previous_output()
+                )));
+            }
+            inputs.push(input);
+        }
+        if total_capacity > self.max_burn {
+            return Err(TxBuilderError::InvalidParameter(anyhow!(
+                "burning {} shannons exceeds the max burn {}",
+                total_capacity,
+                self.max_burn
+            )));
+        }
+        Ok(TransactionBuilder::default()
+            .set_cell_deps(cell_deps)
+            .set_inputs(inputs)
+            .build())
+    }
+}
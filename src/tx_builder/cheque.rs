@@ -1,20 +1,22 @@
-use std::collections::HashSet;
-
 use anyhow::anyhow;
 use ckb_types::{
     bytes::Bytes,
-    core::{Capacity, ScriptHashType, TransactionBuilder, TransactionView},
+    core::{
+        Capacity, EpochNumberWithFraction, HeaderView, ScriptHashType, TransactionBuilder,
+        TransactionView,
+    },
     packed::{CellInput, CellOutput, OutPoint, Script},
     prelude::*,
 };
 
-use super::{TxBuilder, TxBuilderError};
+use super::{extend_unique, TxBuilder, TxBuilderError};
 use crate::constants::{CHEQUE_CELL_SINCE, SIGHASH_TYPE_HASH};
+use crate::rpc::ckb_indexer::SearchMode;
 use crate::traits::{
-    CellCollector, CellDepResolver, CellQueryOptions, HeaderDepResolver,
+    CellCollector, CellDepResolver, CellQueryOptions, HeaderDepResolver, LiveCell, MaturityOption,
     TransactionDependencyProvider, ValueRangeOption,
 };
-use crate::types::ScriptId;
+use crate::types::{ScriptHashTypeExt, ScriptId, Since};
 
 pub struct ChequeClaimBuilder {
     /// The cheque cells to claim, all cells must have same lock script and same
@@ -57,8 +59,7 @@ impl TxBuilder for ChequeClaimBuilder {
             )));
         }
 
-        #[allow(clippy::mutable_key_type)]
-        let mut cell_deps = HashSet::new();
+        let mut cell_deps = Vec::new();
         let mut inputs = self.inputs.clone();
         inputs.push(self.receiver_input.clone());
 
@@ -69,11 +70,10 @@ impl TxBuilder for ChequeClaimBuilder {
         let receiver_type_script = receiver_input_cell.type_().to_opt().ok_or_else(|| {
             TxBuilderError::InvalidParameter(anyhow!("receiver input missing type script"))
         })?;
-        let receiver_input_lock_cell_dep =
-            cell_dep_resolver
-                .resolve(&receiver_input_cell.lock())
-                .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(receiver_input_cell.lock()))?;
-        cell_deps.insert(receiver_input_lock_cell_dep);
+        let receiver_input_lock_cell_deps = cell_dep_resolver
+            .resolve_all(&receiver_input_cell.lock())
+            .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(receiver_input_cell.lock()))?;
+        extend_unique(&mut cell_deps, receiver_input_lock_cell_deps);
 
         if receiver_input_data.len() != 16 {
             return Err(TxBuilderError::InvalidParameter(anyhow!(
@@ -87,10 +87,10 @@ impl TxBuilder for ChequeClaimBuilder {
             u128::from_le_bytes(amount_bytes)
         };
 
-        let receiver_type_cell_dep = cell_dep_resolver
-            .resolve(&receiver_type_script)
+        let receiver_type_cell_deps = cell_dep_resolver
+            .resolve_all(&receiver_type_script)
             .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(receiver_type_script.clone()))?;
-        cell_deps.insert(receiver_type_cell_dep);
+        extend_unique(&mut cell_deps, receiver_type_cell_deps);
 
         let mut cheque_total_amount = 0;
         let mut cheque_total_capacity = 0;
@@ -133,11 +133,11 @@ impl TxBuilder for ChequeClaimBuilder {
                     "all cheque input lock script must be the same"
                 )));
             }
-            let lock_cell_dep = cell_dep_resolver
-                .resolve(&lock_script)
+            let lock_cell_deps = cell_dep_resolver
+                .resolve_all(&lock_script)
                 .ok_or(TxBuilderError::ResolveCellDepFailed(lock_script))?;
 
-            cell_deps.insert(lock_cell_dep);
+            extend_unique(&mut cell_deps, lock_cell_deps);
             cheque_total_amount += input_amount;
             cheque_total_capacity += input_capacity;
         }
@@ -172,7 +172,7 @@ impl TxBuilder for ChequeClaimBuilder {
         let outputs_data = vec![receiver_output_data.pack(), sender_output_data.pack()];
 
         Ok(TransactionBuilder::default()
-            .set_cell_deps(cell_deps.into_iter().collect())
+            .set_cell_deps(cell_deps)
             .set_inputs(inputs)
             .set_outputs(outputs)
             .set_outputs_data(outputs_data)
@@ -268,11 +268,11 @@ impl TxBuilder for ChequeWithdrawBuilder {
         let cheque_lock_script = last_lock_script.unwrap();
         let type_script = last_type_script.unwrap();
 
-        let cheque_cell_dep = cell_dep_resolver
-            .resolve(&cheque_lock_script)
+        let cheque_cell_deps = cell_dep_resolver
+            .resolve_all(&cheque_lock_script)
             .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(cheque_lock_script.clone()))?;
-        let type_cell_dep = cell_dep_resolver
-            .resolve(&type_script)
+        let type_cell_deps = cell_dep_resolver
+            .resolve_all(&type_script)
             .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(type_script.clone()))?;
 
         let cheque_lock_args = cheque_lock_script.args().raw_data();
@@ -283,7 +283,7 @@ impl TxBuilder for ChequeWithdrawBuilder {
             )));
         }
         if self.sender_lock_script.code_hash() != SIGHASH_TYPE_HASH.pack()
-            || self.sender_lock_script.hash_type() != ScriptHashType::Type.into()
+            || self.sender_lock_script.hash_type() != ScriptHashType::Type.to_packed()
             || self.sender_lock_script.args().raw_data().len() != 20
         {
             return Err(TxBuilderError::InvalidParameter(anyhow!(
@@ -298,17 +298,19 @@ impl TxBuilder for ChequeWithdrawBuilder {
             )));
         }
 
-        let mut cell_deps = vec![cheque_cell_dep, type_cell_dep];
+        let mut cell_deps = cheque_cell_deps;
+        extend_unique(&mut cell_deps, type_cell_deps);
         let (sender_lock, total_capacity, total_amount) =
             if let Some(script_id) = self.acp_script_id.as_ref() {
                 let acp_lock = Script::new_builder()
                     .code_hash(script_id.code_hash.pack())
-                    .hash_type(script_id.hash_type.into())
+                    .hash_type(script_id.hash_type.to_packed())
                     .args(self.sender_lock_script.args())
                     .build();
                 let mut query = CellQueryOptions::new_lock(acp_lock.clone());
                 query.secondary_script = Some(type_script.clone());
                 query.data_len_range = Some(ValueRangeOption::new_min(16));
+                query.allow_unconfirmed = true;
                 let (acp_cells, _) = cell_collector.collect_live_cells(&query, true)?;
                 if acp_cells.is_empty() {
                     return Err(TxBuilderError::Other(anyhow!(
@@ -325,10 +327,10 @@ impl TxBuilder for ChequeWithdrawBuilder {
                     .occupied_capacity(Capacity::bytes(acp_cell.output_data.len()).unwrap())
                     .expect("occupied_capacity")
                     .as_u64();
-                let acp_cell_dep = cell_dep_resolver
-                    .resolve(&acp_lock)
+                let acp_cell_deps = cell_dep_resolver
+                    .resolve_all(&acp_lock)
                     .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(acp_lock.clone()))?;
-                cell_deps.push(acp_cell_dep);
+                extend_unique(&mut cell_deps, acp_cell_deps);
                 inputs.push(CellInput::new(acp_cell.out_point.clone(), 0));
                 (
                     acp_lock,
@@ -354,10 +356,220 @@ impl TxBuilder for ChequeWithdrawBuilder {
         let outputs_data = vec![sender_output_data.pack()];
 
         Ok(TransactionBuilder::default()
-            .set_cell_deps(cell_deps.into_iter().collect())
+            .set_cell_deps(cell_deps)
             .set_inputs(inputs)
             .set_outputs(outputs)
             .set_outputs_data(outputs_data)
             .build())
     }
 }
+
+/// Reclaimed cheque cells with the same type script
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ChequeWithdrawSummary {
+    pub type_script: Script,
+    /// Number of reclaimed cheque cells
+    pub cells: usize,
+    pub capacity: u64,
+    pub amount: u128,
+}
+
+pub struct ChequeBulkWithdrawResult {
+    /// The base transactions, need to be balanced and unlocked
+    pub transactions: Vec<TransactionView>,
+    pub reclaimed: Vec<ChequeWithdrawSummary>,
+    /// Cheque cells not yet past the withdraw window, not included in the transactions
+    pub not_ready: Vec<OutPoint>,
+}
+
+/// Withdraw all the cheque cells of a sender that are past the withdraw
+/// window (6 epochs), the cheque cells are batched into transactions by type
+/// script and `max_inputs_per_tx`.
+pub struct ChequeBulkWithdrawBuilder {
+    /// The cheque lock script id
+    pub cheque_script_id: ScriptId,
+
+    /// Sender's lock script, must be a sighash address, all the cheque cells
+    /// whose lock args end with this script's hash are withdrawn.
+    pub sender_lock_script: Script,
+
+    /// Max number of cheque inputs in one transaction
+    pub max_inputs_per_tx: usize,
+}
+
+impl ChequeBulkWithdrawBuilder {
+    pub fn new(
+        cheque_script_id: ScriptId,
+        sender_lock_script: Script,
+    ) -> ChequeBulkWithdrawBuilder {
+        ChequeBulkWithdrawBuilder {
+            cheque_script_id,
+            sender_lock_script,
+            max_inputs_per_tx: 64,
+        }
+    }
+
+    /// Build the base withdraw transactions at the tip `tip_header`, only the
+    /// withdrawn cheque cells are locked in the cell collector.
+    pub fn build_base_txs(
+        &self,
+        cell_collector: &mut dyn CellCollector,
+        cell_dep_resolver: &dyn CellDepResolver,
+        header_dep_resolver: &dyn HeaderDepResolver,
+        tip_header: &HeaderView,
+    ) -> Result<ChequeBulkWithdrawResult, TxBuilderError> {
+        if self.max_inputs_per_tx == 0 {
+            return Err(TxBuilderError::InvalidParameter(anyhow!(
+                "max_inputs_per_tx must be greater than 0"
+            )));
+        }
+        if self.sender_lock_script.code_hash() != SIGHASH_TYPE_HASH.pack()
+            || self.sender_lock_script.hash_type() != ScriptHashType::Type.to_packed()
+            || self.sender_lock_script.args().raw_data().len() != 20
+        {
+            return Err(TxBuilderError::InvalidParameter(anyhow!(
+                "invalid sender lock script, expected: sighash address, got: {:?}",
+                self.sender_lock_script
+            )));
+        }
+        let sender_lock_hash = self.sender_lock_script.calc_script_hash();
+        let sender_lock_hash_prefix = &sender_lock_hash.as_slice()[0..20];
+
+        let query_script = Script::new_builder()
+            .code_hash(self.cheque_script_id.code_hash.pack())
+            .hash_type(self.cheque_script_id.hash_type.to_packed())
+            .args(Bytes::from(sender_lock_hash_prefix.to_vec()).pack())
+            .build();
+        let mut query = CellQueryOptions::new_lock(query_script);
+        query.script_search_mode = Some(SearchMode::Partial);
+        query.data_len_range = Some(ValueRangeOption::new_exact(16));
+        query.maturity = MaturityOption::Both;
+        query.min_total_capacity = u64::MAX;
+        let (cells, _) = cell_collector.collect_live_cells(&query, false)?;
+
+        // type script => cheque cells
+        let mut groups: Vec<(Script, Vec<LiveCell>)> = Vec::new();
+        let mut not_ready = Vec::new();
+        for cell in cells {
+            let lock_args = cell.output.lock().args().raw_data();
+            let type_script = match cell.output.type_().to_opt() {
+                Some(type_script) => type_script,
+                None => continue,
+            };
+            // the partial search may match the receiver part of the args
+            if lock_args.len() != 40 || &lock_args[20..40] != sender_lock_hash_prefix {
+                continue;
+            }
+            let header = header_dep_resolver
+                .resolve_by_tx(&cell.out_point.tx_hash())
+                .map_err(TxBuilderError::Other)?
+                .ok_or_else(|| {
+                    TxBuilderError::ResolveHeaderDepByTxHashFailed(cell.out_point.tx_hash())
+                })?;
+            if !is_past_withdraw_window(header.epoch(), tip_header.epoch()) {
+                not_ready.push(cell.out_point);
+                continue;
+            }
+            match groups.iter_mut().find(|(script, _)| script == &type_script) {
+                Some((_, group_cells)) => group_cells.push(cell),
+                None => groups.push((type_script, vec![cell])),
+            }
+        }
+
+        let mut transactions = Vec::new();
+        let mut reclaimed = Vec::new();
+        for (type_script, group_cells) in groups {
+            let type_cell_deps = cell_dep_resolver
+                .resolve_all(&type_script)
+                .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(type_script.clone()))?;
+            let mut summary = ChequeWithdrawSummary {
+                type_script: type_script.clone(),
+                cells: 0,
+                capacity: 0,
+                amount: 0,
+            };
+            for chunk in group_cells.chunks(self.max_inputs_per_tx) {
+                let mut cell_deps = type_cell_deps.clone();
+                let mut inputs = Vec::with_capacity(chunk.len());
+                let mut total_capacity: u64 = 0;
+                let mut total_amount: u128 = 0;
+                for cell in chunk {
+                    let lock_script = cell.output.lock();
+                    let lock_cell_deps = cell_dep_resolver
+                        .resolve_all(&lock_script)
+                        .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(lock_script))?;
+                    extend_unique(&mut cell_deps, lock_cell_deps);
+                    let mut amount_bytes = [0u8; 16];
+                    amount_bytes.copy_from_slice(cell.output_data.as_ref());
+                    let capacity: u64 = cell.output.capacity().unpack();
+                    let amount = u128::from_le_bytes(amount_bytes);
+                    total_capacity += capacity;
+                    total_amount = total_amount
+                        .checked_add(amount)
+                        .ok_or(TxBuilderError::AmountOverflow(total_amount, amount))?;
+                    inputs.push(CellInput::new(cell.out_point.clone(), CHEQUE_CELL_SINCE));
+                }
+                let sender_output = CellOutput::new_builder()
+                    .lock(self.sender_lock_script.clone())
+                    .type_(Some(type_script.clone()).pack())
+                    .capacity(total_capacity.pack())
+                    .build();
+                let sender_output_data = Bytes::from(total_amount.to_le_bytes().to_vec());
+                transactions.push(
+                    TransactionBuilder::default()
+                        .set_cell_deps(cell_deps)
+                        .set_inputs(inputs)
+                        .set_outputs(vec![sender_output])
+                        .set_outputs_data(vec![sender_output_data.pack()])
+                        .build(),
+                );
+                summary.cells += chunk.len();
+                summary.capacity += total_capacity;
+                summary.amount = summary
+                    .amount
+                    .checked_add(total_amount)
+                    .ok_or(TxBuilderError::AmountOverflow(summary.amount, total_amount))?;
+            }
+            reclaimed.push(summary);
+        }
+        for tx in &transactions {
+            for out_point in tx.input_pts_iter() {
+                cell_collector.lock_cell(out_point, tip_header.number())?;
+            }
+        }
+        Ok(ChequeBulkWithdrawResult {
+            transactions,
+            reclaimed,
+            not_ready,
+        })
+    }
+}
+
+/// Check if a cheque cell created at `created` can be withdrawn at `current`,
+/// the relative epoch since of `CHEQUE_CELL_SINCE` must be satisfied.
+fn is_past_withdraw_window(
+    created: EpochNumberWithFraction,
+    current: EpochNumberWithFraction,
+) -> bool {
+    let withdraw_epochs = Since::from_raw_value(CHEQUE_CELL_SINCE)
+        .extract_metric()
+        .map(|(_, value)| EpochNumberWithFraction::from_full_value(value).number())
+        .expect("cheque since");
+    // compare (created + withdraw_epochs) <= current as fractions
+    let fraction = |epoch: &EpochNumberWithFraction| {
+        if epoch.length() == 0 {
+            (epoch.number() as u128, 0, 1)
+        } else {
+            (
+                epoch.number() as u128,
+                epoch.index() as u128,
+                epoch.length() as u128,
+            )
+        }
+    };
+    let (created_number, created_index, created_length) = fraction(&created);
+    let (current_number, current_index, current_length) = fraction(&current);
+    (created_number + withdraw_epochs as u128) * created_length * current_length
+        + created_index * current_length
+        <= current_number * created_length * current_length + current_index * created_length
+}
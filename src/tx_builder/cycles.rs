@@ -0,0 +1,190 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use ckb_chain_spec::consensus::{Consensus, ConsensusBuilder};
+use ckb_mock_tx_types::{MockResourceLoader, MockTransaction, Resource};
+use ckb_script::{ScriptError, TransactionScriptsVerifier, TxVerifyEnv};
+use ckb_types::{
+    bytes::Bytes,
+    core::{
+        cell::resolve_transaction,
+        hardfork::{HardForks, CKB2021, CKB2023},
+        Cycle, HeaderBuilder, HeaderView, TransactionView,
+    },
+    packed::{Byte32, CellOutput, OutPoint},
+    prelude::*,
+    H256,
+};
+use thiserror::Error;
+
+use crate::mock_tx::{build_mock_transaction, MockTxError};
+use crate::traits::{HeaderDepResolver, TransactionDependencyProvider};
+use crate::types::ScriptGroupType;
+
+#[derive(Error, Debug)]
+pub enum VerifyCyclesError {
+    #[error("mock transaction error: `{0}`")]
+    MockTx(#[from] MockTxError),
+
+    #[error("resolve transaction error: `{0}`")]
+    Resolve(String),
+
+    #[error("{group_type:?} script group `{script_hash:#x}` failed: `{error}`")]
+    Script {
+        group_type: ScriptGroupType,
+        script_hash: H256,
+        error: ScriptError,
+    },
+}
+
+/// The cycles consumed by a script group
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct GroupCycles {
+    pub script_hash: H256,
+    pub group_type: ScriptGroupType,
+    pub cycles: Cycle,
+}
+
+/// The cycles consumed by a transaction
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct CycleReport {
+    pub total: Cycle,
+    /// Lock script groups first, then type script groups, each sorted by
+    /// script hash
+    pub groups: Vec<GroupCycles>,
+}
+
+/// A consensus with all the hard forks activated from the genesis, the
+/// scripts are verified by the latest rules.
+pub fn latest_consensus() -> Consensus {
+    ConsensusBuilder::default()
+        .hardfork_switch(HardForks {
+            ckb2021: CKB2021::new_dev_default(),
+            ckb2023: CKB2023::new_dev_default(),
+        })
+        .build()
+}
+
+/// Run every script group of the transaction in ckb-vm, the same as the
+/// node does before the transaction is accepted by the pool.
+///
+/// The cells and headers the transaction depends on are loaded by
+/// `tx_dep_provider`, `header_dep_resolver` finds the block headers of the
+/// inputs (e.g. for a dao withdraw), a header it does not know is skipped.
+///
+/// The groups share `max_cycles`: a group fails with
+/// `ScriptError::ExceededMaximumCycles` when the groups before it and itself
+/// consume more than `max_cycles` in total.
+pub fn verify_cycles(
+    tx: &TransactionView,
+    tx_dep_provider: &dyn TransactionDependencyProvider,
+    header_dep_resolver: &dyn HeaderDepResolver,
+    consensus: Arc<Consensus>,
+    max_cycles: Cycle,
+) -> Result<CycleReport, VerifyCyclesError> {
+    let mock_tx = build_mock_transaction(tx, tx_dep_provider, header_dep_resolver)?;
+    verify_groups(
+        &MockTransaction::from(mock_tx),
+        consensus,
+        max_cycles,
+        false,
+    )
+}
+
+/// Same as `verify_cycles`, the cells and headers are in the mock transaction.
+pub fn verify_mock_tx_cycles(
+    mock_tx: &MockTransaction,
+    consensus: Arc<Consensus>,
+    max_cycles: Cycle,
+) -> Result<CycleReport, VerifyCyclesError> {
+    verify_groups(mock_tx, consensus, max_cycles, false)
+}
+
+/// Only run the type script groups, the lock scripts of a transaction not
+/// signed yet would fail.
+pub(crate) fn verify_type_script_cycles(
+    tx: &TransactionView,
+    tx_dep_provider: &dyn TransactionDependencyProvider,
+    header_dep_resolver: &dyn HeaderDepResolver,
+    max_cycles: Cycle,
+) -> Result<CycleReport, VerifyCyclesError> {
+    let mock_tx = build_mock_transaction(tx, tx_dep_provider, header_dep_resolver)?;
+    verify_groups(
+        &MockTransaction::from(mock_tx),
+        Arc::new(latest_consensus()),
+        max_cycles,
+        true,
+    )
+}
+
+fn verify_groups(
+    mock_tx: &MockTransaction,
+    consensus: Arc<Consensus>,
+    max_cycles: Cycle,
+    type_scripts_only: bool,
+) -> Result<CycleReport, VerifyCyclesError> {
+    let resource = Resource::from_both(mock_tx, &mut CompleteMockLoader)
+        .map_err(VerifyCyclesError::Resolve)?;
+    let rtx = resolve_transaction(
+        mock_tx.tx.clone().into_view(),
+        &mut HashSet::new(),
+        &resource,
+        &resource,
+    )
+    .map_err(|err| VerifyCyclesError::Resolve(format!("{:?}", err)))?;
+    let tip = HeaderBuilder::default().number(0.pack()).build();
+    let mut verifier = TransactionScriptsVerifier::new(
+        Arc::new(rtx),
+        resource,
+        consensus,
+        Arc::new(TxVerifyEnv::new_submit(&tip)),
+    );
+    verifier.set_debug_printer(|script_hash, message| {
+        log::debug!("script: {:x}, debug: {}", script_hash, message);
+    });
+
+    let groups: Vec<(ckb_script::ScriptGroupType, Byte32)> = verifier
+        .groups_with_type()
+        .map(|(group_type, script_hash, _)| (group_type, script_hash.clone()))
+        .collect();
+    let mut report = CycleReport::default();
+    for (vm_group_type, script_hash) in groups {
+        let group_type = match vm_group_type {
+            ckb_script::ScriptGroupType::Lock => ScriptGroupType::Lock,
+            ckb_script::ScriptGroupType::Type => ScriptGroupType::Type,
+        };
+        if type_scripts_only && group_type == ScriptGroupType::Lock {
+            continue;
+        }
+        let cycles = verifier
+            .verify_single(vm_group_type, &script_hash, max_cycles - report.total)
+            .map_err(|error| VerifyCyclesError::Script {
+                group_type,
+                script_hash: script_hash.unpack(),
+                error,
+            })?;
+        report.total += cycles;
+        report.groups.push(GroupCycles {
+            script_hash: script_hash.unpack(),
+            group_type,
+            cycles,
+        });
+    }
+    Ok(report)
+}
+
+/// The mock transaction already has all the cells and headers.
+struct CompleteMockLoader;
+
+impl MockResourceLoader for CompleteMockLoader {
+    fn get_header(&mut self, hash: H256) -> Result<Option<HeaderView>, String> {
+        Err(format!("header not in the mock transaction: {:#x}", hash))
+    }
+
+    fn get_live_cell(
+        &mut self,
+        out_point: OutPoint,
+    ) -> Result<Option<(CellOutput, Bytes, Option<Byte32>)>, String> {
+        Err(format!("cell not in the mock transaction: {}", out_point))
+    }
+}
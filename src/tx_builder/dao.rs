@@ -1,5 +1,3 @@
-use std::collections::HashSet;
-
 use anyhow::anyhow;
 use ckb_types::{
     bytes::Bytes,
@@ -8,13 +6,13 @@ use ckb_types::{
     prelude::*,
 };
 
-use super::{TxBuilder, TxBuilderError};
+use super::{extend_unique, push_unique, TxBuilder, TxBuilderError};
 use crate::constants::DAO_TYPE_HASH;
 use crate::traits::{
     CellCollector, CellDepResolver, HeaderDepResolver, TransactionDependencyProvider,
 };
-use crate::types::{Since, SinceType};
-use crate::util::{calculate_dao_maximum_withdraw4, minimal_unlock_point};
+use crate::types::{ScriptHashTypeExt, Since};
+use crate::util::{calc_fee, calculate_dao_maximum_withdraw4, minimal_unlock_point, tx_size};
 
 /// Deposit target
 #[derive(Debug, Clone)]
@@ -59,10 +57,10 @@ impl TxBuilder for DaoDepositBuilder {
         }
         let dao_type_script = Script::new_builder()
             .code_hash(DAO_TYPE_HASH.pack())
-            .hash_type(ScriptHashType::Type.into())
+            .hash_type(ScriptHashType::Type.to_packed())
             .build();
-        let dao_cell_dep = cell_dep_resolver
-            .resolve(&dao_type_script)
+        let dao_cell_deps = cell_dep_resolver
+            .resolve_all(&dao_type_script)
             .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(dao_type_script.clone()))?;
 
         let mut outputs = Vec::new();
@@ -77,7 +75,7 @@ impl TxBuilder for DaoDepositBuilder {
             outputs_data.push(Bytes::from(vec![0u8; 8]).pack());
         }
         Ok(TransactionBuilder::default()
-            .set_cell_deps(vec![dao_cell_dep])
+            .set_cell_deps(dao_cell_deps)
             .set_outputs(outputs)
             .set_outputs_data(outputs_data)
             .build())
@@ -136,14 +134,13 @@ impl TxBuilder for DaoPrepareBuilder {
 
         let dao_type_script = Script::new_builder()
             .code_hash(DAO_TYPE_HASH.pack())
-            .hash_type(ScriptHashType::Type.into())
+            .hash_type(ScriptHashType::Type.to_packed())
             .build();
-        let dao_cell_dep = cell_dep_resolver
-            .resolve(&dao_type_script)
+        let dao_cell_deps = cell_dep_resolver
+            .resolve_all(&dao_type_script)
             .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(dao_type_script.clone()))?;
-        #[allow(clippy::mutable_key_type)]
-        let mut cell_deps = HashSet::new();
-        cell_deps.insert(dao_cell_dep);
+        let mut cell_deps = Vec::new();
+        extend_unique(&mut cell_deps, dao_cell_deps);
 
         let mut header_deps = Vec::new();
         let mut inputs = Vec::new();
@@ -162,8 +159,8 @@ impl TxBuilder for DaoPrepareBuilder {
                     "the input cell has invalid type script"
                 )));
             }
-            let input_lock_cell_dep = cell_dep_resolver
-                .resolve(&input_cell.lock())
+            let input_lock_cell_deps = cell_dep_resolver
+                .resolve_all(&input_cell.lock())
                 .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(input_cell.lock()))?;
             let output = {
                 let mut builder = input_cell.as_builder();
@@ -174,14 +171,14 @@ impl TxBuilder for DaoPrepareBuilder {
             };
             let output_data = Bytes::from(deposit_header.number().to_le_bytes().to_vec());
 
-            cell_deps.insert(input_lock_cell_dep);
+            extend_unique(&mut cell_deps, input_lock_cell_deps);
             header_deps.push(deposit_header.hash());
             inputs.push(input.clone());
             outputs.push(output);
             outputs_data.push(output_data.pack());
         }
         Ok(TransactionBuilder::default()
-            .set_cell_deps(cell_deps.into_iter().collect())
+            .set_cell_deps(cell_deps)
             .set_header_deps(header_deps)
             .set_inputs(inputs)
             .set_outputs(outputs)
@@ -255,14 +252,13 @@ impl TxBuilder for DaoWithdrawBuilder {
 
         let dao_type_script = Script::new_builder()
             .code_hash(DAO_TYPE_HASH.pack())
-            .hash_type(ScriptHashType::Type.into())
+            .hash_type(ScriptHashType::Type.to_packed())
             .build();
-        let dao_cell_dep = cell_dep_resolver
-            .resolve(&dao_type_script)
+        let dao_cell_deps = cell_dep_resolver
+            .resolve_all(&dao_type_script)
             .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(dao_type_script.clone()))?;
-        #[allow(clippy::mutable_key_type)]
-        let mut cell_deps = HashSet::new();
-        cell_deps.insert(dao_cell_dep);
+        let mut cell_deps = Vec::new();
+        extend_unique(&mut cell_deps, dao_cell_deps);
 
         let mut header_deps = Vec::new();
         let mut prepare_block_hashes = Vec::new();
@@ -286,8 +282,8 @@ impl TxBuilder for DaoWithdrawBuilder {
                     "the input cell has invalid type script"
                 )));
             }
-            let input_lock_cell_dep = cell_dep_resolver
-                .resolve(&input_cell.lock())
+            let input_lock_cell_deps = cell_dep_resolver
+                .resolve_all(&input_cell.lock())
                 .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(input_cell.lock()))?;
             let data = tx_dep_provider.get_cell_data(out_point)?;
             if data.len() != 8 {
@@ -318,11 +314,7 @@ impl TxBuilder for DaoWithdrawBuilder {
                 ))?;
             let input = {
                 let unlock_point = minimal_unlock_point(&deposit_header, &prepare_header);
-                let since = Since::new(
-                    SinceType::EpochNumberWithFraction,
-                    unlock_point.full_value(),
-                    false,
-                );
+                let since = Since::absolute_epoch(unlock_point);
                 CellInput::new(out_point.clone(), since.value())
             };
             let deposit_block_hash = deposit_header.hash();
@@ -351,14 +343,16 @@ impl TxBuilder for DaoWithdrawBuilder {
             );
             input_total += input_capacity;
 
-            cell_deps.insert(input_lock_cell_dep);
+            extend_unique(&mut cell_deps, input_lock_cell_deps);
             if header_idx == header_deps.len() {
                 header_deps.push(deposit_block_hash);
             }
             inputs.push(input);
             witnesses.push(witness.pack());
         }
-        header_deps.extend(prepare_block_hashes.into_iter().collect::<HashSet<_>>());
+        for prepare_block_hash in prepare_block_hashes {
+            push_unique(&mut header_deps, prepare_block_hash);
+        }
 
         let (outputs, outputs_data) = match &self.receiver {
             DaoWithdrawReceiver::LockScript { script, fee_rate } => {
@@ -369,15 +363,14 @@ impl TxBuilder for DaoWithdrawBuilder {
                     .as_u64();
                 let capacity = if let Some(fee_rate) = fee_rate {
                     let tmp_tx = TransactionBuilder::default()
-                        .set_cell_deps(cell_deps.clone().into_iter().collect())
+                        .set_cell_deps(cell_deps.clone())
                         .set_header_deps(header_deps.clone())
                         .set_inputs(inputs.clone())
                         .set_outputs(vec![tmp_output.clone()])
                         .set_outputs_data(vec![Bytes::new().pack()])
                         .set_witnesses(witnesses.clone())
                         .build();
-                    let tx_size = tmp_tx.data().as_reader().serialized_size_in_block();
-                    let tx_fee = fee_rate.fee(tx_size as u64).as_u64();
+                    let tx_fee = calc_fee(tx_size(&tmp_tx), *fee_rate);
                     input_total - tx_fee
                 } else {
                     input_total
@@ -408,7 +401,7 @@ impl TxBuilder for DaoWithdrawBuilder {
         };
 
         Ok(TransactionBuilder::default()
-            .set_cell_deps(cell_deps.into_iter().collect())
+            .set_cell_deps(cell_deps)
             .set_header_deps(header_deps)
             .set_inputs(inputs)
             .set_outputs(outputs)
@@ -0,0 +1,290 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use ckb_types::{
+    core::{Cycle, TransactionView},
+    prelude::*,
+    H256,
+};
+use serde_derive::{Deserialize, Serialize};
+
+use super::cycles::{latest_consensus, verify_cycles, VerifyCyclesError};
+use super::gen_script_groups;
+use crate::traits::{HeaderDepResolver, TransactionDependencyError, TransactionDependencyProvider};
+use crate::types::ScriptGroupType;
+
+/// The molecule headers of `Transaction` (12 bytes) and `RawTransaction` (28
+/// bytes), plus the `version` field (4 bytes).
+const TX_OVERHEAD_SIZE: usize = 44;
+
+/// The bytes (and optionally cycles) a script group contributes to a transaction
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct GroupFootprint {
+    pub script_hash: H256,
+    pub group_type: ScriptGroupType,
+    /// Serialized size of the witnesses at the group's input indices, or at
+    /// the output indices for a type script group only in outputs. The lock
+    /// script groups together never count a witness twice.
+    pub witness_bytes: usize,
+    /// Cycles consumed by the group, only set after `TxFootprint::measure_cycles`
+    pub cycles: Option<u64>,
+}
+
+/// Per section byte counts of a transaction.
+///
+/// The sections sum to the serialized size of the transaction:
+/// `overhead + cell_deps + header_deps + inputs + outputs + outputs_data + witnesses == total`
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TxFootprint {
+    /// Serialized size of the transaction (`Transaction` molecule)
+    pub total: usize,
+    /// Molecule table headers and the version field
+    pub overhead: usize,
+    pub cell_deps: usize,
+    pub header_deps: usize,
+    pub inputs: usize,
+    pub outputs: usize,
+    pub outputs_data: usize,
+    pub witnesses: usize,
+    /// Lock script groups first, then type script groups, each sorted by script hash
+    pub groups: Vec<GroupFootprint>,
+}
+
+impl TxFootprint {
+    pub fn analyze(
+        tx: &TransactionView,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TxFootprint, TransactionDependencyError> {
+        let raw = tx.data().raw();
+        let witnesses = tx.witnesses();
+        let witness_size = |indices: &[usize]| -> usize {
+            indices
+                .iter()
+                .filter_map(|idx| witnesses.get(*idx))
+                .map(|witness| witness.as_slice().len())
+                .sum()
+        };
+
+        let script_groups = gen_script_groups(tx, tx_dep_provider)?;
+        let mut groups = Vec::new();
+        for (group_type, type_groups) in [
+            (ScriptGroupType::Lock, script_groups.lock_groups),
+            (ScriptGroupType::Type, script_groups.type_groups),
+        ] {
+            let sorted: BTreeMap<H256, _> = type_groups
+                .into_iter()
+                .map(|(script_hash, group)| (script_hash.unpack(), group))
+                .collect();
+            for (script_hash, group) in sorted {
+                let indices = if group.input_indices.is_empty() {
+                    &group.output_indices
+                } else {
+                    &group.input_indices
+                };
+                groups.push(GroupFootprint {
+                    script_hash,
+                    group_type,
+                    witness_bytes: witness_size(indices),
+                    cycles: None,
+                });
+            }
+        }
+
+        Ok(TxFootprint {
+            total: tx.data().as_slice().len(),
+            overhead: TX_OVERHEAD_SIZE,
+            cell_deps: raw.cell_deps().as_slice().len(),
+            header_deps: raw.header_deps().as_slice().len(),
+            inputs: raw.inputs().as_slice().len(),
+            outputs: raw.outputs().as_slice().len(),
+            outputs_data: raw.outputs_data().as_slice().len(),
+            witnesses: witnesses.as_slice().len(),
+            groups,
+        })
+    }
+
+    /// Run every script group of the transaction in ckb-vm (see
+    /// `cycles::verify_cycles`) and record the consumed cycles in `groups`,
+    /// return the total cycles.
+    pub fn measure_cycles(
+        &mut self,
+        tx: &TransactionView,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
+        header_dep_resolver: &dyn HeaderDepResolver,
+        max_cycles: Cycle,
+    ) -> Result<Cycle, VerifyCyclesError> {
+        let report = verify_cycles(
+            tx,
+            tx_dep_provider,
+            header_dep_resolver,
+            Arc::new(latest_consensus()),
+            max_cycles,
+        )?;
+        for group in self.groups.iter_mut() {
+            group.cycles = report
+                .groups
+                .iter()
+                .find(|g| g.script_hash == group.script_hash && g.group_type == group.group_type)
+                .map(|g| g.cycles);
+        }
+        Ok(report.total)
+    }
+
+    /// Compare to the footprint of another version of the transaction, the
+    /// deltas are `other - self`.
+    pub fn diff(&self, other: &TxFootprint) -> TxFootprintDiff {
+        let delta = |before: usize, after: usize| after as i64 - before as i64;
+        let mut groups = Vec::new();
+        for group in &self.groups {
+            let after = other.find_group(group);
+            groups.push(GroupFootprintDiff {
+                script_hash: group.script_hash.clone(),
+                group_type: group.group_type,
+                witness_bytes: delta(
+                    group.witness_bytes,
+                    after.map(|g| g.witness_bytes).unwrap_or_default(),
+                ),
+                cycles: match (group.cycles, after.and_then(|g| g.cycles)) {
+                    (Some(before), Some(after)) => Some(after as i64 - before as i64),
+                    _ => None,
+                },
+            });
+        }
+        for group in &other.groups {
+            if self.find_group(group).is_none() {
+                groups.push(GroupFootprintDiff {
+                    script_hash: group.script_hash.clone(),
+                    group_type: group.group_type,
+                    witness_bytes: group.witness_bytes as i64,
+                    cycles: None,
+                });
+            }
+        }
+        TxFootprintDiff {
+            total: delta(self.total, other.total),
+            overhead: delta(self.overhead, other.overhead),
+            cell_deps: delta(self.cell_deps, other.cell_deps),
+            header_deps: delta(self.header_deps, other.header_deps),
+            inputs: delta(self.inputs, other.inputs),
+            outputs: delta(self.outputs, other.outputs),
+            outputs_data: delta(self.outputs_data, other.outputs_data),
+            witnesses: delta(self.witnesses, other.witnesses),
+            groups,
+        }
+    }
+
+    fn sections(&self) -> [(&'static str, usize); 7] {
+        [
+            ("overhead", self.overhead),
+            ("cell_deps", self.cell_deps),
+            ("header_deps", self.header_deps),
+            ("inputs", self.inputs),
+            ("outputs", self.outputs),
+            ("outputs_data", self.outputs_data),
+            ("witnesses", self.witnesses),
+        ]
+    }
+
+    fn find_group(&self, group: &GroupFootprint) -> Option<&GroupFootprint> {
+        self.groups
+            .iter()
+            .find(|g| g.script_hash == group.script_hash && g.group_type == group.group_type)
+    }
+}
+
+impl fmt::Display for TxFootprint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:<14}{:>10}", "section", "bytes")?;
+        for (name, bytes) in self.sections() {
+            writeln!(f, "{:<14}{:>10}", name, bytes)?;
+        }
+        write!(f, "{:<14}{:>10}", "total", self.total)?;
+        if !self.groups.is_empty() {
+            write!(
+                f,
+                "\n\n{:<68}{:>6}{:>16}{:>14}",
+                "group", "type", "witness_bytes", "cycles"
+            )?;
+            for group in &self.groups {
+                let cycles = group
+                    .cycles
+                    .map(|cycles| cycles.to_string())
+                    .unwrap_or_else(|| "-".to_string());
+                write!(
+                    f,
+                    "\n{:<68}{:>6}{:>16}{:>14}",
+                    format!("{:#x}", group.script_hash),
+                    group.group_type.to_string(),
+                    group.witness_bytes,
+                    cycles
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The witness bytes and cycles changes of a script group
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct GroupFootprintDiff {
+    pub script_hash: H256,
+    pub group_type: ScriptGroupType,
+    pub witness_bytes: i64,
+    /// Only set when the cycles of both sides are measured
+    pub cycles: Option<i64>,
+}
+
+/// The changes between two `TxFootprint`s, see `TxFootprint::diff`
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TxFootprintDiff {
+    pub total: i64,
+    pub overhead: i64,
+    pub cell_deps: i64,
+    pub header_deps: i64,
+    pub inputs: i64,
+    pub outputs: i64,
+    pub outputs_data: i64,
+    pub witnesses: i64,
+    pub groups: Vec<GroupFootprintDiff>,
+}
+
+impl fmt::Display for TxFootprintDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:<14}{:>10}", "section", "bytes")?;
+        for (name, bytes) in [
+            ("overhead", self.overhead),
+            ("cell_deps", self.cell_deps),
+            ("header_deps", self.header_deps),
+            ("inputs", self.inputs),
+            ("outputs", self.outputs),
+            ("outputs_data", self.outputs_data),
+            ("witnesses", self.witnesses),
+        ] {
+            writeln!(f, "{:<14}{:>+10}", name, bytes)?;
+        }
+        write!(f, "{:<14}{:>+10}", "total", self.total)?;
+        if !self.groups.is_empty() {
+            write!(
+                f,
+                "\n\n{:<68}{:>6}{:>16}{:>14}",
+                "group", "type", "witness_bytes", "cycles"
+            )?;
+            for group in &self.groups {
+                let cycles = group
+                    .cycles
+                    .map(|cycles| format!("{:+}", cycles))
+                    .unwrap_or_else(|| "-".to_string());
+                write!(
+                    f,
+                    "\n{:<68}{:>6}{:>+16}{:>14}",
+                    format!("{:#x}", group.script_hash),
+                    group.group_type.to_string(),
+                    group.witness_bytes,
+                    cycles
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+use anyhow::anyhow;
+use ckb_types::{
+    bytes::Bytes,
+    core::{Capacity, TransactionBuilder, TransactionView},
+    packed::{CellInput, CellOutput, OutPoint, Script, WitnessArgs},
+    prelude::*,
+};
+
+use super::{
+    extend_unique,
+    udt::{UdtTargetReceiver, UdtTransferBuilder},
+    TransferAction, TxBuilder, TxBuilderError,
+};
+use crate::traits::{
+    CellCollector, CellDepResolver, HeaderDepResolver, TransactionDependencyProvider,
+};
+use crate::types::{ScriptId, Since};
+use crate::unlock::hashlock::{HashlockArgs, HashlockWitness};
+
+/// The funds locked in a hashlock cell
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum HashlockFunds {
+    /// Plain capacity in shannons
+    Capacity(u64),
+    /// An udt amount, the udt cells of the owner are spent by `UdtTransferBuilder`
+    Udt {
+        type_script: Script,
+        amount: u128,
+        /// The capacity of the hashlock cell
+        capacity: u64,
+    },
+}
+
+/// Build the transactions of a hashlock (HTLC style) swap, see
+/// `crate::unlock::hashlock` for the script.
+///
+/// For an atomic swap both parties lock their funds under the same hash, the
+/// party who knows the preimage uses a longer timeout. Redeeming one cell
+/// reveals the preimage in the witness, so the counterparty can redeem the
+/// other cell.
+pub struct HashlockBuilder {
+    /// The script id of the deployed hashlock script, the cell dep must be
+    /// resolvable by the `CellDepResolver`
+    pub script_id: ScriptId,
+    /// The owner of the funds, the funds are refunded to it after the timeout
+    pub owner: Script,
+}
+
+impl HashlockBuilder {
+    pub fn new(script_id: ScriptId, owner: Script) -> HashlockBuilder {
+        HashlockBuilder { script_id, owner }
+    }
+
+    /// Lock `funds` under `hash` (blake2b_256 of the preimage) for
+    /// `counterparty_lock`, the owner can refund after `timeout_since`.
+    pub fn lock(
+        &self,
+        funds: HashlockFunds,
+        hash: [u8; 32],
+        timeout_since: Since,
+        counterparty_lock: &Script,
+    ) -> HashlockLockBuilder {
+        let args = HashlockArgs::new(hash, counterparty_lock, &self.owner, timeout_since);
+        HashlockLockBuilder {
+            lock_script: args.build_script(&self.script_id),
+            owner: self.owner.clone(),
+            funds,
+        }
+    }
+}
+
+/// Create the hashlock cell
+pub struct HashlockLockBuilder {
+    /// The hashlock script of the new cell
+    pub lock_script: Script,
+    pub owner: Script,
+    pub funds: HashlockFunds,
+}
+
+impl TxBuilder for HashlockLockBuilder {
+    fn build_base(
+        &self,
+        cell_collector: &mut dyn CellCollector,
+        cell_dep_resolver: &dyn CellDepResolver,
+        header_dep_resolver: &dyn HeaderDepResolver,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, TxBuilderError> {
+        match &self.funds {
+            HashlockFunds::Capacity(capacity) => {
+                let output = CellOutput::new_builder()
+                    .lock(self.lock_script.clone())
+                    .capacity(capacity.pack())
+                    .build();
+                let occupied_capacity = output
+                    .occupied_capacity(Capacity::zero())
+                    .expect("occupied capacity")
+                    .as_u64();
+                if occupied_capacity > *capacity {
+                    return Err(TxBuilderError::InvalidParameter(anyhow!(
+                        "hashlock cell capacity {} is less than the occupied capacity {}",
+                        capacity,
+                        occupied_capacity
+                    )));
+                }
+                Ok(TransactionBuilder::default()
+                    .output(output)
+                    .output_data(Bytes::new().pack())
+                    .build())
+            }
+            HashlockFunds::Udt {
+                type_script,
+                amount,
+                capacity,
+            } => {
+                let mut receiver = UdtTargetReceiver::new(
+                    TransferAction::Create,
+                    self.lock_script.clone(),
+                    *amount,
+                );
+                receiver.capacity = Some(*capacity);
+                let builder = UdtTransferBuilder {
+                    type_script: type_script.clone(),
+                    sender: self.owner.clone(),
+                    receivers: vec![receiver],
+                    data_validator: None,
+                };
+                builder.build_base(
+                    cell_collector,
+                    cell_dep_resolver,
+                    header_dep_resolver,
+                    tx_dep_provider,
+                )
+            }
+        }
+    }
+}
+
+/// Spend a hashlock cell, the cell (with its type script and data) is moved to
+/// the receiver lock as it is.
+///
+/// The hashlock script requires an input of the receiver lock, so the capacity
+/// provider of the balancer must be the receiver lock, it also pays the fee.
+pub struct HashlockSpendBuilder {
+    pub hashlock_cell: OutPoint,
+    /// The witness lock field of the hashlock cell
+    pub witness: HashlockWitness,
+    /// The recipient lock on redeem, the refund lock on refund
+    pub receiver: Script,
+}
+
+impl HashlockSpendBuilder {
+    /// Redeem the hashlock cell to the recipient with `preimage`
+    pub fn redeem(
+        hashlock_cell: OutPoint,
+        preimage: Bytes,
+        recipient_lock: Script,
+    ) -> HashlockSpendBuilder {
+        HashlockSpendBuilder {
+            hashlock_cell,
+            witness: HashlockWitness::Redeem(preimage),
+            receiver: recipient_lock,
+        }
+    }
+
+    /// Refund the hashlock cell to the refund lock, the since of the input is
+    /// the timeout since of the hashlock args.
+    pub fn refund(hashlock_cell: OutPoint, refund_lock: Script) -> HashlockSpendBuilder {
+        HashlockSpendBuilder {
+            hashlock_cell,
+            witness: HashlockWitness::Refund,
+            receiver: refund_lock,
+        }
+    }
+}
+
+impl TxBuilder for HashlockSpendBuilder {
+    fn build_base(
+        &self,
+        _cell_collector: &mut dyn CellCollector,
+        cell_dep_resolver: &dyn CellDepResolver,
+        _header_dep_resolver: &dyn HeaderDepResolver,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, TxBuilderError> {
+        let input_cell = tx_dep_provider.get_cell(&self.hashlock_cell)?;
+        let input_data = tx_dep_provider.get_cell_data(&self.hashlock_cell)?;
+        let hashlock_script = input_cell.lock();
+        let args = HashlockArgs::parse(&hashlock_script.args().raw_data())
+            .map_err(|err| TxBuilderError::InvalidParameter(anyhow!(err)))?;
+
+        let receiver_hash = self.receiver.calc_script_hash();
+        let since = match &self.witness {
+            HashlockWitness::Redeem(preimage) => {
+                if !args.is_preimage(preimage) {
+                    return Err(TxBuilderError::InvalidParameter(anyhow!(
+                        "the preimage does not match the hash of hashlock cell {}",
+                        self.hashlock_cell
+                    )));
+                }
+                if receiver_hash != args.recipient_lock_hash {
+                    return Err(TxBuilderError::InvalidParameter(anyhow!(
+                        "the receiver is not the recipient of hashlock cell {}",
+                        self.hashlock_cell
+                    )));
+                }
+                0
+            }
+            HashlockWitness::Refund => {
+                if receiver_hash != args.refund_lock_hash {
+                    return Err(TxBuilderError::InvalidParameter(anyhow!(
+                        "the receiver is not the refund lock of hashlock cell {}",
+                        self.hashlock_cell
+                    )));
+                }
+                args.timeout_since.value()
+            }
+        };
+
+        let mut cell_deps = Vec::new();
+        let lock_cell_deps = cell_dep_resolver
+            .resolve_all(&hashlock_script)
+            .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(hashlock_script.clone()))?;
+        extend_unique(&mut cell_deps, lock_cell_deps);
+        if let Some(type_script) = input_cell.type_().to_opt() {
+            let type_cell_deps = cell_dep_resolver
+                .resolve_all(&type_script)
+                .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(type_script.clone()))?;
+            extend_unique(&mut cell_deps, type_cell_deps);
+        }
+
+        let output = input_cell.as_builder().lock(self.receiver.clone()).build();
+        let witness = WitnessArgs::new_builder()
+            .lock(Some(self.witness.to_bytes()).pack())
+            .build();
+        Ok(TransactionBuilder::default()
+            .set_cell_deps(cell_deps)
+            .input(CellInput::new(self.hashlock_cell.clone(), since))
+            .output(output)
+            .output_data(input_data.pack())
+            .witness(witness.as_bytes().pack())
+            .build())
+    }
+}
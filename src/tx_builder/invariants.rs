@@ -0,0 +1,179 @@
+use std::collections::HashSet;
+
+use ckb_types::{
+    core::{Capacity, TransactionView},
+    packed::{OutPoint, Script},
+    prelude::*,
+};
+use thiserror::Error;
+
+use super::{gen_script_groups, CapacityBalancer};
+use crate::traits::{TransactionDependencyError, TransactionDependencyProvider};
+use crate::util::{calc_fee, tx_size};
+
+/// An invariant of a balanced transaction that does not hold, see
+/// `check_balanced_invariants`.
+#[derive(Error, Debug)]
+pub enum InvariantViolation {
+    #[error("duplicated input: `{0}`")]
+    DuplicateInput(OutPoint),
+
+    #[error("inputs capacity `{0}` is less than outputs capacity `{1}`")]
+    CapacityNotEnough(u64, u64),
+
+    #[error(
+        "output `{index}` capacity `{capacity}` is less than its occupied capacity `{occupied}`"
+    )]
+    OutputCapacityNotEnough {
+        index: usize,
+        capacity: u64,
+        occupied: u64,
+    },
+
+    #[error("fee `{fee}` does not match the fee `{expected}` at the fee rate")]
+    FeeMismatch { fee: u64, expected: u64 },
+
+    #[error("witnesses count `{0}` does not match the expected count `{1}`")]
+    WitnessCountMismatch(usize, usize),
+
+    #[error("misplaced witness of capacity provider lock at index `{0}`")]
+    MisplacedWitness(usize),
+
+    #[error("change output `{0}` does not use the change lock script")]
+    ChangeLockMismatch(usize),
+
+    #[error("transaction dependency error: `{0}`")]
+    TxDep(String),
+
+    #[error("get fee rate error: `{0}`")]
+    FeeRate(String),
+}
+
+/// Check the invariants of a transaction balanced by `balance_tx_capacity`
+/// with `balancer`, before it is unlocked:
+///
+///   * no duplicated inputs
+///   * every output holds its occupied capacity (with its data)
+///   * the fee is exactly `calc_fee(tx_size(tx), fee_rate)` (rounded up), or
+///     at most `force_small_change_as_fee` when there is no change output
+///   * one witness for every input, plus the reserved trailing witnesses
+///   * in every capacity provider lock group, only the witness of the first
+///     input is set (the placeholder)
+///   * the change output, the last output when it has no type script and no
+///     data and is locked by a capacity provider or the change lock, uses the
+///     change lock script
+///
+/// Inputs with extra capacity (e.g. DAO withdraw) are not supported, and the
+/// fee may be higher after a cycle based rebalance.
+pub fn check_balanced_invariants(
+    tx: &TransactionView,
+    tx_dep_provider: &dyn TransactionDependencyProvider,
+    balancer: &CapacityBalancer,
+) -> Result<(), InvariantViolation> {
+    let tx_dep_error = |err: TransactionDependencyError| InvariantViolation::TxDep(err.to_string());
+
+    #[allow(clippy::mutable_key_type)]
+    let mut out_points = HashSet::new();
+    let mut inputs_capacity = 0u64;
+    for out_point in tx.input_pts_iter() {
+        let cell = tx_dep_provider.get_cell(&out_point).map_err(tx_dep_error)?;
+        let capacity: u64 = cell.capacity().unpack();
+        inputs_capacity = inputs_capacity.saturating_add(capacity);
+        if !out_points.insert(out_point.clone()) {
+            return Err(InvariantViolation::DuplicateInput(out_point));
+        }
+    }
+
+    let mut outputs_capacity = 0u64;
+    for (index, (output, data)) in tx.outputs_with_data_iter().enumerate() {
+        let capacity: u64 = output.capacity().unpack();
+        let occupied = Capacity::bytes(data.len())
+            .and_then(|data_capacity| output.occupied_capacity(data_capacity))
+            .map(|capacity| capacity.as_u64())
+            .unwrap_or(u64::MAX);
+        if capacity < occupied {
+            return Err(InvariantViolation::OutputCapacityNotEnough {
+                index,
+                capacity,
+                occupied,
+            });
+        }
+        outputs_capacity = outputs_capacity.saturating_add(capacity);
+    }
+    if inputs_capacity < outputs_capacity {
+        return Err(InvariantViolation::CapacityNotEnough(
+            inputs_capacity,
+            outputs_capacity,
+        ));
+    }
+
+    let provider_locks: Vec<&Script> = balancer
+        .capacity_provider
+        .lock_scripts
+        .iter()
+        .map(|(script, _, _)| script)
+        .collect();
+
+    let expected_witnesses = tx.inputs().len() + balancer.trailing_witnesses.len();
+    if tx.witnesses().len() != expected_witnesses {
+        return Err(InvariantViolation::WitnessCountMismatch(
+            tx.witnesses().len(),
+            expected_witnesses,
+        ));
+    }
+    let lock_groups = gen_script_groups(tx, tx_dep_provider)
+        .map_err(tx_dep_error)?
+        .lock_groups;
+    let witnesses = tx.witnesses();
+    for group in lock_groups.values() {
+        if !provider_locks.contains(&&group.script) {
+            continue;
+        }
+        for (position, idx) in group.input_indices.iter().enumerate() {
+            let is_empty = witnesses
+                .get(*idx)
+                .map(|witness| witness.raw_data().is_empty())
+                .unwrap_or(true);
+            if is_empty == (position == 0) {
+                return Err(InvariantViolation::MisplacedWitness(*idx));
+            }
+        }
+    }
+
+    let base_change_cell = balancer.base_change_cell();
+    let change_lock = base_change_cell.as_ref().map(|(output, _)| output.lock());
+    let change_index = tx.outputs().len().checked_sub(1).filter(|idx| {
+        let (output, data) = tx.output_with_data(*idx).expect("last output");
+        let is_template = base_change_cell
+            .as_ref()
+            .map(|(change, change_data)| {
+                output.type_() == change.type_()
+                    && &data == change_data
+                    && Some(output.lock()) == change_lock
+            })
+            .unwrap_or(false);
+        is_template
+            || (data.is_empty()
+                && output.type_().to_opt().is_none()
+                && provider_locks.contains(&&output.lock()))
+    });
+    if let Some(idx) = change_index {
+        if Some(tx.output(idx).expect("change output").lock()) != change_lock {
+            return Err(InvariantViolation::ChangeLockMismatch(idx));
+        }
+    }
+
+    let fee_rate = balancer
+        .current_fee_rate()
+        .map_err(|err| InvariantViolation::FeeRate(err.to_string()))?;
+    let expected = calc_fee(tx_size(tx), fee_rate);
+    let fee = inputs_capacity - outputs_capacity;
+    let max_fee = match (change_index, balancer.force_small_change_as_fee) {
+        (None, Some(max_fee)) => max_fee.max(expected),
+        _ => expected,
+    };
+    if fee < expected || fee > max_fee {
+        return Err(InvariantViolation::FeeMismatch { fee, expected });
+    }
+    Ok(())
+}
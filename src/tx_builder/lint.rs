@@ -0,0 +1,416 @@
+use std::collections::HashMap;
+
+use ckb_types::{
+    bytes::Bytes,
+    core::TransactionView,
+    packed::{Byte32, CellOutput, Script},
+    prelude::*,
+};
+
+use crate::traits::{TransactionDependencyError, TransactionDependencyProvider};
+use crate::types::ScriptId;
+use crate::util::is_burn_lock;
+use crate::NetworkType;
+
+/// A problem found in a transaction by a `LintRule`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LintIssue {
+    /// The name of the rule
+    pub rule: &'static str,
+    /// The output the issue is about, `None` if it is about the whole transaction
+    pub output_index: Option<usize>,
+    pub message: String,
+}
+
+/// The transaction to check, with all its input cells resolved
+pub struct LintContext<'a> {
+    pub tx: &'a TransactionView,
+    /// The input cells and data, in the order of the inputs
+    pub inputs: Vec<(CellOutput, Bytes)>,
+}
+
+impl<'a> LintContext<'a> {
+    /// Resolve all the input cells of `tx` by `tx_dep_provider`
+    pub fn resolve(
+        tx: &'a TransactionView,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<LintContext<'a>, TransactionDependencyError> {
+        let inputs = tx
+            .input_pts_iter()
+            .map(|out_point| {
+                let output = tx_dep_provider.get_cell(&out_point)?;
+                let data = tx_dep_provider.get_cell_data(&out_point)?;
+                Ok((output, data))
+            })
+            .collect::<Result<Vec<_>, TransactionDependencyError>>()?;
+        Ok(LintContext { tx, inputs })
+    }
+}
+
+/// A check on a transaction before it is sent, without running the scripts
+pub trait LintRule {
+    fn name(&self) -> &'static str;
+    fn check(&self, ctx: &LintContext) -> Vec<LintIssue>;
+}
+
+/// Run all the `rules` on `tx`, the input cells are resolved once.
+pub fn lint_tx(
+    tx: &TransactionView,
+    tx_dep_provider: &dyn TransactionDependencyProvider,
+    rules: &[&dyn LintRule],
+) -> Result<Vec<LintIssue>, TransactionDependencyError> {
+    let ctx = LintContext::resolve(tx, tx_dep_provider)?;
+    Ok(rules.iter().flat_map(|rule| rule.check(&ctx)).collect())
+}
+
+/// Owner mode is also enabled by an input type script hash equals the owner hash
+pub const XUDT_OWNER_MODE_INPUT_TYPE: u32 = 0x8000_0000;
+/// Owner mode is also enabled by an output type script hash equals the owner hash
+pub const XUDT_OWNER_MODE_OUTPUT_TYPE: u32 = 0x4000_0000;
+/// Owner mode is not enabled by an input lock script hash
+pub const XUDT_OWNER_MODE_INPUT_LOCK_NOT: u32 = 0x2000_0000;
+
+/// The xUDT type script args:
+///
+/// ```text
+/// <32 bytes owner hash> [<4 bytes little endian flags> <extension data>]
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct XudtArgs {
+    pub owner_hash: Byte32,
+    /// 0 if the args is only the owner hash
+    pub flags: u32,
+    pub extension_data: Bytes,
+}
+
+impl XudtArgs {
+    pub fn parse(args: &[u8]) -> Result<XudtArgs, String> {
+        if args.len() < 32 {
+            return Err(format!(
+                "xudt args must be at least 32 bytes, got {} bytes",
+                args.len()
+            ));
+        }
+        let owner_hash = Byte32::from_slice(&args[0..32]).expect("32 bytes");
+        if args.len() == 32 {
+            return Ok(XudtArgs {
+                owner_hash,
+                flags: 0,
+                extension_data: Bytes::new(),
+            });
+        }
+        if args.len() < 36 {
+            return Err(format!(
+                "xudt flags is truncated, args length: {}",
+                args.len()
+            ));
+        }
+        let mut flags_bytes = [0u8; 4];
+        flags_bytes.copy_from_slice(&args[32..36]);
+        Ok(XudtArgs {
+            owner_hash,
+            flags: u32::from_le_bytes(flags_bytes),
+            extension_data: Bytes::from(args[36..].to_vec()),
+        })
+    }
+}
+
+/// Every udt issuance (the total output amount of a type script exceeds the
+/// total input amount) must be authorized by the owner:
+///
+///   * sUDT: an input lock script hash equals the type script args
+///   * xUDT: an input lock script hash equals the owner hash, unless
+///     `XUDT_OWNER_MODE_INPUT_LOCK_NOT` is set, or an input/output type script
+///     hash equals the owner hash when `XUDT_OWNER_MODE_INPUT_TYPE`/
+///     `XUDT_OWNER_MODE_OUTPUT_TYPE` is set
+///
+/// Every output of an unauthorized issuance is reported.
+pub struct UdtOwnerModeSatisfied {
+    pub sudt_script_ids: Vec<ScriptId>,
+    pub xudt_script_ids: Vec<ScriptId>,
+}
+
+impl UdtOwnerModeSatisfied {
+    pub fn new(sudt_script_ids: Vec<ScriptId>, xudt_script_ids: Vec<ScriptId>) -> Self {
+        UdtOwnerModeSatisfied {
+            sudt_script_ids,
+            xudt_script_ids,
+        }
+    }
+
+    /// Check the owner mode of `type_script`, return the reason when it is not satisfied
+    fn owner_mode_error(&self, ctx: &LintContext, type_script: &Script) -> Option<String> {
+        let script_id = ScriptId::from(type_script);
+        let args = type_script.args().raw_data();
+        let (owner_hash, flags) = if self.sudt_script_ids.contains(&script_id) {
+            if args.len() < 32 {
+                return Some(format!(
+                    "sudt args must be at least 32 bytes, got {} bytes",
+                    args.len()
+                ));
+            }
+            (Byte32::from_slice(&args[0..32]).expect("32 bytes"), 0)
+        } else {
+            match XudtArgs::parse(&args) {
+                Ok(xudt_args) => (xudt_args.owner_hash, xudt_args.flags),
+                Err(err) => return Some(err),
+            }
+        };
+        let by_input_lock = flags & XUDT_OWNER_MODE_INPUT_LOCK_NOT == 0
+            && ctx
+                .inputs
+                .iter()
+                .any(|(output, _)| output.lock().calc_script_hash() == owner_hash);
+        let by_input_type = flags & XUDT_OWNER_MODE_INPUT_TYPE != 0
+            && ctx.inputs.iter().any(|(output, _)| {
+                output
+                    .type_()
+                    .to_opt()
+                    .map(|script| script.calc_script_hash() == owner_hash)
+                    .unwrap_or(false)
+            });
+        let by_output_type = flags & XUDT_OWNER_MODE_OUTPUT_TYPE != 0
+            && ctx.tx.outputs().into_iter().any(|output| {
+                output
+                    .type_()
+                    .to_opt()
+                    .map(|script| script.calc_script_hash() == owner_hash)
+                    .unwrap_or(false)
+            });
+        if by_input_lock || by_input_type || by_output_type {
+            None
+        } else {
+            Some(format!(
+                "no input lock (or allowed type) script hash equals the owner hash {:#x}",
+                owner_hash
+            ))
+        }
+    }
+
+    fn is_udt(&self, type_script: &Script) -> bool {
+        let script_id = ScriptId::from(type_script);
+        self.sudt_script_ids.contains(&script_id) || self.xudt_script_ids.contains(&script_id)
+    }
+}
+
+/// Sum the udt amounts by type script, cells with less than 16 bytes of data are ignored
+fn udt_totals<'a, I>(cells: I) -> HashMap<Script, u128>
+where
+    I: Iterator<Item = (CellOutput, &'a Bytes)>,
+{
+    let mut totals = HashMap::new();
+    for (output, data) in cells {
+        if let Some(type_script) = output.type_().to_opt() {
+            if data.len() >= 16 {
+                let mut amount_bytes = [0u8; 16];
+                amount_bytes.copy_from_slice(&data[0..16]);
+                let total: &mut u128 = totals.entry(type_script).or_default();
+                *total = total.saturating_add(u128::from_le_bytes(amount_bytes));
+            }
+        }
+    }
+    totals
+}
+
+impl LintRule for UdtOwnerModeSatisfied {
+    fn name(&self) -> &'static str {
+        "UdtOwnerModeSatisfied"
+    }
+
+    fn check(&self, ctx: &LintContext) -> Vec<LintIssue> {
+        let input_totals = udt_totals(
+            ctx.inputs
+                .iter()
+                .map(|(output, data)| (output.clone(), data)),
+        );
+        let outputs_data = ctx
+            .tx
+            .outputs_data()
+            .into_iter()
+            .map(|data| data.raw_data())
+            .collect::<Vec<_>>();
+        let output_totals = udt_totals(ctx.tx.outputs().into_iter().zip(outputs_data.iter()));
+
+        let mut issues = Vec::new();
+        // type script => the reason, `None` if authorized or not an issuance
+        let mut checked: HashMap<Script, Option<String>> = HashMap::new();
+        for (idx, output) in ctx.tx.outputs().into_iter().enumerate() {
+            let type_script = match output.type_().to_opt() {
+                Some(type_script) if self.is_udt(&type_script) => type_script,
+                _ => continue,
+            };
+            let error = checked.entry(type_script.clone()).or_insert_with(|| {
+                let input_total = input_totals.get(&type_script).cloned().unwrap_or(0);
+                let output_total = output_totals.get(&type_script).cloned().unwrap_or(0);
+                if output_total > input_total {
+                    self.owner_mode_error(ctx, &type_script)
+                } else {
+                    None
+                }
+            });
+            if let Some(error) = error {
+                issues.push(LintIssue {
+                    rule: self.name(),
+                    output_index: Some(idx),
+                    message: format!("udt issuance without owner authorization: {}", error),
+                });
+            }
+        }
+        issues
+    }
+}
+
+/// Report every output whose lock is a burn lock (see `is_burn_lock`), the
+/// capacity sent to it is lost forever. Call `allow_burn` when it is intended.
+#[derive(Debug, Clone, Default)]
+pub struct BurnLockOutput {
+    allow_burn: bool,
+}
+
+impl BurnLockOutput {
+    pub fn new() -> Self {
+        BurnLockOutput::default()
+    }
+
+    /// Accept the outputs to a burn lock
+    pub fn allow_burn(&mut self) -> &mut Self {
+        self.allow_burn = true;
+        self
+    }
+}
+
+impl LintRule for BurnLockOutput {
+    fn name(&self) -> &'static str {
+        "BurnLockOutput"
+    }
+
+    fn check(&self, ctx: &LintContext) -> Vec<LintIssue> {
+        if self.allow_burn {
+            return Vec::new();
+        }
+        ctx.tx
+            .outputs()
+            .into_iter()
+            .enumerate()
+            .filter(|(_, output)| is_burn_lock(&output.lock()))
+            .map(|(idx, output)| {
+                let capacity: u64 = output.capacity().unpack();
+                LintIssue {
+                    rule: self.name(),
+                    output_index: Some(idx),
+                    message: format!(
+                        "{} shannons sent to a burn lock, call allow_burn if it is intended",
+                        capacity
+                    ),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Report a transaction without outputs, the capacity of all its inputs is
+/// burned as fee. Call `confirm_burn` with the max amount when it is intended,
+/// as `CapacityBalancer::confirm_burn` for the balancer.
+#[derive(Debug, Clone, Default)]
+pub struct NoOutputsBurn {
+    confirmed_burn: Option<u64>,
+}
+
+impl NoOutputsBurn {
+    pub fn new() -> Self {
+        NoOutputsBurn::default()
+    }
+
+    /// Accept a transaction without outputs burning at most `amount` shannons
+    pub fn confirm_burn(&mut self, amount: u64) -> &mut Self {
+        self.confirmed_burn = Some(amount);
+        self
+    }
+}
+
+impl LintRule for NoOutputsBurn {
+    fn name(&self) -> &'static str {
+        "NoOutputsBurn"
+    }
+
+    fn check(&self, ctx: &LintContext) -> Vec<LintIssue> {
+        if !ctx.tx.outputs().is_empty() {
+            return Vec::new();
+        }
+        let burned = ctx.inputs.iter().fold(0u64, |total, (output, _)| {
+            let capacity: u64 = output.capacity().unpack();
+            total.saturating_add(capacity)
+        });
+        match self.confirmed_burn {
+            Some(amount) if burned <= amount => Vec::new(),
+            Some(amount) => vec![LintIssue {
+                rule: self.name(),
+                output_index: None,
+                message: format!(
+                    "{} shannons burned as fee without outputs, more than the confirmed {}",
+                    burned, amount
+                ),
+            }],
+            None => vec![LintIssue {
+                rule: self.name(),
+                output_index: None,
+                message: format!(
+                    "{} shannons burned as fee without outputs, call confirm_burn if it is intended",
+                    burned
+                ),
+            }],
+        }
+    }
+}
+
+/// Report the outputs on mainnet protected by an always-success lock with
+/// more than `max_capacity` shannons, anyone can spend them.
+#[derive(Debug, Clone)]
+pub struct AlwaysSuccessLockCapacity {
+    pub network: NetworkType,
+    /// The script ids of the always-success lock scripts
+    pub always_success_script_ids: Vec<ScriptId>,
+    pub max_capacity: u64,
+}
+
+impl AlwaysSuccessLockCapacity {
+    pub fn new<N: Into<NetworkType>>(
+        network: N,
+        always_success_script_ids: Vec<ScriptId>,
+        max_capacity: u64,
+    ) -> Self {
+        AlwaysSuccessLockCapacity {
+            network: network.into(),
+            always_success_script_ids,
+            max_capacity,
+        }
+    }
+}
+
+impl LintRule for AlwaysSuccessLockCapacity {
+    fn name(&self) -> &'static str {
+        "AlwaysSuccessLockCapacity"
+    }
+
+    fn check(&self, ctx: &LintContext) -> Vec<LintIssue> {
+        if self.network != NetworkType::Mainnet {
+            return Vec::new();
+        }
+        let mut issues = Vec::new();
+        for (idx, output) in ctx.tx.outputs().into_iter().enumerate() {
+            let script_id = ScriptId::from(&output.lock());
+            let capacity: u64 = output.capacity().unpack();
+            if capacity > self.max_capacity && self.always_success_script_ids.contains(&script_id) {
+                issues.push(LintIssue {
+                    rule: self.name(),
+                    output_index: Some(idx),
+                    message: format!(
+                        "{} shannons protected by an always-success lock on mainnet",
+                        capacity
+                    ),
+                });
+            }
+        }
+        issues
+    }
+}
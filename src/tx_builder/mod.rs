@@ -1,10 +1,39 @@
 pub mod acp;
+pub mod batch;
+pub mod burn;
 pub mod cheque;
 pub mod dao;
+pub mod hashlock;
+pub mod lint;
 pub mod omni_lock;
+pub mod singleton;
+pub mod template;
 pub mod transfer;
+pub mod type_id;
 pub mod udt;
 
+mod cycles;
+pub use cycles::{
+    latest_consensus, verify_cycles, verify_mock_tx_cycles, CycleReport, GroupCycles,
+    VerifyCyclesError,
+};
+mod footprint;
+pub use footprint::{GroupFootprint, GroupFootprintDiff, TxFootprint, TxFootprintDiff};
+mod invariants;
+pub use invariants::{check_balanced_invariants, InvariantViolation};
+#[cfg(feature = "parallel")]
+mod parallel;
+#[cfg(feature = "parallel")]
+pub use parallel::unlock_tx_parallel;
+mod sponsor;
+pub use sponsor::{sponsor_tx, FeeSponsor, SponsorError};
+mod summary;
+pub use summary::{compact_summary, CompactSummary};
+mod trailing;
+pub use trailing::TrailingWitnesses;
+mod tx_context;
+pub use tx_context::TxContext;
+
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
@@ -17,26 +46,30 @@ use thiserror::Error;
 use ckb_types::core::cell::{CellProvider, HeaderChecker};
 use ckb_types::core::HeaderView;
 use ckb_types::{
+    bytes::Bytes,
     core::{
-        cell::resolve_transaction, error::OutPointError, Capacity, CapacityError, FeeRate,
+        cell::resolve_transaction, error::OutPointError, Capacity, CapacityError, Cycle, FeeRate,
         TransactionView,
     },
-    packed::{Byte32, CellInput, CellOutput, Script, WitnessArgs},
+    packed::{self, Byte32, CellInput, CellOutput, OutPoint, Script, WitnessArgs},
     prelude::*,
 };
 
+use crate::tx_checker::{check_transaction_strict, TxCheckError};
 use crate::types::ScriptGroup;
-use crate::types::{HumanCapacity, ScriptId};
-use crate::unlock::{ScriptUnlocker, UnlockError};
-use crate::util::calculate_dao_maximum_withdraw4;
+use crate::types::{HumanCapacity, ScriptId, Since};
+use crate::unlock::{ScriptUnlocker, UnlockError, UnlockerProvider};
+use crate::util::{calc_fee, calculate_dao_maximum_withdraw4, tx_size};
 use crate::{constants::DAO_TYPE_HASH, NetworkType};
 use crate::{
     traits::{
-        CellCollector, CellCollectorError, CellDepResolver, CellQueryOptions, HeaderDepResolver,
+        CellCollector, CellCollectorError, CellDepResolver, CellLockTtl, CellQueryOptions,
+        FeePriority, FeeRateProvider, HeaderDepResolver, NodeFeeRateProvider,
         TransactionDependencyError, TransactionDependencyProvider, ValueRangeOption,
     },
     RpcError,
 };
+use cycles::verify_type_script_cycles;
 
 /// Transaction builder errors
 #[derive(Error, Debug)]
@@ -64,6 +97,9 @@ pub enum TxBuilderError {
     #[error("resolve header dep by block number failed: `{0}`")]
     ResolveHeaderDepByNumberFailed(u64),
 
+    #[error("the header of the block containing input `{0}` is not resolved, the cell may be unconfirmed")]
+    InputHeaderNotResolved(OutPoint),
+
     #[error("unlock error: `{0}`")]
     Unlock(#[from] UnlockError),
 
@@ -76,6 +112,33 @@ pub enum TxBuilderError {
     #[error("can not find specifed output to put small change")]
     NoOutputForSmallChange,
 
+    #[error("amount overflow: `{0}` + `{1}`")]
+    AmountOverflow(u128, u128),
+
+    #[error("udt supply limit exceeded, limit: `{0}`, issued: `{1}`")]
+    SupplyLimitExceeded(u128, u128),
+
+    #[error("insufficient udt balance of type script `{type_script}`, required: `{required}`, available: `{available}`")]
+    InsufficientUdt {
+        type_script: Script,
+        required: u128,
+        available: u128,
+    },
+
+    #[error("invalid data of output `{0}`: `{1}`")]
+    InvalidOutputData(usize, String),
+
+    #[error(
+        "the capacity of output `{index}` can not hold the cell, min: `{min}`, actual: `{actual}`"
+    )]
+    CapacityTooSmallForCell { index: usize, min: u64, actual: u64 },
+
+    #[error("transaction check error: `{0}`")]
+    TxCheck(#[from] TxCheckError),
+
+    #[error("verify cycles error: `{0}`")]
+    VerifyCycles(#[from] VerifyCyclesError),
+
     #[error("other error: `{0}`")]
     Other(anyhow::Error),
 }
@@ -102,24 +165,56 @@ pub trait TxBuilder {
         header_dep_resolver: &dyn HeaderDepResolver,
         tx_dep_provider: &dyn TransactionDependencyProvider,
         balancer: &CapacityBalancer,
-        unlockers: &HashMap<ScriptId, Box<dyn ScriptUnlocker>>,
+        unlockers: &dyn UnlockerProvider,
     ) -> Result<TransactionView, TxBuilderError> {
+        let mut tx_context = TxContext::new(tx_dep_provider);
         let base_tx = self.build_base(
             cell_collector,
             cell_dep_resolver,
             header_dep_resolver,
-            tx_dep_provider,
+            &tx_context,
         )?;
         let (tx_filled_witnesses, _) =
-            fill_placeholder_witnesses(base_tx, tx_dep_provider, unlockers)?;
-        Ok(balance_tx_capacity(
+            fill_placeholder_witnesses_with(base_tx, &mut tx_context, unlockers)?;
+        let tx_dep_provider: &dyn TransactionDependencyProvider = &tx_context;
+        let (balanced_tx, _) = balance_and_adjust(
+            self,
             &tx_filled_witnesses,
             balancer,
             cell_collector,
             tx_dep_provider,
             cell_dep_resolver,
             header_dep_resolver,
-        )?)
+        )?;
+        if balancer.check_transaction {
+            check_transaction_strict(&balanced_tx, tx_dep_provider, None)?;
+        }
+        if let Some(max_cycles) = balancer.verify_type_script_cycles {
+            verify_type_script_cycles(
+                &balanced_tx,
+                tx_dep_provider,
+                header_dep_resolver,
+                max_cycles,
+            )?;
+        }
+        Ok(balanced_tx)
+    }
+
+    /// Adjust the outputs or witnesses after the capacity is balanced (the
+    /// inputs are final) and before the transaction is unlocked, e.g. the type
+    /// id args depend on the first input. Called by `build_balanced` and
+    /// `build_balance_unlocked`.
+    ///
+    /// The hook must not add or remove inputs. If the transaction size is
+    /// changed, the capacity is balanced once more, which may adjust the
+    /// change output or append more inputs after the existing ones, the hook
+    /// is not called again.
+    fn adjust_after_balance(
+        &self,
+        tx: TransactionView,
+        _tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, TxBuilderError> {
+        Ok(tx)
     }
 
     /// Build unlocked transaction that ready to send or for further unlock:
@@ -137,17 +232,20 @@ pub trait TxBuilder {
         header_dep_resolver: &dyn HeaderDepResolver,
         tx_dep_provider: &dyn TransactionDependencyProvider,
         balancer: &CapacityBalancer,
-        unlockers: &HashMap<ScriptId, Box<dyn ScriptUnlocker>>,
+        unlockers: &dyn UnlockerProvider,
     ) -> Result<(TransactionView, Vec<ScriptGroup>), TxBuilderError> {
+        // The cells loaded when building the balanced transaction are reused
+        // when unlocking it
+        let mut tx_context = TxContext::new(tx_dep_provider);
         let balanced_tx = self.build_balanced(
             cell_collector,
             cell_dep_resolver,
             header_dep_resolver,
-            tx_dep_provider,
+            &tx_context,
             balancer,
             unlockers,
         )?;
-        Ok(unlock_tx(balanced_tx, tx_dep_provider, unlockers)?)
+        Ok(unlock_tx_with(balanced_tx, &mut tx_context, unlockers)?)
     }
 
     /// Build unlocked transaction that ready to send or for further unlock, it's similar to `build_unlocked`,
@@ -165,27 +263,27 @@ pub trait TxBuilder {
         header_dep_resolver: &dyn HeaderDepResolver,
         tx_dep_provider: &'static dyn TransactionDependencyProvider,
         balancer: &CapacityBalancer,
-        unlockers: &HashMap<ScriptId, Box<dyn ScriptUnlocker>>,
+        unlockers: &dyn UnlockerProvider,
     ) -> Result<(TransactionView, Vec<ScriptGroup>), TxBuilderError> {
+        let mut tx_context = TxContext::new(tx_dep_provider);
         let base_tx = self.build_base(
             cell_collector,
             cell_dep_resolver,
             header_dep_resolver,
-            tx_dep_provider,
+            &tx_context,
         )?;
         let (tx_filled_witnesses, _) =
-            fill_placeholder_witnesses(base_tx, tx_dep_provider, unlockers)?;
-        let (balanced_tx, mut change_idx) = rebalance_tx_capacity(
+            fill_placeholder_witnesses_with(base_tx, &mut tx_context, unlockers)?;
+        let (balanced_tx, mut change_idx) = balance_and_adjust(
+            self,
             &tx_filled_witnesses,
             balancer,
             cell_collector,
-            tx_dep_provider,
+            &tx_context,
             cell_dep_resolver,
             header_dep_resolver,
-            0,
-            None,
         )?;
-        let (mut tx, unlocked_group) = unlock_tx(balanced_tx, tx_dep_provider, unlockers)?;
+        let (mut tx, unlocked_group) = unlock_tx_with(balanced_tx, &mut tx_context, unlockers)?;
         if unlocked_group.is_empty() {
             let mut ready = false;
             const MAX_LOOP_TIMES: u32 = 16;
@@ -205,7 +303,7 @@ pub trait TxBuilder {
                 ready = ok;
                 change_idx = new_change_idx;
                 if !ready {
-                    let (new_tx, _) = unlock_tx(tx, tx_dep_provider, unlockers)?;
+                    let (new_tx, _) = unlock_tx_with(tx, &mut tx_context, unlockers)?;
                     tx = new_tx
                 }
             }
@@ -217,6 +315,136 @@ pub trait TxBuilder {
     }
 }
 
+/// Balance the capacity then call `TxBuilder::adjust_after_balance`, the fee
+/// is balanced again if the adjustment changed the transaction size.
+fn balance_and_adjust<B: TxBuilder + ?Sized>(
+    builder: &B,
+    tx: &TransactionView,
+    balancer: &CapacityBalancer,
+    cell_collector: &mut dyn CellCollector,
+    tx_dep_provider: &dyn TransactionDependencyProvider,
+    cell_dep_resolver: &dyn CellDepResolver,
+    header_dep_resolver: &dyn HeaderDepResolver,
+) -> Result<(TransactionView, Option<usize>), TxBuilderError> {
+    // read once, a node backed provider may return another rate in the same pass
+    let fee_rate = balancer.current_fee_rate()?;
+    let (balanced_tx, change_idx) = rebalance_tx_capacity(
+        tx,
+        balancer,
+        fee_rate,
+        cell_collector,
+        tx_dep_provider,
+        cell_dep_resolver,
+        header_dep_resolver,
+        0,
+        None,
+    )?;
+    let balanced_size = tx_size(&balanced_tx);
+    let inputs_len = balanced_tx.inputs().len();
+    let adjusted_tx = builder.adjust_after_balance(balanced_tx, tx_dep_provider)?;
+    if adjusted_tx.inputs().len() != inputs_len {
+        return Err(TxBuilderError::Other(anyhow!(
+            "inputs changed by adjust_after_balance, before: {}, after: {}",
+            inputs_len,
+            adjusted_tx.inputs().len()
+        )));
+    }
+    let adjusted_size = tx_size(&adjusted_tx);
+    let (adjusted_tx, change_idx) = if adjusted_size == balanced_size {
+        (adjusted_tx, change_idx)
+    } else {
+        pay_adjusted_fee(
+            adjusted_tx,
+            change_idx,
+            balancer,
+            fee_rate,
+            cell_collector,
+            tx_dep_provider,
+            cell_dep_resolver,
+            header_dep_resolver,
+        )?
+    };
+    if !balancer.input_header_deps {
+        return Ok((adjusted_tx, change_idx));
+    }
+    // The header deps make the transaction larger, rebalancing may add more
+    // inputs whose headers are also required.
+    let (mut tx, mut change_idx) = (adjusted_tx, change_idx);
+    loop {
+        let header_deps = resolve_input_header_deps(&tx, tx_dep_provider, header_dep_resolver)?;
+        let mut all_header_deps: Vec<Byte32> = tx.header_deps().into_iter().collect();
+        let header_deps_len = all_header_deps.len();
+        extend_unique(&mut all_header_deps, header_deps);
+        if all_header_deps.len() == header_deps_len {
+            return Ok((tx, change_idx));
+        }
+        let new_tx = tx
+            .as_advanced_builder()
+            .set_header_deps(all_header_deps)
+            .build();
+        let min_fee = calc_fee(tx_size(&new_tx), fee_rate);
+        let (rebalanced_tx, new_change_idx) = balancer.rebalance_with_fee_rate(
+            &new_tx,
+            fee_rate,
+            cell_collector,
+            tx_dep_provider,
+            cell_dep_resolver,
+            header_dep_resolver,
+            min_fee,
+            change_idx,
+        )?;
+        tx = rebalanced_tx;
+        change_idx = new_change_idx;
+    }
+}
+
+/// Pay the fee of the transaction resized by `TxBuilder::adjust_after_balance`
+#[allow(clippy::too_many_arguments)]
+fn pay_adjusted_fee(
+    adjusted_tx: TransactionView,
+    change_idx: Option<usize>,
+    balancer: &CapacityBalancer,
+    fee_rate: FeeRate,
+    cell_collector: &mut dyn CellCollector,
+    tx_dep_provider: &dyn TransactionDependencyProvider,
+    cell_dep_resolver: &dyn CellDepResolver,
+    header_dep_resolver: &dyn HeaderDepResolver,
+) -> Result<(TransactionView, Option<usize>), TxBuilderError> {
+    let adjusted_size = tx_size(&adjusted_tx);
+    let min_fee = calc_fee(adjusted_size, fee_rate);
+    let fee = tx_fee(adjusted_tx.clone(), tx_dep_provider, header_dep_resolver)
+        .map_err(BalanceTxCapacityError::from)?;
+    match change_idx {
+        // give the saved fee back to the change output
+        Some(idx) if fee > min_fee => {
+            let mut outputs: Vec<_> = adjusted_tx.outputs().into_iter().collect();
+            let change_capacity: u64 = outputs[idx].capacity().unpack();
+            outputs[idx] = outputs[idx]
+                .clone()
+                .as_builder()
+                .capacity((change_capacity + fee - min_fee).pack())
+                .build();
+            let tx = adjusted_tx
+                .as_advanced_builder()
+                .set_outputs(outputs)
+                .build();
+            Ok((tx, change_idx))
+        }
+        _ if fee >= min_fee => Ok((adjusted_tx, change_idx)),
+        // take the fee from the change output or collect more inputs
+        _ => Ok(balancer.rebalance_with_fee_rate(
+            &adjusted_tx,
+            fee_rate,
+            cell_collector,
+            tx_dep_provider,
+            cell_dep_resolver,
+            header_dep_resolver,
+            min_fee,
+            change_idx,
+        )?),
+    }
+}
+
 #[derive(Debug, Eq, PartialEq, Hash, Clone)]
 pub enum TransferAction {
     /// This action will crate a new cell, typecial lock script: cheque, sighash, multisig
@@ -246,6 +474,46 @@ pub enum TransactionFeeError {
     CapacityOverflow(u64),
 }
 
+/// Push the item if it is not already in the list, the insertion order is
+/// kept so the same builder inputs always produce the same transaction.
+pub(crate) fn push_unique<T: PartialEq>(items: &mut Vec<T>, item: T) {
+    if !items.contains(&item) {
+        items.push(item);
+    }
+}
+
+/// The hashes of the blocks containing the input cells, in the order of the
+/// inputs without duplicates. The header is resolved by the hash of the
+/// transaction creating the input cell.
+///
+/// Required in the header deps by the type scripts loading the header of the
+/// input cells, e.g. the Nervos DAO withdraw and some oracles. An input cell
+/// whose header can not be resolved (e.g. an unconfirmed cell) is an error.
+pub fn resolve_input_header_deps(
+    tx: &TransactionView,
+    tx_dep_provider: &dyn TransactionDependencyProvider,
+    header_dep_resolver: &dyn HeaderDepResolver,
+) -> Result<Vec<Byte32>, TxBuilderError> {
+    let mut header_deps = Vec::new();
+    for input in tx.inputs() {
+        let out_point = input.previous_output();
+        // make sure the input cell exists
+        tx_dep_provider.get_cell(&out_point)?;
+        let header = header_dep_resolver
+            .resolve_by_tx(&out_point.tx_hash())
+            .map_err(TxBuilderError::Other)?
+            .ok_or_else(|| TxBuilderError::InputHeaderNotResolved(out_point.clone()))?;
+        push_unique(&mut header_deps, header.hash());
+    }
+    Ok(header_deps)
+}
+
+pub(crate) fn extend_unique<T: PartialEq>(items: &mut Vec<T>, new_items: Vec<T>) {
+    for item in new_items {
+        push_unique(items, item);
+    }
+}
+
 /// Calculate the actual transaction fee of the transaction, include dao
 /// withdraw capacity.
 #[allow(clippy::unnecessary_lazy_evaluations)]
@@ -318,13 +586,13 @@ pub fn tx_fee(
 pub enum SinceSource {
     /// The vaule in the tuple is offset of the args, and the `since` is stored in `lock.args[offset..offset+8]`
     LockArgs(usize),
-    /// raw since value
-    Value(u64),
+    /// The since value
+    Value(Since),
 }
 
 impl Default for SinceSource {
     fn default() -> SinceSource {
-        SinceSource::Value(0)
+        SinceSource::Value(Since::default())
     }
 }
 
@@ -337,12 +605,19 @@ pub struct CapacityProvider {
     /// The lock scripts provider capacity. The second field of the tuple is the
     /// placeholder witness of the lock script.
     pub lock_scripts: Vec<(Script, WitnessArgs, SinceSource)>,
+
+    /// The placeholder policies of the lock scripts, override the placeholder
+    /// witnesses in `lock_scripts`. See `set_placeholder_policy`.
+    pub placeholder_policies: Vec<(Script, PlaceholderPolicy)>,
 }
 
 impl CapacityProvider {
     /// create a new capacity provider.
     pub fn new(lock_scripts: Vec<(Script, WitnessArgs, SinceSource)>) -> CapacityProvider {
-        CapacityProvider { lock_scripts }
+        CapacityProvider {
+            lock_scripts,
+            placeholder_policies: Vec::new(),
+        }
     }
 
     /// create a new capacity provider with the default since source.
@@ -351,8 +626,141 @@ impl CapacityProvider {
             .into_iter()
             .map(|(script, witness)| (script, witness, SinceSource::default()))
             .collect();
-        CapacityProvider { lock_scripts }
+        CapacityProvider::new(lock_scripts)
+    }
+
+    /// Fill the placeholder witnesses of the inputs locked by `lock_script`
+    /// by `policy`, both when balancing the transaction and in
+    /// `fill_placeholder_witnesses_with_policies`. The lock script does not
+    /// need to be a capacity provider lock script.
+    pub fn set_placeholder_policy(&mut self, lock_script: Script, policy: PlaceholderPolicy) {
+        self.placeholder_policies
+            .retain(|(script, _)| script != &lock_script);
+        self.placeholder_policies.push((lock_script, policy));
+    }
+
+    pub fn placeholder_policy(&self, lock_script: &Script) -> Option<&PlaceholderPolicy> {
+        self.placeholder_policies
+            .iter()
+            .find(|(script, _)| script == lock_script)
+            .map(|(_, policy)| policy)
+    }
+
+    /// Apply the placeholder policies to the witnesses of the inputs, the
+    /// lock field of a witness is replaced by the placeholder, the input_type
+    /// and output_type fields are only filled when they are empty.
+    pub fn apply_placeholder_policies(
+        &self,
+        tx: &TransactionView,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, TransactionDependencyError> {
+        if self.placeholder_policies.is_empty() {
+            return Ok(tx.clone());
+        }
+        let mut witnesses: Vec<packed::Bytes> = tx.witnesses().into_iter().collect();
+        while witnesses.len() < tx.inputs().len() {
+            witnesses.push(Default::default());
+        }
+        let mut input_locks = Vec::with_capacity(tx.inputs().len());
+        for input in tx.inputs() {
+            input_locks.push(tx_dep_provider.get_cell(&input.previous_output())?.lock());
+        }
+        for (lock_script, policy) in &self.placeholder_policies {
+            let mut is_first = true;
+            for (idx, input) in tx.inputs().into_iter().enumerate() {
+                if &input_locks[idx] != lock_script {
+                    continue;
+                }
+                let placeholder = match policy {
+                    PlaceholderPolicy::Uniform(placeholder) if is_first => {
+                        Some(placeholder.clone())
+                    }
+                    PlaceholderPolicy::Uniform(_) => continue,
+                    PlaceholderPolicy::FirstOnly(placeholder) if is_first => {
+                        Some(placeholder.clone())
+                    }
+                    PlaceholderPolicy::FirstOnly(_) => None,
+                    PlaceholderPolicy::PerInput(placeholder_fn) => placeholder_fn(idx, &input),
+                };
+                is_first = false;
+                witnesses[idx] = match placeholder {
+                    Some(placeholder) => {
+                        let witness_data = witnesses[idx].raw_data();
+                        apply_placeholder(&witness_data, &placeholder)
+                            .as_bytes()
+                            .pack()
+                    }
+                    None => Default::default(),
+                };
+            }
+        }
+        Ok(tx.as_advanced_builder().set_witnesses(witnesses).build())
+    }
+}
+
+/// The placeholder witness of an input of the lock script by the index of the
+/// input and the input, `None` means a zero-length witness.
+pub type PlaceholderFn = dyn Fn(usize, &CellInput) -> Option<WitnessArgs> + Send + Sync;
+
+/// How the placeholder witnesses of the inputs of a lock script are filled.
+#[derive(Clone)]
+pub enum PlaceholderPolicy {
+    /// The first input of the lock script has the placeholder witness, the
+    /// witnesses of the other inputs are kept. The same as the placeholder
+    /// witness in `CapacityProvider::lock_scripts`.
+    Uniform(WitnessArgs),
+    /// The first input of the lock script has the placeholder witness, the
+    /// other inputs have zero-length witnesses even if they are filled
+    /// before.
+    FirstOnly(WitnessArgs),
+    /// The placeholder witness of every input of the lock script, e.g. a
+    /// zero-length witness for the inputs unlocked by other inputs.
+    PerInput(Arc<PlaceholderFn>),
+}
+
+impl PlaceholderPolicy {
+    /// The placeholder witness of the first input of the lock script, `None`
+    /// when it depends on the input.
+    fn first_placeholder(&self) -> Option<&WitnessArgs> {
+        match self {
+            PlaceholderPolicy::Uniform(placeholder) | PlaceholderPolicy::FirstOnly(placeholder) => {
+                Some(placeholder)
+            }
+            PlaceholderPolicy::PerInput(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Debug for PlaceholderPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlaceholderPolicy::Uniform(placeholder) => {
+                f.debug_tuple("Uniform").field(placeholder).finish()
+            }
+            PlaceholderPolicy::FirstOnly(placeholder) => {
+                f.debug_tuple("FirstOnly").field(placeholder).finish()
+            }
+            PlaceholderPolicy::PerInput(_) => f.write_str("PerInput(..)"),
+        }
+    }
+}
+
+fn apply_placeholder(witness_data: &[u8], placeholder: &WitnessArgs) -> WitnessArgs {
+    let witness = match WitnessArgs::from_slice(witness_data) {
+        Ok(witness) if !witness_data.is_empty() => witness,
+        _ => return placeholder.clone(),
+    };
+    let mut builder = witness.clone().as_builder();
+    if placeholder.lock().is_some() {
+        builder = builder.lock(placeholder.lock());
     }
+    if witness.input_type().is_none() {
+        builder = builder.input_type(placeholder.input_type());
+    }
+    if witness.output_type().is_none() {
+        builder = builder.output_type(placeholder.output_type());
+    }
+    builder.build()
 }
 
 #[derive(Error, Debug)]
@@ -395,8 +803,25 @@ pub enum BalanceTxCapacityError {
 
     #[error("should not try to rebalance, orignal fee {0}, required fee: {1},")]
     AlreadyBalance(u64, u64),
+
+    #[error("get fee rate error: `{0}`")]
+    FeeRate(anyhow::Error),
+
+    #[error("transaction size `{0}` exceeds the limit `{1}`")]
+    TxSizeLimitExceeded(u64, u64),
+
+    #[error("the fee does not converge after `{0}` rounds of change adjustment")]
+    FeeNotConverged(usize),
+
+    #[error("the transaction has no outputs and burns `{0}` shannons as fee, call confirm_burn if it is intended")]
+    BurnNotConfirmed(u64),
 }
 
+/// The max rounds of adjusting the change cell without adding inputs in
+/// `balance_tx_capacity`, the fee converges in one round since the change
+/// capacity does not change the transaction size.
+const MAX_CHANGE_ROUNDS: usize = 4;
+
 /// Transaction capacity balancer config.
 ///
 /// CapacityBalancer will try to balance the transaction capacity by adding inputs from CapacityProvider.
@@ -411,10 +836,53 @@ pub struct CapacityBalancer {
     /// Change cell's lock script if `None` use capacity_provider's first lock script
     pub change_lock_script: Option<Script>,
 
+    /// The lock script, type script and data of the change cell, overrides
+    /// `change_lock_script`. The change cell must hold the occupied capacity
+    /// of all of them.
+    pub change_cell_template: Option<(Script, Option<Script>, Bytes)>,
+
     /// When there is no more inputs for create a change cell to balance the
     /// transaction capacity, force the addition capacity as fee, the value is
     /// actual maximum transaction fee.
     pub force_small_change_as_fee: Option<u64>,
+
+    /// If set, the fee rate is fetched from this provider every time the
+    /// transaction is balanced, and `fee_rate` is ignored.
+    pub fee_rate_provider: Option<Box<dyn FeeRateProvider>>,
+
+    /// The witnesses kept after all the input aligned witnesses
+    pub trailing_witnesses: TrailingWitnesses,
+
+    /// The max capacity a transaction without outputs may burn as fee, see
+    /// `confirm_burn`.
+    pub confirmed_burn: Option<u64>,
+
+    /// Check the transaction by `tx_checker::check_transaction_strict` at the
+    /// end of `TxBuilder::build_balanced`.
+    pub check_transaction: bool,
+
+    /// Run the type scripts of the transaction in ckb-vm with this max cycles
+    /// at the end of `TxBuilder::build_balanced`, before anything is signed.
+    /// The lock scripts are not run since the witnesses are placeholders.
+    pub verify_type_script_cycles: Option<Cycle>,
+
+    /// Lock the cells selected as inputs by `CellCollector::lock_cell_with_ttl`,
+    /// so the cells of an abandoned transaction become collectible again.
+    /// When `None` the cells are locked until the cell collector is reset.
+    /// The cell collector must implement `lock_cell_with_ttl`.
+    pub cell_lock_ttl: Option<CellLockTtl>,
+
+    /// Also spend the cells created by the transactions applied by
+    /// `CellCollector::apply_tx`, so the transactions can be chained before
+    /// they are committed. The default value is true.
+    pub allow_unconfirmed: bool,
+
+    /// Add the headers of the blocks containing the inputs to the header deps
+    /// at the end of balancing, including the inputs added by the balancer.
+    /// Some type scripts require them, see `resolve_input_header_deps`.
+    /// The unconfirmed cells have no header yet, so only the confirmed cells
+    /// are collected when it is set, regardless of `allow_unconfirmed`.
+    pub input_header_deps: bool,
 }
 
 impl CapacityBalancer {
@@ -439,7 +907,16 @@ impl CapacityBalancer {
                 placeholder_witness,
             )]),
             change_lock_script: None,
+            change_cell_template: None,
             force_small_change_as_fee: None,
+            fee_rate_provider: None,
+            trailing_witnesses: TrailingWitnesses::default(),
+            confirmed_burn: None,
+            check_transaction: false,
+            verify_type_script_cycles: None,
+            cell_lock_ttl: None,
+            allow_unconfirmed: true,
+            input_header_deps: false,
         }
     }
 
@@ -458,17 +935,137 @@ impl CapacityBalancer {
                 since_source,
             )]),
             change_lock_script: None,
+            change_cell_template: None,
             force_small_change_as_fee: None,
+            fee_rate_provider: None,
+            trailing_witnesses: TrailingWitnesses::default(),
+            confirmed_burn: None,
+            check_transaction: false,
+            verify_type_script_cycles: None,
+            cell_lock_ttl: None,
+            allow_unconfirmed: true,
+            input_header_deps: false,
         }
     }
 
+    /// Create a simple capacity balancer following the node's limits: the
+    /// fee rate is the larger one of the estimated fee rate for `priority` and
+    /// the node's minimal fee rate, and the balanced transaction must not
+    /// exceed the node's transaction size limit. See `NodeFeeRateProvider`.
+    pub fn new_from_node(
+        ckb_client: &str,
+        capacity_provider: Script,
+        placeholder_witness: WitnessArgs,
+        priority: FeePriority,
+    ) -> CapacityBalancer {
+        let provider = NodeFeeRateProvider::new(ckb_client, priority);
+        let mut balancer = CapacityBalancer::new_simple(
+            capacity_provider,
+            placeholder_witness,
+            provider.limits().min_fee_rate,
+        );
+        balancer.set_fee_rate_provider(Some(Box::new(provider)));
+        balancer
+    }
+
     pub fn new_with_provider(fee_rate: u64, capacity_provider: CapacityProvider) -> Self {
         CapacityBalancer {
             fee_rate: FeeRate::from_u64(fee_rate),
             capacity_provider,
             change_lock_script: None,
+            change_cell_template: None,
             force_small_change_as_fee: None,
+            fee_rate_provider: None,
+            trailing_witnesses: TrailingWitnesses::default(),
+            confirmed_burn: None,
+            check_transaction: false,
+            verify_type_script_cycles: None,
+            cell_lock_ttl: None,
+            allow_unconfirmed: true,
+            input_header_deps: false,
+        }
+    }
+
+    /// Reserve a witness after all the input aligned witnesses, it's kept as
+    /// the last witnesses when the inputs are appended by balancing. See
+    /// `TrailingWitnesses`.
+    pub fn reserve_trailing_witness(&mut self, tag: &str, witness: Bytes) {
+        self.trailing_witnesses.reserve(tag, witness);
+    }
+
+    /// The index of the reserved trailing witness of `tag` in the balanced
+    /// transaction
+    pub fn trailing_witness_index(&self, tx: &TransactionView, tag: &str) -> Option<usize> {
+        self.trailing_witnesses.index(tx, tag)
+    }
+
+    /// Confirm that a transaction without outputs burns at most `amount`
+    /// shannons as fee: no change cell is created for it, and the extra
+    /// capacity is forced as fee up to `amount`.
+    ///
+    /// Without it, a transaction without outputs whose extra capacity is too
+    /// small for a change cell is rejected with `BurnNotConfirmed`.
+    pub fn confirm_burn(&mut self, amount: u64) {
+        self.confirmed_burn = Some(amount);
+        self.force_small_change_as_fee = Some(amount);
+    }
+
+    fn is_burn_confirmed(&self, fee: u64) -> bool {
+        self.confirmed_burn
+            .map(|amount| fee <= amount)
+            .unwrap_or(false)
+    }
+
+    /// Set or clear the change cell template, see `change_cell_template`.
+    pub fn set_change_cell_template(
+        &mut self,
+        lock_script: Script,
+        type_script: Option<Script>,
+        data: Bytes,
+    ) {
+        self.change_cell_template = Some((lock_script, type_script, data));
+    }
+
+    /// The change cell with zero capacity and its data: from
+    /// `change_cell_template`, or locked by `change_lock_script`, or locked by
+    /// the first lock script of the capacity provider.
+    pub fn base_change_cell(&self) -> Option<(CellOutput, Bytes)> {
+        if let Some((lock_script, type_script, data)) = self.change_cell_template.as_ref() {
+            let output = CellOutput::new_builder()
+                .lock(lock_script.clone())
+                .type_(type_script.clone().pack())
+                .build();
+            return Some((output, data.clone()));
         }
+        self.change_lock_script
+            .clone()
+            .or_else(|| {
+                self.capacity_provider
+                    .lock_scripts
+                    .first()
+                    .map(|(script, _, _)| script.clone())
+            })
+            .map(|lock_script| {
+                (
+                    CellOutput::new_builder().lock(lock_script).build(),
+                    Bytes::new(),
+                )
+            })
+    }
+
+    /// Set or clear the ttl of the locks of the selected cells, see `cell_lock_ttl`.
+    pub fn set_cell_lock_ttl(&mut self, ttl: Option<CellLockTtl>) {
+        self.cell_lock_ttl = ttl;
+    }
+
+    /// Whether to spend the unconfirmed cells, see `allow_unconfirmed`.
+    pub fn set_allow_unconfirmed(&mut self, allow_unconfirmed: bool) {
+        self.allow_unconfirmed = allow_unconfirmed;
+    }
+
+    /// Whether to add the headers of the input cells, see `input_header_deps`.
+    pub fn set_input_header_deps(&mut self, input_header_deps: bool) {
+        self.input_header_deps = input_header_deps;
     }
 
     /// Set or clear the force_small_change_as_fee
@@ -476,6 +1073,20 @@ impl CapacityBalancer {
         self.force_small_change_as_fee = max_fee;
     }
 
+    /// Set or clear the fee rate provider, when cleared the fixed `fee_rate` is used.
+    pub fn set_fee_rate_provider(&mut self, provider: Option<Box<dyn FeeRateProvider>>) {
+        self.fee_rate_provider = provider;
+    }
+
+    /// The fee rate used to balance the transaction.
+    pub fn current_fee_rate(&self) -> Result<FeeRate, BalanceTxCapacityError> {
+        if let Some(provider) = self.fee_rate_provider.as_ref() {
+            provider.fee_rate().map_err(BalanceTxCapacityError::FeeRate)
+        } else {
+            Ok(self.fee_rate)
+        }
+    }
+
     pub fn balance_tx_capacity(
         &mut self,
         tx: &TransactionView,
@@ -504,23 +1115,52 @@ impl CapacityBalancer {
         header_dep_resolver: &dyn HeaderDepResolver,
         accepted_min_fee: u64,
         change_index: Option<usize>,
+    ) -> Result<(TransactionView, Option<usize>), BalanceTxCapacityError> {
+        self.rebalance_with_fee_rate(
+            tx,
+            self.current_fee_rate()?,
+            cell_collector,
+            tx_dep_provider,
+            cell_dep_resolver,
+            header_dep_resolver,
+            accepted_min_fee,
+            change_index,
+        )
+    }
+
+    /// Same as `rebalance_tx_capacity`, with the fee rate read at the start of
+    /// the balance pass.
+    #[allow(clippy::too_many_arguments)]
+    fn rebalance_with_fee_rate(
+        &self,
+        tx: &TransactionView,
+        fee_rate: FeeRate,
+        cell_collector: &mut dyn CellCollector,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
+        cell_dep_resolver: &dyn CellDepResolver,
+        header_dep_resolver: &dyn HeaderDepResolver,
+        accepted_min_fee: u64,
+        change_index: Option<usize>,
     ) -> Result<(TransactionView, Option<usize>), BalanceTxCapacityError> {
         if let Some(idx) = change_index {
             let output = tx
                 .outputs()
                 .get(idx)
                 .ok_or(BalanceTxCapacityError::ChangeIndexNotFound(idx))?;
+            let data_len = tx
+                .outputs_data()
+                .get(idx)
+                .map(|data| data.raw_data().len())
+                .unwrap_or_default();
             let base_change_occupied_capacity = output
-                .occupied_capacity(Capacity::zero())
+                .occupied_capacity(Capacity::bytes(data_len).expect("change data capacity"))
                 .expect("init change occupied capacity")
                 .as_u64();
             let output_header_extra = 4 + 4 + 4;
-            // NOTE: extra_min_fee +1 is for `FeeRate::fee` round
-            let extra_min_fee = self
-                .fee_rate
-                .fee(output.as_slice().len() as u64 + output_header_extra)
-                .as_u64()
-                + 1;
+            let extra_min_fee = calc_fee(
+                output.as_slice().len() as u64 + output_header_extra,
+                fee_rate,
+            );
             let original_fee = tx_fee(tx.clone(), tx_dep_provider, header_dep_resolver)?;
             if original_fee >= accepted_min_fee {
                 return Err(BalanceTxCapacityError::AlreadyBalance(
@@ -546,6 +1186,7 @@ impl CapacityBalancer {
         rebalance_tx_capacity(
             tx,
             self,
+            fee_rate,
             cell_collector,
             tx_dep_provider,
             cell_dep_resolver,
@@ -566,20 +1207,22 @@ impl CapacityBalancer {
     ) -> Result<(TransactionView, Option<usize>, bool), BalanceTxCapacityError> {
         let cycle_resolver = CycleResolver::new(tx_dep_provider);
         let cycle = cycle_resolver.estimate_cycles(&tx)?;
-        let cycle_size = (cycle as f64 * bytes_per_cycle()) as usize;
-        let serialized_size = tx.data().as_reader().serialized_size_in_block();
+        let cycle_size = (cycle as f64 * bytes_per_cycle()) as u64;
+        let serialized_size = tx_size(&tx);
         if serialized_size >= cycle_size {
             return Ok((tx, None, true));
         }
         let fee = tx_fee(tx.clone(), tx_dep_provider, header_dep_resolver).unwrap();
-        let cycle_fee = self.fee_rate.fee(cycle_size as u64).as_u64();
+        let fee_rate = self.current_fee_rate()?;
+        let cycle_fee = calc_fee(cycle_size, fee_rate);
 
         if fee >= cycle_fee {
             return Ok((tx, None, true));
         }
 
-        let (tx, idx) = self.rebalance_tx_capacity(
+        let (tx, idx) = self.rebalance_with_fee_rate(
             &tx,
+            fee_rate,
             cell_collector,
             tx_dep_provider,
             cell_dep_resolver,
@@ -623,6 +1266,17 @@ impl<
     }
 
     fn estimate_cycles(&self, tx: &TransactionView) -> Result<u64, BalanceTxCapacityError> {
+        self.build_verifier(tx)?
+            .verify(u64::max_value())
+            .map_err(|err| {
+                BalanceTxCapacityError::VerifyScript(format!("Verify script error : {:?}", err))
+            })
+    }
+
+    fn build_verifier(
+        &self,
+        tx: &TransactionView,
+    ) -> Result<TransactionScriptsVerifier<DL>, BalanceTxCapacityError> {
         let rtx = resolve_transaction(
             tx.clone(),
             &mut HashSet::new(),
@@ -642,9 +1296,7 @@ impl<
         verifier.set_debug_printer(|script_hash, message| {
             println!("script: {:x}, debug: {}", script_hash, message);
         });
-        verifier.verify(u64::max_value()).map_err(|err| {
-            BalanceTxCapacityError::VerifyScript(format!("Verify script error : {:?}", err))
-        })
+        Ok(verifier)
     }
 }
 
@@ -660,6 +1312,7 @@ pub fn balance_tx_capacity(
     let (tx, _change_idx) = rebalance_tx_capacity(
         tx,
         balancer,
+        balancer.current_fee_rate()?,
         cell_collector,
         tx_dep_provider,
         cell_dep_resolver,
@@ -670,10 +1323,72 @@ pub fn balance_tx_capacity(
     Ok(tx)
 }
 
+/// Same as `balance_tx_capacity`, the cells are loaded by `tx_context` and
+/// the appended inputs are added to its script groups.
+pub fn balance_tx_capacity_with(
+    tx: &TransactionView,
+    balancer: &CapacityBalancer,
+    cell_collector: &mut dyn CellCollector,
+    tx_context: &mut TxContext,
+    cell_dep_resolver: &dyn CellDepResolver,
+    header_dep_resolver: &dyn HeaderDepResolver,
+) -> Result<TransactionView, BalanceTxCapacityError> {
+    let tx = balance_tx_capacity(
+        tx,
+        balancer,
+        cell_collector,
+        tx_context,
+        cell_dep_resolver,
+        header_dep_resolver,
+    )?;
+    tx_context.script_groups(&tx)?;
+    Ok(tx)
+}
+
 #[allow(clippy::too_many_arguments)]
 fn rebalance_tx_capacity(
     tx: &TransactionView,
     balancer: &CapacityBalancer,
+    fee_rate: FeeRate,
+    cell_collector: &mut dyn CellCollector,
+    tx_dep_provider: &dyn TransactionDependencyProvider,
+    cell_dep_resolver: &dyn CellDepResolver,
+    header_dep_resolver: &dyn HeaderDepResolver,
+    accepted_min_fee: u64,
+    change_index: Option<usize>,
+) -> Result<(TransactionView, Option<usize>), BalanceTxCapacityError> {
+    let (tx, change_index) = rebalance_tx_capacity_unchecked(
+        tx,
+        balancer,
+        fee_rate,
+        cell_collector,
+        tx_dep_provider,
+        cell_dep_resolver,
+        header_dep_resolver,
+        accepted_min_fee,
+        change_index,
+    )?;
+    if let Some(max_tx_size) = balancer
+        .fee_rate_provider
+        .as_ref()
+        .and_then(|provider| provider.max_tx_size())
+    {
+        let size = tx_size(&tx);
+        if size > max_tx_size {
+            return Err(BalanceTxCapacityError::TxSizeLimitExceeded(
+                size,
+                max_tx_size,
+            ));
+        }
+    }
+    Ok((tx, change_index))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn rebalance_tx_capacity_unchecked(
+    tx: &TransactionView,
+    balancer: &CapacityBalancer,
+    fee_rate: FeeRate,
     cell_collector: &mut dyn CellCollector,
     tx_dep_provider: &dyn TransactionDependencyProvider,
     cell_dep_resolver: &dyn CellDepResolver,
@@ -685,47 +1400,57 @@ fn rebalance_tx_capacity(
     if capacity_provider.lock_scripts.is_empty() {
         return Err(BalanceTxCapacityError::EmptyCapacityProvider);
     }
-    let change_lock_script = balancer
-        .change_lock_script
-        .clone()
-        .unwrap_or_else(|| capacity_provider.lock_scripts[0].0.clone());
-    let (tx, base_change_output, base_change_occupied_capacity) = if let Some(idx) = change_index {
-        let outputs = tx.outputs();
+    // the reserved trailing witnesses are put back after the new witnesses
+    let tx = &balancer.trailing_witnesses.strip(tx);
+    let (tx, base_change_output, base_change_data) = if let Some(idx) = change_index {
         let output = tx
             .outputs()
             .get(idx)
             .ok_or(BalanceTxCapacityError::ChangeIndexNotFound(idx))?;
+        let data = tx
+            .outputs_data()
+            .get(idx)
+            .map(|data| data.raw_data())
+            .unwrap_or_default();
 
         // remove change output
-        let outputs: Vec<_> = outputs
+        let (outputs, outputs_data): (Vec<_>, Vec<_>) = tx
+            .outputs()
             .into_iter()
+            .zip(tx.outputs_data())
             .enumerate()
-            .filter_map(|(i, output)| if idx == i { None } else { Some(output) })
-            .collect();
-        let base_change_occupied_capacity = output
-            .occupied_capacity(Capacity::zero())
-            .expect("init change occupied capacity")
-            .as_u64();
-        let tx = tx.data().as_advanced_builder().set_outputs(outputs).build();
-        (tx, output, base_change_occupied_capacity)
+            .filter_map(|(i, cell)| if idx == i { None } else { Some(cell) })
+            .unzip();
+        let tx = tx
+            .data()
+            .as_advanced_builder()
+            .set_outputs(outputs)
+            .set_outputs_data(outputs_data)
+            .build();
+        (tx, output, data)
     } else {
-        let base_change_output = CellOutput::new_builder().lock(change_lock_script).build();
-        let base_change_occupied_capacity = base_change_output
-            .occupied_capacity(Capacity::zero())
-            .expect("init change occupied capacity")
-            .as_u64();
-        (
-            tx.clone(),
-            base_change_output,
-            base_change_occupied_capacity,
-        )
+        let (output, data) = balancer
+            .base_change_cell()
+            .expect("capacity provider is not empty");
+        (tx.clone(), output, data)
     };
+    // the change cell must hold the occupied capacity of its lock script,
+    // type script and data
+    let base_change_occupied_capacity = base_change_output
+        .occupied_capacity(Capacity::bytes(base_change_data.len()).expect("change data capacity"))
+        .expect("init change occupied capacity")
+        .as_u64();
 
     let mut lock_scripts = Vec::new();
     // remove duplicated lock script
     for (script, placeholder, since_source) in &capacity_provider.lock_scripts {
         if lock_scripts.iter().all(|(target, _, _)| target != script) {
-            lock_scripts.push((script.clone(), placeholder.clone(), since_source.clone()));
+            // the witnesses of the policy `PerInput` are filled when applying the policies
+            let placeholder = match capacity_provider.placeholder_policy(script) {
+                Some(policy) => policy.first_placeholder().cloned(),
+                None => Some(placeholder.clone()),
+            };
+            lock_scripts.push((script.clone(), placeholder, since_source.clone()));
         }
     }
     let mut lock_script_idx = 0;
@@ -740,12 +1465,14 @@ fn rebalance_tx_capacity(
     };
     let mut changed_witnesses: HashMap<usize, WitnessArgs> = HashMap::default();
     let mut witnesses = Vec::new();
+    let mut change_rounds = 0;
     loop {
         let (lock_script, placeholder_witness, since_source) = &lock_scripts[lock_script_idx];
         let base_query = {
             let mut query = CellQueryOptions::new_lock(lock_script.clone());
             query.secondary_script_len_range = Some(ValueRangeOption::new_exact(0));
             query.data_len_range = Some(ValueRangeOption::new_exact(0));
+            query.allow_unconfirmed = balancer.allow_unconfirmed && !balancer.input_header_deps;
             query
         };
         // check if capacity provider lock script already in inputs
@@ -768,6 +1495,7 @@ fn rebalance_tx_capacity(
                 all_witnesses[*idx] = witness_args.as_bytes().pack();
             }
             all_witnesses.extend(witnesses.clone());
+            all_witnesses.extend(balancer.trailing_witnesses.witnesses());
             let output_len = tx.outputs().len();
             let mut builder = tx
                 .data()
@@ -777,12 +1505,11 @@ fn rebalance_tx_capacity(
                 .set_witnesses(all_witnesses);
             if let Some(output) = change_output.clone() {
                 ret_change_index = Some(output_len);
-                builder = builder.output(output).output_data(Default::default());
+                builder = builder.output(output).output_data(base_change_data.pack());
             }
-            builder.build()
+            capacity_provider.apply_placeholder_policies(&builder.build(), tx_dep_provider)?
         };
-        let tx_size = new_tx.data().as_reader().serialized_size_in_block();
-        let min_fee = accepted_min_fee.max(balancer.fee_rate.fee(tx_size as u64).as_u64());
+        let min_fee = accepted_min_fee.max(calc_fee(tx_size(&new_tx), fee_rate));
         let mut need_more_capacity = 1;
         let fee_result: Result<u64, TransactionFeeError> =
             tx_fee(new_tx.clone(), tx_dep_provider, header_dep_resolver);
@@ -792,6 +1519,13 @@ fn rebalance_tx_capacity(
             }
             Ok(fee) if fee > min_fee => {
                 let delta = fee - min_fee;
+                if change_output.is_none()
+                    && tx.outputs().is_empty()
+                    && balancer.is_burn_confirmed(fee)
+                {
+                    // An intended burn, all the extra capacity is the fee
+                    return Ok((new_tx, ret_change_index));
+                }
                 if let Some(output) = change_output.take() {
                     // If change cell already exits, just change the capacity field
                     let old_capacity: u64 = output.capacity().unpack();
@@ -804,25 +1538,30 @@ fn rebalance_tx_capacity(
                 } else {
                     // If change cell not exists, add a change cell.
 
-                    // The output extra header size is for:
-                    //   * first 4 bytes is for output data header (the length)
-                    //   * second 4 bytes if for output data offset
-                    //   * third 4 bytes is for output offset
-                    let output_header_extra = 4 + 4 + 4;
-                    // NOTE: extra_min_fee +1 is for `FeeRate::fee` round
-                    let extra_min_fee = balancer
-                        .fee_rate
-                        .fee(base_change_output.as_slice().len() as u64 + output_header_extra)
-                        .as_u64()
-                        + 1;
-                    // The extra capacity (delta - extra_min_fee) is enough to hold the change cell.
-                    if delta >= base_change_occupied_capacity + extra_min_fee {
+                    // The min fee is calculated from the serialized size of the
+                    // transaction with the change cell, the capacity of the
+                    // change cell does not change the size.
+                    let change_tx = new_tx
+                        .as_advanced_builder()
+                        .output(
+                            base_change_output
+                                .clone()
+                                .as_builder()
+                                .capacity(base_change_occupied_capacity.pack())
+                                .build(),
+                        )
+                        .output_data(base_change_data.pack())
+                        .build();
+                    let change_min_fee =
+                        accepted_min_fee.max(calc_fee(tx_size(&change_tx), fee_rate));
+                    // The extra capacity (fee - change_min_fee) is enough to hold the change cell.
+                    if fee >= base_change_occupied_capacity + change_min_fee {
                         // next loop round must return new_tx;
                         change_output = Some(
                             base_change_output
                                 .clone()
                                 .as_builder()
-                                .capacity((delta - extra_min_fee).pack())
+                                .capacity((fee - change_min_fee).pack())
                                 .build(),
                         );
                         need_more_capacity = 0;
@@ -830,12 +1569,17 @@ fn rebalance_tx_capacity(
                         // peek if there is more live cell owned by this capacity provider
                         let (more_cells, _more_capacity) =
                             cell_collector.collect_live_cells(&base_query, false)?;
-                        if more_cells.is_empty() {
+                        if more_cells
+                            .iter()
+                            .all(|cell| is_tx_input(tx, &inputs, &cell.out_point))
+                        {
                             if let Some(capacity) = balancer.force_small_change_as_fee {
                                 if fee > capacity {
                                     return Err(
                                         BalanceTxCapacityError::ForceSmallChangeAsFeeFailed(fee),
                                     );
+                                } else if tx.outputs().is_empty() {
+                                    return Err(BalanceTxCapacityError::BurnNotConfirmed(fee));
                                 } else {
                                     return Ok((new_tx, ret_change_index));
                                 }
@@ -863,7 +1607,27 @@ fn rebalance_tx_capacity(
             }
             // fee is positive and `fee < min_fee`
             Ok(fee) => {
-                need_more_capacity = min_fee - fee;
+                let shortfall = min_fee - fee;
+                match change_output.take() {
+                    // Take the shortfall from the change cell if it can afford
+                    Some(output)
+                        if Unpack::<u64>::unpack(&output.capacity())
+                            >= base_change_occupied_capacity + shortfall =>
+                    {
+                        let old_capacity: u64 = output.capacity().unpack();
+                        change_output = Some(
+                            output
+                                .as_builder()
+                                .capacity((old_capacity - shortfall).pack())
+                                .build(),
+                        );
+                        need_more_capacity = 0;
+                    }
+                    output => {
+                        change_output = output;
+                        need_more_capacity = shortfall;
+                    }
+                }
             }
             Err(TransactionFeeError::CapacityOverflow(delta)) => {
                 need_more_capacity = delta.checked_add(min_fee).ok_or_else(|| {
@@ -877,7 +1641,14 @@ fn rebalance_tx_capacity(
                 return Err(err.into());
             }
         }
-        if need_more_capacity > 0 {
+        if need_more_capacity == 0 {
+            // only the change cell is adjusted, the size of the transaction
+            // is the same in the next round
+            change_rounds += 1;
+            if change_rounds > MAX_CHANGE_ROUNDS {
+                return Err(BalanceTxCapacityError::FeeNotConverged(change_rounds));
+            }
+        } else {
             let query = {
                 let mut query = base_query.clone();
                 query.min_total_capacity = need_more_capacity;
@@ -895,21 +1666,38 @@ fn rebalance_tx_capacity(
                     continue;
                 }
             }
+            // The cells given by the builder may be collected again, they are
+            // locked by the collector now, collect more in the next round.
+            let more_cells: Vec<_> = more_cells
+                .into_iter()
+                .filter(|cell| !is_tx_input(tx, &inputs, &cell.out_point))
+                .collect();
+            if more_cells.is_empty() {
+                continue;
+            }
+            if let Some(ttl) = balancer.cell_lock_ttl {
+                for cell in &more_cells {
+                    cell_collector.lock_cell_with_ttl(cell.out_point.clone(), ttl)?;
+                }
+            }
             if !resolved_scripts.contains(lock_script) {
-                let provider_cell_dep =
-                    cell_dep_resolver.resolve(lock_script).ok_or_else(|| {
+                let provider_cell_deps =
+                    cell_dep_resolver.resolve_all(lock_script).ok_or_else(|| {
                         BalanceTxCapacityError::ResolveCellDepFailed(lock_script.clone())
                     })?;
-                if tx
-                    .cell_deps()
-                    .into_iter()
-                    .all(|cell_dep| cell_dep != provider_cell_dep)
-                {
-                    cell_deps.push(provider_cell_dep);
-                    resolved_scripts.insert(lock_script);
+                for provider_cell_dep in provider_cell_deps {
+                    if !cell_deps.contains(&provider_cell_dep)
+                        && tx
+                            .cell_deps()
+                            .into_iter()
+                            .all(|cell_dep| cell_dep != provider_cell_dep)
+                    {
+                        cell_deps.push(provider_cell_dep);
+                    }
                 }
+                resolved_scripts.insert(lock_script);
             }
-            if !has_provider {
+            if let (false, Some(placeholder_witness)) = (has_provider, placeholder_witness) {
                 if tx.witnesses().item_count() > tx.inputs().item_count() + inputs.len() {
                     let idx = tx.inputs().item_count() + inputs.len();
                     let witness_data = tx.witnesses().get(idx).expect("get witness").raw_data();
@@ -920,22 +1708,23 @@ fn rebalance_tx_capacity(
                         WitnessArgs::from_slice(witness_data.as_ref())
                             .map_err(|err| BalanceTxCapacityError::InvalidWitnessArgs(err.into()))?
                     };
-                    if let Some(data) = placeholder_witness.input_type().to_opt() {
+                    // only the empty fields are filled by the placeholder
+                    if witness.input_type().is_none() {
                         witness = witness
                             .as_builder()
-                            .input_type(Some(data.raw_data()).pack())
+                            .input_type(placeholder_witness.input_type())
                             .build();
                     }
-                    if let Some(data) = placeholder_witness.output_type().to_opt() {
+                    if witness.output_type().is_none() {
                         witness = witness
                             .as_builder()
-                            .output_type(Some(data.raw_data()).pack())
+                            .output_type(placeholder_witness.output_type())
                             .build();
                     }
-                    if let Some(data) = placeholder_witness.lock().to_opt() {
+                    if witness.lock().is_none() {
                         witness = witness
                             .as_builder()
-                            .lock(Some(data.raw_data()).pack())
+                            .lock(placeholder_witness.lock())
                             .build();
                     }
                     changed_witnesses.insert(idx, witness);
@@ -956,7 +1745,7 @@ fn rebalance_tx_capacity(
                     since_bytes.copy_from_slice(&lock_arg[*offset..*offset + 8]);
                     u64::from_le_bytes(since_bytes)
                 }
-                SinceSource::Value(since_value) => *since_value,
+                SinceSource::Value(since) => since.value(),
             };
             inputs.extend(
                 more_cells
@@ -967,6 +1756,13 @@ fn rebalance_tx_capacity(
     }
 }
 
+fn is_tx_input(tx: &TransactionView, inputs: &[CellInput], out_point: &OutPoint) -> bool {
+    tx.input_pts_iter()
+        .chain(inputs.iter().map(|input| input.previous_output()))
+        .any(|input| input == *out_point)
+}
+
+#[derive(Clone, Default)]
 pub struct ScriptGroups {
     pub lock_groups: HashMap<Byte32, ScriptGroup>,
     pub type_groups: HashMap<Byte32, ScriptGroup>,
@@ -1015,15 +1811,29 @@ pub fn gen_script_groups(
 pub fn fill_placeholder_witnesses(
     balanced_tx: TransactionView,
     tx_dep_provider: &dyn TransactionDependencyProvider,
-    unlockers: &HashMap<ScriptId, Box<dyn ScriptUnlocker>>,
+    unlockers: &dyn UnlockerProvider,
+) -> Result<(TransactionView, Vec<ScriptGroup>), UnlockError> {
+    fill_placeholder_witnesses_with(balanced_tx, &mut TxContext::new(tx_dep_provider), unlockers)
+}
+
+/// Same as `fill_placeholder_witnesses`, the cells and the script groups are
+/// from `tx_context`.
+pub fn fill_placeholder_witnesses_with(
+    balanced_tx: TransactionView,
+    tx_context: &mut TxContext,
+    unlockers: &dyn UnlockerProvider,
 ) -> Result<(TransactionView, Vec<ScriptGroup>), UnlockError> {
-    let ScriptGroups { lock_groups, .. } = gen_script_groups(&balanced_tx, tx_dep_provider)?;
+    let ScriptGroups { lock_groups, .. } = tx_context.script_groups(&balanced_tx)?;
+    let tx_dep_provider: &dyn TransactionDependencyProvider = &*tx_context;
     let mut tx = balanced_tx;
     let mut not_matched = Vec::new();
     for script_group in lock_groups.values() {
         let script_id = ScriptId::from(&script_group.script);
         let script_args = script_group.script.args().raw_data();
-        if let Some(unlocker) = unlockers.get(&script_id) {
+        if let Some(unlocker) = unlockers
+            .find_by_args(&script_id, script_args.as_ref())
+            .or_else(|| unlockers.get(&script_id))
+        {
             if !unlocker.is_unlocked(&tx, script_group, tx_dep_provider)? {
                 if unlocker.match_args(script_args.as_ref()) {
                     tx = unlocker.fill_placeholder_witness(&tx, script_group, tx_dep_provider)?;
@@ -1038,6 +1848,19 @@ pub fn fill_placeholder_witnesses(
     Ok((tx, not_matched))
 }
 
+/// Same as `fill_placeholder_witnesses`, then the placeholder policies of
+/// `capacity_provider` are applied, see `CapacityProvider::set_placeholder_policy`.
+pub fn fill_placeholder_witnesses_with_policies(
+    balanced_tx: TransactionView,
+    tx_dep_provider: &dyn TransactionDependencyProvider,
+    unlockers: &dyn UnlockerProvider,
+    capacity_provider: &CapacityProvider,
+) -> Result<(TransactionView, Vec<ScriptGroup>), UnlockError> {
+    let (tx, not_matched) = fill_placeholder_witnesses(balanced_tx, tx_dep_provider, unlockers)?;
+    let tx = capacity_provider.apply_placeholder_policies(&tx, tx_dep_provider)?;
+    Ok((tx, not_matched))
+}
+
 /// Build unlocked transaction that ready to send or for further unlock.
 ///
 /// Return value:
@@ -1046,29 +1869,171 @@ pub fn fill_placeholder_witnesses(
 pub fn unlock_tx(
     balanced_tx: TransactionView,
     tx_dep_provider: &dyn TransactionDependencyProvider,
-    unlockers: &HashMap<ScriptId, Box<dyn ScriptUnlocker>>,
+    unlockers: &dyn UnlockerProvider,
 ) -> Result<(TransactionView, Vec<ScriptGroup>), UnlockError> {
-    let ScriptGroups { lock_groups, .. } = gen_script_groups(&balanced_tx, tx_dep_provider)?;
-    let mut tx = balanced_tx;
+    unlock_tx_with(balanced_tx, &mut TxContext::new(tx_dep_provider), unlockers)
+}
+
+/// Same as `unlock_tx`, the cells and the script groups are from `tx_context`.
+pub fn unlock_tx_with(
+    balanced_tx: TransactionView,
+    tx_context: &mut TxContext,
+    unlockers: &dyn UnlockerProvider,
+) -> Result<(TransactionView, Vec<ScriptGroup>), UnlockError> {
+    unlock_tx_inner(balanced_tx, tx_context, unlockers, false)
+}
+
+/// Same as `unlock_tx`, except that when the unlocker of a script group is
+/// found but its `match_args` rejects the group's args (e.g. a wrong code hash
+/// is used for the `ScriptId`), `UnlockError::UnlockerArgsMismatch` is returned
+/// instead of leaving the group not unlocked.
+pub fn unlock_tx_checked(
+    balanced_tx: TransactionView,
+    tx_dep_provider: &dyn TransactionDependencyProvider,
+    unlockers: &dyn UnlockerProvider,
+) -> Result<(TransactionView, Vec<ScriptGroup>), UnlockError> {
+    unlock_tx_inner(
+        balanced_tx,
+        &mut TxContext::new(tx_dep_provider),
+        unlockers,
+        true,
+    )
+}
+
+fn unlock_tx_inner(
+    balanced_tx: TransactionView,
+    tx_context: &mut TxContext,
+    unlockers: &dyn UnlockerProvider,
+    check_args: bool,
+) -> Result<(TransactionView, Vec<ScriptGroup>), UnlockError> {
+    let (tx, results) = unlock_tx_detailed_with(balanced_tx, tx_context, unlockers)?;
     let mut not_unlocked = Vec::new();
-    for script_group in lock_groups.values() {
-        let script_id = ScriptId::from(&script_group.script);
-        let script_args = script_group.script.args().raw_data();
-        if let Some(unlocker) = unlockers.get(&script_id) {
-            if unlocker.is_unlocked(&tx, script_group, tx_dep_provider)? {
-                tx = unlocker.clear_placeholder_witness(&tx, script_group)?;
-            } else if unlocker.match_args(script_args.as_ref()) {
-                tx = unlocker.unlock(&tx, script_group, tx_dep_provider)?;
-            } else {
-                not_unlocked.push(script_group.clone());
+    for result in results {
+        match result.status {
+            UnlockGroupStatus::Unlocked | UnlockGroupStatus::AlreadyUnlocked => {}
+            UnlockGroupStatus::NoUnlockerMatched { script_id, .. } => {
+                if check_args && unlockers.get(&script_id).is_some() {
+                    return Err(UnlockError::UnlockerArgsMismatch { script_id });
+                }
+                not_unlocked.push(result.script_group);
             }
-        } else {
-            not_unlocked.push(script_group.clone());
+            UnlockGroupStatus::Failed(err) => return Err(err),
         }
     }
     Ok((tx, not_unlocked))
 }
 
+/// The unlock status of a script group
+#[derive(Debug)]
+pub enum UnlockGroupStatus {
+    /// Unlocked by the unlocker
+    Unlocked,
+    /// Already unlocked before, the placeholder witness is cleared
+    AlreadyUnlocked,
+    /// No unlocker is registered for the script id, or the unlocker does not
+    /// accept the args
+    NoUnlockerMatched { script_id: ScriptId, args: Bytes },
+    /// The unlocker failed, the transaction is not changed for this group
+    Failed(UnlockError),
+}
+
+/// The unlock result of a lock script group
+#[derive(Debug)]
+pub struct UnlockGroupResult {
+    pub script_group: ScriptGroup,
+    pub status: UnlockGroupStatus,
+}
+
+impl UnlockGroupResult {
+    pub fn is_unlocked(&self) -> bool {
+        matches!(
+            self.status,
+            UnlockGroupStatus::Unlocked | UnlockGroupStatus::AlreadyUnlocked
+        )
+    }
+}
+
+/// Same as `unlock_tx`, but an unlocker error does not abort the unlocking:
+/// the successful unlocks are applied, the failed groups are left untouched
+/// and reported with the error, so the caller can retry only those groups.
+///
+/// Return value:
+///   * The transaction with all the successful unlocks applied
+///   * The result of every lock script group
+pub fn unlock_tx_detailed(
+    balanced_tx: TransactionView,
+    tx_dep_provider: &dyn TransactionDependencyProvider,
+    unlockers: &dyn UnlockerProvider,
+) -> Result<(TransactionView, Vec<UnlockGroupResult>), UnlockError> {
+    unlock_tx_detailed_with(balanced_tx, &mut TxContext::new(tx_dep_provider), unlockers)
+}
+
+fn unlock_tx_detailed_with(
+    balanced_tx: TransactionView,
+    tx_context: &mut TxContext,
+    unlockers: &dyn UnlockerProvider,
+) -> Result<(TransactionView, Vec<UnlockGroupResult>), UnlockError> {
+    let ScriptGroups { lock_groups, .. } = tx_context.script_groups(&balanced_tx)?;
+    let tx_dep_provider: &dyn TransactionDependencyProvider = &*tx_context;
+    let mut tx = balanced_tx;
+    let mut results = Vec::with_capacity(lock_groups.len());
+    for script_group in lock_groups.into_values() {
+        let (new_tx, status) = unlock_group_status(&tx, &script_group, unlockers, tx_dep_provider);
+        if let Some(new_tx) = new_tx {
+            tx = new_tx;
+        }
+        results.push(UnlockGroupResult {
+            script_group,
+            status,
+        });
+    }
+    Ok((tx, results))
+}
+
+/// Find the unlocker of the script group and unlock it, the unlocked
+/// transaction is returned when the group is (or was already) unlocked.
+fn unlock_group_status(
+    tx: &TransactionView,
+    script_group: &ScriptGroup,
+    unlockers: &dyn UnlockerProvider,
+    tx_dep_provider: &dyn TransactionDependencyProvider,
+) -> (Option<TransactionView>, UnlockGroupStatus) {
+    let script_id = ScriptId::from(&script_group.script);
+    let script_args = script_group.script.args().raw_data();
+    let unlocker = unlockers
+        .find_by_args(&script_id, script_args.as_ref())
+        .or_else(|| unlockers.get(&script_id));
+    match unlocker.map(|unlocker| unlock_group(tx, script_group, unlocker, tx_dep_provider)) {
+        Some(Ok(Some((new_tx, status)))) => (Some(new_tx), status),
+        Some(Err(err)) => (None, UnlockGroupStatus::Failed(err)),
+        Some(Ok(None)) | None => (
+            None,
+            UnlockGroupStatus::NoUnlockerMatched {
+                script_id,
+                args: script_args,
+            },
+        ),
+    }
+}
+
+/// Unlock one script group, `None` if the unlocker does not accept the args
+fn unlock_group(
+    tx: &TransactionView,
+    script_group: &ScriptGroup,
+    unlocker: &dyn ScriptUnlocker,
+    tx_dep_provider: &dyn TransactionDependencyProvider,
+) -> Result<Option<(TransactionView, UnlockGroupStatus)>, UnlockError> {
+    if unlocker.is_unlocked(tx, script_group, tx_dep_provider)? {
+        let tx = unlocker.clear_placeholder_witness(tx, script_group)?;
+        Ok(Some((tx, UnlockGroupStatus::AlreadyUnlocked)))
+    } else if unlocker.match_args(script_group.script.args().raw_data().as_ref()) {
+        let tx = unlocker.unlock(tx, script_group, tx_dep_provider)?;
+        Ok(Some((tx, UnlockGroupStatus::Unlocked)))
+    } else {
+        Ok(None)
+    }
+}
+
 #[cfg(test)]
 mod anyhow_tests {
     use anyhow::anyhow;
@@ -1083,6 +2048,16 @@ mod anyhow_tests {
         );
     }
 
+    #[test]
+    fn test_amount_overflow_error() {
+        let error = super::TxBuilderError::AmountOverflow(u128::MAX, 1);
+        let error = anyhow!(error);
+        assert_eq!(
+            "amount overflow: `340282366920938463463374607431768211455` + `1`",
+            error.to_string()
+        );
+    }
+
     #[test]
     fn test_transaction_fee_error() {
         let error = super::TransactionFeeError::CapacityOverflow(0);
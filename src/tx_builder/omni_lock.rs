@@ -1,14 +1,12 @@
-use std::collections::HashSet;
-
 use ckb_types::{
     bytes::Bytes,
     core::{DepType, TransactionBuilder, TransactionView},
-    packed::{CellDep, CellInput, CellOutput, OutPoint},
+    packed::{CellDep, CellInput, CellOutput, OutPoint, Script},
     prelude::*,
 };
 
-use super::{TxBuilder, TxBuilderError};
-use crate::types::ScriptId;
+use super::{extend_unique, push_unique, TxBuilder, TxBuilderError};
+use crate::types::{ScriptHashTypeExt, ScriptId};
 use crate::{
     traits::{CellCollector, CellDepResolver, HeaderDepResolver, TransactionDependencyProvider},
     unlock::OmniLockConfig,
@@ -43,10 +41,8 @@ impl TxBuilder for OmniLockTransferBuilder {
         _header_dep_resolver: &dyn HeaderDepResolver,
         tx_dep_provider: &dyn TransactionDependencyProvider,
     ) -> Result<TransactionView, TxBuilderError> {
-        #[allow(clippy::mutable_key_type)]
-        let mut cell_deps = HashSet::new();
-        #[allow(clippy::mutable_key_type)]
-        let mut inputs = HashSet::new();
+        let mut cell_deps = Vec::new();
+        let mut inputs = Vec::new();
         let mut outputs = Vec::new();
         let mut outputs_data = Vec::new();
         for (output, output_data) in &self.outputs {
@@ -55,13 +51,27 @@ impl TxBuilder for OmniLockTransferBuilder {
             if let Some(type_script) = output.type_().to_opt() {
                 let script_id = ScriptId::from(&type_script);
                 if !script_id.is_type_id() {
-                    let cell_dep = cell_dep_resolver
-                        .resolve(&type_script)
+                    let type_cell_deps = cell_dep_resolver
+                        .resolve_all(&type_script)
                         .ok_or(TxBuilderError::ResolveCellDepFailed(type_script))?;
-                    cell_deps.insert(cell_dep);
+                    extend_unique(&mut cell_deps, type_cell_deps);
                 }
             }
         }
+        // The exec or dl auth script loaded from a cell dep
+        if let Some(exec_dl_config) = self.cfg.get_exec_dl_config() {
+            if exec_dl_config.place() == 0 {
+                let auth_id = exec_dl_config.script_id();
+                let auth_script = Script::new_builder()
+                    .code_hash(auth_id.code_hash.pack())
+                    .hash_type(auth_id.hash_type.to_packed())
+                    .build();
+                let auth_cell_deps = cell_dep_resolver
+                    .resolve_all(&auth_script)
+                    .ok_or(TxBuilderError::ResolveCellDepFailed(auth_script))?;
+                extend_unique(&mut cell_deps, auth_cell_deps);
+            }
+        }
         if let Some(admin_cfg) = self.cfg.get_admin_config() {
             if let Some(rce_cells) = self.rce_cells.as_ref() {
                 if admin_cfg.rce_in_input() {
@@ -69,17 +79,17 @@ impl TxBuilder for OmniLockTransferBuilder {
                         let input = CellInput::new_builder()
                             .previous_output(cell.clone())
                             .build();
-                        inputs.insert(input);
+                        push_unique(&mut inputs, input);
                         let cell_output = tx_dep_provider.get_cell(cell)?;
                         // extract lock dep
                         let lock = cell_output.lock();
-                        if let Some(cell_dep) = cell_dep_resolver.resolve(&lock) {
-                            cell_deps.insert(cell_dep);
+                        if let Some(lock_cell_deps) = cell_dep_resolver.resolve_all(&lock) {
+                            extend_unique(&mut cell_deps, lock_cell_deps);
                         }
                         // extract type dependency
                         if let Some(type_) = cell_output.type_().to_opt() {
-                            if let Some(cell_dep) = cell_dep_resolver.resolve(&type_) {
-                                cell_deps.insert(cell_dep);
+                            if let Some(type_cell_deps) = cell_dep_resolver.resolve_all(&type_) {
+                                extend_unique(&mut cell_deps, type_cell_deps);
                             }
                         }
                     }
@@ -89,15 +99,15 @@ impl TxBuilder for OmniLockTransferBuilder {
                             .out_point(cell.clone())
                             .dep_type(DepType::Code.into())
                             .build();
-                        cell_deps.insert(cell_dep);
+                        push_unique(&mut cell_deps, cell_dep);
                     }
                 }
             }
         }
         Ok(TransactionBuilder::default()
-            .set_cell_deps(cell_deps.into_iter().collect())
+            .set_cell_deps(cell_deps)
             .set_outputs(outputs)
-            .set_inputs(inputs.into_iter().collect())
+            .set_inputs(inputs)
             .set_outputs_data(outputs_data)
             .build())
     }
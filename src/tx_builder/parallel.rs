@@ -0,0 +1,150 @@
+use std::panic;
+use std::thread;
+
+use ckb_types::{core::TransactionView, packed, prelude::*};
+
+use super::{
+    gen_script_groups, unlock_group_status, ScriptGroups, UnlockGroupResult, UnlockGroupStatus,
+};
+use crate::traits::TransactionDependencyProvider;
+use crate::types::ScriptGroup;
+use crate::unlock::{UnlockError, UnlockerProvider};
+
+/// Same as `unlock_tx`, the lock script groups are unlocked concurrently in
+/// scoped threads, one thread per available cpu.
+///
+/// Every thread builds its own unlockers by `build_unlockers`, so the
+/// unlockers and their signers need not be `Send` or `Sync`. Each group is
+/// unlocked against `balanced_tx`, then the witnesses of the groups are
+/// merged in the order of their first inputs. A group whose unlocker changed
+/// anything other than the witnesses of its own inputs (e.g. appended a
+/// witness) is unlocked again on the merged transaction, one by one after
+/// the others.
+pub fn unlock_tx_parallel<F, U>(
+    balanced_tx: TransactionView,
+    tx_dep_provider: &dyn TransactionDependencyProvider,
+    build_unlockers: F,
+) -> Result<(TransactionView, Vec<ScriptGroup>), UnlockError>
+where
+    F: Fn() -> U + Sync,
+    U: UnlockerProvider,
+{
+    let ScriptGroups { lock_groups, .. } = gen_script_groups(&balanced_tx, tx_dep_provider)?;
+    let mut groups: Vec<ScriptGroup> = lock_groups.into_values().collect();
+    groups.sort_by_key(|group| group.input_indices[0]);
+    let threads = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(groups.len())
+        .max(1);
+
+    let mut outcomes = thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|worker| {
+                let (base_tx, groups, build_unlockers) = (&balanced_tx, &groups, &build_unlockers);
+                scope.spawn(move || {
+                    let unlockers = build_unlockers();
+                    groups
+                        .iter()
+                        .enumerate()
+                        .skip(worker)
+                        .step_by(threads)
+                        .map(|(idx, group)| {
+                            let (new_tx, status) =
+                                unlock_group_status(base_tx, group, &unlockers, tx_dep_provider);
+                            (idx, new_tx, status)
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|err| panic::resume_unwind(err))
+            })
+            .collect::<Vec<_>>()
+    });
+    outcomes.sort_by_key(|(idx, _, _)| *idx);
+
+    let mut witnesses: Vec<packed::Bytes> = balanced_tx.witnesses().into_iter().collect();
+    let mut results = Vec::with_capacity(groups.len());
+    let mut retry_groups = Vec::new();
+    for ((_, new_tx, status), script_group) in outcomes.into_iter().zip(groups) {
+        if let Some(new_tx) = new_tx {
+            match changed_witnesses(&balanced_tx, &new_tx, &script_group) {
+                Some(changed) => {
+                    for (idx, witness) in changed {
+                        if idx >= witnesses.len() {
+                            witnesses.resize(idx + 1, packed::Bytes::default());
+                        }
+                        witnesses[idx] = witness;
+                    }
+                }
+                None => {
+                    retry_groups.push(script_group);
+                    continue;
+                }
+            }
+        }
+        results.push(UnlockGroupResult {
+            script_group,
+            status,
+        });
+    }
+    let mut tx = balanced_tx
+        .as_advanced_builder()
+        .set_witnesses(witnesses)
+        .build();
+    if !retry_groups.is_empty() {
+        let unlockers = build_unlockers();
+        for script_group in retry_groups {
+            let (new_tx, status) =
+                unlock_group_status(&tx, &script_group, &unlockers, tx_dep_provider);
+            if let Some(new_tx) = new_tx {
+                tx = new_tx;
+            }
+            results.push(UnlockGroupResult {
+                script_group,
+                status,
+            });
+        }
+    }
+
+    let mut not_unlocked = Vec::new();
+    for result in results {
+        match result.status {
+            UnlockGroupStatus::Unlocked | UnlockGroupStatus::AlreadyUnlocked => {}
+            UnlockGroupStatus::NoUnlockerMatched { .. } => not_unlocked.push(result.script_group),
+            UnlockGroupStatus::Failed(err) => return Err(err),
+        }
+    }
+    Ok((tx, not_unlocked))
+}
+
+/// The witnesses changed by unlocking `script_group`, `None` if anything
+/// other than the witnesses of the group's inputs is changed.
+fn changed_witnesses(
+    base_tx: &TransactionView,
+    new_tx: &TransactionView,
+    script_group: &ScriptGroup,
+) -> Option<Vec<(usize, packed::Bytes)>> {
+    if new_tx.hash() != base_tx.hash() {
+        return None;
+    }
+    let (old_witnesses, new_witnesses) = (base_tx.witnesses(), new_tx.witnesses());
+    let mut changed = Vec::new();
+    for idx in 0..old_witnesses.len().max(new_witnesses.len()) {
+        let old_witness = old_witnesses.get(idx).unwrap_or_default();
+        let new_witness = new_witnesses.get(idx).unwrap_or_default();
+        if old_witness.as_slice() != new_witness.as_slice() {
+            if !script_group.input_indices.contains(&idx) {
+                return None;
+            }
+            changed.push((idx, new_witness));
+        }
+    }
+    Some(changed)
+}
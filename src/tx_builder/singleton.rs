@@ -0,0 +1,226 @@
+use std::thread;
+use std::time::Duration;
+
+use ckb_types::{
+    bytes::Bytes,
+    core::TransactionView,
+    packed::{CellOutput, OutPoint, Script},
+    prelude::*,
+    H256,
+};
+use thiserror::Error;
+
+use super::{type_id::build_update_tx, TxBuilder, TxBuilderError};
+use crate::rpc::{SendTransactionRpc, TransactionSubmitError};
+use crate::traits::{
+    CellCollector, CellCollectorError, CellDepResolver, CellQueryOptions, HeaderDepResolver,
+    TransactionDependencyProvider,
+};
+
+#[derive(Error, Debug)]
+pub enum SingletonError {
+    #[error("singleton cell not found by type script: `{0}`")]
+    NotFound(Script),
+
+    #[error("cell collector error: `{0}`")]
+    CellCollector(#[from] CellCollectorError),
+
+    #[error("build transaction error: `{0}`")]
+    Build(anyhow::Error),
+
+    #[error("the transaction does not spend the singleton cell: `{0}`")]
+    NotSpendingSingleton(OutPoint),
+
+    #[error(transparent)]
+    Submit(#[from] TransactionSubmitError),
+
+    #[error("the singleton cell is still updated by others after `{0}` attempts")]
+    ExceedMaxAttempts(usize),
+}
+
+/// The current version of a singleton cell
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SingletonHandle {
+    pub out_point: OutPoint,
+    pub output: CellOutput,
+    pub data: Bytes,
+    /// The block number of the cell, a newer version is always in a later block
+    pub version_hint: u64,
+}
+
+/// The delay between two attempts of `SingletonCellUpdater::submit_with_retry`,
+/// starts from `initial_delay` and doubled after every attempt up to `max_delay`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct RetryBackoff {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryBackoff {
+    fn default() -> Self {
+        RetryBackoff {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(8),
+        }
+    }
+}
+
+impl RetryBackoff {
+    /// Retry at once
+    pub fn none() -> RetryBackoff {
+        RetryBackoff {
+            initial_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+        }
+    }
+
+    /// The delay after the failed attempt `attempt` (starts from 0)
+    pub fn delay(&self, attempt: usize) -> Duration {
+        let factor = if attempt < 32 {
+            1u32 << attempt
+        } else {
+            u32::MAX
+        };
+        self.initial_delay
+            .checked_mul(factor)
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay))
+    }
+}
+
+/// Update a singleton cell (usually a type id cell holding a global config)
+/// which may be updated by other services at the same time.
+///
+/// The update is optimistic: the transaction is built from the latest fetched
+/// version, and when the submission fails because that version is already
+/// spent, the new version is fetched and the transaction is rebuilt from it.
+pub struct SingletonCellUpdater {
+    pub type_script: Script,
+    /// The delay between the attempts of `submit_with_retry`
+    pub backoff: RetryBackoff,
+}
+
+impl SingletonCellUpdater {
+    pub fn new(type_script: Script) -> SingletonCellUpdater {
+        SingletonCellUpdater {
+            type_script,
+            backoff: RetryBackoff::default(),
+        }
+    }
+
+    pub fn set_backoff(&mut self, backoff: RetryBackoff) {
+        self.backoff = backoff;
+    }
+
+    /// Fetch the latest version of the singleton cell, the cell is not locked
+    /// in `cell_collector`.
+    pub fn fetch(
+        &self,
+        cell_collector: &mut dyn CellCollector,
+    ) -> Result<SingletonHandle, SingletonError> {
+        let query = CellQueryOptions::new_type(self.type_script.clone());
+        let (cells, _) = cell_collector.collect_live_cells(&query, false)?;
+        let cell = cells
+            .into_iter()
+            .next()
+            .ok_or_else(|| SingletonError::NotFound(self.type_script.clone()))?;
+        Ok(SingletonHandle {
+            out_point: cell.out_point,
+            output: cell.output,
+            data: cell.output_data,
+            version_hint: cell.block_number,
+        })
+    }
+
+    /// A builder spending `handle` and recreating the cell with `new_data`
+    pub fn build_update(
+        &self,
+        handle: &SingletonHandle,
+        new_data: Bytes,
+    ) -> SingletonUpdateBuilder {
+        SingletonUpdateBuilder {
+            handle: handle.clone(),
+            new_data,
+            new_lock: None,
+        }
+    }
+
+    /// Fetch the latest version, build the transaction by `rebuild_fn` and
+    /// submit it. When the fetched version is spent before the transaction is
+    /// accepted, the new version is fetched and `rebuild_fn` is called again
+    /// with it, at most `max_attempts` times. The attempts are separated by
+    /// the delays of `backoff`, so the competing transaction can be committed.
+    ///
+    /// A transaction submitted by a previous attempt may still be committed
+    /// (e.g. the node accepted it but the response is lost). When the new
+    /// version is created by such a transaction, its hash is returned and
+    /// nothing is rebuilt.
+    pub fn submit_with_retry<F>(
+        &self,
+        cell_collector: &mut dyn CellCollector,
+        rpc: &dyn SendTransactionRpc,
+        mut rebuild_fn: F,
+        max_attempts: usize,
+    ) -> Result<H256, SingletonError>
+    where
+        F: FnMut(&SingletonHandle) -> Result<TransactionView, anyhow::Error>,
+    {
+        let mut submitted: Vec<H256> = Vec::new();
+        for attempt in 0..max_attempts {
+            if attempt > 0 {
+                thread::sleep(self.backoff.delay(attempt - 1));
+            }
+            let handle = self.fetch(cell_collector)?;
+            let creator: H256 = handle.out_point.tx_hash().unpack();
+            if submitted.contains(&creator) {
+                return Ok(creator);
+            }
+
+            let tx = rebuild_fn(&handle).map_err(SingletonError::Build)?;
+            if !tx
+                .input_pts_iter()
+                .any(|out_point| out_point == handle.out_point)
+            {
+                return Err(SingletonError::NotSpendingSingleton(handle.out_point));
+            }
+            let tx_hash: H256 = tx.hash().unpack();
+            submitted.push(tx_hash.clone());
+            match rpc.send_transaction(tx.data().into()) {
+                Ok(_) | Err(TransactionSubmitError::DuplicatedTransaction) => return Ok(tx_hash),
+                // Someone else (or a previous attempt) spent the version
+                Err(TransactionSubmitError::DeadOutPoint(out_point))
+                | Err(TransactionSubmitError::UnknownOutPoint(out_point))
+                    if out_point == handle.out_point => {}
+                // The result is unknown, the transaction may be accepted
+                Err(TransactionSubmitError::Rpc(_)) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Err(SingletonError::ExceedMaxAttempts(max_attempts))
+    }
+}
+
+/// Spend the singleton cell of a `SingletonHandle` and recreate it with new data
+pub struct SingletonUpdateBuilder {
+    pub handle: SingletonHandle,
+    pub new_data: Bytes,
+    /// The lock of the updated cell, `None` means keep the current lock
+    pub new_lock: Option<Script>,
+}
+
+impl TxBuilder for SingletonUpdateBuilder {
+    fn build_base(
+        &self,
+        _cell_collector: &mut dyn CellCollector,
+        cell_dep_resolver: &dyn CellDepResolver,
+        _header_dep_resolver: &dyn HeaderDepResolver,
+        _tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, TxBuilderError> {
+        build_update_tx(
+            &self.handle.out_point,
+            &self.handle.output,
+            &self.new_data,
+            self.new_lock.as_ref(),
+            cell_dep_resolver,
+        )
+    }
+}
@@ -0,0 +1,122 @@
+use ckb_types::{
+    core::TransactionView,
+    packed::{Script, WitnessArgs},
+    prelude::*,
+};
+use thiserror::Error;
+
+use super::{balance_tx_capacity, BalanceTxCapacityError, CapacityBalancer};
+use crate::traits::{
+    CellCollector, CellDepResolver, HeaderDepResolver, TransactionDependencyError,
+    TransactionDependencyProvider,
+};
+
+/// The account paying the fee of a transaction built by someone else, see
+/// `sponsor_tx`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct FeeSponsor {
+    /// The lock script of the sponsor inputs and the sponsor change cell
+    pub lock: Script,
+    /// The placeholder witness of the first sponsor input
+    pub placeholder_witness: WitnessArgs,
+    /// The max capacity the sponsor pays, the small change is also forced as
+    /// fee up to this value.
+    pub max_fee: u64,
+}
+
+impl FeeSponsor {
+    pub fn new(lock: Script, placeholder_witness: WitnessArgs, max_fee: u64) -> FeeSponsor {
+        FeeSponsor {
+            lock,
+            placeholder_witness,
+            max_fee,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SponsorError {
+    #[error("balance transaction capacity error: `{0}`")]
+    Balance(#[from] BalanceTxCapacityError),
+
+    #[error("transaction dependency error: `{0}`")]
+    TxDep(#[from] TransactionDependencyError),
+
+    #[error("the sponsor lock is already used by the input `{0}`")]
+    SponsorLockInUse(usize),
+
+    #[error("the transaction has `{0}` witnesses after the input aligned witnesses")]
+    ExtraWitnesses(usize),
+
+    #[error("the sponsor pays `{0}` shannons, more than the max fee `{1}`")]
+    MaxFeeExceeded(u64, u64),
+}
+
+/// Pay the fee of `tx` by the cells of `sponsor`:
+///   * the sponsor inputs are appended after all the inputs
+///   * the sponsor change cell is appended as the last output
+///   * the sponsor cell deps are appended after the cell deps
+///   * only the witnesses of the sponsor inputs are appended, the witness of
+///     the first sponsor input is the sponsor placeholder witness
+///
+/// The existing inputs, outputs, output data, cell deps, header deps and
+/// witnesses are kept byte-identical, so the witnesses which do not sign the
+/// appended parts stay valid. The locks signing the whole transaction (e.g.
+/// secp256k1 sighash all) must be unlocked after sponsoring. The sponsor
+/// inputs are unlocked separately by `unlock_tx` with the unlocker of the
+/// sponsor lock only.
+///
+/// The sponsor lock must not lock any input of `tx`, and `tx` must not have
+/// witnesses after the input aligned ones.
+pub fn sponsor_tx(
+    tx: &TransactionView,
+    sponsor: &FeeSponsor,
+    fee_rate: u64,
+    cell_collector: &mut dyn CellCollector,
+    tx_dep_provider: &dyn TransactionDependencyProvider,
+    cell_dep_resolver: &dyn CellDepResolver,
+    header_dep_resolver: &dyn HeaderDepResolver,
+) -> Result<TransactionView, SponsorError> {
+    for (idx, input) in tx.inputs().into_iter().enumerate() {
+        if tx_dep_provider.get_cell(&input.previous_output())?.lock() == sponsor.lock {
+            return Err(SponsorError::SponsorLockInUse(idx));
+        }
+    }
+    if tx.witnesses().len() > tx.inputs().len() {
+        return Err(SponsorError::ExtraWitnesses(
+            tx.witnesses().len() - tx.inputs().len(),
+        ));
+    }
+
+    let mut balancer = CapacityBalancer::new_simple(
+        sponsor.lock.clone(),
+        sponsor.placeholder_witness.clone(),
+        fee_rate,
+    );
+    balancer.set_max_fee(Some(sponsor.max_fee));
+    let sponsored_tx = balance_tx_capacity(
+        tx,
+        &balancer,
+        cell_collector,
+        tx_dep_provider,
+        cell_dep_resolver,
+        header_dep_resolver,
+    )?;
+
+    let mut paid: u64 = 0;
+    for input in sponsored_tx.inputs().into_iter().skip(tx.inputs().len()) {
+        let capacity: u64 = tx_dep_provider
+            .get_cell(&input.previous_output())?
+            .capacity()
+            .unpack();
+        paid += capacity;
+    }
+    for output in sponsored_tx.outputs().into_iter().skip(tx.outputs().len()) {
+        let capacity: u64 = output.capacity().unpack();
+        paid = paid.saturating_sub(capacity);
+    }
+    if paid > sponsor.max_fee {
+        return Err(SponsorError::MaxFeeExceeded(paid, sponsor.max_fee));
+    }
+    Ok(sponsored_tx)
+}
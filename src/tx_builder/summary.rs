@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+
+use anyhow::anyhow;
+use ckb_hash::blake2b_256;
+use ckb_types::{
+    bytes::{BufMut, Bytes, BytesMut},
+    core::TransactionView,
+    packed::{Byte32, Script},
+    prelude::*,
+    H256,
+};
+
+use crate::traits::{TransactionDependencyError, TransactionDependencyProvider};
+
+/// A minimal digest of what a transaction does with the signer's funds, small
+/// enough to be shown on a signing device with a tiny screen.
+///
+/// The host sends the summary together with its `commitment`, the device
+/// computes the summary from the raw transaction by itself and compares the
+/// commitments.
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+pub struct CompactSummary {
+    /// Capacity of the outputs not locked by an owned lock
+    pub external_out: u64,
+    /// Capacity of the outputs locked by an owned lock
+    pub change_back: u64,
+    /// Inputs capacity minus outputs capacity
+    pub fee: u64,
+    /// udt amount of the outputs not locked by an owned lock, grouped by type
+    /// script hash and sorted by it
+    pub external_udt: Vec<(Byte32, u128)>,
+}
+
+impl CompactSummary {
+    /// The canonical serialization (all integers are little endian):
+    ///
+    /// ```text
+    /// external_out: u64 | change_back: u64 | fee: u64 | udt_count: u32 |
+    /// udt_count * (type_hash: [u8; 32] | amount: u128)
+    /// ```
+    pub fn to_bytes(&self) -> Bytes {
+        let mut data = BytesMut::with_capacity(28 + self.external_udt.len() * 48);
+        data.put_u64_le(self.external_out);
+        data.put_u64_le(self.change_back);
+        data.put_u64_le(self.fee);
+        data.put_u32_le(self.external_udt.len() as u32);
+        for (type_hash, amount) in &self.external_udt {
+            data.put(type_hash.as_slice());
+            data.put_u128_le(*amount);
+        }
+        data.freeze()
+    }
+
+    /// The blake2b (ckb personalization) hash of `to_bytes()`
+    pub fn commitment(&self) -> H256 {
+        H256::from(blake2b_256(self.to_bytes()))
+    }
+}
+
+/// Summarize the capacity and udt flow of `tx` from the view of the owner of
+/// `owned_locks`.
+///
+/// An output is owned only when its lock script is exactly one of
+/// `owned_locks`. Prefix (or partial args) matching is unsafe here: a lock
+/// with the same code hash and extra args appended (e.g. an acp or omni-lock
+/// with different flags) can be controlled by someone else, so a payment to it
+/// would be displayed as change.
+///
+/// Any output with a type script and at least 16 bytes of data is read as a
+/// udt cell, the amount is the first 16 bytes of the data.
+pub fn compact_summary(
+    tx: &TransactionView,
+    tx_dep_provider: &dyn TransactionDependencyProvider,
+    owned_locks: &[Script],
+) -> Result<CompactSummary, TransactionDependencyError> {
+    let mut inputs_capacity: u64 = 0;
+    for input in tx.inputs() {
+        let output = tx_dep_provider.get_cell(&input.previous_output())?;
+        let capacity: u64 = output.capacity().unpack();
+        inputs_capacity = inputs_capacity
+            .checked_add(capacity)
+            .ok_or_else(|| anyhow!("inputs capacity overflow"))?;
+    }
+
+    let mut external_out: u64 = 0;
+    let mut change_back: u64 = 0;
+    let mut external_udt: BTreeMap<H256, u128> = BTreeMap::new();
+    for (output, data) in tx.outputs_with_data_iter() {
+        let capacity: u64 = output.capacity().unpack();
+        if owned_locks.contains(&output.lock()) {
+            change_back = change_back
+                .checked_add(capacity)
+                .ok_or_else(|| anyhow!("change capacity overflow"))?;
+            continue;
+        }
+        external_out = external_out
+            .checked_add(capacity)
+            .ok_or_else(|| anyhow!("external capacity overflow"))?;
+        if let Some(type_script) = output.type_().to_opt() {
+            if data.len() >= 16 {
+                let mut amount_bytes = [0u8; 16];
+                amount_bytes.copy_from_slice(&data[0..16]);
+                let total = external_udt
+                    .entry(type_script.calc_script_hash().unpack())
+                    .or_default();
+                *total = total
+                    .checked_add(u128::from_le_bytes(amount_bytes))
+                    .ok_or_else(|| anyhow!("udt amount overflow"))?;
+            }
+        }
+    }
+
+    let outputs_capacity = external_out
+        .checked_add(change_back)
+        .ok_or_else(|| anyhow!("outputs capacity overflow"))?;
+    let fee = inputs_capacity
+        .checked_sub(outputs_capacity)
+        .ok_or_else(|| {
+            anyhow!(
+                "outputs capacity exceeds inputs capacity: {} > {}",
+                outputs_capacity,
+                inputs_capacity
+            )
+        })?;
+    Ok(CompactSummary {
+        external_out,
+        change_back,
+        fee,
+        external_udt: external_udt
+            .into_iter()
+            .map(|(type_hash, amount)| (type_hash.pack(), amount))
+            .collect(),
+    })
+}
@@ -0,0 +1,454 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use ckb_jsonrpc_types::JsonBytes;
+use ckb_types::{
+    bytes::Bytes,
+    core::{Capacity, TransactionView},
+    packed::{CellOutput, Script},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::batch::{BatchTransferBuilder, TransferItem};
+use super::transfer::CapacityTransferBuilder;
+use super::udt::TokenRegistry;
+use super::{TransferAction, TxBuilder, TxBuilderError};
+use crate::traits::{
+    CellCollector, CellDepResolver, HeaderDepResolver, TransactionDependencyProvider,
+};
+use crate::types::{Address, NetworkType};
+
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum TemplateError {
+    #[error("duplicated slot name: `{0}`")]
+    DuplicatedSlot(String),
+
+    #[error("template must have exactly one fee payer slot, got: `{0}`")]
+    FeePayerCount(usize),
+
+    #[error("token symbol not found: `{0}`")]
+    SymbolNotFound(String),
+
+    #[error("invalid address `{0}`: `{1}`")]
+    InvalidAddress(String, String),
+
+    #[error("address network mismatch, expected: `{expected:?}`, got: `{actual:?}`")]
+    NetworkMismatch {
+        expected: NetworkType,
+        actual: NetworkType,
+    },
+
+    #[error("fixed output `{0}` with data can not be used with udt receiver slots")]
+    FixedOutputData(String),
+
+    #[error("not enough capacity for slot `{slot}`, min: `{min}`, actual: `{actual}`")]
+    InsufficientCapacity { slot: String, min: u64, actual: u64 },
+
+    #[error("invalid parameter for slot `{0}`: `{1}`")]
+    InvalidParam(String, String),
+}
+
+/// A slot in the shape of a transaction
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TemplateSlot {
+    /// One or more plain capacity outputs, given by `SlotValue::Capacity`
+    CapacityReceiver { name: String },
+    /// One or more udt outputs of the token `symbol`, given by `SlotValue::Udt`
+    UdtReceiver { name: String, symbol: String },
+    /// An output which is the same in every transaction
+    FixedOutput {
+        name: String,
+        address: String,
+        /// The capacity in shannons
+        capacity: u64,
+        #[serde(default)]
+        data: JsonBytes,
+    },
+    /// Pays the fee and receives the change, it is also the udt sender, given
+    /// by `SlotValue::FeePayer`
+    FeePayer { name: String },
+}
+
+impl TemplateSlot {
+    pub fn name(&self) -> &str {
+        match self {
+            TemplateSlot::CapacityReceiver { name }
+            | TemplateSlot::UdtReceiver { name, .. }
+            | TemplateSlot::FixedOutput { name, .. }
+            | TemplateSlot::FeePayer { name } => name,
+        }
+    }
+}
+
+/// The shape of a transaction, the outputs are in the order of the slots.
+///
+/// Compile it once by `TxTemplate::compile`, then instantiate the compiled
+/// template for every transaction.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TxTemplate {
+    pub name: String,
+    pub slots: Vec<TemplateSlot>,
+}
+
+impl TxTemplate {
+    /// Pay capacity to the `receivers` slot, the fee is paid by the `payer` slot.
+    pub fn payout() -> TxTemplate {
+        TxTemplate {
+            name: "payout".to_string(),
+            slots: vec![
+                TemplateSlot::CapacityReceiver {
+                    name: "receivers".to_string(),
+                },
+                TemplateSlot::FeePayer {
+                    name: "payer".to_string(),
+                },
+            ],
+        }
+    }
+
+    /// Send the udt `symbol` to the `receivers` slot from the `sender` slot,
+    /// the sender also pays the fee.
+    pub fn airdrop(symbol: &str) -> TxTemplate {
+        TxTemplate {
+            name: "airdrop".to_string(),
+            slots: vec![
+                TemplateSlot::UdtReceiver {
+                    name: "receivers".to_string(),
+                    symbol: symbol.to_string(),
+                },
+                TemplateSlot::FeePayer {
+                    name: "sender".to_string(),
+                },
+            ],
+        }
+    }
+
+    /// Validate the slots, resolve the udt type scripts and the fixed outputs.
+    /// The network is a `NetworkType` or a `&NetworkInfo`.
+    pub fn compile<N: Into<NetworkType>>(
+        &self,
+        network: N,
+        registry: &TokenRegistry,
+    ) -> Result<CompiledTemplate, TemplateError> {
+        let network = network.into();
+        let mut names = HashSet::new();
+        for slot in &self.slots {
+            if !names.insert(slot.name()) {
+                return Err(TemplateError::DuplicatedSlot(slot.name().to_string()));
+            }
+        }
+        let fee_payer_count = self
+            .slots
+            .iter()
+            .filter(|slot| matches!(slot, TemplateSlot::FeePayer { .. }))
+            .count();
+        if fee_payer_count != 1 {
+            return Err(TemplateError::FeePayerCount(fee_payer_count));
+        }
+        let has_udt = self
+            .slots
+            .iter()
+            .any(|slot| matches!(slot, TemplateSlot::UdtReceiver { .. }));
+
+        let mut slots = Vec::with_capacity(self.slots.len());
+        let mut fee_payer = String::new();
+        for slot in &self.slots {
+            let compiled = match slot {
+                TemplateSlot::CapacityReceiver { name } => CompiledSlot::Capacity {
+                    name: name.clone(),
+                    // the occupied capacity without the lock args, which are
+                    // only known by the slot values
+                    min_capacity: CellOutput::default()
+                        .occupied_capacity(Capacity::zero())
+                        .unwrap()
+                        .as_u64(),
+                },
+                TemplateSlot::UdtReceiver { name, symbol } => {
+                    let entry = registry
+                        .lookup_by_symbol(symbol)
+                        .ok_or_else(|| TemplateError::SymbolNotFound(symbol.clone()))?;
+                    CompiledSlot::Udt {
+                        name: name.clone(),
+                        type_script: entry.type_script.clone(),
+                    }
+                }
+                TemplateSlot::FixedOutput {
+                    name,
+                    address,
+                    capacity,
+                    data,
+                } => {
+                    let data = data.clone().into_bytes();
+                    if has_udt && !data.is_empty() {
+                        return Err(TemplateError::FixedOutputData(name.clone()));
+                    }
+                    let address = Address::from_str(address)
+                        .map_err(|err| TemplateError::InvalidAddress(address.clone(), err))?;
+                    check_network(network, &address)?;
+                    let output = CellOutput::new_builder()
+                        .lock(Script::from(&address))
+                        .capacity(capacity.pack())
+                        .build();
+                    let min = output
+                        .occupied_capacity(Capacity::bytes(data.len()).unwrap())
+                        .unwrap()
+                        .as_u64();
+                    if *capacity < min {
+                        return Err(TemplateError::InsufficientCapacity {
+                            slot: name.clone(),
+                            min,
+                            actual: *capacity,
+                        });
+                    }
+                    CompiledSlot::Fixed { output, data }
+                }
+                TemplateSlot::FeePayer { name } => {
+                    fee_payer = name.clone();
+                    continue;
+                }
+            };
+            slots.push(compiled);
+        }
+        Ok(CompiledTemplate {
+            network,
+            slots,
+            fee_payer,
+            has_udt,
+        })
+    }
+}
+
+fn check_network(expected: NetworkType, address: &Address) -> Result<(), TemplateError> {
+    if address.network() != expected {
+        return Err(TemplateError::NetworkMismatch {
+            expected,
+            actual: address.network(),
+        });
+    }
+    Ok(())
+}
+
+/// The value of a slot for one transaction
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum SlotValue {
+    /// The capacity in shannons
+    Capacity {
+        receiver: Address,
+        capacity: u64,
+    },
+    Udt {
+        receiver: Address,
+        amount: u128,
+    },
+    FeePayer(Address),
+}
+
+/// The slot values of one transaction, by slot name. Every receiver slot
+/// needs one or more values, the fee payer slot needs exactly one.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct TemplateParams {
+    values: HashMap<String, Vec<SlotValue>>,
+}
+
+impl TemplateParams {
+    pub fn new() -> TemplateParams {
+        TemplateParams::default()
+    }
+
+    pub fn push(&mut self, slot: &str, value: SlotValue) -> &mut TemplateParams {
+        self.values.entry(slot.to_string()).or_default().push(value);
+        self
+    }
+
+    pub fn values(&self, slot: &str) -> &[SlotValue] {
+        self.values
+            .get(slot)
+            .map(|values| values.as_slice())
+            .unwrap_or(&[])
+    }
+}
+
+#[derive(Debug, Clone)]
+enum CompiledSlot {
+    Capacity { name: String, min_capacity: u64 },
+    Udt { name: String, type_script: Script },
+    Fixed { output: CellOutput, data: Bytes },
+}
+
+/// A validated `TxTemplate`, instantiate it with the slot values of every transaction.
+#[derive(Debug, Clone)]
+pub struct CompiledTemplate {
+    network: NetworkType,
+    slots: Vec<CompiledSlot>,
+    fee_payer: String,
+    has_udt: bool,
+}
+
+impl CompiledTemplate {
+    pub fn network(&self) -> NetworkType {
+        self.network
+    }
+
+    /// The name of the fee payer slot
+    pub fn fee_payer_slot(&self) -> &str {
+        &self.fee_payer
+    }
+
+    /// Build the transaction builder from the slot values. Only the values
+    /// are checked, the template itself is validated by `TxTemplate::compile`.
+    pub fn instantiate(&self, params: &TemplateParams) -> Result<TemplateInstance, TemplateError> {
+        for slot in params.values.keys() {
+            let known = slot == &self.fee_payer
+                || self.slots.iter().any(|compiled| match compiled {
+                    CompiledSlot::Capacity { name, .. } | CompiledSlot::Udt { name, .. } => {
+                        name == slot
+                    }
+                    CompiledSlot::Fixed { .. } => false,
+                });
+            if !known {
+                return Err(TemplateError::InvalidParam(
+                    slot.clone(),
+                    "unknown slot".to_string(),
+                ));
+            }
+        }
+        let fee_payer = match params.values(&self.fee_payer) {
+            [SlotValue::FeePayer(address)] => {
+                check_network(self.network, address)?;
+                Script::from(address)
+            }
+            _ => {
+                return Err(TemplateError::InvalidParam(
+                    self.fee_payer.clone(),
+                    "expected exactly one fee payer".to_string(),
+                ))
+            }
+        };
+
+        let mut outputs = Vec::new();
+        let mut items = Vec::new();
+        for slot in &self.slots {
+            match slot {
+                CompiledSlot::Capacity { name, min_capacity } => {
+                    for value in non_empty_values(params, name)? {
+                        let (receiver, capacity) = match value {
+                            SlotValue::Capacity { receiver, capacity } => (receiver, *capacity),
+                            _ => return Err(unexpected_value(name)),
+                        };
+                        check_network(self.network, receiver)?;
+                        let lock = Script::from(receiver);
+                        let min = min_capacity
+                            + Capacity::bytes(lock.args().raw_data().len())
+                                .unwrap()
+                                .as_u64();
+                        if capacity < min {
+                            return Err(TemplateError::InsufficientCapacity {
+                                slot: name.clone(),
+                                min,
+                                actual: capacity,
+                            });
+                        }
+                        let output = CellOutput::new_builder()
+                            .lock(lock)
+                            .capacity(capacity.pack())
+                            .build();
+                        if self.has_udt {
+                            items.push(TransferItem::new_ckb(output.lock(), capacity));
+                        } else {
+                            outputs.push((output, Bytes::new()));
+                        }
+                    }
+                }
+                CompiledSlot::Udt { name, type_script } => {
+                    for value in non_empty_values(params, name)? {
+                        let (receiver, amount) = match value {
+                            SlotValue::Udt { receiver, amount } => (receiver, *amount),
+                            _ => return Err(unexpected_value(name)),
+                        };
+                        check_network(self.network, receiver)?;
+                        items.push(TransferItem::new_udt(
+                            Script::from(receiver),
+                            type_script.clone(),
+                            amount,
+                            TransferAction::Create,
+                        ));
+                    }
+                }
+                CompiledSlot::Fixed { output, data } => {
+                    if self.has_udt {
+                        let capacity: u64 = output.capacity().unpack();
+                        items.push(TransferItem::new_ckb(output.lock(), capacity));
+                    } else {
+                        outputs.push((output.clone(), data.clone()));
+                    }
+                }
+            }
+        }
+        let builder = if self.has_udt {
+            TemplateTxBuilder::Batch(BatchTransferBuilder::new(fee_payer.clone(), items))
+        } else {
+            TemplateTxBuilder::Capacity(CapacityTransferBuilder::new(outputs))
+        };
+        Ok(TemplateInstance { fee_payer, builder })
+    }
+}
+
+fn non_empty_values<'a>(
+    params: &'a TemplateParams,
+    slot: &str,
+) -> Result<&'a [SlotValue], TemplateError> {
+    let values = params.values(slot);
+    if values.is_empty() {
+        return Err(TemplateError::InvalidParam(
+            slot.to_string(),
+            "missing value".to_string(),
+        ));
+    }
+    Ok(values)
+}
+
+fn unexpected_value(slot: &str) -> TemplateError {
+    TemplateError::InvalidParam(slot.to_string(), "unexpected value type".to_string())
+}
+
+/// The builder a template is mapped onto
+pub enum TemplateTxBuilder {
+    /// No udt receiver slot
+    Capacity(CapacityTransferBuilder),
+    /// With udt receiver slots, the fee payer is the udt sender
+    Batch(BatchTransferBuilder),
+}
+
+/// An instantiated template, use `fee_payer` as the sender of the `CapacityBalancer`.
+pub struct TemplateInstance {
+    pub fee_payer: Script,
+    pub builder: TemplateTxBuilder,
+}
+
+impl TxBuilder for TemplateInstance {
+    fn build_base(
+        &self,
+        cell_collector: &mut dyn CellCollector,
+        cell_dep_resolver: &dyn CellDepResolver,
+        header_dep_resolver: &dyn HeaderDepResolver,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, TxBuilderError> {
+        match &self.builder {
+            TemplateTxBuilder::Capacity(builder) => builder.build_base(
+                cell_collector,
+                cell_dep_resolver,
+                header_dep_resolver,
+                tx_dep_provider,
+            ),
+            TemplateTxBuilder::Batch(builder) => builder.build_base(
+                cell_collector,
+                cell_dep_resolver,
+                header_dep_resolver,
+                tx_dep_provider,
+            ),
+        }
+    }
+}
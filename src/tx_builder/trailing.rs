@@ -0,0 +1,86 @@
+use ckb_types::{bytes::Bytes, core::TransactionView, packed, prelude::*};
+
+/// Witnesses reserved after all the input aligned witnesses, e.g. the
+/// commitment of a layer-2 protocol.
+///
+/// The balancer appends inputs (and their witnesses), so the index of a
+/// witness beyond the inputs is not stable. The reserved witnesses are
+/// removed before and put back after every balancing round, they are always
+/// the last witnesses of the balanced transaction and are covered by the
+/// sighash signature as the witnesses beyond the inputs.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct TrailingWitnesses {
+    entries: Vec<(String, Bytes)>,
+}
+
+impl TrailingWitnesses {
+    /// Reserve a trailing witness, the witness of an existing `tag` is replaced
+    /// in place.
+    pub fn reserve(&mut self, tag: &str, witness: Bytes) {
+        if let Some(entry) = self.entries.iter_mut().find(|(name, _)| name == tag) {
+            entry.1 = witness;
+        } else {
+            self.entries.push((tag.to_string(), witness));
+        }
+    }
+
+    pub fn get(&self, tag: &str) -> Option<&Bytes> {
+        self.entries
+            .iter()
+            .find(|(name, _)| name == tag)
+            .map(|(_, witness)| witness)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The reserved witnesses in order
+    pub fn witnesses(&self) -> Vec<packed::Bytes> {
+        self.entries
+            .iter()
+            .map(|(_, witness)| witness.pack())
+            .collect()
+    }
+
+    /// The index of the witness of `tag` in `tx`, `None` if the reserved
+    /// witnesses are not the last witnesses of `tx`.
+    pub fn index(&self, tx: &TransactionView, tag: &str) -> Option<usize> {
+        let position = self.entries.iter().position(|(name, _)| name == tag)?;
+        if !self.is_placed(tx) {
+            return None;
+        }
+        Some(tx.witnesses().len() - self.entries.len() + position)
+    }
+
+    /// Remove the reserved witnesses from the end of the witnesses of `tx`,
+    /// `tx` is returned as it is if they are not there.
+    pub fn strip(&self, tx: &TransactionView) -> TransactionView {
+        if self.is_empty() || !self.is_placed(tx) {
+            return tx.clone();
+        }
+        let witnesses: Vec<packed::Bytes> = tx.witnesses().into_iter().collect();
+        let len = witnesses.len() - self.entries.len();
+        tx.as_advanced_builder()
+            .set_witnesses(witnesses[..len].to_vec())
+            .build()
+    }
+
+    fn is_placed(&self, tx: &TransactionView) -> bool {
+        let witnesses = tx.witnesses();
+        if witnesses.len() < self.entries.len() {
+            return false;
+        }
+        let offset = witnesses.len() - self.entries.len();
+        self.entries.iter().enumerate().all(|(idx, (_, witness))| {
+            witnesses
+                .get(offset + idx)
+                .map(|data| data.raw_data() == *witness)
+                .unwrap_or(false)
+        })
+    }
+}
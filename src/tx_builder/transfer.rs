@@ -1,17 +1,23 @@
-use std::collections::HashSet;
-
+use anyhow::anyhow;
 use ckb_types::{
     bytes::Bytes,
-    core::{TransactionBuilder, TransactionView},
-    packed::CellOutput,
+    core::{Capacity, TransactionBuilder, TransactionView},
+    packed::{CellInput, CellOutput, Script},
     prelude::*,
 };
 
-use super::{TxBuilder, TxBuilderError};
+use super::{
+    extend_unique, fill_placeholder_witnesses, BalanceTxCapacityError, CapacityBalancer, TxBuilder,
+    TxBuilderError,
+};
+use crate::constants::MULTISIG_TYPE_HASH;
 use crate::traits::{
-    CellCollector, CellDepResolver, HeaderDepResolver, TransactionDependencyProvider,
+    CellCollector, CellDepResolver, CellQueryOptions, HeaderDepResolver,
+    TransactionDependencyProvider, ValueRangeOption,
 };
 use crate::types::ScriptId;
+use crate::unlock::{multisig_args_since, UnlockerProvider};
+use crate::util::{calc_fee, tx_size};
 
 /// A builder to build a transaction simply transfer capcity to an address. It
 /// will resolve the type script's cell_dep if given.
@@ -23,6 +29,35 @@ impl CapacityTransferBuilder {
     pub fn new(outputs: Vec<(CellOutput, Bytes)>) -> CapacityTransferBuilder {
         CapacityTransferBuilder { outputs }
     }
+
+    /// Create the outputs from `(lock, type, data)`, the capacity of each
+    /// output is the minimal capacity to hold the cell. The balancer pays
+    /// the total capacity.
+    pub fn new_with_auto_capacity(
+        items: Vec<(Script, Option<Script>, Bytes)>,
+    ) -> CapacityTransferBuilder {
+        let outputs = items
+            .into_iter()
+            .map(|(lock, type_script, data)| {
+                let output = CellOutput::new_builder()
+                    .lock(lock)
+                    .type_(type_script.pack())
+                    .build();
+                let capacity =
+                    min_output_capacity(&output, &data).expect("output occupied capacity overflow");
+                let output = output.as_builder().capacity(capacity.pack()).build();
+                (output, data)
+            })
+            .collect();
+        CapacityTransferBuilder { outputs }
+    }
+}
+
+fn min_output_capacity(output: &CellOutput, data: &Bytes) -> Result<u64, TxBuilderError> {
+    Capacity::bytes(data.len())
+        .and_then(|data_capacity| output.occupied_capacity(data_capacity))
+        .map(|capacity| capacity.as_u64())
+        .map_err(|err| TxBuilderError::Other(anyhow!("occupied capacity overflow: {}", err)))
 }
 
 impl TxBuilder for CapacityTransferBuilder {
@@ -33,27 +68,208 @@ impl TxBuilder for CapacityTransferBuilder {
         _header_dep_resolver: &dyn HeaderDepResolver,
         _tx_dep_provider: &dyn TransactionDependencyProvider,
     ) -> Result<TransactionView, TxBuilderError> {
-        #[allow(clippy::mutable_key_type)]
-        let mut cell_deps = HashSet::new();
+        let mut cell_deps = Vec::new();
         let mut outputs = Vec::new();
         let mut outputs_data = Vec::new();
-        for (output, output_data) in &self.outputs {
+        for (index, (output, output_data)) in self.outputs.iter().enumerate() {
+            let min = min_output_capacity(output, output_data)?;
+            let actual: u64 = output.capacity().unpack();
+            if actual < min {
+                return Err(TxBuilderError::CapacityTooSmallForCell { index, min, actual });
+            }
             outputs.push(output.clone());
             outputs_data.push(output_data.pack());
             if let Some(type_script) = output.type_().to_opt() {
                 let script_id = ScriptId::from(&type_script);
                 if !script_id.is_type_id() {
-                    let cell_dep = cell_dep_resolver
-                        .resolve(&type_script)
+                    let type_cell_deps = cell_dep_resolver
+                        .resolve_all(&type_script)
                         .ok_or(TxBuilderError::ResolveCellDepFailed(type_script))?;
-                    cell_deps.insert(cell_dep);
+                    extend_unique(&mut cell_deps, type_cell_deps);
                 }
             }
         }
         Ok(TransactionBuilder::default()
-            .set_cell_deps(cell_deps.into_iter().collect())
+            .set_cell_deps(cell_deps)
             .set_outputs(outputs)
             .set_outputs_data(outputs_data)
             .build())
     }
 }
+
+/// A builder to transfer all the capacity of some lock scripts to one
+/// receiver, the fee is paid by the receiver output and there is no change
+/// output.
+///
+/// All the live and mature cells of `sender_locks` without type script and
+/// data are collected. Use `build_balanced` or `build_unlocked` to deduct the
+/// fee, the output of `build_base` holds all the input capacity.
+pub struct CapacitySweepBuilder {
+    pub sender_locks: Vec<Script>,
+    pub receiver_lock: Script,
+    /// Also collect the cells with type script (and data). The type script is
+    /// not kept in the output, so only set this when the type script allows
+    /// the cell to be destroyed.
+    pub allow_type_script: bool,
+}
+
+impl CapacitySweepBuilder {
+    pub fn new(sender_locks: Vec<Script>, receiver_lock: Script) -> CapacitySweepBuilder {
+        CapacitySweepBuilder {
+            sender_locks,
+            receiver_lock,
+            allow_type_script: false,
+        }
+    }
+}
+
+impl TxBuilder for CapacitySweepBuilder {
+    fn build_base(
+        &self,
+        cell_collector: &mut dyn CellCollector,
+        cell_dep_resolver: &dyn CellDepResolver,
+        _header_dep_resolver: &dyn HeaderDepResolver,
+        _tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, TxBuilderError> {
+        if self.sender_locks.is_empty() {
+            return Err(TxBuilderError::InvalidParameter(anyhow!(
+                "empty sweep sender locks"
+            )));
+        }
+        let mut cell_deps = Vec::new();
+        let mut inputs = Vec::new();
+        let mut total_capacity: u64 = 0;
+        for lock_script in &self.sender_locks {
+            let mut query = CellQueryOptions::new_lock(lock_script.clone());
+            if !self.allow_type_script {
+                query.secondary_script_len_range = Some(ValueRangeOption::new_exact(0));
+                query.data_len_range = Some(ValueRangeOption::new_exact(0));
+            }
+            query.min_total_capacity = u64::MAX;
+            let (cells, _) = cell_collector.collect_live_cells(&query, true)?;
+            for cell in cells {
+                if let Some(type_script) = cell.output.type_().to_opt() {
+                    let type_cell_deps = cell_dep_resolver
+                        .resolve_all(&type_script)
+                        .ok_or(TxBuilderError::ResolveCellDepFailed(type_script))?;
+                    extend_unique(&mut cell_deps, type_cell_deps);
+                }
+                let capacity: u64 = cell.output.capacity().unpack();
+                total_capacity = total_capacity
+                    .checked_add(capacity)
+                    .ok_or_else(|| TxBuilderError::Other(anyhow!("input capacity overflow")))?;
+                inputs.push(CellInput::new(cell.out_point, 0));
+            }
+        }
+        if inputs.is_empty() {
+            return Err(TxBuilderError::Other(anyhow!(
+                "no live cell to sweep, sender locks: {:?}",
+                self.sender_locks
+            )));
+        }
+        let output = CellOutput::new_builder()
+            .lock(self.receiver_lock.clone())
+            .capacity(total_capacity.pack())
+            .build();
+        Ok(TransactionBuilder::default()
+            .set_cell_deps(cell_deps)
+            .set_inputs(inputs)
+            .output(output)
+            .output_data(Bytes::default().pack())
+            .build())
+    }
+
+    /// Deduct the fee from the only output at the balancer's fee rate, no
+    /// more input or change output is added.
+    fn build_balanced(
+        &self,
+        cell_collector: &mut dyn CellCollector,
+        cell_dep_resolver: &dyn CellDepResolver,
+        header_dep_resolver: &dyn HeaderDepResolver,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
+        balancer: &CapacityBalancer,
+        unlockers: &dyn UnlockerProvider,
+    ) -> Result<TransactionView, TxBuilderError> {
+        let base_tx = self.build_base(
+            cell_collector,
+            cell_dep_resolver,
+            header_dep_resolver,
+            tx_dep_provider,
+        )?;
+        let (tx, _) = fill_placeholder_witnesses(base_tx, tx_dep_provider, unlockers)?;
+        let output = tx.output(0).expect("sweep output");
+        let total_capacity: u64 = output.capacity().unpack();
+        let occupied_capacity = output
+            .occupied_capacity(Capacity::zero())
+            .expect("sweep output occupied capacity")
+            .as_u64();
+        let fee_rate = balancer.current_fee_rate()?;
+        let mut fee = 0;
+        loop {
+            let capacity = total_capacity
+                .checked_sub(fee)
+                .filter(|capacity| *capacity >= occupied_capacity)
+                .ok_or_else(|| {
+                    BalanceTxCapacityError::CapacityNotEnough(format!(
+                        "sweep capacity {} can not pay fee {} and hold the output",
+                        total_capacity, fee
+                    ))
+                })?;
+            let new_tx = tx
+                .as_advanced_builder()
+                .set_outputs(vec![output
+                    .clone()
+                    .as_builder()
+                    .capacity(capacity.pack())
+                    .build()])
+                .build();
+            // The output capacity is fixed size, the second round always
+            // returns, the loop only guards against future size changes.
+            let size = tx_size(&new_tx);
+            let min_fee = calc_fee(size, fee_rate);
+            if fee >= min_fee {
+                if let Some(max_tx_size) = balancer
+                    .fee_rate_provider
+                    .as_ref()
+                    .and_then(|provider| provider.max_tx_size())
+                {
+                    if size > max_tx_size {
+                        return Err(
+                            BalanceTxCapacityError::TxSizeLimitExceeded(size, max_tx_size).into(),
+                        );
+                    }
+                }
+                return Ok(new_tx);
+            }
+            fee = min_fee;
+        }
+    }
+}
+
+/// Set the since of every input locked by a multisig lock with an embedded
+/// since (see `MultisigConfig::to_script_with_since`) to the since in the lock
+/// args, the other inputs are unchanged.
+///
+/// The balancer does it for the cells it collects only when the since source
+/// is `SinceSource::LockArgs(20)`, call it before signing otherwise. The size
+/// of the transaction is not changed.
+pub fn set_lock_args_since(
+    tx: &TransactionView,
+    tx_dep_provider: &dyn TransactionDependencyProvider,
+) -> Result<TransactionView, TxBuilderError> {
+    let multisig_id = ScriptId::new_type(MULTISIG_TYPE_HASH.clone());
+    let mut inputs = Vec::new();
+    for input in tx.inputs() {
+        let lock = tx_dep_provider.get_cell(&input.previous_output())?.lock();
+        let since = if ScriptId::from(&lock) == multisig_id {
+            multisig_args_since(&lock.args().raw_data())
+        } else {
+            None
+        };
+        inputs.push(match since {
+            Some(since) => input.as_builder().since(since.value().pack()).build(),
+            None => input,
+        });
+    }
+    Ok(tx.as_advanced_builder().set_inputs(inputs).build())
+}
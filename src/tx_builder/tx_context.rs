@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use ckb_types::{
+    bytes::Bytes,
+    core::{HeaderView, TransactionView},
+    packed::{Byte32, CellOutput, OutPoint},
+    prelude::*,
+};
+use parking_lot::Mutex;
+
+use super::ScriptGroups;
+use crate::traits::{TransactionDependencyError, TransactionDependencyProvider};
+use crate::types::ScriptGroup;
+
+/// The cells and the script groups of a transaction being built, shared by
+/// `fill_placeholder_witnesses_with`, `balance_tx_capacity_with` and
+/// `unlock_tx_with` so a cell is only loaded once from the provider.
+///
+/// `TxContext` is a `TransactionDependencyProvider` itself, the cells and
+/// the cell data are cached, the other requests are passed through.
+pub struct TxContext<'a> {
+    tx_dep_provider: &'a dyn TransactionDependencyProvider,
+    cells: Mutex<HashMap<OutPoint, CellOutput>>,
+    cell_data: Mutex<HashMap<OutPoint, Bytes>>,
+    /// The inputs `input_groups` is computed from
+    inputs: Vec<OutPoint>,
+    /// The script groups of the inputs, without the output type scripts
+    input_groups: ScriptGroups,
+}
+
+impl<'a> TxContext<'a> {
+    pub fn new(tx_dep_provider: &'a dyn TransactionDependencyProvider) -> TxContext<'a> {
+        TxContext {
+            tx_dep_provider,
+            cells: Mutex::new(HashMap::new()),
+            cell_data: Mutex::new(HashMap::new()),
+            inputs: Vec::new(),
+            input_groups: ScriptGroups::default(),
+        }
+    }
+
+    /// The script groups of `tx`, the same as `gen_script_groups`.
+    ///
+    /// When the inputs of `tx` start with the inputs of the last call (e.g.
+    /// the balancer appended some inputs), only the new inputs are added to
+    /// the groups, otherwise the groups are computed again.
+    pub fn script_groups(
+        &mut self,
+        tx: &TransactionView,
+    ) -> Result<ScriptGroups, TransactionDependencyError> {
+        let inputs: Vec<OutPoint> = tx.input_pts_iter().collect();
+        if !inputs.starts_with(&self.inputs) {
+            self.inputs.clear();
+            self.input_groups = ScriptGroups::default();
+        }
+        for (i, out_point) in inputs.iter().enumerate().skip(self.inputs.len()) {
+            let output = self.get_cell(out_point)?;
+            self.input_groups
+                .lock_groups
+                .entry(output.calc_lock_hash())
+                .or_insert_with(|| ScriptGroup::from_lock_script(&output.lock()))
+                .input_indices
+                .push(i);
+            if let Some(t) = &output.type_().to_opt() {
+                self.input_groups
+                    .type_groups
+                    .entry(t.calc_script_hash())
+                    .or_insert_with(|| ScriptGroup::from_type_script(t))
+                    .input_indices
+                    .push(i);
+            }
+        }
+        self.inputs = inputs;
+
+        let mut groups = self.input_groups.clone();
+        for (i, output) in tx.outputs().into_iter().enumerate() {
+            if let Some(t) = &output.type_().to_opt() {
+                groups
+                    .type_groups
+                    .entry(t.calc_script_hash())
+                    .or_insert_with(|| ScriptGroup::from_type_script(t))
+                    .output_indices
+                    .push(i);
+            }
+        }
+        Ok(groups)
+    }
+}
+
+impl<'a> TransactionDependencyProvider for TxContext<'a> {
+    fn get_transaction(
+        &self,
+        tx_hash: &Byte32,
+    ) -> Result<TransactionView, TransactionDependencyError> {
+        self.tx_dep_provider.get_transaction(tx_hash)
+    }
+
+    fn get_cell(&self, out_point: &OutPoint) -> Result<CellOutput, TransactionDependencyError> {
+        if let Some(output) = self.cells.lock().get(out_point) {
+            return Ok(output.clone());
+        }
+        let output = self.tx_dep_provider.get_cell(out_point)?;
+        self.cells.lock().insert(out_point.clone(), output.clone());
+        Ok(output)
+    }
+
+    fn get_cell_data(&self, out_point: &OutPoint) -> Result<Bytes, TransactionDependencyError> {
+        if let Some(data) = self.cell_data.lock().get(out_point) {
+            return Ok(data.clone());
+        }
+        let data = self.tx_dep_provider.get_cell_data(out_point)?;
+        self.cell_data
+            .lock()
+            .insert(out_point.clone(), data.clone());
+        Ok(data)
+    }
+
+    fn get_header(&self, block_hash: &Byte32) -> Result<HeaderView, TransactionDependencyError> {
+        self.tx_dep_provider.get_header(block_hash)
+    }
+
+    fn get_block_extension(
+        &self,
+        block_hash: &Byte32,
+    ) -> Result<Option<ckb_types::packed::Bytes>, TransactionDependencyError> {
+        self.tx_dep_provider.get_block_extension(block_hash)
+    }
+}
@@ -0,0 +1,209 @@
+use anyhow::anyhow;
+use ckb_hash::new_blake2b;
+use ckb_types::{
+    bytes::Bytes,
+    core::{Capacity, TransactionBuilder, TransactionView},
+    packed::{CellInput, CellOutput, OutPoint, Script},
+    prelude::*,
+    H256,
+};
+
+use super::{TxBuilder, TxBuilderError};
+use crate::constants::TYPE_ID_CODE_HASH;
+use crate::traits::{
+    CellCollector, CellDepResolver, CellQueryOptions, HeaderDepResolver,
+    TransactionDependencyProvider,
+};
+use crate::types::ScriptId;
+
+/// Calculate the type id args: blake2b(first_input | output_index as u64 little endian)
+pub fn calculate_type_id(first_input: &CellInput, output_index: u64) -> H256 {
+    let mut blake2b = new_blake2b();
+    blake2b.update(first_input.as_slice());
+    blake2b.update(&output_index.to_le_bytes());
+    let mut type_id = [0u8; 32];
+    blake2b.finalize(&mut type_id);
+    H256::from(type_id)
+}
+
+/// Replace the placeholder args (see `ScriptId::dummy_type_id_script`) of the
+/// type id outputs by the real type id. Must be called after the inputs are
+/// fixed, the transaction size is not changed.
+pub fn fill_type_id_args(tx: TransactionView) -> Result<TransactionView, TxBuilderError> {
+    let placeholder = ScriptId::new_type(TYPE_ID_CODE_HASH).dummy_type_id_script();
+    let first_input = tx
+        .inputs()
+        .get(0)
+        .ok_or_else(|| TxBuilderError::Other(anyhow!("no input to calculate type id")))?;
+    let outputs = tx
+        .outputs()
+        .into_iter()
+        .enumerate()
+        .map(|(idx, output)| {
+            if output.type_().to_opt().as_ref() != Some(&placeholder) {
+                return output;
+            }
+            let type_id = calculate_type_id(&first_input, idx as u64);
+            let type_script = placeholder
+                .clone()
+                .as_builder()
+                .args(type_id.as_bytes().pack())
+                .build();
+            output.as_builder().type_(Some(type_script).pack()).build()
+        })
+        .collect::<Vec<_>>();
+    Ok(tx.as_advanced_builder().set_outputs(outputs).build())
+}
+
+/// A builder to create a type id cell, the type id args is filled in
+/// `adjust_after_balance` when the first input is known, so use
+/// `build_balanced` or `build_unlocked`.
+pub struct TypeIdCreateBuilder {
+    pub lock: Script,
+    pub data: Bytes,
+    /// The capacity of the type id cell, `None` means the occupied capacity
+    pub capacity: Option<u64>,
+}
+
+impl TypeIdCreateBuilder {
+    pub fn new(lock: Script, data: Bytes) -> TypeIdCreateBuilder {
+        TypeIdCreateBuilder {
+            lock,
+            data,
+            capacity: None,
+        }
+    }
+}
+
+impl TxBuilder for TypeIdCreateBuilder {
+    fn build_base(
+        &self,
+        _cell_collector: &mut dyn CellCollector,
+        cell_dep_resolver: &dyn CellDepResolver,
+        _header_dep_resolver: &dyn HeaderDepResolver,
+        _tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, TxBuilderError> {
+        let lock_cell_deps = cell_dep_resolver
+            .resolve_all(&self.lock)
+            .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(self.lock.clone()))?;
+        let type_script = ScriptId::new_type(TYPE_ID_CODE_HASH).dummy_type_id_script();
+        let base_output = CellOutput::new_builder()
+            .lock(self.lock.clone())
+            .type_(Some(type_script).pack())
+            .build();
+        let occupied_capacity = base_output
+            .occupied_capacity(Capacity::bytes(self.data.len()).unwrap())
+            .unwrap()
+            .as_u64();
+        let capacity = match self.capacity {
+            Some(capacity) if capacity < occupied_capacity => {
+                return Err(TxBuilderError::Other(anyhow!(
+                    "Not enough capacity to hold a type id cell, min: {}, actual: {}",
+                    occupied_capacity,
+                    capacity,
+                )));
+            }
+            Some(capacity) => capacity,
+            None => occupied_capacity,
+        };
+        Ok(TransactionBuilder::default()
+            .cell_deps(lock_cell_deps)
+            .output(base_output.as_builder().capacity(capacity.pack()).build())
+            .output_data(self.data.pack())
+            .build())
+    }
+
+    fn adjust_after_balance(
+        &self,
+        tx: TransactionView,
+        _tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, TxBuilderError> {
+        fill_type_id_args(tx)
+    }
+}
+
+/// A builder to update the data (and optionally the lock) of a type id cell.
+///
+/// The capacity of the cell is kept, or increased to the occupied capacity
+/// when the new data is larger, the difference is balanced by the `CapacityBalancer`.
+pub struct TypeIdUpdateBuilder {
+    pub type_id_script: Script,
+    pub new_data: Bytes,
+    /// The lock of the updated cell, `None` means keep the current lock
+    pub new_lock: Option<Script>,
+}
+
+impl TypeIdUpdateBuilder {
+    pub fn new(type_id_script: Script, new_data: Bytes) -> TypeIdUpdateBuilder {
+        TypeIdUpdateBuilder {
+            type_id_script,
+            new_data,
+            new_lock: None,
+        }
+    }
+}
+
+impl TxBuilder for TypeIdUpdateBuilder {
+    fn build_base(
+        &self,
+        cell_collector: &mut dyn CellCollector,
+        cell_dep_resolver: &dyn CellDepResolver,
+        _header_dep_resolver: &dyn HeaderDepResolver,
+        _tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, TxBuilderError> {
+        if !ScriptId::from(&self.type_id_script).is_type_id() {
+            return Err(TxBuilderError::InvalidParameter(anyhow!(
+                "not a type id script: {:?}",
+                self.type_id_script
+            )));
+        }
+        let query = CellQueryOptions::new_type(self.type_id_script.clone());
+        let (cells, _) = cell_collector.collect_live_cells(&query, true)?;
+        let cell = cells.into_iter().next().ok_or_else(|| {
+            TxBuilderError::Other(anyhow!(
+                "can not find type id cell by type script: {:?}",
+                self.type_id_script
+            ))
+        })?;
+
+        build_update_tx(
+            &cell.out_point,
+            &cell.output,
+            &self.new_data,
+            self.new_lock.as_ref(),
+            cell_dep_resolver,
+        )
+    }
+}
+
+/// Spend the cell at `out_point` and recreate it with `new_data` (and
+/// `new_lock`), the capacity is increased to the occupied capacity if needed.
+pub(crate) fn build_update_tx(
+    out_point: &OutPoint,
+    cell_output: &CellOutput,
+    new_data: &Bytes,
+    new_lock: Option<&Script>,
+    cell_dep_resolver: &dyn CellDepResolver,
+) -> Result<TransactionView, TxBuilderError> {
+    let input_lock = cell_output.lock();
+    let lock_cell_deps = cell_dep_resolver
+        .resolve_all(&input_lock)
+        .ok_or(TxBuilderError::ResolveCellDepFailed(input_lock))?;
+    let lock = new_lock.cloned().unwrap_or_else(|| cell_output.lock());
+    let base_output = cell_output.clone().as_builder().lock(lock).build();
+    let occupied_capacity = base_output
+        .occupied_capacity(Capacity::bytes(new_data.len()).unwrap())
+        .unwrap()
+        .as_u64();
+    let capacity: u64 = cell_output.capacity().unpack();
+    let output = base_output
+        .as_builder()
+        .capacity(capacity.max(occupied_capacity).pack())
+        .build();
+    Ok(TransactionBuilder::default()
+        .cell_deps(lock_cell_deps)
+        .input(CellInput::new(out_point.clone(), 0))
+        .output(output)
+        .output_data(new_data.pack())
+        .build())
+}
@@ -1,43 +1,91 @@
+mod plan;
+mod registry;
+mod smart;
 mod sudt;
+mod validator;
+pub mod xudt;
+
+pub use plan::{PlannedAction, PlannedCell, PlannedReceiver, UdtTransferPlan};
+pub use registry::{TokenEntry, TokenRegistry, TokenRegistryError};
+pub use smart::{UdtReceiverDecision, UdtSmartTransferBuilder};
+pub use validator::{validate_sudt_data, validate_xudt_data, UdtDataValidator};
 
 use anyhow::anyhow;
+use ckb_jsonrpc_types::JsonBytes;
 use ckb_types::{
     bytes::{BufMut, Bytes, BytesMut},
-    core::{Capacity, TransactionBuilder, TransactionView},
-    packed::{Byte32, CellDep, CellInput, CellOutput, Script},
+    core::{Capacity, DepType, TransactionBuilder, TransactionView},
+    packed::{Byte32, CellDep, CellInput, CellOutput, OutPoint, Script},
     prelude::*,
 };
-use std::collections::HashSet;
 
-use super::{TransferAction, TxBuilder, TxBuilderError};
+use super::{extend_unique, TransferAction, TxBuilder, TxBuilderError};
 use crate::traits::{
-    CellCollector, CellDepResolver, CellQueryOptions, HeaderDepResolver,
+    CellCollector, CellDepResolver, CellQueryOptions, HeaderDepResolver, LiveCell,
     TransactionDependencyProvider, ValueRangeOption,
 };
-use crate::types::ScriptId;
+use crate::types::{ScriptHashTypeExt, ScriptId};
+
+/// The xUDT flag of a fixed total supply, the 16 bytes little endian supply
+/// limit follows the flags in the type args. Only checked by the xUDT builds
+/// supporting the supply limit.
+pub const XUDT_FLAG_SUPPLY_LIMIT: u32 = 0x1000_0000;
 
 /// The udt type
 #[derive(Debug, Eq, PartialEq, Hash, Clone)]
 pub enum UdtType {
     Sudt,
-    /// The parameter is <xudt args> (NOTE: xudt is current not supported, this variant is for future support)
-    Xudt(Bytes),
+    Xudt {
+        /// The <xudt args> after the owner lock hash: <4 bytes flags> <extension data>
+        args: Bytes,
+        /// The total supply, when set `XUDT_FLAG_SUPPLY_LIMIT` is added to
+        /// the flags (0 if `args` is empty) and the limit is inserted between
+        /// the flags and the extension data.
+        supply_limit: Option<u128>,
+    },
 }
 
 impl UdtType {
+    /// xUDT without a supply limit
+    pub fn new_xudt(args: Bytes) -> UdtType {
+        UdtType::Xudt {
+            args,
+            supply_limit: None,
+        }
+    }
+
     pub fn build_script(&self, script_id: &ScriptId, owner_lock_hash: &Byte32) -> Script {
         let type_script_args = match self {
             UdtType::Sudt => owner_lock_hash.as_bytes(),
-            UdtType::Xudt(extra_args) => {
-                let mut data = BytesMut::with_capacity(32 + extra_args.len());
+            UdtType::Xudt {
+                args,
+                supply_limit: None,
+            } => {
+                let mut data = BytesMut::with_capacity(32 + args.len());
                 data.put(owner_lock_hash.as_slice());
-                data.put(extra_args.as_ref());
+                data.put(args.as_ref());
+                data.freeze()
+            }
+            UdtType::Xudt {
+                args,
+                supply_limit: Some(limit),
+            } => {
+                let mut flags_bytes = [0u8; 4];
+                let flags_len = args.len().min(4);
+                flags_bytes[..flags_len].copy_from_slice(&args[..flags_len]);
+                let flags = u32::from_le_bytes(flags_bytes) | XUDT_FLAG_SUPPLY_LIMIT;
+                let extension_data = &args[flags_len..];
+                let mut data = BytesMut::with_capacity(32 + 4 + 16 + extension_data.len());
+                data.put(owner_lock_hash.as_slice());
+                data.put(&flags.to_le_bytes()[..]);
+                data.put(&limit.to_le_bytes()[..]);
+                data.put(extension_data);
                 data.freeze()
             }
         };
         Script::new_builder()
             .code_hash(script_id.code_hash.pack())
-            .hash_type(script_id.hash_type.into())
+            .hash_type(script_id.hash_type.to_packed())
             .args(type_script_args.pack())
             .build()
     }
@@ -63,7 +111,7 @@ pub struct UdtTargetReceiver {
 }
 
 pub struct ReceiverBuildOutput {
-    pub input: Option<(CellInput, CellDep)>,
+    pub input: Option<(CellInput, Vec<CellDep>)>,
     pub output: CellOutput,
     pub output_data: Bytes,
 }
@@ -85,92 +133,197 @@ impl UdtTargetReceiver {
         cell_collector: &mut dyn CellCollector,
         cell_dep_resolver: &dyn CellDepResolver,
     ) -> Result<ReceiverBuildOutput, TxBuilderError> {
-        match self.action {
-            TransferAction::Create => {
-                let data_len = self
-                    .extra_data
-                    .as_ref()
-                    .map(|data| data.len())
-                    .unwrap_or_default()
-                    + 16;
-                let mut data = BytesMut::with_capacity(data_len);
-                data.put(&self.amount.to_le_bytes()[..]);
-                if let Some(extra_data) = self.extra_data.as_ref() {
-                    data.put(extra_data.as_ref());
-                }
+        self.build_excluding(type_script, cell_collector, cell_dep_resolver, &[])
+    }
 
-                let base_output = CellOutput::new_builder()
-                    .lock(self.lock_script.clone())
-                    .type_(Some(type_script.clone()).pack())
-                    .build();
-                let base_occupied_capacity = base_output
-                    .occupied_capacity(Capacity::bytes(data_len).unwrap())
-                    .unwrap()
-                    .as_u64();
-                let final_capacity = if let Some(capacity) = self.capacity.as_ref() {
-                    if *capacity >= base_occupied_capacity {
-                        *capacity
-                    } else {
-                        return Err(TxBuilderError::Other(anyhow!(
-                            "Not enough capacity to hold a receiver cell, min: {}, actual: {}",
-                            base_occupied_capacity,
-                            *capacity,
-                        )));
-                    }
-                } else {
-                    base_occupied_capacity
-                };
-                let output = base_output
-                    .as_builder()
-                    .capacity(final_capacity.pack())
-                    .build();
-                Ok(ReceiverBuildOutput {
-                    input: None,
-                    output,
-                    output_data: data.freeze(),
-                })
-            }
+    /// Same as `build`, but the cells in `excluded_out_points` (usually the
+    /// inputs already in the transaction) are never picked when `action` is
+    /// `Update`.
+    pub fn build_excluding(
+        &self,
+        type_script: &Script,
+        cell_collector: &mut dyn CellCollector,
+        cell_dep_resolver: &dyn CellDepResolver,
+        excluded_out_points: &[OutPoint],
+    ) -> Result<ReceiverBuildOutput, TxBuilderError> {
+        self.build_excluding_with(
+            type_script,
+            cell_collector,
+            cell_dep_resolver,
+            excluded_out_points,
+            true,
+        )
+    }
+
+    /// Same as `build_excluding`, the `Update` receiver cell is only locked in
+    /// the cell collector when `apply_changes` is true.
+    pub(crate) fn build_excluding_with(
+        &self,
+        type_script: &Script,
+        cell_collector: &mut dyn CellCollector,
+        cell_dep_resolver: &dyn CellDepResolver,
+        excluded_out_points: &[OutPoint],
+        apply_changes: bool,
+    ) -> Result<ReceiverBuildOutput, TxBuilderError> {
+        match self.action {
+            TransferAction::Create => self.build_create(type_script),
             TransferAction::Update => {
-                let receiver_query = {
-                    let mut query = CellQueryOptions::new_lock(self.lock_script.clone());
-                    query.secondary_script = Some(type_script.clone());
-                    query.data_len_range = Some(ValueRangeOption::new_min(16));
-                    query
-                };
-                let (receiver_cells, _) =
-                    cell_collector.collect_live_cells(&receiver_query, true)?;
-                if receiver_cells.is_empty() {
-                    return Err(TxBuilderError::Other(anyhow!(
-                        "update receiver cell failed, cell not found, lock={:?}",
-                        self.lock_script
-                    )));
-                }
+                let receiver_cell = self
+                    .collect_update_cell(
+                        type_script,
+                        cell_collector,
+                        excluded_out_points,
+                        apply_changes,
+                    )?
+                    .ok_or_else(|| {
+                        TxBuilderError::Other(anyhow!(
+                            "update receiver cell failed, cell not found, lock={:?}",
+                            self.lock_script
+                        ))
+                    })?;
+                self.build_update(&receiver_cell, cell_dep_resolver)
+            }
+        }
+    }
 
-                let receiver_cell_dep =
-                    cell_dep_resolver
-                        .resolve(&self.lock_script)
-                        .ok_or_else(|| {
-                            TxBuilderError::ResolveCellDepFailed(self.lock_script.clone())
-                        })?;
-
-                let mut amount_bytes = [0u8; 16];
-                let receiver_cell = &receiver_cells[0];
-                amount_bytes.copy_from_slice(&receiver_cell.output_data.as_ref()[0..16]);
-                let old_amount = u128::from_le_bytes(amount_bytes);
-                let new_amount = old_amount + self.amount;
-                let mut new_data = receiver_cell.output_data.as_ref().to_vec();
-                new_data[0..16].copy_from_slice(&new_amount.to_le_bytes()[..]);
-                let output_data = Bytes::from(new_data);
-
-                let input = CellInput::new(receiver_cell.out_point.clone(), 0);
-                Ok(ReceiverBuildOutput {
-                    input: Some((input, receiver_cell_dep)),
-                    output: receiver_cell.output.clone(),
-                    output_data,
-                })
+    fn build_create(&self, type_script: &Script) -> Result<ReceiverBuildOutput, TxBuilderError> {
+        let data_len = self
+            .extra_data
+            .as_ref()
+            .map(|data| data.len())
+            .unwrap_or_default()
+            + 16;
+        let mut data = BytesMut::with_capacity(data_len);
+        data.put(&self.amount.to_le_bytes()[..]);
+        if let Some(extra_data) = self.extra_data.as_ref() {
+            data.put(extra_data.as_ref());
+        }
+
+        let base_output = CellOutput::new_builder()
+            .lock(self.lock_script.clone())
+            .type_(Some(type_script.clone()).pack())
+            .build();
+        let base_occupied_capacity = base_output
+            .occupied_capacity(Capacity::bytes(data_len).unwrap())
+            .unwrap()
+            .as_u64();
+        let final_capacity = if let Some(capacity) = self.capacity.as_ref() {
+            if *capacity >= base_occupied_capacity {
+                *capacity
+            } else {
+                return Err(TxBuilderError::Other(anyhow!(
+                    "Not enough capacity to hold a receiver cell, min: {}, actual: {}",
+                    base_occupied_capacity,
+                    *capacity,
+                )));
             }
+        } else {
+            base_occupied_capacity
+        };
+        let output = base_output
+            .as_builder()
+            .capacity(final_capacity.pack())
+            .build();
+        Ok(ReceiverBuildOutput {
+            input: None,
+            output,
+            output_data: data.freeze(),
+        })
+    }
+
+    /// The query of the receiver cells updated by the `Update` action
+    pub(crate) fn update_query(&self, type_script: &Script) -> CellQueryOptions {
+        let mut query = CellQueryOptions::new_lock(self.lock_script.clone());
+        query.secondary_script = Some(type_script.clone());
+        query.data_len_range = Some(ValueRangeOption::new_min(16));
+        // the receiver cell may be created by a chained transaction
+        query.allow_unconfirmed = true;
+        query
+    }
+
+    fn collect_update_cell(
+        &self,
+        type_script: &Script,
+        cell_collector: &mut dyn CellCollector,
+        excluded_out_points: &[OutPoint],
+        apply_changes: bool,
+    ) -> Result<Option<LiveCell>, TxBuilderError> {
+        let mut receiver_query = self.update_query(type_script);
+        receiver_query.excluded_out_points = excluded_out_points.to_vec();
+        let (receiver_cells, _) =
+            cell_collector.collect_live_cells(&receiver_query, apply_changes)?;
+        Ok(receiver_cells.into_iter().next())
+    }
+
+    fn build_update(
+        &self,
+        receiver_cell: &LiveCell,
+        cell_dep_resolver: &dyn CellDepResolver,
+    ) -> Result<ReceiverBuildOutput, TxBuilderError> {
+        let receiver_cell_deps = cell_dep_resolver
+            .resolve_all(&self.lock_script)
+            .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(self.lock_script.clone()))?;
+
+        let output_data = add_udt_amount(&receiver_cell.output_data, self.amount)?;
+
+        let input = CellInput::new(receiver_cell.out_point.clone(), 0);
+        Ok(ReceiverBuildOutput {
+            input: Some((input, receiver_cell_deps)),
+            output: receiver_cell.output.clone(),
+            output_data,
+        })
+    }
+}
+
+fn validate_outputs_data(
+    validator: Option<&UdtDataValidator>,
+    outputs_data: &[ckb_types::packed::Bytes],
+) -> Result<(), TxBuilderError> {
+    if let Some(validator) = validator {
+        for (idx, data) in outputs_data.iter().enumerate() {
+            validator(&data.raw_data())
+                .map_err(|err| TxBuilderError::InvalidOutputData(idx, err))?;
         }
     }
+    Ok(())
+}
+
+pub(crate) fn checked_add_amount(total: u128, amount: u128) -> Result<u128, TxBuilderError> {
+    total
+        .checked_add(amount)
+        .ok_or(TxBuilderError::AmountOverflow(total, amount))
+}
+
+/// Add `amount` to the udt amount (the first 16 bytes) of `data`
+fn add_udt_amount(data: &Bytes, amount: u128) -> Result<Bytes, TxBuilderError> {
+    let mut amount_bytes = [0u8; 16];
+    amount_bytes.copy_from_slice(&data.as_ref()[0..16]);
+    let new_amount = checked_add_amount(u128::from_le_bytes(amount_bytes), amount)?;
+    Ok(set_udt_amount(data, new_amount))
+}
+
+/// Replace the udt amount (the first 16 bytes) of `data`
+pub(crate) fn set_udt_amount(data: &Bytes, amount: u128) -> Bytes {
+    let mut new_data = data.as_ref().to_vec();
+    new_data[0..16].copy_from_slice(&amount.to_le_bytes()[..]);
+    Bytes::from(new_data)
+}
+
+/// How the owner authorizes an issuance
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Default)]
+pub enum OwnerMode {
+    /// Spend a plain cell of the owner lock as the first input. The sUDT
+    /// owner mode, and the xUDT owner mode when
+    /// `XUDT_OWNER_MODE_INPUT_LOCK_NOT` (`0x2000_0000`) is not set in the flags.
+    #[default]
+    ConsumeOwnerCell,
+    /// Add the live cell `out_point` of the owner lock as a cell dep instead
+    /// of spending it. The xUDT scripts only check the input locks (and the
+    /// input/output types when `XUDT_OWNER_MODE_INPUT_TYPE`/
+    /// `XUDT_OWNER_MODE_OUTPUT_TYPE` is set), so this mode is for the xUDT
+    /// args with `XUDT_OWNER_MODE_INPUT_LOCK_NOT` (`0x2000_0000`) set and an
+    /// extension script authorizing the owner by the cell deps or witnesses.
+    OwnerCellDep { out_point: OutPoint },
 }
 
 /// The udt issue transaction builder
@@ -185,10 +338,52 @@ pub struct UdtIssueBuilder {
     ///   * type script is None
     ///   * data field is empty
     ///   * is mature
+    ///
+    /// When `owner_mode` is `OwnerCellDep`, the owner cell is not collected.
+    /// The cell deps of the owner lock are added in both modes.
     pub owner: Script,
 
+    /// How the owner authorizes the issuance
+    pub owner_mode: OwnerMode,
+
     /// The receivers
     pub receivers: Vec<UdtTargetReceiver>,
+
+    /// Check every output data before the transaction is assembled
+    pub data_validator: Option<UdtDataValidator>,
+}
+
+impl UdtIssueBuilder {
+    /// The owner cell is always placed at this input index, so the owner
+    /// lock group's witness (which the owner unlocker signs) is the witness
+    /// at this index, no matter how the owner's placeholder witness is sized.
+    /// Only when `owner_mode` is `ConsumeOwnerCell`.
+    pub const OWNER_INPUT_INDEX: usize = 0;
+
+    /// The type script of the issued udt, callers can record its script hash
+    /// as the token id.
+    pub fn type_script(&self) -> Script {
+        self.udt_type
+            .build_script(&self.script_id, &self.owner.calc_script_hash())
+    }
+
+    /// Check the total issued amount against the xUDT supply limit
+    fn check_supply_limit(&self) -> Result<(), TxBuilderError> {
+        if let UdtType::Xudt {
+            supply_limit: Some(limit),
+            ..
+        } = self.udt_type
+        {
+            let mut total: u128 = 0;
+            for receiver in &self.receivers {
+                total = checked_add_amount(total, receiver.amount)?;
+            }
+            if total > limit {
+                return Err(TxBuilderError::SupplyLimitExceeded(limit, total));
+            }
+        }
+        Ok(())
+    }
 }
 
 impl TxBuilder for UdtIssueBuilder {
@@ -197,38 +392,57 @@ impl TxBuilder for UdtIssueBuilder {
         cell_collector: &mut dyn CellCollector,
         cell_dep_resolver: &dyn CellDepResolver,
         _header_dep_resolver: &dyn HeaderDepResolver,
-        _tx_dep_provider: &dyn TransactionDependencyProvider,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
     ) -> Result<TransactionView, TxBuilderError> {
-        // Build inputs
-        let owner_query = {
-            let mut query = CellQueryOptions::new_lock(self.owner.clone());
-            query.secondary_script_len_range = Some(ValueRangeOption::new_exact(0));
-            query.data_len_range = Some(ValueRangeOption::new_exact(0));
-            query
-        };
+        self.check_supply_limit()?;
 
-        let (owner_cells, _) = cell_collector.collect_live_cells(&owner_query, true)?;
-        if owner_cells.is_empty() {
-            return Err(TxBuilderError::Other(anyhow!("owner cell not found")));
+        // Build inputs
+        let mut inputs = Vec::new();
+        let mut owner_cell_dep = None;
+        match &self.owner_mode {
+            OwnerMode::ConsumeOwnerCell => {
+                let owner_query = {
+                    let mut query = CellQueryOptions::new_lock(self.owner.clone());
+                    query.secondary_script_len_range = Some(ValueRangeOption::new_exact(0));
+                    query.data_len_range = Some(ValueRangeOption::new_exact(0));
+                    query
+                };
+                let (owner_cells, _) = cell_collector.collect_live_cells(&owner_query, true)?;
+                if owner_cells.is_empty() {
+                    return Err(TxBuilderError::Other(anyhow!("owner cell not found")));
+                }
+                inputs.push(CellInput::new(owner_cells[0].out_point.clone(), 0));
+            }
+            OwnerMode::OwnerCellDep { out_point } => {
+                let owner_cell = tx_dep_provider.get_cell(out_point)?;
+                if owner_cell.lock() != self.owner {
+                    return Err(TxBuilderError::InvalidParameter(anyhow!(
+                        "the lock of owner cell {} is not the owner lock",
+                        out_point
+                    )));
+                }
+                owner_cell_dep = Some(
+                    CellDep::new_builder()
+                        .out_point(out_point.clone())
+                        .dep_type(DepType::Code.into())
+                        .build(),
+                );
+            }
         }
-        let mut inputs = vec![CellInput::new(owner_cells[0].out_point.clone(), 0)];
 
         // Build output type script
-        let owner_lock_hash = self.owner.calc_script_hash();
-        let type_script = self
-            .udt_type
-            .build_script(&self.script_id, &owner_lock_hash);
+        let type_script = self.type_script();
 
-        let owner_cell_dep = cell_dep_resolver
-            .resolve(&self.owner)
+        let owner_cell_deps = cell_dep_resolver
+            .resolve_all(&self.owner)
             .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(self.owner.clone()))?;
-        let udt_cell_dep = cell_dep_resolver
-            .resolve(&type_script)
+        let udt_cell_deps = cell_dep_resolver
+            .resolve_all(&type_script)
             .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(type_script.clone()))?;
-        #[allow(clippy::mutable_key_type)]
-        let mut cell_deps = HashSet::new();
-        cell_deps.insert(owner_cell_dep);
-        cell_deps.insert(udt_cell_dep);
+        let mut cell_deps = Vec::new();
+        extend_unique(&mut cell_deps, owner_cell_deps);
+        extend_unique(&mut cell_deps, udt_cell_deps);
+        extend_unique(&mut cell_deps, owner_cell_dep.into_iter().collect());
 
         // Build outputs, outputs_data, cell_deps
         let mut outputs = Vec::new();
@@ -239,15 +453,16 @@ impl TxBuilder for UdtIssueBuilder {
                 output,
                 output_data,
             } = receiver.build(&type_script, cell_collector, cell_dep_resolver)?;
-            if let Some((input, input_lock_cell_dep)) = input {
+            if let Some((input, input_lock_cell_deps)) = input {
                 inputs.push(input);
-                cell_deps.insert(input_lock_cell_dep);
+                extend_unique(&mut cell_deps, input_lock_cell_deps);
             }
             outputs.push(output);
             outputs_data.push(output_data.pack());
         }
+        validate_outputs_data(self.data_validator.as_ref(), &outputs_data)?;
         Ok(TransactionBuilder::default()
-            .set_cell_deps(cell_deps.into_iter().collect())
+            .set_cell_deps(cell_deps)
             .set_inputs(inputs)
             .set_outputs(outputs)
             .set_outputs_data(outputs_data)
@@ -265,43 +480,117 @@ pub struct UdtTransferBuilder {
 
     /// The transfer receivers
     pub receivers: Vec<UdtTargetReceiver>,
+
+    /// Check every output data (include the sender's change cell) before the
+    /// transaction is assembled
+    pub data_validator: Option<UdtDataValidator>,
 }
 
-impl TxBuilder for UdtTransferBuilder {
-    fn build_base(
+impl UdtTransferBuilder {
+    /// Build a transfer of the token registered as `symbol`, the receiver
+    /// amounts are human readable (e.g. `"12.5"`) and scaled by the token's decimals.
+    pub fn new_by_symbol(
+        registry: &TokenRegistry,
+        symbol: &str,
+        sender: Script,
+        receivers: Vec<(TransferAction, Script, &str)>,
+    ) -> Result<UdtTransferBuilder, TokenRegistryError> {
+        let entry = registry
+            .lookup_by_symbol(symbol)
+            .ok_or_else(|| TokenRegistryError::SymbolNotFound(symbol.to_string()))?;
+        let receivers = receivers
+            .into_iter()
+            .map(|(action, lock_script, amount)| {
+                let amount = entry.parse_amount(amount)?;
+                Ok(UdtTargetReceiver::new(action, lock_script, amount))
+            })
+            .collect::<Result<Vec<_>, TokenRegistryError>>()?;
+        Ok(UdtTransferBuilder {
+            type_script: entry.type_script.clone(),
+            sender,
+            receivers,
+            data_validator: None,
+        })
+    }
+
+    /// Select the cells and resolve the receivers without building the
+    /// transaction. The selected cells are locked in `cell_collector`.
+    pub fn plan(
         &self,
         cell_collector: &mut dyn CellCollector,
-        cell_dep_resolver: &dyn CellDepResolver,
-        _header_dep_resolver: &dyn HeaderDepResolver,
-        _tx_dep_provider: &dyn TransactionDependencyProvider,
-    ) -> Result<TransactionView, TxBuilderError> {
+    ) -> Result<UdtTransferPlan, TxBuilderError> {
         let sender_query = {
             let mut query = CellQueryOptions::new_lock(self.sender.clone());
             query.secondary_script = Some(self.type_script.clone());
             query.data_len_range = Some(ValueRangeOption::new_min(16));
+            // spend the udt change of a chained transaction
+            query.allow_unconfirmed = true;
             query
         };
         let (sender_cells, _) = cell_collector.collect_live_cells(&sender_query, true)?;
         if sender_cells.is_empty() {
             return Err(TxBuilderError::Other(anyhow!("sender cell not found")));
         }
-        let sender_cell = &sender_cells[0];
+        let sender_cell = PlannedCell::from(&sender_cells[0]);
 
-        let sender_cell_dep = cell_dep_resolver
-            .resolve(&self.sender)
-            .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(self.sender.clone()))?;
-        let udt_cell_dep = cell_dep_resolver
-            .resolve(&self.type_script)
-            .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(self.type_script.clone()))?;
-        #[allow(clippy::mutable_key_type)]
-        let mut cell_deps = HashSet::new();
-        cell_deps.insert(sender_cell_dep);
-        cell_deps.insert(udt_cell_dep);
-
-        let mut amount_bytes = [0u8; 16];
-        amount_bytes.copy_from_slice(&sender_cell.output_data.as_ref()[0..16]);
-        let input_total = u128::from_le_bytes(amount_bytes);
-        let output_total: u128 = self.receivers.iter().map(|receiver| receiver.amount).sum();
+        let mut used_out_points = vec![sender_cells[0].out_point.clone()];
+        let mut receivers = Vec::new();
+        // Amount sent back to the sender cell itself
+        let mut merged_amount: u128 = 0;
+        for receiver in &self.receivers {
+            let action = match receiver.action {
+                TransferAction::Create => {
+                    let ReceiverBuildOutput {
+                        output,
+                        output_data,
+                        ..
+                    } = receiver.build_create(&self.type_script)?;
+                    let capacity: u64 = output.capacity().unpack();
+                    PlannedAction::Create {
+                        capacity: capacity.into(),
+                        output_data: JsonBytes::from_bytes(output_data),
+                    }
+                }
+                TransferAction::Update => {
+                    match receiver.collect_update_cell(
+                        &self.type_script,
+                        cell_collector,
+                        &used_out_points,
+                        true,
+                    )? {
+                        Some(cell) => {
+                            used_out_points.push(cell.out_point.clone());
+                            PlannedAction::Update {
+                                cell: PlannedCell::from(&cell),
+                            }
+                        }
+                        // A receiver sharing the sender lock must not pick a
+                        // cell already used as input, if no other cell is
+                        // found the amount is merged into the sender cell.
+                        None if receiver.lock_script == self.sender => {
+                            merged_amount = checked_add_amount(merged_amount, receiver.amount)?;
+                            PlannedAction::MergeIntoSender
+                        }
+                        None => {
+                            return Err(TxBuilderError::Other(anyhow!(
+                                "update receiver cell failed, cell not found, lock={:?}",
+                                receiver.lock_script
+                            )));
+                        }
+                    }
+                }
+            };
+            receivers.push(PlannedReceiver {
+                lock_script: receiver.lock_script.clone().into(),
+                amount: receiver.amount.into(),
+                action,
+            });
+        }
+
+        let input_total = sender_cell.udt_amount()?;
+        let output_total = self.receivers.iter().try_fold(0u128, |total, receiver| {
+            checked_add_amount(total, receiver.amount)
+        })? - merged_amount;
         if input_total < output_total {
             return Err(TxBuilderError::Other(anyhow!(
                 "sender udt amount not enough, expected at least: {}, actual: {}",
@@ -309,37 +598,113 @@ impl TxBuilder for UdtTransferBuilder {
                 input_total
             )));
         }
+        Ok(UdtTransferPlan {
+            sender_cell,
+            change_amount: (input_total - output_total).into(),
+            receivers,
+        })
+    }
 
-        let sender_output_data = {
-            let new_amount = input_total - output_total;
-            let mut new_data = sender_cell.output_data.as_ref().to_vec();
-            new_data[0..16].copy_from_slice(&new_amount.to_le_bytes()[..]);
-            Bytes::from(new_data)
-        };
-
-        let mut inputs = vec![CellInput::new(sender_cell.out_point.clone(), 0)];
-        let mut outputs = vec![sender_cell.output.clone()];
-        let mut outputs_data = vec![sender_output_data.pack()];
-
-        for receiver in &self.receivers {
-            let ReceiverBuildOutput {
-                input,
-                output,
-                output_data,
-            } = receiver.build(&self.type_script, cell_collector, cell_dep_resolver)?;
-            if let Some((input, input_lock_cell_dep)) = input {
-                inputs.push(input);
-                cell_deps.insert(input_lock_cell_dep);
+    /// Build the transaction from a plan returned by `plan`, the result is
+    /// the same as `build_base`.
+    pub fn build_from_plan(
+        &self,
+        plan: &UdtTransferPlan,
+        cell_dep_resolver: &dyn CellDepResolver,
+    ) -> Result<TransactionView, TxBuilderError> {
+        let sender_output = CellOutput::from(plan.sender_cell.output.clone());
+        if sender_output.lock() != self.sender
+            || sender_output.type_().to_opt().as_ref() != Some(&self.type_script)
+        {
+            return Err(TxBuilderError::InvalidParameter(anyhow!(
+                "the sender cell of the plan does not match the builder: {:?}",
+                plan.sender_cell.out_point
+            )));
+        }
+        let sender_cell_deps = cell_dep_resolver
+            .resolve_all(&self.sender)
+            .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(self.sender.clone()))?;
+        let udt_cell_deps = cell_dep_resolver
+            .resolve_all(&self.type_script)
+            .ok_or_else(|| TxBuilderError::ResolveCellDepFailed(self.type_script.clone()))?;
+        let mut cell_deps = Vec::new();
+        extend_unique(&mut cell_deps, sender_cell_deps);
+        extend_unique(&mut cell_deps, udt_cell_deps);
+
+        let mut inputs = vec![CellInput::new(plan.sender_cell.out_point.clone().into(), 0)];
+        let mut outputs = vec![sender_output];
+        let mut outputs_data = vec![Default::default()];
+        let mut output_total: u128 = 0;
+        for receiver in &plan.receivers {
+            let lock_script = Script::from(receiver.lock_script.clone());
+            let amount: u128 = receiver.amount.into();
+            match &receiver.action {
+                PlannedAction::Create {
+                    capacity,
+                    output_data,
+                } => {
+                    let capacity: u64 = (*capacity).into();
+                    let output = CellOutput::new_builder()
+                        .lock(lock_script)
+                        .type_(Some(self.type_script.clone()).pack())
+                        .capacity(capacity.pack())
+                        .build();
+                    outputs.push(output);
+                    outputs_data.push(output_data.clone().into_bytes().pack());
+                    output_total = checked_add_amount(output_total, amount)?;
+                }
+                PlannedAction::Update { cell } => {
+                    let lock_cell_deps = cell_dep_resolver
+                        .resolve_all(&lock_script)
+                        .ok_or(TxBuilderError::ResolveCellDepFailed(lock_script))?;
+                    extend_unique(&mut cell_deps, lock_cell_deps);
+                    let new_amount = checked_add_amount(cell.udt_amount()?, amount)?;
+                    let output_data =
+                        set_udt_amount(&cell.output_data.clone().into_bytes(), new_amount);
+                    inputs.push(CellInput::new(cell.out_point.clone().into(), 0));
+                    outputs.push(cell.output.clone().into());
+                    outputs_data.push(output_data.pack());
+                    output_total = checked_add_amount(output_total, amount)?;
+                }
+                PlannedAction::MergeIntoSender => {}
             }
-            outputs.push(output);
-            outputs_data.push(output_data.pack());
         }
 
+        let change_amount: u128 = plan.change_amount.into();
+        let input_total = plan.sender_cell.udt_amount()?;
+        if checked_add_amount(output_total, change_amount)? != input_total {
+            return Err(TxBuilderError::InvalidParameter(anyhow!(
+                "udt amount of the plan is not balanced, input: {}, output: {}, change: {}",
+                input_total,
+                output_total,
+                change_amount
+            )));
+        }
+        outputs_data[0] = set_udt_amount(
+            &plan.sender_cell.output_data.clone().into_bytes(),
+            change_amount,
+        )
+        .pack();
+        validate_outputs_data(self.data_validator.as_ref(), &outputs_data)?;
+
         Ok(TransactionBuilder::default()
-            .set_cell_deps(cell_deps.into_iter().collect())
+            .set_cell_deps(cell_deps)
             .set_inputs(inputs)
             .set_outputs(outputs)
             .set_outputs_data(outputs_data)
             .build())
     }
 }
+
+impl TxBuilder for UdtTransferBuilder {
+    fn build_base(
+        &self,
+        cell_collector: &mut dyn CellCollector,
+        cell_dep_resolver: &dyn CellDepResolver,
+        _header_dep_resolver: &dyn HeaderDepResolver,
+        _tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, TxBuilderError> {
+        let plan = self.plan(cell_collector)?;
+        self.build_from_plan(&plan, cell_dep_resolver)
+    }
+}
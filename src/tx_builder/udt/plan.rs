@@ -0,0 +1,123 @@
+use anyhow::anyhow;
+use ckb_jsonrpc_types as json_types;
+use ckb_types::{packed, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::traits::{CellCollector, CellQueryOptions, LiveCell, ValueRangeOption};
+use crate::tx_builder::TxBuilderError;
+
+/// A live udt cell selected by `UdtTransferBuilder::plan`
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PlannedCell {
+    pub out_point: json_types::OutPoint,
+    pub output: json_types::CellOutput,
+    pub output_data: json_types::JsonBytes,
+}
+
+impl PlannedCell {
+    /// The udt amount, the first 16 bytes of the data
+    pub fn udt_amount(&self) -> Result<u128, TxBuilderError> {
+        let data = self.output_data.as_bytes();
+        if data.len() < 16 {
+            return Err(TxBuilderError::InvalidParameter(anyhow!(
+                "invalid udt cell data length: {}, out point: {:?}",
+                data.len(),
+                self.out_point
+            )));
+        }
+        let mut amount_bytes = [0u8; 16];
+        amount_bytes.copy_from_slice(&data[0..16]);
+        Ok(u128::from_le_bytes(amount_bytes))
+    }
+
+    /// Check that the cell is still live and not changed
+    fn is_live(&self, cell_collector: &mut dyn CellCollector) -> Result<bool, TxBuilderError> {
+        let output = packed::CellOutput::from(self.output.clone());
+        let out_point = packed::OutPoint::from(self.out_point.clone());
+        let mut query = CellQueryOptions::new_lock(output.lock());
+        query.secondary_script = output.type_().to_opt();
+        query.data_len_range = Some(ValueRangeOption::new_exact(self.output_data.len() as u64));
+        query.min_total_capacity = u64::MAX;
+        let (cells, _) = cell_collector.collect_live_cells(&query, false)?;
+        Ok(cells.iter().any(|cell| {
+            cell.out_point == out_point
+                && cell.output == output
+                && cell.output_data.as_ref() == self.output_data.as_bytes()
+        }))
+    }
+}
+
+impl From<&LiveCell> for PlannedCell {
+    fn from(cell: &LiveCell) -> PlannedCell {
+        PlannedCell {
+            out_point: cell.out_point.clone().into(),
+            output: cell.output.clone().into(),
+            output_data: json_types::JsonBytes::from_bytes(cell.output_data.clone()),
+        }
+    }
+}
+
+/// How a receiver of `UdtTransferBuilder` is resolved
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlannedAction {
+    /// Create a new udt cell with this capacity and data
+    Create {
+        capacity: json_types::Capacity,
+        output_data: json_types::JsonBytes,
+    },
+    /// Add the amount to this udt cell
+    Update { cell: PlannedCell },
+    /// The receiver uses the sender lock and has no other udt cell, the amount
+    /// is kept in the sender cell
+    MergeIntoSender,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PlannedReceiver {
+    pub lock_script: json_types::Script,
+    pub amount: json_types::Uint128,
+    pub action: PlannedAction,
+}
+
+/// The cells and amounts of a udt transfer, decided before the transaction is
+/// built, so it can be shown to the user for confirmation.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct UdtTransferPlan {
+    /// The sender udt cell, it is the first input and the first output
+    pub sender_cell: PlannedCell,
+    /// The udt amount left in the sender cell
+    pub change_amount: json_types::Uint128,
+    pub receivers: Vec<PlannedReceiver>,
+}
+
+impl UdtTransferPlan {
+    /// All the cells consumed by the plan, in the order of the transaction inputs
+    pub fn input_cells(&self) -> Vec<&PlannedCell> {
+        let mut cells = vec![&self.sender_cell];
+        for receiver in &self.receivers {
+            if let PlannedAction::Update { cell } = &receiver.action {
+                cells.push(cell);
+            }
+        }
+        cells
+    }
+
+    /// Check that all the cells consumed by the plan are still live and not
+    /// changed, the plan can not be built otherwise.
+    ///
+    /// The cells locked by `cell_collector` (e.g. when `plan` was called with
+    /// the same collector) are treated as spent, so usually a new collector
+    /// is used.
+    pub fn revalidate(&self, cell_collector: &mut dyn CellCollector) -> Result<(), TxBuilderError> {
+        for cell in self.input_cells() {
+            if !cell.is_live(cell_collector)? {
+                return Err(TxBuilderError::Other(anyhow!(
+                    "planned cell is spent or changed: {:?}",
+                    cell.out_point
+                )));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,326 @@
+use std::convert::TryFrom;
+use std::fs;
+use std::path::Path;
+
+use ckb_jsonrpc_types as json_types;
+use ckb_types::{
+    packed::{Byte32, Script},
+    H256,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum TokenRegistryError {
+    #[error("token symbol already registered: `{0}`")]
+    DuplicatedSymbol(String),
+
+    #[error("token symbol not found: `{0}`")]
+    SymbolNotFound(String),
+
+    #[error("invalid amount `{0}` for token with `{1}` decimals")]
+    InvalidAmount(String, u8),
+
+    #[error("io error: `{0}`")]
+    Io(#[from] std::io::Error),
+
+    #[error("parse json error: `{0}`")]
+    Json(#[from] serde_json::Error),
+}
+
+/// A known UDT token
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TokenEntry {
+    /// The symbol used to refer to the token, e.g. `USDT`
+    pub symbol: String,
+
+    /// The sudt/xudt type script of the token
+    #[serde(with = "json_script")]
+    pub type_script: Script,
+
+    /// The number of decimal places of the human readable amount
+    pub decimals: u8,
+
+    /// The lock script hash of the issuer (owner) if known
+    pub issuer_lock_hash: Option<H256>,
+}
+
+impl TokenEntry {
+    pub fn new(symbol: &str, type_script: Script, decimals: u8) -> TokenEntry {
+        TokenEntry {
+            symbol: symbol.to_string(),
+            type_script,
+            decimals,
+            issuer_lock_hash: None,
+        }
+    }
+
+    /// Parse a human readable amount (e.g. `"12.5"`) into the raw udt amount.
+    pub fn parse_amount(&self, value: &str) -> Result<u128, TokenRegistryError> {
+        let invalid = || TokenRegistryError::InvalidAmount(value.to_string(), self.decimals);
+        let (int_part, frac_part) = value.split_once('.').unwrap_or((value, ""));
+        if (int_part.is_empty() && frac_part.is_empty())
+            || frac_part.len() > self.decimals as usize
+            || !int_part
+                .chars()
+                .chain(frac_part.chars())
+                .all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+        let unit = 10u128
+            .checked_pow(self.decimals as u32)
+            .ok_or_else(invalid)?;
+        let int_value = if int_part.is_empty() {
+            0
+        } else {
+            int_part.parse::<u128>().map_err(|_| invalid())?
+        };
+        let frac_value = if frac_part.is_empty() {
+            0
+        } else {
+            // frac_part.len() <= decimals, so it can not overflow
+            frac_part.parse::<u128>().map_err(|_| invalid())?
+                * 10u128.pow(self.decimals as u32 - frac_part.len() as u32)
+        };
+        int_value
+            .checked_mul(unit)
+            .and_then(|value| value.checked_add(frac_value))
+            .ok_or_else(invalid)
+    }
+
+    /// Format the raw udt amount with the decimals and symbol, e.g. `"12.5 USDT"`.
+    pub fn format_amount(&self, amount: u128) -> String {
+        let unit = match 10u128.checked_pow(self.decimals as u32) {
+            Some(unit) if self.decimals > 0 => unit,
+            _ => return format!("{} {}", amount, self.symbol),
+        };
+        let frac_value = amount % unit;
+        if frac_value == 0 {
+            format!("{} {}", amount / unit, self.symbol)
+        } else {
+            let frac_str = format!("{:0width$}", frac_value, width = self.decimals as usize);
+            format!(
+                "{}.{} {}",
+                amount / unit,
+                frac_str.trim_end_matches('0'),
+                self.symbol
+            )
+        }
+    }
+}
+
+/// A registry of known UDT tokens, can be persisted as a JSON array of `TokenEntry`.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<TokenEntry>", into = "Vec<TokenEntry>")]
+pub struct TokenRegistry {
+    entries: Vec<TokenEntry>,
+}
+
+impl TokenRegistry {
+    pub fn new() -> TokenRegistry {
+        TokenRegistry::default()
+    }
+
+    /// Register a token, return error if the symbol is already registered.
+    pub fn register(&mut self, entry: TokenEntry) -> Result<(), TokenRegistryError> {
+        if self.lookup_by_symbol(&entry.symbol).is_some() {
+            return Err(TokenRegistryError::DuplicatedSymbol(entry.symbol));
+        }
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    pub fn lookup_by_symbol(&self, symbol: &str) -> Option<&TokenEntry> {
+        self.entries.iter().find(|entry| entry.symbol == symbol)
+    }
+
+    pub fn lookup_by_type_hash(&self, type_hash: &Byte32) -> Option<&TokenEntry> {
+        self.entries
+            .iter()
+            .find(|entry| &entry.type_script.calc_script_hash() == type_hash)
+    }
+
+    pub fn entries(&self) -> &[TokenEntry] {
+        &self.entries
+    }
+
+    pub fn load_from_json_file<P: AsRef<Path>>(
+        path: P,
+    ) -> Result<TokenRegistry, TokenRegistryError> {
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn save_to_json_file<P: AsRef<Path>>(&self, path: P) -> Result<(), TokenRegistryError> {
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(path, content)?;
+        Ok(())
+    }
+}
+
+impl TryFrom<Vec<TokenEntry>> for TokenRegistry {
+    type Error = TokenRegistryError;
+
+    fn try_from(entries: Vec<TokenEntry>) -> Result<Self, Self::Error> {
+        let mut registry = TokenRegistry::new();
+        for entry in entries {
+            registry.register(entry)?;
+        }
+        Ok(registry)
+    }
+}
+
+impl From<TokenRegistry> for Vec<TokenEntry> {
+    fn from(registry: TokenRegistry) -> Self {
+        registry.entries
+    }
+}
+
+mod json_script {
+    use super::json_types;
+    use ckb_types::packed::Script;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(script: &Script, serializer: S) -> Result<S::Ok, S::Error> {
+        json_types::Script::from(script.clone()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Script, D::Error> {
+        json_types::Script::deserialize(deserializer).map(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tx_builder::{udt::UdtTransferBuilder, TransferAction};
+    use crate::types::ScriptHashTypeExt;
+    use ckb_types::{bytes::Bytes, core::ScriptHashType, h256, prelude::*};
+
+    fn build_type_script(args: u8) -> Script {
+        Script::new_builder()
+            .code_hash(
+                h256!("0x5e7a36a77e68eecc013dfa2fe6a23f3b6c344b04005808694ae6dd45eea4cfd5").pack(),
+            )
+            .hash_type(ScriptHashType::Type.to_packed())
+            .args(Bytes::from(vec![args; 32]).pack())
+            .build()
+    }
+
+    #[test]
+    fn test_register_and_lookup() {
+        let mut registry = TokenRegistry::new();
+        let usdt = TokenEntry::new("USDT", build_type_script(1), 6);
+        registry.register(usdt.clone()).unwrap();
+        registry
+            .register(TokenEntry::new("DAI", build_type_script(2), 18))
+            .unwrap();
+
+        assert_eq!(registry.lookup_by_symbol("USDT"), Some(&usdt));
+        assert_eq!(
+            registry.lookup_by_type_hash(&usdt.type_script.calc_script_hash()),
+            Some(&usdt)
+        );
+        assert!(registry.lookup_by_symbol("BTC").is_none());
+        assert!(registry
+            .lookup_by_type_hash(&build_type_script(3).calc_script_hash())
+            .is_none());
+
+        let err = registry
+            .register(TokenEntry::new("USDT", build_type_script(3), 6))
+            .unwrap_err();
+        assert!(matches!(err, TokenRegistryError::DuplicatedSymbol(symbol) if symbol == "USDT"));
+        assert_eq!(registry.entries().len(), 2);
+    }
+
+    #[test]
+    fn test_transfer_builder_by_symbol() {
+        let mut registry = TokenRegistry::new();
+        let usdt = TokenEntry::new("USDT", build_type_script(1), 6);
+        registry.register(usdt.clone()).unwrap();
+        let sender = build_type_script(8);
+        let receiver = build_type_script(9);
+
+        let builder = UdtTransferBuilder::new_by_symbol(
+            &registry,
+            "USDT",
+            sender.clone(),
+            vec![(TransferAction::Create, receiver.clone(), "1.25")],
+        )
+        .unwrap();
+        assert_eq!(builder.type_script, usdt.type_script);
+        assert_eq!(builder.sender, sender);
+        assert_eq!(builder.receivers[0].amount, 1_250_000);
+
+        let err = UdtTransferBuilder::new_by_symbol(
+            &registry,
+            "DAI",
+            sender.clone(),
+            vec![(TransferAction::Create, receiver.clone(), "1")],
+        )
+        .err()
+        .unwrap();
+        assert!(matches!(err, TokenRegistryError::SymbolNotFound(symbol) if symbol == "DAI"));
+
+        let err = UdtTransferBuilder::new_by_symbol(
+            &registry,
+            "USDT",
+            sender,
+            vec![(TransferAction::Create, receiver, "0.0000001")],
+        )
+        .err()
+        .unwrap();
+        assert!(matches!(err, TokenRegistryError::InvalidAmount(_, 6)));
+    }
+
+    #[test]
+    fn test_parse_and_format_amount() {
+        let usdt = TokenEntry::new("USDT", build_type_script(1), 6);
+        assert_eq!(usdt.parse_amount("12.5").unwrap(), 12_500_000);
+        assert_eq!(usdt.parse_amount("12").unwrap(), 12_000_000);
+        assert_eq!(usdt.parse_amount(".000001").unwrap(), 1);
+        for invalid in ["", ".", "1.0000001", "-1", "1e3", "1.2.3"] {
+            assert!(usdt.parse_amount(invalid).is_err(), "{}", invalid);
+        }
+        assert_eq!(usdt.format_amount(12_500_000), "12.5 USDT");
+        assert_eq!(usdt.format_amount(12_000_000), "12 USDT");
+        assert_eq!(usdt.format_amount(1), "0.000001 USDT");
+
+        let raw = TokenEntry::new("RAW", build_type_script(2), 0);
+        assert_eq!(raw.parse_amount("42").unwrap(), 42);
+        assert!(raw.parse_amount("4.2").is_err());
+        assert_eq!(raw.format_amount(42), "42 RAW");
+    }
+
+    #[test]
+    fn test_load_from_json_file() {
+        let mut registry = TokenRegistry::new();
+        let mut usdt = TokenEntry::new("USDT", build_type_script(1), 6);
+        usdt.issuer_lock_hash = Some(h256!("0x1234"));
+        registry.register(usdt).unwrap();
+
+        let path = std::env::temp_dir().join("ckb-sdk-test-token-registry.json");
+        registry.save_to_json_file(&path).unwrap();
+        let loaded = TokenRegistry::load_from_json_file(&path).unwrap();
+        assert_eq!(loaded, registry);
+
+        // two entries with the same symbol
+        let usdt = serde_json::to_value(&registry.entries()[0]).unwrap();
+        fs::write(&path, serde_json::json!([usdt.clone(), usdt]).to_string()).unwrap();
+        let err = TokenRegistry::load_from_json_file(&path).unwrap_err();
+        assert!(matches!(err, TokenRegistryError::Json(_)));
+        fs::remove_file(&path).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod anyhow_tests {
+    use anyhow::anyhow;
+    #[test]
+    fn test_token_registry_error() {
+        let error = super::TokenRegistryError::SymbolNotFound("USDT".to_string());
+        let error = anyhow!(error);
+        assert_eq!("token symbol not found: `USDT`", error.to_string());
+    }
+}
@@ -0,0 +1,193 @@
+use ckb_types::{
+    bytes::Bytes,
+    core::TransactionView,
+    packed::{OutPoint, Script},
+    prelude::*,
+};
+
+use super::{
+    validate_outputs_data, PlannedAction, UdtDataValidator, UdtTargetReceiver, UdtTransferBuilder,
+};
+use crate::constants::{ACP_TYPE_HASH_AGGRON, ACP_TYPE_HASH_LINA};
+use crate::traits::{
+    CellCollector, CellDepResolver, CellQueryOptions, HeaderDepResolver,
+    TransactionDependencyProvider, ValueRangeOption,
+};
+use crate::tx_builder::{TransferAction, TxBuilder, TxBuilderError};
+use crate::types::{ScriptHashTypeExt, ScriptId};
+use crate::unlock::omni_lock::OmniLockFlags;
+
+/// How the udt is delivered to a receiver of `UdtSmartTransferBuilder`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum UdtReceiverDecision {
+    /// The receiver lock is anyone-can-pay, the amount is added to this
+    /// existing udt cell of the receiver
+    Update(OutPoint),
+    /// The receiver lock is anyone-can-pay but has no udt cell, a new udt cell
+    /// is created, the capacity is paid by the sender
+    CreateAcp { capacity: u64 },
+    /// The receiver can not accept udt without signing, a cheque cell with
+    /// this lock is created for the receiver to claim
+    Cheque { lock_script: Script, capacity: u64 },
+}
+
+/// Transfer udt to receivers by their lock script only, how each receiver is
+/// paid is decided by the lock:
+///
+///   * anyone-can-pay lock (or omni-lock with the ACP flag) with a udt cell:
+///     update the udt cell
+///   * anyone-can-pay lock without a udt cell: create one
+///   * other locks: create a cheque cell
+pub struct UdtSmartTransferBuilder {
+    /// The udt type script
+    pub type_script: Script,
+
+    /// Sender's lock script, also the sender of the cheque cells
+    pub sender: Script,
+
+    /// The receiver lock scripts and amounts
+    pub receivers: Vec<(Script, u128)>,
+
+    /// The anyone-can-pay lock script ids
+    pub acp_script_ids: Vec<ScriptId>,
+
+    /// The omni-lock script ids, the lock is anyone-can-pay when the ACP flag
+    /// is set in the args
+    pub omni_lock_script_ids: Vec<ScriptId>,
+
+    /// The cheque lock script id
+    pub cheque_script_id: ScriptId,
+
+    /// Check every output data before the transaction is assembled
+    pub data_validator: Option<UdtDataValidator>,
+}
+
+impl UdtSmartTransferBuilder {
+    /// The anyone-can-pay lock of mainnet and testnet are known by default
+    pub fn new(
+        type_script: Script,
+        sender: Script,
+        receivers: Vec<(Script, u128)>,
+        cheque_script_id: ScriptId,
+    ) -> UdtSmartTransferBuilder {
+        UdtSmartTransferBuilder {
+            type_script,
+            sender,
+            receivers,
+            acp_script_ids: vec![
+                ScriptId::new_type(ACP_TYPE_HASH_LINA),
+                ScriptId::new_type(ACP_TYPE_HASH_AGGRON),
+            ],
+            omni_lock_script_ids: Vec::new(),
+            cheque_script_id,
+            data_validator: None,
+        }
+    }
+
+    /// Check if the lock accepts udt without the owner's signature
+    pub fn is_acp_lock(&self, lock_script: &Script) -> bool {
+        let script_id = ScriptId::from(lock_script);
+        if self.acp_script_ids.contains(&script_id) {
+            return true;
+        }
+        let args = lock_script.args().raw_data();
+        // <21 bytes auth> <1 byte omni-lock flags> ...
+        self.omni_lock_script_ids.contains(&script_id)
+            && args.len() >= 22
+            && OmniLockFlags::from_bits_truncate(args[21]).contains(OmniLockFlags::ACP)
+    }
+
+    /// The cheque lock script from the sender to `receiver`
+    pub fn cheque_lock_script(&self, receiver: &Script) -> Script {
+        let mut args = Vec::with_capacity(40);
+        args.extend_from_slice(&receiver.calc_script_hash().as_slice()[0..20]);
+        args.extend_from_slice(&self.sender.calc_script_hash().as_slice()[0..20]);
+        Script::new_builder()
+            .code_hash(self.cheque_script_id.code_hash.pack())
+            .hash_type(self.cheque_script_id.hash_type.to_packed())
+            .args(Bytes::from(args).pack())
+            .build()
+    }
+
+    fn has_udt_cell(
+        &self,
+        lock_script: &Script,
+        cell_collector: &mut dyn CellCollector,
+    ) -> Result<bool, TxBuilderError> {
+        let mut query = CellQueryOptions::new_lock(lock_script.clone());
+        query.secondary_script = Some(self.type_script.clone());
+        query.data_len_range = Some(ValueRangeOption::new_min(16));
+        let (cells, _) = cell_collector.collect_live_cells(&query, false)?;
+        Ok(!cells.is_empty())
+    }
+
+    /// Build the base transaction, and return how each receiver (in the order
+    /// of `receivers`) is paid.
+    pub fn build_base_with_decisions(
+        &self,
+        cell_collector: &mut dyn CellCollector,
+        cell_dep_resolver: &dyn CellDepResolver,
+    ) -> Result<(TransactionView, Vec<UdtReceiverDecision>), TxBuilderError> {
+        let mut receivers = Vec::with_capacity(self.receivers.len());
+        for (lock_script, amount) in &self.receivers {
+            let receiver = if !self.is_acp_lock(lock_script) {
+                let cheque_lock = self.cheque_lock_script(lock_script);
+                UdtTargetReceiver::new(TransferAction::Create, cheque_lock, *amount)
+            } else if self.has_udt_cell(lock_script, cell_collector)? {
+                UdtTargetReceiver::new(TransferAction::Update, lock_script.clone(), *amount)
+            } else {
+                UdtTargetReceiver::new(TransferAction::Create, lock_script.clone(), *amount)
+            };
+            receivers.push(receiver);
+        }
+        let transfer_builder = UdtTransferBuilder {
+            type_script: self.type_script.clone(),
+            sender: self.sender.clone(),
+            receivers,
+            data_validator: None,
+        };
+        let plan = transfer_builder.plan(cell_collector)?;
+        let decisions = plan
+            .receivers
+            .iter()
+            .map(|receiver| match &receiver.action {
+                PlannedAction::Update { cell } => {
+                    UdtReceiverDecision::Update(cell.out_point.clone().into())
+                }
+                // The receiver is the sender itself
+                PlannedAction::MergeIntoSender => {
+                    UdtReceiverDecision::Update(plan.sender_cell.out_point.clone().into())
+                }
+                PlannedAction::Create { capacity, .. } => {
+                    let lock_script = Script::from(receiver.lock_script.clone());
+                    let capacity = (*capacity).into();
+                    if self.is_acp_lock(&lock_script) {
+                        UdtReceiverDecision::CreateAcp { capacity }
+                    } else {
+                        UdtReceiverDecision::Cheque {
+                            lock_script,
+                            capacity,
+                        }
+                    }
+                }
+            })
+            .collect();
+        let tx = transfer_builder.build_from_plan(&plan, cell_dep_resolver)?;
+        let outputs_data = tx.outputs_data().into_iter().collect::<Vec<_>>();
+        validate_outputs_data(self.data_validator.as_ref(), &outputs_data)?;
+        Ok((tx, decisions))
+    }
+}
+
+impl TxBuilder for UdtSmartTransferBuilder {
+    fn build_base(
+        &self,
+        cell_collector: &mut dyn CellCollector,
+        cell_dep_resolver: &dyn CellDepResolver,
+        _header_dep_resolver: &dyn HeaderDepResolver,
+        _tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, TxBuilderError> {
+        self.build_base_with_decisions(cell_collector, cell_dep_resolver)
+            .map(|(tx, _)| tx)
+    }
+}
@@ -0,0 +1,122 @@
+use ckb_types::{
+    bytes::Bytes,
+    packed::{BytesReader, BytesVecReader},
+    prelude::*,
+};
+
+/// Check the data of an udt output cell, return the reason when it is invalid
+pub type UdtDataValidator = Box<dyn Fn(&Bytes) -> Result<(), String>>;
+
+/// sUDT data: a 16 bytes little endian amount, anything after it is ignored by
+/// the sUDT script.
+pub fn validate_sudt_data(data: &Bytes) -> Result<(), String> {
+    if data.len() < 16 {
+        return Err(format!(
+            "udt data must be at least 16 bytes, got {} bytes",
+            data.len()
+        ));
+    }
+    Ok(())
+}
+
+/// xUDT data: a 16 bytes little endian amount, optionally followed by a
+/// molecule serialized `XudtData`:
+///
+/// ```text
+/// table XudtData {
+///     lock: Bytes,
+///     data: BytesVec,
+/// }
+/// ```
+pub fn validate_xudt_data(data: &Bytes) -> Result<(), String> {
+    validate_sudt_data(data)?;
+    let xudt_data = &data[16..];
+    if xudt_data.is_empty() {
+        return Ok(());
+    }
+    let read_u32 = |offset: usize| -> Result<usize, String> {
+        xudt_data
+            .get(offset..offset + 4)
+            .map(|bytes| {
+                let mut buf = [0u8; 4];
+                buf.copy_from_slice(bytes);
+                u32::from_le_bytes(buf) as usize
+            })
+            .ok_or_else(|| format!("xudt data header is truncated at offset {}", offset))
+    };
+    let total_size = read_u32(0)?;
+    if total_size != xudt_data.len() {
+        return Err(format!(
+            "xudt data total size mismatch, header: {}, actual: {}",
+            total_size,
+            xudt_data.len()
+        ));
+    }
+    let first_offset = read_u32(4)?;
+    if first_offset % 4 != 0 || first_offset < 12 {
+        return Err(format!("invalid xudt data first offset: {}", first_offset));
+    }
+    let field_count = first_offset / 4 - 1;
+    let mut offsets = (0..field_count)
+        .map(|idx| read_u32(4 + idx * 4))
+        .collect::<Result<Vec<_>, _>>()?;
+    offsets.push(total_size);
+    if offsets.windows(2).any(|pair| pair[0] > pair[1]) {
+        return Err("xudt data field offsets are not in order".to_string());
+    }
+    BytesReader::verify(&xudt_data[offsets[0]..offsets[1]], true)
+        .map_err(|err| format!("invalid xudt data lock field: {}", err))?;
+    BytesVecReader::verify(&xudt_data[offsets[1]..offsets[2]], true)
+        .map_err(|err| format!("invalid xudt data data field: {}", err))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_types::{bytes::BufMut, bytes::BytesMut, packed};
+
+    fn xudt_data(lock: &[u8], data: Vec<Bytes>) -> BytesMut {
+        let lock = lock.pack();
+        let data = packed::BytesVec::new_builder()
+            .set(data.iter().map(|item| item.pack()).collect())
+            .build();
+        let header_size = 4 * 3;
+        let total_size = header_size + lock.as_slice().len() + data.as_slice().len();
+        let mut buf = BytesMut::with_capacity(16 + total_size);
+        buf.put(&100u128.to_le_bytes()[..]);
+        buf.put(&(total_size as u32).to_le_bytes()[..]);
+        buf.put(&(header_size as u32).to_le_bytes()[..]);
+        buf.put(&((header_size + lock.as_slice().len()) as u32).to_le_bytes()[..]);
+        buf.put(lock.as_slice());
+        buf.put(data.as_slice());
+        buf
+    }
+
+    #[test]
+    fn test_validate_sudt_data() {
+        assert!(validate_sudt_data(&Bytes::from(vec![0u8; 15])).is_err());
+        validate_sudt_data(&Bytes::from(vec![0u8; 16])).unwrap();
+        validate_sudt_data(&Bytes::from(vec![0u8; 20])).unwrap();
+    }
+
+    #[test]
+    fn test_validate_xudt_data() {
+        validate_xudt_data(&Bytes::from(vec![0u8; 16])).unwrap();
+        let data = xudt_data(&[1u8; 32], vec![Bytes::from(vec![2u8; 3])]).freeze();
+        validate_xudt_data(&data).unwrap();
+
+        // truncated
+        let err = validate_xudt_data(&data.slice(0..data.len() - 1)).unwrap_err();
+        assert!(err.contains("total size mismatch"), "{}", err);
+        // extra bytes which are not a table
+        let mut data = BytesMut::from(&100u128.to_le_bytes()[..]);
+        data.put(&[1u8, 2, 3][..]);
+        assert!(validate_xudt_data(&data.freeze()).is_err());
+        // corrupted embedded length of the lock field
+        let mut data = xudt_data(&[1u8; 32], Vec::new());
+        data[16 + 12] = 33;
+        let err = validate_xudt_data(&data.freeze()).unwrap_err();
+        assert!(err.contains("lock field"), "{}", err);
+    }
+}
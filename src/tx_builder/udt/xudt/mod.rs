@@ -0,0 +1,2 @@
+#[cfg(feature = "rce")]
+pub mod rce;
@@ -0,0 +1,211 @@
+use anyhow::anyhow;
+use ckb_types::{bytes::Bytes, core::TransactionView, packed::Script, prelude::*};
+use sparse_merkle_tree::H256 as SmtH256;
+
+use crate::traits::{
+    CellCollector, CellDepResolver, CellQueryOptions, HeaderDepResolver,
+    TransactionDependencyProvider,
+};
+use crate::tx_builder::type_id::{build_update_tx, fill_type_id_args, TypeIdCreateBuilder};
+use crate::tx_builder::{TxBuilder, TxBuilderError};
+use crate::types::{
+    xudt_rce_mol::{RCData, RCDataBuilder, RCDataUnion, RCRule, RCRuleBuilder},
+    ScriptId,
+};
+use crate::unlock::rc_data::{
+    ListType, RcDataError, RcRuleDataBuilder, SMT_EXISTING, SMT_NOT_EXISTING,
+};
+
+/// The RCData of a RC rule cell
+pub fn build_rc_rule_data(smt_root: &SmtH256, flags: u8) -> Bytes {
+    let rc_rule = RCRuleBuilder::default()
+        .flags(flags.into())
+        .smt_root(Into::<[u8; 32]>::into(*smt_root).pack())
+        .build();
+    RCDataBuilder::default()
+        .set(RCDataUnion::RCRule(rc_rule))
+        .build()
+        .as_bytes()
+}
+
+/// Parse the RCData of a RC rule cell, return the smt root and the flags
+pub fn parse_rc_rule_data(data: &[u8]) -> Result<(SmtH256, u8), TxBuilderError> {
+    let rc_data = RCData::from_slice(data)
+        .map_err(|err| TxBuilderError::Other(anyhow!("invalid RCData: {}", err)))?;
+    match rc_data.to_enum() {
+        RCDataUnion::RCRule(rc_rule) => Ok(rc_rule_root_and_flags(&rc_rule)),
+        RCDataUnion::RCCellVec(_) => Err(TxBuilderError::Other(anyhow!(
+            "expected a RCRule, got a RCCellVec"
+        ))),
+    }
+}
+
+fn rc_rule_root_and_flags(rc_rule: &RCRule) -> (SmtH256, u8) {
+    let mut root = [0u8; 32];
+    root.copy_from_slice(rc_rule.smt_root().as_slice());
+    (SmtH256::from(root), rc_rule.flags().into())
+}
+
+/// Create a RC rule cell owned by a type id, the xudt/omni-lock scripts refer
+/// to it by the type script hash. The type id args is filled in
+/// `adjust_after_balance`, so use `build_balanced` or `build_unlocked`.
+pub struct RceCellCreateBuilder {
+    pub lock: Script,
+    pub smt_root: SmtH256,
+    /// See `rc_data::WHITE_BLACK_LIST_MASK` and `rc_data::EMERGENCY_HALT_MODE_MASK`
+    pub flags: u8,
+    /// The capacity of the RC rule cell, `None` means the occupied capacity
+    pub capacity: Option<u64>,
+}
+
+impl RceCellCreateBuilder {
+    pub fn new(lock: Script, smt_root: SmtH256, flags: u8) -> RceCellCreateBuilder {
+        RceCellCreateBuilder {
+            lock,
+            smt_root,
+            flags,
+            capacity: None,
+        }
+    }
+}
+
+impl TxBuilder for RceCellCreateBuilder {
+    fn build_base(
+        &self,
+        cell_collector: &mut dyn CellCollector,
+        cell_dep_resolver: &dyn CellDepResolver,
+        header_dep_resolver: &dyn HeaderDepResolver,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, TxBuilderError> {
+        let type_id_builder = TypeIdCreateBuilder {
+            lock: self.lock.clone(),
+            data: build_rc_rule_data(&self.smt_root, self.flags),
+            capacity: self.capacity,
+        };
+        type_id_builder.build_base(
+            cell_collector,
+            cell_dep_resolver,
+            header_dep_resolver,
+            tx_dep_provider,
+        )
+    }
+
+    fn adjust_after_balance(
+        &self,
+        tx: TransactionView,
+        _tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, TxBuilderError> {
+        fill_type_id_args(tx)
+    }
+}
+
+/// Insert and remove lock hashes of the smt of a RC rule cell, the flags are kept.
+///
+/// Only the smt root is on chain, so all the keys currently in the smt must be
+/// given by `existing_keys`, the rebuilt root is checked against the cell.
+/// The inserted keys are applied before the removed keys.
+pub struct RceCellUpdateBuilder {
+    /// The type id script of the RC rule cell
+    pub type_id_script: Script,
+    pub existing_keys: Vec<SmtH256>,
+    pub insert_keys: Vec<SmtH256>,
+    pub remove_keys: Vec<SmtH256>,
+}
+
+impl RceCellUpdateBuilder {
+    pub fn new(
+        type_id_script: Script,
+        existing_keys: Vec<SmtH256>,
+        insert_keys: Vec<SmtH256>,
+        remove_keys: Vec<SmtH256>,
+    ) -> RceCellUpdateBuilder {
+        RceCellUpdateBuilder {
+            type_id_script,
+            existing_keys,
+            insert_keys,
+            remove_keys,
+        }
+    }
+
+    // The list type only affects the flags of the rc rule, not the smt root
+    // or the proofs, the flags of the cell are kept when building the data.
+    fn build_tree(&self, updated: bool) -> RcRuleDataBuilder {
+        let mut builder = RcRuleDataBuilder::new(ListType::Black, false);
+        builder.update_hashes(&self.existing_keys);
+        if updated {
+            builder.update_hashes(&self.insert_keys);
+            let removed = self
+                .remove_keys
+                .iter()
+                .map(|key| (*key, *SMT_NOT_EXISTING))
+                .collect::<Vec<_>>();
+            builder.update(&removed);
+        }
+        builder
+    }
+
+    /// The smt root after the update
+    pub fn new_root(&self) -> SmtH256 {
+        self.build_tree(true).root()
+    }
+
+    /// The compiled proof of `keys` against the new root. The keys in the smt
+    /// are proved with the value `SMT_EXISTING` (inclusion), the others with
+    /// `SMT_NOT_EXISTING` (exclusion).
+    pub fn proof(&self, keys: &[SmtH256]) -> Result<Vec<u8>, RcDataError> {
+        self.build_tree(true).proof_keys(keys)
+    }
+
+    /// The value of `key` in the smt after the update
+    pub fn value_of(&self, key: &SmtH256) -> SmtH256 {
+        let removed = self.remove_keys.contains(key);
+        let present = self.existing_keys.contains(key) || self.insert_keys.contains(key);
+        if present && !removed {
+            *SMT_EXISTING
+        } else {
+            *SMT_NOT_EXISTING
+        }
+    }
+}
+
+impl TxBuilder for RceCellUpdateBuilder {
+    fn build_base(
+        &self,
+        cell_collector: &mut dyn CellCollector,
+        cell_dep_resolver: &dyn CellDepResolver,
+        _header_dep_resolver: &dyn HeaderDepResolver,
+        _tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, TxBuilderError> {
+        if !ScriptId::from(&self.type_id_script).is_type_id() {
+            return Err(TxBuilderError::InvalidParameter(anyhow!(
+                "not a type id script: {:?}",
+                self.type_id_script
+            )));
+        }
+        let query = CellQueryOptions::new_type(self.type_id_script.clone());
+        let (cells, _) = cell_collector.collect_live_cells(&query, true)?;
+        let cell = cells.into_iter().next().ok_or_else(|| {
+            TxBuilderError::Other(anyhow!(
+                "can not find RC rule cell by type script: {:?}",
+                self.type_id_script
+            ))
+        })?;
+        let (smt_root, flags) = parse_rc_rule_data(cell.output_data.as_ref())?;
+        let existing_root = self.build_tree(false).root();
+        if existing_root != smt_root {
+            return Err(TxBuilderError::InvalidParameter(anyhow!(
+                "the existing keys do not match the smt root of the RC rule cell, expected: {:?}, actual: {:?}",
+                smt_root,
+                existing_root
+            )));
+        }
+        let new_data = build_rc_rule_data(&self.new_root(), flags);
+        build_update_tx(
+            &cell.out_point,
+            &cell.output,
+            &new_data,
+            None,
+            cell_dep_resolver,
+        )
+    }
+}
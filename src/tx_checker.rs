@@ -0,0 +1,255 @@
+//! Check a transaction for the mistakes a node rejects anyway, before it is
+//! sent. The scripts are not run.
+use std::collections::HashSet;
+
+use ckb_chain_spec::consensus::{Consensus, MAX_BLOCK_BYTES};
+use ckb_types::{
+    core::{Capacity, TransactionView},
+    packed::CellDep,
+    prelude::*,
+};
+use thiserror::Error;
+
+use crate::traits::TransactionDependencyProvider;
+use crate::tx_builder::gen_script_groups;
+use crate::types::{ScriptRegistry, Since};
+use crate::util::tx_size;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Severity {
+    /// The node rejects the transaction
+    Error,
+    /// The transaction can not be fully checked
+    Warning,
+}
+
+/// An issue found by `check_transaction`, the index is the index of the
+/// offending input, output, cell dep or witness.
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum TxCheckIssue {
+    #[error("the transaction has no inputs")]
+    EmptyInputs,
+
+    #[error("outputs count `{outputs}` does not match outputs data count `{outputs_data}`")]
+    OutputsDataMismatch { outputs: usize, outputs_data: usize },
+
+    #[error(
+        "output `{index}` capacity `{capacity}` is less than its occupied capacity `{occupied}`"
+    )]
+    OutputCapacityNotEnough {
+        index: usize,
+        capacity: u64,
+        occupied: u64,
+    },
+
+    #[error("input `{0}` is duplicated")]
+    DuplicateInput(usize),
+
+    #[error("cell dep `{0}` is duplicated")]
+    DuplicateCellDep(usize),
+
+    #[error("transaction size `{size}` exceeds the block bytes limit `{limit}`")]
+    TxSizeExceeded { size: u64, limit: u64 },
+
+    #[error("the since `{since:#x}` of input `{index}` can never be satisfied")]
+    UnsatisfiableSince { index: usize, since: u64 },
+
+    #[error("missing witness `{0}` of the first input of a lock script group")]
+    MissingGroupWitness(usize),
+
+    #[error("input `{index}` can not be resolved: `{reason}`")]
+    UnresolvedInput { index: usize, reason: String },
+
+    #[error("the cell dep of the known script `{0}` is not in the transaction")]
+    MissingCellDep(String),
+}
+
+impl TxCheckIssue {
+    pub fn severity(&self) -> Severity {
+        match self {
+            TxCheckIssue::UnresolvedInput { .. } | TxCheckIssue::MissingCellDep(_) => {
+                Severity::Warning
+            }
+            _ => Severity::Error,
+        }
+    }
+
+    /// The index of the offending input, output, cell dep or witness, `None`
+    /// if the issue is about the whole transaction.
+    pub fn index(&self) -> Option<usize> {
+        match self {
+            TxCheckIssue::EmptyInputs
+            | TxCheckIssue::OutputsDataMismatch { .. }
+            | TxCheckIssue::TxSizeExceeded { .. }
+            | TxCheckIssue::MissingCellDep(_) => None,
+            TxCheckIssue::OutputCapacityNotEnough { index, .. }
+            | TxCheckIssue::UnsatisfiableSince { index, .. }
+            | TxCheckIssue::UnresolvedInput { index, .. } => Some(*index),
+            TxCheckIssue::DuplicateInput(index)
+            | TxCheckIssue::DuplicateCellDep(index)
+            | TxCheckIssue::MissingGroupWitness(index) => Some(*index),
+        }
+    }
+}
+
+/// The error issues found by `check_transaction_strict`
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+#[error("transaction check failed with `{}` error issues, the first: `{}`", .0.len(), .0[0])]
+pub struct TxCheckError(pub Vec<TxCheckIssue>);
+
+/// Check `tx` before it is sent:
+///
+///   * the transaction has inputs, and no duplicated inputs or cell deps
+///   * every output has its data and holds its occupied capacity
+///   * the serialized size is within the block bytes limit of `consensus`
+///     (the default limit when it is `None`)
+///   * the since of every input is well formed, see `Since::from_raw`
+///   * the first input of every lock script group has a witness
+///
+/// The input cells are resolved by `tx_dep_provider` to group the lock
+/// scripts, an input that can not be resolved is a warning.
+pub fn check_transaction(
+    tx: &TransactionView,
+    tx_dep_provider: &dyn TransactionDependencyProvider,
+    consensus: Option<&Consensus>,
+) -> Vec<TxCheckIssue> {
+    let mut issues = Vec::new();
+    if tx.inputs().is_empty() {
+        issues.push(TxCheckIssue::EmptyInputs);
+    }
+
+    let outputs_count = tx.outputs().len();
+    let outputs_data_count = tx.outputs_data().len();
+    if outputs_count != outputs_data_count {
+        issues.push(TxCheckIssue::OutputsDataMismatch {
+            outputs: outputs_count,
+            outputs_data: outputs_data_count,
+        });
+    }
+    for (index, output) in tx.outputs().into_iter().enumerate() {
+        let data_len = tx
+            .outputs_data()
+            .get(index)
+            .map(|data| data.raw_data().len())
+            .unwrap_or(0);
+        let capacity: u64 = output.capacity().unpack();
+        let occupied = Capacity::bytes(data_len)
+            .and_then(|data_capacity| output.occupied_capacity(data_capacity))
+            .map(|capacity| capacity.as_u64())
+            .unwrap_or(u64::MAX);
+        if capacity < occupied {
+            issues.push(TxCheckIssue::OutputCapacityNotEnough {
+                index,
+                capacity,
+                occupied,
+            });
+        }
+    }
+
+    #[allow(clippy::mutable_key_type)]
+    let mut out_points = HashSet::new();
+    for (index, input) in tx.inputs().into_iter().enumerate() {
+        if !out_points.insert(input.previous_output()) {
+            issues.push(TxCheckIssue::DuplicateInput(index));
+        }
+        let since: u64 = input.since().unpack();
+        if Since::from_raw(since).is_err() {
+            issues.push(TxCheckIssue::UnsatisfiableSince { index, since });
+        }
+    }
+    #[allow(clippy::mutable_key_type)]
+    let mut cell_deps = HashSet::new();
+    for (index, cell_dep) in tx.cell_deps().into_iter().enumerate() {
+        if !cell_deps.insert(cell_dep) {
+            issues.push(TxCheckIssue::DuplicateCellDep(index));
+        }
+    }
+
+    let size = tx_size(tx);
+    let limit = consensus
+        .map(|consensus| consensus.max_block_bytes())
+        .unwrap_or(MAX_BLOCK_BYTES);
+    if size > limit {
+        issues.push(TxCheckIssue::TxSizeExceeded { size, limit });
+    }
+
+    let mut unresolved = false;
+    for (index, out_point) in tx.input_pts_iter().enumerate() {
+        if let Err(err) = tx_dep_provider.get_cell(&out_point) {
+            unresolved = true;
+            issues.push(TxCheckIssue::UnresolvedInput {
+                index,
+                reason: err.to_string(),
+            });
+        }
+    }
+    if !unresolved {
+        if let Ok(groups) = gen_script_groups(tx, tx_dep_provider) {
+            let mut first_indices: Vec<usize> = groups
+                .lock_groups
+                .values()
+                .map(|group| group.input_indices[0])
+                .collect();
+            first_indices.sort_unstable();
+            for index in first_indices {
+                if index >= tx.witnesses().len() {
+                    issues.push(TxCheckIssue::MissingGroupWitness(index));
+                }
+            }
+        }
+    }
+    issues
+}
+
+/// Check `tx` by `check_transaction`, and warn about every script known by
+/// `registry` (an input lock, an input or output type) whose cell dep in the
+/// registry is not in the transaction. The script may be loaded by another
+/// cell dep, so it is only a warning.
+pub fn check_transaction_with_registry(
+    tx: &TransactionView,
+    tx_dep_provider: &dyn TransactionDependencyProvider,
+    consensus: Option<&Consensus>,
+    registry: &ScriptRegistry,
+) -> Vec<TxCheckIssue> {
+    let mut issues = check_transaction(tx, tx_dep_provider, consensus);
+    let mut scripts = Vec::new();
+    for out_point in tx.input_pts_iter() {
+        if let Ok(output) = tx_dep_provider.get_cell(&out_point) {
+            scripts.push(output.lock());
+            scripts.extend(output.type_().to_opt());
+        }
+    }
+    scripts.extend(
+        tx.outputs()
+            .into_iter()
+            .filter_map(|output| output.type_().to_opt()),
+    );
+    let cell_deps: Vec<CellDep> = tx.cell_deps().into_iter().collect();
+    let mut missing: Vec<String> = Vec::new();
+    for script in scripts {
+        if let Some(known) = registry.lookup_by_script(&script) {
+            if !cell_deps.contains(&known.cell_dep) && !missing.contains(&known.name) {
+                missing.push(known.name);
+            }
+        }
+    }
+    issues.extend(missing.into_iter().map(TxCheckIssue::MissingCellDep));
+    issues
+}
+
+/// Check `tx` by `check_transaction`, fail if there is any error issue, the
+/// warning issues are returned otherwise.
+pub fn check_transaction_strict(
+    tx: &TransactionView,
+    tx_dep_provider: &dyn TransactionDependencyProvider,
+    consensus: Option<&Consensus>,
+) -> Result<Vec<TxCheckIssue>, TxCheckError> {
+    let (errors, warnings): (Vec<_>, Vec<_>) = check_transaction(tx, tx_dep_provider, consensus)
+        .into_iter()
+        .partition(|issue| issue.severity() == Severity::Error);
+    if errors.is_empty() {
+        Ok(warnings)
+    } else {
+        Err(TxCheckError(errors))
+    }
+}
@@ -1,4 +1,3 @@
-use std::convert::{TryFrom, TryInto};
 use std::fmt;
 use std::str::FromStr;
 
@@ -13,10 +12,11 @@ use ckb_types::{
 };
 use serde_derive::{Deserialize, Serialize};
 
-use super::NetworkType;
+use super::{ChainParams, NetworkType, ScriptHashTypeExt, ScriptId};
 use crate::constants::{
     ACP_TYPE_HASH_AGGRON, ACP_TYPE_HASH_LINA, MULTISIG_TYPE_HASH, SIGHASH_TYPE_HASH,
 };
+use crate::unlock::{AcpConfig, OmniLockConfig};
 pub use old_addr::{Address as OldAddress, AddressFormat as OldAddressFormat};
 
 #[derive(Hash, Eq, PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
@@ -170,13 +170,35 @@ impl AddressPayload {
         AddressPayload::from_pubkey_hash(hash)
     }
 
+    /// Same as `from_pubkey`, but hash the pubkey with the chain's blake2b
+    /// personalization.
+    pub fn from_pubkey_with_params(
+        pubkey: &secp256k1::PublicKey,
+        chain_params: &ChainParams,
+    ) -> AddressPayload {
+        AddressPayload::from_pubkey_hash(chain_params.blake160(&pubkey.serialize()[..]))
+    }
+
     pub fn from_pubkey_hash(hash: H160) -> AddressPayload {
         let index = CodeHashIndex::Sighash;
         AddressPayload::Short { index, hash }
     }
 
     pub fn display_with_network(&self, network: NetworkType, is_new: bool) -> String {
-        let hrp = network.to_prefix();
+        self.display_with_hrp(network.to_prefix(), network, is_new)
+    }
+
+    /// Same as `display_with_network`, but use the chain's address prefix.
+    pub fn display_with_params(
+        &self,
+        network: NetworkType,
+        is_new: bool,
+        chain_params: &ChainParams,
+    ) -> String {
+        self.display_with_hrp(chain_params.hrp(network), network, is_new)
+    }
+
+    fn display_with_hrp(&self, hrp: &str, network: NetworkType, is_new: bool) -> String {
         let (data, variant) = if is_new {
             // payload = 0x00 | code_hash | hash_type | args
             let code_hash = self.code_hash(Some(network));
@@ -185,7 +207,7 @@ impl AddressPayload {
             let mut data = vec![0u8; 34 + args.len()];
             data[0] = 0x00;
             data[1..33].copy_from_slice(code_hash.as_slice());
-            data[33] = hash_type as u8;
+            data[33] = hash_type.to_byte();
             data[34..].copy_from_slice(args.as_ref());
             (data, bech32::Variant::Bech32m)
         } else {
@@ -235,7 +257,7 @@ impl fmt::Debug for AddressPayload {
 impl From<&AddressPayload> for Script {
     fn from(payload: &AddressPayload) -> Script {
         Script::new_builder()
-            .hash_type(payload.hash_type().into())
+            .hash_type(payload.hash_type().to_packed())
             .code_hash(payload.code_hash(None))
             .args(payload.args().pack())
             .build()
@@ -245,7 +267,7 @@ impl From<&AddressPayload> for Script {
 impl From<Script> for AddressPayload {
     #[allow(clippy::fallible_impl_from)]
     fn from(lock: Script) -> AddressPayload {
-        let hash_type: ScriptHashType = lock.hash_type().try_into().expect("Invalid hash_type");
+        let hash_type = ScriptHashType::from_packed(&lock.hash_type()).expect("Invalid hash_type");
         let code_hash = lock.code_hash();
         let code_hash_h256: H256 = code_hash.unpack();
         let args = lock.args().raw_data();
@@ -291,9 +313,10 @@ pub struct Address {
 }
 
 impl Address {
-    pub fn new(network: NetworkType, payload: AddressPayload, is_new: bool) -> Address {
+    /// The network is a `NetworkType` or a `&NetworkInfo`
+    pub fn new<N: Into<NetworkType>>(network: N, payload: AddressPayload, is_new: bool) -> Address {
         Address {
-            network,
+            network: network.into(),
             payload,
             is_new,
         }
@@ -310,6 +333,64 @@ impl Address {
     pub fn is_new(&self) -> bool {
         self.is_new
     }
+
+    /// Encode the address with the chain's address prefix, same as
+    /// `to_string()` with the default value.
+    pub fn display_with_params(&self, chain_params: &ChainParams) -> String {
+        self.payload
+            .display_with_params(self.network, self.is_new, chain_params)
+    }
+
+    /// Decode an address with the chain's address prefix, same as
+    /// `Address::from_str` with the default value.
+    pub fn from_str_with_params(input: &str, chain_params: &ChainParams) -> Result<Self, String> {
+        let (hrp, data, variant) = bech32::decode(input).map_err(|err| err.to_string())?;
+        let network = chain_params
+            .network_from_hrp(&hrp)
+            .ok_or_else(|| format!("Invalid hrp: {}", hrp))?;
+        Address::from_bech32_data(network, data, variant)
+    }
+
+    /// The full format address of the omni-lock `config`, the reverse of
+    /// `OmniLockConfig::from_address`.
+    pub fn from_omnilock_config<N: Into<NetworkType>>(
+        config: &OmniLockConfig,
+        omni_script_id: &ScriptId,
+        network: N,
+    ) -> Address {
+        Self::new_full_with_script_id(omni_script_id, config.build_args(), network)
+    }
+
+    /// The omni-lock address of an ethereum address (the keccak160 of the
+    /// public key).
+    pub fn from_ethereum_address<N: Into<NetworkType>>(
+        eth_address: H160,
+        omni_script_id: &ScriptId,
+        network: N,
+    ) -> Address {
+        let config = OmniLockConfig::new_ethereum(eth_address);
+        Self::from_omnilock_config(&config, omni_script_id, network)
+    }
+
+    /// The full format address of the anyone-can-pay `config`, the reverse of
+    /// `AcpConfig::from_address`.
+    pub fn from_acp_config<N: Into<NetworkType>>(
+        config: &AcpConfig,
+        acp_script_id: &ScriptId,
+        network: N,
+    ) -> Address {
+        Self::new_full_with_script_id(acp_script_id, config.build_args(), network)
+    }
+
+    fn new_full_with_script_id<N: Into<NetworkType>>(
+        script_id: &ScriptId,
+        args: Bytes,
+        network: N,
+    ) -> Address {
+        let payload =
+            AddressPayload::new_full(script_id.hash_type, script_id.code_hash.pack(), args);
+        Address::new(network, payload, true)
+    }
 }
 
 impl fmt::Debug for Address {
@@ -333,7 +414,7 @@ impl fmt::Debug for Address {
 impl From<&Address> for Script {
     fn from(addr: &Address) -> Script {
         Script::new_builder()
-            .hash_type(addr.payload.hash_type().into())
+            .hash_type(addr.payload.hash_type().to_packed())
             .code_hash(addr.payload.code_hash(Some(addr.network)))
             .args(addr.payload.args().pack())
             .build()
@@ -357,6 +438,16 @@ impl FromStr for Address {
         let (hrp, data, variant) = bech32::decode(input).map_err(|err| err.to_string())?;
         let network =
             NetworkType::from_prefix(&hrp).ok_or_else(|| format!("Invalid hrp: {}", hrp))?;
+        Address::from_bech32_data(network, data, variant)
+    }
+}
+
+impl Address {
+    fn from_bech32_data(
+        network: NetworkType,
+        data: Vec<bech32::u5>,
+        variant: Variant,
+    ) -> Result<Self, String> {
         let data = convert_bits(&data, 5, 8, false).unwrap();
         let ty = AddressType::from_u8(data[0])?;
         match ty {
@@ -415,7 +506,7 @@ impl FromStr for Address {
                 }
                 let code_hash = Byte32::from_slice(&data[1..33]).unwrap();
                 let hash_type =
-                    ScriptHashType::try_from(data[33]).map_err(|err| err.to_string())?;
+                    ScriptHashType::from_byte(data[33]).map_err(|err| err.to_string())?;
                 let args = Bytes::from(data[34..].to_vec());
                 let payload = AddressPayload::Full {
                     hash_type,
@@ -435,7 +526,7 @@ impl FromStr for Address {
 mod old_addr {
     use super::{
         bech32, blake2b_256, convert_bits, Deserialize, NetworkType, Script, ScriptHashType,
-        Serialize, ToBase32, H160, H256,
+        ScriptHashTypeExt, Serialize, ToBase32, H160, H256,
     };
     use ckb_crypto::secp::Pubkey;
     use ckb_types::prelude::*;
@@ -495,7 +586,7 @@ mod old_addr {
             Script::new_builder()
                 .args(self.hash.as_bytes().pack())
                 .code_hash(code_hash.pack())
-                .hash_type(ScriptHashType::Data.into())
+                .hash_type(ScriptHashType::Data.to_packed())
                 .build()
         }
 
@@ -755,7 +846,7 @@ mod test {
             let mut data = vec![0u8; 34 + args.len()];
             data[0] = 0x00;
             data[1..33].copy_from_slice(code_hash.as_bytes());
-            data[33] = hash_type as u8;
+            data[33] = hash_type.to_byte();
             data[34..].copy_from_slice(args.as_ref());
             let variant = bech32::Variant::Bech32;
             let addr = bech32::encode("ckb", data.to_base32(), variant).unwrap();
@@ -779,4 +870,162 @@ mod test {
         assert_eq!(format!("{:?}", payload), "AddressPayload { hash_type: \"data1\", code_hash: Byte32(0x9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8), args: b\"abcd\" }");
         assert_eq!(format!("{:?}", address), "Address { network: Mainnet, hash_type: \"data1\", code_hash: Byte32(0x9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8), args: b\"abcd\", is_new: true }");
     }
+
+    fn omni_script_id() -> ScriptId {
+        ScriptId::new_type(h256!(
+            "0xf329effd1c475a2978453c8600e1eaf0bc2087ee093c3ee64cc96ec6847752cb"
+        ))
+    }
+
+    #[test]
+    fn test_omnilock_address_roundtrip() {
+        use crate::unlock::omni_lock::{AdminConfig, Identity};
+        use crate::unlock::{IdentityFlag, OmniLockAcpConfig};
+        use crate::Since;
+
+        let script_id = omni_script_id();
+        let flags = [
+            IdentityFlag::PubkeyHash,
+            IdentityFlag::Ethereum,
+            IdentityFlag::Eos,
+            IdentityFlag::Tron,
+            IdentityFlag::Bitcoin,
+            IdentityFlag::Dogecoin,
+            IdentityFlag::Multisig,
+            IdentityFlag::OwnerLock,
+            IdentityFlag::Exec,
+            IdentityFlag::Dl,
+        ];
+        for flag in flags {
+            // every combination of the admin, acp, time lock and supply args
+            for sections in 0u8..16 {
+                let mut config =
+                    OmniLockConfig::new(flag, h160!("0x0102030405060708090a0b0c0d0e0f1011121314"));
+                if sections & 1 != 0 {
+                    config.set_admin_config(AdminConfig::new(
+                        h256!("0xaa"),
+                        Default::default(),
+                        Identity::default(),
+                        None,
+                        false,
+                    ));
+                }
+                if sections & 2 != 0 {
+                    config.set_acp_config(OmniLockAcpConfig::new(3, 5));
+                }
+                if sections & 4 != 0 {
+                    config.set_time_lock_config(Since::absolute_block(1000));
+                }
+                if sections & 8 != 0 {
+                    config.set_info_cell(h256!("0xbb"));
+                }
+                let address =
+                    Address::from_omnilock_config(&config, &script_id, NetworkType::Testnet);
+                let address = Address::from_str(&address.to_string()).unwrap();
+                assert_eq!(
+                    OmniLockConfig::from_address(&address, &script_id).unwrap(),
+                    config,
+                    "flag: {:?}, sections: {}",
+                    flag,
+                    sections
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_ethereum_address() {
+        let script_id = omni_script_id();
+        let eth_address = h160!("0x0102030405060708090a0b0c0d0e0f1011121314");
+        let address =
+            Address::from_ethereum_address(eth_address.clone(), &script_id, NetworkType::Mainnet);
+        assert_eq!(address.network(), NetworkType::Mainnet);
+        let config = OmniLockConfig::from_address(&address, &script_id).unwrap();
+        assert!(config.is_ethereum());
+        assert_eq!(config.id().auth_content(), &eth_address);
+        assert_eq!(config, OmniLockConfig::new_ethereum(eth_address));
+    }
+
+    #[test]
+    fn test_omnilock_address_errors() {
+        use crate::unlock::omni_lock::ConfigError;
+
+        let script_id = omni_script_id();
+        let config = OmniLockConfig::new_pubkey_hash(H160::default());
+        let address = Address::from_omnilock_config(&config, &script_id, NetworkType::Testnet);
+        let other_id = ScriptId::new_data1(script_id.code_hash.clone());
+        assert!(matches!(
+            OmniLockConfig::from_address(&address, &other_id),
+            Err(ConfigError::ScriptIdMismatch(expected, _)) if expected == other_id
+        ));
+
+        let args = config.build_args();
+        for invalid in [
+            args.slice(0..21),
+            // the acp args is missing
+            Bytes::from([&args[0..21], &[0x02u8][..]].concat()),
+            // unknown flags
+            Bytes::from([&args[0..21], &[0x10u8][..]].concat()),
+            // unknown identity flag
+            Bytes::from([&[0x07u8][..], &args[1..]].concat()),
+            Bytes::from([&args[..], &[0u8][..]].concat()),
+        ] {
+            assert!(
+                matches!(
+                    OmniLockConfig::from_args(&invalid),
+                    Err(ConfigError::InvalidArgs(_))
+                ),
+                "{:?}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_acp_address_roundtrip() {
+        use crate::unlock::omni_lock::ConfigError;
+
+        let script_id = ScriptId::new_type(ACP_TYPE_HASH_AGGRON);
+        let lock_arg = h160!("0x0102030405060708090a0b0c0d0e0f1011121314");
+        let configs = [
+            AcpConfig::new(lock_arg.clone()),
+            AcpConfig::new_with_minimum(lock_arg.clone(), 2, None),
+            AcpConfig::new_with_minimum(lock_arg.clone(), 2, Some(6)),
+        ];
+        for (config, args_len) in configs.iter().zip([20, 21, 22]) {
+            assert_eq!(config.build_args().len(), args_len);
+            let address = Address::from_acp_config(config, &script_id, NetworkType::Testnet);
+            assert!(!address.payload().is_short());
+            let address = Address::from_str(&address.to_string()).unwrap();
+            assert_eq!(
+                &AcpConfig::from_address(&address, &script_id).unwrap(),
+                config
+            );
+        }
+        // the ckb minimum is written as 0 before the udt minimum
+        let config = AcpConfig {
+            lock_arg: lock_arg.clone(),
+            ckb_minimum: None,
+            udt_minimum: Some(6),
+        };
+        assert_eq!(
+            AcpConfig::from_args(&config.build_args()).unwrap(),
+            AcpConfig::new_with_minimum(lock_arg.clone(), 0, Some(6))
+        );
+
+        assert!(matches!(
+            AcpConfig::from_args(&[0u8; 23]),
+            Err(ConfigError::InvalidArgs(_))
+        ));
+        assert!(matches!(
+            AcpConfig::from_args(&[0u8; 19]),
+            Err(ConfigError::InvalidArgs(_))
+        ));
+        let address =
+            Address::from_acp_config(&configs[0], &omni_script_id(), NetworkType::Testnet);
+        assert!(matches!(
+            AcpConfig::from_address(&address, &script_id),
+            Err(ConfigError::ScriptIdMismatch(_, _))
+        ));
+    }
 }
@@ -0,0 +1,323 @@
+use std::{collections::HashMap, fs, io, path::Path, str::FromStr};
+
+use ckb_types::{
+    core::{DepType, ScriptHashType},
+    packed::{CellDep, OutPoint},
+    prelude::*,
+    H256,
+};
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{NetworkType, ScriptHashTypeExt, ScriptId};
+
+#[derive(Error, Debug)]
+pub enum CellDepConfigError {
+    #[error("read config file error: `{0}`")]
+    Io(#[from] io::Error),
+
+    #[error("invalid config format: `{0}`")]
+    InvalidFormat(String),
+
+    #[error("invalid hash of `{0}`: `{1}`")]
+    InvalidHash(String, String),
+
+    #[error("unknown hash type of `{0}`: `{1}`")]
+    UnknownHashType(String, String),
+
+    #[error("unknown dep type of `{0}`: `{1}`")]
+    UnknownDepType(String, String),
+
+    #[error("unknown network of `{0}`: `{1}`")]
+    UnknownNetwork(String, String),
+
+    #[error("duplicate script id of `{0}`, already used by `{1}`")]
+    DuplicateScriptId(String, String),
+}
+
+/// A script and its cell dep in a `CellDepConfig`
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct CellDepConfigItem {
+    pub name: String,
+    /// Hex string, the `0x` prefix is optional
+    pub code_hash: String,
+    /// One of `data`, `type`, `data1` and `data2`
+    pub hash_type: String,
+    /// Hex string, the `0x` prefix is optional
+    pub tx_hash: String,
+    pub index: u32,
+    /// `code` or `dep_group`
+    pub dep_type: String,
+    /// The network the item is deployed on, e.g. `ckb_testnet`, an item
+    /// without it is used on every network.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+}
+
+impl CellDepConfigItem {
+    pub fn new(name: String, script_id: &ScriptId, cell_dep: &CellDep) -> CellDepConfigItem {
+        let tx_hash: H256 = cell_dep.out_point().tx_hash().unpack();
+        let index: u32 = cell_dep.out_point().index().unpack();
+        let dep_type = if cell_dep.dep_type() == DepType::DepGroup.into() {
+            "dep_group"
+        } else {
+            "code"
+        };
+        CellDepConfigItem {
+            name,
+            code_hash: format!("{:#x}", script_id.code_hash),
+            hash_type: script_id.hash_type.to_name().to_string(),
+            tx_hash: format!("{:#x}", tx_hash),
+            index,
+            dep_type: dep_type.to_string(),
+            network: None,
+        }
+    }
+
+    /// Parse the script id and the cell dep of the item.
+    pub fn parse(&self) -> Result<(ScriptId, CellDep), CellDepConfigError> {
+        let code_hash = parse_hash(&self.name, &self.code_hash)?;
+        let hash_type = ScriptHashType::from_name(&self.hash_type).ok_or_else(|| {
+            CellDepConfigError::UnknownHashType(self.name.clone(), self.hash_type.clone())
+        })?;
+        let tx_hash = parse_hash(&self.name, &self.tx_hash)?;
+        let dep_type = match self.dep_type.as_str() {
+            "code" => DepType::Code,
+            "dep_group" => DepType::DepGroup,
+            _ => {
+                return Err(CellDepConfigError::UnknownDepType(
+                    self.name.clone(),
+                    self.dep_type.clone(),
+                ))
+            }
+        };
+        let cell_dep = CellDep::new_builder()
+            .out_point(OutPoint::new(tx_hash.pack(), self.index))
+            .dep_type(dep_type.into())
+            .build();
+        Ok((ScriptId::new(code_hash, hash_type), cell_dep))
+    }
+
+    /// The network of the item, `None` when it is not tagged.
+    pub fn network(&self) -> Result<Option<NetworkType>, CellDepConfigError> {
+        self.network
+            .as_ref()
+            .map(|value| {
+                NetworkType::from_raw_str(value).ok_or_else(|| {
+                    CellDepConfigError::UnknownNetwork(self.name.clone(), value.clone())
+                })
+            })
+            .transpose()
+    }
+}
+
+/// A declarative list of cell deps, in TOML:
+///
+/// ```toml
+/// [[cell_deps]]
+/// name = "sighash"
+/// code_hash = "0x9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8"
+/// hash_type = "type"
+/// tx_hash = "0xf8de3bb47d055cdf460d93a2a6e1b05f7432f9777c8c474abf4eec1d4aee5d37"
+/// index = 0
+/// dep_type = "dep_group"
+/// network = "ckb_testnet"
+/// ```
+///
+/// or the same fields in JSON: `{"cell_deps": [{"name": "sighash", ...}]}`.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct CellDepConfig {
+    #[serde(default)]
+    pub cell_deps: Vec<CellDepConfigItem>,
+}
+
+impl CellDepConfig {
+    /// Parse a TOML or JSON config, a content starts with `{` is JSON.
+    pub fn from_config_str(content: &str) -> Result<CellDepConfig, CellDepConfigError> {
+        if content.trim_start().starts_with('{') {
+            serde_json::from_str(content)
+                .map_err(|err| CellDepConfigError::InvalidFormat(err.to_string()))
+        } else {
+            toml::from_str(content)
+                .map_err(|err| CellDepConfigError::InvalidFormat(err.to_string()))
+        }
+    }
+
+    pub fn from_config_file<P: AsRef<Path>>(path: P) -> Result<CellDepConfig, CellDepConfigError> {
+        let content = fs::read_to_string(path)?;
+        Self::from_config_str(&content)
+    }
+
+    pub fn to_toml_string(&self) -> Result<String, CellDepConfigError> {
+        toml::to_string(self).map_err(|err| CellDepConfigError::InvalidFormat(err.to_string()))
+    }
+
+    pub fn to_json_string(&self) -> Result<String, CellDepConfigError> {
+        serde_json::to_string_pretty(self)
+            .map_err(|err| CellDepConfigError::InvalidFormat(err.to_string()))
+    }
+
+    /// The items used on `network` with their script ids and cell deps, all
+    /// the items when `network` is `None`. An item is checked even when it is
+    /// not used, a script id can only be used once by the selected items.
+    pub fn resolve_items(
+        &self,
+        network: Option<NetworkType>,
+    ) -> Result<Vec<(String, ScriptId, CellDep)>, CellDepConfigError> {
+        let mut names: HashMap<ScriptId, &str> = HashMap::new();
+        let mut items = Vec::new();
+        for item in &self.cell_deps {
+            let (script_id, cell_dep) = item.parse()?;
+            let item_network = item.network()?;
+            if network.is_some() && item_network.is_some() && item_network != network {
+                continue;
+            }
+            if let Some(name) = names.insert(script_id.clone(), &item.name) {
+                return Err(CellDepConfigError::DuplicateScriptId(
+                    item.name.clone(),
+                    name.to_string(),
+                ));
+            }
+            items.push((item.name.clone(), script_id, cell_dep));
+        }
+        Ok(items)
+    }
+}
+
+fn parse_hash(name: &str, value: &str) -> Result<H256, CellDepConfigError> {
+    let hex = value.strip_prefix("0x").unwrap_or(value);
+    H256::from_str(hex).map_err(|err| {
+        CellDepConfigError::InvalidHash(name.to_string(), format!("{}: {}", value, err))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_types::h256;
+
+    const CONFIG_TOML: &str = r#"
+[[cell_deps]]
+name = "my_lock"
+code_hash = "0x0000000000000000000000000000000000000000000000000000000000001234"
+hash_type = "data1"
+tx_hash = "0000000000000000000000000000000000000000000000000000000000005678"
+index = 1
+dep_type = "code"
+
+[[cell_deps]]
+name = "my_type"
+code_hash = "0x0000000000000000000000000000000000000000000000000000000000004321"
+hash_type = "type"
+tx_hash = "0x0000000000000000000000000000000000000000000000000000000000008765"
+index = 0
+dep_type = "dep_group"
+network = "ckb_testnet"
+
+[[cell_deps]]
+name = "my_type_dev"
+code_hash = "0x0000000000000000000000000000000000000000000000000000000000004321"
+hash_type = "type"
+tx_hash = "0x0000000000000000000000000000000000000000000000000000000000009999"
+index = 2
+dep_type = "code"
+network = "ckb_dev"
+"#;
+
+    fn item() -> CellDepConfigItem {
+        let config = CellDepConfig::from_config_str(CONFIG_TOML).unwrap();
+        config.cell_deps[0].clone()
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config = CellDepConfig::from_config_str(CONFIG_TOML).unwrap();
+        assert_eq!(config.cell_deps.len(), 3);
+        let json = config.to_json_string().unwrap();
+        assert_eq!(CellDepConfig::from_config_str(&json).unwrap(), config);
+        let toml = config.to_toml_string().unwrap();
+        assert_eq!(CellDepConfig::from_config_str(&toml).unwrap(), config);
+
+        let (script_id, cell_dep) = config.cell_deps[0].parse().unwrap();
+        assert_eq!(script_id, ScriptId::new_data1(h256!("0x1234")));
+        assert_eq!(
+            cell_dep,
+            CellDep::new_builder()
+                .out_point(OutPoint::new(h256!("0x5678").pack(), 1))
+                .dep_type(DepType::Code.into())
+                .build()
+        );
+        assert_eq!(
+            CellDepConfigItem::new("my_lock".to_string(), &script_id, &cell_dep)
+                .parse()
+                .unwrap(),
+            (script_id, cell_dep)
+        );
+        assert_eq!(
+            config.cell_deps[1].network().unwrap(),
+            Some(NetworkType::Testnet)
+        );
+    }
+
+    #[test]
+    fn test_resolve_items_by_network() {
+        let config = CellDepConfig::from_config_str(CONFIG_TOML).unwrap();
+        let names = |network| {
+            config
+                .resolve_items(Some(network))
+                .unwrap()
+                .into_iter()
+                .map(|(name, _, _)| name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(NetworkType::Testnet), vec!["my_lock", "my_type"]);
+        assert_eq!(names(NetworkType::Dev), vec!["my_lock", "my_type_dev"]);
+        assert_eq!(names(NetworkType::Mainnet), vec!["my_lock"]);
+        // the testnet and the dev items use the same script id
+        match config.resolve_items(None) {
+            Err(CellDepConfigError::DuplicateScriptId(name, other)) => {
+                assert_eq!(name, "my_type_dev");
+                assert_eq!(other, "my_type");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_invalid_item() {
+        let mut invalid = item();
+        invalid.code_hash = "0x1234".to_string();
+        assert!(matches!(
+            invalid.parse(),
+            Err(CellDepConfigError::InvalidHash(name, _)) if name == "my_lock"
+        ));
+        let mut invalid = item();
+        invalid.tx_hash = "xyz".to_string();
+        assert!(matches!(
+            invalid.parse(),
+            Err(CellDepConfigError::InvalidHash(name, _)) if name == "my_lock"
+        ));
+        let mut invalid = item();
+        invalid.hash_type = "data3".to_string();
+        assert!(matches!(
+            invalid.parse(),
+            Err(CellDepConfigError::UnknownHashType(name, value)) if name == "my_lock" && value == "data3"
+        ));
+        let mut invalid = item();
+        invalid.dep_type = "group".to_string();
+        assert!(matches!(
+            invalid.parse(),
+            Err(CellDepConfigError::UnknownDepType(name, value)) if name == "my_lock" && value == "group"
+        ));
+        let mut invalid = item();
+        invalid.network = Some("testnet".to_string());
+        assert!(matches!(
+            invalid.network(),
+            Err(CellDepConfigError::UnknownNetwork(name, value)) if name == "my_lock" && value == "testnet"
+        ));
+        assert!(matches!(
+            CellDepConfig::from_config_str("[[cell_deps]]\nname = 1"),
+            Err(CellDepConfigError::InvalidFormat(_))
+        ));
+    }
+}
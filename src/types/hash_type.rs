@@ -0,0 +1,152 @@
+use ckb_jsonrpc_types as json_types;
+use ckb_types::{core::ScriptHashType, packed, prelude::*};
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, Copy, Eq, PartialEq)]
+#[error("invalid script hash type: `{0}`")]
+pub(crate) struct InvalidScriptHashType(pub(crate) u8);
+
+/// Explicit conversions between the hash type representations.
+///
+/// The `From`/`Into` impls provided by ckb-types go through a plain `u8`, the
+/// methods here match every variant exhaustively so a new hash type or a
+/// renumbered one is a compile error instead of a silently wrong script.
+pub(crate) trait ScriptHashTypeExt: Sized {
+    fn to_byte(self) -> u8;
+    fn to_packed(self) -> packed::Byte;
+    fn to_json(self) -> json_types::ScriptHashType;
+    fn from_byte(value: u8) -> Result<Self, InvalidScriptHashType>;
+    fn from_packed(value: &packed::Byte) -> Result<Self, InvalidScriptHashType>;
+    fn from_json(value: json_types::ScriptHashType) -> Self;
+    /// The name used in configs, URIs and `ScriptId` strings: `data`, `type`,
+    /// `data1` or `data2`.
+    fn to_name(self) -> &'static str;
+    fn from_name(value: &str) -> Option<Self>;
+}
+
+impl ScriptHashTypeExt for ScriptHashType {
+    fn to_byte(self) -> u8 {
+        match self {
+            ScriptHashType::Data => 0,
+            ScriptHashType::Type => 1,
+            ScriptHashType::Data1 => 2,
+            ScriptHashType::Data2 => 4,
+        }
+    }
+
+    fn to_packed(self) -> packed::Byte {
+        packed::Byte::new(self.to_byte())
+    }
+
+    fn to_json(self) -> json_types::ScriptHashType {
+        match self {
+            ScriptHashType::Data => json_types::ScriptHashType::Data,
+            ScriptHashType::Type => json_types::ScriptHashType::Type,
+            ScriptHashType::Data1 => json_types::ScriptHashType::Data1,
+            ScriptHashType::Data2 => json_types::ScriptHashType::Data2,
+        }
+    }
+
+    fn from_byte(value: u8) -> Result<Self, InvalidScriptHashType> {
+        match value {
+            0 => Ok(ScriptHashType::Data),
+            1 => Ok(ScriptHashType::Type),
+            2 => Ok(ScriptHashType::Data1),
+            4 => Ok(ScriptHashType::Data2),
+            _ => Err(InvalidScriptHashType(value)),
+        }
+    }
+
+    fn from_packed(value: &packed::Byte) -> Result<Self, InvalidScriptHashType> {
+        Self::from_byte(value.as_slice()[0])
+    }
+
+    fn from_json(value: json_types::ScriptHashType) -> Self {
+        match value {
+            json_types::ScriptHashType::Data => ScriptHashType::Data,
+            json_types::ScriptHashType::Type => ScriptHashType::Type,
+            json_types::ScriptHashType::Data1 => ScriptHashType::Data1,
+            json_types::ScriptHashType::Data2 => ScriptHashType::Data2,
+        }
+    }
+
+    fn to_name(self) -> &'static str {
+        match self {
+            ScriptHashType::Data => "data",
+            ScriptHashType::Type => "type",
+            ScriptHashType::Data1 => "data1",
+            ScriptHashType::Data2 => "data2",
+        }
+    }
+
+    fn from_name(value: &str) -> Option<Self> {
+        match value {
+            "data" => Some(ScriptHashType::Data),
+            "type" => Some(ScriptHashType::Type),
+            "data1" => Some(ScriptHashType::Data1),
+            "data2" => Some(ScriptHashType::Data2),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL_HASH_TYPES: [ScriptHashType; 4] = [
+        ScriptHashType::Data,
+        ScriptHashType::Type,
+        ScriptHashType::Data1,
+        ScriptHashType::Data2,
+    ];
+
+    #[test]
+    fn test_round_trip() {
+        for hash_type in ALL_HASH_TYPES {
+            assert_eq!(
+                ScriptHashType::from_byte(hash_type.to_byte()),
+                Ok(hash_type)
+            );
+            assert_eq!(
+                ScriptHashType::from_packed(&hash_type.to_packed()),
+                Ok(hash_type)
+            );
+            assert_eq!(ScriptHashType::from_json(hash_type.to_json()), hash_type);
+            assert_eq!(
+                ScriptHashType::from_name(hash_type.to_name()),
+                Some(hash_type)
+            );
+            // the same names as the json types
+            assert_eq!(
+                serde_json::to_string(&hash_type.to_json()).unwrap(),
+                format!("\"{}\"", hash_type.to_name())
+            );
+            // agree with the conversions provided by ckb-types
+            assert_eq!(hash_type.to_packed(), packed::Byte::from(hash_type));
+            assert_eq!(
+                json_types::ScriptHashType::from(hash_type),
+                hash_type.to_json()
+            );
+        }
+    }
+
+    #[test]
+    fn test_reject_unknown_byte() {
+        // 4 is data2, the gap before it and everything after it are invalid
+        for value in [3u8, 5, 6, 0x80, 0xff] {
+            assert_eq!(
+                ScriptHashType::from_byte(value),
+                Err(InvalidScriptHashType(value))
+            );
+            assert_eq!(
+                ScriptHashType::from_packed(&packed::Byte::new(value)),
+                Err(InvalidScriptHashType(value))
+            );
+        }
+        assert_eq!(ScriptHashType::from_byte(4), Ok(ScriptHashType::Data2));
+        for name in ["", "Type", "data3", " type"] {
+            assert_eq!(ScriptHashType::from_name(name), None);
+        }
+    }
+}
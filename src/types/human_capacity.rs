@@ -2,11 +2,75 @@ use std::fmt;
 use std::ops::Deref;
 use std::str::FromStr;
 
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
 use crate::constants::ONE_CKB;
 
+/// The max decimal places of a capacity in CKB, 1 shannon is 0.00000001 CKB.
+const DECIMAL_PLACES: usize = 8;
+
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum HumanCapacityError {
+    #[error("empty capacity")]
+    Empty,
+
+    #[error("negative capacity: `{0}`")]
+    Negative(String),
+
+    #[error("invalid capacity: `{0}`")]
+    InvalidFormat(String),
+
+    #[error("too many decimal places, at most 8, got: `{0}`")]
+    TooManyDecimals(usize),
+
+    #[error("capacity overflow: `{0}`")]
+    Overflow(String),
+}
+
+/// A capacity in shannons, displayed and parsed in CKB, e.g. `"123.45"`.
+///
+/// Underscores can be used as digit separators between two digits, e.g.
+/// `"1_000.5"`. It is serialized as a string in CKB.
 #[derive(Default, Clone, Copy, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
 pub struct HumanCapacity(pub u64);
 
+impl HumanCapacity {
+    pub fn from_shannons(shannons: u64) -> HumanCapacity {
+        HumanCapacity(shannons)
+    }
+
+    pub fn as_shannons(self) -> u64 {
+        self.0
+    }
+
+    pub fn checked_add(self, rhs: HumanCapacity) -> Result<HumanCapacity, HumanCapacityError> {
+        self.0
+            .checked_add(rhs.0)
+            .map(HumanCapacity)
+            .ok_or_else(|| HumanCapacityError::Overflow(format!("{} + {}", self, rhs)))
+    }
+
+    pub fn checked_sub(self, rhs: HumanCapacity) -> Result<HumanCapacity, HumanCapacityError> {
+        self.0
+            .checked_sub(rhs.0)
+            .map(HumanCapacity)
+            .ok_or_else(|| HumanCapacityError::Overflow(format!("{} - {}", self, rhs)))
+    }
+
+    pub fn checked_mul(self, rhs: u64) -> Result<HumanCapacity, HumanCapacityError> {
+        self.0
+            .checked_mul(rhs)
+            .map(HumanCapacity)
+            .ok_or_else(|| HumanCapacityError::Overflow(format!("{} * {}", self, rhs)))
+    }
+
+    /// Format with all the 8 decimal places, e.g. `"1.50000000"`.
+    pub fn to_exact_string(&self) -> String {
+        format!("{:.8}", self)
+    }
+}
+
 impl From<u64> for HumanCapacity {
     fn from(value: u64) -> HumanCapacity {
         HumanCapacity(value)
@@ -26,31 +90,64 @@ impl Deref for HumanCapacity {
     }
 }
 
+/// Remove the underscores between digits, `None` if the part is empty, has
+/// other characters or an underscore is not between two digits.
+fn strip_separators(part: &str) -> Option<String> {
+    let bytes = part.as_bytes();
+    let mut digits = String::with_capacity(part.len());
+    for (i, b) in bytes.iter().enumerate() {
+        match b {
+            b'0'..=b'9' => digits.push(char::from(*b)),
+            b'_' if i > 0
+                && bytes[i - 1].is_ascii_digit()
+                && bytes.get(i + 1).map(u8::is_ascii_digit).unwrap_or(false) => {}
+            _ => return None,
+        }
+    }
+    if digits.is_empty() {
+        None
+    } else {
+        Some(digits)
+    }
+}
+
 impl FromStr for HumanCapacity {
-    type Err = String;
+    type Err = HumanCapacityError;
     fn from_str(input: &str) -> Result<Self, Self::Err> {
-        let parts = input.trim().split('.').collect::<Vec<_>>();
-        let mut capacity = ONE_CKB
-            * parts
-                .first()
-                .ok_or_else(|| "Missing input".to_owned())?
-                .parse::<u64>()
-                .map_err(|err| err.to_string())?;
-        if let Some(shannon_str) = parts.get(1) {
-            let shannon_str = shannon_str.trim();
-            if shannon_str.len() > 8 {
-                return Err(format!("decimal part too long: {}", shannon_str.len()));
-            }
-            let mut shannon = shannon_str.parse::<u32>().map_err(|err| err.to_string())?;
-            for _ in 0..(8 - shannon_str.len()) {
-                shannon *= 10;
+        let input = input.trim();
+        if input.is_empty() {
+            return Err(HumanCapacityError::Empty);
+        }
+        if input.starts_with('-') {
+            return Err(HumanCapacityError::Negative(input.to_string()));
+        }
+        let invalid = || HumanCapacityError::InvalidFormat(input.to_string());
+        let overflow = || HumanCapacityError::Overflow(input.to_string());
+        let mut parts = input.splitn(2, '.');
+        let ckb_part = parts
+            .next()
+            .and_then(strip_separators)
+            .ok_or_else(invalid)?;
+        let ckb = ckb_part.parse::<u64>().map_err(|_| overflow())?;
+        let mut capacity = ckb.checked_mul(ONE_CKB).ok_or_else(overflow)?;
+        if let Some(shannon_part) = parts.next() {
+            let shannon_part = strip_separators(shannon_part).ok_or_else(invalid)?;
+            if shannon_part.len() > DECIMAL_PLACES {
+                return Err(HumanCapacityError::TooManyDecimals(shannon_part.len()));
             }
-            capacity += u64::from(shannon);
+            let shannons = format!("{:0<8}", shannon_part)
+                .parse::<u64>()
+                .map_err(|_| invalid())?;
+            capacity = capacity.checked_add(shannons).ok_or_else(overflow)?;
         }
         Ok(capacity.into())
     }
 }
 
+/// Trailing zeros of the decimal part are dropped, a precision pads the
+/// decimal part to at least that many places (at most 8) but never cuts the
+/// significant digits, e.g. `format!("{:.2}", HumanCapacity(123_456_000))`
+/// is `"1.23456"`.
 impl fmt::Display for HumanCapacity {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         let ckb_part = self.0 / ONE_CKB;
@@ -65,27 +162,43 @@ impl fmt::Display for HumanCapacity {
             }
             base *= 10;
         }
+        let decimal_places = (8 - suffix_zero).max(f.precision().unwrap_or(0).min(DECIMAL_PLACES));
         if f.alternate() {
             write!(
                 f,
                 "{}.{} (CKB)",
                 ckb_part,
-                &shannon_part_string[..(8 - suffix_zero)]
+                &shannon_part_string[..decimal_places]
             )
         } else {
-            write!(
-                f,
-                "{}.{}",
-                ckb_part,
-                &shannon_part_string[..(8 - suffix_zero)]
-            )
+            write!(f, "{}.{}", ckb_part, &shannon_part_string[..decimal_places])
         }
     }
 }
 
+impl Serialize for HumanCapacity {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for HumanCapacity {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        HumanCapacity::from_str(&value).map_err(D::Error::custom)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn test_human_capacity() {
@@ -118,5 +231,133 @@ mod test {
         assert!(HumanCapacity::from_str("abc").is_err());
         assert!(HumanCapacity::from_str("-234").is_err());
         assert!(HumanCapacity::from_str("-234.3").is_err());
+        assert!(HumanCapacity::from_str("184467440738").is_err());
+        assert!(HumanCapacity::from_str("184467440737.1").is_err());
+        assert_eq!(
+            HumanCapacity::from_str("184467440737.09551615"),
+            Ok(HumanCapacity::from(u64::MAX))
+        );
+    }
+
+    #[test]
+    fn test_human_capacity_separators() {
+        for (input, capacity) in &[
+            ("1_000.5", 1000 * ONE_CKB + 50_000_000),
+            ("1_000_000", 1_000_000 * ONE_CKB),
+            ("0.000_000_01", 1),
+            (" 12.5 ", 12 * ONE_CKB + 50_000_000),
+        ] {
+            assert_eq!(HumanCapacity::from_str(input), Ok((*capacity).into()));
+        }
+        for input in &["_1", "1_", "1__0", "1._5", "1_.5", "1.5_", "_"] {
+            assert_eq!(
+                HumanCapacity::from_str(input),
+                Err(HumanCapacityError::InvalidFormat(input.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_human_capacity_errors() {
+        let parse = HumanCapacity::from_str;
+        assert_eq!(parse(""), Err(HumanCapacityError::Empty));
+        assert_eq!(parse("  "), Err(HumanCapacityError::Empty));
+        assert_eq!(
+            parse("-1.5"),
+            Err(HumanCapacityError::Negative("-1.5".to_string()))
+        );
+        assert_eq!(
+            parse("1.123456789"),
+            Err(HumanCapacityError::TooManyDecimals(9))
+        );
+        assert_eq!(
+            parse("0.000_000_001"),
+            Err(HumanCapacityError::TooManyDecimals(9))
+        );
+        for input in &["1.2.3", "1.", ".5", "+1", "1e8", "1,000", "0x10"] {
+            assert_eq!(
+                parse(input),
+                Err(HumanCapacityError::InvalidFormat(input.to_string()))
+            );
+        }
+        for input in &[
+            "184467440738",
+            "99999999999999999999",
+            "184467440737.09551616",
+        ] {
+            assert_eq!(
+                parse(input),
+                Err(HumanCapacityError::Overflow(input.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_human_capacity_arithmetic() {
+        let one = HumanCapacity::from_shannons(ONE_CKB);
+        let max = HumanCapacity::from_shannons(u64::MAX);
+        assert_eq!(one.as_shannons(), ONE_CKB);
+        assert_eq!(one.checked_add(one), Ok(HumanCapacity(2 * ONE_CKB)));
+        assert_eq!(one.checked_sub(one), Ok(HumanCapacity(0)));
+        assert_eq!(one.checked_mul(3), Ok(HumanCapacity(3 * ONE_CKB)));
+        assert!(matches!(
+            max.checked_add(HumanCapacity(1)),
+            Err(HumanCapacityError::Overflow(_))
+        ));
+        assert!(matches!(
+            HumanCapacity(0).checked_sub(HumanCapacity(1)),
+            Err(HumanCapacityError::Overflow(_))
+        ));
+        assert!(matches!(
+            max.checked_mul(2),
+            Err(HumanCapacityError::Overflow(_))
+        ));
+    }
+
+    #[test]
+    fn test_human_capacity_format() {
+        let capacity = HumanCapacity(150_000_000);
+        assert_eq!(capacity.to_string(), "1.5");
+        assert_eq!(capacity.to_exact_string(), "1.50000000");
+        assert_eq!(format!("{:.2}", capacity), "1.50");
+        assert_eq!(format!("{:#.2}", capacity), "1.50 (CKB)");
+        // the precision never cuts the significant digits
+        assert_eq!(format!("{:.2}", HumanCapacity(123_456_000)), "1.23456");
+        assert_eq!(format!("{:.20}", capacity), "1.50000000");
+        assert_eq!(HumanCapacity(0).to_exact_string(), "0.00000000");
+    }
+
+    #[test]
+    fn test_human_capacity_serde() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Config {
+            amount: HumanCapacity,
+        }
+        let config: Config = serde_json::from_str(r#"{"amount": "500.0"}"#).unwrap();
+        assert_eq!(config.amount, HumanCapacity(500 * ONE_CKB));
+        assert_eq!(
+            serde_json::to_string(&config).unwrap(),
+            r#"{"amount":"500.0"}"#
+        );
+        assert!(serde_json::from_str::<Config>(r#"{"amount": 500}"#).is_err());
+        assert!(serde_json::from_str::<Config>(r#"{"amount": "-1"}"#).is_err());
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(256))]
+
+        #[test]
+        fn test_human_capacity_roundtrip(shannons in any::<u64>()) {
+            let capacity = HumanCapacity::from_shannons(shannons);
+            prop_assert_eq!(HumanCapacity::from_str(&capacity.to_string()), Ok(capacity));
+            prop_assert_eq!(HumanCapacity::from_str(&capacity.to_exact_string()), Ok(capacity));
+            let json = serde_json::to_string(&capacity).unwrap();
+            prop_assert_eq!(serde_json::from_str::<HumanCapacity>(&json).unwrap(), capacity);
+        }
+
+        #[test]
+        fn test_human_capacity_parse_never_panics(input in ".*") {
+            let _ = HumanCapacity::from_str(&input);
+        }
     }
 }
@@ -1,11 +1,15 @@
 //! Basic ckb sdk types
 mod address;
+mod cell_dep_config;
+mod hash_type;
 mod human_capacity;
 mod network_type;
 #[allow(clippy::all)]
 pub mod omni_lock;
+mod omni_lock_witness;
 mod script_group;
 mod script_id;
+mod script_registry;
 mod since;
 pub mod transaction_with_groups;
 #[allow(clippy::all)]
@@ -14,9 +18,12 @@ pub mod xudt_rce_mol;
 pub use address::{
     Address, AddressPayload, AddressType, CodeHashIndex, OldAddress, OldAddressFormat,
 };
-pub use human_capacity::HumanCapacity;
-pub use network_type::{NetworkInfo, NetworkType};
+pub use cell_dep_config::{CellDepConfig, CellDepConfigError, CellDepConfigItem};
+pub(crate) use hash_type::ScriptHashTypeExt;
+pub use human_capacity::{HumanCapacity, HumanCapacityError};
+pub use network_type::{ChainParams, DetectError, NetworkInfo, NetworkType};
 pub use script_group::{ScriptGroup, ScriptGroupType};
-pub use script_id::ScriptId;
-pub use since::{Since, SinceType};
+pub use script_id::{ScriptId, ScriptIdParseError};
+pub use script_registry::{KnownScript, ScriptRegistry};
+pub use since::{Since, SinceParseError, SinceType};
 pub use transaction_with_groups::TransactionWithScriptGroups;
@@ -1,11 +1,16 @@
 use std::fmt;
 
+use ckb_hash::{Blake2b, Blake2bBuilder, CKB_HASH_PERSONALIZATION};
+use ckb_types::{H160, H256};
 use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
 
+use super::{Address, AddressPayload};
 use crate::constants::{
-    NETWORK_DEV, NETWORK_MAINNET, NETWORK_PREVIEW, NETWORK_STAGING, NETWORK_TESTNET,
-    PREFIX_MAINNET, PREFIX_TESTNET,
+    MAINNET_GENESIS_HASH, NETWORK_DEV, NETWORK_MAINNET, NETWORK_PREVIEW, NETWORK_STAGING,
+    NETWORK_TESTNET, PREFIX_MAINNET, PREFIX_TESTNET, TESTNET_GENESIS_HASH,
 };
+use crate::rpc::{CkbRpcClient, RpcError};
 
 #[derive(Hash, Eq, PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum NetworkType {
@@ -63,15 +68,110 @@ impl fmt::Display for NetworkType {
     }
 }
 
+/// The parameters of a chain which reuses the CKB transaction format but may
+/// change the hash personalization or the address prefix (e.g. a fork or a
+/// sidechain of CKB). The default value is the CKB one.
+///
+/// Only the hashing done by this SDK itself (signing messages, blake160 of
+/// public keys) is affected, the hashes computed by ckb-types (transaction
+/// hash, script hash, ...) always use the CKB personalization.
+#[derive(Hash, Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct ChainParams {
+    pub blake2b_personalization: [u8; 16],
+    pub address_hrp_main: String,
+    pub address_hrp_test: String,
+}
+
+impl Default for ChainParams {
+    fn default() -> Self {
+        let mut blake2b_personalization = [0u8; 16];
+        blake2b_personalization.copy_from_slice(CKB_HASH_PERSONALIZATION);
+        ChainParams {
+            blake2b_personalization,
+            address_hrp_main: PREFIX_MAINNET.to_string(),
+            address_hrp_test: PREFIX_TESTNET.to_string(),
+        }
+    }
+}
+
+impl ChainParams {
+    /// A blake2b hasher with 32 bytes output and the chain's personalization
+    pub fn new_blake2b(&self) -> Blake2b {
+        Blake2bBuilder::new(32)
+            .personal(&self.blake2b_personalization)
+            .build()
+    }
+
+    pub fn blake2b_256<T: AsRef<[u8]>>(&self, data: T) -> [u8; 32] {
+        let mut result = [0u8; 32];
+        let mut blake2b = self.new_blake2b();
+        blake2b.update(data.as_ref());
+        blake2b.finalize(&mut result);
+        result
+    }
+
+    pub fn blake160(&self, data: &[u8]) -> H160 {
+        H160::from_slice(&self.blake2b_256(data)[0..20]).expect("blake160")
+    }
+
+    /// The address prefix of the network, same as `NetworkType::to_prefix`
+    /// with the default value.
+    pub fn hrp(&self, network: NetworkType) -> &str {
+        match network {
+            NetworkType::Mainnet => &self.address_hrp_main,
+            _ => &self.address_hrp_test,
+        }
+    }
+
+    /// Same as `NetworkType::from_prefix` with the default value.
+    pub fn network_from_hrp(&self, hrp: &str) -> Option<NetworkType> {
+        if hrp == self.address_hrp_main {
+            Some(NetworkType::Mainnet)
+        } else if hrp == self.address_hrp_test {
+            Some(NetworkType::Testnet)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum DetectError {
+    #[error("rpc error: `{0}`")]
+    Rpc(#[from] RpcError),
+
+    #[error("genesis block not found")]
+    GenesisNotFound,
+
+    #[error("network mismatch, expected: `{0}`, detected: `{1}`")]
+    NetworkMismatch(NetworkType, NetworkType),
+}
+
 #[derive(Debug, Clone)]
 pub struct NetworkInfo {
     pub network_type: NetworkType,
     pub url: String,
+    pub chain_params: ChainParams,
+    /// The genesis block hash, set when the network is detected
+    pub genesis_hash: Option<H256>,
+    /// The chain id (`chain` of `get_blockchain_info`), set when the network
+    /// is detected
+    pub chain: Option<String>,
 }
 
 impl NetworkInfo {
     pub fn new(network_type: NetworkType, url: String) -> Self {
-        Self { network_type, url }
+        Self {
+            network_type,
+            url,
+            chain_params: ChainParams::default(),
+            genesis_hash: None,
+            chain: None,
+        }
+    }
+    pub fn with_chain_params(mut self, chain_params: ChainParams) -> Self {
+        self.chain_params = chain_params;
+        self
     }
     pub fn from_network_type(network_type: NetworkType) -> Option<Self> {
         match network_type {
@@ -82,23 +182,158 @@ impl NetworkInfo {
             NetworkType::Dev => None,
         }
     }
-    pub fn mainnet() -> Self {
-        Self {
-            network_type: NetworkType::Mainnet,
-            url: "https://mainnet.ckb.dev".to_string(),
+    /// The mainnet or the testnet by the genesis block hash, a dev chain
+    /// otherwise.
+    pub fn from_genesis_hash(genesis_hash: H256, url: String) -> Self {
+        let network_type = if genesis_hash == MAINNET_GENESIS_HASH {
+            NetworkType::Mainnet
+        } else if genesis_hash == TESTNET_GENESIS_HASH {
+            NetworkType::Testnet
+        } else {
+            NetworkType::Dev
+        };
+        let mut info = Self::new(network_type, url);
+        info.genesis_hash = Some(genesis_hash);
+        info
+    }
+    /// Detect the network of the node by its genesis block hash.
+    pub fn detect(rpc: &CkbRpcClient) -> Result<Self, DetectError> {
+        let genesis_hash = rpc
+            .get_block_hash(0u64.into())?
+            .ok_or(DetectError::GenesisNotFound)?;
+        let chain_info = rpc.get_blockchain_info()?;
+        let mut info = Self::from_genesis_hash(genesis_hash, rpc.url.to_string());
+        info.chain = Some(chain_info.chain);
+        Ok(info)
+    }
+    /// Same as `detect`, but the detected network must be `expected`.
+    pub fn detect_expected(rpc: &CkbRpcClient, expected: NetworkType) -> Result<Self, DetectError> {
+        let info = Self::detect(rpc)?;
+        info.check_network(expected)?;
+        Ok(info)
+    }
+    pub fn check_network(&self, expected: NetworkType) -> Result<(), DetectError> {
+        if self.network_type != expected {
+            return Err(DetectError::NetworkMismatch(expected, self.network_type));
         }
+        Ok(())
+    }
+    /// The full format address of `payload` on the network
+    pub fn address(&self, payload: AddressPayload) -> Address {
+        Address::new(self.network_type, payload, true)
+    }
+    /// Encode the address with the chain's address prefix of the network
+    pub fn display_address(&self, address: &Address) -> String {
+        address.payload().display_with_params(
+            self.network_type,
+            address.is_new(),
+            &self.chain_params,
+        )
+    }
+    pub fn mainnet() -> Self {
+        Self::new(NetworkType::Mainnet, "https://mainnet.ckb.dev".to_string())
     }
     pub fn testnet() -> Self {
-        Self {
-            network_type: NetworkType::Testnet,
-            url: "https://testnet.ckb.dev".to_string(),
-        }
+        Self::new(NetworkType::Testnet, "https://testnet.ckb.dev".to_string())
     }
 
     pub fn devnet() -> Self {
-        Self {
-            network_type: NetworkType::Dev,
-            url: "http://localhost:8114".to_string(),
-        }
+        Self::new(NetworkType::Dev, "http://localhost:8114".to_string())
+    }
+}
+
+impl From<&NetworkInfo> for NetworkType {
+    fn from(info: &NetworkInfo) -> NetworkType {
+        info.network_type
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockRpcResult;
+    use ckb_types::h256;
+    use httpmock::prelude::*;
+
+    fn mock_node(genesis_hash: H256, chain: &str) -> MockServer {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST).path("/").body_contains("get_block_hash");
+            then.status(200)
+                .body(MockRpcResult::new(genesis_hash).to_json());
+        });
+        let chain_info = serde_json::json!({
+            "chain": chain,
+            "median_time": "0x0",
+            "epoch": "0x0",
+            "difficulty": "0x1",
+            "is_initial_block_download": false,
+            "alerts": [],
+        });
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/")
+                .body_contains("get_blockchain_info");
+            then.status(200)
+                .body(MockRpcResult::new(chain_info).to_json());
+        });
+        server
+    }
+
+    #[test]
+    fn test_from_genesis_hash() {
+        let url = "http://localhost:8114".to_string();
+        let info = NetworkInfo::from_genesis_hash(MAINNET_GENESIS_HASH, url.clone());
+        assert_eq!(info.network_type, NetworkType::Mainnet);
+        let info = NetworkInfo::from_genesis_hash(TESTNET_GENESIS_HASH, url.clone());
+        assert_eq!(info.network_type, NetworkType::Testnet);
+        let dev_hash = h256!("0x1234");
+        let info = NetworkInfo::from_genesis_hash(dev_hash.clone(), url);
+        assert_eq!(info.network_type, NetworkType::Dev);
+        assert_eq!(info.genesis_hash, Some(dev_hash));
+        assert_eq!(NetworkType::from(&info), NetworkType::Dev);
+    }
+
+    #[test]
+    fn test_detect() {
+        let server = mock_node(TESTNET_GENESIS_HASH, "ckb_testnet");
+        let rpc = CkbRpcClient::new(server.base_url().as_str());
+        let info = NetworkInfo::detect(&rpc).unwrap();
+        assert_eq!(info.network_type, NetworkType::Testnet);
+        assert_eq!(info.genesis_hash, Some(TESTNET_GENESIS_HASH));
+        assert_eq!(info.chain.as_deref(), Some("ckb_testnet"));
+        assert_eq!(info.url, rpc.url.to_string());
+        assert!(NetworkInfo::detect_expected(&rpc, NetworkType::Testnet).is_ok());
+        assert!(matches!(
+            NetworkInfo::detect_expected(&rpc, NetworkType::Mainnet),
+            Err(DetectError::NetworkMismatch(
+                NetworkType::Mainnet,
+                NetworkType::Testnet
+            ))
+        ));
+
+        let server = mock_node(h256!("0x1234"), "ckb_dev");
+        let rpc = CkbRpcClient::new(server.base_url().as_str());
+        let info = NetworkInfo::detect(&rpc).unwrap();
+        assert_eq!(info.network_type, NetworkType::Dev);
+        assert_eq!(info.chain.as_deref(), Some("ckb_dev"));
+    }
+
+    #[test]
+    fn test_network_info_address() {
+        let payload = AddressPayload::from_pubkey_hash(H160::default());
+        let mainnet = NetworkInfo::mainnet();
+        let address = mainnet.address(payload.clone());
+        assert_eq!(address.network(), NetworkType::Mainnet);
+        assert_eq!(mainnet.display_address(&address), address.to_string());
+        assert_eq!(Address::new(&mainnet, payload.clone(), true), address);
+
+        let params = ChainParams {
+            address_hrp_test: "xyz".to_string(),
+            ..ChainParams::default()
+        };
+        let devnet = NetworkInfo::devnet().with_chain_params(params);
+        let address = devnet.address(payload);
+        assert!(devnet.display_address(&address).starts_with("xyz1"));
     }
 }
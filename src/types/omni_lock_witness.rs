@@ -0,0 +1,44 @@
+use ckb_types::{error::VerificationError, prelude::*};
+
+use super::omni_lock::OmniLockWitnessLock;
+
+// total size and the offsets of the 3 fields
+const WITNESS_LOCK_HEADER_LEN: usize = 4 + 3 * 4;
+// total size and the offsets of the 2 fields
+const IDENTITY_HEADER_LEN: usize = 4 + 2 * 4;
+const AUTH_LEN: usize = 21;
+const BYTES_HEADER_LEN: usize = 4;
+
+impl OmniLockWitnessLock {
+    /// The serialized length of the witness lock with the given fields, to
+    /// compute the exact size of a placeholder witness lock:
+    ///   * `signature_len` - the length of the signature, 0 means no signature
+    ///   * `proofs_len` - the serialized length of the `SmtProofEntryVec` in
+    ///     the omni identity, 0 means no omni identity (an empty proof vector
+    ///     is 4 bytes long)
+    ///   * `preimage_len` - the length of the preimage, 0 means no preimage
+    pub fn size_with(signature_len: usize, proofs_len: usize, preimage_len: usize) -> usize {
+        let bytes_opt_len = |len: usize| {
+            if len == 0 {
+                0
+            } else {
+                BYTES_HEADER_LEN + len
+            }
+        };
+        let identity_len = if proofs_len == 0 {
+            0
+        } else {
+            IDENTITY_HEADER_LEN + AUTH_LEN + proofs_len
+        };
+        WITNESS_LOCK_HEADER_LEN
+            + bytes_opt_len(signature_len)
+            + identity_len
+            + bytes_opt_len(preimage_len)
+    }
+
+    /// Parse the lock field of a witness, the field must be a valid
+    /// `OmniLockWitnessLock`.
+    pub fn parse(witness_lock: &[u8]) -> Result<OmniLockWitnessLock, VerificationError> {
+        OmniLockWitnessLock::from_slice(witness_lock)
+    }
+}
@@ -1,8 +1,27 @@
-use std::convert::TryFrom;
 use std::fmt;
+use std::str::FromStr;
 
+use super::{ScriptHashTypeExt, ScriptRegistry};
 use crate::constants::{DAO_TYPE_HASH, TYPE_ID_CODE_HASH};
+use ckb_jsonrpc_types as json_types;
 use ckb_types::{core::ScriptHashType, packed::Script, prelude::*, H256};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum ScriptIdParseError {
+    #[error("invalid script id, expected `<code_hash>-<hash_type>`, got: `{0}`")]
+    InvalidFormat(String),
+
+    #[error("invalid code hash: `{0}`")]
+    InvalidCodeHash(String),
+
+    #[error("unknown hash type: `{0}`")]
+    UnknownHashType(String),
+
+    #[error("the name `{1}` does not match script id `{0}`")]
+    NameMismatch(ScriptId, String),
+}
 
 #[derive(Clone, Hash, Eq, PartialEq, Debug, Default)]
 pub struct ScriptId {
@@ -39,7 +58,7 @@ impl ScriptId {
     pub fn dummy_type_id_script(&self) -> Script {
         Script::new_builder()
             .code_hash(self.code_hash.pack())
-            .hash_type(self.hash_type.into())
+            .hash_type(self.hash_type.to_packed())
             .args(<[u8]>::pack(&[0u8; 32]))
             .build()
     }
@@ -48,7 +67,7 @@ impl ScriptId {
 impl From<&Script> for ScriptId {
     fn from(script: &Script) -> ScriptId {
         let code_hash: H256 = script.code_hash().unpack();
-        let hash_type = ScriptHashType::try_from(script.hash_type()).expect("hash type");
+        let hash_type = ScriptHashType::from_packed(&script.hash_type()).expect("hash type");
         ScriptId {
             code_hash,
             hash_type,
@@ -56,12 +75,171 @@ impl From<&Script> for ScriptId {
     }
 }
 
+/// Formatted as `<code_hash>-<hash_type>`, e.g. `0x9bd7..cce8-type`. A well
+/// known script of the mainnet or the testnet has its registry name as a
+/// suffix: `0x9bd7..cce8-type (secp256k1_blake160_sighash_all)`.
 impl fmt::Display for ScriptId {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "code_hash={:?}, hash_type={:?}",
-            self.code_hash, self.hash_type
-        )
+        write!(f, "{:#x}-{}", self.code_hash, self.hash_type.to_name())?;
+        if let Some(name) = ScriptRegistry::well_known_name(self) {
+            write!(f, " ({})", name)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse the `Display` format, the `0x` prefix of the code hash and the name
+/// suffix are optional, the name must match the script id when it is given.
+impl FromStr for ScriptId {
+    type Err = ScriptIdParseError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input = input.trim();
+        let (id_part, name) = match input.split_once(' ') {
+            Some((id_part, suffix)) => {
+                let name = suffix
+                    .trim_start()
+                    .strip_prefix('(')
+                    .and_then(|value| value.strip_suffix(')'))
+                    .ok_or_else(|| ScriptIdParseError::InvalidFormat(input.to_string()))?;
+                (id_part, Some(name))
+            }
+            None => (input, None),
+        };
+        let (code_hash, hash_type) = id_part
+            .split_once('-')
+            .ok_or_else(|| ScriptIdParseError::InvalidFormat(input.to_string()))?;
+        let hex = code_hash.strip_prefix("0x").unwrap_or(code_hash);
+        // `H256::from_str` also accepts a short hex without the leading zeros
+        if hex.len() != 64 {
+            return Err(ScriptIdParseError::InvalidCodeHash(code_hash.to_string()));
+        }
+        let code_hash = H256::from_str(hex)
+            .map_err(|_| ScriptIdParseError::InvalidCodeHash(code_hash.to_string()))?;
+        let hash_type = ScriptHashType::from_name(hash_type)
+            .ok_or_else(|| ScriptIdParseError::UnknownHashType(hash_type.to_string()))?;
+        let script_id = ScriptId::new(code_hash, hash_type);
+        if let Some(name) = name {
+            if ScriptRegistry::well_known_name(&script_id) != Some(name) {
+                return Err(ScriptIdParseError::NameMismatch(
+                    script_id,
+                    name.to_string(),
+                ));
+            }
+        }
+        Ok(script_id)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ScriptIdJson {
+    code_hash: H256,
+    hash_type: json_types::ScriptHashType,
+}
+
+/// Serialized as a struct: `{"code_hash": "0x..", "hash_type": "type"}`
+impl Serialize for ScriptId {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        ScriptIdJson {
+            code_hash: self.code_hash.clone(),
+            hash_type: self.hash_type.to_json(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ScriptId {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = ScriptIdJson::deserialize(deserializer)?;
+        Ok(ScriptId::new(
+            value.code_hash,
+            ScriptHashType::from_json(value.hash_type),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::SIGHASH_TYPE_HASH;
+    use ckb_types::h256;
+
+    #[test]
+    fn test_display_and_parse() {
+        let script_id = ScriptId::new_data1(h256!("0x1234"));
+        let text = script_id.to_string();
+        assert_eq!(
+            text,
+            "0x0000000000000000000000000000000000000000000000000000000000001234-data1"
+        );
+        assert_eq!(ScriptId::from_str(&text), Ok(script_id.clone()));
+        assert_eq!(
+            ScriptId::from_str(text.trim_start_matches("0x")),
+            Ok(script_id.clone())
+        );
+
+        let sighash = ScriptId::new_type(SIGHASH_TYPE_HASH);
+        let text = sighash.to_string();
+        assert_eq!(
+            text,
+            "0x9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8-type (secp256k1_blake160_sighash_all)"
+        );
+        assert_eq!(ScriptId::from_str(&text), Ok(sighash.clone()));
+        assert_eq!(
+            ScriptId::from_str(text.split(' ').next().unwrap()),
+            Ok(sighash.clone())
+        );
+        assert_eq!(
+            ScriptId::from_str(&format!("{:#x}-type (dao)", SIGHASH_TYPE_HASH)),
+            Err(ScriptIdParseError::NameMismatch(sighash, "dao".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let code_hash = format!("{:#x}", SIGHASH_TYPE_HASH);
+        for input in [
+            String::new(),
+            code_hash.clone(),
+            format!("{}-type (sighash", code_hash),
+            format!("{}-type sighash", code_hash),
+        ] {
+            assert_eq!(
+                ScriptId::from_str(&input),
+                Err(ScriptIdParseError::InvalidFormat(input.trim().to_string()))
+            );
+        }
+        for hash in ["0x1234", "xyz", &code_hash[..64], "0x0x1234"] {
+            assert_eq!(
+                ScriptId::from_str(&format!("{}-type", hash)),
+                Err(ScriptIdParseError::InvalidCodeHash(hash.to_string()))
+            );
+        }
+        assert_eq!(
+            ScriptId::from_str(&format!("{}-Type", code_hash)),
+            Err(ScriptIdParseError::UnknownHashType("Type".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_serde() {
+        let script_id = ScriptId::new_type(SIGHASH_TYPE_HASH);
+        let json = serde_json::to_string(&script_id).unwrap();
+        assert_eq!(
+            json,
+            r#"{"code_hash":"0x9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8","hash_type":"type"}"#
+        );
+        assert_eq!(serde_json::from_str::<ScriptId>(&json).unwrap(), script_id);
+        assert!(
+            serde_json::from_str::<ScriptId>(r#"{"code_hash":"0x1234","hash_type":"type"}"#)
+                .is_err()
+        );
+        assert!(serde_json::from_str::<ScriptId>(&json.replace("\"type\"", "\"data3\"")).is_err());
     }
 }
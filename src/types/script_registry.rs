@@ -0,0 +1,624 @@
+use std::collections::HashMap;
+
+use ckb_types::{
+    core::{BlockView, DepType, ScriptHashType},
+    h256,
+    packed::{CellDep, OutPoint, Script},
+    prelude::*,
+    H256,
+};
+
+use super::{NetworkType, ScriptHashTypeExt, ScriptId};
+use crate::constants::{
+    ACP_TYPE_HASH_AGGRON, ACP_TYPE_HASH_LINA, DAO_TYPE_HASH, MULTISIG_TYPE_HASH,
+    PW_LOCK_TYPE_HASH_AGGRON, PW_LOCK_TYPE_HASH_LINA, SIGHASH_TYPE_HASH,
+};
+use crate::traits::{default_impls::ParseGenesisInfoError, DefaultCellDepResolver};
+
+lazy_static::lazy_static! {
+    static ref WELL_KNOWN_NAMES: HashMap<ScriptId, &'static str> = {
+        let mut names = HashMap::new();
+        for registry in [ScriptRegistry::mainnet(), ScriptRegistry::testnet()] {
+            for name in ScriptRegistry::BUILTIN_NAMES {
+                if let Some(script) = registry.get(name) {
+                    names.insert(script.script_id.clone(), name);
+                }
+            }
+        }
+        names
+    };
+}
+
+/// A script deployed on a chain
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct KnownScript {
+    pub name: String,
+    pub script_id: ScriptId,
+    pub cell_dep: CellDep,
+}
+
+/// The well known scripts of a chain and their cell deps, keyed by name.
+///
+/// The built in names are the associated constants, e.g.
+/// `ScriptRegistry::SIGHASH`.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ScriptRegistry {
+    scripts: Vec<KnownScript>,
+}
+
+impl ScriptRegistry {
+    pub const SIGHASH: &'static str = "secp256k1_blake160_sighash_all";
+    pub const MULTISIG: &'static str = "secp256k1_blake160_multisig_all";
+    pub const DAO: &'static str = "dao";
+    pub const ACP: &'static str = "anyone_can_pay";
+    pub const CHEQUE: &'static str = "cheque";
+    pub const SUDT: &'static str = "sudt";
+    pub const XUDT: &'static str = "xudt";
+    pub const OMNI_LOCK: &'static str = "omni_lock";
+    /// The pw-lock also needs the secp256k1 data cell, which is in the
+    /// cell dep of `SIGHASH`.
+    pub const PW_LOCK: &'static str = "pw_lock";
+
+    const BUILTIN_NAMES: [&'static str; 9] = [
+        Self::SIGHASH,
+        Self::MULTISIG,
+        Self::DAO,
+        Self::ACP,
+        Self::CHEQUE,
+        Self::SUDT,
+        Self::XUDT,
+        Self::OMNI_LOCK,
+        Self::PW_LOCK,
+    ];
+
+    /// The built in name of a script deployed on the mainnet or the testnet,
+    /// used by the `Display` of `ScriptId`.
+    pub fn well_known_name(script_id: &ScriptId) -> Option<&'static str> {
+        WELL_KNOWN_NAMES.get(script_id).copied()
+    }
+
+    /// The scripts deployed on the mainnet (Lina)
+    pub fn mainnet() -> ScriptRegistry {
+        let secp_dep_group =
+            h256!("0x71a7ba8fc96349fea0ed3a5c47992e3b4084b031a42264a018e0072e8172e46c");
+        let mut registry = ScriptRegistry::default();
+        registry.register(
+            Self::SIGHASH,
+            ScriptId::new_type(SIGHASH_TYPE_HASH),
+            cell_dep(secp_dep_group.clone(), 0, DepType::DepGroup),
+        );
+        registry.register(
+            Self::MULTISIG,
+            ScriptId::new_type(MULTISIG_TYPE_HASH),
+            cell_dep(secp_dep_group, 1, DepType::DepGroup),
+        );
+        registry.register(
+            Self::DAO,
+            ScriptId::new_type(DAO_TYPE_HASH),
+            cell_dep(
+                h256!("0xe2fb199810d49a4d8beec56718ba2593b665db9d52299a0f9e6e75416d73ff5c"),
+                2,
+                DepType::Code,
+            ),
+        );
+        registry.register(
+            Self::ACP,
+            ScriptId::new_type(ACP_TYPE_HASH_LINA),
+            cell_dep(
+                h256!("0x4153a2014952d7cac45f285ce9a7c5c0c0e1b21f2d378b82ac1433cb11c25c4d"),
+                0,
+                DepType::DepGroup,
+            ),
+        );
+        registry.register(
+            Self::CHEQUE,
+            ScriptId::new_type(h256!(
+                "0xe4d4ecc6e5f9a059bf2f7a82cca292083aebc0c421566a52484fe2ec51a9fb0c"
+            )),
+            cell_dep(
+                h256!("0x04632cc459459cf5c9d384b43dee3e36f542a464bdd4127be7d6618ac6f8d268"),
+                0,
+                DepType::DepGroup,
+            ),
+        );
+        registry.register(
+            Self::SUDT,
+            ScriptId::new_type(h256!(
+                "0x5e7a36a77e68eecc013dfa2fe6a23f3b6c344b04005808694ae6dd45eea4cfd5"
+            )),
+            cell_dep(
+                h256!("0xc7813f6a415144643970c2e88e0bb6ca6a8edc5dd7c1022746f628284a9936d5"),
+                0,
+                DepType::Code,
+            ),
+        );
+        registry.register(
+            Self::XUDT,
+            ScriptId::new_data1(h256!(
+                "0x50bd8d6680b8b9cf98b73f3c08faf8b2a21914311954118ad6609be6e78a1b95"
+            )),
+            cell_dep(
+                h256!("0xc07844ce21b38e4b071dd0e1ee3b0e27afd8d7532491327f39b786343f558ab7"),
+                0,
+                DepType::Code,
+            ),
+        );
+        registry.register(
+            Self::OMNI_LOCK,
+            ScriptId::new_type(h256!(
+                "0x9b819793a64463aed77c615d6cb226eea5487ccfc0783043a587254cda2b6f26"
+            )),
+            cell_dep(
+                h256!("0xc76edf469816aa22f416503c38d0b533d2a018e253e379f134c3985b3472c842"),
+                0,
+                DepType::Code,
+            ),
+        );
+        registry.register(
+            Self::PW_LOCK,
+            ScriptId::new_type(PW_LOCK_TYPE_HASH_LINA),
+            cell_dep(
+                h256!("0x1d60cb8f4666e039f418ea94730b1a8c5aa0bf2f7781474406387462924d15d4"),
+                0,
+                DepType::Code,
+            ),
+        );
+        registry
+    }
+
+    /// The scripts deployed on the testnet (Aggron4)
+    pub fn testnet() -> ScriptRegistry {
+        let secp_dep_group =
+            h256!("0xf8de3bb47d055cdf460d93a2a6e1b05f7432f9777c8c474abf4eec1d4aee5d37");
+        let mut registry = ScriptRegistry::default();
+        registry.register(
+            Self::SIGHASH,
+            ScriptId::new_type(SIGHASH_TYPE_HASH),
+            cell_dep(secp_dep_group.clone(), 0, DepType::DepGroup),
+        );
+        registry.register(
+            Self::MULTISIG,
+            ScriptId::new_type(MULTISIG_TYPE_HASH),
+            cell_dep(secp_dep_group, 1, DepType::DepGroup),
+        );
+        registry.register(
+            Self::DAO,
+            ScriptId::new_type(DAO_TYPE_HASH),
+            cell_dep(
+                h256!("0x8f8c79eb6671709633fe6a46de93c0fedc9c1b8a6527a18d3983879542635c9f"),
+                2,
+                DepType::Code,
+            ),
+        );
+        registry.register(
+            Self::ACP,
+            ScriptId::new_type(ACP_TYPE_HASH_AGGRON),
+            cell_dep(
+                h256!("0xec26b0f85ed839ece5f11c4c4e837ec359f5adc4420410f6453b1f6b60fb96a6"),
+                0,
+                DepType::DepGroup,
+            ),
+        );
+        registry.register(
+            Self::CHEQUE,
+            ScriptId::new_type(h256!(
+                "0x60d5f39efce409c587cb9ea359cefdead650ca128f0bd9cb3855348f98c70d5b"
+            )),
+            cell_dep(
+                h256!("0x7f96858be0a9d584b4a9ea190e0420835156a6010a5fde15ffcdc9d9c721ccab"),
+                0,
+                DepType::DepGroup,
+            ),
+        );
+        registry.register(
+            Self::SUDT,
+            ScriptId::new_type(h256!(
+                "0xc5e5dcf215925f7ef4dfaf5f4b4f105bc321c02776d6e7d52a1db3fcd9d011a4"
+            )),
+            cell_dep(
+                h256!("0xe12877ebd2c3c364dc46c5c992bcfaf4fee33fa13eebdf82c591fc9825aab769"),
+                0,
+                DepType::Code,
+            ),
+        );
+        registry.register(
+            Self::XUDT,
+            ScriptId::new_type(h256!(
+                "0x25c29dc317811a6f6f3985a7a9ebc4838bd388d19d0feeecf0bcd60f6c0975bb"
+            )),
+            cell_dep(
+                h256!("0xbf6fb538763efec2a70a6a3dcb7242787087e1030c4e7d86585bc63a9d337f5f"),
+                0,
+                DepType::Code,
+            ),
+        );
+        registry.register(
+            Self::OMNI_LOCK,
+            ScriptId::new_type(h256!(
+                "0xf329effd1c475a2978453c8600e1eaf0bc2087ee093c3ee64cc96ec6847752cb"
+            )),
+            cell_dep(
+                h256!("0xec18bf0d857c981c3d1f4e17999b9b90c484b303378e94de1a57b0872f5d4602"),
+                0,
+                DepType::Code,
+            ),
+        );
+        registry.register(
+            Self::PW_LOCK,
+            ScriptId::new_type(PW_LOCK_TYPE_HASH_AGGRON),
+            cell_dep(
+                h256!("0x57a62003daeab9d54aa29b944fc3b451213a5ebdf2e232216a3cfed0dde61b38"),
+                0,
+                DepType::Code,
+            ),
+        );
+        registry
+    }
+
+    /// The registry of the mainnet or the testnet, `None` for the other
+    /// networks, see `from_genesis_block`. The network is a `NetworkType` or
+    /// a `&NetworkInfo`.
+    pub fn from_network<N: Into<NetworkType>>(network: N) -> Option<ScriptRegistry> {
+        match network.into() {
+            NetworkType::Mainnet => Some(Self::mainnet()),
+            NetworkType::Testnet => Some(Self::testnet()),
+            _ => None,
+        }
+    }
+
+    /// The system scripts of a dev chain (sighash, multisig and dao), found in
+    /// its genesis block. The other scripts can be added by `register`.
+    pub fn from_genesis_block(
+        genesis_block: &BlockView,
+    ) -> Result<ScriptRegistry, ParseGenesisInfoError> {
+        let resolver = DefaultCellDepResolver::from_genesis(genesis_block)?;
+        let mut registry = ScriptRegistry::default();
+        for (name, code_hash, item) in [
+            (Self::SIGHASH, SIGHASH_TYPE_HASH, resolver.sighash_dep()),
+            (Self::MULTISIG, MULTISIG_TYPE_HASH, resolver.multisig_dep()),
+            (Self::DAO, DAO_TYPE_HASH, resolver.dao_dep()),
+        ] {
+            if let Some((cell_dep, _)) = item {
+                registry.register(name, ScriptId::new_type(code_hash), cell_dep.clone());
+            }
+        }
+        Ok(registry)
+    }
+
+    /// Add a script, the script already registered with the same name or the
+    /// same script id is replaced and returned.
+    pub fn register(
+        &mut self,
+        name: &str,
+        script_id: ScriptId,
+        cell_dep: CellDep,
+    ) -> Option<KnownScript> {
+        let old_index = self
+            .scripts
+            .iter()
+            .position(|item| item.name == name || item.script_id == script_id);
+        let script = KnownScript {
+            name: name.to_string(),
+            script_id,
+            cell_dep,
+        };
+        match old_index {
+            Some(index) => Some(std::mem::replace(&mut self.scripts[index], script)),
+            None => {
+                self.scripts.push(script);
+                None
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&KnownScript> {
+        self.scripts.iter().find(|item| item.name == name)
+    }
+
+    pub fn get_by_script_id(&self, script_id: &ScriptId) -> Option<&KnownScript> {
+        self.scripts
+            .iter()
+            .find(|item| item.script_id == *script_id)
+    }
+
+    /// The known script `script` runs, `None` when its hash type is invalid.
+    pub fn lookup_by_script(&self, script: &Script) -> Option<KnownScript> {
+        ScriptHashType::from_packed(&script.hash_type()).ok()?;
+        self.get_by_script_id(&ScriptId::from(script)).cloned()
+    }
+
+    pub fn cell_dep(&self, name: &str) -> Option<CellDep> {
+        self.get(name).map(|item| item.cell_dep.clone())
+    }
+
+    /// The scripts in the order they are registered
+    pub fn iter(&self) -> impl Iterator<Item = &KnownScript> {
+        self.scripts.iter()
+    }
+}
+
+fn cell_dep(tx_hash: H256, index: u32, dep_type: DepType) -> CellDep {
+    CellDep::new_builder()
+        .out_point(OutPoint::new(tx_hash.pack(), index))
+        .dep_type(dep_type.into())
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_jsonrpc_types as json_types;
+    use ckb_types::packed::Byte;
+
+    use crate::traits::CellDepResolver;
+    use crate::types::NetworkInfo;
+
+    const TESTNET_GENESIS_JSON: &str = include_str!("../test-data/genesis_block.json");
+
+    fn assert_script(
+        registry: &ScriptRegistry,
+        name: &str,
+        code_hash: H256,
+        hash_type: ScriptHashType,
+        tx_hash: H256,
+        index: u32,
+        dep_type: DepType,
+    ) {
+        let script = registry.get(name).unwrap();
+        assert_eq!(
+            script.script_id,
+            ScriptId::new(code_hash, hash_type),
+            "{}",
+            name
+        );
+        assert_eq!(
+            script.cell_dep,
+            cell_dep(tx_hash, index, dep_type),
+            "{}",
+            name
+        );
+    }
+
+    #[test]
+    fn test_mainnet_scripts() {
+        let registry = ScriptRegistry::mainnet();
+        assert_eq!(registry.iter().count(), 9);
+        let cases = [
+            (
+                ScriptRegistry::SIGHASH,
+                h256!("0x9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8"),
+                ScriptHashType::Type,
+                h256!("0x71a7ba8fc96349fea0ed3a5c47992e3b4084b031a42264a018e0072e8172e46c"),
+                0,
+                DepType::DepGroup,
+            ),
+            (
+                ScriptRegistry::MULTISIG,
+                h256!("0x5c5069eb0857efc65e1bca0c07df34c31663b3622fd3876c876320fc9634e2a8"),
+                ScriptHashType::Type,
+                h256!("0x71a7ba8fc96349fea0ed3a5c47992e3b4084b031a42264a018e0072e8172e46c"),
+                1,
+                DepType::DepGroup,
+            ),
+            (
+                ScriptRegistry::DAO,
+                h256!("0x82d76d1b75fe2fd9a27dfbaa65a039221a380d76c926f378d3f81cf3e7e13f2e"),
+                ScriptHashType::Type,
+                h256!("0xe2fb199810d49a4d8beec56718ba2593b665db9d52299a0f9e6e75416d73ff5c"),
+                2,
+                DepType::Code,
+            ),
+            (
+                ScriptRegistry::ACP,
+                h256!("0xd369597ff47f29fbc0d47d2e3775370d1250b85140c670e4718af712983a2354"),
+                ScriptHashType::Type,
+                h256!("0x4153a2014952d7cac45f285ce9a7c5c0c0e1b21f2d378b82ac1433cb11c25c4d"),
+                0,
+                DepType::DepGroup,
+            ),
+            (
+                ScriptRegistry::CHEQUE,
+                h256!("0xe4d4ecc6e5f9a059bf2f7a82cca292083aebc0c421566a52484fe2ec51a9fb0c"),
+                ScriptHashType::Type,
+                h256!("0x04632cc459459cf5c9d384b43dee3e36f542a464bdd4127be7d6618ac6f8d268"),
+                0,
+                DepType::DepGroup,
+            ),
+            (
+                ScriptRegistry::SUDT,
+                h256!("0x5e7a36a77e68eecc013dfa2fe6a23f3b6c344b04005808694ae6dd45eea4cfd5"),
+                ScriptHashType::Type,
+                h256!("0xc7813f6a415144643970c2e88e0bb6ca6a8edc5dd7c1022746f628284a9936d5"),
+                0,
+                DepType::Code,
+            ),
+            (
+                ScriptRegistry::XUDT,
+                h256!("0x50bd8d6680b8b9cf98b73f3c08faf8b2a21914311954118ad6609be6e78a1b95"),
+                ScriptHashType::Data1,
+                h256!("0xc07844ce21b38e4b071dd0e1ee3b0e27afd8d7532491327f39b786343f558ab7"),
+                0,
+                DepType::Code,
+            ),
+            (
+                ScriptRegistry::OMNI_LOCK,
+                h256!("0x9b819793a64463aed77c615d6cb226eea5487ccfc0783043a587254cda2b6f26"),
+                ScriptHashType::Type,
+                h256!("0xc76edf469816aa22f416503c38d0b533d2a018e253e379f134c3985b3472c842"),
+                0,
+                DepType::Code,
+            ),
+            (
+                ScriptRegistry::PW_LOCK,
+                h256!("0xbf43c3602455798c1a61a596e0d95278864c552fafe231c063b3fabf97a8febc"),
+                ScriptHashType::Type,
+                h256!("0x1d60cb8f4666e039f418ea94730b1a8c5aa0bf2f7781474406387462924d15d4"),
+                0,
+                DepType::Code,
+            ),
+        ];
+        for (name, code_hash, hash_type, tx_hash, index, dep_type) in cases {
+            assert_script(
+                &registry, name, code_hash, hash_type, tx_hash, index, dep_type,
+            );
+        }
+    }
+
+    #[test]
+    fn test_testnet_scripts() {
+        let registry = ScriptRegistry::testnet();
+        assert_eq!(registry.iter().count(), 9);
+        assert_eq!(
+            ScriptRegistry::from_network(&NetworkInfo::testnet()),
+            Some(registry.clone())
+        );
+        assert_eq!(ScriptRegistry::from_network(&NetworkInfo::devnet()), None);
+        let cases = [
+            (
+                ScriptRegistry::SIGHASH,
+                h256!("0x9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8"),
+                ScriptHashType::Type,
+                h256!("0xf8de3bb47d055cdf460d93a2a6e1b05f7432f9777c8c474abf4eec1d4aee5d37"),
+                0,
+                DepType::DepGroup,
+            ),
+            (
+                ScriptRegistry::MULTISIG,
+                h256!("0x5c5069eb0857efc65e1bca0c07df34c31663b3622fd3876c876320fc9634e2a8"),
+                ScriptHashType::Type,
+                h256!("0xf8de3bb47d055cdf460d93a2a6e1b05f7432f9777c8c474abf4eec1d4aee5d37"),
+                1,
+                DepType::DepGroup,
+            ),
+            (
+                ScriptRegistry::DAO,
+                h256!("0x82d76d1b75fe2fd9a27dfbaa65a039221a380d76c926f378d3f81cf3e7e13f2e"),
+                ScriptHashType::Type,
+                h256!("0x8f8c79eb6671709633fe6a46de93c0fedc9c1b8a6527a18d3983879542635c9f"),
+                2,
+                DepType::Code,
+            ),
+            (
+                ScriptRegistry::ACP,
+                h256!("0x3419a1c09eb2567f6552ee7a8ecffd64155cffe0f1796e6e61ec088d740c1356"),
+                ScriptHashType::Type,
+                h256!("0xec26b0f85ed839ece5f11c4c4e837ec359f5adc4420410f6453b1f6b60fb96a6"),
+                0,
+                DepType::DepGroup,
+            ),
+            (
+                ScriptRegistry::CHEQUE,
+                h256!("0x60d5f39efce409c587cb9ea359cefdead650ca128f0bd9cb3855348f98c70d5b"),
+                ScriptHashType::Type,
+                h256!("0x7f96858be0a9d584b4a9ea190e0420835156a6010a5fde15ffcdc9d9c721ccab"),
+                0,
+                DepType::DepGroup,
+            ),
+            (
+                ScriptRegistry::SUDT,
+                h256!("0xc5e5dcf215925f7ef4dfaf5f4b4f105bc321c02776d6e7d52a1db3fcd9d011a4"),
+                ScriptHashType::Type,
+                h256!("0xe12877ebd2c3c364dc46c5c992bcfaf4fee33fa13eebdf82c591fc9825aab769"),
+                0,
+                DepType::Code,
+            ),
+            (
+                ScriptRegistry::XUDT,
+                h256!("0x25c29dc317811a6f6f3985a7a9ebc4838bd388d19d0feeecf0bcd60f6c0975bb"),
+                ScriptHashType::Type,
+                h256!("0xbf6fb538763efec2a70a6a3dcb7242787087e1030c4e7d86585bc63a9d337f5f"),
+                0,
+                DepType::Code,
+            ),
+            (
+                ScriptRegistry::OMNI_LOCK,
+                h256!("0xf329effd1c475a2978453c8600e1eaf0bc2087ee093c3ee64cc96ec6847752cb"),
+                ScriptHashType::Type,
+                h256!("0xec18bf0d857c981c3d1f4e17999b9b90c484b303378e94de1a57b0872f5d4602"),
+                0,
+                DepType::Code,
+            ),
+            (
+                ScriptRegistry::PW_LOCK,
+                h256!("0x58c5f491aba6d61678b7cf7edf4910b1f5e00ec0cde2f42e0abb4fd9aff25a63"),
+                ScriptHashType::Type,
+                h256!("0x57a62003daeab9d54aa29b944fc3b451213a5ebdf2e232216a3cfed0dde61b38"),
+                0,
+                DepType::Code,
+            ),
+        ];
+        for (name, code_hash, hash_type, tx_hash, index, dep_type) in cases {
+            assert_script(
+                &registry, name, code_hash, hash_type, tx_hash, index, dep_type,
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_genesis_block() {
+        let genesis_block: json_types::BlockView =
+            serde_json::from_str(TESTNET_GENESIS_JSON).unwrap();
+        let registry = ScriptRegistry::from_genesis_block(&genesis_block.into()).unwrap();
+        assert_eq!(registry.iter().count(), 3);
+        // the system scripts of the testnet genesis block
+        let testnet = ScriptRegistry::testnet();
+        for name in [
+            ScriptRegistry::SIGHASH,
+            ScriptRegistry::MULTISIG,
+            ScriptRegistry::DAO,
+        ] {
+            assert_eq!(registry.get(name), testnet.get(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_register_and_lookup() {
+        let mut registry = ScriptRegistry::testnet();
+        let sudt = registry.get(ScriptRegistry::SUDT).unwrap().clone();
+        let script = Script::new_builder()
+            .code_hash(sudt.script_id.code_hash.pack())
+            .hash_type(sudt.script_id.hash_type.to_packed())
+            .args(vec![1u8; 32].pack())
+            .build();
+        assert_eq!(registry.lookup_by_script(&script), Some(sudt.clone()));
+        assert_eq!(
+            registry.cell_dep(ScriptRegistry::SUDT),
+            Some(sudt.cell_dep.clone())
+        );
+        assert_eq!(registry.cell_dep("unknown"), None);
+        let resolver = DefaultCellDepResolver::from_registry(&registry);
+        assert_eq!(resolver.resolve(&script), Some(sudt.cell_dep.clone()));
+
+        let my_script_id = ScriptId::new_data1(h256!("0x1234"));
+        let my_dep = cell_dep(h256!("0x5678"), 1, DepType::Code);
+        assert_eq!(
+            registry.register("my_script", my_script_id.clone(), my_dep.clone()),
+            None
+        );
+        assert_eq!(registry.cell_dep("my_script"), Some(my_dep.clone()));
+        // the same name is replaced
+        let old = registry.register("my_script", my_script_id, sudt.cell_dep.clone());
+        assert_eq!(old.map(|item| item.cell_dep), Some(my_dep));
+        assert_eq!(registry.iter().count(), 9);
+
+        let invalid_script = script.as_builder().hash_type(Byte::new(3)).build();
+        assert_eq!(registry.lookup_by_script(&invalid_script), None);
+    }
+
+    #[test]
+    fn test_well_known_name() {
+        for registry in [ScriptRegistry::mainnet(), ScriptRegistry::testnet()] {
+            for script in registry.iter() {
+                assert_eq!(
+                    ScriptRegistry::well_known_name(&script.script_id),
+                    Some(script.name.as_str())
+                );
+            }
+        }
+        // the scripts registered later are not well known
+        assert_eq!(
+            ScriptRegistry::well_known_name(&ScriptId::new_data1(h256!("0x1234"))),
+            None
+        );
+    }
+}
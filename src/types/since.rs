@@ -1,7 +1,19 @@
 use ckb_types::core::EpochNumberWithFraction;
+use thiserror::Error;
 
 use crate::constants::{LOCK_TYPE_FLAG, METRIC_TYPE_FLAG_MASK, REMAIN_FLAGS_BITS, VALUE_MASK};
 
+/// The error of `Since::from_raw`, the raw value would be rejected by the
+/// consensus as an invalid since.
+#[derive(Error, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SinceParseError {
+    #[error("invalid since flags: `{0:#x}`")]
+    InvalidFlags(u64),
+
+    #[error("malformed since epoch fraction: `{0:#x}`")]
+    MalformedEpoch(u64),
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum SinceType {
     BlockNumber,
@@ -9,7 +21,12 @@ pub enum SinceType {
     Timestamp,
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+/// The `since` field of a cell input, see RFC 0017.
+///
+/// The block number and the timestamp are 56 bits values, the timestamp is in
+/// seconds (the median time of the node is in milliseconds). The zero value
+/// (the default) is no lock at all.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
 pub struct Since(u64);
 
 impl Since {
@@ -35,10 +52,61 @@ impl Since {
         )
     }
 
+    pub fn absolute_block(number: u64) -> Since {
+        Self::new(SinceType::BlockNumber, number, false)
+    }
+
+    pub fn relative_block(blocks: u64) -> Since {
+        Self::new(SinceType::BlockNumber, blocks, true)
+    }
+
+    pub fn absolute_epoch(epoch: EpochNumberWithFraction) -> Since {
+        Self::new(
+            SinceType::EpochNumberWithFraction,
+            epoch.full_value(),
+            false,
+        )
+    }
+
+    pub fn relative_epoch(epochs: EpochNumberWithFraction) -> Since {
+        Self::new(
+            SinceType::EpochNumberWithFraction,
+            epochs.full_value(),
+            true,
+        )
+    }
+
+    pub fn absolute_timestamp(unix_seconds: u64) -> Since {
+        Self::new(SinceType::Timestamp, unix_seconds, false)
+    }
+
+    pub fn relative_timestamp(seconds: u64) -> Since {
+        Self::new(SinceType::Timestamp, seconds, true)
+    }
+
+    /// The raw value is taken as it is, see `from_raw` for a checked one.
     pub fn from_raw_value(value: u64) -> Since {
         Since(value)
     }
 
+    /// Parse a raw since value, the flags must be valid and an epoch fraction
+    /// must be well formed (`index < length`, or both are zero which is the
+    /// same as `index = 0, length = 1`).
+    pub fn from_raw(value: u64) -> Result<Since, SinceParseError> {
+        let since = Since(value);
+        if !since.flags_is_valid() {
+            return Err(SinceParseError::InvalidFlags(value));
+        }
+        if let Some((SinceType::EpochNumberWithFraction, epoch)) = since.extract_metric() {
+            let epoch = EpochNumberWithFraction::from_full_value(epoch);
+            let is_zero_fraction = epoch.index() == 0 && epoch.length() == 0;
+            if !is_zero_fraction && epoch.index() >= epoch.length() {
+                return Err(SinceParseError::MalformedEpoch(value));
+            }
+        }
+        Ok(since)
+    }
+
     pub fn value(self) -> u64 {
         self.0
     }
@@ -69,4 +137,274 @@ impl Since {
         };
         ty_opt.map(|ty| (ty, value))
     }
+
+    /// Check an absolute since against the tip: the block number, the epoch
+    /// and the median time (in milliseconds) of the tip block.
+    ///
+    /// A relative since is never satisfied here, see `is_relative_satisfied_by`.
+    pub fn is_satisfied_by(
+        self,
+        tip_number: u64,
+        tip_epoch: EpochNumberWithFraction,
+        median_time: u64,
+    ) -> bool {
+        if self.is_relative() || Self::from_raw(self.0).is_err() {
+            return false;
+        }
+        match self.extract_metric() {
+            Some((SinceType::BlockNumber, number)) => tip_number >= number,
+            Some((SinceType::EpochNumberWithFraction, epoch)) => {
+                let since_epoch = epoch_fraction(EpochNumberWithFraction::from_full_value(epoch));
+                fraction_ge(epoch_fraction(tip_epoch), since_epoch)
+            }
+            Some((SinceType::Timestamp, seconds)) => median_time >= seconds.saturating_mul(1000),
+            None => false,
+        }
+    }
+
+    /// Check a since of an input committed at `committed_number`,
+    /// `committed_epoch` with `committed_median_time`, against the tip. The
+    /// median times are in milliseconds.
+    ///
+    /// An absolute since is checked by `is_satisfied_by`.
+    pub fn is_relative_satisfied_by(
+        self,
+        committed_number: u64,
+        committed_epoch: EpochNumberWithFraction,
+        committed_median_time: u64,
+        tip_number: u64,
+        tip_epoch: EpochNumberWithFraction,
+        median_time: u64,
+    ) -> bool {
+        if self.is_absolute() {
+            return self.is_satisfied_by(tip_number, tip_epoch, median_time);
+        }
+        if Self::from_raw(self.0).is_err() {
+            return false;
+        }
+        match self.extract_metric() {
+            Some((SinceType::BlockNumber, blocks)) => {
+                tip_number >= committed_number.saturating_add(blocks)
+            }
+            Some((SinceType::EpochNumberWithFraction, epochs)) => {
+                let (num0, den0) = epoch_fraction(committed_epoch);
+                let (num1, den1) = epoch_fraction(EpochNumberWithFraction::from_full_value(epochs));
+                let target = (num0 * den1 + num1 * den0, den0 * den1);
+                fraction_ge(epoch_fraction(tip_epoch), target)
+            }
+            Some((SinceType::Timestamp, seconds)) => {
+                median_time >= committed_median_time.saturating_add(seconds.saturating_mul(1000))
+            }
+            None => false,
+        }
+    }
+}
+
+/// The epoch as a fraction of epochs, a zero length is normalized to `0/1`.
+fn epoch_fraction(epoch: EpochNumberWithFraction) -> (u128, u128) {
+    let (index, length) = if epoch.length() == 0 {
+        (0, 1)
+    } else {
+        (epoch.index() as u128, epoch.length() as u128)
+    };
+    (epoch.number() as u128 * length + index, length)
+}
+
+fn fraction_ge(lhs: (u128, u128), rhs: (u128, u128)) -> bool {
+    lhs.0 * rhs.1 >= rhs.0 * lhs.1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn epoch(number: u64, index: u64, length: u64) -> EpochNumberWithFraction {
+        EpochNumberWithFraction::new_unchecked(number, index, length)
+    }
+
+    #[test]
+    fn test_since_round_trip() {
+        let epoch_max = epoch(0xff_ffff, 0xfffe, 0xffff);
+        for (since, ty, value, is_relative) in [
+            (Since::absolute_block(0), SinceType::BlockNumber, 0, false),
+            (
+                Since::absolute_block(VALUE_MASK),
+                SinceType::BlockNumber,
+                VALUE_MASK,
+                false,
+            ),
+            (
+                Since::relative_block(100),
+                SinceType::BlockNumber,
+                100,
+                true,
+            ),
+            (
+                Since::absolute_epoch(epoch(10, 1, 2)),
+                SinceType::EpochNumberWithFraction,
+                epoch(10, 1, 2).full_value(),
+                false,
+            ),
+            (
+                Since::relative_epoch(epoch_max),
+                SinceType::EpochNumberWithFraction,
+                epoch_max.full_value(),
+                true,
+            ),
+            (
+                Since::relative_epoch(epoch(6, 0, 0)),
+                SinceType::EpochNumberWithFraction,
+                epoch(6, 0, 0).full_value(),
+                true,
+            ),
+            (
+                Since::absolute_timestamp(1_700_000_000),
+                SinceType::Timestamp,
+                1_700_000_000,
+                false,
+            ),
+            (
+                Since::relative_timestamp(VALUE_MASK),
+                SinceType::Timestamp,
+                VALUE_MASK,
+                true,
+            ),
+        ] {
+            assert_eq!(Since::from_raw(since.value()), Ok(since));
+            assert_eq!(Since::new(ty, value, is_relative), since);
+            assert_eq!(since.extract_metric(), Some((ty, value)));
+            assert_eq!(since.is_relative(), is_relative);
+        }
+        assert_eq!(
+            Since::absolute_epoch(epoch(200, 0, 1)),
+            Since::new_absolute_epoch(200)
+        );
+        assert_eq!(Since::default().value(), 0);
+        assert_eq!(Since::relative_block(100).value(), 0x8000_0000_0000_0064);
+        assert_eq!(
+            Since::relative_epoch(epoch(6, 0, 1)).value(),
+            0xa000_0100_0000_0006
+        );
+        assert_eq!(
+            Since::absolute_timestamp(0x10).value(),
+            0x4000_0000_0000_0010
+        );
+    }
+
+    #[test]
+    fn test_since_from_raw_invalid() {
+        for bit in 56..61 {
+            let value = Since::relative_block(1).value() | (1 << bit);
+            assert_eq!(
+                Since::from_raw(value),
+                Err(SinceParseError::InvalidFlags(value))
+            );
+        }
+        for value in [0x6000_0000_0000_0000, 0xe000_0000_0000_0001] {
+            assert_eq!(
+                Since::from_raw(value),
+                Err(SinceParseError::InvalidFlags(value))
+            );
+        }
+
+        // the fraction is normalized only when both index and length are zero
+        for (fraction, is_valid) in [
+            (epoch(1, 0, 0), true),
+            (epoch(1, 0, 1), true),
+            (epoch(1, 4, 5), true),
+            (epoch(1, 1, 0), false),
+            (epoch(1, 5, 5), false),
+            (epoch(1, 6, 5), false),
+        ] {
+            for since in [
+                Since::absolute_epoch(fraction),
+                Since::relative_epoch(fraction),
+            ] {
+                let expected = if is_valid {
+                    Ok(since)
+                } else {
+                    Err(SinceParseError::MalformedEpoch(since.value()))
+                };
+                assert_eq!(Since::from_raw(since.value()), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_since_is_satisfied_by() {
+        let tip = |number: u64, tip_epoch: EpochNumberWithFraction, median_time: u64| {
+            move |since: Since| since.is_satisfied_by(number, tip_epoch, median_time)
+        };
+        let check = tip(100, epoch(10, 500, 1000), 2_000_000);
+        assert!(check(Since::default()));
+        assert!(check(Since::absolute_block(100)));
+        assert!(!check(Since::absolute_block(101)));
+        assert!(check(Since::absolute_epoch(epoch(10, 0, 0))));
+        assert!(check(Since::absolute_epoch(epoch(10, 1, 2))));
+        assert!(!check(Since::absolute_epoch(epoch(10, 501, 1000))));
+        assert!(!check(Since::absolute_epoch(epoch(11, 0, 0))));
+        assert!(check(Since::absolute_timestamp(2000)));
+        assert!(!check(Since::absolute_timestamp(2001)));
+        // relative or malformed since
+        assert!(!check(Since::relative_block(0)));
+        assert!(!check(Since::absolute_epoch(epoch(1, 1, 0))));
+        assert!(!check(Since::from_raw_value(0x0100_0000_0000_0000)));
+    }
+
+    #[test]
+    fn test_since_is_relative_satisfied_by() {
+        let committed_epoch = epoch(5, 1, 2);
+        let check = |since: Since, tip_number: u64, tip_epoch, median_time: u64| {
+            since.is_relative_satisfied_by(
+                100,
+                committed_epoch,
+                1_000_000,
+                tip_number,
+                tip_epoch,
+                median_time,
+            )
+        };
+        let tip_epoch = epoch(7, 0, 1);
+        assert!(check(Since::relative_block(10), 110, tip_epoch, 0));
+        assert!(!check(Since::relative_block(10), 109, tip_epoch, 0));
+        assert!(check(
+            Since::relative_epoch(epoch(1, 1, 2)),
+            0,
+            tip_epoch,
+            0
+        ));
+        assert!(!check(
+            Since::relative_epoch(epoch(1, 1, 2)),
+            0,
+            epoch(6, 999, 1000),
+            0
+        ));
+        // 5 1/2 + 1 = 6 1/2
+        assert!(check(
+            Since::relative_epoch(epoch(1, 0, 0)),
+            0,
+            epoch(6, 1, 2),
+            0
+        ));
+        assert!(!check(
+            Since::relative_epoch(epoch(1, 1, 0)),
+            0,
+            tip_epoch,
+            0
+        ));
+        assert!(check(
+            Since::relative_timestamp(10),
+            0,
+            tip_epoch,
+            1_010_000
+        ));
+        assert!(!check(
+            Since::relative_timestamp(10),
+            0,
+            tip_epoch,
+            1_009_999
+        ));
+        // an absolute since is checked against the tip only
+        assert!(check(Since::absolute_block(50), 50, tip_epoch, 0));
+    }
 }
@@ -0,0 +1,85 @@
+use ckb_types::{
+    bytes::{BufMut, Bytes, BytesMut},
+    packed::Script,
+    prelude::*,
+    H160,
+};
+use serde::{Deserialize, Serialize};
+
+use super::omni_lock::ConfigError;
+use crate::types::{Address, ScriptId};
+
+/// The anyone-can-pay lock args: 20 bytes blake160 of the public key, then
+/// the optional minimum ckb and minimum udt amount (as the exponents of 10).
+///
+/// `udt_minimum` can only be set with `ckb_minimum`, a `None` ckb minimum is
+/// written as 0 when the udt minimum is set.
+#[derive(Clone, Serialize, Deserialize, Debug, Hash, Eq, PartialEq)]
+pub struct AcpConfig {
+    pub lock_arg: H160,
+    pub ckb_minimum: Option<u8>,
+    pub udt_minimum: Option<u8>,
+}
+
+impl AcpConfig {
+    pub fn new(lock_arg: H160) -> Self {
+        AcpConfig {
+            lock_arg,
+            ckb_minimum: None,
+            udt_minimum: None,
+        }
+    }
+
+    pub fn new_with_minimum(lock_arg: H160, ckb_minimum: u8, udt_minimum: Option<u8>) -> Self {
+        AcpConfig {
+            lock_arg,
+            ckb_minimum: Some(ckb_minimum),
+            udt_minimum,
+        }
+    }
+
+    /// Build lock script arguments
+    pub fn build_args(&self) -> Bytes {
+        let mut bytes = BytesMut::with_capacity(22);
+        bytes.put(self.lock_arg.as_bytes());
+        if self.ckb_minimum.is_some() || self.udt_minimum.is_some() {
+            bytes.put_u8(self.ckb_minimum.unwrap_or(0));
+        }
+        if let Some(udt_minimum) = self.udt_minimum {
+            bytes.put_u8(udt_minimum);
+        }
+        bytes.freeze()
+    }
+
+    /// Parse the lock script args built by `build_args`.
+    pub fn from_args(args: &[u8]) -> Result<Self, ConfigError> {
+        if !(20..=22).contains(&args.len()) {
+            return Err(ConfigError::InvalidArgs(format!(
+                "expected 20 to 22 bytes, got: {}",
+                args.len()
+            )));
+        }
+        Ok(AcpConfig {
+            lock_arg: H160::from_slice(&args[0..20]).expect("lock arg"),
+            ckb_minimum: args.get(20).cloned(),
+            udt_minimum: args.get(21).cloned(),
+        })
+    }
+
+    /// Parse the config from an anyone-can-pay address, the address must be
+    /// of `expected_script_id`.
+    pub fn from_address(
+        address: &Address,
+        expected_script_id: &ScriptId,
+    ) -> Result<Self, ConfigError> {
+        let script = Script::from(address);
+        let script_id = ScriptId::from(&script);
+        if script_id != *expected_script_id {
+            return Err(ConfigError::ScriptIdMismatch(
+                expected_script_id.clone(),
+                script_id,
+            ));
+        }
+        Self::from_args(&script.args().raw_data())
+    }
+}
@@ -0,0 +1,286 @@
+//! The hashlock (HTLC style) lock script, see `test-data/hashlock.md` for the
+//! reference script.
+//!
+//! A hashlock cell can be:
+//!   * redeemed by the recipient with the preimage of the hash, before or after
+//!     the timeout
+//!   * refunded to the refund lock after the timeout
+//!
+//! In both cases the transaction must also have an input locked by the
+//! recipient (redeem) or the refund lock (refund), so the hashlock script does
+//! not need to verify any signature itself.
+use bytes::{BufMut, BytesMut};
+use ckb_hash::blake2b_256;
+use ckb_types::{
+    bytes::Bytes,
+    core::TransactionView,
+    packed::{Byte32, Script, WitnessArgs},
+    prelude::*,
+};
+
+use super::{update_witness_field, ScriptUnlocker, UnlockError, WitnessField};
+use crate::traits::TransactionDependencyProvider;
+use crate::types::{ScriptGroup, ScriptHashTypeExt, ScriptId, Since};
+
+/// The length of the hashlock script args
+pub const HASHLOCK_ARGS_LEN: usize = 104;
+
+/// The first byte of the witness lock field for redeem
+pub const HASHLOCK_REDEEM_ACTION: u8 = 0;
+/// The first byte of the witness lock field for refund
+pub const HASHLOCK_REFUND_ACTION: u8 = 1;
+
+/// The hashlock script args:
+///
+/// ```text
+/// <32 bytes blake2b_256(preimage)> <32 bytes recipient lock hash> <32 bytes refund lock hash> <8 bytes little endian timeout since>
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct HashlockArgs {
+    pub hash: [u8; 32],
+    pub recipient_lock_hash: Byte32,
+    pub refund_lock_hash: Byte32,
+    /// The since value of the hashlock input in a refund transaction
+    pub timeout_since: Since,
+}
+
+impl HashlockArgs {
+    pub fn new(
+        hash: [u8; 32],
+        recipient_lock: &Script,
+        refund_lock: &Script,
+        timeout_since: Since,
+    ) -> HashlockArgs {
+        HashlockArgs {
+            hash,
+            recipient_lock_hash: recipient_lock.calc_script_hash(),
+            refund_lock_hash: refund_lock.calc_script_hash(),
+            timeout_since,
+        }
+    }
+
+    pub fn parse(args: &[u8]) -> Result<HashlockArgs, String> {
+        if args.len() != HASHLOCK_ARGS_LEN {
+            return Err(format!(
+                "hashlock args must be {} bytes, got {} bytes",
+                HASHLOCK_ARGS_LEN,
+                args.len()
+            ));
+        }
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(&args[0..32]);
+        let mut since_bytes = [0u8; 8];
+        since_bytes.copy_from_slice(&args[96..104]);
+        Ok(HashlockArgs {
+            hash,
+            recipient_lock_hash: Byte32::from_slice(&args[32..64]).expect("32 bytes"),
+            refund_lock_hash: Byte32::from_slice(&args[64..96]).expect("32 bytes"),
+            timeout_since: Since::from_raw_value(u64::from_le_bytes(since_bytes)),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut args = BytesMut::with_capacity(HASHLOCK_ARGS_LEN);
+        args.put(&self.hash[..]);
+        args.put(self.recipient_lock_hash.as_slice());
+        args.put(self.refund_lock_hash.as_slice());
+        args.put(&self.timeout_since.value().to_le_bytes()[..]);
+        args.freeze()
+    }
+
+    /// Build the hashlock script of `script_id` with this args
+    pub fn build_script(&self, script_id: &ScriptId) -> Script {
+        Script::new_builder()
+            .code_hash(script_id.code_hash.pack())
+            .hash_type(script_id.hash_type.to_packed())
+            .args(self.to_bytes().pack())
+            .build()
+    }
+
+    /// Check if `preimage` is the preimage of the hash
+    pub fn is_preimage(&self, preimage: &[u8]) -> bool {
+        blake2b_256(preimage) == self.hash
+    }
+}
+
+/// The witness lock field layout of the hashlock script:
+///
+/// ```text
+/// redeem: <1 byte HASHLOCK_REDEEM_ACTION> <preimage>
+/// refund: <1 byte HASHLOCK_REFUND_ACTION>
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum HashlockWitness {
+    Redeem(Bytes),
+    Refund,
+}
+
+impl HashlockWitness {
+    pub fn parse(lock_field: &[u8]) -> Result<HashlockWitness, String> {
+        match lock_field.split_first() {
+            Some((&HASHLOCK_REDEEM_ACTION, preimage)) => {
+                Ok(HashlockWitness::Redeem(Bytes::from(preimage.to_vec())))
+            }
+            Some((&HASHLOCK_REFUND_ACTION, rest)) if rest.is_empty() => Ok(HashlockWitness::Refund),
+            _ => Err(format!(
+                "invalid hashlock witness lock field, length: {}",
+                lock_field.len()
+            )),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        match self {
+            HashlockWitness::Redeem(preimage) => {
+                let mut data = BytesMut::with_capacity(1 + preimage.len());
+                data.put_u8(HASHLOCK_REDEEM_ACTION);
+                data.put(preimage.as_ref());
+                data.freeze()
+            }
+            HashlockWitness::Refund => Bytes::from(vec![HASHLOCK_REFUND_ACTION]),
+        }
+    }
+
+    /// The size of the witness lock field, used to size the placeholder
+    pub fn lock_size(&self) -> usize {
+        match self {
+            HashlockWitness::Redeem(preimage) => 1 + preimage.len(),
+            HashlockWitness::Refund => 1,
+        }
+    }
+
+    /// Parse the lock field of a serialized `WitnessArgs`, `None` if the
+    /// witness or the lock field is empty.
+    ///
+    /// The counterparty of a swap reads the preimage from the redeem
+    /// transaction this way.
+    pub fn from_witness(witness: &[u8]) -> Result<Option<HashlockWitness>, String> {
+        if witness.is_empty() {
+            return Ok(None);
+        }
+        let witness_args = WitnessArgs::from_slice(witness)
+            .map_err(|err| format!("invalid witness args: {}", err))?;
+        match witness_args.lock().to_opt() {
+            Some(lock_field) => HashlockWitness::parse(&lock_field.raw_data()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Check if the witness unlocks the script group in `tx`, the input of the
+    /// recipient or the refund lock is not checked.
+    pub fn is_valid(
+        &self,
+        args: &HashlockArgs,
+        tx: &TransactionView,
+        script_group: &ScriptGroup,
+    ) -> bool {
+        match self {
+            HashlockWitness::Redeem(preimage) => args.is_preimage(preimage),
+            HashlockWitness::Refund => script_group.input_indices.iter().all(|idx| {
+                tx.inputs()
+                    .get(*idx)
+                    .map(|input| {
+                        let since: u64 = input.since().unpack();
+                        since == args.timeout_since.value()
+                    })
+                    .unwrap_or(false)
+            }),
+        }
+    }
+}
+
+/// Unlock the hashlock cells by the known preimages, or by refund when the
+/// since of the hashlock inputs is the timeout since.
+///
+/// Register it with the `ScriptId` of the hashlock script:
+///
+/// ```ignore
+/// cell_dep_resolver.insert(hashlock_script_id.clone(), hashlock_cell_dep, "hashlock".to_string());
+/// unlockers.insert(hashlock_script_id, Box::new(HashlockUnlocker::new(vec![preimage])));
+/// ```
+#[derive(Default, Clone)]
+pub struct HashlockUnlocker {
+    preimages: Vec<Bytes>,
+}
+
+impl HashlockUnlocker {
+    pub fn new(preimages: Vec<Bytes>) -> HashlockUnlocker {
+        HashlockUnlocker { preimages }
+    }
+
+    pub fn add_preimage(&mut self, preimage: Bytes) {
+        self.preimages.push(preimage);
+    }
+
+    /// The witness to unlock the script group: redeem if the preimage is
+    /// known, otherwise refund.
+    pub fn witness_for(&self, args: &HashlockArgs) -> HashlockWitness {
+        self.preimages
+            .iter()
+            .find(|preimage| args.is_preimage(preimage))
+            .map(|preimage| HashlockWitness::Redeem(preimage.clone()))
+            .unwrap_or(HashlockWitness::Refund)
+    }
+
+    fn parse_args(script_group: &ScriptGroup) -> Result<HashlockArgs, UnlockError> {
+        HashlockArgs::parse(&script_group.script.args().raw_data())
+            .map_err(|err| UnlockError::Other(anyhow::anyhow!(err)))
+    }
+}
+
+impl ScriptUnlocker for HashlockUnlocker {
+    fn match_args(&self, args: &[u8]) -> bool {
+        HashlockArgs::parse(args).is_ok()
+    }
+
+    fn is_unlocked(
+        &self,
+        tx: &TransactionView,
+        script_group: &ScriptGroup,
+        _tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<bool, UnlockError> {
+        let args = Self::parse_args(script_group)?;
+        let witness_data = tx
+            .witnesses()
+            .get(script_group.input_indices[0])
+            .map(|data| data.raw_data())
+            .unwrap_or_default();
+        // the placeholder or a malformed witness is not unlocked
+        Ok(match HashlockWitness::from_witness(&witness_data) {
+            Ok(Some(witness)) => witness.is_valid(&args, tx, script_group),
+            _ => false,
+        })
+    }
+
+    fn unlock(
+        &self,
+        tx: &TransactionView,
+        script_group: &ScriptGroup,
+        _tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, UnlockError> {
+        let args = Self::parse_args(script_group)?;
+        let witness = self.witness_for(&args);
+        if !witness.is_valid(&args, tx, script_group) {
+            return Err(UnlockError::Other(anyhow::anyhow!(
+                "the preimage is unknown and the since of the hashlock inputs is not the timeout since"
+            )));
+        }
+        Ok(update_witness_field(
+            tx,
+            script_group.input_indices[0],
+            WitnessField::Lock,
+            witness.to_bytes(),
+        )?)
+    }
+
+    fn fill_placeholder_witness(
+        &self,
+        tx: &TransactionView,
+        script_group: &ScriptGroup,
+        _tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, UnlockError> {
+        let args = Self::parse_args(script_group)?;
+        let lock_size = self.witness_for(&args).lock_size();
+        super::fill_witness_lock(tx, script_group, Bytes::from(vec![0u8; lock_size]))
+    }
+}
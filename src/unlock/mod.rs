@@ -1,16 +1,37 @@
-pub(crate) mod omni_lock;
+mod acp;
+pub mod hashlock;
+pub mod omni_lock;
 pub mod rc_data;
+mod sighash_message;
 mod signer;
+mod signing_package;
+mod static_witness;
 mod unlocker;
 
+pub use sighash_message::{
+    attach_signature, compute_sighash_messages, verify_signature_against_message, GroupMessage,
+    SignAlgorithm, WitnessLayout,
+};
 pub use signer::{
-    generate_message, AcpScriptSigner, ChequeAction, ChequeScriptSigner, MultisigConfig,
-    OmniLockScriptSigner, OmniUnlockMode, ScriptSignError, ScriptSigner, SecpMultisigScriptSigner,
-    SecpSighashScriptSigner,
+    generate_keccak256_message, generate_message, generate_message_with_params,
+    multisig_args_since, AcpScriptSigner, AuthWitnessProvider, ChequeAction, ChequeScriptSigner,
+    MultisigConfig, OmniLockScriptSigner, OmniUnlockMode, PwLockScriptSigner, ScriptSignError,
+    ScriptSigner, SecpMultisigScriptSigner, SecpSighashScriptSigner,
+};
+pub(crate) use signer::{load_witness_args, update_witness_field, WitnessField};
+pub use signing_package::{
+    GroupStatus, LockDescriptor, SignatureStatus, SigningDescriptor, SigningInput, SigningPackage,
+    SigningPackageError, SIGNING_PACKAGE_VERSION,
 };
+pub use static_witness::{StaticWitnessUnlocker, WitnessBuilderFn};
 pub use unlocker::{
-    fill_witness_lock, reset_witness_lock, AcpUnlocker, ChequeUnlocker, OmniLockUnlocker,
-    ScriptUnlocker, SecpMultisigUnlocker, SecpSighashUnlocker, UnlockError,
+    build_unlockers, fill_witness_lock, reset_witness_lock, AcpUnlocker, ChequeUnlocker,
+    OmniLockUnlocker, PwLockUnlocker, RegistryError, ScriptUnlocker, ScriptUnlockerManager,
+    SecpMultisigUnlocker, SecpSighashUnlocker, UnlockError, UnlockerProvider,
 };
 
-pub use omni_lock::{IdentityFlag, InfoCellData, OmniLockAcpConfig, OmniLockConfig};
+pub use acp::AcpConfig;
+pub use hashlock::HashlockUnlocker;
+#[cfg(feature = "rsa")]
+pub use omni_lock::rsa_auth::{RsaAlgorithm, RsaPubkey, RsaSigner};
+pub use omni_lock::{ExecDlConfig, IdentityFlag, InfoCellData, OmniLockAcpConfig, OmniLockConfig};
@@ -6,11 +6,15 @@ use crate::{
     types::{
         omni_lock::{Auth, Identity as IdentityType, IdentityOpt, OmniLockWitnessLock},
         xudt_rce_mol::SmtProofEntryVec,
+        Address, ScriptHashTypeExt, ScriptId, Since,
     },
+    unlock::rc_data::proof_entry_vec_size,
+    util::blake160,
 };
 use ckb_types::{
     bytes::{BufMut, Bytes, BytesMut},
-    packed::WitnessArgs,
+    core::ScriptHashType,
+    packed::{Script, WitnessArgs},
     prelude::*,
     H160, H256,
 };
@@ -25,6 +29,10 @@ use bitflags::bitflags;
 use super::{MultisigConfig, OmniUnlockMode};
 use thiserror::Error;
 
+pub mod rce;
+#[cfg(feature = "rsa")]
+pub mod rsa_auth;
+
 #[derive(
     Clone,
     Copy,
@@ -304,6 +312,10 @@ pub struct AdminConfig {
     multisig_config: Option<MultisigConfig>,
     /// If set the rce cell in the input
     rce_in_input: bool,
+    /// The smt proofs of the script groups by the lock script args, the
+    /// `proofs` is used if the args is not here.
+    #[serde(default)]
+    args_proofs: Vec<(Bytes, SmtProofEntryVec)>,
 }
 
 impl AdminConfig {
@@ -320,6 +332,30 @@ impl AdminConfig {
         &self.proofs
     }
 
+    /// Set the smt proofs for the script group with lock script `args`, when
+    /// the transaction has omni-lock inputs of different identities.
+    pub fn set_proofs_for_args(&mut self, args: Bytes, proofs: SmtProofEntryVec) {
+        if let Some(entry) = self
+            .args_proofs
+            .iter_mut()
+            .find(|(entry_args, _)| entry_args == &args)
+        {
+            entry.1 = proofs;
+        } else {
+            self.args_proofs.push((args, proofs));
+        }
+    }
+
+    /// The smt proofs for the script group with lock script `args`, fall back
+    /// to `proofs` if not set by `set_proofs_for_args`.
+    pub fn proofs_for_args(&self, args: &[u8]) -> &SmtProofEntryVec {
+        self.args_proofs
+            .iter()
+            .find(|(entry_args, _)| entry_args.as_ref() == args)
+            .map(|(_, proofs)| proofs)
+            .unwrap_or(&self.proofs)
+    }
+
     /// set the additional auth, it will be used to sign the transaction.
     pub fn set_auth(&mut self, auth: Identity) {
         self.auth = auth;
@@ -358,18 +394,109 @@ impl AdminConfig {
             auth,
             multisig_config,
             rce_in_input,
+            args_proofs: Vec::new(),
         }
     }
 }
 
+/// The auth delegated to another script by exec or dynamic linking, see
+/// `IdentityFlag::Exec` and `IdentityFlag::Dl`.
+///
+/// The auth content of the identity is the blake160 hash of the preimage:
+/// 1. 32 bytes code hash of the auth script
+/// 2. 1 byte hash type of the auth script
+/// 3. 1 byte place, 0 for cell dep, 1 for witness
+/// 4. 8 bytes bounds of the code in the place, little endian
+/// 5. 20 bytes pubkey hash passed to the auth script
+///
+/// The preimage is put into the witness lock along with the signature, which
+/// is whatever the auth script requires, see `AuthWitnessProvider`.
+#[derive(Clone, Serialize, Deserialize, Debug, Hash, Eq, PartialEq)]
+pub struct ExecDlConfig {
+    preimage: Bytes,
+    /// The length of the signature, the placeholder witness is sized by it
+    sig_len: usize,
+}
+
+impl ExecDlConfig {
+    /// The length of the preimage
+    pub const PREIMAGE_LEN: usize = 62;
+
+    /// Create the config with a 65 bytes signature, set `sig_len` if the
+    /// auth script requires another length.
+    pub fn new(
+        code_hash: &H256,
+        hash_type: ScriptHashType,
+        place: u8,
+        bounds: u64,
+        pubkey_hash: &H160,
+    ) -> Self {
+        let mut preimage = BytesMut::with_capacity(Self::PREIMAGE_LEN);
+        preimage.put(code_hash.as_bytes());
+        preimage.put_u8(hash_type.to_byte());
+        preimage.put_u8(place);
+        preimage.put_u64_le(bounds);
+        preimage.put(pubkey_hash.as_bytes());
+        ExecDlConfig {
+            preimage: preimage.freeze(),
+            sig_len: 65,
+        }
+    }
+
+    pub fn preimage(&self) -> &Bytes {
+        &self.preimage
+    }
+
+    /// The auth script to delegate to.
+    pub fn script_id(&self) -> ScriptId {
+        let code_hash = H256::from_slice(&self.preimage[0..32]).expect("code hash");
+        let hash_type = ScriptHashType::from_byte(self.preimage[32]).expect("hash type");
+        ScriptId::new(code_hash, hash_type)
+    }
+
+    /// Where the code of the auth script is, 0 for cell dep, 1 for witness
+    pub fn place(&self) -> u8 {
+        self.preimage[33]
+    }
+
+    pub fn pubkey_hash(&self) -> H160 {
+        H160::from_slice(&self.preimage[42..62]).expect("pubkey hash")
+    }
+
+    /// The auth content of the identity, the blake160 hash of the preimage.
+    pub fn auth_content(&self) -> H160 {
+        blake160(&self.preimage)
+    }
+
+    pub fn sig_len(&self) -> usize {
+        self.sig_len
+    }
+
+    pub fn set_sig_len(&mut self, sig_len: usize) {
+        self.sig_len = sig_len;
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum ConfigError {
     #[error("there is no admin configuration in the OmniLockConfig")]
     NoAdminConfig,
 
+    #[error("there is no exec or dl auth configuration in the OmniLockConfig")]
+    NoExecDlConfig,
+
     #[error("there is no multisig config in the OmniLockConfig")]
     NoMultiSigConfig,
 
+    #[error("invalid lock script args: `{0}`")]
+    InvalidArgs(String),
+
+    #[error("unexpected script, expected: `{0}`, got: `{1}`")]
+    ScriptIdMismatch(ScriptId, ScriptId),
+
+    #[error("invalid RSA public key: `{0}`")]
+    InvalidRsaKey(String),
+
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -415,6 +542,9 @@ pub struct OmniLockConfig {
     time_lock_config: Option<u64>,
     // 32 bytes type script hash
     info_cell: Option<H256>,
+    /// The exec or dl auth, it is not in the lock script args
+    #[serde(default)]
+    exec_dl_config: Option<ExecDlConfig>,
 }
 
 impl OmniLockConfig {
@@ -438,6 +568,7 @@ impl OmniLockConfig {
             acp_config: None,
             time_lock_config: None,
             info_cell: None,
+            exec_dl_config: None,
         }
     }
     /// Create an ethereum algorithm omnilock with pubkey
@@ -467,12 +598,76 @@ impl OmniLockConfig {
         Self::new(IdentityFlag::OwnerLock, script_hash)
     }
 
+    /// Create an omnilock delegating the signature verification to the auth
+    /// script `code_hash` by exec, the identity is the blake160 hash of the
+    /// preimage, see `ExecDlConfig`.
+    pub fn new_exec_auth(
+        code_hash: H256,
+        hash_type: ScriptHashType,
+        place: u8,
+        bounds: u64,
+        pubkey_hash: H160,
+    ) -> Self {
+        let exec_dl_config = ExecDlConfig::new(&code_hash, hash_type, place, bounds, &pubkey_hash);
+        Self::new_with_exec_dl_config(IdentityFlag::Exec, exec_dl_config)
+    }
+
+    /// Same as `new_exec_auth`, the auth script is dynamically linked.
+    pub fn new_dl_auth(
+        code_hash: H256,
+        hash_type: ScriptHashType,
+        place: u8,
+        bounds: u64,
+        pubkey_hash: H160,
+    ) -> Self {
+        let exec_dl_config = ExecDlConfig::new(&code_hash, hash_type, place, bounds, &pubkey_hash);
+        Self::new_with_exec_dl_config(IdentityFlag::Dl, exec_dl_config)
+    }
+
+    /// Create an omnilock of a RSA public key (DER encoded SubjectPublicKeyInfo
+    /// or PKCS#1), the signature is verified by the ckb-auth script
+    /// `auth_script` in a cell dep by exec or dl (`flag`), see `rsa_auth`.
+    ///
+    /// The placeholder witness is sized by the key size.
+    #[cfg(feature = "rsa")]
+    pub fn new_rsa(
+        pubkey_der: Bytes,
+        algorithm: rsa_auth::RsaAlgorithm,
+        flag: IdentityFlag,
+        auth_script: &ScriptId,
+    ) -> Result<Self, ConfigError> {
+        if flag != IdentityFlag::Exec && flag != IdentityFlag::Dl {
+            return Err(ConfigError::Other(anyhow::anyhow!(
+                "the RSA identity is verified by exec or dl, got identity flag {:?}",
+                flag
+            )));
+        }
+        let pubkey = rsa_auth::RsaPubkey::from_der(&pubkey_der, algorithm)?;
+        let mut exec_dl_config = ExecDlConfig::new(
+            &auth_script.code_hash,
+            auth_script.hash_type,
+            0,
+            0,
+            &pubkey.pubkey_hash(),
+        );
+        exec_dl_config.set_sig_len(pubkey.rsa_info_len());
+        Ok(Self::new_with_exec_dl_config(flag, exec_dl_config))
+    }
+
+    fn new_with_exec_dl_config(flag: IdentityFlag, exec_dl_config: ExecDlConfig) -> Self {
+        let mut config = Self::new(flag, exec_dl_config.auth_content());
+        config.exec_dl_config = Some(exec_dl_config);
+        config
+    }
+
     /// Create a new OmniLockConfig
     pub fn new(flag: IdentityFlag, auth_content: H160) -> Self {
         let auth_content = match flag {
-            IdentityFlag::PubkeyHash | IdentityFlag::Ethereum | IdentityFlag::OwnerLock => {
-                auth_content
-            }
+            IdentityFlag::PubkeyHash
+            | IdentityFlag::Ethereum
+            | IdentityFlag::OwnerLock
+            | IdentityFlag::Exec
+            | IdentityFlag::Dl => auth_content,
             _ => H160::from_slice(&[0; 20]).unwrap(),
         };
 
@@ -484,6 +679,7 @@ impl OmniLockConfig {
             acp_config: None,
             time_lock_config: None,
             info_cell: None,
+            exec_dl_config: None,
         }
     }
 
@@ -512,10 +708,10 @@ impl OmniLockConfig {
         self.omni_lock_flags.set(OmniLockFlags::ACP, false);
         self.acp_config = None;
     }
-    /// Set the time lock config with raw since value, and set the OmniLockFlags::TIME_LOCK flag.
-    pub fn set_time_lock_config(&mut self, since: u64) {
+    /// Set the time lock config with since value, and set the OmniLockFlags::TIME_LOCK flag.
+    pub fn set_time_lock_config(&mut self, since: Since) {
         self.omni_lock_flags.set(OmniLockFlags::TIME_LOCK, true);
-        self.time_lock_config = Some(since);
+        self.time_lock_config = Some(since.value());
     }
     /// Remove the time lock config, set it to None, and clear the OmniLockFlags::TIME_LOCK flag.
     pub fn clear_time_lock_config(&mut self) {
@@ -579,6 +775,82 @@ impl OmniLockConfig {
         bytes.freeze()
     }
 
+    /// Parse the lock script args built by `build_args`.
+    ///
+    /// The multisig config of a multisig identity and the smt proofs of the
+    /// administrator mode are not in the args, set them after parsing.
+    pub fn from_args(args: &[u8]) -> Result<Self, ConfigError> {
+        if args.len() < 22 {
+            return Err(ConfigError::InvalidArgs(format!(
+                "expected at least 22 bytes, got: {}",
+                args.len()
+            )));
+        }
+        let id = Identity::from_slice(&args[0..21]).map_err(ConfigError::InvalidArgs)?;
+        let omni_lock_flags = OmniLockFlags::from_bits(args[21]).ok_or_else(|| {
+            ConfigError::InvalidArgs(format!("unknown omni-lock flags: {:#04x}", args[21]))
+        })?;
+        let mut config = OmniLockConfig {
+            id,
+            multisig_config: None,
+            omni_lock_flags,
+            admin_config: None,
+            acp_config: None,
+            time_lock_config: None,
+            info_cell: None,
+            exec_dl_config: None,
+        };
+        let expected_len = config.get_args_len();
+        if args.len() != expected_len {
+            return Err(ConfigError::InvalidArgs(format!(
+                "expected {} bytes for flags {:#04x}, got: {}",
+                expected_len,
+                args[21],
+                args.len()
+            )));
+        }
+        let mut offset = 22;
+        if omni_lock_flags.contains(OmniLockFlags::ADMIN) {
+            config.admin_config = Some(AdminConfig {
+                rc_type_id: H256::from_slice(&args[offset..offset + 32]).expect("rc type id"),
+                ..Default::default()
+            });
+            offset += 32;
+        }
+        if omni_lock_flags.contains(OmniLockFlags::ACP) {
+            config.acp_config = Some(OmniLockAcpConfig::new(args[offset], args[offset + 1]));
+            offset += 2;
+        }
+        if omni_lock_flags.contains(OmniLockFlags::TIME_LOCK) {
+            let mut since_bytes = [0u8; 8];
+            since_bytes.copy_from_slice(&args[offset..offset + 8]);
+            config.time_lock_config = Some(u64::from_le_bytes(since_bytes));
+            offset += 8;
+        }
+        if omni_lock_flags.contains(OmniLockFlags::SUPPLY) {
+            config.info_cell =
+                Some(H256::from_slice(&args[offset..offset + 32]).expect("info cell"));
+        }
+        Ok(config)
+    }
+
+    /// Parse the config from an omni-lock address, the address must be of
+    /// `expected_script_id`. See `from_args`.
+    pub fn from_address(
+        address: &Address,
+        expected_script_id: &ScriptId,
+    ) -> Result<Self, ConfigError> {
+        let script = Script::from(address);
+        let script_id = ScriptId::from(&script);
+        if script_id != *expected_script_id {
+            return Err(ConfigError::ScriptIdMismatch(
+                expected_script_id.clone(),
+                script_id,
+            ));
+        }
+        Self::from_args(&script.args().raw_data())
+    }
+
     /// return the internal reference of admin_config
     pub fn get_admin_config(&self) -> Option<&AdminConfig> {
         self.admin_config.as_ref()
@@ -588,6 +860,17 @@ impl OmniLockConfig {
         self.info_cell.as_ref()
     }
 
+    /// return the exec or dl auth configuration
+    pub fn get_exec_dl_config(&self) -> Option<&ExecDlConfig> {
+        self.exec_dl_config.as_ref()
+    }
+
+    /// Set the exec or dl auth configuration, e.g. the preimage after parsing
+    /// the config by `from_args`.
+    pub fn set_exec_dl_config(&mut self, exec_dl_config: ExecDlConfig) {
+        self.exec_dl_config = Some(exec_dl_config);
+    }
+
     /// Calculate script args length
     pub fn get_args_len(&self) -> usize {
         let mut len = 22;
@@ -618,7 +901,7 @@ impl OmniLockConfig {
             }
             SinceSource::LockArgs(offset)
         } else {
-            SinceSource::Value(0)
+            SinceSource::Value(Since::default())
         }
     }
 
@@ -642,27 +925,95 @@ impl OmniLockConfig {
         self.id.flag == IdentityFlag::OwnerLock
     }
 
+    /// Check if the auth is delegated by exec or dl.
+    pub fn is_exec_dl(&self) -> bool {
+        matches!(self.id.flag, IdentityFlag::Exec | IdentityFlag::Dl)
+    }
+
     pub fn placeholder_witness_lock(
         &self,
         unlock_mode: OmniUnlockMode,
     ) -> Result<Bytes, ConfigError> {
-        let mut builder = match self.id.flag {
+        self.build_placeholder_witness_lock(unlock_mode, None)
+    }
+
+    /// Same as `placeholder_witness_lock`, in administrator mode the proofs
+    /// for the lock script `args` are used, see `AdminConfig::set_proofs_for_args`.
+    pub fn placeholder_witness_lock_for_args(
+        &self,
+        unlock_mode: OmniUnlockMode,
+        args: &[u8],
+    ) -> Result<Bytes, ConfigError> {
+        self.build_placeholder_witness_lock(unlock_mode, Some(args))
+    }
+
+    fn build_placeholder_witness_lock(
+        &self,
+        unlock_mode: OmniUnlockMode,
+        args: Option<&[u8]>,
+    ) -> Result<Bytes, ConfigError> {
+        let proofs = match (unlock_mode, self.admin_config.as_ref()) {
+            (OmniUnlockMode::Admin, Some(config)) => match args {
+                Some(args) => config.proofs_for_args(args).clone(),
+                None => config.proofs.clone(),
+            },
+            _ => SmtProofEntryVec::default(),
+        };
+        self.build_witness_lock_with_proofs(unlock_mode, proofs)
+    }
+
+    /// The length of the placeholder witness lock, in administrator mode the
+    /// proofs are sized by `rc_data::proof_entry_vec_size`.
+    fn placeholder_witness_lock_len(
+        &self,
+        unlock_mode: OmniUnlockMode,
+        args: Option<&[u8]>,
+    ) -> Result<usize, ConfigError> {
+        let len = self
+            .build_witness_lock_with_proofs(unlock_mode, SmtProofEntryVec::default())?
+            .len();
+        match (unlock_mode, self.admin_config.as_ref()) {
+            (OmniUnlockMode::Admin, Some(config)) => {
+                let proofs = match args {
+                    Some(args) => config.proofs_for_args(args),
+                    None => &config.proofs,
+                };
+                let proof_sizes = proofs
+                    .clone()
+                    .into_iter()
+                    .map(|entry| entry.proof().len())
+                    .collect::<Vec<_>>();
+                Ok(len - proof_entry_vec_size(&[]) + proof_entry_vec_size(&proof_sizes))
+            }
+            _ => Ok(len),
+        }
+    }
+
+    fn build_witness_lock_with_proofs(
+        &self,
+        unlock_mode: OmniUnlockMode,
+        proofs: SmtProofEntryVec,
+    ) -> Result<Bytes, ConfigError> {
+        // In administrator mode the transaction is signed by the auth of the
+        // admin config, not the identity in the args.
+        let (flag, multisig_config) = match unlock_mode {
+            OmniUnlockMode::Admin => {
+                let admin_config = self
+                    .admin_config
+                    .as_ref()
+                    .ok_or(ConfigError::NoAdminConfig)?;
+                (
+                    admin_config.auth.flag,
+                    admin_config.multisig_config.as_ref(),
+                )
+            }
+            OmniUnlockMode::Normal => (self.id.flag, self.multisig_config.as_ref()),
+        };
+        let mut builder = match flag {
             IdentityFlag::PubkeyHash | IdentityFlag::Ethereum => OmniLockWitnessLock::new_builder()
                 .signature(Some(Bytes::from(vec![0u8; 65])).pack()),
             IdentityFlag::Multisig => {
-                let multisig_config = match unlock_mode {
-                    OmniUnlockMode::Admin => self
-                        .admin_config
-                        .as_ref()
-                        .ok_or(ConfigError::NoAdminConfig)?
-                        .multisig_config
-                        .as_ref()
-                        .ok_or(ConfigError::NoMultiSigConfig)?,
-                    OmniUnlockMode::Normal => self
-                        .multisig_config
-                        .as_ref()
-                        .ok_or(ConfigError::NoMultiSigConfig)?,
-                };
+                let multisig_config = multisig_config.ok_or(ConfigError::NoMultiSigConfig)?;
                 let config_data = multisig_config.to_witness_data();
                 let multisig_len = config_data.len() + multisig_config.threshold() as usize * 65;
                 let mut omni_sig = vec![0u8; multisig_len];
@@ -670,7 +1021,21 @@ impl OmniLockConfig {
                 OmniLockWitnessLock::new_builder().signature(Some(Bytes::from(omni_sig)).pack())
             }
             IdentityFlag::OwnerLock => OmniLockWitnessLock::new_builder(),
-            _ => todo!("to support other placeholder_witness_lock implementions"),
+            IdentityFlag::Exec | IdentityFlag::Dl => {
+                let exec_dl_config = self
+                    .exec_dl_config
+                    .as_ref()
+                    .ok_or(ConfigError::NoExecDlConfig)?;
+                OmniLockWitnessLock::new_builder()
+                    .signature(Some(Bytes::from(vec![0u8; exec_dl_config.sig_len])).pack())
+                    .preimage(Some(exec_dl_config.preimage.clone()).pack())
+            }
+            _ => {
+                return Err(ConfigError::Other(anyhow::anyhow!(
+                    "placeholder witness of identity flag {:?} is not supported",
+                    flag
+                )))
+            }
         };
 
         if unlock_mode == OmniUnlockMode::Admin {
@@ -681,7 +1046,7 @@ impl OmniLockConfig {
                 let auth = Auth::from_slice(&temp).unwrap();
                 let ident = IdentityType::new_builder()
                     .identity(auth)
-                    .proofs(config.proofs.clone())
+                    .proofs(proofs)
                     .build();
 
                 let ident_opt = IdentityOpt::new_builder().set(Some(ident)).build();
@@ -695,29 +1060,32 @@ impl OmniLockConfig {
 
     /// Build zero lock content for signature
     pub fn zero_lock(&self, unlock_mode: OmniUnlockMode) -> Result<Bytes, ConfigError> {
-        let len = self.placeholder_witness_lock(unlock_mode)?.len();
+        let len = self.placeholder_witness_lock_len(unlock_mode, None)?;
         Ok(Bytes::from(vec![0u8; len]))
     }
 
-    /// Create a zero lock witness placeholder
+    /// Build zero lock content for signature of the script group with lock script `args`
+    pub fn zero_lock_for_args(
+        &self,
+        unlock_mode: OmniUnlockMode,
+        args: &[u8],
+    ) -> Result<Bytes, ConfigError> {
+        let len = self.placeholder_witness_lock_len(unlock_mode, Some(args))?;
+        Ok(Bytes::from(vec![0u8; len]))
+    }
+
+    /// Create a zero lock witness placeholder, its size is the same as the
+    /// signed witness, so the fee is estimated correctly when balancing.
     pub fn placeholder_witness(
         &self,
         unlock_mode: OmniUnlockMode,
     ) -> Result<WitnessArgs, ConfigError> {
         match self.id.flag {
-            IdentityFlag::PubkeyHash | IdentityFlag::Ethereum | IdentityFlag::Multisig => {
+            IdentityFlag::OwnerLock if self.admin_config.is_none() => Ok(WitnessArgs::default()),
+            _ => {
                 let lock = self.placeholder_witness_lock(unlock_mode)?;
                 Ok(WitnessArgs::new_builder().lock(Some(lock).pack()).build())
             }
-            IdentityFlag::OwnerLock => {
-                if self.admin_config.is_some() {
-                    let lock = self.placeholder_witness_lock(unlock_mode)?;
-                    Ok(WitnessArgs::new_builder().lock(Some(lock).pack()).build())
-                } else {
-                    Ok(WitnessArgs::default())
-                }
-            }
-            _ => todo!("to support other placeholder_witness implementions"),
         }
     }
 }
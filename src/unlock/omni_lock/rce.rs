@@ -0,0 +1,140 @@
+use ckb_types::{bytes::Bytes, packed::Byte32, prelude::*, H256};
+use sparse_merkle_tree::H256 as SmtH256;
+
+use super::Identity;
+use crate::types::xudt_rce_mol::{RCCellVecBuilder, RCDataBuilder, RCDataUnion, SmtProofEntryVec};
+use crate::unlock::rc_data::{ListType, Mask, RcDataError, RcRuleDataBuilder, RcRuleVecBuilder};
+
+/// How the RC rules of the omni-lock administrator mode are built.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum RcScheme {
+    /// Two rules: a white list of the identities for the inputs, and a white
+    /// list without the proven identity for the outputs.
+    OnlyInputOnWhiteList,
+    /// One white list of the identities for both the inputs and the outputs.
+    BothOnWhiteList,
+    /// Same as `BothOnWhiteList`, but the rule is in emergency halt mode, so
+    /// the administrator mode is rejected.
+    EmergencyHalt,
+}
+
+fn smt_key(identity: &Identity) -> SmtH256 {
+    SmtH256::from(identity.to_smt_key())
+}
+
+/// Build the RC rules of `identities` and the proofs of `proven` for the
+/// administrator mode of omni-lock. `proven` is always on the list.
+///
+/// Returns the smt root of the list, the proofs for `AdminConfig::new` and the
+/// data of the RC rule cells (in the order of the proofs). After the RC rule
+/// cells are deployed, build the RC cell vec by `build_rc_cell_vec_data` with
+/// their type script hashes.
+pub fn generate_admin_proofs(
+    identities: &[Identity],
+    proven: &Identity,
+    scheme: RcScheme,
+) -> Result<(H256, SmtProofEntryVec, Vec<Bytes>), RcDataError> {
+    let proven_key = smt_key(proven);
+    let other_keys = identities
+        .iter()
+        .map(smt_key)
+        .filter(|key| key != &proven_key)
+        .collect::<Vec<_>>();
+
+    let is_emergency = scheme == RcScheme::EmergencyHalt;
+    let mut rule_builder = RcRuleDataBuilder::new(ListType::White, is_emergency);
+    rule_builder.update_hashes(&other_keys);
+    rule_builder.update_hashes(&[proven_key]);
+    let root = rule_builder.root();
+
+    let mut builder = RcRuleVecBuilder::new();
+    match scheme {
+        RcScheme::OnlyInputOnWhiteList => {
+            builder.build_proof_and_add_rule(&mut rule_builder, &[proven_key], Mask::Input)?;
+            let mut output_rule_builder = RcRuleDataBuilder::new(ListType::White, false);
+            output_rule_builder.update_hashes(&other_keys);
+            builder.build_proof_and_add_rule(
+                &mut output_rule_builder,
+                &[proven_key],
+                Mask::Output,
+            )?;
+        }
+        RcScheme::BothOnWhiteList | RcScheme::EmergencyHalt => {
+            builder.build_proof_and_add_rule(&mut rule_builder, &[proven_key], Mask::Both)?;
+        }
+    }
+    Ok((
+        H256::from(<[u8; 32]>::from(root)),
+        builder.build_proofs(),
+        builder.rc_rules().clone(),
+    ))
+}
+
+/// The data of the RC cell which refers to the RC rule cells by their type
+/// script hashes, its type script hash is the `rc_type_id` of `AdminConfig`.
+pub fn build_rc_cell_vec_data(rc_rule_type_hashes: &[Byte32]) -> Bytes {
+    let cell_vec = RCCellVecBuilder::default()
+        .set(rc_rule_type_hashes.to_vec())
+        .build();
+    RCDataBuilder::default()
+        .set(RCDataUnion::RCCellVec(cell_vec))
+        .build()
+        .as_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use ckb_types::H160;
+    use sparse_merkle_tree::CompiledMerkleProof;
+
+    use super::*;
+    use crate::types::xudt_rce_mol::RCData;
+    use crate::unlock::rc_data::{
+        CKBBlake2bHasher, EMERGENCY_HALT_MODE_MASK, SMT_EXISTING, WHITE_BLACK_LIST_MASK,
+    };
+
+    fn rule_flags(rc_rule: &Bytes) -> u8 {
+        match RCData::from_slice(rc_rule).unwrap().to_enum() {
+            RCDataUnion::RCRule(rc_rule) => rc_rule.flags().into(),
+            RCDataUnion::RCCellVec(_) => panic!("expected rc_rule"),
+        }
+    }
+
+    #[test]
+    fn test_generate_admin_proofs() {
+        let identities = (0u8..3)
+            .map(|idx| Identity::new_pubkey_hash(H160::from([idx; 20])))
+            .collect::<Vec<_>>();
+        let proven = &identities[1];
+
+        let (root, proofs, rc_rules) =
+            generate_admin_proofs(&identities, proven, RcScheme::OnlyInputOnWhiteList).unwrap();
+        assert_eq!(proofs.len(), 2);
+        assert_eq!(rc_rules.len(), 2);
+        let proof: Vec<u8> = proofs.get(0).unwrap().proof().raw_data().to_vec();
+        let root = SmtH256::from(<[u8; 32]>::from(root));
+        assert!(CompiledMerkleProof(proof)
+            .verify::<CKBBlake2bHasher>(&root, vec![(smt_key(proven), *SMT_EXISTING)])
+            .unwrap());
+
+        let (both_root, proofs, rc_rules) =
+            generate_admin_proofs(&identities, proven, RcScheme::BothOnWhiteList).unwrap();
+        assert_eq!(both_root, H256::from(<[u8; 32]>::from(root)));
+        assert_eq!(proofs.len(), 1);
+        assert_eq!(rule_flags(&rc_rules[0]), WHITE_BLACK_LIST_MASK);
+
+        let (_, _, rc_rules) =
+            generate_admin_proofs(&identities, proven, RcScheme::EmergencyHalt).unwrap();
+        assert_eq!(
+            rule_flags(&rc_rules[0]),
+            WHITE_BLACK_LIST_MASK | EMERGENCY_HALT_MODE_MASK
+        );
+
+        let type_hashes = vec![Byte32::zero(), Byte32::zero()];
+        let data = build_rc_cell_vec_data(&type_hashes);
+        match RCData::from_slice(&data).unwrap().to_enum() {
+            RCDataUnion::RCCellVec(cell_vec) => assert_eq!(cell_vec.len(), 2),
+            RCDataUnion::RCRule(_) => panic!("expected rc_cell_vec"),
+        }
+    }
+}
@@ -0,0 +1,232 @@
+//! The RSA identities of omni-lock, the signature is verified by the RSA auth
+//! of ckb-auth through exec or dl, see `OmniLockConfig::new_rsa`.
+//!
+//! The signature passed to the auth script is the `RsaInfo` of ckb-auth:
+//!
+//! ```text
+//! <1 byte algorithm id> <1 byte key size> <1 byte padding> <1 byte md type>
+//! <4 bytes little endian E> <key size / 8 bytes little endian N> <key size / 8 bytes signature>
+//! ```
+//!
+//! The pubkey hash of the identity is the blake160 hash of the `RsaInfo`
+//! without the signature.
+use ckb_types::{
+    bytes::{BufMut, Bytes, BytesMut},
+    core::TransactionView,
+    H160,
+};
+use rsa::{
+    pkcs1::DecodeRsaPublicKey,
+    pkcs1v15::SigningKey,
+    pkcs8::DecodePublicKey,
+    signature::{SignatureEncoding, Signer as _},
+    traits::{PrivateKeyParts, PublicKeyParts},
+    BigUint, RsaPrivateKey, RsaPublicKey,
+};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use super::ConfigError;
+use crate::{traits::SignerError, unlock::AuthWitnessProvider, util::blake160};
+
+/// The length of the `RsaInfo` fields before N
+pub const RSA_INFO_HEADER_LEN: usize = 8;
+
+const ALGORITHM_ID_RSA: u8 = 1;
+const ALGORITHM_ID_ISO9796_2: u8 = 2;
+const PADDING_PKCS15: u8 = 0;
+// the md types of mbedtls
+const MD_SHA1: u8 = 4;
+const MD_SHA256: u8 = 6;
+const SHA1_LEN: usize = 20;
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Hash, Eq, PartialEq)]
+pub enum RsaAlgorithm {
+    /// PKCS#1 v1.5 with SHA-256
+    Pkcs1v15,
+    /// ISO 9796-2 scheme 1 with SHA-1 and the implicit trailer, the message is
+    /// fully recoverable.
+    Iso9796_2,
+}
+
+impl RsaAlgorithm {
+    fn algorithm_id(self) -> u8 {
+        match self {
+            RsaAlgorithm::Pkcs1v15 => ALGORITHM_ID_RSA,
+            RsaAlgorithm::Iso9796_2 => ALGORITHM_ID_ISO9796_2,
+        }
+    }
+
+    fn md_type(self) -> u8 {
+        match self {
+            RsaAlgorithm::Pkcs1v15 => MD_SHA256,
+            RsaAlgorithm::Iso9796_2 => MD_SHA1,
+        }
+    }
+}
+
+/// A RSA public key of 1024, 2048 or 4096 bits
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+pub struct RsaPubkey {
+    algorithm: RsaAlgorithm,
+    e: u32,
+    /// Big endian
+    n: Vec<u8>,
+}
+
+impl RsaPubkey {
+    pub fn new(key: &RsaPublicKey, algorithm: RsaAlgorithm) -> Result<RsaPubkey, ConfigError> {
+        let bits = key.n().bits();
+        if bits != 1024 && bits != 2048 && bits != 4096 {
+            return Err(ConfigError::InvalidRsaKey(format!(
+                "unsupported key size: {} bits, expected 1024, 2048 or 4096",
+                bits
+            )));
+        }
+        let e_bytes = key.e().to_bytes_be();
+        if e_bytes.len() > 4 {
+            return Err(ConfigError::InvalidRsaKey(format!(
+                "the public exponent is longer than 4 bytes: {}",
+                e_bytes.len()
+            )));
+        }
+        let e = e_bytes.iter().fold(0u32, |e, byte| (e << 8) | *byte as u32);
+        Ok(RsaPubkey {
+            algorithm,
+            e,
+            n: key.n().to_bytes_be(),
+        })
+    }
+
+    /// Parse a DER encoded SubjectPublicKeyInfo or PKCS#1 public key
+    pub fn from_der(der: &[u8], algorithm: RsaAlgorithm) -> Result<RsaPubkey, ConfigError> {
+        let key = RsaPublicKey::from_public_key_der(der)
+            .or_else(|_| RsaPublicKey::from_pkcs1_der(der))
+            .map_err(|err| ConfigError::InvalidRsaKey(err.to_string()))?;
+        RsaPubkey::new(&key, algorithm)
+    }
+
+    pub fn algorithm(&self) -> RsaAlgorithm {
+        self.algorithm
+    }
+
+    /// The key size in bits
+    pub fn key_size(&self) -> usize {
+        self.n.len() * 8
+    }
+
+    fn key_size_id(&self) -> u8 {
+        match self.key_size() {
+            1024 => 1,
+            2048 => 2,
+            _ => 3,
+        }
+    }
+
+    /// The `RsaInfo` without the signature
+    pub fn pubkey_info(&self) -> Bytes {
+        let mut info = BytesMut::with_capacity(RSA_INFO_HEADER_LEN + self.n.len());
+        info.put_u8(self.algorithm.algorithm_id());
+        info.put_u8(self.key_size_id());
+        info.put_u8(PADDING_PKCS15);
+        info.put_u8(self.algorithm.md_type());
+        info.put_u32_le(self.e);
+        info.extend(self.n.iter().rev());
+        info.freeze()
+    }
+
+    /// The pubkey hash passed to the auth script
+    pub fn pubkey_hash(&self) -> H160 {
+        blake160(&self.pubkey_info())
+    }
+
+    /// The length of the `RsaInfo` with the signature, the signature length
+    /// of `ExecDlConfig`.
+    pub fn rsa_info_len(&self) -> usize {
+        RSA_INFO_HEADER_LEN + self.n.len() * 2
+    }
+
+    /// The `RsaInfo` of `signature`
+    pub fn rsa_info(&self, signature: &[u8]) -> Bytes {
+        let mut info = BytesMut::with_capacity(self.rsa_info_len());
+        info.put(self.pubkey_info().as_ref());
+        info.put(signature);
+        info.freeze()
+    }
+}
+
+/// Sign the omni-lock message by a RSA private key, register it by
+/// `OmniLockScriptSigner::with_auth_witness_provider`.
+pub struct RsaSigner {
+    key: RsaPrivateKey,
+    pubkey: RsaPubkey,
+}
+
+impl RsaSigner {
+    pub fn new(key: RsaPrivateKey, algorithm: RsaAlgorithm) -> Result<RsaSigner, ConfigError> {
+        let pubkey = RsaPubkey::new(&key.to_public_key(), algorithm)?;
+        Ok(RsaSigner { key, pubkey })
+    }
+
+    pub fn pubkey(&self) -> &RsaPubkey {
+        &self.pubkey
+    }
+
+    /// The raw signature of the message, key size / 8 bytes
+    pub fn sign_message(&self, message: &[u8]) -> Result<Bytes, SignerError> {
+        let signature = match self.pubkey.algorithm {
+            RsaAlgorithm::Pkcs1v15 => SigningKey::<Sha256>::new(self.key.clone())
+                .try_sign(message)
+                .map_err(|err| SignerError::Other(anyhow::anyhow!(err)))?
+                .to_vec(),
+            RsaAlgorithm::Iso9796_2 => self.sign_iso9796_2(message)?,
+        };
+        Ok(Bytes::from(signature))
+    }
+
+    fn sign_iso9796_2(&self, message: &[u8]) -> Result<Vec<u8>, SignerError> {
+        let key_len = self.pubkey.n.len();
+        let block_len = (self.pubkey.key_size() - 1) / 8;
+        // header, at least one padding byte, the message, the hash and the trailer
+        if message.len() + SHA1_LEN + 3 > block_len {
+            return Err(SignerError::InvalidMessage(format!(
+                "the message is too long to recover: {} bytes",
+                message.len()
+            )));
+        }
+        let mut block = vec![0xbbu8; block_len];
+        let hash_start = block_len - SHA1_LEN - 1;
+        block[hash_start..block_len - 1].copy_from_slice(&Sha1::digest(message));
+        block[block_len - 1] = 0xbc;
+        let message_start = hash_start - message.len();
+        block[message_start..hash_start].copy_from_slice(message);
+        block[message_start - 1] = 0xba;
+        block[0] = 0x4b;
+        let signature = BigUint::from_bytes_be(&block)
+            .modpow(self.key.d(), self.key.n())
+            .to_bytes_be();
+        let mut padded = vec![0u8; key_len - signature.len()];
+        padded.extend(signature);
+        Ok(padded)
+    }
+}
+
+impl AuthWitnessProvider for RsaSigner {
+    fn match_pubkey_hash(&self, pubkey_hash: &H160) -> bool {
+        pubkey_hash == &self.pubkey.pubkey_hash()
+    }
+
+    fn sign(
+        &self,
+        pubkey_hash: &H160,
+        message: &[u8],
+        _tx: &TransactionView,
+    ) -> Result<Bytes, SignerError> {
+        if !self.match_pubkey_hash(pubkey_hash) {
+            return Err(SignerError::IdNotFound);
+        }
+        let signature = self.sign_message(message)?;
+        Ok(self.pubkey.rsa_info(&signature))
+    }
+}
@@ -1,6 +1,8 @@
 use lazy_static::lazy_static;
 
-use sparse_merkle_tree::{default_store::DefaultStore, SparseMerkleTree, H256 as SmtH256};
+use sparse_merkle_tree::{
+    default_store::DefaultStore, merge::MergeValue, SparseMerkleTree, H256 as SmtH256,
+};
 
 use crate::types::xudt_rce_mol::{
     RCDataBuilder, RCDataUnion, RCRuleBuilder, SmtProofBuilder, SmtProofEntryBuilder,
@@ -33,15 +35,17 @@ pub enum RcDataError {
     BuildTree(String),
     #[error("fail to compile proof, reason:`{0}`")]
     CompileProof(String),
+    #[error("proof size `{size}` exceeds the max proof size `{max}`")]
+    ProofTooLarge { size: usize, max: usize },
 }
 
 // on(1): white list
 // off(0): black list
-const WHITE_BLACK_LIST_MASK: u8 = 0x2;
+pub const WHITE_BLACK_LIST_MASK: u8 = 0x2;
 
 // on(1): emergency halt mode
 // off(0): not int emergency halt mode
-const EMERGENCY_HALT_MODE_MASK: u8 = 0x1;
+pub const EMERGENCY_HALT_MODE_MASK: u8 = 0x1;
 pub struct CKBBlake2bHasher(Blake2b);
 
 impl Default for CKBBlake2bHasher {
@@ -81,6 +85,8 @@ pub struct RcRuleDataBuilder {
     list_type: ListType,
     /// indicate if the rule is emergency
     is_emergency: bool,
+    /// the max size of a compiled proof, see `set_max_proof_size`
+    max_proof_size: Option<usize>,
 }
 
 impl RcRuleDataBuilder {
@@ -90,6 +96,7 @@ impl RcRuleDataBuilder {
             smt,
             list_type,
             is_emergency,
+            max_proof_size: None,
         }
     }
     /// create a default smt tree with initial smt values.
@@ -119,12 +126,21 @@ impl RcRuleDataBuilder {
         self.update(&pairs);
     }
 
-    /// Build a smt tree with it's keys and gnerate proofs with the according keys.
-    /// # Arguments
-    /// * `keys` - The keys to generate the proofs.
-    /// # Return
-    /// The smt_tree root and the proofs of the proof_keys.
-    pub fn proof_keys(&mut self, keys: &[SmtH256]) -> Result<Vec<u8>> {
+    /// Limit the size of the proofs generated by `proof_keys`.
+    ///
+    /// The proof is put into the witness of every omni-lock admin mode
+    /// unlock, it grows with the number of keys in the tree. When the limit is
+    /// exceeded `RcDataError::ProofTooLarge` is returned, so the caller can
+    /// split the list into multiple rc rules.
+    pub fn set_max_proof_size(&mut self, max_proof_size: Option<usize>) {
+        self.max_proof_size = max_proof_size;
+    }
+
+    pub fn max_proof_size(&self) -> Option<usize> {
+        self.max_proof_size
+    }
+
+    fn compile_proof(&self, keys: &[SmtH256]) -> Result<Vec<u8>> {
         let proof = self
             .smt
             .merkle_proof(keys.to_vec())
@@ -135,6 +151,100 @@ impl RcRuleDataBuilder {
         Ok(compiled_proof.into())
     }
 
+    /// The size of the compiled proof of `keys`, which is the size
+    /// `proof_keys` returns. The max proof size is not checked.
+    ///
+    /// The size is counted from the siblings on the paths of the keys, the
+    /// proof is not compiled.
+    pub fn proof_size(&self, keys: &[SmtH256]) -> Result<usize> {
+        let proof = self
+            .smt
+            .merkle_proof(keys.to_vec())
+            .map_err(|err| RcDataError::BuildTree(err.to_string()))?;
+        if keys.is_empty() {
+            return Err(RcDataError::CompileProof("empty keys".to_string()));
+        }
+        let mut keys = keys.to_vec();
+        keys.sort_unstable();
+        let leaves_bitmap = proof.leaves_bitmap();
+        let mut merkle_path = proof.merkle_path().iter();
+        let mut fork_heights: Vec<u8> = Vec::new();
+        let mut size = 0;
+        // the same op codes as `MerkleProof::compile`
+        for (idx, key) in keys.iter().enumerate() {
+            let is_last = idx + 1 == keys.len();
+            let fork_height = if is_last {
+                u8::MAX
+            } else {
+                key.fork_height(&keys[idx + 1])
+            };
+            // L: push the leaf
+            size += 1;
+            let mut zero_count = 0;
+            for height in 0..=fork_height {
+                if height == fork_height && !is_last {
+                    break;
+                }
+                let op_size = if fork_heights.last() == Some(&height) {
+                    // H: merge with the leaves on the stack
+                    fork_heights.pop();
+                    1
+                } else if leaves_bitmap
+                    .get(idx)
+                    .map(|bitmap| bitmap.get_bit(height))
+                    .unwrap_or(false)
+                {
+                    match merkle_path.next() {
+                        // P: a sibling hash
+                        Some(MergeValue::Value(_)) => 1 + 32,
+                        // Q: a sibling merged with zeros, the zero count, base node and zero bits
+                        Some(_) => 1 + 1 + 32 + 32,
+                        None => {
+                            return Err(RcDataError::CompileProof(
+                                "corrupted merkle proof".to_string(),
+                            ))
+                        }
+                    }
+                } else {
+                    zero_count += 1;
+                    0
+                };
+                if op_size > 0 {
+                    if zero_count > 0 {
+                        // O: the count of zero siblings
+                        size += 2;
+                        zero_count = 0;
+                    }
+                    size += op_size;
+                }
+            }
+            if zero_count > 0 {
+                size += 2;
+            }
+            fork_heights.push(fork_height);
+        }
+        Ok(size)
+    }
+
+    /// Build a smt tree with it's keys and gnerate proofs with the according keys.
+    /// # Arguments
+    /// * `keys` - The keys to generate the proofs.
+    /// # Return
+    /// The compiled proof of the keys, or `RcDataError::ProofTooLarge` if the
+    /// proof is larger than the max proof size.
+    pub fn proof_keys(&mut self, keys: &[SmtH256]) -> Result<Vec<u8>> {
+        let proof = self.compile_proof(keys)?;
+        if let Some(max) = self.max_proof_size {
+            if proof.len() > max {
+                return Err(RcDataError::ProofTooLarge {
+                    size: proof.len(),
+                    max,
+                });
+            }
+        }
+        Ok(proof)
+    }
+
     /// Build the rc_rule after key/value pairs are set.
     pub fn build_rc_rule(&self) -> Bytes {
         let smt_root = self.smt.root();
@@ -177,6 +287,20 @@ impl RcRuleDataBuilder {
     }
 }
 
+/// The serialized size of the `SmtProofEntryVec` of the rc rules, each rule
+/// with a compiled proof of `proof_sizes[i]` bytes, see
+/// `RcRuleDataBuilder::proof_size`.
+pub fn proof_entry_vec_size(proof_sizes: &[usize]) -> usize {
+    // the total size and an offset for each entry
+    let header_size = molecule::NUMBER_SIZE * (1 + proof_sizes.len());
+    // an entry is a table of the mask byte and the proof bytes
+    let entries_size: usize = proof_sizes
+        .iter()
+        .map(|size| molecule::NUMBER_SIZE * 3 + 1 + molecule::NUMBER_SIZE + size)
+        .sum();
+    header_size + entries_size
+}
+
 /// Indicate which the rule is applied to.
 #[repr(u8)]
 #[derive(Clone, Copy)]
@@ -239,6 +363,26 @@ impl RcRuleVecBuilder {
         Ok(())
     }
 
+    /// Generate the proof of `keys` in the tree of `rc_rule_builder`, then add
+    /// the proof and the rule with self.add_rule.
+    /// # Arguments
+    /// * `rc_rule_builder` The rule with all the keys of the list updated.
+    /// * `keys` The keys to prove.
+    /// * `mask` The mask indicate which rule to apply.
+    pub fn build_proof_and_add_rule(
+        &mut self,
+        rc_rule_builder: &mut RcRuleDataBuilder,
+        keys: &[SmtH256],
+        mask: Mask,
+    ) -> Result<()> {
+        let proof = rc_rule_builder.proof_keys(keys)?;
+        self.add_rule(
+            ProofWithMask::new(proof, mask),
+            rc_rule_builder.build_rc_rule(),
+        );
+        Ok(())
+    }
+
     pub fn build_proofs(&self) -> SmtProofEntryVec {
         let mut builder = SmtProofEntryVecBuilder::default();
         for ProofWithMask { proof, mask } in &self.proofs {
@@ -293,6 +437,69 @@ mod tests {
             .unwrap());
     }
 
+    #[test]
+    fn test_proof_size_limit() {
+        let keys: Vec<SmtH256> = (0u32..100)
+            .map(|idx| SmtH256::from(ckb_hash::blake2b_256(idx.to_le_bytes())))
+            .collect();
+        let mut builder = RcRuleDataBuilder::new(ListType::White, false);
+        builder.update_hashes(&keys);
+        let size = builder.proof_size(&keys[..1]).unwrap();
+        let proof = builder.proof_keys(&keys[..1]).unwrap();
+        assert_eq!(proof.len(), size);
+        // more keys in the tree, larger proof
+        let mut small_builder = RcRuleDataBuilder::new(ListType::White, false);
+        small_builder.update_hashes(&keys[..2]);
+        assert!(small_builder.proof_size(&keys[..1]).unwrap() < size);
+
+        builder.set_max_proof_size(Some(size));
+        assert_eq!(builder.proof_keys(&keys[..1]).unwrap(), proof);
+        builder.set_max_proof_size(Some(size - 1));
+        assert_eq!(
+            builder.proof_keys(&keys[..1]).unwrap_err(),
+            RcDataError::ProofTooLarge {
+                size,
+                max: size - 1
+            }
+        );
+        // the size is still available to restructure the list
+        assert_eq!(builder.proof_size(&keys[..1]).unwrap(), size);
+
+        builder.set_max_proof_size(None);
+        let empty_builder = RcRuleDataBuilder::new(ListType::Black, false);
+        for (builder, keys) in [
+            (&builder, &keys[..2]),
+            (&builder, &keys[10..40]),
+            (&builder, &keys[..]),
+            (&empty_builder, &keys[..3]),
+        ] {
+            assert_eq!(
+                builder.proof_size(keys).unwrap(),
+                builder.compile_proof(keys).unwrap().len()
+            );
+        }
+
+        let mut rule_vec = RcRuleVecBuilder::new();
+        rule_vec
+            .build_proof_and_add_rule(&mut builder, &keys[..2], Mask::Input)
+            .unwrap();
+        rule_vec
+            .build_proof_and_add_rule(&mut small_builder, &keys[..1], Mask::Output)
+            .unwrap();
+        let proof_sizes = [
+            builder.proof_size(&keys[..2]).unwrap(),
+            small_builder.proof_size(&keys[..1]).unwrap(),
+        ];
+        assert_eq!(
+            rule_vec.build_proofs().as_slice().len(),
+            proof_entry_vec_size(&proof_sizes)
+        );
+        assert_eq!(
+            proof_entry_vec_size(&[]),
+            SmtProofEntryVec::default().as_slice().len()
+        );
+    }
+
     #[test]
     fn test_build_smt_on_wl() {
         let smt_key = SmtH256::zero();
@@ -0,0 +1,404 @@
+//! The messages signed by the secp256k1 keys of the lock script groups, for
+//! the signers outside of the SDK (e.g. hardware wallets) to display and sign.
+use std::collections::HashMap;
+
+use ckb_types::{bytes::Bytes, core::TransactionView, packed, prelude::*, H160, H256};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    generate_message, load_witness_args, omni_lock::ConfigError, update_witness_field,
+    IdentityFlag, MultisigConfig, OmniLockConfig, OmniUnlockMode, ScriptSignError,
+    SigningDescriptor, UnlockError, WitnessField,
+};
+use crate::traits::TransactionDependencyProvider;
+use crate::tx_builder::gen_script_groups;
+use crate::types::{omni_lock::OmniLockWitnessLock, ScriptGroup, ScriptId};
+use crate::util::{blake160, convert_keccak256_hash, keccak160};
+use crate::SECP256K1;
+
+const SIGNATURE_SIZE: usize = 65;
+
+/// How the message of a script group is signed
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum SignAlgorithm {
+    /// A recoverable secp256k1 signature of the message, the key is
+    /// identified by the blake160 hash of the compressed public key.
+    Secp256k1Blake160,
+    /// A recoverable secp256k1 signature of the message, which is the
+    /// keccak256 hash of the ethereum personal message of the blake2b
+    /// message. The key is identified by the ethereum address.
+    EthPersonalSign,
+    /// The message is passed to the auth script of an exec or dl omni-lock,
+    /// the signature is up to the script.
+    AuthScript,
+}
+
+/// The message to sign of a lock script group
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GroupMessage {
+    pub script_group: ScriptGroup,
+    pub descriptor: SigningDescriptor,
+    /// The 32 bytes to sign, the same as the message signed by the script signer
+    pub message: H256,
+    pub algorithm: SignAlgorithm,
+}
+
+/// The messages of the lock script groups of `tx` with a descriptor in
+/// `unlock_configs`, in the order of their first input.
+///
+/// The messages are the same as the ones the script signers sign with the
+/// default chain params, the witnesses must be filled with the placeholders
+/// before. An omni-lock group of an owner lock identity is skipped since it
+/// is not signed.
+pub fn compute_sighash_messages(
+    tx: &TransactionView,
+    tx_dep_provider: &dyn TransactionDependencyProvider,
+    unlock_configs: &HashMap<ScriptId, SigningDescriptor>,
+) -> Result<Vec<GroupMessage>, ScriptSignError> {
+    let mut groups: Vec<ScriptGroup> = gen_script_groups(tx, tx_dep_provider)
+        .map_err(|err| ScriptSignError::Other(err.into()))?
+        .lock_groups
+        .into_values()
+        .collect();
+    groups.sort_by_key(|group| group.input_indices[0]);
+
+    let mut messages = Vec::new();
+    for script_group in groups {
+        let descriptor = match unlock_configs.get(&ScriptId::from(&script_group.script)) {
+            Some(descriptor) => descriptor,
+            None => continue,
+        };
+        let algorithm = match sign_algorithm(descriptor)? {
+            Some(algorithm) => algorithm,
+            None => continue,
+        };
+        let lock_len = placeholder_lock(descriptor, &script_group)?.len();
+        let raw = raw_message(tx, &script_group, lock_len)?;
+        let message = match algorithm {
+            SignAlgorithm::EthPersonalSign => convert_keccak256_hash(raw.as_ref()),
+            _ => H256::from_slice(raw.as_ref()).expect("message"),
+        };
+        messages.push(GroupMessage {
+            script_group,
+            descriptor: descriptor.clone(),
+            message,
+            algorithm,
+        });
+    }
+    Ok(messages)
+}
+
+/// Check `signature` is a signature of `message` by the key of `pubkey_hash`,
+/// which is the blake160 hash or the ethereum address according to
+/// `algorithm`.
+pub fn verify_signature_against_message(
+    message: &H256,
+    signature: &[u8],
+    algorithm: SignAlgorithm,
+    pubkey_hash: &H160,
+) -> Result<(), ScriptSignError> {
+    if algorithm == SignAlgorithm::AuthScript {
+        return Err(ScriptSignError::InvalidSignature(
+            "the signature of an auth script can not be verified".to_string(),
+        ));
+    }
+    if signature.len() != SIGNATURE_SIZE {
+        return Err(ScriptSignError::InvalidSignature(format!(
+            "expected {} bytes, got: {}",
+            SIGNATURE_SIZE,
+            signature.len()
+        )));
+    }
+    let invalid = |err: secp256k1::Error| ScriptSignError::InvalidSignature(err.to_string());
+    let recovery_id =
+        secp256k1::ecdsa::RecoveryId::from_i32(signature[64] as i32).map_err(invalid)?;
+    let recoverable =
+        secp256k1::ecdsa::RecoverableSignature::from_compact(&signature[0..64], recovery_id)
+            .map_err(invalid)?;
+    let msg = secp256k1::Message::from_digest_slice(message.as_bytes()).map_err(invalid)?;
+    let pubkey = SECP256K1
+        .recover_ecdsa(&msg, &recoverable)
+        .map_err(invalid)?;
+    let recovered_hash = match algorithm {
+        SignAlgorithm::EthPersonalSign => keccak160(&pubkey.serialize_uncompressed()[1..]),
+        _ => blake160(&pubkey.serialize()),
+    };
+    if &recovered_hash != pubkey_hash {
+        return Err(ScriptSignError::InvalidSignature(format!(
+            "signed by {:#x}, expected: {:#x}",
+            recovered_hash, pubkey_hash
+        )));
+    }
+    Ok(())
+}
+
+/// Where a signature is put into the witness lock of a script group
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum WitnessLayout {
+    /// The witness lock of the sighash all lock is the signature
+    SighashLock,
+    /// A slot of the multisig lock, the signature of `pubkey_hash` replaces
+    /// the previous signature of the same key, or takes the first empty slot.
+    MultisigSlot {
+        config: MultisigConfig,
+        pubkey_hash: H160,
+    },
+    /// The signature field of the `OmniLockWitnessLock`, the signature of a
+    /// multisig identity takes the first empty slot.
+    OmniLock {
+        config: OmniLockConfig,
+        unlock_mode: OmniUnlockMode,
+    },
+}
+
+/// Put an externally produced `signature` of `script_group` into its witness
+/// lock, e.g. a signature of the message by `compute_sighash_messages`.
+///
+/// The lock is built from the placeholder when it is not filled yet, the
+/// other fields of the witness and the witnesses of the other groups are
+/// kept. A signature of a wrong length for the layout is rejected, so is a
+/// multisig signature not by `pubkey_hash`.
+pub fn attach_signature(
+    tx: &TransactionView,
+    script_group: &ScriptGroup,
+    signature: Bytes,
+    layout: WitnessLayout,
+) -> Result<TransactionView, UnlockError> {
+    let (placeholder, signature_len) = match &layout {
+        WitnessLayout::SighashLock => (Bytes::from(vec![0u8; SIGNATURE_SIZE]), SIGNATURE_SIZE),
+        WitnessLayout::MultisigSlot { config, .. } => {
+            (multisig_placeholder(config), SIGNATURE_SIZE)
+        }
+        WitnessLayout::OmniLock {
+            config,
+            unlock_mode,
+        } => {
+            let signature_len = match omni_lock_auth(config, *unlock_mode)? {
+                IdentityFlag::Exec | IdentityFlag::Dl => config
+                    .get_exec_dl_config()
+                    .ok_or(ConfigError::NoExecDlConfig)?
+                    .sig_len(),
+                _ => SIGNATURE_SIZE,
+            };
+            let args = script_group.script.args().raw_data();
+            (
+                config.placeholder_witness_lock_for_args(*unlock_mode, &args)?,
+                signature_len,
+            )
+        }
+    };
+    if signature.len() != signature_len {
+        return Err(ScriptSignError::InvalidSignature(format!(
+            "expected {} bytes, got: {}",
+            signature_len,
+            signature.len()
+        ))
+        .into());
+    }
+
+    let witness_idx = script_group.input_indices[0];
+    let lock = load_witness_args(tx, witness_idx)?
+        .lock()
+        .to_opt()
+        .map(|data| data.raw_data())
+        .filter(|lock| lock.len() == placeholder.len())
+        .unwrap_or_else(|| placeholder.clone());
+    let lock = match &layout {
+        WitnessLayout::SighashLock => signature,
+        WitnessLayout::MultisigSlot {
+            config,
+            pubkey_hash,
+        } => {
+            let message =
+                H256::from_slice(raw_message(tx, script_group, placeholder.len())?.as_ref())
+                    .expect("message");
+            verify_signature_against_message(
+                &message,
+                &signature,
+                SignAlgorithm::Secp256k1Blake160,
+                pubkey_hash,
+            )?;
+            put_multisig_signature(
+                lock.as_ref(),
+                config,
+                &signature,
+                Some((&message, pubkey_hash)),
+            )?
+        }
+        WitnessLayout::OmniLock {
+            config,
+            unlock_mode,
+        } => {
+            let omni_lock =
+                OmniLockWitnessLock::from_slice(lock.as_ref()).map_err(ScriptSignError::from)?;
+            let omni_sig = match omni_lock_multisig_config(config, *unlock_mode)? {
+                Some(multisig_config) => {
+                    let current = omni_lock
+                        .signature()
+                        .to_opt()
+                        .map(|data| data.raw_data())
+                        .unwrap_or_default();
+                    put_multisig_signature(current.as_ref(), multisig_config, &signature, None)?
+                }
+                None => signature,
+            };
+            omni_lock
+                .as_builder()
+                .signature(Some(omni_sig).pack())
+                .build()
+                .as_bytes()
+        }
+    };
+    Ok(update_witness_field(
+        tx,
+        witness_idx,
+        WitnessField::Lock,
+        lock,
+    )?)
+}
+
+// The blake2b message of the group, generated with a zero lock of `lock_len`
+fn raw_message(
+    tx: &TransactionView,
+    script_group: &ScriptGroup,
+    lock_len: usize,
+) -> Result<Bytes, ScriptSignError> {
+    let witness_idx = script_group.input_indices[0];
+    let mut witnesses: Vec<packed::Bytes> = tx.witnesses().into_iter().collect();
+    while witnesses.len() <= witness_idx {
+        witnesses.push(Default::default());
+    }
+    let tx_new = tx.as_advanced_builder().set_witnesses(witnesses).build();
+    generate_message(&tx_new, script_group, Bytes::from(vec![0u8; lock_len]))
+}
+
+// The algorithm of the descriptor, `None` if the group is not signed
+fn sign_algorithm(
+    descriptor: &SigningDescriptor,
+) -> Result<Option<SignAlgorithm>, ScriptSignError> {
+    let flag = match descriptor {
+        SigningDescriptor::Sighash | SigningDescriptor::Multisig { .. } => {
+            return Ok(Some(SignAlgorithm::Secp256k1Blake160))
+        }
+        SigningDescriptor::OmniLock {
+            config,
+            unlock_mode,
+        } => omni_lock_auth(config, *unlock_mode)?,
+    };
+    match flag {
+        IdentityFlag::PubkeyHash | IdentityFlag::Multisig => {
+            Ok(Some(SignAlgorithm::Secp256k1Blake160))
+        }
+        IdentityFlag::Ethereum => Ok(Some(SignAlgorithm::EthPersonalSign)),
+        IdentityFlag::Exec | IdentityFlag::Dl => Ok(Some(SignAlgorithm::AuthScript)),
+        IdentityFlag::OwnerLock => Ok(None),
+        flag => Err(ScriptSignError::Other(anyhow::anyhow!(
+            "the message of identity flag {:?} is not supported",
+            flag
+        ))),
+    }
+}
+
+// The identity flag signing an omni-lock group
+fn omni_lock_auth(
+    config: &OmniLockConfig,
+    unlock_mode: OmniUnlockMode,
+) -> Result<IdentityFlag, ScriptSignError> {
+    Ok(match unlock_mode {
+        OmniUnlockMode::Admin => config
+            .get_admin_config()
+            .ok_or(ConfigError::NoAdminConfig)?
+            .get_auth()
+            .flag(),
+        OmniUnlockMode::Normal => config.id().flag(),
+    })
+}
+
+fn omni_lock_multisig_config(
+    config: &OmniLockConfig,
+    unlock_mode: OmniUnlockMode,
+) -> Result<Option<&MultisigConfig>, ScriptSignError> {
+    if omni_lock_auth(config, unlock_mode)? != IdentityFlag::Multisig {
+        return Ok(None);
+    }
+    let multisig_config = match unlock_mode {
+        OmniUnlockMode::Admin => config
+            .get_admin_config()
+            .and_then(|admin_config| admin_config.get_multisig_config()),
+        OmniUnlockMode::Normal => config.multisig_config(),
+    };
+    Ok(Some(multisig_config.ok_or(ConfigError::NoMultiSigConfig)?))
+}
+
+// The unsigned witness lock, the message is generated with a zero lock of
+// the same size
+fn placeholder_lock(
+    descriptor: &SigningDescriptor,
+    script_group: &ScriptGroup,
+) -> Result<Bytes, ScriptSignError> {
+    Ok(match descriptor {
+        SigningDescriptor::Sighash => Bytes::from(vec![0u8; SIGNATURE_SIZE]),
+        SigningDescriptor::Multisig { config } => multisig_placeholder(config),
+        SigningDescriptor::OmniLock {
+            config,
+            unlock_mode,
+        } => {
+            let args = script_group.script.args().raw_data();
+            config.placeholder_witness_lock_for_args(*unlock_mode, &args)?
+        }
+    })
+}
+
+fn multisig_placeholder(config: &MultisigConfig) -> Bytes {
+    let config_data = config.to_witness_data();
+    let mut lock = vec![0u8; config_data.len() + config.threshold() as usize * SIGNATURE_SIZE];
+    lock[..config_data.len()].copy_from_slice(&config_data);
+    Bytes::from(lock)
+}
+
+// Put the signature into the first empty slot of the multisig lock. With
+// the message and the pubkey hash of the signature, a previous signature of
+// the same key is replaced.
+fn put_multisig_signature(
+    lock: &[u8],
+    config: &MultisigConfig,
+    signature: &[u8],
+    signed_by: Option<(&H256, &H160)>,
+) -> Result<Bytes, ScriptSignError> {
+    if signature.len() != SIGNATURE_SIZE {
+        return Err(ScriptSignError::InvalidSignature(format!(
+            "expected {} bytes, got: {}",
+            SIGNATURE_SIZE,
+            signature.len()
+        )));
+    }
+    let mut lock = if lock.len() == multisig_placeholder(config).len() {
+        lock.to_vec()
+    } else {
+        multisig_placeholder(config).to_vec()
+    };
+    let config_len = config.to_witness_data().len();
+    let same_key = |slot: &[u8]| match signed_by {
+        Some((message, pubkey_hash)) => verify_signature_against_message(
+            message,
+            slot,
+            SignAlgorithm::Secp256k1Blake160,
+            pubkey_hash,
+        )
+        .is_ok(),
+        None => false,
+    };
+    let slots = &mut lock[config_len..];
+    let idx = slots
+        .chunks(SIGNATURE_SIZE)
+        .position(|slot| slot == signature || same_key(slot))
+        .or_else(|| {
+            slots
+                .chunks(SIGNATURE_SIZE)
+                .position(|slot| slot.iter().all(|byte| *byte == 0))
+        })
+        .ok_or(ScriptSignError::TooManySignatures)?;
+    slots[idx * SIGNATURE_SIZE..(idx + 1) * SIGNATURE_SIZE].copy_from_slice(signature);
+    Ok(Bytes::from(lock))
+}
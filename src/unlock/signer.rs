@@ -1,25 +1,29 @@
 use std::collections::HashSet;
 
 use anyhow::anyhow;
-use ckb_hash::{blake2b_256, new_blake2b};
+use ckb_hash::blake2b_256;
 use ckb_types::{
     bytes::{Bytes, BytesMut},
-    core::{ScriptHashType, TransactionView},
+    core::{EpochNumberWithFraction, ScriptHashType, TransactionView},
     error::VerificationError,
     packed::{self, BytesOpt, Script, WitnessArgs},
     prelude::*,
     H160,
 };
 use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
 use thiserror::Error;
 
-use crate::{constants::MULTISIG_TYPE_HASH, types::omni_lock::OmniLockWitnessLock};
+use crate::{
+    constants::{CHEQUE_CELL_SINCE, MULTISIG_TYPE_HASH},
+    types::{omni_lock::OmniLockWitnessLock, ScriptHashTypeExt, SinceType},
+};
 use crate::{
     traits::{Signer, SignerError},
     util::convert_keccak256_hash,
 };
 use crate::{
-    types::{AddressPayload, CodeHashIndex, ScriptGroup, Since},
+    types::{AddressPayload, ChainParams, CodeHashIndex, ScriptGroup, Since},
     Address, NetworkType,
 };
 
@@ -51,6 +55,18 @@ pub enum ScriptSignError {
     #[error("there is an configuration error: `{0}`")]
     InvalidConfig(#[from] ConfigError),
 
+    #[error("invalid signature: `{0}`")]
+    InvalidSignature(String),
+
+    #[error("invalid cheque withdraw since: `{0}`")]
+    InvalidChequeWithdrawSince(String),
+
+    #[error("the since of input `{0}` is `{1:#x}`, the multisig lock args requires `{2:#x}`")]
+    MultisigSinceMismatch(usize, u64, u64),
+
+    #[error("can not infer the cheque action: `{0}`")]
+    ChequeActionNotInferred(String),
+
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -70,15 +86,81 @@ pub trait ScriptSigner {
     ) -> Result<TransactionView, ScriptSignError>;
 }
 
+/// The field of a `WitnessArgs` a script group writes into.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub(crate) enum WitnessField {
+    Lock,
+    InputType,
+    OutputType,
+}
+
+/// Load the `WitnessArgs` at `witness_idx`, an empty or missing witness is
+/// treated as `WitnessArgs::default()`.
+pub(crate) fn load_witness_args(
+    tx: &TransactionView,
+    witness_idx: usize,
+) -> Result<WitnessArgs, ScriptSignError> {
+    let witness_data = tx
+        .witnesses()
+        .get(witness_idx)
+        .map(|data| data.raw_data())
+        .unwrap_or_default();
+    if witness_data.is_empty() {
+        Ok(WitnessArgs::default())
+    } else {
+        Ok(WitnessArgs::from_slice(witness_data.as_ref())?)
+    }
+}
+
+/// Replace one field of the `WitnessArgs` at `witness_idx`.
+///
+/// The witness is read-modify-written: a lock group and a type group may share
+/// the same witness index (e.g. the sighash signature in `lock` and the xUDT
+/// extension data in `input_type`), the fields not owned by the caller are
+/// kept as is.
+pub(crate) fn update_witness_field(
+    tx: &TransactionView,
+    witness_idx: usize,
+    field: WitnessField,
+    data: Bytes,
+) -> Result<TransactionView, ScriptSignError> {
+    let current_witness = load_witness_args(tx, witness_idx)?;
+    let builder = current_witness.as_builder();
+    let new_witness = match field {
+        WitnessField::Lock => builder.lock(Some(data).pack()),
+        WitnessField::InputType => builder.input_type(Some(data).pack()),
+        WitnessField::OutputType => builder.output_type(Some(data).pack()),
+    }
+    .build();
+    let mut witnesses: Vec<packed::Bytes> = tx.witnesses().into_iter().collect();
+    while witnesses.len() <= witness_idx {
+        witnesses.push(Default::default());
+    }
+    witnesses[witness_idx] = new_witness.as_bytes().pack();
+    Ok(tx.as_advanced_builder().set_witnesses(witnesses).build())
+}
+
 /// Signer for secp256k1 sighash all lock script
 pub struct SecpSighashScriptSigner {
     // Can be: SecpCkbRawKeySigner, HardwareWalletSigner
     signer: Box<dyn Signer>,
+    chain_params: ChainParams,
 }
 
 impl SecpSighashScriptSigner {
     pub fn new(signer: Box<dyn Signer>) -> SecpSighashScriptSigner {
-        SecpSighashScriptSigner { signer }
+        Self::new_with_chain_params(signer, ChainParams::default())
+    }
+
+    /// Generate the signing message with the chain's blake2b personalization
+    pub fn new_with_chain_params(
+        signer: Box<dyn Signer>,
+        chain_params: ChainParams,
+    ) -> SecpSighashScriptSigner {
+        SecpSighashScriptSigner {
+            signer,
+            chain_params,
+        }
     }
 
     pub fn signer(&self) -> &dyn Signer {
@@ -102,23 +184,13 @@ impl SecpSighashScriptSigner {
             .build();
 
         let zero_lock = Bytes::from(vec![0u8; 65]);
-        let message = generate_message(&tx_new, script_group, zero_lock)?;
+        let message =
+            generate_message_with_params(&tx_new, script_group, zero_lock, &self.chain_params)?;
 
         let signature = self.signer.sign(owner_id, message.as_ref(), true, tx)?;
 
         // Put signature into witness
-        let witness_data = witnesses[witness_idx].raw_data();
-        let mut current_witness: WitnessArgs = if witness_data.is_empty() {
-            WitnessArgs::default()
-        } else {
-            WitnessArgs::from_slice(witness_data.as_ref())?
-        };
-        current_witness = current_witness
-            .as_builder()
-            .lock(Some(signature).pack())
-            .build();
-        witnesses[witness_idx] = current_witness.as_bytes().pack();
-        Ok(tx.as_advanced_builder().set_witnesses(witnesses).build())
+        update_witness_field(tx, witness_idx, WitnessField::Lock, signature)
     }
 }
 
@@ -238,17 +310,45 @@ impl MultisigConfig {
             .build()
     }
 
-    pub fn to_address(&self, network: NetworkType, since_absolute_epoch: Option<u64>) -> Address {
+    pub fn to_address<N: Into<NetworkType>>(
+        &self,
+        network: N,
+        since_absolute_epoch: Option<u64>,
+    ) -> Address {
         let payload = self.to_address_payload(since_absolute_epoch);
         Address::new(network, payload, true)
     }
+
+    /// The multisig lock script with `since` embedded in the 28 bytes args
+    /// (`hash160 ++ since` in little endian), every input of it must have
+    /// exactly this since.
+    pub fn to_script_with_since(&self, since: Since) -> Script {
+        let mut args = BytesMut::from(self.hash160().as_bytes());
+        args.extend_from_slice(&since.value().to_le_bytes()[..]);
+        Script::new_builder()
+            .code_hash(MULTISIG_TYPE_HASH.pack())
+            .hash_type(ScriptHashType::Type.to_packed())
+            .args(args.freeze().pack())
+            .build()
+    }
+}
+
+/// The since embedded in the multisig lock args, `None` if the args is not 28
+/// bytes.
+pub fn multisig_args_since(args: &[u8]) -> Option<Since> {
+    if args.len() != 28 {
+        return None;
+    }
+    let mut since_bytes = [0u8; 8];
+    since_bytes.copy_from_slice(&args[20..28]);
+    Some(Since::from_raw_value(u64::from_le_bytes(since_bytes)))
 }
 
 impl From<&MultisigConfig> for Script {
     fn from(value: &MultisigConfig) -> Self {
         Script::new_builder()
             .code_hash(MULTISIG_TYPE_HASH.pack())
-            .hash_type(ScriptHashType::Type.into())
+            .hash_type(ScriptHashType::Type.to_packed())
             .args(Bytes::from(value.hash160().as_bytes().to_vec()).pack())
             .build()
     }
@@ -260,14 +360,26 @@ pub struct SecpMultisigScriptSigner {
     signer: Box<dyn Signer>,
     config: MultisigConfig,
     config_hash: [u8; 32],
+    chain_params: ChainParams,
 }
 impl SecpMultisigScriptSigner {
     pub fn new(signer: Box<dyn Signer>, config: MultisigConfig) -> SecpMultisigScriptSigner {
-        let config_hash = blake2b_256(config.to_witness_data());
+        Self::new_with_chain_params(signer, config, ChainParams::default())
+    }
+
+    /// Hash the multisig config and generate the signing message with the
+    /// chain's blake2b personalization
+    pub fn new_with_chain_params(
+        signer: Box<dyn Signer>,
+        config: MultisigConfig,
+        chain_params: ChainParams,
+    ) -> SecpMultisigScriptSigner {
+        let config_hash = chain_params.blake2b_256(config.to_witness_data());
         SecpMultisigScriptSigner {
             signer,
             config,
             config_hash,
+            chain_params,
         }
     }
     pub fn signer(&self) -> &dyn Signer {
@@ -293,6 +405,23 @@ impl ScriptSigner for SecpMultisigScriptSigner {
         tx: &TransactionView,
         script_group: &ScriptGroup,
     ) -> Result<TransactionView, ScriptSignError> {
+        // the signature covers the since, check it before signing
+        if let Some(since) = multisig_args_since(&script_group.script.args().raw_data()) {
+            for idx in &script_group.input_indices {
+                let input_since: u64 = tx
+                    .inputs()
+                    .get(*idx)
+                    .map(|input| input.since().unpack())
+                    .unwrap_or_default();
+                if input_since != since.value() {
+                    return Err(ScriptSignError::MultisigSinceMismatch(
+                        *idx,
+                        input_since,
+                        since.value(),
+                    ));
+                }
+            }
+        }
         let witness_idx = script_group.input_indices[0];
         let mut witnesses: Vec<packed::Bytes> = tx.witnesses().into_iter().collect();
         while witnesses.len() <= witness_idx {
@@ -306,7 +435,12 @@ impl ScriptSigner for SecpMultisigScriptSigner {
         let config_data = self.config.to_witness_data();
         let mut zero_lock = vec![0u8; config_data.len() + 65 * (self.config.threshold as usize)];
         zero_lock[0..config_data.len()].copy_from_slice(&config_data);
-        let message = generate_message(&tx_new, script_group, Bytes::from(zero_lock.clone()))?;
+        let message = generate_message_with_params(
+            &tx_new,
+            script_group,
+            Bytes::from(zero_lock.clone()),
+            &self.chain_params,
+        )?;
 
         let signatures = self
             .config
@@ -316,13 +450,7 @@ impl ScriptSigner for SecpMultisigScriptSigner {
             .map(|id| self.signer.sign(id.as_bytes(), message.as_ref(), true, tx))
             .collect::<Result<Vec<_>, SignerError>>()?;
         // Put signature into witness
-        let witness_idx = script_group.input_indices[0];
-        let witness_data = witnesses[witness_idx].raw_data();
-        let mut current_witness: WitnessArgs = if witness_data.is_empty() {
-            WitnessArgs::default()
-        } else {
-            WitnessArgs::from_slice(witness_data.as_ref())?
-        };
+        let current_witness = load_witness_args(&tx_new, witness_idx)?;
         let mut lock_field = current_witness
             .lock()
             .to_opt()
@@ -352,12 +480,7 @@ impl ScriptSigner for SecpMultisigScriptSigner {
             }
         }
 
-        current_witness = current_witness
-            .as_builder()
-            .lock(Some(Bytes::from(lock_field)).pack())
-            .build();
-        witnesses[witness_idx] = current_witness.as_bytes().pack();
-        Ok(tx.as_advanced_builder().set_witnesses(witnesses).build())
+        update_witness_field(tx, witness_idx, WitnessField::Lock, Bytes::from(lock_field))
     }
 }
 
@@ -392,14 +515,69 @@ impl ScriptSigner for AcpScriptSigner {
     }
 }
 
+/// Signer for the pw-lock, the ethereum style lock which signs the keccak256
+/// sighash message as an ethereum personal message and has the
+/// anyone-can-pay behavior. The script args is the 20 bytes ethereum address
+/// (`keccak160(pubkey)`) with the optional acp minimums.
+pub struct PwLockScriptSigner {
+    // Can be: SecpCkbRawKeySigner with ethereum keys, HardwareWalletSigner
+    signer: Box<dyn Signer>,
+}
+
+impl PwLockScriptSigner {
+    pub fn new(signer: Box<dyn Signer>) -> PwLockScriptSigner {
+        PwLockScriptSigner { signer }
+    }
+
+    pub fn signer(&self) -> &dyn Signer {
+        self.signer.as_ref()
+    }
+}
+
+impl ScriptSigner for PwLockScriptSigner {
+    fn match_args(&self, args: &[u8]) -> bool {
+        args.len() >= 20 && args.len() <= 22 && self.signer.match_id(&args[0..20])
+    }
+
+    fn sign_tx(
+        &self,
+        tx: &TransactionView,
+        script_group: &ScriptGroup,
+    ) -> Result<TransactionView, ScriptSignError> {
+        let args = script_group.script.args().raw_data();
+        let witness_idx = script_group.input_indices[0];
+        let mut witnesses: Vec<packed::Bytes> = tx.witnesses().into_iter().collect();
+        while witnesses.len() <= witness_idx {
+            witnesses.push(Default::default());
+        }
+        let tx_new = tx
+            .as_advanced_builder()
+            .set_witnesses(witnesses.clone())
+            .build();
+
+        let zero_lock = Bytes::from(vec![0u8; 65]);
+        let message = generate_keccak256_message(&tx_new, script_group, zero_lock)?;
+        let message = convert_keccak256_hash(message.as_ref());
+        let signature = self.signer.sign(&args[0..20], message.as_ref(), true, tx)?;
+
+        // Put signature into witness
+        update_witness_field(tx, witness_idx, WitnessField::Lock, signature)
+    }
+}
+
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
 pub enum ChequeAction {
     Claim,
     Withdraw,
 }
+/// The since value of the cheque inputs to claim
+pub(crate) const CHEQUE_CLAIM_SINCE: u64 = 0;
+
 pub struct ChequeScriptSigner {
     sighash_signer: SecpSighashScriptSigner,
     action: ChequeAction,
+    auto_action: bool,
+    withdraw_since: Since,
 }
 impl ChequeScriptSigner {
     pub fn new(signer: Box<dyn Signer>, action: ChequeAction) -> ChequeScriptSigner {
@@ -407,20 +585,161 @@ impl ChequeScriptSigner {
         ChequeScriptSigner {
             sighash_signer,
             action,
+            auto_action: false,
+            withdraw_since: Since::from_raw_value(CHEQUE_CELL_SINCE),
         }
     }
+
+    /// Create a signer choosing the action by the transaction, see
+    /// `ChequeScriptSigner::action_for`.
+    pub fn new_auto(signer: Box<dyn Signer>) -> ChequeScriptSigner {
+        let mut cheque_signer = ChequeScriptSigner::new(signer, ChequeAction::Claim);
+        cheque_signer.auto_action = true;
+        cheque_signer
+    }
+
+    /// Create a withdraw signer for a cheque script deployed with another lock
+    /// period, `since` is the since value of the cheque inputs, it must be a
+    /// relative epoch since (e.g. `CHEQUE_CELL_SINCE` is relative 6 epochs).
+    pub fn new_with_withdraw_since(
+        signer: Box<dyn Signer>,
+        since: Since,
+    ) -> Result<ChequeScriptSigner, ScriptSignError> {
+        check_cheque_withdraw_since(since)?;
+        let mut cheque_signer = ChequeScriptSigner::new(signer, ChequeAction::Withdraw);
+        cheque_signer.withdraw_since = since;
+        Ok(cheque_signer)
+    }
     pub fn owner_id<'t>(&self, args: &'t [u8]) -> &'t [u8] {
         if args.len() != 40 {
             &args[0..0]
-        } else if self.action == ChequeAction::Claim {
-            &args[0..20]
+        } else if self.auto_action {
+            let signer = self.sighash_signer.signer();
+            if signer.match_id(&args[0..20]) {
+                &args[0..20]
+            } else if signer.match_id(&args[20..40]) {
+                &args[20..40]
+            } else {
+                &args[0..0]
+            }
         } else {
-            &args[20..40]
+            Self::action_owner_id(self.action, args)
+        }
+    }
+    fn action_owner_id(action: ChequeAction, args: &[u8]) -> &[u8] {
+        match action {
+            ChequeAction::Claim => &args[0..20],
+            ChequeAction::Withdraw => &args[20..40],
         }
     }
+    /// The action given to the constructor, it is a placeholder when the
+    /// action is chosen by the transaction (`is_auto()`).
     pub fn action(&self) -> ChequeAction {
         self.action
     }
+    pub fn is_auto(&self) -> bool {
+        self.auto_action
+    }
+
+    /// The action to sign the script group with.
+    ///
+    /// When the signer is created by `new_auto`, the action is inferred from
+    /// the cheque args and the since values of the group inputs:
+    ///   * the signer only has the receiver key (`args[0..20]`): claim, the
+    ///     since values must be `0`
+    ///   * the signer only has the sender key (`args[20..40]`): withdraw, the
+    ///     since values must be the withdraw since
+    ///   * the signer has both keys: chosen by the since values
+    ///
+    /// Otherwise the action given to the constructor is returned.
+    pub fn action_for(
+        &self,
+        tx: &TransactionView,
+        script_group: &ScriptGroup,
+    ) -> Result<ChequeAction, ScriptSignError> {
+        if !self.auto_action {
+            return Ok(self.action);
+        }
+        let args = script_group.script.args().raw_data();
+        if args.len() != 40 {
+            return Err(ScriptSignError::ChequeActionNotInferred(format!(
+                "invalid script args length, expected: 40, got: {}",
+                args.len()
+            )));
+        }
+        let signer = self.sighash_signer.signer();
+        let is_receiver = signer.match_id(&args[0..20]);
+        let is_sender = signer.match_id(&args[20..40]);
+        let inputs: Vec<_> = tx.inputs().into_iter().collect();
+        let mut since_list = Vec::with_capacity(script_group.input_indices.len());
+        for idx in &script_group.input_indices {
+            let input = inputs.get(*idx).ok_or_else(|| {
+                ScriptSignError::ChequeActionNotInferred(format!(
+                    "input index out of bound: {}",
+                    idx
+                ))
+            })?;
+            let since: u64 = input.since().unpack();
+            since_list.push((*idx, since));
+        }
+        let withdraw_since = self.withdraw_since.value();
+        let check_since = |action: ChequeAction, expected: u64| match since_list
+            .iter()
+            .find(|(_, since)| *since != expected)
+        {
+            Some((idx, since)) => Err(ScriptSignError::ChequeActionNotInferred(format!(
+                "{:?} action requires since {:#x}, the since of input {} is {:#x}",
+                action, expected, idx, since
+            ))),
+            None => Ok(action),
+        };
+        match (is_receiver, is_sender) {
+            (false, false) => Err(ScriptSignError::ChequeActionNotInferred(
+                "the signer is neither the receiver nor the sender".to_string(),
+            )),
+            (true, false) => check_since(ChequeAction::Claim, CHEQUE_CLAIM_SINCE),
+            (false, true) => check_since(ChequeAction::Withdraw, withdraw_since),
+            (true, true) => check_since(ChequeAction::Claim, CHEQUE_CLAIM_SINCE)
+                .or_else(|_| check_since(ChequeAction::Withdraw, withdraw_since)),
+        }
+    }
+    /// The since value of the cheque inputs to withdraw
+    pub fn withdraw_since(&self) -> Since {
+        self.withdraw_since
+    }
+}
+
+/// The withdraw since must be a relative epoch since with a well formed
+/// fraction: `index < length`, or both are zero for whole epochs.
+fn check_cheque_withdraw_since(since: Since) -> Result<(), ScriptSignError> {
+    let invalid = |reason: &str| {
+        Err(ScriptSignError::InvalidChequeWithdrawSince(format!(
+            "{:#x}, {}",
+            since.value(),
+            reason
+        )))
+    };
+    if !since.flags_is_valid() || since.is_absolute() {
+        return invalid("expected a relative since");
+    }
+    let epoch = match since.extract_metric() {
+        Some((SinceType::EpochNumberWithFraction, value)) => {
+            EpochNumberWithFraction::from_full_value(value)
+        }
+        _ => return invalid("expected an epoch since"),
+    };
+    let well_formed = if epoch.length() == 0 {
+        epoch.index() == 0
+    } else {
+        epoch.index() < epoch.length()
+    };
+    if !well_formed {
+        return invalid("the epoch index must be less than the epoch length");
+    }
+    if epoch.number() == 0 && epoch.index() == 0 {
+        return invalid("the lock period is zero");
+    }
+    Ok(())
 }
 
 impl ScriptSigner for ChequeScriptSigner {
@@ -435,7 +754,12 @@ impl ScriptSigner for ChequeScriptSigner {
         script_group: &ScriptGroup,
     ) -> Result<TransactionView, ScriptSignError> {
         let args = script_group.script.args().raw_data();
-        let id = self.owner_id(args.as_ref());
+        let id = if self.auto_action {
+            let action = self.action_for(tx, script_group)?;
+            Self::action_owner_id(action, args.as_ref())
+        } else {
+            self.owner_id(args.as_ref())
+        };
         self.sighash_signer
             .sign_tx_with_owner_id(id, tx, script_group)
     }
@@ -443,11 +767,63 @@ impl ScriptSigner for ChequeScriptSigner {
 
 /// Common logic of generate message for certain script group. Overwrite
 /// this method to support special use case.
+///
+/// The message is the same as the one the secp256k1 sighash-all lock script
+/// computes, it is a blake2b hash (ckb personalization, 32 bytes output) over:
+///
+/// 1. the transaction hash (32 bytes)
+/// 2. the witness of the group's first input (`witnesses[input_indices[0]]`),
+///    parsed as `WitnessArgs` with the `lock` field replaced by `zero_lock`,
+///    prefixed by its length as a little endian u64
+/// 3. the witnesses of the group's other inputs, in the order of
+///    `input_indices`, each as `len: u64 le | raw bytes` (these are not
+///    required to be `WitnessArgs`, missing ones are skipped)
+/// 4. the witnesses at index `tx.inputs().len()` and beyond (those not
+///    covered by any input), each as `len: u64 le | raw bytes`, no matter
+///    where the group's inputs are located
+///
+/// The witnesses of inputs from other groups are never hashed. The returned
+/// message is the 32 bytes hash.
 pub fn generate_message(
     tx: &TransactionView,
     script_group: &ScriptGroup,
     zero_lock: Bytes,
 ) -> Result<Bytes, ScriptSignError> {
+    generate_message_with_params(tx, script_group, zero_lock, &ChainParams::default())
+}
+
+/// Same as `generate_message`, but hash with the chain's blake2b
+/// personalization. The transaction hash is still computed by ckb-types.
+pub fn generate_message_with_params(
+    tx: &TransactionView,
+    script_group: &ScriptGroup,
+    zero_lock: Bytes,
+    chain_params: &ChainParams,
+) -> Result<Bytes, ScriptSignError> {
+    let mut blake2b = chain_params.new_blake2b();
+    update_sighash_all(tx, script_group, zero_lock, |data| blake2b.update(data))?;
+    let mut message = vec![0u8; 32];
+    blake2b.finalize(&mut message);
+    Ok(Bytes::from(message))
+}
+
+/// Same as `generate_message`, but hash with keccak256 as the pw-lock does.
+pub fn generate_keccak256_message(
+    tx: &TransactionView,
+    script_group: &ScriptGroup,
+    zero_lock: Bytes,
+) -> Result<Bytes, ScriptSignError> {
+    let mut hasher = Keccak256::new();
+    update_sighash_all(tx, script_group, zero_lock, |data| hasher.update(data))?;
+    Ok(Bytes::from(hasher.finalize().to_vec()))
+}
+
+fn update_sighash_all<F: FnMut(&[u8])>(
+    tx: &TransactionView,
+    script_group: &ScriptGroup,
+    zero_lock: Bytes,
+    mut update: F,
+) -> Result<(), ScriptSignError> {
     if tx.witnesses().item_count() <= script_group.input_indices[0] {
         return Err(ScriptSignError::WitnessNotEnough);
     }
@@ -491,25 +867,22 @@ pub fn generate_message(
         Default::default()
     };
 
-    let mut blake2b = new_blake2b();
-    blake2b.update(tx.hash().as_slice());
-    blake2b.update(&(init_witness.as_bytes().len() as u64).to_le_bytes());
-    blake2b.update(&init_witness.as_bytes());
+    update(tx.hash().as_slice());
+    update(&(init_witness.as_bytes().len() as u64).to_le_bytes());
+    update(&init_witness.as_bytes());
     for (len_le, data) in other_witnesses {
-        blake2b.update(&len_le);
-        blake2b.update(&data);
+        update(&len_le);
+        update(&data);
     }
     for (len_le, data) in outter_witnesses {
-        blake2b.update(&len_le);
-        blake2b.update(&data);
+        update(&len_le);
+        update(&data);
     }
-    let mut message = vec![0u8; 32];
-    blake2b.finalize(&mut message);
-    Ok(Bytes::from(message))
+    Ok(())
 }
 
 /// specify the unlock mode for a omnilock transaction.
-#[derive(Clone, Copy, Eq, PartialEq, Debug, Hash, Default)]
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Hash, Default, Serialize, Deserialize)]
 pub enum OmniUnlockMode {
     /// Use the normal mode to unlock the omnilock transaction.
     #[default]
@@ -518,10 +891,30 @@ pub enum OmniUnlockMode {
     Admin = 2,
 }
 
+/// Provide the signature checked by the auth script of an exec or dl
+/// omni-lock, see `OmniLockConfig::new_exec_auth`.
+///
+/// The message is generated and the signature is put into the witness with
+/// the preimage by `OmniLockScriptSigner`, the signature must be
+/// `ExecDlConfig::sig_len` bytes.
+pub trait AuthWitnessProvider {
+    /// If the signature of `pubkey_hash` in the preimage can be provided
+    fn match_pubkey_hash(&self, pubkey_hash: &H160) -> bool;
+
+    /// The signature of `message` required by the auth script
+    fn sign(
+        &self,
+        pubkey_hash: &H160,
+        message: &[u8],
+        tx: &TransactionView,
+    ) -> Result<Bytes, SignerError>;
+}
+
 pub struct OmniLockScriptSigner {
     signer: Box<dyn Signer>,
     config: OmniLockConfig,
     unlock_mode: OmniUnlockMode,
+    auth_witness_provider: Option<Box<dyn AuthWitnessProvider>>,
 }
 
 impl OmniLockScriptSigner {
@@ -534,8 +927,17 @@ impl OmniLockScriptSigner {
             signer,
             config,
             unlock_mode,
+            auth_witness_provider: None,
         }
     }
+    /// Set the provider of the signature of an exec or dl auth
+    pub fn with_auth_witness_provider(
+        mut self,
+        auth_witness_provider: Box<dyn AuthWitnessProvider>,
+    ) -> OmniLockScriptSigner {
+        self.auth_witness_provider = Some(auth_witness_provider);
+        self
+    }
     pub fn signer(&self) -> &dyn Signer {
         self.signer.as_ref()
     }
@@ -562,7 +964,9 @@ impl OmniLockScriptSigner {
             .set_witnesses(witnesses.clone())
             .build();
 
-        let zero_lock = self.config.zero_lock(self.unlock_mode)?;
+        let zero_lock = self
+            .config
+            .zero_lock_for_args(self.unlock_mode, &script_group.script.args().raw_data())?;
         let zero_lock_len = zero_lock.len();
         let message = generate_message(&tx_new, script_group, zero_lock)?;
 
@@ -582,13 +986,7 @@ impl OmniLockScriptSigner {
             .map(|id| self.signer.sign(id.as_bytes(), message.as_ref(), true, tx))
             .collect::<Result<Vec<_>, SignerError>>()?;
         // Put signature into witness
-        let witness_idx = script_group.input_indices[0];
-        let witness_data = witnesses[witness_idx].raw_data();
-        let mut current_witness: WitnessArgs = if witness_data.is_empty() {
-            WitnessArgs::default()
-        } else {
-            WitnessArgs::from_slice(witness_data.as_ref())?
-        };
+        let current_witness = load_witness_args(&tx_new, witness_idx)?;
         let lock_field = current_witness.lock().to_opt().map(|data| data.raw_data());
         let omnilock_witnesslock = if let Some(lock_field) = lock_field {
             if lock_field.len() != zero_lock_len {
@@ -598,7 +996,7 @@ impl OmniLockScriptSigner {
                     zero_lock_len,
                 )));
             }
-            OmniLockWitnessLock::from_slice(lock_field.as_ref())?
+            OmniLockWitnessLock::parse(lock_field.as_ref())?
         } else {
             OmniLockWitnessLock::default()
         };
@@ -635,9 +1033,7 @@ impl OmniLockScriptSigner {
             .build()
             .as_bytes();
 
-        current_witness = current_witness.as_builder().lock(Some(lock).pack()).build();
-        witnesses[witness_idx] = current_witness.as_bytes().pack();
-        Ok(tx.as_advanced_builder().set_witnesses(witnesses).build())
+        update_witness_field(tx, witness_idx, WitnessField::Lock, lock)
     }
 
     fn sign_ethereum_tx(
@@ -656,7 +1052,9 @@ impl OmniLockScriptSigner {
             .set_witnesses(witnesses.clone())
             .build();
 
-        let zero_lock = self.config.zero_lock(self.unlock_mode())?;
+        let zero_lock = self
+            .config
+            .zero_lock_for_args(self.unlock_mode(), &script_group.script.args().raw_data())?;
         let message = generate_message(&tx_new, script_group, zero_lock)?;
         let message = convert_keccak256_hash(message.as_ref());
 
@@ -665,17 +1063,58 @@ impl OmniLockScriptSigner {
             .sign(id.auth_content().as_ref(), message.as_ref(), true, tx)?;
 
         // Put signature into witness
-        let witness_data = witnesses[witness_idx].raw_data();
-        let mut current_witness: WitnessArgs = if witness_data.is_empty() {
-            WitnessArgs::default()
-        } else {
-            WitnessArgs::from_slice(witness_data.as_ref())?
-        };
-
+        let current_witness = load_witness_args(&tx_new, witness_idx)?;
         let lock = Self::build_witness_lock(current_witness.lock(), signature)?;
-        current_witness = current_witness.as_builder().lock(Some(lock).pack()).build();
-        witnesses[witness_idx] = current_witness.as_bytes().pack();
-        Ok(tx.as_advanced_builder().set_witnesses(witnesses).build())
+        update_witness_field(tx, witness_idx, WitnessField::Lock, lock)
+    }
+
+    fn sign_exec_dl_tx(
+        &self,
+        tx: &TransactionView,
+        script_group: &ScriptGroup,
+    ) -> Result<TransactionView, ScriptSignError> {
+        let exec_dl_config = self
+            .config
+            .get_exec_dl_config()
+            .ok_or(ConfigError::NoExecDlConfig)?;
+        let auth_witness_provider = self.auth_witness_provider.as_ref().ok_or_else(|| {
+            ScriptSignError::Other(anyhow!("no auth witness provider for the exec or dl auth"))
+        })?;
+        let witness_idx = script_group.input_indices[0];
+        let mut witnesses: Vec<packed::Bytes> = tx.witnesses().into_iter().collect();
+        while witnesses.len() <= witness_idx {
+            witnesses.push(Default::default());
+        }
+        let tx_new = tx
+            .as_advanced_builder()
+            .set_witnesses(witnesses.clone())
+            .build();
+
+        let zero_lock = self
+            .config
+            .zero_lock_for_args(self.unlock_mode, &script_group.script.args().raw_data())?;
+        let message = generate_message(&tx_new, script_group, zero_lock)?;
+        let signature =
+            auth_witness_provider.sign(&exec_dl_config.pubkey_hash(), message.as_ref(), tx)?;
+        if signature.len() != exec_dl_config.sig_len() {
+            return Err(ScriptSignError::Other(anyhow!(
+                "invalid auth signature length: {}, expected: {}",
+                signature.len(),
+                exec_dl_config.sig_len(),
+            )));
+        }
+
+        // Put signature and preimage into witness
+        let current_witness = load_witness_args(&tx_new, witness_idx)?;
+        let lock = OmniLockWitnessLock::parse(&Self::build_witness_lock(
+            current_witness.lock(),
+            signature,
+        )?)?
+        .as_builder()
+        .preimage(Some(exec_dl_config.preimage().clone()).pack())
+        .build()
+        .as_bytes();
+        update_witness_field(tx, witness_idx, WitnessField::Lock, lock)
     }
 
     /// Build proper witness lock
@@ -685,7 +1124,7 @@ impl OmniLockScriptSigner {
     ) -> Result<Bytes, ScriptSignError> {
         let lock_field = orig_lock.to_opt().map(|data| data.raw_data());
         let omnilock_witnesslock = if let Some(lock_field) = lock_field {
-            OmniLockWitnessLock::from_slice(lock_field.as_ref())?
+            OmniLockWitnessLock::parse(lock_field.as_ref())?
         } else {
             OmniLockWitnessLock::default()
         };
@@ -748,6 +1187,19 @@ impl ScriptSigner for OmniLockScriptSigner {
                 // should not reach here, return true for compatible reason
                 true
             }
+            IdentityFlag::Exec | IdentityFlag::Dl => {
+                match (
+                    self.config.get_exec_dl_config(),
+                    self.auth_witness_provider.as_ref(),
+                ) {
+                    (Some(exec_dl_config), Some(auth_witness_provider)) => {
+                        self.config.id().auth_content().as_ref() == &args[1..21]
+                            && auth_witness_provider
+                                .match_pubkey_hash(&exec_dl_config.pubkey_hash())
+                    }
+                    _ => false,
+                }
+            }
             _ => todo!("other auth type not supported yet"),
         }
     }
@@ -778,7 +1230,9 @@ impl ScriptSigner for OmniLockScriptSigner {
                     .set_witnesses(witnesses.clone())
                     .build();
 
-                let zero_lock = self.config.zero_lock(self.unlock_mode)?;
+                let zero_lock = self
+                    .config
+                    .zero_lock_for_args(self.unlock_mode, &script_group.script.args().raw_data())?;
                 let message = generate_message(&tx_new, script_group, zero_lock)?;
 
                 let signature =
@@ -786,21 +1240,13 @@ impl ScriptSigner for OmniLockScriptSigner {
                         .sign(id.auth_content().as_ref(), message.as_ref(), true, tx)?;
 
                 // Put signature into witness
-                let witness_data = witnesses[witness_idx].raw_data();
-                let mut current_witness: WitnessArgs = if witness_data.is_empty() {
-                    WitnessArgs::default()
-                } else {
-                    WitnessArgs::from_slice(witness_data.as_ref())?
-                };
-
+                let current_witness = load_witness_args(&tx_new, witness_idx)?;
                 let lock = Self::build_witness_lock(current_witness.lock(), signature)?;
-
-                current_witness = current_witness.as_builder().lock(Some(lock).pack()).build();
-                witnesses[witness_idx] = current_witness.as_bytes().pack();
-                Ok(tx.as_advanced_builder().set_witnesses(witnesses).build())
+                update_witness_field(tx, witness_idx, WitnessField::Lock, lock)
             }
             IdentityFlag::Ethereum => self.sign_ethereum_tx(tx, script_group, &id),
             IdentityFlag::Multisig => self.sign_multisig_tx(tx, script_group),
+            IdentityFlag::Exec | IdentityFlag::Dl => self.sign_exec_dl_tx(tx, script_group),
             IdentityFlag::OwnerLock => {
                 // should not reach here, just return a clone for compatible reason.
                 Ok(tx.clone())
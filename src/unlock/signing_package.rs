@@ -0,0 +1,525 @@
+//! A partially signed transaction passed between the signers of a multi-party
+//! transaction, with everything an offline signer needs.
+use std::sync::Arc;
+
+use ckb_jsonrpc_types::{self as json_types, JsonBytes};
+use ckb_types::{
+    bytes::Bytes,
+    core::TransactionView,
+    packed::{self, WitnessArgs},
+    prelude::*,
+    H256,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{
+    load_witness_args, update_witness_field, IdentityFlag, MultisigConfig, OmniLockConfig,
+    OmniLockScriptSigner, OmniUnlockMode, ScriptSignError, ScriptSigner, SecpMultisigScriptSigner,
+    SecpSighashScriptSigner, WitnessField,
+};
+use crate::mock_tx::MockTxDependencyProvider;
+use crate::traits::{
+    Signer, SignerError, TransactionDependencyError, TransactionDependencyProvider,
+};
+use crate::tx_builder::gen_script_groups;
+use crate::types::{omni_lock::OmniLockWitnessLock, ScriptGroup, ScriptHashTypeExt, ScriptId};
+
+/// The version of the signing package format, bumped on every incompatible
+/// change of the JSON layout.
+pub const SIGNING_PACKAGE_VERSION: u32 = 1;
+
+const SIGNATURE_SIZE: usize = 65;
+
+#[derive(Error, Debug)]
+pub enum SigningPackageError {
+    #[error("unsupported signing package version: `{0}`")]
+    UnsupportedVersion(u32),
+
+    #[error("invalid signing package: `{0}`")]
+    InvalidPackage(String),
+
+    #[error("transaction dependency error: `{0}`")]
+    TxDep(#[from] TransactionDependencyError),
+
+    #[error("sign script error: `{0}`")]
+    ScriptSign(#[from] ScriptSignError),
+
+    #[error("the transaction `{1:#x}` to merge differs from `{0:#x}`")]
+    TransactionMismatch(H256, H256),
+
+    #[error("invalid witness of the lock group `{0:#x}`: `{1}`")]
+    InvalidWitness(H256, String),
+
+    #[error("the lock groups are not fully signed: `{0:?}`")]
+    Unsigned(Vec<H256>),
+}
+
+/// How the lock scripts of a script id are signed
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SigningDescriptor {
+    /// The secp256k1 sighash all lock
+    Sighash,
+    /// The secp256k1 multisig all lock
+    Multisig { config: MultisigConfig },
+    /// The omni lock
+    OmniLock {
+        config: OmniLockConfig,
+        unlock_mode: OmniUnlockMode,
+    },
+}
+
+/// The signing descriptor of a lock script id
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct LockDescriptor {
+    pub code_hash: H256,
+    pub hash_type: json_types::ScriptHashType,
+    pub descriptor: SigningDescriptor,
+}
+
+impl LockDescriptor {
+    pub fn script_id(&self) -> ScriptId {
+        ScriptId::new(
+            self.code_hash.clone(),
+            ckb_types::core::ScriptHashType::from_json(self.hash_type.clone()),
+        )
+    }
+}
+
+/// The cell spent by an input
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct SigningInput {
+    pub output: json_types::CellOutput,
+    pub data: JsonBytes,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum SignatureStatus {
+    Unsigned,
+    /// Some of the signatures of a multisig lock
+    Partial {
+        signatures: u8,
+        threshold: u8,
+    },
+    Signed,
+    /// No descriptor for the lock script, the group is not checked
+    Unknown,
+}
+
+/// The signature status of a lock script group
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct GroupStatus {
+    pub lock_hash: H256,
+    pub input_indices: Vec<usize>,
+    pub status: SignatureStatus,
+}
+
+/// A partially signed transaction, every signer adds signatures to it by
+/// `sign_with`, the packages signed in parallel are combined by `merge`:
+///
+/// ```ignore
+/// let package = SigningPackage::new(&tx, &tx_dep_provider, descriptors)?;
+/// let json = serde_json::to_string(&package)?;
+/// // on every signer
+/// let mut package: SigningPackage = serde_json::from_str(&json)?;
+/// package.sign_with(Box::new(signer))?;
+/// // back to the coordinator
+/// package_a.merge(&package_b)?;
+/// let tx = package_a.finalize()?;
+/// ```
+///
+/// The witnesses of the transaction must be filled with the placeholders
+/// before, e.g. by `TxBuilder::build_balanced`.
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct SigningPackage {
+    pub version: u32,
+    pub transaction: json_types::Transaction,
+    /// The cell of every input, in the order of the inputs
+    pub inputs: Vec<SigningInput>,
+    pub descriptors: Vec<LockDescriptor>,
+    /// The lock script groups in the order of their first input, updated by
+    /// every change of the witnesses
+    pub groups: Vec<GroupStatus>,
+}
+
+impl SigningPackage {
+    /// The input cells are resolved by `tx_dep_provider`
+    pub fn new(
+        tx: &TransactionView,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
+        descriptors: Vec<(ScriptId, SigningDescriptor)>,
+    ) -> Result<SigningPackage, SigningPackageError> {
+        let mut inputs = Vec::with_capacity(tx.inputs().len());
+        for out_point in tx.input_pts_iter() {
+            inputs.push(SigningInput {
+                output: tx_dep_provider.get_cell(&out_point)?.into(),
+                data: JsonBytes::from_bytes(tx_dep_provider.get_cell_data(&out_point)?),
+            });
+        }
+        let descriptors = descriptors
+            .into_iter()
+            .map(|(script_id, descriptor)| LockDescriptor {
+                code_hash: script_id.code_hash,
+                hash_type: script_id.hash_type.to_json(),
+                descriptor,
+            })
+            .collect();
+        let mut package = SigningPackage {
+            version: SIGNING_PACKAGE_VERSION,
+            transaction: tx.data().into(),
+            inputs,
+            descriptors,
+            groups: Vec::new(),
+        };
+        package.refresh_status()?;
+        Ok(package)
+    }
+
+    pub fn tx(&self) -> TransactionView {
+        packed::Transaction::from(self.transaction.clone()).into_view()
+    }
+
+    /// Sign every lock script group `signer` has a key of, the signatures
+    /// already there are kept. Returns the number of the signed groups.
+    pub fn sign_with(&mut self, signer: Box<dyn Signer>) -> Result<usize, SigningPackageError> {
+        let signer: Arc<dyn Signer> = Arc::from(signer);
+        let mut tx = self.tx();
+        let mut signed = 0;
+        for (group, descriptor) in self.lock_groups(&tx)? {
+            let descriptor = match descriptor {
+                Some(descriptor) => descriptor,
+                None => continue,
+            };
+            let status = group_status(&tx, &group, Some(&descriptor))?;
+            if status == SignatureStatus::Signed {
+                continue;
+            }
+            let shared = Box::new(SharedSigner(signer.clone()));
+            let script_signer: Box<dyn ScriptSigner> = match descriptor {
+                SigningDescriptor::Sighash => Box::new(SecpSighashScriptSigner::new(shared)),
+                SigningDescriptor::Multisig { config } => {
+                    Box::new(SecpMultisigScriptSigner::new(shared, config))
+                }
+                SigningDescriptor::OmniLock {
+                    config,
+                    unlock_mode,
+                } => Box::new(OmniLockScriptSigner::new(shared, config, unlock_mode)),
+            };
+            if !script_signer.match_args(&group.script.args().raw_data()) {
+                continue;
+            }
+            tx = script_signer.sign_tx(&tx, &group)?;
+            signed += 1;
+        }
+        self.transaction = tx.data().into();
+        self.refresh_status()?;
+        Ok(signed)
+    }
+
+    /// Add the signatures in `other`, a package of the same transaction
+    /// signed in parallel.
+    pub fn merge(&mut self, other: &SigningPackage) -> Result<(), SigningPackageError> {
+        self.check_version()?;
+        other.check_version()?;
+        let tx = self.tx();
+        let other_tx = other.tx();
+        if tx.hash() != other_tx.hash() {
+            return Err(SigningPackageError::TransactionMismatch(
+                tx.hash().unpack(),
+                other_tx.hash().unpack(),
+            ));
+        }
+        for descriptor in &other.descriptors {
+            if !self.descriptors.contains(descriptor) {
+                self.descriptors.push(descriptor.clone());
+            }
+        }
+
+        let mut tx = tx;
+        for (group, descriptor) in self.lock_groups(&tx)? {
+            let witness_idx = group.input_indices[0];
+            let lock_hash: H256 = group.script.calc_script_hash().unpack();
+            let ours = lock_field(&tx, witness_idx, &lock_hash)?;
+            let theirs = lock_field(&other_tx, witness_idx, &lock_hash)?;
+            let merged = match (ours, theirs) {
+                (_, None) => continue,
+                (None, Some(theirs)) => theirs,
+                (Some(ours), Some(theirs)) if ours == theirs => continue,
+                (Some(ours), Some(theirs)) => {
+                    merge_lock_field(&ours, &theirs, descriptor.as_ref(), &lock_hash)?
+                }
+            };
+            tx = update_witness_field(&tx, witness_idx, WitnessField::Lock, merged)?;
+        }
+        self.transaction = tx.data().into();
+        self.refresh_status()
+    }
+
+    /// The signed transaction, fails with the lock hashes of the groups not
+    /// fully signed. The groups without a descriptor are not checked.
+    pub fn finalize(&self) -> Result<TransactionView, SigningPackageError> {
+        self.check_version()?;
+        let tx = self.tx();
+        let mut unsigned: Vec<H256> = Vec::new();
+        for (group, descriptor) in self.lock_groups(&tx)? {
+            match group_status(&tx, &group, descriptor.as_ref())? {
+                SignatureStatus::Signed | SignatureStatus::Unknown => {}
+                _ => unsigned.push(group.script.calc_script_hash().unpack()),
+            }
+        }
+        if unsigned.is_empty() {
+            Ok(tx)
+        } else {
+            Err(SigningPackageError::Unsigned(unsigned))
+        }
+    }
+
+    fn check_version(&self) -> Result<(), SigningPackageError> {
+        if self.version != SIGNING_PACKAGE_VERSION {
+            return Err(SigningPackageError::UnsupportedVersion(self.version));
+        }
+        Ok(())
+    }
+
+    fn refresh_status(&mut self) -> Result<(), SigningPackageError> {
+        let tx = self.tx();
+        let mut groups = Vec::new();
+        for (group, descriptor) in self.lock_groups(&tx)? {
+            groups.push(GroupStatus {
+                lock_hash: group.script.calc_script_hash().unpack(),
+                status: group_status(&tx, &group, descriptor.as_ref())?,
+                input_indices: group.input_indices,
+            });
+        }
+        self.groups = groups;
+        Ok(())
+    }
+
+    // The lock script groups with their descriptors, in the order of their
+    // first input
+    fn lock_groups(
+        &self,
+        tx: &TransactionView,
+    ) -> Result<Vec<(ScriptGroup, Option<SigningDescriptor>)>, SigningPackageError> {
+        self.check_version()?;
+        if self.inputs.len() != tx.inputs().len() {
+            return Err(SigningPackageError::InvalidPackage(format!(
+                "{} input cells for {} inputs",
+                self.inputs.len(),
+                tx.inputs().len()
+            )));
+        }
+        let mut provider = MockTxDependencyProvider::default();
+        for (out_point, input) in tx.input_pts_iter().zip(self.inputs.iter()) {
+            provider.cells.insert(
+                (out_point.tx_hash().unpack(), out_point.index().unpack()),
+                (input.output.clone().into(), input.data.clone().into_bytes()),
+            );
+        }
+        let mut groups: Vec<ScriptGroup> = gen_script_groups(tx, &provider)?
+            .lock_groups
+            .into_values()
+            .collect();
+        groups.sort_by_key(|group| group.input_indices[0]);
+        Ok(groups
+            .into_iter()
+            .map(|group| {
+                let script_id = ScriptId::from(&group.script);
+                let descriptor = self
+                    .descriptors
+                    .iter()
+                    .find(|item| item.script_id() == script_id)
+                    .map(|item| item.descriptor.clone());
+                (group, descriptor)
+            })
+            .collect())
+    }
+}
+
+/// Share one signer among the script signers of a package
+struct SharedSigner(Arc<dyn Signer>);
+
+impl Signer for SharedSigner {
+    fn match_id(&self, id: &[u8]) -> bool {
+        self.0.match_id(id)
+    }
+
+    fn sign(
+        &self,
+        id: &[u8],
+        message: &[u8],
+        recoverable: bool,
+        tx: &TransactionView,
+    ) -> Result<Bytes, SignerError> {
+        self.0.sign(id, message, recoverable, tx)
+    }
+}
+
+fn lock_field(
+    tx: &TransactionView,
+    witness_idx: usize,
+    lock_hash: &H256,
+) -> Result<Option<Bytes>, SigningPackageError> {
+    let witness: WitnessArgs = load_witness_args(tx, witness_idx)
+        .map_err(|err| SigningPackageError::InvalidWitness(lock_hash.clone(), err.to_string()))?;
+    Ok(witness.lock().to_opt().map(|data| data.raw_data()))
+}
+
+// The multisig config when the group is signed by a multisig
+fn multisig_config(descriptor: &SigningDescriptor) -> Option<&MultisigConfig> {
+    match descriptor {
+        SigningDescriptor::Sighash => None,
+        SigningDescriptor::Multisig { config } => Some(config),
+        SigningDescriptor::OmniLock {
+            config,
+            unlock_mode: OmniUnlockMode::Normal,
+        } => Some(config)
+            .filter(|config| config.id().flag() == IdentityFlag::Multisig)
+            .and_then(|config| config.multisig_config()),
+        SigningDescriptor::OmniLock {
+            config,
+            unlock_mode: OmniUnlockMode::Admin,
+        } => config
+            .get_admin_config()
+            .filter(|admin_config| admin_config.get_auth().flag() == IdentityFlag::Multisig)
+            .and_then(|admin_config| admin_config.get_multisig_config()),
+    }
+}
+
+// The signature part of the lock field: the lock field itself, or the
+// signature field of the omni lock witness lock
+fn signature_part(
+    lock: &Bytes,
+    descriptor: &SigningDescriptor,
+) -> Result<Option<Bytes>, ScriptSignError> {
+    match descriptor {
+        SigningDescriptor::OmniLock { .. } => {
+            let omni_lock = OmniLockWitnessLock::from_slice(lock.as_ref())?;
+            Ok(omni_lock.signature().to_opt().map(|data| data.raw_data()))
+        }
+        _ => Ok(Some(lock.clone())),
+    }
+}
+
+fn is_signature(slot: &[u8]) -> bool {
+    slot.iter().any(|byte| *byte != 0)
+}
+
+fn group_status(
+    tx: &TransactionView,
+    group: &ScriptGroup,
+    descriptor: Option<&SigningDescriptor>,
+) -> Result<SignatureStatus, SigningPackageError> {
+    let descriptor = match descriptor {
+        Some(descriptor) => descriptor,
+        None => return Ok(SignatureStatus::Unknown),
+    };
+    let lock_hash: H256 = group.script.calc_script_hash().unpack();
+    let lock = match lock_field(tx, group.input_indices[0], &lock_hash)? {
+        Some(lock) => lock,
+        None => return Ok(SignatureStatus::Unsigned),
+    };
+    let signature = match signature_part(&lock, descriptor)
+        .map_err(|err| SigningPackageError::InvalidWitness(lock_hash.clone(), err.to_string()))?
+    {
+        Some(signature) => signature,
+        None => return Ok(SignatureStatus::Unsigned),
+    };
+    let config = match multisig_config(descriptor) {
+        Some(config) => config,
+        None if is_signature(&signature) => return Ok(SignatureStatus::Signed),
+        None => return Ok(SignatureStatus::Unsigned),
+    };
+    let config_len = config.to_witness_data().len();
+    let threshold = config.threshold();
+    if signature.len() != config_len + threshold as usize * SIGNATURE_SIZE {
+        return Err(SigningPackageError::InvalidWitness(
+            lock_hash,
+            format!("invalid multisig signature length: {}", signature.len()),
+        ));
+    }
+    let signatures = signature[config_len..]
+        .chunks(SIGNATURE_SIZE)
+        .filter(|slot| is_signature(slot))
+        .count() as u8;
+    Ok(if signatures == 0 {
+        SignatureStatus::Unsigned
+    } else if signatures >= threshold {
+        SignatureStatus::Signed
+    } else {
+        SignatureStatus::Partial {
+            signatures,
+            threshold,
+        }
+    })
+}
+
+// Add the multisig signatures in `theirs` to the empty slots of `ours`, a
+// single signature lock takes `theirs` when `ours` is not signed.
+fn merge_lock_field(
+    ours: &Bytes,
+    theirs: &Bytes,
+    descriptor: Option<&SigningDescriptor>,
+    lock_hash: &H256,
+) -> Result<Bytes, SigningPackageError> {
+    let invalid_witness = |err: ScriptSignError| {
+        SigningPackageError::InvalidWitness(lock_hash.clone(), err.to_string())
+    };
+    let descriptor = match descriptor {
+        Some(descriptor) => descriptor,
+        None => return Ok(ours.clone()),
+    };
+    let our_signature = signature_part(ours, descriptor).map_err(invalid_witness)?;
+    let their_signature = signature_part(theirs, descriptor).map_err(invalid_witness)?;
+    let (our_signature, their_signature) = match (our_signature, their_signature) {
+        (_, None) => return Ok(ours.clone()),
+        (None, Some(_)) => return Ok(theirs.clone()),
+        (Some(our_signature), Some(their_signature)) => (our_signature, their_signature),
+    };
+    let config_len = match multisig_config(descriptor) {
+        Some(config) => config.to_witness_data().len(),
+        None if is_signature(&our_signature) => return Ok(ours.clone()),
+        None => return Ok(theirs.clone()),
+    };
+    if our_signature.len() != their_signature.len() || our_signature.len() < config_len {
+        return Err(SigningPackageError::InvalidWitness(
+            lock_hash.clone(),
+            "multisig signature length mismatch".to_string(),
+        ));
+    }
+
+    let mut merged = our_signature.to_vec();
+    for slot in their_signature[config_len..].chunks(SIGNATURE_SIZE) {
+        if !is_signature(slot) {
+            continue;
+        }
+        let mut placed = false;
+        for our_slot in merged[config_len..].chunks_mut(SIGNATURE_SIZE) {
+            if our_slot == slot {
+                placed = true;
+                break;
+            } else if !is_signature(our_slot) {
+                our_slot.copy_from_slice(slot);
+                placed = true;
+                break;
+            }
+        }
+        if !placed {
+            return Err(ScriptSignError::TooManySignatures.into());
+        }
+    }
+    match descriptor {
+        SigningDescriptor::OmniLock { .. } => {
+            let omni_lock = OmniLockWitnessLock::from_slice(ours.as_ref())
+                .map_err(|err| invalid_witness(err.into()))?;
+            Ok(omni_lock
+                .as_builder()
+                .signature(Some(Bytes::from(merged)).pack())
+                .build()
+                .as_bytes())
+        }
+        _ => Ok(Bytes::from(merged)),
+    }
+}
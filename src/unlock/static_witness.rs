@@ -0,0 +1,120 @@
+use ckb_types::{core::TransactionView, packed::WitnessArgs, prelude::*};
+
+use super::{update_witness_field, ScriptUnlocker, UnlockError, WitnessField};
+use crate::traits::TransactionDependencyProvider;
+use crate::types::{ScriptGroup, ScriptId};
+
+/// Build the final witness of the first input of the script group, the
+/// transaction passed in already has the placeholder witness.
+pub type WitnessBuilderFn =
+    dyn Fn(&TransactionView, &ScriptGroup) -> Result<WitnessArgs, UnlockError> + Send + Sync;
+
+/// An unlocker installing fixed witnesses, so the transaction builders can be
+/// used with a lock script which does not have an unlocker yet (e.g. when
+/// prototyping a new lock script):
+///   * `fill_placeholder_witness` installs the placeholder witness, the fee is
+///     estimated with its size
+///   * `unlock` installs the witness built by the witness builder, or the
+///     placeholder witness when there is no witness builder
+///
+/// Only the fields set in the installed witness are replaced, the other
+/// fields of the current witness are kept.
+///
+/// The script args are not checked, every script of the `ScriptId` is
+/// accepted.
+pub struct StaticWitnessUnlocker {
+    script_id: ScriptId,
+    placeholder_witness: WitnessArgs,
+    witness_builder: Option<Box<WitnessBuilderFn>>,
+}
+
+impl StaticWitnessUnlocker {
+    pub fn new(script_id: ScriptId, placeholder_witness: WitnessArgs) -> StaticWitnessUnlocker {
+        StaticWitnessUnlocker {
+            script_id,
+            placeholder_witness,
+            witness_builder: None,
+        }
+    }
+
+    /// Build the final witness by `witness_builder` instead of installing the
+    /// placeholder witness.
+    pub fn with_witness_builder<F>(mut self, witness_builder: F) -> StaticWitnessUnlocker
+    where
+        F: Fn(&TransactionView, &ScriptGroup) -> Result<WitnessArgs, UnlockError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.witness_builder = Some(Box::new(witness_builder));
+        self
+    }
+
+    pub fn script_id(&self) -> &ScriptId {
+        &self.script_id
+    }
+
+    pub fn placeholder_witness(&self) -> &WitnessArgs {
+        &self.placeholder_witness
+    }
+
+    fn check_script_id(&self, script_group: &ScriptGroup) -> Result<(), UnlockError> {
+        if ScriptId::from(&script_group.script) != self.script_id {
+            return Err(UnlockError::UnlockerArgsMismatch {
+                script_id: ScriptId::from(&script_group.script),
+            });
+        }
+        Ok(())
+    }
+}
+
+// The fields set in `witness` replace the fields of the current witness, the
+// other fields are kept.
+fn update_witness_fields(
+    tx: &TransactionView,
+    witness_idx: usize,
+    witness: &WitnessArgs,
+) -> Result<TransactionView, UnlockError> {
+    let fields = [
+        (WitnessField::Lock, witness.lock()),
+        (WitnessField::InputType, witness.input_type()),
+        (WitnessField::OutputType, witness.output_type()),
+    ];
+    let mut tx = tx.clone();
+    for (field, data) in fields {
+        if let Some(data) = data.to_opt() {
+            tx = update_witness_field(&tx, witness_idx, field, data.raw_data())?;
+        }
+    }
+    Ok(tx)
+}
+
+impl ScriptUnlocker for StaticWitnessUnlocker {
+    fn match_args(&self, _args: &[u8]) -> bool {
+        true
+    }
+
+    fn unlock(
+        &self,
+        tx: &TransactionView,
+        script_group: &ScriptGroup,
+        _tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, UnlockError> {
+        self.check_script_id(script_group)?;
+        let witness = match self.witness_builder.as_ref() {
+            Some(witness_builder) => witness_builder(tx, script_group)?,
+            None => self.placeholder_witness.clone(),
+        };
+        update_witness_fields(tx, script_group.input_indices[0], &witness)
+    }
+
+    fn fill_placeholder_witness(
+        &self,
+        tx: &TransactionView,
+        script_group: &ScriptGroup,
+        _tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, UnlockError> {
+        self.check_script_id(script_group)?;
+        update_witness_fields(tx, script_group.input_indices[0], &self.placeholder_witness)
+    }
+}
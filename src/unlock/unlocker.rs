@@ -1,25 +1,26 @@
+use std::collections::HashMap;
+
 use anyhow::anyhow;
 use ckb_types::{
     bytes::Bytes,
     core::TransactionView,
     packed::{self, Byte32, BytesOpt, WitnessArgs},
     prelude::*,
+    H256,
 };
 use thiserror::Error;
 
 use super::{
     omni_lock::{ConfigError, OmniLockFlags},
     signer::{
-        AcpScriptSigner, ChequeAction, ChequeScriptSigner, MultisigConfig, ScriptSignError,
-        ScriptSigner, SecpMultisigScriptSigner, SecpSighashScriptSigner,
+        AcpScriptSigner, ChequeAction, ChequeScriptSigner, MultisigConfig, PwLockScriptSigner,
+        ScriptSignError, ScriptSigner, SecpMultisigScriptSigner, SecpSighashScriptSigner,
+        CHEQUE_CLAIM_SINCE,
     },
     OmniLockConfig, OmniLockScriptSigner, OmniUnlockMode,
 };
 use crate::traits::{Signer, TransactionDependencyError, TransactionDependencyProvider};
-use crate::types::ScriptGroup;
-
-const CHEQUE_CLAIM_SINCE: u64 = 0;
-const CHEQUE_WITHDRAW_SINCE: u64 = 0xA000000000000006;
+use crate::types::{ScriptGroup, ScriptId, Since};
 
 #[derive(Error, Debug)]
 pub enum UnlockError {
@@ -32,12 +33,18 @@ pub enum UnlockError {
     #[error("invalid witness args: witness index=`{0}`")]
     InvalidWitnessArgs(usize),
 
+    #[error("witness lock is occupied by other data: witness index=`{0}`")]
+    WitnessLockOccupied(usize),
+
     #[error("there is an configuration error: `{0}`")]
     InvalidConfig(#[from] ConfigError),
 
     #[error("sign context is incorrect")]
     SignContextTypeIncorrect,
 
+    #[error("the unlocker does not accept the script args: `{script_id}`")]
+    UnlockerArgsMismatch { script_id: ScriptId },
+
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -87,6 +94,129 @@ pub trait ScriptUnlocker {
     ) -> Result<TransactionView, UnlockError>;
 }
 
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum RegistryError {
+    #[error("duplicated unlocker for script id: `{0}`")]
+    Duplicate(ScriptId),
+
+    #[error("duplicated fallback unlocker for code hash: `{0:#x}`")]
+    DuplicateCodeHash(H256),
+}
+
+/// Look up the unlocker of a lock script, used by `fill_placeholder_witnesses`,
+/// `unlock_tx` and the `TxBuilder` methods.
+pub trait UnlockerProvider {
+    /// The unlocker registered for exactly `script_id`
+    fn get(&self, script_id: &ScriptId) -> Option<&dyn ScriptUnlocker>;
+
+    /// Find an unlocker accepting `args` for the script, by default only the
+    /// unlocker registered for exactly `script_id` is checked.
+    fn find_by_args(&self, script_id: &ScriptId, args: &[u8]) -> Option<&dyn ScriptUnlocker> {
+        self.get(script_id)
+            .filter(|unlocker| unlocker.match_args(args))
+    }
+}
+
+impl UnlockerProvider for HashMap<ScriptId, Box<dyn ScriptUnlocker>> {
+    fn get(&self, script_id: &ScriptId) -> Option<&dyn ScriptUnlocker> {
+        HashMap::get(self, script_id).map(|unlocker| unlocker.as_ref())
+    }
+}
+
+/// The unlockers registered by `ScriptId`, plus fallback unlockers registered
+/// by code hash only.
+///
+/// When no unlocker registered for the exact `ScriptId` accepts the args, the
+/// fallback unlockers of the same code hash are tried by `match_args`, so one
+/// registration covers the script deployed with any hash type (e.g. both
+/// `data1` and `data2` of the omni-lock binary).
+#[derive(Default)]
+pub struct ScriptUnlockerManager {
+    unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>>,
+    fallbacks: Vec<(H256, Box<dyn ScriptUnlocker>)>,
+}
+
+impl ScriptUnlockerManager {
+    pub fn new() -> ScriptUnlockerManager {
+        ScriptUnlockerManager::default()
+    }
+
+    /// Register the unlocker of exactly `script_id`
+    pub fn register(
+        &mut self,
+        script_id: ScriptId,
+        unlocker: Box<dyn ScriptUnlocker>,
+    ) -> Result<(), RegistryError> {
+        if self.unlockers.contains_key(&script_id) {
+            return Err(RegistryError::Duplicate(script_id));
+        }
+        self.unlockers.insert(script_id, unlocker);
+        Ok(())
+    }
+
+    /// Register a fallback unlocker of `code_hash` regardless of the hash type
+    pub fn register_code_hash(
+        &mut self,
+        code_hash: H256,
+        unlocker: Box<dyn ScriptUnlocker>,
+    ) -> Result<(), RegistryError> {
+        if self.fallbacks.iter().any(|(hash, _)| hash == &code_hash) {
+            return Err(RegistryError::DuplicateCodeHash(code_hash));
+        }
+        self.fallbacks.push((code_hash, unlocker));
+        Ok(())
+    }
+}
+
+impl From<HashMap<ScriptId, Box<dyn ScriptUnlocker>>> for ScriptUnlockerManager {
+    fn from(unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>>) -> ScriptUnlockerManager {
+        ScriptUnlockerManager {
+            unlockers,
+            fallbacks: Vec::new(),
+        }
+    }
+}
+
+impl UnlockerProvider for ScriptUnlockerManager {
+    fn get(&self, script_id: &ScriptId) -> Option<&dyn ScriptUnlocker> {
+        self.unlockers
+            .get(script_id)
+            .map(|unlocker| unlocker.as_ref())
+    }
+
+    fn find_by_args(&self, script_id: &ScriptId, args: &[u8]) -> Option<&dyn ScriptUnlocker> {
+        self.get(script_id)
+            .filter(|unlocker| unlocker.match_args(args))
+            .or_else(|| {
+                self.fallbacks
+                    .iter()
+                    .filter(|(code_hash, _)| code_hash == &script_id.code_hash)
+                    .map(|(_, unlocker)| unlocker.as_ref())
+                    .find(|unlocker| unlocker.match_args(args))
+            })
+    }
+}
+
+/// Build the unlockers map, unlike `HashMap::insert` (which silently keeps the
+/// last one) two unlockers for the same `ScriptId` is an error.
+pub fn build_unlockers(
+    entries: Vec<(ScriptId, Box<dyn ScriptUnlocker>)>,
+) -> Result<HashMap<ScriptId, Box<dyn ScriptUnlocker>>, RegistryError> {
+    let mut unlockers = HashMap::with_capacity(entries.len());
+    for (script_id, unlocker) in entries {
+        if unlockers.contains_key(&script_id) {
+            return Err(RegistryError::Duplicate(script_id));
+        }
+        unlockers.insert(script_id, unlocker);
+    }
+    Ok(unlockers)
+}
+
+/// Put `lock_field` into the lock field of the first witness of the script group.
+///
+/// The existing witness is parsed as `WitnessArgs`, its `input_type` and
+/// `output_type` are kept as they are. An error is returned if the lock field
+/// is already occupied by data other than `lock_field`.
 pub fn fill_witness_lock(
     tx: &TransactionView,
     script_group: &ScriptGroup,
@@ -104,13 +234,19 @@ pub fn fill_witness_lock(
         WitnessArgs::from_slice(witness_data.as_ref())
             .map_err(|_| UnlockError::InvalidWitnessArgs(witness_idx))?
     };
-    if witness.lock().is_none() {
-        witness = witness.as_builder().lock(Some(lock_field).pack()).build();
+    match witness.lock().to_opt() {
+        None => {
+            witness = witness.as_builder().lock(Some(lock_field).pack()).build();
+        }
+        Some(lock) if lock.raw_data() == lock_field => {}
+        Some(_) => return Err(UnlockError::WitnessLockOccupied(witness_idx)),
     }
     witnesses[witness_idx] = witness.as_bytes().pack();
     Ok(tx.as_advanced_builder().set_witnesses(witnesses).build())
 }
 
+/// Blank the lock field of the witness, the `input_type` and `output_type`
+/// are kept. The witness becomes empty when there is no type field.
 pub fn reset_witness_lock(
     tx: TransactionView,
     witness_idx: usize,
@@ -360,12 +496,20 @@ fn acp_is_unlocked(
         let mut found_inputs = 0;
         for input_wallet in &mut input_wallets {
             if input_wallet.type_hash_opt == type_hash_opt {
-                let (min_output_ckb_amount, ckb_overflow) =
-                    input_wallet.ckb_amount.overflowing_add(min_ckb_amount);
-                let meet_ckb_cond = !ckb_overflow && ckb_amount >= min_output_ckb_amount;
-                let (min_output_udt_amount, udt_overflow) =
-                    input_wallet.udt_amount.overflowing_add(min_udt_amount);
-                let meet_udt_cond = !udt_overflow && udt_amount >= min_output_udt_amount;
+                // Same as the acp contract, when the minimal output amount
+                // overflows the condition is treated as not met.
+                let meet_ckb_cond = input_wallet
+                    .ckb_amount
+                    .checked_add(min_ckb_amount)
+                    .map_or(false, |min_output_ckb_amount| {
+                        ckb_amount >= min_output_ckb_amount
+                    });
+                let meet_udt_cond = input_wallet
+                    .udt_amount
+                    .checked_add(min_udt_amount)
+                    .map_or(false, |min_output_udt_amount| {
+                        udt_amount >= min_output_udt_amount
+                    });
                 if !(meet_ckb_cond || meet_udt_cond) {
                     // ERROR_OUTPUT_AMOUNT_NOT_ENOUGH
                     return Ok(false);
@@ -461,6 +605,75 @@ impl ScriptUnlocker for AcpUnlocker {
     }
 }
 
+/// Unlocker for the pw-lock, the witness is reset when the acp rules are met
+/// like `AcpUnlocker`, otherwise the ethereum key signs the transaction.
+pub struct PwLockUnlocker {
+    signer: PwLockScriptSigner,
+}
+
+impl PwLockUnlocker {
+    pub fn new(signer: PwLockScriptSigner) -> PwLockUnlocker {
+        PwLockUnlocker { signer }
+    }
+}
+impl From<Box<dyn Signer>> for PwLockUnlocker {
+    fn from(signer: Box<dyn Signer>) -> PwLockUnlocker {
+        PwLockUnlocker::new(PwLockScriptSigner::new(signer))
+    }
+}
+
+impl ScriptUnlocker for PwLockUnlocker {
+    fn match_args(&self, args: &[u8]) -> bool {
+        self.signer.match_args(args)
+    }
+
+    fn is_unlocked(
+        &self,
+        tx: &TransactionView,
+        script_group: &ScriptGroup,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<bool, UnlockError> {
+        let raw_data = script_group.script.args().raw_data();
+        let acp_args = raw_data.get(20..).unwrap_or(&[]);
+        acp_is_unlocked(tx, script_group, tx_dep_provider, acp_args)
+    }
+
+    fn unlock(
+        &self,
+        tx: &TransactionView,
+        script_group: &ScriptGroup,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, UnlockError> {
+        if self.is_unlocked(tx, script_group, tx_dep_provider)? {
+            self.clear_placeholder_witness(tx, script_group)
+        } else {
+            Ok(self.signer.sign_tx(tx, script_group)?)
+        }
+    }
+
+    fn clear_placeholder_witness(
+        &self,
+        tx: &TransactionView,
+        script_group: &ScriptGroup,
+    ) -> Result<TransactionView, UnlockError> {
+        reset_witness_lock(tx.clone(), script_group.input_indices[0])
+            .map_err(UnlockError::InvalidWitnessArgs)
+    }
+
+    fn fill_placeholder_witness(
+        &self,
+        tx: &TransactionView,
+        script_group: &ScriptGroup,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, UnlockError> {
+        if self.is_unlocked(tx, script_group, tx_dep_provider)? {
+            Ok(tx.clone())
+        } else {
+            fill_witness_lock(tx, script_group, Bytes::from(vec![0u8; 65]))
+        }
+    }
+}
+
 pub struct ChequeUnlocker {
     signer: ChequeScriptSigner,
 }
@@ -468,6 +681,21 @@ impl ChequeUnlocker {
     pub fn new(signer: ChequeScriptSigner) -> ChequeUnlocker {
         ChequeUnlocker { signer }
     }
+
+    /// Create a withdraw unlocker for a cheque script deployed with another
+    /// lock period, see `ChequeScriptSigner::new_with_withdraw_since`.
+    pub fn new_with_withdraw_since(
+        signer: Box<dyn Signer>,
+        since: Since,
+    ) -> Result<ChequeUnlocker, ScriptSignError> {
+        ChequeScriptSigner::new_with_withdraw_since(signer, since).map(ChequeUnlocker::new)
+    }
+
+    /// Create an unlocker choosing the action by the transaction, see
+    /// `ChequeScriptSigner::new_auto`.
+    pub fn new_auto(signer: Box<dyn Signer>) -> ChequeUnlocker {
+        ChequeUnlocker::new(ChequeScriptSigner::new_auto(signer))
+    }
 }
 impl From<(Box<dyn Signer>, ChequeAction)> for ChequeUnlocker {
     fn from((signer, action): (Box<dyn Signer>, ChequeAction)) -> ChequeUnlocker {
@@ -527,14 +755,15 @@ impl ScriptUnlocker for ChequeUnlocker {
             }
         }
         // NOTE: receiver has higher priority than sender
-        if self.signer.action() == ChequeAction::Claim {
+        if self.signer.action_for(tx, script_group)? == ChequeAction::Claim {
             if let Some((_input_idx, witness)) = receiver_lock_witness {
-                if group_since_list
+                if let Some(since) = group_since_list
                     .iter()
-                    .any(|since| *since != CHEQUE_CLAIM_SINCE)
+                    .find(|since| **since != CHEQUE_CLAIM_SINCE)
                 {
                     return Err(UnlockError::Other(anyhow!(
-                        "claim action must have all zero since in cheque inputs"
+                        "claim action requires zero since in all cheque inputs, got: {:#x}",
+                        since
                     )));
                 }
                 let witness_args = match WitnessArgs::from_slice(witness.as_ref()) {
@@ -549,12 +778,15 @@ impl ScriptUnlocker for ChequeUnlocker {
                 return Ok(true);
             }
         } else if let Some((_input_idx, witness)) = sender_lock_witness {
-            if group_since_list
+            let withdraw_since = self.signer.withdraw_since().value();
+            if let Some(since) = group_since_list
                 .iter()
-                .any(|since| *since != CHEQUE_WITHDRAW_SINCE)
+                .find(|since| **since != withdraw_since)
             {
                 return Err(UnlockError::Other(anyhow!(
-                    "withdraw action must have all relative 6 epochs since in cheque inputs"
+                    "withdraw action requires since {:#x} in all cheque inputs, got: {:#x}",
+                    withdraw_since,
+                    since
                 )));
             }
             let witness_args = match WitnessArgs::from_slice(witness.as_ref()) {
@@ -676,13 +908,8 @@ impl ScriptUnlocker for OmniLockUnlocker {
             self.config.id().auth_content()
         };
 
-        let inputs = tx.inputs();
-        if tx.inputs().len() < 2 {
-            return Err(UnlockError::Other(anyhow!(
-                "expect more than 1 input, got: {}",
-                inputs.len()
-            )));
-        }
+        // The owner lock input may not be added yet (e.g. before balancing),
+        // the script group is not unlocked until it is.
         let matched = tx
             .inputs()
             .into_iter()
@@ -697,11 +924,6 @@ impl ScriptUnlocker for OmniLockUnlocker {
                     false
                 }
             });
-        if !matched {
-            return Err(UnlockError::Other(anyhow!(
-                "can not find according owner lock input"
-            )));
-        }
         Ok(matched)
     }
 
@@ -709,11 +931,40 @@ impl ScriptUnlocker for OmniLockUnlocker {
         &self,
         tx: &TransactionView,
         script_group: &ScriptGroup,
-        _tx_dep_provider: &dyn TransactionDependencyProvider,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
     ) -> Result<TransactionView, UnlockError> {
+        if self.signer.config().is_ownerlock()
+            && self.signer.unlock_mode() == OmniUnlockMode::Normal
+        {
+            // There is no signature, only the owner lock input unlocks it
+            return if self.is_unlocked(tx, script_group, tx_dep_provider)? {
+                self.clear_placeholder_witness(tx, script_group)
+            } else {
+                Err(UnlockError::Other(anyhow!(
+                    "can not find according owner lock input"
+                )))
+            };
+        }
         Ok(self.signer.sign_tx(tx, script_group)?)
     }
 
+    /// The witness of an ownerlock omni-lock is not needed when the owner lock
+    /// input is present, unless the identity of the administrator is required.
+    fn clear_placeholder_witness(
+        &self,
+        tx: &TransactionView,
+        script_group: &ScriptGroup,
+    ) -> Result<TransactionView, UnlockError> {
+        if self.signer.config().is_ownerlock()
+            && !self.config.omni_lock_flags().contains(OmniLockFlags::ADMIN)
+        {
+            reset_witness_lock(tx.clone(), script_group.input_indices[0])
+                .map_err(UnlockError::InvalidWitnessArgs)
+        } else {
+            Ok(tx.clone())
+        }
+    }
+
     fn fill_placeholder_witness(
         &self,
         tx: &TransactionView,
@@ -721,7 +972,10 @@ impl ScriptUnlocker for OmniLockUnlocker {
         _tx_dep_provider: &dyn TransactionDependencyProvider,
     ) -> Result<TransactionView, UnlockError> {
         let config = self.signer.config();
-        let lock_field = config.placeholder_witness_lock(self.signer.unlock_mode())?;
+        let lock_field = config.placeholder_witness_lock_for_args(
+            self.signer.unlock_mode(),
+            &script_group.script.args().raw_data(),
+        )?;
         fill_witness_lock(tx, script_group, lock_field)
     }
 }
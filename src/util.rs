@@ -1,16 +1,26 @@
-use std::{convert::TryInto, ptr, sync::atomic};
+use std::{collections::BTreeMap, convert::TryInto, fmt, ptr, sync::atomic};
 
+use anyhow::anyhow;
 use ckb_dao_utils::extract_dao_data;
 use ckb_types::{
-    core::{Capacity, EpochNumber, EpochNumberWithFraction, HeaderView},
-    packed::CellOutput,
+    core::{
+        Capacity, DepType, EpochNumber, EpochNumberWithFraction, FeeRate, HeaderView,
+        ScriptHashType, TransactionView,
+    },
+    packed::{CellDep, CellOutput, OutPoint, OutPointVec, Script},
     prelude::*,
     H160, H256, U256,
 };
 use sha3::{Digest, Keccak256};
 
+use crate::constants::{MULTISIG_TYPE_HASH, SIGHASH_TYPE_HASH};
 use crate::rpc::CkbRpcClient;
-use crate::traits::LiveCell;
+use crate::traits::{LiveCell, TransactionDependencyError, TransactionDependencyProvider};
+use crate::tx_builder::TransactionFeeError;
+use crate::types::{
+    Address, AddressPayload, HumanCapacity, NetworkType, ScriptGroupType, ScriptHashTypeExt,
+    ScriptId, ScriptRegistry,
+};
 
 use secp256k1::ffi::CPtr;
 
@@ -78,11 +88,7 @@ pub fn get_max_mature_number(rpc_client: &CkbRpcClient) -> Result<u64, String> {
 }
 
 pub fn is_mature(info: &LiveCell, max_mature_number: u64) -> bool {
-    // Not cellbase cell
-    info.tx_index > 0
-    // Live cells in genesis are all mature
-        || info.block_number == 0
-        || info.block_number <= max_mature_number
+    info.is_mature(max_mature_number)
 }
 
 pub fn minimal_unlock_point(
@@ -125,6 +131,50 @@ pub fn calculate_dao_maximum_withdraw4(
     occupied_capacity + withdraw_counted_capacity as u64
 }
 
+/// The size of `tx` paid by the fee: the serialized size plus the 4 bytes
+/// offset of the transaction in the block.
+pub fn tx_size(tx: &TransactionView) -> u64 {
+    tx.data().as_reader().serialized_size_in_block() as u64
+}
+
+/// The fee of `size` bytes at `fee_rate` (shannons per KB), rounded up.
+pub fn calc_fee(size: u64, fee_rate: FeeRate) -> u64 {
+    let fee = (u128::from(size) * u128::from(fee_rate.as_u64()) + 999) / 1000;
+    fee.min(u128::from(u64::MAX)) as u64
+}
+
+/// The fee paid by `tx`, the inputs capacity minus the outputs capacity.
+///
+/// The DAO withdraw compensation of the inputs is not counted, see
+/// `tx_builder::tx_fee` for that.
+pub fn tx_fee(
+    tx: &TransactionView,
+    tx_dep_provider: &dyn TransactionDependencyProvider,
+) -> Result<u64, TransactionFeeError> {
+    let mut input_total = Capacity::zero();
+    for out_point in tx.input_pts_iter() {
+        let capacity: Capacity = tx_dep_provider.get_cell(&out_point)?.capacity().unpack();
+        input_total = input_total.safe_add(capacity)?;
+    }
+    let input_total = input_total.as_u64();
+    let output_total = tx.outputs_capacity()?.as_u64();
+    #[allow(clippy::unnecessary_lazy_evaluations)]
+    input_total
+        .checked_sub(output_total)
+        .ok_or_else(|| TransactionFeeError::CapacityOverflow(output_total - input_total))
+}
+
+/// The fee rate actually paid by `tx`, the fee of `tx_fee` per KB of `tx_size`
+/// (rounded down).
+pub fn effective_fee_rate(
+    tx: &TransactionView,
+    tx_dep_provider: &dyn TransactionDependencyProvider,
+) -> Result<FeeRate, TransactionFeeError> {
+    let fee = tx_fee(tx, tx_dep_provider)?;
+    let rate = u128::from(fee) * 1000 / u128::from(tx_size(tx));
+    Ok(FeeRate::from_u64(rate.min(u128::from(u64::MAX)) as u64))
+}
+
 pub fn serialize_signature(signature: &secp256k1::ecdsa::RecoverableSignature) -> [u8; 65] {
     let (recov_id, data) = signature.serialize_compact();
     let mut signature_bytes = [0u8; 65];
@@ -156,6 +206,292 @@ pub fn convert_keccak256_hash(message: &[u8]) -> H256 {
     H256::from_slice(r.as_slice()).expect("convert_keccak256_hash")
 }
 
+/// The cells in the dep group `cell_dep` as code cell deps, the dep group
+/// cell data is loaded by `tx_dep_provider`. A code cell dep is returned as
+/// is.
+pub fn expand_dep_group(
+    cell_dep: &CellDep,
+    tx_dep_provider: &dyn TransactionDependencyProvider,
+) -> Result<Vec<CellDep>, TransactionDependencyError> {
+    if cell_dep.dep_type() != DepType::DepGroup.into() {
+        return Ok(vec![cell_dep.clone()]);
+    }
+    let out_point = cell_dep.out_point();
+    let data = tx_dep_provider.get_cell_data(&out_point)?;
+    let out_points = OutPointVec::from_slice(&data).map_err(|err| {
+        TransactionDependencyError::Other(anyhow!("invalid dep group cell {}: {}", out_point, err))
+    })?;
+    Ok(out_points
+        .into_iter()
+        .map(|out_point| {
+            CellDep::new_builder()
+                .out_point(out_point)
+                .dep_type(DepType::Code.into())
+                .build()
+        })
+        .collect())
+}
+
+/// Check if `script` is a well known burn lock, nobody can unlock the cells it protects:
+///
+///   * the sighash (or multisig) lock with args of all zeros, the blake160 of no key
+///   * any lock with a zero code hash and args of all zeros, no cell has a zero
+///     data hash or type hash
+pub fn is_burn_lock(script: &Script) -> bool {
+    let args = script.args().raw_data();
+    if args.iter().any(|byte| *byte != 0) {
+        return false;
+    }
+    let script_id = ScriptId::from(script);
+    if script_id.code_hash == H256::default() {
+        return true;
+    }
+    args.len() == 20
+        && (script_id == ScriptId::new_type(SIGHASH_TYPE_HASH)
+            || script_id == ScriptId::new_type(MULTISIG_TYPE_HASH))
+}
+
+/// A resolved input cell or an output of `TxExplanation`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CellExplanation {
+    pub capacity: u64,
+    /// The address of the lock script, the script itself when its hash type
+    /// is invalid
+    pub lock: String,
+    pub type_hash: Option<H256>,
+    /// The name of the type script in the `ScriptRegistry`
+    pub type_name: Option<String>,
+    pub data_len: usize,
+}
+
+impl CellExplanation {
+    fn new(
+        output: &CellOutput,
+        data_len: usize,
+        network: NetworkType,
+        registry: &ScriptRegistry,
+    ) -> CellExplanation {
+        let lock = output.lock();
+        let lock = if ScriptHashType::from_packed(&lock.hash_type()).is_ok() {
+            Address::new(network, AddressPayload::from(lock), true).to_string()
+        } else {
+            format!("{}", lock)
+        };
+        let type_script = output.type_().to_opt();
+        CellExplanation {
+            capacity: output.capacity().unpack(),
+            lock,
+            type_hash: type_script
+                .as_ref()
+                .map(|script| script.calc_script_hash().unpack()),
+            type_name: type_script
+                .as_ref()
+                .and_then(|script| registry.lookup_by_script(script))
+                .map(|known| known.name),
+            data_len,
+        }
+    }
+}
+
+impl fmt::Display for CellExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "capacity: {:#}, lock: {}",
+            HumanCapacity(self.capacity),
+            self.lock
+        )?;
+        if let Some(type_hash) = &self.type_hash {
+            write!(
+                f,
+                ", type: {} ({:#x})",
+                self.type_name.as_deref().unwrap_or("unknown"),
+                type_hash
+            )?;
+        }
+        write!(f, ", data: {} bytes", self.data_len)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct InputExplanation {
+    pub out_point: OutPoint,
+    pub since: u64,
+    /// The resolved cell, or why it can not be resolved
+    pub cell: Result<CellExplanation, String>,
+}
+
+/// The witness of a script group: the witness at the first input of the
+/// group, or at the first output for a type script only in outputs.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct GroupWitnessExplanation {
+    pub script_hash: H256,
+    pub group_type: ScriptGroupType,
+    pub witness_index: usize,
+    /// `None` when the witness is missing
+    pub witness_len: Option<usize>,
+}
+
+/// A human readable view of a transaction, see `explain_tx`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TxExplanation {
+    pub hash: H256,
+    pub size: u64,
+    /// The fee of `tx_fee`, or why it can not be calculated
+    pub fee: Result<u64, String>,
+    pub inputs: Vec<InputExplanation>,
+    pub outputs: Vec<CellExplanation>,
+    /// Lock script groups first, then type script groups, each sorted by
+    /// script hash. The unresolved inputs are not in any group.
+    pub groups: Vec<GroupWitnessExplanation>,
+}
+
+impl TxExplanation {
+    /// The scripts are named by the registry of `network`, see
+    /// `ScriptRegistry::from_network`.
+    pub fn new<N: Into<NetworkType>>(
+        tx: &TransactionView,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
+        network: N,
+    ) -> TxExplanation {
+        let network = network.into();
+        let registry = ScriptRegistry::from_network(network).unwrap_or_default();
+        Self::new_with_registry(tx, tx_dep_provider, network, &registry)
+    }
+
+    pub fn new_with_registry(
+        tx: &TransactionView,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
+        network: NetworkType,
+        registry: &ScriptRegistry,
+    ) -> TxExplanation {
+        let mut lock_groups: BTreeMap<H256, usize> = BTreeMap::new();
+        let mut type_groups: BTreeMap<H256, usize> = BTreeMap::new();
+        let mut inputs = Vec::new();
+        for (index, input) in tx.inputs().into_iter().enumerate() {
+            let out_point = input.previous_output();
+            let cell = tx_dep_provider
+                .get_cell(&out_point)
+                .and_then(|output| {
+                    let data = tx_dep_provider.get_cell_data(&out_point)?;
+                    Ok((output, data))
+                })
+                .map(|(output, data)| {
+                    lock_groups
+                        .entry(output.lock().calc_script_hash().unpack())
+                        .or_insert(index);
+                    if let Some(type_script) = output.type_().to_opt() {
+                        type_groups
+                            .entry(type_script.calc_script_hash().unpack())
+                            .or_insert(index);
+                    }
+                    CellExplanation::new(&output, data.len(), network, registry)
+                })
+                .map_err(|err| err.to_string());
+            inputs.push(InputExplanation {
+                out_point,
+                since: input.since().unpack(),
+                cell,
+            });
+        }
+
+        let mut outputs = Vec::new();
+        for (index, output) in tx.outputs().into_iter().enumerate() {
+            if let Some(type_script) = output.type_().to_opt() {
+                type_groups
+                    .entry(type_script.calc_script_hash().unpack())
+                    .or_insert(index);
+            }
+            let data_len = tx
+                .outputs_data()
+                .get(index)
+                .map(|data| data.raw_data().len())
+                .unwrap_or(0);
+            outputs.push(CellExplanation::new(&output, data_len, network, registry));
+        }
+
+        let witnesses = tx.witnesses();
+        let mut groups = Vec::new();
+        for (group_type, type_groups) in [
+            (ScriptGroupType::Lock, lock_groups),
+            (ScriptGroupType::Type, type_groups),
+        ] {
+            for (script_hash, witness_index) in type_groups {
+                groups.push(GroupWitnessExplanation {
+                    script_hash,
+                    group_type,
+                    witness_index,
+                    witness_len: witnesses
+                        .get(witness_index)
+                        .map(|witness| witness.raw_data().len()),
+                });
+            }
+        }
+
+        TxExplanation {
+            hash: tx.hash().unpack(),
+            size: tx_size(tx),
+            fee: tx_fee(tx, tx_dep_provider).map_err(|err| err.to_string()),
+            inputs,
+            outputs,
+            groups,
+        }
+    }
+}
+
+impl fmt::Display for TxExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "transaction: {:#x}", self.hash)?;
+        writeln!(f, "size: {} bytes", self.size)?;
+        match &self.fee {
+            Ok(fee) => writeln!(f, "fee: {:#}", HumanCapacity(*fee))?,
+            Err(err) => writeln!(f, "fee: unknown, {}", err)?,
+        }
+        writeln!(f, "inputs:")?;
+        for (index, input) in self.inputs.iter().enumerate() {
+            let tx_hash: H256 = input.out_point.tx_hash().unpack();
+            let out_index: u32 = input.out_point.index().unpack();
+            write!(
+                f,
+                "  #{} {:#x}:{}, since: {:#x}, ",
+                index, tx_hash, out_index, input.since
+            )?;
+            match &input.cell {
+                Ok(cell) => writeln!(f, "{}", cell)?,
+                Err(err) => writeln!(f, "unresolved: {}", err)?,
+            }
+        }
+        writeln!(f, "outputs:")?;
+        for (index, output) in self.outputs.iter().enumerate() {
+            writeln!(f, "  #{} {}", index, output)?;
+        }
+        write!(f, "groups:")?;
+        for group in &self.groups {
+            let witness_len = group
+                .witness_len
+                .map(|len| format!("{} bytes", len))
+                .unwrap_or_else(|| "missing".to_string());
+            write!(
+                f,
+                "\n  {} {:#x}, witness #{}: {}",
+                group.group_type, group.script_hash, group.witness_index, witness_len
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Render `tx` for debugging: every input with its resolved cell, every
+/// output, the fee, the size and the witness length of every script group.
+/// An input `tx_dep_provider` can not resolve is shown as unresolved.
+pub fn explain_tx<N: Into<NetworkType>>(
+    tx: &TransactionView,
+    tx_dep_provider: &dyn TransactionDependencyProvider,
+    network: N,
+) -> String {
+    TxExplanation::new(tx, tx_dep_provider, network).to_string()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -381,4 +717,62 @@ mod tests {
             assert_eq!(151500, get_max_mature_number(&rpc_client).unwrap());
         }
     }
+
+    #[test]
+    fn test_tx_size_and_fee() {
+        use crate::constants::ONE_CKB;
+        use crate::test_util::{random_out_point, Context};
+        use ckb_types::{
+            core::TransactionBuilder,
+            packed::{CellDep, CellInput},
+        };
+
+        let out_point = random_out_point();
+        let mut ctx = Context::default();
+        ctx.add_simple_live_cell(
+            out_point.clone(),
+            Script::default(),
+            Some(capacity_bytes!(100).as_u64() + 1000),
+        );
+        let tx = TransactionBuilder::default()
+            .cell_dep(CellDep::default())
+            .input(CellInput::new(out_point, 0))
+            .output(
+                CellOutput::new_builder()
+                    .capacity(capacity_bytes!(100).pack())
+                    .build(),
+            )
+            .output_data(Bytes::new().pack())
+            .witness(Bytes::new().pack())
+            .build();
+
+        // 246 bytes of the transaction and the 4 bytes offset in the block
+        assert_eq!(tx.data().as_slice().len(), 246);
+        assert_eq!(tx_size(&tx), 250);
+
+        assert_eq!(calc_fee(250, FeeRate::from_u64(1000)), 250);
+        // rounded up, `FeeRate::fee` rounds down
+        assert_eq!(calc_fee(250, FeeRate::from_u64(1001)), 251);
+        assert_eq!(FeeRate::from_u64(1001).fee(250).as_u64(), 250);
+        assert_eq!(calc_fee(1, FeeRate::from_u64(1)), 1);
+        assert_eq!(calc_fee(0, FeeRate::from_u64(1000)), 0);
+        assert_eq!(calc_fee(u64::MAX, FeeRate::from_u64(u64::MAX)), u64::MAX);
+
+        assert_eq!(tx_fee(&tx, &ctx).unwrap(), 1000);
+        assert_eq!(
+            effective_fee_rate(&tx, &ctx).unwrap(),
+            FeeRate::from_u64(4000)
+        );
+
+        let tx = tx
+            .as_advanced_builder()
+            .set_outputs(vec![CellOutput::new_builder()
+                .capacity(capacity_bytes!(101).pack())
+                .build()])
+            .build();
+        assert!(matches!(
+            tx_fee(&tx, &ctx),
+            Err(TransactionFeeError::CapacityOverflow(delta)) if delta == ONE_CKB - 1000
+        ));
+    }
 }